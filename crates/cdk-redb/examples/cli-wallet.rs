@@ -0,0 +1,402 @@
+//! Command line wallet on a redb database
+//!
+//! ```sh
+//! cargo run -p cdk-redb --example cli-wallet -- --mint http://127.0.0.1:3338 mint 100
+//! cargo run -p cdk-redb --example cli-wallet -- send 10
+//! ```
+//!
+//! Proofs and keyset counters are kept in the database file. Secrets are
+//! derived from `--mnemonic` when one is given, so the wallet can later be
+//! restored from it, and are random otherwise.
+
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use cdk::nuts::CurrencyUnit;
+use cdk::url::UncheckedUrl;
+use cdk::wallet::{ReceiveOptions, Wallet};
+use cdk::{Amount, HttpClient, Mnemonic};
+use cdk_redb::RedbWalletDatabase;
+use thiserror::Error;
+
+const USAGE: &str = "Usage: cli-wallet [OPTIONS] <COMMAND>
+
+Commands:
+  balance              Balance of every mint
+  mint <AMOUNT>        Request a quote, print its invoice and mint once paid
+  send <AMOUNT>        Print a token for AMOUNT
+  receive <TOKEN>      Swap the proofs of TOKEN into the wallet
+  melt <BOLT11>        Pay BOLT11 with the wallet proofs
+  restore <MNEMONIC>   Restore the proofs of MNEMONIC from the mint
+
+Options:
+  --db <PATH>          redb database file [env: WALLET_DB] [default: wallet.redb]
+  --mint <URL>         Mint to use [env: WALLET_MINT_URL] [default: http://127.0.0.1:3338]
+  --mnemonic <WORDS>   Seed of the wallet secrets [env: WALLET_MNEMONIC]
+  --help               Print this message";
+
+/// How long `mint` waits for its invoice to be paid
+const MINT_TIMEOUT: Duration = Duration::from_secs(600);
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Error, PartialEq, Eq)]
+enum ArgsError {
+    #[error("Help requested")]
+    Help,
+    #[error("Unknown argument `{0}`")]
+    UnknownArgument(String),
+    #[error("Missing value for `{0}`")]
+    MissingValue(String),
+    #[error("Invalid value `{value}` for `{name}`")]
+    InvalidValue { name: &'static str, value: String },
+    #[error("Missing command")]
+    MissingCommand,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Command {
+    Balance,
+    Mint(Amount),
+    Send(Amount),
+    Receive(String),
+    Melt(String),
+    Restore(Mnemonic),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Args {
+    db_path: String,
+    mint_url: UncheckedUrl,
+    mnemonic: Option<Mnemonic>,
+    command: Command,
+}
+
+impl Args {
+    /// Args from the command line `args`, without the program name, falling
+    /// back to the variables of `env`
+    fn parse<A, E>(args: A, env: E) -> Result<Self, ArgsError>
+    where
+        A: IntoIterator<Item = String>,
+        E: Fn(&str) -> Option<String>,
+    {
+        let mut db_path = env("WALLET_DB");
+        let mut mint_url = env("WALLET_MINT_URL");
+        let mut mnemonic = env("WALLET_MNEMONIC");
+        let mut positional = Vec::new();
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let slot = match arg.as_str() {
+                "--db" => &mut db_path,
+                "--mint" => &mut mint_url,
+                "--mnemonic" => &mut mnemonic,
+                "--help" | "-h" => return Err(ArgsError::Help),
+                flag if flag.starts_with("--") => return Err(ArgsError::UnknownArgument(arg)),
+                _ => {
+                    positional.push(arg);
+                    continue;
+                }
+            };
+            *slot = Some(args.next().ok_or(ArgsError::MissingValue(arg))?);
+        }
+
+        let mut positional = positional.into_iter();
+        let name = positional.next().ok_or(ArgsError::MissingCommand)?;
+        let mut value = |name: &'static str| {
+            positional
+                .next()
+                .ok_or_else(|| ArgsError::MissingValue(name.to_string()))
+        };
+        let command = match name.as_str() {
+            "balance" => Command::Balance,
+            "mint" => Command::Mint(parse_amount(value("amount")?)?),
+            "send" => Command::Send(parse_amount(value("amount")?)?),
+            "receive" => Command::Receive(value("token")?),
+            "melt" => Command::Melt(value("bolt11")?),
+            "restore" => Command::Restore(parse_value("mnemonic", value("mnemonic")?)?),
+            _ => return Err(ArgsError::UnknownArgument(name)),
+        };
+        if let Some(extra) = positional.next() {
+            return Err(ArgsError::UnknownArgument(extra));
+        }
+
+        Ok(Self {
+            db_path: db_path.unwrap_or_else(|| "wallet.redb".to_string()),
+            mint_url: parse_value(
+                "mint",
+                mint_url.unwrap_or_else(|| "http://127.0.0.1:3338".to_string()),
+            )?,
+            mnemonic: mnemonic
+                .map(|mnemonic| parse_value("mnemonic", mnemonic))
+                .transpose()?,
+            command,
+        })
+    }
+}
+
+fn parse_value<T>(name: &'static str, value: String) -> Result<T, ArgsError>
+where
+    T: FromStr,
+{
+    value
+        .parse()
+        .map_err(|_| ArgsError::InvalidValue { name, value })
+}
+
+fn parse_amount(value: String) -> Result<Amount, ArgsError> {
+    parse_value::<u64>("amount", value).map(Amount::from)
+}
+
+async fn open_wallet(localstore: Arc<RedbWalletDatabase>, mnemonic: Option<Mnemonic>) -> Wallet {
+    Wallet::new(HttpClient::new(), localstore, mnemonic).await
+}
+
+async fn run(args: Args) -> Result<(), Box<dyn std::error::Error>> {
+    let localstore = Arc::new(RedbWalletDatabase::new(&args.db_path)?);
+    let mut wallet = open_wallet(Arc::clone(&localstore), args.mnemonic).await;
+    let mint_url = args.mint_url;
+
+    match args.command {
+        Command::Balance => {
+            for (mint_url, balance) in wallet.mint_balances().await? {
+                println!("{mint_url}: {balance} sat");
+            }
+        }
+        Command::Mint(amount) => {
+            let quote = wallet
+                .mint_quote(mint_url.clone(), amount, CurrencyUnit::Sat, None)
+                .await?;
+            println!("Pay this invoice to mint {amount} sat:\n{}", quote.request);
+
+            let started = Instant::now();
+            while !wallet.mint_quote_paid(mint_url.clone(), &quote.id).await? {
+                if started.elapsed() > MINT_TIMEOUT {
+                    return Err("Invoice was not paid in time".into());
+                }
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+
+            let minted = wallet.mint(mint_url, &quote.id).await?;
+            println!("Minted {minted} sat");
+        }
+        Command::Send(amount) => {
+            let proofs = wallet
+                .send(&mint_url, &CurrencyUnit::Sat, amount, true)
+                .await?;
+            let token = wallet.proofs_to_token(mint_url, proofs, None, Some(CurrencyUnit::Sat))?;
+            println!("{token}");
+        }
+        Command::Receive(token) => {
+            wallet.receive(&token, ReceiveOptions::default()).await?;
+            println!("Received");
+        }
+        Command::Melt(bolt11) => {
+            let quote = wallet
+                .melt_quote(mint_url.clone(), CurrencyUnit::Sat, bolt11, None)
+                .await?;
+            println!(
+                "Paying {} sat with a fee reserve of {} sat",
+                quote.amount, quote.fee_reserve
+            );
+
+            let melted = wallet.melt(&mint_url, &quote.id).await?;
+            match (melted.paid, melted.preimage) {
+                (true, Some(preimage)) => println!("Paid, preimage {preimage}"),
+                (true, None) => println!("Paid"),
+                (false, _) => return Err("Mint did not pay the invoice".into()),
+            }
+        }
+        Command::Restore(mnemonic) => {
+            let mut wallet = open_wallet(localstore, Some(mnemonic)).await;
+            let restored = wallet.restore(mint_url).await?;
+            println!("Restored {restored} sat");
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    let args = match Args::parse(std::env::args().skip(1), |name| std::env::var(name).ok()) {
+        Ok(args) => args,
+        Err(ArgsError::Help) => {
+            println!("{USAGE}");
+            return;
+        }
+        Err(err) => {
+            eprintln!("{err}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(err) = run(args).await {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use cdk::cdk_database::WalletDatabase;
+    use cdk::nuts::{Id, Proof, PublicKey};
+    use cdk::secret::Secret;
+
+    use super::*;
+
+    const MNEMONIC: &str =
+        "half depart obvious quality work element tank gorilla view sugar picture humble";
+
+    fn parse(args: &[&str], env: &[(&str, &str)]) -> Result<Args, ArgsError> {
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+
+        Args::parse(args.iter().map(|arg| arg.to_string()), |name| {
+            env.get(name).cloned()
+        })
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        let args = parse(&["balance"], &[]).unwrap();
+        assert_eq!(args.command, Command::Balance);
+        assert_eq!(args.db_path, "wallet.redb");
+        assert_eq!(
+            args.mint_url,
+            UncheckedUrl::from_str("http://127.0.0.1:3338").unwrap()
+        );
+        assert_eq!(args.mnemonic, None);
+
+        assert_eq!(
+            parse(&["mint", "100"], &[]).unwrap().command,
+            Command::Mint(Amount::from(100))
+        );
+        assert_eq!(
+            parse(&["send", "10"], &[]).unwrap().command,
+            Command::Send(Amount::from(10))
+        );
+        assert_eq!(
+            parse(&["receive", "cashuA..."], &[]).unwrap().command,
+            Command::Receive("cashuA...".to_string())
+        );
+        assert_eq!(
+            parse(&["melt", "lnbc1..."], &[]).unwrap().command,
+            Command::Melt("lnbc1...".to_string())
+        );
+        assert_eq!(
+            parse(&["restore", MNEMONIC], &[]).unwrap().command,
+            Command::Restore(Mnemonic::from_str(MNEMONIC).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let args = parse(
+            &[
+                "--db",
+                "/tmp/wallet.redb",
+                "balance",
+                "--mint",
+                "https://mint.example",
+            ],
+            &[
+                ("WALLET_MINT_URL", "http://localhost:3338"),
+                ("WALLET_MNEMONIC", MNEMONIC),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(args.db_path, "/tmp/wallet.redb");
+        assert_eq!(
+            args.mint_url,
+            UncheckedUrl::from_str("https://mint.example").unwrap()
+        );
+        assert_eq!(args.mnemonic, Some(Mnemonic::from_str(MNEMONIC).unwrap()));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse(&[], &[]), Err(ArgsError::MissingCommand));
+        assert_eq!(parse(&["--help"], &[]), Err(ArgsError::Help));
+        assert_eq!(
+            parse(&["mint"], &[]),
+            Err(ArgsError::MissingValue("amount".to_string()))
+        );
+        assert_eq!(
+            parse(&["send", "ten"], &[]),
+            Err(ArgsError::InvalidValue {
+                name: "amount",
+                value: "ten".to_string()
+            })
+        );
+        assert_eq!(
+            parse(&["swap"], &[]),
+            Err(ArgsError::UnknownArgument("swap".to_string()))
+        );
+        assert_eq!(
+            parse(&["balance", "--verbose"], &[]),
+            Err(ArgsError::UnknownArgument("--verbose".to_string()))
+        );
+        assert_eq!(
+            parse(&["balance", "extra"], &[]),
+            Err(ArgsError::UnknownArgument("extra".to_string()))
+        );
+        assert_eq!(
+            parse(&["--db"], &[]),
+            Err(ArgsError::MissingValue("--db".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_file_store_round_trip() {
+        let path = std::env::temp_dir().join(format!(
+            "cli-wallet-{}.redb",
+            cdk::util::hex::encode(cdk::util::random_hash())
+        ));
+        let path = path.to_str().unwrap();
+        let mint_url = UncheckedUrl::from_str("http://127.0.0.1:3338").unwrap();
+        let keyset_id = Id::from_str("009a1f293253e41e").unwrap();
+        let proof = Proof::new(
+            Amount::from(8),
+            keyset_id,
+            Secret::generate(),
+            PublicKey::from_str(
+                "02194603ffa36356f4a56b7df9371fc3192472351453ec7398b8da8117e7c3e104",
+            )
+            .unwrap(),
+        );
+
+        {
+            let localstore = RedbWalletDatabase::new(path).unwrap();
+            localstore.add_mint(mint_url.clone(), None).await.unwrap();
+            localstore
+                .add_proofs(mint_url.clone(), vec![proof.clone()])
+                .await
+                .unwrap();
+            localstore.set_keyset_counter(&keyset_id, 7).await.unwrap();
+        }
+
+        let localstore = Arc::new(RedbWalletDatabase::new(path).unwrap());
+        assert_eq!(
+            localstore.get_proofs(mint_url.clone()).await.unwrap(),
+            Some(vec![proof])
+        );
+        assert_eq!(
+            localstore.get_keyset_counter(&keyset_id).await.unwrap(),
+            Some(7)
+        );
+
+        let wallet = open_wallet(localstore, None).await;
+        assert_eq!(
+            wallet.mint_balances().await.unwrap().get(&mint_url),
+            Some(&Amount::from(8))
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
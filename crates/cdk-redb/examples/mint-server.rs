@@ -0,0 +1,576 @@
+//! Mint server on a redb database
+//!
+//! ```sh
+//! MINT_MNEMONIC="<12 words>" cargo run -p cdk-redb --example mint-server -- --listen 127.0.0.1:3338
+//! ```
+//!
+//! Every flag can also be set from the environment, see [USAGE]. Lines read
+//! from stdin control the running mint:
+//!
+//! - `rotate` rotates the sat keyset
+//! - `shutdown` stops accepting connections and exits once the requests in
+//!   flight, melts included, are answered
+//!
+//! With the `fake` Lightning backend mint quotes are paid as soon as they are
+//! created and every melt is paid without fee.
+
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use cdk::cdk_database::MintDatabase;
+use cdk::lightning::{self, LnBackend, Payment};
+use cdk::lightning_invoice::{Currency, InvoiceBuilder, PaymentSecret};
+use cdk::mint::{derivation_path_from_unit, Mint};
+use cdk::nuts::{CurrencyUnit, MintInfo};
+use cdk::router::MintRouter;
+use cdk::secp256k1::hashes::Hash;
+use cdk::secp256k1::{Secp256k1, SecretKey};
+use cdk::types::InvoiceDescription;
+use cdk::util::{hex, random_hash, unix_time};
+use cdk::{Amount, Bolt11Invoice, Mnemonic, Sha256};
+use cdk_redb::{KeysetKey, MintRedbDatabase};
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinSet;
+use tracing::{error, info, warn, Level};
+
+const USAGE: &str = "Usage: mint-server [OPTIONS]
+
+Options:
+  --listen <ADDR>        Address to listen on [env: MINT_LISTEN] [default: 127.0.0.1:3338]
+  --db <PATH>            redb database file [env: MINT_DB] [default: mint.redb]
+  --mnemonic <WORDS>     Seed of the mint keys [env: MINT_MNEMONIC]
+  --keyset-passphrase <PASSPHRASE>
+                         Encrypt keyset private keys in the database [env: MINT_KEYSET_PASSPHRASE]
+  --ln <BACKEND>         Lightning backend, only `fake` [env: MINT_LN] [default: fake]
+  --quote-ttl <SECS>     Seconds until quotes expire [env: MINT_QUOTE_TTL] [default: 3600]
+  --log-level <LEVEL>    trace, debug, info, warn or error [env: MINT_LOG] [default: info]
+  --help                 Print this message";
+
+/// Max order of the keysets generated on rotation
+const MAX_ORDER: u8 = 64;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+enum ConfigError {
+    #[error("Help requested")]
+    Help,
+    #[error("Unknown argument `{0}`")]
+    UnknownArgument(String),
+    #[error("Missing value for `{0}`")]
+    MissingValue(String),
+    #[error("Invalid value `{value}` for `{name}`")]
+    InvalidValue { name: &'static str, value: String },
+    #[error("Mnemonic required, set --mnemonic or MINT_MNEMONIC")]
+    MissingMnemonic,
+    #[error("Lightning backend `{0}` is not supported, use `fake`")]
+    UnsupportedBackend(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LnKind {
+    Fake,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Config {
+    listen: SocketAddr,
+    db_path: String,
+    mnemonic: Mnemonic,
+    keyset_passphrase: Option<String>,
+    ln: LnKind,
+    quote_ttl: u64,
+    log_level: Level,
+}
+
+impl Config {
+    /// Config from command line `args`, without the program name, falling back
+    /// to the variables of `env`
+    fn parse<A, E>(args: A, env: E) -> Result<Self, ConfigError>
+    where
+        A: IntoIterator<Item = String>,
+        E: Fn(&str) -> Option<String>,
+    {
+        let mut listen = env("MINT_LISTEN");
+        let mut db_path = env("MINT_DB");
+        let mut mnemonic = env("MINT_MNEMONIC");
+        let mut keyset_passphrase = env("MINT_KEYSET_PASSPHRASE");
+        let mut ln = env("MINT_LN");
+        let mut quote_ttl = env("MINT_QUOTE_TTL");
+        let mut log_level = env("MINT_LOG");
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let slot = match arg.as_str() {
+                "--listen" => &mut listen,
+                "--db" => &mut db_path,
+                "--mnemonic" => &mut mnemonic,
+                "--keyset-passphrase" => &mut keyset_passphrase,
+                "--ln" => &mut ln,
+                "--quote-ttl" => &mut quote_ttl,
+                "--log-level" => &mut log_level,
+                "--help" | "-h" => return Err(ConfigError::Help),
+                _ => return Err(ConfigError::UnknownArgument(arg)),
+            };
+            *slot = Some(args.next().ok_or(ConfigError::MissingValue(arg))?);
+        }
+
+        let mnemonic = mnemonic.ok_or(ConfigError::MissingMnemonic)?;
+        let ln = match ln.as_deref().unwrap_or("fake") {
+            "fake" => LnKind::Fake,
+            other => return Err(ConfigError::UnsupportedBackend(other.to_string())),
+        };
+
+        Ok(Self {
+            listen: parse_value("listen", listen, "127.0.0.1:3338")?,
+            db_path: db_path.unwrap_or_else(|| "mint.redb".to_string()),
+            mnemonic: parse_value("mnemonic", Some(mnemonic), "")?,
+            keyset_passphrase,
+            ln,
+            quote_ttl: parse_value("quote-ttl", quote_ttl, "3600")?,
+            log_level: parse_value("log-level", log_level, "info")?,
+        })
+    }
+}
+
+fn parse_value<T>(
+    name: &'static str,
+    value: Option<String>,
+    default: &str,
+) -> Result<T, ConfigError>
+where
+    T: FromStr,
+{
+    let value = value.unwrap_or_else(|| default.to_string());
+
+    value
+        .parse()
+        .map_err(|_| ConfigError::InvalidValue { name, value })
+}
+
+/// Lightning stand-in paying every invoice at no fee
+struct FakeLnBackend {
+    secret_key: SecretKey,
+}
+
+impl FakeLnBackend {
+    fn new() -> Self {
+        Self {
+            secret_key: SecretKey::from_slice(&[42; 32]).expect("valid secret key"),
+        }
+    }
+}
+
+#[async_trait]
+impl LnBackend for FakeLnBackend {
+    async fn create_invoice(
+        &self,
+        amount: Amount,
+        _unit: &CurrencyUnit,
+        description: Option<InvoiceDescription>,
+        expiry: u64,
+    ) -> Result<Bolt11Invoice, lightning::Error> {
+        let preimage = random_hash();
+
+        let builder = InvoiceBuilder::new(Currency::Regtest);
+        let builder = match description {
+            Some(InvoiceDescription::Hash(hash)) => {
+                builder.description_hash(Sha256::from_byte_array(hash))
+            }
+            Some(InvoiceDescription::Text(description)) => builder.description(description),
+            None => builder.description(String::new()),
+        };
+        builder
+            .payment_hash(Sha256::hash(&preimage))
+            .payment_secret(PaymentSecret([0; 32]))
+            .current_timestamp()
+            .expiry_time(Duration::from_secs(expiry.saturating_sub(unix_time())))
+            .min_final_cltv_expiry_delta(144)
+            .amount_milli_satoshis(amount.to_msat())
+            .build_signed(|hash| Secp256k1::new().sign_ecdsa_recoverable(hash, &self.secret_key))
+            .map_err(|err| lightning::Error::Backend(err.to_string().into()))
+    }
+
+    fn supports_description_hash(&self) -> bool {
+        true
+    }
+
+    async fn pay_invoice(
+        &self,
+        _bolt11: &Bolt11Invoice,
+        amount_msat: u64,
+        _max_fee: Amount,
+    ) -> Result<Payment, lightning::Error> {
+        Ok(Payment {
+            preimage: hex::encode(random_hash()),
+            total_spent_msat: amount_msat,
+        })
+    }
+}
+
+/// Prints events as `LEVEL target: message key=value` lines on stderr
+struct LineSubscriber {
+    max_level: Level,
+}
+
+struct FieldWriter(String);
+
+impl tracing::field::Visit for FieldWriter {
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        match field.name() {
+            "message" => self.0.push_str(&format!(" {value:?}")),
+            name => self.0.push_str(&format!(" {name}={value:?}")),
+        }
+    }
+}
+
+impl tracing::Subscriber for LineSubscriber {
+    fn enabled(&self, metadata: &tracing::Metadata<'_>) -> bool {
+        *metadata.level() <= self.max_level
+    }
+
+    fn new_span(&self, _span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+        tracing::span::Id::from_u64(1)
+    }
+
+    fn record(&self, _span: &tracing::span::Id, _values: &tracing::span::Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+
+    fn event(&self, event: &tracing::Event<'_>) {
+        let metadata = event.metadata();
+        let mut line = FieldWriter(format!("{} {}:", metadata.level(), metadata.target()));
+        event.record(&mut line);
+        eprintln!("{}", line.0);
+    }
+
+    fn enter(&self, _span: &tracing::span::Id) {}
+
+    fn exit(&self, _span: &tracing::span::Id) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Rotate,
+    Shutdown,
+}
+
+/// Commands read from stdin, on a thread as tokio has no stdin here
+fn commands() -> mpsc::UnboundedReceiver<Command> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lines() {
+            let Ok(line) = line else { break };
+            let command = match line.trim() {
+                "rotate" => Command::Rotate,
+                "shutdown" => Command::Shutdown,
+                "" => continue,
+                other => {
+                    warn!(command = other, "Unknown command");
+                    continue;
+                }
+            };
+            if sender.send(command).is_err() {
+                break;
+            }
+        }
+    });
+
+    receiver
+}
+
+/// Rotate the sat keyset to the next derivation index
+async fn rotate(mint: &Mutex<Mint>) -> Result<(), cdk::mint::Error> {
+    let mut mint = mint.lock().await;
+
+    let index = mint
+        .keysets_info()
+        .await?
+        .iter()
+        .filter(|keyset| keyset.unit == CurrencyUnit::Sat)
+        .count();
+    let derivation_path = derivation_path_from_unit(&CurrencyUnit::Sat, index as u32)?;
+    mint.rotate_keyset(CurrencyUnit::Sat, &derivation_path, MAX_ORDER)
+        .await?;
+
+    info!(index, "Rotated sat keyset");
+
+    Ok(())
+}
+
+/// Mark mint quotes paid as soon as they exist, standing in for the invoice
+/// subscription of a real node
+async fn pay_fake_invoices(mint: Arc<Mutex<Mint>>) {
+    let mut interval = tokio::time::interval(Duration::from_millis(500));
+
+    loop {
+        interval.tick().await;
+
+        let mint = mint.lock().await;
+        let Ok(quotes) = mint.mint_quotes().await else {
+            continue;
+        };
+        for quote in quotes.iter().filter(|quote| !quote.paid) {
+            if let Err(err) = mint.pay_mint_quote(&quote.id).await {
+                warn!(quote = %quote.id, "Could not pay fake invoice: {err}");
+            }
+        }
+    }
+}
+
+/// Answer one HTTP/1.1 request on `stream` and close it
+async fn serve_connection(router: MintRouter, stream: TcpStream) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(uri)) = (parts.next(), parts.next()) else {
+        return Ok(());
+    };
+    let (method, uri) = (method.to_string(), uri.to_string());
+
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 || line == "\r\n" {
+            break;
+        }
+        if let Some(length) = line.to_lowercase().strip_prefix("content-length:") {
+            content_length = length.trim().parse().unwrap_or(0);
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).await?;
+
+    let Ok(request) = http::Request::builder()
+        .method(method.as_str())
+        .uri(uri)
+        .body(body)
+    else {
+        return Ok(());
+    };
+    let response = router.handle(request).await;
+
+    let mut stream = reader.into_inner();
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status(),
+        response.body().len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(response.body()).await?;
+    stream.shutdown().await
+}
+
+#[tokio::main]
+async fn main() {
+    let config = match Config::parse(std::env::args().skip(1), |name| std::env::var(name).ok()) {
+        Ok(config) => config,
+        Err(ConfigError::Help) => {
+            println!("{USAGE}");
+            return;
+        }
+        Err(err) => {
+            eprintln!("{err}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(err) = run(config).await {
+        error!("Mint server failed: {err}");
+        std::process::exit(1);
+    }
+}
+
+async fn run(config: Config) -> Result<(), Box<dyn std::error::Error>> {
+    tracing::subscriber::set_global_default(LineSubscriber {
+        max_level: config.log_level,
+    })?;
+
+    let localstore = match &config.keyset_passphrase {
+        Some(passphrase) => MintRedbDatabase::with_keyset_key(
+            &config.db_path,
+            KeysetKey::Passphrase(passphrase.clone()),
+        )?,
+        None => MintRedbDatabase::new(&config.db_path)?,
+    };
+    if localstore.get_mint_info().await.is_err() {
+        localstore
+            .set_mint_info(&MintInfo {
+                name: Some("cdk example mint".to_string()),
+                ..Default::default()
+            })
+            .await?;
+    }
+    let mut mint = Mint::new(
+        Arc::new(localstore),
+        config.mnemonic.clone(),
+        HashSet::new(),
+        Amount::from(1),
+        0.01,
+    )
+    .await?;
+
+    match config.ln {
+        LnKind::Fake => mint.set_ln_backend(Arc::new(FakeLnBackend::new())),
+    }
+
+    let mint = Arc::new(Mutex::new(mint));
+    let mut router = MintRouter::new(Arc::clone(&mint));
+    router.set_quote_ttl(config.quote_ttl);
+
+    let fake_payer = match config.ln {
+        LnKind::Fake => tokio::spawn(pay_fake_invoices(Arc::clone(&mint))),
+    };
+
+    let listener = TcpListener::bind(config.listen).await?;
+    info!(listen = %config.listen, db = %config.db_path, "Mint listening");
+
+    let mut commands = commands();
+    let mut connections = JoinSet::new();
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, peer)) => {
+                    let router = router.clone();
+                    connections.spawn(async move {
+                        if let Err(err) = serve_connection(router, stream).await {
+                            warn!(%peer, "Connection failed: {err}");
+                        }
+                    });
+                }
+                Err(err) => warn!("Accept failed: {err}"),
+            },
+            command = commands.recv() => match command {
+                Some(Command::Rotate) => {
+                    if let Err(err) = rotate(&mint).await {
+                        error!("Keyset rotation failed: {err}");
+                    }
+                }
+                Some(Command::Shutdown) => break,
+                // stdin closed, keep serving
+                None => commands = mpsc::unbounded_channel().1,
+            },
+            Some(_) = connections.join_next(), if !connections.is_empty() => {}
+        }
+    }
+
+    // No new connections from here, requests in flight are answered
+    drop(listener);
+    info!(in_flight = connections.len(), "Shutting down");
+    while connections.join_next().await.is_some() {}
+    fake_payer.abort();
+
+    let pending = mint.lock().await.pending_melts().await?;
+    if !pending.is_empty() {
+        warn!(
+            pending = pending.len(),
+            "Melts left pending, resolve them after restart"
+        );
+    }
+    info!("Mint stopped");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    const MNEMONIC: &str =
+        "half depart obvious quality work element tank gorilla view sugar picture humble";
+
+    fn parse(args: &[&str], env: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+
+        Config::parse(args.iter().map(|arg| arg.to_string()), |name| {
+            env.get(name).cloned()
+        })
+    }
+
+    #[test]
+    fn test_config_defaults() {
+        let config = parse(&[], &[("MINT_MNEMONIC", MNEMONIC)]).unwrap();
+
+        assert_eq!(config.listen, "127.0.0.1:3338".parse().unwrap());
+        assert_eq!(config.db_path, "mint.redb");
+        assert_eq!(config.mnemonic, Mnemonic::from_str(MNEMONIC).unwrap());
+        assert_eq!(config.keyset_passphrase, None);
+        assert_eq!(config.ln, LnKind::Fake);
+        assert_eq!(config.quote_ttl, 3600);
+        assert_eq!(config.log_level, Level::INFO);
+    }
+
+    #[test]
+    fn test_config_flags_override_env() {
+        let config = parse(
+            &[
+                "--listen",
+                "0.0.0.0:8080",
+                "--quote-ttl",
+                "60",
+                "--log-level",
+                "debug",
+            ],
+            &[
+                ("MINT_MNEMONIC", MNEMONIC),
+                ("MINT_LISTEN", "127.0.0.1:9000"),
+                ("MINT_DB", "/tmp/mint.redb"),
+                ("MINT_QUOTE_TTL", "120"),
+                ("MINT_KEYSET_PASSPHRASE", "hunter2"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(config.listen, "0.0.0.0:8080".parse().unwrap());
+        assert_eq!(config.db_path, "/tmp/mint.redb");
+        assert_eq!(config.keyset_passphrase.as_deref(), Some("hunter2"));
+        assert_eq!(config.quote_ttl, 60);
+        assert_eq!(config.log_level, Level::DEBUG);
+    }
+
+    #[test]
+    fn test_config_errors() {
+        assert_eq!(parse(&[], &[]), Err(ConfigError::MissingMnemonic));
+        assert_eq!(
+            parse(&["--mnemonic", MNEMONIC, "--verbose"], &[]),
+            Err(ConfigError::UnknownArgument("--verbose".to_string()))
+        );
+        assert_eq!(
+            parse(&["--mnemonic"], &[]),
+            Err(ConfigError::MissingValue("--mnemonic".to_string()))
+        );
+        assert_eq!(
+            parse(&["--quote-ttl", "soon"], &[("MINT_MNEMONIC", MNEMONIC)]),
+            Err(ConfigError::InvalidValue {
+                name: "quote-ttl",
+                value: "soon".to_string()
+            })
+        );
+        assert_eq!(
+            parse(&["--mnemonic", "not words"], &[]),
+            Err(ConfigError::InvalidValue {
+                name: "mnemonic",
+                value: "not words".to_string()
+            })
+        );
+        assert_eq!(
+            parse(&["--ln", "lnd"], &[("MINT_MNEMONIC", MNEMONIC)]),
+            Err(ConfigError::UnsupportedBackend("lnd".to_string()))
+        );
+        assert_eq!(parse(&["--help"], &[]), Err(ConfigError::Help));
+    }
+}
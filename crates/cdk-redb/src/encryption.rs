@@ -0,0 +1,223 @@
+//! Encryption of keyset private keys at rest
+//!
+//! Keysets are sealed with ChaCha20-Poly1305 under a key derived from an
+//! operator passphrase with PBKDF2-HMAC-SHA256, or given raw. The KDF
+//! parameters are stored next to a sealed check value, so a wrong passphrase
+//! is refused when the database is opened rather than on the first signature.
+
+use std::num::NonZeroU32;
+
+use cdk::util::hex;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::pbkdf2;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+
+use super::error::Error;
+
+/// PBKDF2 iterations of new databases, the OWASP recommendation for
+/// HMAC-SHA256
+pub const PBKDF2_ITERATIONS: u32 = 600_000;
+
+/// Associated data of the check value
+const CHECK_AAD: &[u8] = b"cdk-redb keyset key check";
+
+/// Key protecting the keyset private keys of a [MintRedbDatabase](crate::MintRedbDatabase)
+#[derive(Clone)]
+pub enum KeysetKey {
+    /// Passphrase of the operator, stretched with PBKDF2
+    Passphrase(String),
+    /// Key used as is, from a KMS or a secrets file
+    Raw([u8; 32]),
+}
+
+impl std::fmt::Debug for KeysetKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Passphrase(_) => f.write_str("KeysetKey::Passphrase(..)"),
+            Self::Raw(_) => f.write_str("KeysetKey::Raw(..)"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "name", rename_all = "snake_case")]
+enum Kdf {
+    Pbkdf2Sha256 { iterations: u32, salt: String },
+    Raw,
+}
+
+/// How the key of a database is derived, stored in its config table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct KeyParams {
+    kdf: Kdf,
+    check: Sealed,
+}
+
+/// Nonce and ciphertext with its tag, hex encoded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Sealed {
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Seals and opens keysets of a database
+pub(crate) struct Cipher {
+    key: LessSafeKey,
+    rng: SystemRandom,
+}
+
+impl std::fmt::Debug for Cipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Cipher(..)")
+    }
+}
+
+impl Cipher {
+    /// Cipher and parameters for a database without keyset encryption yet
+    #[allow(clippy::result_large_err)]
+    pub fn create(key: &KeysetKey, iterations: u32) -> Result<(Self, KeyParams), Error> {
+        let rng = SystemRandom::new();
+        let kdf = match key {
+            KeysetKey::Passphrase(_) => {
+                let mut salt = [0; 16];
+                rng.fill(&mut salt).map_err(|_| Error::Encryption)?;
+                Kdf::Pbkdf2Sha256 {
+                    iterations,
+                    salt: hex::encode(salt),
+                }
+            }
+            KeysetKey::Raw(_) => Kdf::Raw,
+        };
+
+        let cipher = Self::derive(key, &kdf)?;
+        let check = cipher.seal(&[], CHECK_AAD)?;
+
+        Ok((cipher, KeyParams { kdf, check }))
+    }
+
+    /// Cipher of a database with keyset encryption, checking `key` against
+    /// its stored parameters
+    #[allow(clippy::result_large_err)]
+    pub fn open(key: &KeysetKey, params: &KeyParams) -> Result<Self, Error> {
+        let cipher = Self::derive(key, &params.kdf)?;
+        cipher
+            .open_sealed(&params.check, CHECK_AAD)
+            .map_err(|_| Error::WrongKeysetKey)?;
+
+        Ok(cipher)
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn derive(key: &KeysetKey, kdf: &Kdf) -> Result<Self, Error> {
+        let mut bytes = [0; 32];
+        match (key, kdf) {
+            (KeysetKey::Passphrase(passphrase), Kdf::Pbkdf2Sha256 { iterations, salt }) => {
+                let iterations = NonZeroU32::new(*iterations).ok_or(Error::Encryption)?;
+                let salt = hex::decode(salt).map_err(|_| Error::Encryption)?;
+                pbkdf2::derive(
+                    pbkdf2::PBKDF2_HMAC_SHA256,
+                    iterations,
+                    &salt,
+                    passphrase.as_bytes(),
+                    &mut bytes,
+                );
+            }
+            (KeysetKey::Raw(key), Kdf::Raw) => bytes = *key,
+            // A passphrase for a raw key database or the other way round
+            _ => return Err(Error::WrongKeysetKey),
+        }
+
+        let key = UnboundKey::new(&CHACHA20_POLY1305, &bytes).map_err(|_| Error::Encryption)?;
+
+        Ok(Self {
+            key: LessSafeKey::new(key),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    /// Seal `plaintext`, bound to `aad`
+    #[allow(clippy::result_large_err)]
+    pub fn seal(&self, plaintext: &[u8], aad: &[u8]) -> Result<Sealed, Error> {
+        let mut nonce = [0; NONCE_LEN];
+        self.rng.fill(&mut nonce).map_err(|_| Error::Encryption)?;
+
+        let mut in_out = plaintext.to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(aad),
+                &mut in_out,
+            )
+            .map_err(|_| Error::Encryption)?;
+
+        Ok(Sealed {
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(in_out),
+        })
+    }
+
+    /// Plaintext of `sealed`, if it was sealed with this key and `aad`
+    #[allow(clippy::result_large_err)]
+    pub fn open_sealed(&self, sealed: &Sealed, aad: &[u8]) -> Result<Vec<u8>, Error> {
+        let nonce: [u8; NONCE_LEN] = hex::decode(&sealed.nonce)
+            .ok()
+            .and_then(|nonce| nonce.try_into().ok())
+            .ok_or(Error::Encryption)?;
+        let mut in_out = hex::decode(&sealed.ciphertext).map_err(|_| Error::Encryption)?;
+
+        let plaintext = self
+            .key
+            .open_in_place(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(aad),
+                &mut in_out,
+            )
+            .map_err(|_| Error::Encryption)?;
+
+        Ok(plaintext.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open() {
+        let key = KeysetKey::Passphrase("correct horse".to_string());
+        let (cipher, params) = Cipher::create(&key, 1000).unwrap();
+
+        let sealed = cipher.seal(b"secret keys", b"00ad268c4d1f5826").unwrap();
+        assert!(!sealed.ciphertext.contains(&hex::encode(b"secret keys")));
+        assert_eq!(
+            cipher.open_sealed(&sealed, b"00ad268c4d1f5826").unwrap(),
+            b"secret keys"
+        );
+        // Bound to the keyset it was sealed for
+        assert!(cipher.open_sealed(&sealed, b"009a1f293253e41e").is_err());
+
+        let reopened = Cipher::open(&key, &params).unwrap();
+        assert_eq!(
+            reopened.open_sealed(&sealed, b"00ad268c4d1f5826").unwrap(),
+            b"secret keys"
+        );
+
+        for wrong in [
+            KeysetKey::Passphrase("wrong horse".to_string()),
+            KeysetKey::Raw([7; 32]),
+        ] {
+            assert!(matches!(
+                Cipher::open(&wrong, &params),
+                Err(Error::WrongKeysetKey)
+            ));
+        }
+
+        let (_, raw_params) = Cipher::create(&KeysetKey::Raw([7; 32]), 1000).unwrap();
+        assert!(Cipher::open(&KeysetKey::Raw([7; 32]), &raw_params).is_ok());
+        assert!(matches!(
+            Cipher::open(&KeysetKey::Raw([8; 32]), &raw_params),
+            Err(Error::WrongKeysetKey)
+        ));
+    }
+}
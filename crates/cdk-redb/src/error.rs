@@ -34,6 +34,9 @@ pub enum Error {
     /// CDK Error
     #[error(transparent)]
     CDK(#[from] cdk::error::Error),
+    /// NUT01 Error
+    #[error(transparent)]
+    CDKNUT01(#[from] cdk::nuts::nut01::Error),
     /// NUT02 Error
     #[error(transparent)]
     CDKNUT02(#[from] cdk::nuts::nut02::Error),
@@ -43,6 +46,23 @@ pub enum Error {
     /// Unknown Mint Info
     #[error("Unknown Mint Info")]
     UnknownMintInfo,
+    /// Keysets are encrypted but the database was opened without a key
+    #[error("Keysets are encrypted, open the database with a keyset passphrase or key")]
+    KeysetKeyRequired,
+    /// Keysets were encrypted under another passphrase or key
+    #[error("Wrong keyset passphrase or key")]
+    WrongKeysetKey,
+    /// Keyset could not be sealed or opened
+    #[error("Keyset encryption error")]
+    Encryption,
+    /// Database was written by a newer version
+    #[error("Database version {found} is newer than the supported version {supported}")]
+    UnsupportedDatabaseVersion {
+        /// Version of the database file
+        found: u64,
+        /// Newest version this release reads
+        supported: u64,
+    },
 }
 
 impl From<Error> for cdk::cdk_database::Error {
@@ -1,3 +1,5 @@
+#[cfg(feature = "mint")]
+pub mod encryption;
 pub mod error;
 
 #[cfg(feature = "mint")]
@@ -5,6 +7,8 @@ pub mod mint;
 #[cfg(feature = "wallet")]
 pub mod wallet;
 
+#[cfg(feature = "mint")]
+pub use encryption::KeysetKey;
 #[cfg(feature = "mint")]
 pub use mint::MintRedbDatabase;
 #[cfg(feature = "wallet")]
@@ -3,40 +3,85 @@ use std::str::FromStr;
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use cdk::cdk_database::{self, MintDatabase};
+use cdk::cdk_database::{self, CachedSwapResponse, MintDatabase, MintTransaction};
 use cdk::dhke::hash_to_curve;
 use cdk::nuts::{
-    BlindSignature, CurrencyUnit, Id, MintInfo, MintKeySet as KeySet, Proof, PublicKey,
+    BlindSignature, CurrencyUnit, Id, MintInfo, MintKeySet as KeySet, Proof, Proofs, PublicKey,
+    SwapResponse,
 };
+use cdk::secp256k1::hashes::Hash;
 use cdk::secret::Secret;
 use cdk::types::{MeltQuote, MintQuote};
-use redb::{Database, ReadableTable, TableDefinition};
+use cdk::Sha256;
+use redb::{
+    Database, MultimapTableDefinition, ReadableTable, Table, TableDefinition, WriteTransaction,
+};
 use tokio::sync::Mutex;
 use tracing::debug;
 
+use super::encryption::{Cipher, KeyParams, KeysetKey, Sealed, PBKDF2_ITERATIONS};
 use super::error::Error;
 
 const ACTIVE_KEYSETS_TABLE: TableDefinition<&str, &str> = TableDefinition::new("active_keysets");
 const KEYSETS_TABLE: TableDefinition<&str, &str> = TableDefinition::new("keysets");
 const MINT_QUOTES_TABLE: TableDefinition<&str, &str> = TableDefinition::new("mint_quotes");
 const MELT_QUOTES_TABLE: TableDefinition<&str, &str> = TableDefinition::new("melt_quotes");
+// Key is payment hash of the invoice, value is mint quote id
+const MINT_QUOTE_PAYMENT_HASHES_TABLE: TableDefinition<[u8; 32], &str> =
+    TableDefinition::new("mint_quote_payment_hashes");
 const PENDING_PROOFS_TABLE: TableDefinition<[u8; 33], &str> =
     TableDefinition::new("pending_proofs");
 const SPENT_PROOFS_TABLE: TableDefinition<[u8; 33], &str> = TableDefinition::new("spent_proofs");
+// Key is keyset id, values are the spent Ys of that keyset
+const SPENT_BY_KEYSET_TABLE: MultimapTableDefinition<&str, [u8; 33]> =
+    MultimapTableDefinition::new("spent_proofs_by_keyset");
 const CONFIG_TABLE: TableDefinition<&str, &str> = TableDefinition::new("config");
 // Key is hex blinded_message B_ value is blinded_signature
 const BLINDED_SIGNATURES: TableDefinition<[u8; 33], &str> =
     TableDefinition::new("blinded_signatures");
+// Key is the hash of a swap request, value is its response and expiry
+const SWAP_RESPONSES_TABLE: TableDefinition<[u8; 32], &str> =
+    TableDefinition::new("swap_responses");
+
+const DATABASE_VERSION: u64 = 3;
 
-const DATABASE_VERSION: u64 = 0;
+/// Keyset recorded for spent proofs whose keyset id cannot be read
+const UNKNOWN_KEYSET: &str = "unknown";
 
 #[derive(Debug, Clone)]
 pub struct MintRedbDatabase {
     db: Arc<Mutex<Database>>,
+    /// Seals keysets, when they are encrypted
+    cipher: Option<Arc<Cipher>>,
+    /// Fail transactions once their spent proofs are written, as if the
+    /// mint was killed
+    #[cfg(test)]
+    crash_after_spent: Arc<std::sync::atomic::AtomicBool>,
 }
 
 impl MintRedbDatabase {
+    /// Open or create the database at `path`, keysets in plaintext
+    ///
+    /// Fails with [Error::KeysetKeyRequired] if its keysets are encrypted.
+    #[allow(clippy::result_large_err)]
     pub fn new(path: &str) -> Result<Self, Error> {
+        Self::open(path, None, PBKDF2_ITERATIONS)
+    }
+
+    /// Open or create the database at `path`, the private keys of keysets
+    /// encrypted under `key`
+    ///
+    /// Keysets of a database that was not encrypted are encrypted when it is
+    /// opened, though pages redb freed may hold their plaintext until they
+    /// are reused. Fails with [Error::WrongKeysetKey] if keysets were
+    /// encrypted under another key.
+    #[allow(clippy::result_large_err)]
+    pub fn with_keyset_key(path: &str, key: KeysetKey) -> Result<Self, Error> {
+        Self::open(path, Some(&key), PBKDF2_ITERATIONS)
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn open(path: &str, key: Option<&KeysetKey>, iterations: u32) -> Result<Self, Error> {
         let db = Database::create(path)?;
 
         let write_txn = db.begin_write()?;
@@ -51,9 +96,25 @@ impl MintRedbDatabase {
             match db_version {
                 Some(db_version) => {
                     let current_file_version = u64::from_str(&db_version)?;
-                    if current_file_version.ne(&DATABASE_VERSION) {
-                        // Database needs to be upgraded
-                        todo!()
+                    // Written by a newer release, whose tables this one
+                    // cannot read
+                    if current_file_version > DATABASE_VERSION {
+                        return Err(Error::UnsupportedDatabaseVersion {
+                            found: current_file_version,
+                            supported: DATABASE_VERSION,
+                        });
+                    }
+                    if current_file_version < 1 {
+                        index_spent_proofs(&write_txn)?;
+                    }
+                    if current_file_version < 2 {
+                        index_mint_quotes(&write_txn)?;
+                    }
+                    if current_file_version < 3 {
+                        let _ = write_txn.open_table(SWAP_RESPONSES_TABLE)?;
+                    }
+                    if current_file_version < DATABASE_VERSION {
+                        table.insert("db_version", DATABASE_VERSION.to_string().as_str())?;
                     }
                 }
                 None => {
@@ -62,20 +123,202 @@ impl MintRedbDatabase {
                     let _ = write_txn.open_table(KEYSETS_TABLE)?;
                     let _ = write_txn.open_table(MINT_QUOTES_TABLE)?;
                     let _ = write_txn.open_table(MELT_QUOTES_TABLE)?;
+                    let _ = write_txn.open_table(MINT_QUOTE_PAYMENT_HASHES_TABLE)?;
                     let _ = write_txn.open_table(PENDING_PROOFS_TABLE)?;
                     let _ = write_txn.open_table(SPENT_PROOFS_TABLE)?;
+                    let _ = write_txn.open_multimap_table(SPENT_BY_KEYSET_TABLE)?;
                     let _ = write_txn.open_table(BLINDED_SIGNATURES)?;
+                    let _ = write_txn.open_table(SWAP_RESPONSES_TABLE)?;
 
-                    table.insert("db_version", "0")?;
+                    table.insert("db_version", DATABASE_VERSION.to_string().as_str())?;
                 }
             }
         }
 
+        let cipher = {
+            let mut table = write_txn.open_table(CONFIG_TABLE)?;
+            let params: Option<KeyParams> = table
+                .get("keyset_key")?
+                .map(|params| serde_json::from_str(params.value()))
+                .transpose()?;
+
+            match (params, key) {
+                (None, None) => None,
+                (Some(_), None) => return Err(Error::KeysetKeyRequired),
+                (Some(params), Some(key)) => Some(Cipher::open(key, &params)?),
+                (None, Some(key)) => {
+                    let (cipher, params) = Cipher::create(key, iterations)?;
+                    encrypt_keysets(&write_txn, &cipher)?;
+                    table.insert("keyset_key", serde_json::to_string(&params)?.as_str())?;
+                    Some(cipher)
+                }
+            }
+        };
+
         write_txn.commit()?;
         Ok(Self {
             db: Arc::new(Mutex::new(db)),
+            cipher: cipher.map(Arc::new),
+            #[cfg(test)]
+            crash_after_spent: Default::default(),
         })
     }
+
+    /// Value of `keyset` in the keysets table
+    #[allow(clippy::result_large_err)]
+    fn encode_keyset(&self, keyset: &KeySet) -> Result<String, Error> {
+        let mut json = Vec::new();
+        keyset.serialize_secret(&mut serde_json::Serializer::new(&mut json))?;
+
+        match &self.cipher {
+            Some(cipher) => Ok(serde_json::to_string(
+                &cipher.seal(&json, keyset.id.to_string().as_bytes())?,
+            )?),
+            None => Ok(String::from_utf8(json).expect("JSON is UTF-8")),
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn decode_keyset(&self, id: &str, value: &str) -> Result<KeySet, Error> {
+        match &self.cipher {
+            Some(cipher) => {
+                let sealed: Sealed = serde_json::from_str(value)?;
+                Ok(serde_json::from_slice(
+                    &cipher.open_sealed(&sealed, id.as_bytes())?,
+                )?)
+            }
+            None => Ok(serde_json::from_str(value)?),
+        }
+    }
+}
+
+/// Seal the keysets of a database that was not encrypted
+#[allow(clippy::result_large_err)]
+fn encrypt_keysets(write_txn: &WriteTransaction, cipher: &Cipher) -> Result<(), Error> {
+    let mut table = write_txn.open_table(KEYSETS_TABLE)?;
+
+    let keysets: Vec<(String, String)> = table
+        .iter()?
+        .flatten()
+        .map(|(id, keyset)| (id.value().to_string(), keyset.value().to_string()))
+        .collect();
+
+    for (id, keyset) in keysets {
+        let sealed = cipher.seal(keyset.as_bytes(), id.as_bytes())?;
+        table.insert(id.as_str(), serde_json::to_string(&sealed)?.as_str())?;
+    }
+
+    Ok(())
+}
+
+/// Build the keyset index of spent proofs of a version 0 database
+#[allow(clippy::result_large_err)]
+fn index_spent_proofs(write_txn: &WriteTransaction) -> Result<(), Error> {
+    let spent_proofs = write_txn.open_table(SPENT_PROOFS_TABLE)?;
+    let mut index = write_txn.open_multimap_table(SPENT_BY_KEYSET_TABLE)?;
+
+    for (y, proof) in spent_proofs.iter()?.flatten() {
+        let proof: serde_json::Value = serde_json::from_str(proof.value())?;
+        let keyset_id = proof
+            .get("id")
+            .and_then(|id| id.as_str())
+            .and_then(|id| Id::from_str(id).ok())
+            .map(|id| id.to_string());
+
+        index.insert(keyset_id.as_deref().unwrap_or(UNKNOWN_KEYSET), y.value())?;
+    }
+
+    Ok(())
+}
+
+/// Build the payment hash index of mint quotes of a version 0 or 1 database
+#[allow(clippy::result_large_err)]
+fn index_mint_quotes(write_txn: &WriteTransaction) -> Result<(), Error> {
+    let mint_quotes = write_txn.open_table(MINT_QUOTES_TABLE)?;
+    let mut index = write_txn.open_table(MINT_QUOTE_PAYMENT_HASHES_TABLE)?;
+
+    for (id, quote) in mint_quotes.iter()?.flatten() {
+        let quote: MintQuote = serde_json::from_str(quote.value())?;
+        if let Some(payment_hash) = quote.payment_hash() {
+            index.insert(payment_hash.to_byte_array(), id.value())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Insert `proofs` into the spent set of `write_txn`, releasing the pending
+/// `released` `Y`s
+///
+/// Returns the `Y`s that were already spent or pending, the transaction must
+/// then be dropped to discard every insert.
+#[allow(clippy::result_large_err)]
+fn stage_spent_proofs(
+    write_txn: &WriteTransaction,
+    proofs: &Proofs,
+    released: &[PublicKey],
+) -> Result<Vec<PublicKey>, Error> {
+    let mut table = write_txn.open_table(SPENT_PROOFS_TABLE)?;
+    let mut index = write_txn.open_multimap_table(SPENT_BY_KEYSET_TABLE)?;
+    let mut pending = write_txn.open_table(PENDING_PROOFS_TABLE)?;
+
+    for y in released {
+        pending.remove(y.to_bytes())?;
+    }
+
+    let mut spent = Vec::new();
+    for proof in proofs {
+        let y: PublicKey = proof.y()?;
+        let previous = table.insert(y.to_bytes(), serde_json::to_string(proof)?.as_str())?;
+        if previous.is_some() || pending.get(y.to_bytes())?.is_some() {
+            spent.push(y);
+            continue;
+        }
+
+        index.insert(proof.keyset_id.to_string().as_str(), y.to_bytes())?;
+    }
+
+    Ok(spent)
+}
+
+/// Insert `proofs` into the pending set of `write_txn`
+///
+/// Returns the `Y`s that were already spent or pending, the transaction must
+/// then be dropped to discard every insert.
+#[allow(clippy::result_large_err)]
+fn stage_pending_proofs(
+    write_txn: &WriteTransaction,
+    proofs: &Proofs,
+) -> Result<Vec<PublicKey>, Error> {
+    let spent_table = write_txn.open_table(SPENT_PROOFS_TABLE)?;
+    let mut table = write_txn.open_table(PENDING_PROOFS_TABLE)?;
+
+    let mut spent = Vec::new();
+    for proof in proofs {
+        let y: PublicKey = proof.y()?;
+        let previous = table.insert(y.to_bytes(), serde_json::to_string(proof)?.as_str())?;
+        if previous.is_some() || spent_table.get(y.to_bytes())?.is_some() {
+            spent.push(y);
+        }
+    }
+
+    Ok(spent)
+}
+
+/// Remove the payment hash entry of `quote` unless a later quote took it
+#[allow(clippy::result_large_err)]
+fn unindex_mint_quote(index: &mut Table<[u8; 32], &str>, quote: &MintQuote) -> Result<(), Error> {
+    let Some(payment_hash) = quote.payment_hash() else {
+        return Ok(());
+    };
+
+    let key = payment_hash.to_byte_array();
+    let indexed = index.get(key)?.is_some_and(|id| id.value() == quote.id);
+    if indexed {
+        index.remove(key)?;
+    }
+
+    Ok(())
 }
 
 #[async_trait]
@@ -177,9 +420,7 @@ impl MintDatabase for MintRedbDatabase {
             table
                 .insert(
                     Id::from(keyset.clone()).to_string().as_str(),
-                    serde_json::to_string(&keyset)
-                        .map_err(Error::from)?
-                        .as_str(),
+                    self.encode_keyset(&keyset)?.as_str(),
                 )
                 .map_err(Error::from)?;
         }
@@ -197,7 +438,9 @@ impl MintDatabase for MintRedbDatabase {
             .get(keyset_id.to_string().as_str())
             .map_err(Error::from)?
         {
-            Some(keyset) => Ok(serde_json::from_str(keyset.value()).map_err(Error::from)?),
+            Some(keyset) => Ok(Some(
+                self.decode_keyset(&keyset_id.to_string(), keyset.value())?,
+            )),
             None => Ok(None),
         }
     }
@@ -209,8 +452,8 @@ impl MintDatabase for MintRedbDatabase {
 
         let mut keysets = Vec::new();
 
-        for (_id, keyset) in (table.iter().map_err(Error::from)?).flatten() {
-            let keyset = serde_json::from_str(keyset.value()).map_err(Error::from)?;
+        for (id, keyset) in (table.iter().map_err(Error::from)?).flatten() {
+            let keyset = self.decode_keyset(id.value(), keyset.value())?;
 
             keysets.push(keyset)
         }
@@ -218,6 +461,22 @@ impl MintDatabase for MintRedbDatabase {
         Ok(keysets)
     }
 
+    async fn remove_keyset(&self, keyset_id: &Id) -> Result<(), Self::Err> {
+        let db = self.db.lock().await;
+
+        let write_txn = db.begin_write().map_err(Error::from)?;
+
+        {
+            let mut table = write_txn.open_table(KEYSETS_TABLE).map_err(Error::from)?;
+            table
+                .remove(keyset_id.to_string().as_str())
+                .map_err(Error::from)?;
+        }
+        write_txn.commit().map_err(Error::from)?;
+
+        Ok(())
+    }
+
     async fn add_mint_quote(&self, quote: MintQuote) -> Result<(), Self::Err> {
         let db = self.db.lock().await;
 
@@ -227,12 +486,34 @@ impl MintDatabase for MintRedbDatabase {
             let mut table = write_txn
                 .open_table(MINT_QUOTES_TABLE)
                 .map_err(Error::from)?;
-            table
+            let mut index = write_txn
+                .open_table(MINT_QUOTE_PAYMENT_HASHES_TABLE)
+                .map_err(Error::from)?;
+
+            let previous: Option<MintQuote> = table
                 .insert(
                     quote.id.as_str(),
                     serde_json::to_string(&quote).map_err(Error::from)?.as_str(),
                 )
+                .map_err(Error::from)?
+                .map(|previous| serde_json::from_str(previous.value()))
+                .transpose()
                 .map_err(Error::from)?;
+
+            match previous {
+                // An update keeps the entry, which may be of a later quote
+                Some(previous) if previous.request == quote.request => (),
+                previous => {
+                    if let Some(previous) = previous {
+                        unindex_mint_quote(&mut index, &previous)?;
+                    }
+                    if let Some(payment_hash) = quote.payment_hash() {
+                        index
+                            .insert(payment_hash.to_byte_array(), quote.id.as_str())
+                            .map_err(Error::from)?;
+                    }
+                }
+            }
         }
         write_txn.commit().map_err(Error::from)?;
 
@@ -270,6 +551,32 @@ impl MintDatabase for MintRedbDatabase {
         Ok(quotes)
     }
 
+    async fn get_mint_quote_by_payment_hash(
+        &self,
+        payment_hash: &Sha256,
+    ) -> Result<Option<MintQuote>, Self::Err> {
+        let db = self.db.lock().await;
+        let read_txn = db.begin_read().map_err(Error::from)?;
+        let index = read_txn
+            .open_table(MINT_QUOTE_PAYMENT_HASHES_TABLE)
+            .map_err(Error::from)?;
+        let table = read_txn
+            .open_table(MINT_QUOTES_TABLE)
+            .map_err(Error::from)?;
+
+        let Some(quote_id) = index
+            .get(payment_hash.to_byte_array())
+            .map_err(Error::from)?
+        else {
+            return Ok(None);
+        };
+
+        match table.get(quote_id.value()).map_err(Error::from)? {
+            Some(quote) => Ok(serde_json::from_str(quote.value()).map_err(Error::from)?),
+            None => Ok(None),
+        }
+    }
+
     async fn remove_mint_quote(&self, quote_id: &str) -> Result<(), Self::Err> {
         let db = self.db.lock().await;
 
@@ -279,7 +586,49 @@ impl MintDatabase for MintRedbDatabase {
             let mut table = write_txn
                 .open_table(MINT_QUOTES_TABLE)
                 .map_err(Error::from)?;
-            table.remove(quote_id).map_err(Error::from)?;
+            let mut index = write_txn
+                .open_table(MINT_QUOTE_PAYMENT_HASHES_TABLE)
+                .map_err(Error::from)?;
+
+            let removed = table
+                .remove(quote_id)
+                .map_err(Error::from)?
+                .map(|quote| serde_json::from_str::<MintQuote>(quote.value()))
+                .transpose()
+                .map_err(Error::from)?;
+            if let Some(quote) = removed {
+                unindex_mint_quote(&mut index, &quote)?;
+            }
+        }
+        write_txn.commit().map_err(Error::from)?;
+
+        Ok(())
+    }
+
+    async fn remove_mint_quotes(&self, quote_ids: &[String]) -> Result<(), Self::Err> {
+        let db = self.db.lock().await;
+
+        let write_txn = db.begin_write().map_err(Error::from)?;
+
+        {
+            let mut table = write_txn
+                .open_table(MINT_QUOTES_TABLE)
+                .map_err(Error::from)?;
+            let mut index = write_txn
+                .open_table(MINT_QUOTE_PAYMENT_HASHES_TABLE)
+                .map_err(Error::from)?;
+
+            for quote_id in quote_ids {
+                let removed = table
+                    .remove(quote_id.as_str())
+                    .map_err(Error::from)?
+                    .map(|quote| serde_json::from_str::<MintQuote>(quote.value()))
+                    .transpose()
+                    .map_err(Error::from)?;
+                if let Some(quote) = removed {
+                    unindex_mint_quote(&mut index, &quote)?;
+                }
+            }
         }
         write_txn.commit().map_err(Error::from)?;
 
@@ -353,6 +702,24 @@ impl MintDatabase for MintRedbDatabase {
         Ok(())
     }
 
+    async fn remove_melt_quotes(&self, quote_ids: &[String]) -> Result<(), Self::Err> {
+        let db = self.db.lock().await;
+
+        let write_txn = db.begin_write().map_err(Error::from)?;
+
+        {
+            let mut table = write_txn
+                .open_table(MELT_QUOTES_TABLE)
+                .map_err(Error::from)?;
+            for quote_id in quote_ids {
+                table.remove(quote_id.as_str()).map_err(Error::from)?;
+            }
+        }
+        write_txn.commit().map_err(Error::from)?;
+
+        Ok(())
+    }
+
     async fn add_spent_proof(&self, proof: Proof) -> Result<(), Self::Err> {
         let db = self.db.lock().await;
 
@@ -362,13 +729,20 @@ impl MintDatabase for MintRedbDatabase {
             let mut table = write_txn
                 .open_table(SPENT_PROOFS_TABLE)
                 .map_err(Error::from)?;
-            let y: PublicKey = hash_to_curve(&proof.secret.to_bytes()).map_err(Error::from)?;
+            let y: PublicKey = proof.y().map_err(Error::from)?;
             table
                 .insert(
                     y.to_bytes(),
                     serde_json::to_string(&proof).map_err(Error::from)?.as_str(),
                 )
                 .map_err(Error::from)?;
+
+            let mut index = write_txn
+                .open_multimap_table(SPENT_BY_KEYSET_TABLE)
+                .map_err(Error::from)?;
+            index
+                .insert(proof.keyset_id.to_string().as_str(), y.to_bytes())
+                .map_err(Error::from)?;
         }
         write_txn.commit().map_err(Error::from)?;
         debug!("Added spend secret: {}", proof.secret.to_string());
@@ -376,6 +750,26 @@ impl MintDatabase for MintRedbDatabase {
         Ok(())
     }
 
+    async fn add_spent_proofs_if_unspent(
+        &self,
+        proofs: Proofs,
+    ) -> Result<Vec<PublicKey>, Self::Err> {
+        let db = self.db.lock().await;
+
+        let write_txn = db.begin_write().map_err(Error::from)?;
+
+        let spent = stage_spent_proofs(&write_txn, &proofs, &[])?;
+
+        // Dropping the transaction discards every insert
+        if !spent.is_empty() {
+            return Ok(spent);
+        }
+        write_txn.commit().map_err(Error::from)?;
+        debug!("Added {} spent proofs", proofs.len());
+
+        Ok(spent)
+    }
+
     async fn get_spent_proof_by_y(&self, y: &PublicKey) -> Result<Option<Proof>, Self::Err> {
         let db = self.db.lock().await;
         let read_txn = db.begin_read().map_err(Error::from)?;
@@ -389,6 +783,92 @@ impl MintDatabase for MintRedbDatabase {
         }
     }
 
+    async fn get_spent_ys(&self) -> Result<Vec<PublicKey>, Self::Err> {
+        let db = self.db.lock().await;
+        let read_txn = db.begin_read().map_err(Error::from)?;
+        let table = read_txn
+            .open_table(SPENT_PROOFS_TABLE)
+            .map_err(Error::from)?;
+
+        let mut ys = Vec::new();
+
+        for (y, _proof) in (table.iter().map_err(Error::from)?).flatten() {
+            ys.push(PublicKey::from_slice(&y.value()).map_err(Error::from)?);
+        }
+
+        Ok(ys)
+    }
+
+    async fn get_spent_proofs_by_keyset(&self, keyset_id: &Id) -> Result<Proofs, Self::Err> {
+        let db = self.db.lock().await;
+        let read_txn = db.begin_read().map_err(Error::from)?;
+        let table = read_txn
+            .open_table(SPENT_PROOFS_TABLE)
+            .map_err(Error::from)?;
+        let index = read_txn
+            .open_multimap_table(SPENT_BY_KEYSET_TABLE)
+            .map_err(Error::from)?;
+
+        let mut proofs = Vec::new();
+
+        for y in index
+            .get(keyset_id.to_string().as_str())
+            .map_err(Error::from)?
+            .flatten()
+        {
+            if let Some(proof) = table.get(y.value()).map_err(Error::from)? {
+                proofs.push(serde_json::from_str(proof.value()).map_err(Error::from)?);
+            }
+        }
+
+        Ok(proofs)
+    }
+
+    async fn get_spent_count_by_keyset(&self, keyset_id: &Id) -> Result<u64, Self::Err> {
+        let db = self.db.lock().await;
+        let read_txn = db.begin_read().map_err(Error::from)?;
+        let index = read_txn
+            .open_multimap_table(SPENT_BY_KEYSET_TABLE)
+            .map_err(Error::from)?;
+
+        let ys = index
+            .get(keyset_id.to_string().as_str())
+            .map_err(Error::from)?;
+
+        Ok(ys.len())
+    }
+
+    async fn remove_spent_proofs(&self, ys: &[PublicKey]) -> Result<(), Self::Err> {
+        let db = self.db.lock().await;
+
+        let write_txn = db.begin_write().map_err(Error::from)?;
+
+        {
+            let mut table = write_txn
+                .open_table(SPENT_PROOFS_TABLE)
+                .map_err(Error::from)?;
+            let mut index = write_txn
+                .open_multimap_table(SPENT_BY_KEYSET_TABLE)
+                .map_err(Error::from)?;
+            for y in ys {
+                let keyset_id = match table.remove(y.to_bytes()).map_err(Error::from)? {
+                    Some(proof) => {
+                        let proof: Proof =
+                            serde_json::from_str(proof.value()).map_err(Error::from)?;
+                        proof.keyset_id.to_string()
+                    }
+                    None => continue,
+                };
+                index
+                    .remove(keyset_id.as_str(), y.to_bytes())
+                    .map_err(Error::from)?;
+            }
+        }
+        write_txn.commit().map_err(Error::from)?;
+
+        Ok(())
+    }
+
     async fn get_spent_proof_by_secret(&self, secret: &Secret) -> Result<Option<Proof>, Self::Err> {
         let db = self.db.lock().await;
         let read_txn = db.begin_read().map_err(Error::from)?;
@@ -415,7 +895,7 @@ impl MintDatabase for MintRedbDatabase {
                 .map_err(Error::from)?;
             table
                 .insert(
-                    hash_to_curve(&proof.secret.to_bytes())?.to_bytes(),
+                    proof.y()?.to_bytes(),
                     serde_json::to_string(&proof).map_err(Error::from)?.as_str(),
                 )
                 .map_err(Error::from)?;
@@ -425,6 +905,26 @@ impl MintDatabase for MintRedbDatabase {
         Ok(())
     }
 
+    async fn add_pending_proofs_if_unspent(
+        &self,
+        proofs: Proofs,
+    ) -> Result<Vec<PublicKey>, Self::Err> {
+        let db = self.db.lock().await;
+
+        let write_txn = db.begin_write().map_err(Error::from)?;
+
+        let spent = stage_pending_proofs(&write_txn, &proofs)?;
+
+        // Dropping the transaction discards every insert
+        if !spent.is_empty() {
+            return Ok(spent);
+        }
+        write_txn.commit().map_err(Error::from)?;
+        debug!("Added {} pending proofs", proofs.len());
+
+        Ok(spent)
+    }
+
     async fn get_pending_proof_by_y(&self, y: &PublicKey) -> Result<Option<Proof>, Self::Err> {
         let db = self.db.lock().await;
         let read_txn = db.begin_read().map_err(Error::from)?;
@@ -541,4 +1041,621 @@ impl MintDatabase for MintRedbDatabase {
 
         Ok(signatures)
     }
+
+    async fn get_blinded_signatures_by_keyset(
+        &self,
+        keyset_id: &Id,
+    ) -> Result<Vec<BlindSignature>, Self::Err> {
+        let db = self.db.lock().await;
+        let read_txn = db.begin_read().map_err(Error::from)?;
+        let table = read_txn
+            .open_table(BLINDED_SIGNATURES)
+            .map_err(Error::from)?;
+
+        let mut signatures = Vec::new();
+
+        for (_blinded_message, blind_signature) in (table.iter().map_err(Error::from)?).flatten() {
+            let blind_signature: BlindSignature =
+                serde_json::from_str(blind_signature.value()).map_err(Error::from)?;
+            if blind_signature.keyset_id == *keyset_id {
+                signatures.push(blind_signature);
+            }
+        }
+
+        Ok(signatures)
+    }
+
+    async fn commit_transaction(
+        &self,
+        transaction: MintTransaction,
+    ) -> Result<Vec<PublicKey>, Self::Err> {
+        let db = self.db.lock().await;
+
+        let write_txn = db.begin_write().map_err(Error::from)?;
+
+        // Dropping the transaction on any early return discards every insert
+        let spent = stage_spent_proofs(
+            &write_txn,
+            &transaction.spent_proofs,
+            &transaction.released_ys,
+        )?;
+        if !spent.is_empty() {
+            return Ok(spent);
+        }
+
+        #[cfg(test)]
+        if self
+            .crash_after_spent
+            .load(std::sync::atomic::Ordering::SeqCst)
+        {
+            return Err(cdk_database::Error::Database(
+                "Killed after the spent set".into(),
+            ));
+        }
+
+        {
+            let mut table = write_txn
+                .open_table(BLINDED_SIGNATURES)
+                .map_err(Error::from)?;
+            for (blinded_message, blinded_signature) in &transaction.blinded_signatures {
+                table
+                    .insert(
+                        blinded_message.to_bytes(),
+                        serde_json::to_string(blinded_signature)
+                            .map_err(Error::from)?
+                            .as_str(),
+                    )
+                    .map_err(Error::from)?;
+            }
+
+            let mut table = write_txn
+                .open_table(SWAP_RESPONSES_TABLE)
+                .map_err(Error::from)?;
+            for (request_hash, response) in &transaction.swap_responses {
+                table
+                    .insert(
+                        request_hash.to_byte_array(),
+                        serde_json::to_string(response)
+                            .map_err(Error::from)?
+                            .as_str(),
+                    )
+                    .map_err(Error::from)?;
+            }
+        }
+
+        write_txn.commit().map_err(Error::from)?;
+        debug!(
+            "Committed {} spent proofs and {} signatures",
+            transaction.spent_proofs.len(),
+            transaction.blinded_signatures.len()
+        );
+
+        Ok(spent)
+    }
+
+    async fn get_swap_response(
+        &self,
+        request_hash: &Sha256,
+    ) -> Result<Option<SwapResponse>, Self::Err> {
+        let db = self.db.lock().await;
+        let read_txn = db.begin_read().map_err(Error::from)?;
+        let table = read_txn
+            .open_table(SWAP_RESPONSES_TABLE)
+            .map_err(Error::from)?;
+
+        match table
+            .get(request_hash.to_byte_array())
+            .map_err(Error::from)?
+        {
+            Some(cached) => {
+                let cached: CachedSwapResponse =
+                    serde_json::from_str(cached.value()).map_err(Error::from)?;
+                Ok(Some(cached.response))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn remove_expired_swap_responses(&self, now: u64) -> Result<usize, Self::Err> {
+        let db = self.db.lock().await;
+        let write_txn = db.begin_write().map_err(Error::from)?;
+
+        let mut removed = 0;
+        {
+            let mut table = write_txn
+                .open_table(SWAP_RESPONSES_TABLE)
+                .map_err(Error::from)?;
+            table
+                .retain(|_, cached| {
+                    // Unreadable responses are kept, as get_swap_response
+                    // reports them
+                    let expired = serde_json::from_str::<CachedSwapResponse>(cached)
+                        .is_ok_and(|cached| cached.expiry <= now);
+                    removed += usize::from(expired);
+                    !expired
+                })
+                .map_err(Error::from)?;
+        }
+        write_txn.commit().map_err(Error::from)?;
+
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::atomic::Ordering;
+
+    use cdk::dhke::construct_proofs;
+    use cdk::mint::Mint;
+    use cdk::nuts::{PreMintSecrets, SwapRequest};
+    use cdk::{Amount, Mnemonic};
+
+    use super::*;
+
+    const MNEMONIC: &str =
+        "half depart obvious quality work element tank gorilla view sugar picture humble";
+
+    /// Fresh database path, removed when dropped
+    struct TempPath(std::path::PathBuf);
+
+    impl TempPath {
+        fn new(name: &str) -> Self {
+            let nanos = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos();
+            Self(std::env::temp_dir().join(format!(
+                "cdk-redb-{}-{}-{}.redb",
+                name,
+                std::process::id(),
+                nanos
+            )))
+        }
+
+        fn as_str(&self) -> &str {
+            self.0.to_str().unwrap()
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn passphrase(passphrase: &str) -> KeysetKey {
+        KeysetKey::Passphrase(passphrase.to_string())
+    }
+
+    /// Keysets generated by a mint on `db`
+    async fn generate_keysets(db: MintRedbDatabase) -> Vec<KeySet> {
+        let mint = Mint::new(
+            Arc::new(db.clone()),
+            Mnemonic::from_str(MNEMONIC).unwrap(),
+            HashSet::new(),
+            Amount::from(1),
+            0.01,
+        )
+        .await
+        .unwrap();
+        let ids: Vec<Id> = mint
+            .keysets()
+            .await
+            .unwrap()
+            .keysets
+            .iter()
+            .map(|k| k.id)
+            .collect();
+
+        let mut keysets = Vec::new();
+        for id in ids {
+            keysets.push(db.get_keyset(&id).await.unwrap().unwrap());
+        }
+        keysets
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_keysets() {
+        let path = TempPath::new("encrypted");
+        let db = MintRedbDatabase::open(path.as_str(), Some(&passphrase("hunter2")), 1000).unwrap();
+        let keysets = generate_keysets(db).await;
+        assert!(!keysets.is_empty());
+
+        // No private key is on disk, hex or raw
+        let file = std::fs::read(&path.0).unwrap();
+        let secrets: HashSet<[u8; 32]> = keysets
+            .iter()
+            .flat_map(|keyset| keyset.keys.values())
+            .map(|pair| pair.secret_key.to_secret_bytes())
+            .collect();
+        assert!(!file
+            .windows(32)
+            .any(|window| secrets.contains(<&[u8; 32]>::try_from(window).unwrap())));
+        let text = String::from_utf8_lossy(&file);
+        for secret in &secrets {
+            assert!(!text.contains(&cdk::util::hex::encode(secret)));
+        }
+
+        assert!(matches!(
+            MintRedbDatabase::new(path.as_str()),
+            Err(Error::KeysetKeyRequired)
+        ));
+        for wrong in [passphrase("hunter3"), KeysetKey::Raw([0; 32])] {
+            assert!(matches!(
+                MintRedbDatabase::with_keyset_key(path.as_str(), wrong),
+                Err(Error::WrongKeysetKey)
+            ));
+        }
+
+        let db = MintRedbDatabase::with_keyset_key(path.as_str(), passphrase("hunter2")).unwrap();
+        let stored = db.get_keysets().await.unwrap();
+        assert_eq!(stored.len(), keysets.len());
+        for keyset in stored {
+            // Ids follow from the decrypted keys
+            assert_eq!(Id::from(keyset.clone()), keyset.id);
+            assert!(keysets.contains(&keyset));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_encrypt_existing_database() {
+        let path = TempPath::new("migrate");
+        let keysets = generate_keysets(MintRedbDatabase::new(path.as_str()).unwrap()).await;
+
+        let key = KeysetKey::Raw([7; 32]);
+        let db = MintRedbDatabase::open(path.as_str(), Some(&key), 1000).unwrap();
+        assert_eq!(db.get_keysets().await.unwrap(), keysets);
+        drop(db);
+
+        assert!(matches!(
+            MintRedbDatabase::new(path.as_str()),
+            Err(Error::KeysetKeyRequired)
+        ));
+        let db = MintRedbDatabase::with_keyset_key(path.as_str(), key).unwrap();
+        assert_eq!(db.get_keysets().await.unwrap(), keysets);
+    }
+
+    #[tokio::test]
+    async fn test_remove_expired_swap_responses() {
+        let path = TempPath::new("swap-responses");
+        let db = MintRedbDatabase::new(path.as_str()).unwrap();
+        let hash = |data: &[u8]| Sha256::hash(data);
+
+        let mut transaction = MintTransaction::new();
+        transaction.cache_swap_response(hash(b"expired"), SwapResponse::new(vec![]), 10);
+        transaction.cache_swap_response(hash(b"live"), SwapResponse::new(vec![]), 20);
+        assert!(db.commit_transaction(transaction).await.unwrap().is_empty());
+
+        // Cached before responses had an expiry
+        {
+            let inner = db.db.lock().await;
+            let write_txn = inner.begin_write().unwrap();
+            {
+                let mut table = write_txn.open_table(SWAP_RESPONSES_TABLE).unwrap();
+                let legacy = serde_json::to_string(&SwapResponse::new(vec![])).unwrap();
+                table
+                    .insert(hash(b"legacy").to_byte_array(), legacy.as_str())
+                    .unwrap();
+            }
+            write_txn.commit().unwrap();
+        }
+        assert!(db
+            .get_swap_response(&hash(b"legacy"))
+            .await
+            .unwrap()
+            .is_some());
+
+        assert_eq!(db.remove_expired_swap_responses(15).await.unwrap(), 2);
+        for (data, kept) in [
+            (b"expired".as_slice(), false),
+            (b"legacy", false),
+            (b"live", true),
+        ] {
+            assert_eq!(
+                db.get_swap_response(&hash(data)).await.unwrap().is_some(),
+                kept
+            );
+        }
+        assert_eq!(db.remove_expired_swap_responses(15).await.unwrap(), 0);
+    }
+
+    #[test]
+    fn test_newer_database_version() {
+        let path = TempPath::new("version");
+        drop(MintRedbDatabase::new(path.as_str()).unwrap());
+
+        let db = Database::create(path.as_str()).unwrap();
+        let write_txn = db.begin_write().unwrap();
+        {
+            let mut table = write_txn.open_table(CONFIG_TABLE).unwrap();
+            let version = (DATABASE_VERSION + 1).to_string();
+            table.insert("db_version", version.as_str()).unwrap();
+        }
+        write_txn.commit().unwrap();
+        drop(db);
+
+        assert!(matches!(
+            MintRedbDatabase::new(path.as_str()),
+            Err(Error::UnsupportedDatabaseVersion { found, supported })
+                if found == DATABASE_VERSION + 1 && supported == DATABASE_VERSION
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_add_spent_proofs_if_unspent() {
+        let path = TempPath::new("spent");
+        let db = MintRedbDatabase::new(path.as_str()).unwrap();
+        let keyset_id = Id::from_str("009a1f293253e41e").unwrap();
+        let proofs: Proofs = (0..3)
+            .map(|_| {
+                Proof::new(
+                    Amount::from(1),
+                    keyset_id,
+                    Secret::generate(),
+                    PublicKey::from_str(
+                        "02194603ffa36356f4a56b7df9371fc3192472351453ec7398b8da8117e7c3e104",
+                    )
+                    .unwrap(),
+                )
+            })
+            .collect();
+        let y = |i: usize| proofs[i].y().unwrap();
+
+        let spent = db
+            .add_spent_proofs_if_unspent(proofs[..2].to_vec())
+            .await
+            .unwrap();
+        assert!(spent.is_empty());
+
+        // Nothing of a batch with a spent proof is recorded
+        let spent = db
+            .add_spent_proofs_if_unspent(vec![proofs[2].clone(), proofs[1].clone()])
+            .await
+            .unwrap();
+        assert_eq!(spent, vec![y(1)]);
+        assert!(db.get_spent_proof_by_y(&y(2)).await.unwrap().is_none());
+
+        let spent = db
+            .add_spent_proofs_if_unspent(vec![proofs[2].clone(), proofs[2].clone()])
+            .await
+            .unwrap();
+        assert_eq!(spent, vec![y(2)]);
+        assert!(db.get_spent_proof_by_y(&y(2)).await.unwrap().is_none());
+        assert_eq!(db.get_spent_count_by_keyset(&keyset_id).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_add_pending_proofs_if_unspent() {
+        let path = TempPath::new("pending");
+        let db = MintRedbDatabase::new(path.as_str()).unwrap();
+        let keyset_id = Id::from_str("009a1f293253e41e").unwrap();
+        let proofs: Proofs = (0..3)
+            .map(|_| {
+                Proof::new(
+                    Amount::from(1),
+                    keyset_id,
+                    Secret::generate(),
+                    PublicKey::from_str(
+                        "02194603ffa36356f4a56b7df9371fc3192472351453ec7398b8da8117e7c3e104",
+                    )
+                    .unwrap(),
+                )
+            })
+            .collect();
+        let y = |i: usize| proofs[i].y().unwrap();
+
+        db.add_spent_proof(proofs[0].clone()).await.unwrap();
+        let pending = db
+            .add_pending_proofs_if_unspent(vec![proofs[1].clone(), proofs[0].clone()])
+            .await
+            .unwrap();
+        assert_eq!(pending, vec![y(0)]);
+        assert!(db.get_pending_proof_by_y(&y(1)).await.unwrap().is_none());
+
+        let pending = db
+            .add_pending_proofs_if_unspent(proofs[1..].to_vec())
+            .await
+            .unwrap();
+        assert!(pending.is_empty());
+        let pending = db
+            .add_pending_proofs_if_unspent(vec![proofs[2].clone()])
+            .await
+            .unwrap();
+        assert_eq!(pending, vec![y(2)]);
+
+        // Pending proofs are only spent by the transaction releasing them
+        let spent = db
+            .add_spent_proofs_if_unspent(vec![proofs[1].clone()])
+            .await
+            .unwrap();
+        assert_eq!(spent, vec![y(1)]);
+        let mut transaction = MintTransaction::new();
+        transaction.spend(proofs[1..].to_vec());
+        transaction.release(vec![y(1)]);
+        assert_eq!(
+            db.commit_transaction(transaction).await.unwrap(),
+            vec![y(2)]
+        );
+
+        let mut transaction = MintTransaction::new();
+        transaction.spend(proofs[1..].to_vec());
+        transaction.release(vec![y(1), y(2)]);
+        assert!(db.commit_transaction(transaction).await.unwrap().is_empty());
+        assert!(db.get_pending_proof_by_y(&y(1)).await.unwrap().is_none());
+        assert_eq!(db.get_spent_count_by_keyset(&keyset_id).await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_swap_survives_crash_whole_or_not_at_all() {
+        let path = TempPath::new("swap");
+        let open = |db: &MintRedbDatabase| {
+            let db = db.clone();
+            async move {
+                Mint::new(
+                    Arc::new(db),
+                    Mnemonic::from_str(MNEMONIC).unwrap(),
+                    HashSet::new(),
+                    Amount::from(1),
+                    0.01,
+                )
+                .await
+                .unwrap()
+            }
+        };
+
+        let db = MintRedbDatabase::new(path.as_str()).unwrap();
+        let mut mint = open(&db).await;
+        let keyset = mint.pubkeys().await.unwrap().keysets.remove(0);
+        let pre_mint = PreMintSecrets::random(keyset.id, Amount::from(8)).unwrap();
+        let mut signatures = Vec::new();
+        for blinded_message in pre_mint.blinded_messages() {
+            signatures.push(mint.blind_sign(&blinded_message).await.unwrap());
+        }
+        let inputs =
+            construct_proofs(signatures, pre_mint.rs(), pre_mint.secrets(), &keyset.keys).unwrap();
+        let ys: Vec<PublicKey> = inputs.iter().map(|proof| proof.y().unwrap()).collect();
+        let outputs = PreMintSecrets::random(keyset.id, Amount::from(8))
+            .unwrap()
+            .blinded_messages();
+        let swap_request = SwapRequest::new(inputs.clone(), outputs.clone());
+
+        // Killed once the inputs are written to the spent set
+        db.crash_after_spent.store(true, Ordering::SeqCst);
+        assert!(mint
+            .process_swap_request(swap_request.clone())
+            .await
+            .is_err());
+        drop((mint, db));
+
+        // None of the swap is visible after a restart
+        let db = MintRedbDatabase::new(path.as_str()).unwrap();
+        for y in &ys {
+            assert!(db.get_spent_proof_by_y(y).await.unwrap().is_none());
+        }
+        for output in &outputs {
+            assert!(db
+                .get_blinded_signature(&output.blinded_secret)
+                .await
+                .unwrap()
+                .is_none());
+        }
+
+        let mut mint = open(&db).await;
+        let response = mint
+            .process_swap_request(swap_request.clone())
+            .await
+            .unwrap();
+        drop((mint, db));
+
+        // All of it is once the swap committed, and it is answered again
+        let db = MintRedbDatabase::new(path.as_str()).unwrap();
+        for y in &ys {
+            assert!(db.get_spent_proof_by_y(y).await.unwrap().is_some());
+        }
+        for (output, signature) in outputs.iter().zip(&response.signatures) {
+            assert_eq!(
+                db.get_blinded_signature(&output.blinded_secret)
+                    .await
+                    .unwrap()
+                    .as_ref(),
+                Some(signature)
+            );
+        }
+        let mut mint = open(&db).await;
+        assert_eq!(
+            mint.process_swap_request(swap_request).await.unwrap(),
+            response
+        );
+
+        // The same inputs with other outputs are spent
+        let outputs = PreMintSecrets::random(keyset.id, Amount::from(8))
+            .unwrap()
+            .blinded_messages();
+        assert!(matches!(
+            mint.process_swap_request(SwapRequest::new(inputs, outputs))
+                .await,
+            Err(cdk::mint::Error::TokenSpent)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_mint_quote_by_payment_hash() {
+        const INVOICE: &str = "lnbc2500u1pj48ugqdq8w3jhxaqpp5qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqsp59g4z52329g4z52329g4z52329g4z52329g4z52329g4z52329g4q9qrsgqcqzysd6exwz2yjprkglq820pffp8wcu97sxuzcgx7raensa83dnc4kpmhkuqevwf6hmv6a3mc9aegn89jv7a8e9wggdxtfsjwrur7n6sx4rsq7rvjx3";
+
+        let path = TempPath::new("quotes");
+        let db = MintRedbDatabase::new(path.as_str()).unwrap();
+        let quote = |request: &str| {
+            MintQuote::new(
+                request.to_string(),
+                CurrencyUnit::Sat,
+                Amount::from(2500),
+                0,
+                None,
+            )
+        };
+
+        let first = quote(INVOICE);
+        let payment_hash = first.payment_hash().unwrap();
+        db.add_mint_quote(first.clone()).await.unwrap();
+        db.add_mint_quote(quote("not an invoice")).await.unwrap();
+        assert_eq!(
+            db.get_mint_quote_by_payment_hash(&payment_hash)
+                .await
+                .unwrap(),
+            Some(first.clone())
+        );
+        assert!(db
+            .get_mint_quote_by_payment_hash(&Sha256::hash(b"unknown"))
+            .await
+            .unwrap()
+            .is_none());
+
+        // Updates keep the entry, removing an older quote leaves the latest
+        let later = quote(INVOICE);
+        db.add_mint_quote(later.clone()).await.unwrap();
+        let mut paid = first.clone();
+        paid.paid = true;
+        db.add_mint_quote(paid).await.unwrap();
+        db.remove_mint_quote(&first.id).await.unwrap();
+        assert_eq!(
+            db.get_mint_quote_by_payment_hash(&payment_hash)
+                .await
+                .unwrap(),
+            Some(later.clone())
+        );
+        db.remove_mint_quotes(std::slice::from_ref(&later.id))
+            .await
+            .unwrap();
+        assert!(db
+            .get_mint_quote_by_payment_hash(&payment_hash)
+            .await
+            .unwrap()
+            .is_none());
+
+        // A version 1 database is indexed when opened
+        db.add_mint_quote(first.clone()).await.unwrap();
+        drop(db);
+        {
+            let db = Database::create(path.as_str()).unwrap();
+            let write_txn = db.begin_write().unwrap();
+            write_txn
+                .delete_table(MINT_QUOTE_PAYMENT_HASHES_TABLE)
+                .unwrap();
+            write_txn
+                .open_table(CONFIG_TABLE)
+                .unwrap()
+                .insert("db_version", "1")
+                .unwrap();
+            write_txn.commit().unwrap();
+        }
+        let db = MintRedbDatabase::new(path.as_str()).unwrap();
+        assert_eq!(
+            db.get_mint_quote_by_payment_hash(&payment_hash)
+                .await
+                .unwrap(),
+            Some(first)
+        );
+    }
 }
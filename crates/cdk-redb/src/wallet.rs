@@ -4,10 +4,15 @@ use std::sync::Arc;
 
 use async_trait::async_trait;
 use cdk::cdk_database::{self, WalletDatabase};
-use cdk::nuts::{Id, KeySetInfo, Keys, MintInfo, Proofs};
-use cdk::types::{MeltQuote, MintQuote};
+use cdk::nuts::{Id, KeySetInfo, Keys, MintInfo, Proof, Proofs, PublicKey};
+use cdk::secp256k1::hashes::Hash;
+use cdk::types::{MeltQuote, MintQuote, ReceivedToken, Transaction};
 use cdk::url::UncheckedUrl;
-use redb::{Database, MultimapTableDefinition, ReadableTable, TableDefinition};
+use cdk::Sha256;
+use redb::{
+    Database, MultimapTable, MultimapTableDefinition, ReadableMultimapTable, ReadableTable,
+    TableDefinition,
+};
 use tokio::sync::Mutex;
 
 use super::error::Error;
@@ -21,17 +26,81 @@ const MINT_KEYS_TABLE: TableDefinition<&str, &str> = TableDefinition::new("mint_
 const PROOFS_TABLE: MultimapTableDefinition<&str, &str> = MultimapTableDefinition::new("proofs");
 const PENDING_PROOFS_TABLE: MultimapTableDefinition<&str, &str> =
     MultimapTableDefinition::new("pending_proofs");
+const RESERVED_PROOFS_TABLE: MultimapTableDefinition<&str, &str> =
+    MultimapTableDefinition::new("reserved_proofs");
 const CONFIG_TABLE: TableDefinition<&str, &str> = TableDefinition::new("config");
 const KEYSET_COUNTER: TableDefinition<&str, u64> = TableDefinition::new("keyset_counter");
+const TRANSACTIONS_TABLE: TableDefinition<&str, &str> = TableDefinition::new("transactions");
+const RECEIVED_TOKENS_TABLE: TableDefinition<[u8; 32], &str> =
+    TableDefinition::new("received_tokens");
+/// Fingerprint of the received token of each proof `Y`
+const RECEIVED_YS_TABLE: TableDefinition<[u8; 33], [u8; 32]> = TableDefinition::new("received_ys");
 
 const DATABASE_VERSION: u64 = 0;
 
+/// Stored JSON of the proofs of `mint_url` by their `Y`
+fn stored_proofs(
+    table: &MultimapTable<&str, &str>,
+    mint_url: &str,
+) -> Result<HashMap<PublicKey, String>, cdk_database::Error> {
+    let mut stored = HashMap::new();
+
+    for value in table.get(mint_url).map_err(Error::from)? {
+        let json = value.map_err(Error::from)?.value().to_string();
+        if let Ok(proof) = serde_json::from_str::<Proof>(&json) {
+            stored.insert(proof.y().map_err(Error::from)?, json);
+        }
+    }
+
+    Ok(stored)
+}
+
+/// Add `proofs` whose `Y` is not stored yet
+fn insert_proofs(
+    table: &mut MultimapTable<&str, &str>,
+    mint_url: &str,
+    proofs: Proofs,
+) -> Result<(), cdk_database::Error> {
+    let mut stored = stored_proofs(table, mint_url)?;
+
+    for proof in proofs {
+        let y = proof.y().map_err(Error::from)?;
+        if stored.contains_key(&y) {
+            continue;
+        }
+
+        let json = serde_json::to_string(&proof).map_err(Error::from)?;
+        table.insert(mint_url, json.as_str()).map_err(Error::from)?;
+        stored.insert(y, json);
+    }
+
+    Ok(())
+}
+
+/// Remove the stored proofs with the `Y`s of `proofs`
+fn delete_proofs(
+    table: &mut MultimapTable<&str, &str>,
+    mint_url: &str,
+    proofs: &Proofs,
+) -> Result<(), cdk_database::Error> {
+    let mut stored = stored_proofs(table, mint_url)?;
+
+    for proof in proofs {
+        if let Some(json) = stored.remove(&proof.y().map_err(Error::from)?) {
+            table.remove(mint_url, json.as_str()).map_err(Error::from)?;
+        }
+    }
+
+    Ok(())
+}
+
 #[derive(Debug, Clone)]
 pub struct RedbWalletDatabase {
     db: Arc<Mutex<Database>>,
 }
 
 impl RedbWalletDatabase {
+    #[allow(clippy::result_large_err)]
     pub fn new(path: &str) -> Result<Self, Error> {
         let db = Database::create(path)?;
 
@@ -47,11 +116,18 @@ impl RedbWalletDatabase {
             match db_version {
                 Some(db_version) => {
                     let current_file_version = u64::from_str(&db_version)?;
-                    if current_file_version.ne(&DATABASE_VERSION) {
-                        // Database needs to be upgraded
-                        todo!()
+                    if current_file_version > DATABASE_VERSION {
+                        return Err(Error::UnsupportedDatabaseVersion {
+                            found: current_file_version,
+                            supported: DATABASE_VERSION,
+                        });
                     }
                     let _ = write_txn.open_table(KEYSET_COUNTER)?;
+                    let _ = write_txn.open_multimap_table(PENDING_PROOFS_TABLE)?;
+                    let _ = write_txn.open_multimap_table(RESERVED_PROOFS_TABLE)?;
+                    let _ = write_txn.open_table(TRANSACTIONS_TABLE)?;
+                    let _ = write_txn.open_table(RECEIVED_TOKENS_TABLE)?;
+                    let _ = write_txn.open_table(RECEIVED_YS_TABLE)?;
                 }
                 None => {
                     // Open all tables to init a new db
@@ -61,7 +137,12 @@ impl RedbWalletDatabase {
                     let _ = write_txn.open_table(MELT_QUOTES_TABLE)?;
                     let _ = write_txn.open_table(MINT_KEYS_TABLE)?;
                     let _ = write_txn.open_multimap_table(PROOFS_TABLE)?;
+                    let _ = write_txn.open_multimap_table(PENDING_PROOFS_TABLE)?;
+                    let _ = write_txn.open_multimap_table(RESERVED_PROOFS_TABLE)?;
                     let _ = write_txn.open_table(KEYSET_COUNTER)?;
+                    let _ = write_txn.open_table(TRANSACTIONS_TABLE)?;
+                    let _ = write_txn.open_table(RECEIVED_TOKENS_TABLE)?;
+                    let _ = write_txn.open_table(RECEIVED_YS_TABLE)?;
                     table.insert("db_version", "0")?;
                 }
             }
@@ -120,10 +201,7 @@ impl WalletDatabase for RedbWalletDatabase {
 
     async fn get_mints(&self) -> Result<HashMap<UncheckedUrl, Option<MintInfo>>, Self::Err> {
         let db = self.db.lock().await;
-        let read_txn = db
-            .begin_read()
-            .map_err(Into::<Error>::into)
-            .map_err(Error::from)?;
+        let read_txn = db.begin_read().map_err(Into::<Error>::into)?;
         let table = read_txn.open_table(MINTS_TABLE).map_err(Error::from)?;
 
         let mints = table
@@ -155,6 +233,25 @@ impl WalletDatabase for RedbWalletDatabase {
                 .open_multimap_table(MINT_KEYSETS_TABLE)
                 .map_err(Error::from)?;
 
+            // A keyset sent again replaces the stored one, which may have been active
+            let mut stale = Vec::new();
+            for value in table
+                .get(mint_url.to_string().as_str())
+                .map_err(Error::from)?
+            {
+                let json = value.map_err(Error::from)?.value().to_string();
+                if let Ok(stored) = serde_json::from_str::<KeySetInfo>(&json) {
+                    if keysets.iter().any(|keyset| keyset.id == stored.id) {
+                        stale.push(json);
+                    }
+                }
+            }
+            for json in stale {
+                table
+                    .remove(mint_url.to_string().as_str(), json.as_str())
+                    .map_err(Error::from)?;
+            }
+
             for keyset in keysets {
                 table
                     .insert(
@@ -349,14 +446,7 @@ impl WalletDatabase for RedbWalletDatabase {
                 .open_multimap_table(PROOFS_TABLE)
                 .map_err(Error::from)?;
 
-            for proof in proofs {
-                table
-                    .insert(
-                        mint_url.to_string().as_str(),
-                        serde_json::to_string(&proof).map_err(Error::from)?.as_str(),
-                    )
-                    .map_err(Error::from)?;
-            }
+            insert_proofs(&mut table, mint_url.to_string().as_str(), proofs)?;
         }
         write_txn.commit().map_err(Error::from)?;
 
@@ -394,14 +484,7 @@ impl WalletDatabase for RedbWalletDatabase {
                 .open_multimap_table(PROOFS_TABLE)
                 .map_err(Error::from)?;
 
-            for proof in proofs {
-                table
-                    .remove(
-                        mint_url.to_string().as_str(),
-                        serde_json::to_string(&proof).map_err(Error::from)?.as_str(),
-                    )
-                    .map_err(Error::from)?;
-            }
+            delete_proofs(&mut table, mint_url.to_string().as_str(), proofs)?;
         }
         write_txn.commit().map_err(Error::from)?;
 
@@ -422,14 +505,7 @@ impl WalletDatabase for RedbWalletDatabase {
                 .open_multimap_table(PENDING_PROOFS_TABLE)
                 .map_err(Error::from)?;
 
-            for proof in proofs {
-                table
-                    .insert(
-                        mint_url.to_string().as_str(),
-                        serde_json::to_string(&proof).map_err(Error::from)?.as_str(),
-                    )
-                    .map_err(Error::from)?;
-            }
+            insert_proofs(&mut table, mint_url.to_string().as_str(), proofs)?;
         }
         write_txn.commit().map_err(Error::from)?;
 
@@ -470,14 +546,69 @@ impl WalletDatabase for RedbWalletDatabase {
                 .open_multimap_table(PENDING_PROOFS_TABLE)
                 .map_err(Error::from)?;
 
-            for proof in proofs {
-                table
-                    .remove(
-                        mint_url.to_string().as_str(),
-                        serde_json::to_string(&proof).map_err(Error::from)?.as_str(),
-                    )
-                    .map_err(Error::from)?;
-            }
+            delete_proofs(&mut table, mint_url.to_string().as_str(), proofs)?;
+        }
+        write_txn.commit().map_err(Error::from)?;
+
+        Ok(())
+    }
+
+    async fn add_reserved_proofs(
+        &self,
+        mint_url: UncheckedUrl,
+        proofs: Proofs,
+    ) -> Result<(), Self::Err> {
+        let db = self.db.lock().await;
+
+        let write_txn = db.begin_write().map_err(Error::from)?;
+
+        {
+            let mut table = write_txn
+                .open_multimap_table(RESERVED_PROOFS_TABLE)
+                .map_err(Error::from)?;
+
+            insert_proofs(&mut table, mint_url.to_string().as_str(), proofs)?;
+        }
+        write_txn.commit().map_err(Error::from)?;
+
+        Ok(())
+    }
+
+    async fn get_reserved_proofs(
+        &self,
+        mint_url: UncheckedUrl,
+    ) -> Result<Option<Proofs>, Self::Err> {
+        let db = self.db.lock().await;
+        let read_txn = db.begin_read().map_err(Error::from)?;
+        let table = read_txn
+            .open_multimap_table(RESERVED_PROOFS_TABLE)
+            .map_err(Error::from)?;
+
+        let proofs = table
+            .get(mint_url.to_string().as_str())
+            .map_err(Error::from)?
+            .flatten()
+            .flat_map(|k| serde_json::from_str(k.value()))
+            .collect();
+
+        Ok(proofs)
+    }
+
+    async fn remove_reserved_proofs(
+        &self,
+        mint_url: UncheckedUrl,
+        proofs: &Proofs,
+    ) -> Result<(), Self::Err> {
+        let db = self.db.lock().await;
+
+        let write_txn = db.begin_write().map_err(Error::from)?;
+
+        {
+            let mut table = write_txn
+                .open_multimap_table(RESERVED_PROOFS_TABLE)
+                .map_err(Error::from)?;
+
+            delete_proofs(&mut table, mint_url.to_string().as_str(), proofs)?;
         }
         write_txn.commit().map_err(Error::from)?;
 
@@ -515,6 +646,21 @@ impl WalletDatabase for RedbWalletDatabase {
         Ok(())
     }
 
+    async fn set_keyset_counter(&self, keyset_id: &Id, counter: u64) -> Result<(), Self::Err> {
+        let db = self.db.lock().await;
+
+        let write_txn = db.begin_write().map_err(Error::from)?;
+        {
+            let mut table = write_txn.open_table(KEYSET_COUNTER).map_err(Error::from)?;
+            table
+                .insert(keyset_id.to_string().as_str(), counter)
+                .map_err(Error::from)?;
+        }
+        write_txn.commit().map_err(Error::from)?;
+
+        Ok(())
+    }
+
     async fn get_keyset_counter(&self, keyset_id: &Id) -> Result<Option<u64>, Self::Err> {
         let db = self.db.lock().await;
         let read_txn = db.begin_read().map_err(Error::from)?;
@@ -526,4 +672,141 @@ impl WalletDatabase for RedbWalletDatabase {
 
         Ok(counter.map(|c| c.value()))
     }
+
+    async fn add_transaction(&self, transaction: Transaction) -> Result<(), Self::Err> {
+        let db = self.db.lock().await;
+        let write_txn = db.begin_write().map_err(Error::from)?;
+
+        {
+            let mut table = write_txn
+                .open_table(TRANSACTIONS_TABLE)
+                .map_err(Error::from)?;
+            table
+                .insert(
+                    transaction.id.as_str(),
+                    serde_json::to_string(&transaction)
+                        .map_err(Error::from)?
+                        .as_str(),
+                )
+                .map_err(Error::from)?;
+        }
+
+        write_txn.commit().map_err(Error::from)?;
+
+        Ok(())
+    }
+
+    async fn get_transaction(&self, id: &str) -> Result<Option<Transaction>, Self::Err> {
+        let db = self.db.lock().await;
+        let read_txn = db.begin_read().map_err(Error::from)?;
+        let table = read_txn
+            .open_table(TRANSACTIONS_TABLE)
+            .map_err(Error::from)?;
+
+        if let Some(transaction) = table.get(id).map_err(Error::from)? {
+            return Ok(serde_json::from_str(transaction.value()).map_err(Error::from)?);
+        }
+
+        Ok(None)
+    }
+
+    async fn get_transactions(&self) -> Result<Vec<Transaction>, Self::Err> {
+        let db = self.db.lock().await;
+        let read_txn = db.begin_read().map_err(Error::from)?;
+        let table = read_txn
+            .open_table(TRANSACTIONS_TABLE)
+            .map_err(Error::from)?;
+
+        let transactions = table
+            .iter()
+            .map_err(Error::from)?
+            .flatten()
+            .flat_map(|(_, transaction)| serde_json::from_str(transaction.value()))
+            .collect();
+
+        Ok(transactions)
+    }
+
+    async fn add_received_token(
+        &self,
+        token: ReceivedToken,
+    ) -> Result<Vec<ReceivedToken>, Self::Err> {
+        let db = self.db.lock().await;
+        let write_txn = db.begin_write().map_err(Error::from)?;
+
+        let mut overlapping = Vec::new();
+        {
+            let mut tokens = write_txn
+                .open_table(RECEIVED_TOKENS_TABLE)
+                .map_err(Error::from)?;
+            let mut ys = write_txn
+                .open_table(RECEIVED_YS_TABLE)
+                .map_err(Error::from)?;
+
+            let mut fingerprints = Vec::new();
+            for y in &token.ys {
+                if let Some(fingerprint) = ys.get(y.to_bytes()).map_err(Error::from)? {
+                    let fingerprint = fingerprint.value();
+                    if !fingerprints.contains(&fingerprint) {
+                        fingerprints.push(fingerprint);
+                    }
+                }
+            }
+            for fingerprint in fingerprints {
+                if let Some(received) = tokens.get(fingerprint).map_err(Error::from)? {
+                    overlapping.push(serde_json::from_str(received.value()).map_err(Error::from)?);
+                }
+            }
+
+            if overlapping.is_empty() {
+                let fingerprint = token.fingerprint.to_byte_array();
+                tokens
+                    .insert(
+                        fingerprint,
+                        serde_json::to_string(&token).map_err(Error::from)?.as_str(),
+                    )
+                    .map_err(Error::from)?;
+                for y in &token.ys {
+                    ys.insert(y.to_bytes(), fingerprint).map_err(Error::from)?;
+                }
+            }
+        }
+
+        if overlapping.is_empty() {
+            write_txn.commit().map_err(Error::from)?;
+        } else {
+            write_txn.abort().map_err(Error::from)?;
+        }
+
+        Ok(overlapping)
+    }
+
+    async fn remove_received_token(&self, fingerprint: &Sha256) -> Result<(), Self::Err> {
+        let db = self.db.lock().await;
+        let write_txn = db.begin_write().map_err(Error::from)?;
+
+        {
+            let mut tokens = write_txn
+                .open_table(RECEIVED_TOKENS_TABLE)
+                .map_err(Error::from)?;
+            let removed = tokens
+                .remove(fingerprint.to_byte_array())
+                .map_err(Error::from)?
+                .map(|received| serde_json::from_str::<ReceivedToken>(received.value()));
+
+            if let Some(received) = removed {
+                let received = received.map_err(Error::from)?;
+                let mut ys = write_txn
+                    .open_table(RECEIVED_YS_TABLE)
+                    .map_err(Error::from)?;
+                for y in received.ys {
+                    ys.remove(y.to_bytes()).map_err(Error::from)?;
+                }
+            }
+        }
+
+        write_txn.commit().map_err(Error::from)?;
+
+        Ok(())
+    }
 }
@@ -0,0 +1,87 @@
+/*
+ * C interface of cdk, enabled with the `ffi` feature
+ *
+ * Link against the static or dynamic library built with
+ *   cargo rustc -p cdk --release --no-default-features --features ffi --crate-type staticlib
+ *
+ * Tokens and proofs are integer handles. Every handle returned must be freed
+ * exactly once; using or freeing a freed handle returns
+ * CDK_ERROR_INVALID_HANDLE. Fallible functions return CDK_OK or an error code,
+ * cdk_last_error_message() describes the failure.
+ *
+ * Keep in sync with src/ffi.rs, checked by its tests.
+ */
+
+#ifndef CDK_H
+#define CDK_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define CDK_OK 0
+/* A required pointer argument was null */
+#define CDK_ERROR_NULL_POINTER 1
+/* A string argument was not valid UTF-8 */
+#define CDK_ERROR_INVALID_UTF8 2
+/* The handle was freed or never allocated */
+#define CDK_ERROR_INVALID_HANDLE 3
+#define CDK_ERROR_INVALID_TOKEN 4
+#define CDK_ERROR_INVALID_KEYSET 5
+/* A proof has no DLEQ proof, or it does not verify */
+#define CDK_ERROR_DLEQ 6
+/* The output buffer is too small, the required length is still written */
+#define CDK_ERROR_BUFFER_TOO_SMALL 7
+/* The library panicked, this is a bug */
+#define CDK_ERROR_PANIC 8
+
+/* Handle to a parsed token */
+typedef uint64_t CdkToken;
+/* Handle to a set of proofs */
+typedef uint64_t CdkProofs;
+
+/*
+ * Description of the last error on the calling thread, or NULL if no call on
+ * this thread failed yet. Owned by the library, valid until the next failing
+ * call on the same thread.
+ */
+const char *cdk_last_error_message(void);
+
+/* Parse a serialized token into a new handle */
+int32_t cdk_token_parse(const char *token, CdkToken *out);
+
+/* Total amount of all proofs in the token */
+int32_t cdk_token_amount(CdkToken token, uint64_t *out);
+
+/*
+ * Mint URL of the token as a NUL-terminated string written to buf. The length
+ * without the NUL is written to out_len even if buf is too small, call with a
+ * NULL buf and buf_len 0 to query the size.
+ */
+int32_t cdk_token_mint_url(CdkToken token, char *buf, size_t buf_len, size_t *out_len);
+
+/* Proofs of all mints in the token as a new handle, independent of the token */
+int32_t cdk_token_proofs(CdkToken token, CdkProofs *out);
+
+/*
+ * Verify the DLEQ proof of every proof against a keyset, either the
+ * {"id", "unit", "keys"} object or the {"keysets": [...]} response of
+ * /v1/keys
+ */
+int32_t cdk_proofs_verify_dleq(CdkProofs proofs, const char *keyset_json);
+
+/* Total amount of the proofs */
+int32_t cdk_proofs_amount(CdkProofs proofs, uint64_t *out);
+
+int32_t cdk_token_free(CdkToken token);
+
+int32_t cdk_proofs_free(CdkProofs proofs);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* CDK_H */
@@ -0,0 +1,195 @@
+//! Mint accounting counters
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::nuts::CurrencyUnit;
+use crate::types::MeltSettlement;
+use crate::Amount;
+
+/// Running totals of the melts settled in one unit
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MeltTotals {
+    pub melts: u64,
+    /// Msat sent over Lightning
+    pub amount_msat: u64,
+    /// Lightning fees in msat
+    pub fee_msat: u64,
+    /// Sats of the inputs spent
+    pub charged: Amount,
+    /// Change signed for the wallets
+    pub change: Amount,
+    /// Change not returned because too few outputs were provided
+    pub burnt: Amount,
+    /// Sub-sat remainders of [MeltTotals::charged] over the amount and fee
+    pub remainder_msat: u64,
+}
+
+impl MeltTotals {
+    /// Msat kept by the mint, the remainders and the burnt change
+    pub fn profit_msat(&self) -> u64 {
+        self.remainder_msat.saturating_add(self.burnt.to_msat())
+    }
+}
+
+/// Counters of a [Mint](crate::mint::Mint) since it was created
+///
+/// Counters are kept in memory and saturate instead of overflowing.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Accounting {
+    melts: HashMap<CurrencyUnit, MeltTotals>,
+}
+
+impl Accounting {
+    /// Totals of the melts settled in `unit`
+    pub fn melts(&self, unit: &CurrencyUnit) -> MeltTotals {
+        self.melts.get(unit).cloned().unwrap_or_default()
+    }
+
+    /// Record a settled melt of which `returned` sats of change were signed
+    pub fn record_melt(
+        &mut self,
+        unit: CurrencyUnit,
+        settlement: &MeltSettlement,
+        returned: Amount,
+    ) {
+        let totals = self.melts.entry(unit).or_default();
+        totals.melts = totals.melts.saturating_add(1);
+        totals.amount_msat = totals.amount_msat.saturating_add(settlement.amount_msat);
+        totals.fee_msat = totals.fee_msat.saturating_add(settlement.fee_msat);
+        totals.charged = totals.charged.saturating_add(settlement.charged);
+        totals.change = totals.change.saturating_add(returned);
+        totals.burnt = totals
+            .burnt
+            .saturating_add(settlement.change.saturating_sub(returned));
+        totals.remainder_msat = totals
+            .remainder_msat
+            .saturating_add(settlement.remainder_msat);
+    }
+}
+
+/// Outstanding ecash of each unit as the operations of a mint account for it
+///
+/// Opened from the database and moved by every deposit and withdrawal, so it
+/// can be compared with the blind signatures and spent proofs the database
+/// holds afterwards. Outstanding ecash is signed, as swapping proofs the
+/// mint never recorded signing lowers it below zero.
+#[cfg(feature = "invariant-checks")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Ledger {
+    outstanding: HashMap<CurrencyUnit, i128>,
+}
+
+#[cfg(feature = "invariant-checks")]
+impl Ledger {
+    pub fn open(outstanding: HashMap<CurrencyUnit, i128>) -> Self {
+        Self { outstanding }
+    }
+
+    pub fn outstanding(&self, unit: &CurrencyUnit) -> i128 {
+        self.outstanding.get(unit).copied().unwrap_or_default()
+    }
+
+    pub fn units(&self) -> impl Iterator<Item = &CurrencyUnit> {
+        self.outstanding.keys()
+    }
+
+    /// Ecash issued for `amount` paid to the mint
+    pub fn deposit(&mut self, unit: &CurrencyUnit, amount: Amount) {
+        *self.outstanding.entry(unit.clone()).or_default() += i128::from(u64::from(amount));
+    }
+
+    /// Ecash redeemed and not reissued, whether melted or kept as fees
+    pub fn withdraw(&mut self, unit: &CurrencyUnit, amount: Amount) {
+        *self.outstanding.entry(unit.clone()).or_default() -= i128::from(u64::from(amount));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_melt_settlement_msat_remainders() {
+        // (proofs, amount_msat, fee_msat, charged, change, remainder_msat)
+        let cases: [(u64, u64, u64, u64, u64, u64); 14] = [
+            (10, 0, 0, 0, 10, 0),
+            (10, 999, 0, 1, 9, 1),
+            (10, 1000, 0, 1, 9, 0),
+            (10, 1001, 0, 2, 8, 999),
+            (10, 500, 499, 1, 9, 1),
+            (10, 500, 500, 1, 9, 0),
+            (10, 500, 501, 2, 8, 999),
+            (10, 0, 999, 1, 9, 1),
+            (10, 1999, 1, 2, 8, 0),
+            (1012, 1_000_500, 2_250, 1003, 9, 250),
+            (1003, 1_000_500, 2_250, 1003, 0, 250),
+            (1002, 1_000_999, 1_001, 1002, 0, 0),
+            (5, 4_000, 999, 5, 0, 1),
+            (1, 1, 0, 1, 0, 999),
+        ];
+
+        for (proofs, amount_msat, fee_msat, charged, change, remainder_msat) in cases {
+            let settlement =
+                MeltSettlement::new(Amount::from(proofs), amount_msat, fee_msat).unwrap();
+            assert_eq!(
+                settlement,
+                MeltSettlement {
+                    amount_msat,
+                    fee_msat,
+                    charged: Amount::from(charged),
+                    change: Amount::from(change),
+                    remainder_msat,
+                },
+                "{proofs} sat paying {amount_msat} + {fee_msat} msat"
+            );
+            // Every msat of the inputs is charged, changed or kept
+            assert_eq!(
+                proofs * 1000,
+                amount_msat + fee_msat + remainder_msat + change * 1000
+            );
+            // and the reported fee leaves the change of the whole sats
+            assert_eq!(
+                Amount::from(proofs),
+                Amount::from_msat_ceil(amount_msat) + settlement.fee_paid() + settlement.change
+            );
+        }
+    }
+
+    #[test]
+    fn test_melt_settlement_never_negative() {
+        // A single msat over the inputs costs a whole sat
+        assert_eq!(MeltSettlement::new(Amount::from(1), 1000, 1), None);
+        assert_eq!(MeltSettlement::new(Amount::from(4), 4_000, 1), None);
+        assert_eq!(MeltSettlement::new(Amount::ZERO, 1, 0), None);
+        assert_eq!(MeltSettlement::new(Amount::from(10), u64::MAX, 1), None);
+    }
+
+    #[test]
+    fn test_record_melt() {
+        let mut accounting = Accounting::default();
+        assert_eq!(accounting.melts(&CurrencyUnit::Sat), MeltTotals::default());
+
+        let settlement = MeltSettlement::new(Amount::from(1012), 1_000_500, 2_250).unwrap();
+        accounting.record_melt(CurrencyUnit::Sat, &settlement, Amount::from(8));
+        let settlement = MeltSettlement::new(Amount::from(3), 1_001, 0).unwrap();
+        accounting.record_melt(CurrencyUnit::Sat, &settlement, Amount::from(1));
+
+        let totals = accounting.melts(&CurrencyUnit::Sat);
+        assert_eq!(
+            totals,
+            MeltTotals {
+                melts: 2,
+                amount_msat: 1_001_501,
+                fee_msat: 2_250,
+                charged: Amount::from(1005),
+                change: Amount::from(9),
+                burnt: Amount::from(1),
+                remainder_msat: 1_249,
+            }
+        );
+        assert_eq!(totals.profit_msat(), 2_249);
+        assert_eq!(accounting.melts(&CurrencyUnit::Usd), MeltTotals::default());
+    }
+}
@@ -1,25 +1,413 @@
 use std::fmt;
+use std::num::ParseIntError;
+use std::str::FromStr;
 
-use serde::{Deserialize, Serialize};
+use bitcoin::secp256k1::rand::rngs::StdRng;
+use bitcoin::secp256k1::rand::{self, Rng, SeedableRng};
+use serde::{de, Deserialize, Deserializer, Serialize};
+use thiserror::Error;
+
+use crate::nuts::CurrencyUnit;
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Amounts of different units combined
+    #[error("Cannot combine `{0}` with `{1}`")]
+    UnitMismatch(CurrencyUnit, CurrencyUnit),
+    /// No fixed conversion between the units
+    #[error("Cannot convert `{from}` to `{to}`")]
+    UnsupportedConversion {
+        from: CurrencyUnit,
+        to: CurrencyUnit,
+    },
+    /// Exchange rate negative or not finite
+    #[error("Invalid exchange rate")]
+    InvalidRate,
+    #[error("Amount Overflow")]
+    AmountOverflow,
+    /// Inputs of a swap do not cover the input fee and the amount sent
+    #[error("Inputs of `{inputs}` do not cover `{send}` and a fee of `{fee}`")]
+    InsufficientInputs {
+        inputs: Amount,
+        send: Amount,
+        fee: Amount,
+    },
+}
 
 /// Number of satoshis
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+///
+/// Serialized as an integer. Deserializes from an integer or a string of
+/// decimal digits, as some wallets send amounts and keyset amount keys as
+/// strings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
 #[serde(transparent)]
 pub struct Amount(u64);
 
 impl Amount {
     pub const ZERO: Amount = Amount(0);
 
-    /// Split into parts that are powers of two
+    /// Millisatoshis in a satoshi
+    pub const MSAT_PER_SAT: u64 = 1000;
+
+    /// Sats needed to cover `msat`, any fraction of a sat is rounded up
+    ///
+    /// For what is charged, so the mint is never paid less than it spends.
+    pub fn from_msat_ceil(msat: u64) -> Self {
+        Self(msat / Self::MSAT_PER_SAT + u64::from(msat % Self::MSAT_PER_SAT != 0))
+    }
+
+    /// Sats `msat` is worth, any fraction of a sat is dropped
+    ///
+    /// For what is received, so a wallet never expects more than it gets.
+    pub fn from_msat_floor(msat: u64) -> Self {
+        Self(msat / Self::MSAT_PER_SAT)
+    }
+
+    /// Millisatoshis, clamping at [`u64::MAX`]
+    ///
+    /// Only for bounds and totals where clamping is harmless, amounts that
+    /// are paid or invoiced use [Amount::checked_to_msat].
+    pub fn to_msat(self) -> u64 {
+        self.0.saturating_mul(Self::MSAT_PER_SAT)
+    }
+
+    /// Millisatoshis, [`None`] if they do not fit in a [`u64`]
+    pub fn checked_to_msat(self) -> Option<u64> {
+        self.0.checked_mul(Self::MSAT_PER_SAT)
+    }
+
+    /// Split into parts that are powers of two, largest first
     pub fn split(&self) -> Vec<Self> {
-        let sats = self.0;
-        (0_u64..64)
-            .rev()
-            .filter_map(|bit| {
-                let part = 1 << bit;
-                ((sats & part) == part).then_some(Self::from(part))
-            })
-            .collect()
+        let mut parts = Vec::with_capacity(self.0.count_ones() as usize);
+        parts.extend(self.split_iter());
+        parts
+    }
+
+    /// Iterate over the parts of [Amount::split] without allocating
+    pub fn split_iter(&self) -> impl Iterator<Item = Self> {
+        let mut sats = self.0;
+
+        std::iter::from_fn(move || {
+            if sats == 0 {
+                return None;
+            }
+
+            let part = 1 << (u64::BITS - 1 - sats.leading_zeros());
+            sats ^= part;
+            Some(Self(part))
+        })
+    }
+
+    /// Split as `target` asks, largest first
+    pub fn split_targeted(&self, target: &SplitTarget) -> Vec<Self> {
+        match target {
+            SplitTarget::None => self.split(),
+            SplitTarget::Random { seed } => {
+                self.split_random(*seed, SplitTarget::DEFAULT_INFLATION)
+            }
+            SplitTarget::Value(value) => self.split_value(*value),
+        }
+    }
+
+    /// Split into as many parts of `value` as fit, each split into powers of
+    /// two, and the powers of two of the rest, largest first
+    ///
+    /// A zero `value` splits as [Amount::split].
+    pub fn split_value(&self, value: Amount) -> Vec<Self> {
+        if value == Self::ZERO {
+            return self.split();
+        }
+
+        let mut parts = Vec::new();
+        for _ in 0..self.0 / value.0 {
+            parts.extend(value.split_iter());
+        }
+        parts.extend(Self(self.0 % value.0).split_iter());
+        parts.sort_unstable_by(|a, b| b.cmp(a));
+
+        parts
+    }
+
+    /// Split into powers of two, some halved at random, largest first
+    ///
+    /// Starts from [Amount::split] and halves randomly chosen parts until a
+    /// random number of parts is reached, at most `inflation` times as many
+    /// as [Amount::split] gives. The same `seed` always gives the same parts.
+    pub fn split_random(&self, seed: Option<u64>, inflation: usize) -> Vec<Self> {
+        match seed {
+            Some(seed) => self.split_random_with(&mut StdRng::seed_from_u64(seed), inflation),
+            None => self.split_random_with(&mut rand::thread_rng(), inflation),
+        }
+    }
+
+    fn split_random_with<R: Rng>(&self, rng: &mut R, inflation: usize) -> Vec<Self> {
+        let mut parts = self.split();
+        let max_parts = parts.len().saturating_mul(inflation.max(1));
+        let target = rng.gen_range(parts.len()..=max_parts);
+
+        while parts.len() < target {
+            let splittable: Vec<usize> = (0..parts.len()).filter(|i| parts[*i].0 > 1).collect();
+            if splittable.is_empty() {
+                break;
+            }
+
+            let i = splittable[rng.gen_range(0..splittable.len())];
+            parts[i] = Self(parts[i].0 / 2);
+            parts.push(parts[i]);
+        }
+
+        parts.sort_unstable_by(|a, b| b.cmp(a));
+        parts
+    }
+
+    /// Outputs of a swap of inputs worth `inputs_total` that pay `fee`
+    ///
+    /// `send`, or everything left after the fee if [None], and the rest kept
+    /// are split as `target` asks, so the outputs add up to the inputs less
+    /// the fee.
+    pub fn split_with_fee(
+        inputs_total: Amount,
+        fee: Amount,
+        send: Option<Amount>,
+        target: &SplitTarget,
+    ) -> Result<SwapSplit, Error> {
+        let insufficient = || Error::InsufficientInputs {
+            inputs: inputs_total,
+            send: send.unwrap_or(Amount::ZERO),
+            fee,
+        };
+        let available = inputs_total.checked_sub(fee).ok_or_else(insufficient)?;
+        let send = send.unwrap_or(available);
+        let keep = available.checked_sub(send).ok_or_else(insufficient)?;
+
+        Ok(SwapSplit {
+            send: send.split_targeted(target),
+            keep: keep.split_targeted(target),
+        })
+    }
+
+    /// Split greedily into `denominations`, largest first
+    ///
+    /// Returns [None] if the greedy split leaves a remainder, which cannot
+    /// happen when `1` is one of the denominations.
+    pub fn split_into(&self, denominations: &[Amount]) -> Option<Vec<Self>> {
+        let mut denominations: Vec<u64> = denominations
+            .iter()
+            .map(|d| d.0)
+            .filter(|d| *d > 0)
+            .collect();
+        denominations.sort_unstable_by(|a, b| b.cmp(a));
+        denominations.dedup();
+
+        let mut sats = self.0;
+        let mut parts = Vec::new();
+
+        for denomination in denominations {
+            let count = sats / denomination;
+            parts.extend(std::iter::repeat(Self(denomination)).take(count as usize));
+            sats %= denomination;
+        }
+
+        (sats == 0).then_some(parts)
+    }
+
+    /// Checked addition. Computes `self + other` returning [`None`] on overflow
+    pub fn checked_add(self, other: Amount) -> Option<Amount> {
+        self.0.checked_add(other.0).map(Amount)
+    }
+
+    /// Checked subtraction. Computes `self - other` returning [`None`] on underflow
+    pub fn checked_sub(self, other: Amount) -> Option<Amount> {
+        self.0.checked_sub(other.0).map(Amount)
+    }
+
+    /// Checked multiplication. Computes `self * factor` returning [`None`] on overflow
+    pub fn checked_mul(self, factor: u64) -> Option<Amount> {
+        self.0.checked_mul(factor).map(Amount)
+    }
+
+    /// Checked division. Computes `self / divisor` rounded down, returning
+    /// [`None`] if `divisor` is zero
+    pub fn checked_div(self, divisor: u64) -> Option<Amount> {
+        self.0.checked_div(divisor).map(Amount)
+    }
+
+    /// Saturating addition. Computes `self + other` clamping at [`u64::MAX`]
+    pub fn saturating_add(self, other: Amount) -> Amount {
+        Amount(self.0.saturating_add(other.0))
+    }
+
+    /// Saturating subtraction. Computes `self - other` clamping at [`Amount::ZERO`]
+    pub fn saturating_sub(self, other: Amount) -> Amount {
+        Amount(self.0.saturating_sub(other.0))
+    }
+}
+
+/// How an amount is decomposed into outputs
+///
+/// Always splitting into [Amount::split] makes amounts easy to recognise
+/// across swaps, [SplitTarget::Random] trades a few more outputs for that.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SplitTarget {
+    /// One output per power of two of the amount
+    #[default]
+    None,
+    /// Powers of two halved at random, see [Amount::split_random]
+    ///
+    /// A `seed` makes the split reproducible, for tests.
+    Random { seed: Option<u64> },
+    /// As many outputs of a value as fit, see [Amount::split_value]
+    ///
+    /// `Value(1)` keeps small proofs around for exact payments.
+    Value(Amount),
+}
+
+impl SplitTarget {
+    /// Most outputs [SplitTarget::Random] makes, as a multiple of the
+    /// outputs of [SplitTarget::None]
+    pub const DEFAULT_INFLATION: usize = 2;
+}
+
+/// Output amounts of a swap, see [Amount::split_with_fee]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SwapSplit {
+    /// Outputs of the amount sent
+    pub send: Vec<Amount>,
+    /// Outputs of the change
+    pub keep: Vec<Amount>,
+}
+
+impl SwapSplit {
+    pub fn send_total(&self) -> Amount {
+        self.send.iter().copied().sum()
+    }
+
+    pub fn keep_total(&self) -> Amount {
+        self.keep.iter().copied().sum()
+    }
+}
+
+/// [Amount] together with its [CurrencyUnit]
+///
+/// Used where amounts leave the unit of a single keyset, so amounts of
+/// different units are never added up by accident. Arithmetic refuses
+/// mismatched units with [Error::UnitMismatch], converting first is
+/// explicit.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct UnitAmount {
+    pub amount: Amount,
+    pub unit: CurrencyUnit,
+}
+
+impl UnitAmount {
+    pub fn new(amount: Amount, unit: CurrencyUnit) -> Self {
+        Self { amount, unit }
+    }
+
+    pub fn sat(amount: Amount) -> Self {
+        Self::new(amount, CurrencyUnit::Sat)
+    }
+
+    pub fn msat(amount: Amount) -> Self {
+        Self::new(amount, CurrencyUnit::Msat)
+    }
+
+    fn check_unit(&self, other: &UnitAmount) -> Result<(), Error> {
+        if self.unit != other.unit {
+            return Err(Error::UnitMismatch(self.unit.clone(), other.unit.clone()));
+        }
+
+        Ok(())
+    }
+
+    /// `self + other`, both of the same unit
+    pub fn checked_add(&self, other: &UnitAmount) -> Result<UnitAmount, Error> {
+        self.check_unit(other)?;
+        let amount = self
+            .amount
+            .checked_add(other.amount)
+            .ok_or(Error::AmountOverflow)?;
+
+        Ok(Self::new(amount, self.unit.clone()))
+    }
+
+    /// `self - other`, both of the same unit
+    pub fn checked_sub(&self, other: &UnitAmount) -> Result<UnitAmount, Error> {
+        self.check_unit(other)?;
+        let amount = self
+            .amount
+            .checked_sub(other.amount)
+            .ok_or(Error::AmountOverflow)?;
+
+        Ok(Self::new(amount, self.unit.clone()))
+    }
+
+    /// Same amount in msat, exact
+    pub fn to_msat(&self) -> Result<UnitAmount, Error> {
+        match self.unit {
+            CurrencyUnit::Msat => Ok(self.clone()),
+            CurrencyUnit::Sat => self
+                .amount
+                .checked_to_msat()
+                .map(|msat| Self::msat(Amount::from(msat)))
+                .ok_or(Error::AmountOverflow),
+            _ => Err(self.unsupported(CurrencyUnit::Msat)),
+        }
+    }
+
+    /// Amount in sat, any fraction of a msat amount rounded up
+    ///
+    /// For what has to be paid, as [Amount::from_msat_ceil].
+    pub fn to_sat_ceil(&self) -> Result<UnitAmount, Error> {
+        match self.unit {
+            CurrencyUnit::Sat => Ok(self.clone()),
+            CurrencyUnit::Msat => Ok(Self::sat(Amount::from_msat_ceil(self.amount.into()))),
+            _ => Err(self.unsupported(CurrencyUnit::Sat)),
+        }
+    }
+
+    /// Amount in sat, any fraction of a msat amount rounded down
+    ///
+    /// For what is paid out, e.g. change.
+    pub fn to_sat_floor(&self) -> Result<UnitAmount, Error> {
+        match self.unit {
+            CurrencyUnit::Sat => Ok(self.clone()),
+            CurrencyUnit::Msat => Ok(Self::sat(Amount::from_msat_floor(self.amount.into()))),
+            _ => Err(self.unsupported(CurrencyUnit::Sat)),
+        }
+    }
+
+    /// Amount in `unit` at `rate` of `unit` per one of [UnitAmount::unit],
+    /// e.g. usd cents per sat, rounded down
+    ///
+    /// For units without a fixed conversion. Sat and msat convert with
+    /// [UnitAmount::to_msat] and [UnitAmount::to_sat_ceil] or
+    /// [UnitAmount::to_sat_floor] instead.
+    pub fn to_unit(&self, unit: CurrencyUnit, rate: f64) -> Result<UnitAmount, Error> {
+        if !rate.is_finite() || rate < 0.0 {
+            return Err(Error::InvalidRate);
+        }
+
+        let converted = (u64::from(self.amount) as f64 * rate).floor();
+        // `u64::MAX as f64` is 2^64, one past the largest amount
+        if converted >= u64::MAX as f64 {
+            return Err(Error::AmountOverflow);
+        }
+
+        Ok(Self::new(Amount::from(converted as u64), unit))
+    }
+
+    fn unsupported(&self, to: CurrencyUnit) -> Error {
+        Error::UnsupportedConversion {
+            from: self.unit.clone(),
+            to,
+        }
+    }
+}
+
+impl fmt::Display for UnitAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.amount, self.unit)
     }
 }
 
@@ -69,6 +457,72 @@ impl std::ops::Sub for Amount {
     }
 }
 
+impl std::ops::SubAssign for Amount {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.0 -= rhs.0;
+    }
+}
+
+impl FromStr for Amount {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(u64::from_str(s)?))
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct AmountVisitor;
+
+        impl<'de> de::Visitor<'de> for AmountVisitor {
+            type Value = Amount;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an unsigned integer or a string of decimal digits")
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<Amount, E> {
+                Ok(Amount(value))
+            }
+
+            fn visit_i64<E>(self, value: i64) -> Result<Amount, E>
+            where
+                E: de::Error,
+            {
+                u64::try_from(value)
+                    .map(Amount)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Signed(value), &self))
+            }
+
+            fn visit_f64<E>(self, value: f64) -> Result<Amount, E>
+            where
+                E: de::Error,
+            {
+                Err(E::invalid_type(de::Unexpected::Float(value), &self))
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Amount, E>
+            where
+                E: de::Error,
+            {
+                // `u64::from_str` alone would also accept a leading `+`
+                if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(E::invalid_value(de::Unexpected::Str(value), &self));
+                }
+
+                Amount::from_str(value)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+            }
+        }
+
+        deserializer.deserialize_any(AmountVisitor)
+    }
+}
+
 impl fmt::Display for Amount {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.0)
@@ -82,10 +536,85 @@ impl core::iter::Sum for Amount {
     }
 }
 
+impl<'a> core::iter::Sum<&'a Amount> for Amount {
+    fn sum<I: Iterator<Item = &'a Amount>>(iter: I) -> Self {
+        iter.copied().sum()
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
+
+    use proptest::prelude::*;
+
     use super::*;
 
+    #[test]
+    fn test_msat_conversion() {
+        assert_eq!(Amount::from_msat_ceil(0), Amount::ZERO);
+        assert_eq!(Amount::from_msat_ceil(1), Amount::from(1));
+        assert_eq!(Amount::from_msat_ceil(999), Amount::from(1));
+        assert_eq!(Amount::from_msat_ceil(1000), Amount::from(1));
+        assert_eq!(Amount::from_msat_ceil(1_000_500), Amount::from(1001));
+        assert_eq!(Amount::from_msat_ceil(1_000_999), Amount::from(1001));
+        assert_eq!(Amount::from_msat_ceil(1_001_000), Amount::from(1001));
+        assert_eq!(
+            Amount::from_msat_ceil(u64::MAX),
+            Amount::from(u64::MAX / 1000 + 1)
+        );
+
+        assert_eq!(Amount::from(1001).to_msat(), 1_001_000);
+        assert_eq!(Amount::from(u64::MAX).to_msat(), u64::MAX);
+        assert_eq!(
+            Amount::from_msat_ceil(Amount::from(42).to_msat()),
+            Amount::from(42)
+        );
+    }
+
+    #[test]
+    fn test_msat_rounding_every_remainder() {
+        let max_sat = u64::MAX / 1000;
+
+        for base in [0, 1_000_000, (max_sat - 1) * 1000] {
+            let sats = base / 1000;
+            for remainder in 0..1000 {
+                let msat = base + remainder;
+                let up = u64::from(remainder != 0);
+
+                assert_eq!(Amount::from_msat_floor(msat), Amount::from(sats));
+                assert_eq!(Amount::from_msat_ceil(msat), Amount::from(sats + up));
+                assert!(Amount::from_msat_floor(msat).to_msat() <= msat);
+                assert!(Amount::from_msat_ceil(msat).to_msat() >= msat);
+            }
+        }
+
+        // The last whole sat below `u64::MAX` msat and the 615 msat above it
+        for msat in max_sat * 1000..=u64::MAX {
+            assert_eq!(Amount::from_msat_floor(msat), Amount::from(max_sat));
+            let ceil = if msat == max_sat * 1000 {
+                max_sat
+            } else {
+                max_sat + 1
+            };
+            assert_eq!(Amount::from_msat_ceil(msat), Amount::from(ceil));
+        }
+    }
+
+    #[test]
+    fn test_checked_msat_arithmetic() {
+        let max_sat = Amount::from(u64::MAX / 1000);
+
+        assert_eq!(max_sat.checked_to_msat(), Some(u64::MAX / 1000 * 1000));
+        assert_eq!((max_sat + Amount::from(1)).checked_to_msat(), None);
+        assert_eq!((max_sat + Amount::from(1)).to_msat(), u64::MAX);
+        assert_eq!(Amount::ZERO.checked_to_msat(), Some(0));
+
+        assert_eq!(Amount::from(7).checked_div(2), Some(Amount::from(3)));
+        assert_eq!(Amount::from(7).checked_div(0), None);
+        assert_eq!(Amount::from(u64::MAX).checked_div(1000), Some(max_sat));
+    }
+
     #[test]
     fn test_split_amount() {
         assert_eq!(Amount::from(1).split(), vec![Amount::from(1)]);
@@ -102,4 +631,436 @@ mod tests {
             .collect();
         assert_eq!(Amount::from(255).split(), amounts);
     }
+
+    #[test]
+    fn test_split_into_denominations() {
+        let denominations: Vec<Amount> = [1, 5, 10, 50].into_iter().map(Amount::from).collect();
+
+        let parts: Vec<Amount> = [50, 10, 10, 1, 1, 1]
+            .into_iter()
+            .map(Amount::from)
+            .collect();
+        assert_eq!(Amount::from(73).split_into(&denominations), Some(parts));
+        assert_eq!(Amount::ZERO.split_into(&denominations), Some(vec![]));
+
+        // Greedy split cannot make 3 from 2s and 5s
+        let denominations = [Amount::from(5), Amount::from(2)];
+        assert_eq!(Amount::from(3).split_into(&denominations), None);
+        assert_eq!(
+            Amount::from(9).split_into(&denominations),
+            Some(vec![Amount::from(5), Amount::from(2), Amount::from(2)])
+        );
+    }
+
+    #[test]
+    fn test_split_edges() {
+        assert!(Amount::ZERO.split().is_empty());
+        assert_eq!(Amount::ZERO.split_iter().next(), None);
+
+        let max = Amount::from(u64::MAX).split();
+        assert_eq!(max.len(), 64);
+        assert_eq!(max[0], Amount::from(1 << 63));
+        assert_eq!(max[63], Amount::from(1));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_split_parts_sum_to_amount(amount in any::<Amount>()) {
+            let parts = amount.split();
+
+            prop_assert_eq!(parts.iter().sum::<Amount>(), amount);
+            prop_assert!(parts.iter().all(|part| u64::from(*part).is_power_of_two()));
+            prop_assert!(parts.windows(2).all(|pair| pair[0] > pair[1]));
+            prop_assert_eq!(amount.split_iter().collect::<Vec<Amount>>(), parts.clone());
+
+            let powers: Vec<Amount> = (0..64).map(|i| Amount::from(1 << i)).collect();
+            prop_assert_eq!(amount.split_into(&powers), Some(parts));
+        }
+    }
+
+    #[test]
+    fn test_split_random() {
+        let amount = Amount::from(255);
+        let parts = amount.split_random(Some(7), 3);
+
+        assert_eq!(parts, amount.split_random(Some(7), 3));
+        assert!(parts.len() >= 8 && parts.len() <= 24);
+        assert_eq!(amount.split_targeted(&SplitTarget::None), amount.split());
+
+        // Nothing to halve
+        assert_eq!(Amount::from(1).split_random(None, 4), vec![Amount::from(1)]);
+        assert!(Amount::ZERO.split_random(None, 4).is_empty());
+        assert_eq!(
+            Amount::from(6).split_random(Some(1), 1),
+            Amount::from(6).split()
+        );
+    }
+
+    #[test]
+    fn test_split_random_differs_across_seeds() {
+        let amount = Amount::from(1000);
+        let splits: HashSet<Vec<Amount>> = (0..32)
+            .map(|seed| amount.split_targeted(&SplitTarget::Random { seed: Some(seed) }))
+            .collect();
+
+        assert!(splits.len() > 1);
+    }
+
+    #[test]
+    fn test_split_value() {
+        let amounts =
+            |parts: Vec<Amount>| -> Vec<u64> { parts.into_iter().map(u64::from).collect() };
+
+        assert_eq!(
+            amounts(Amount::from(5).split_value(Amount::from(1))),
+            vec![1; 5]
+        );
+        assert_eq!(
+            amounts(Amount::from(23).split_value(Amount::from(5))),
+            vec![4, 4, 4, 4, 2, 1, 1, 1, 1, 1]
+        );
+        assert_eq!(
+            amounts(Amount::from(3).split_value(Amount::from(8))),
+            vec![2, 1]
+        );
+        assert_eq!(
+            Amount::from(13).split_targeted(&SplitTarget::Value(Amount::ZERO)),
+            Amount::from(13).split()
+        );
+    }
+
+    #[test]
+    fn test_split_with_fee() {
+        let amounts =
+            |parts: &[u64]| -> Vec<Amount> { parts.iter().copied().map(Amount::from).collect() };
+
+        // (inputs, fee, send, target, send outputs, keep outputs)
+        type Case = (
+            u64,
+            u64,
+            Option<u64>,
+            SplitTarget,
+            &'static [u64],
+            &'static [u64],
+        );
+        let cases: [Case; 9] = [
+            (16, 0, Some(10), SplitTarget::None, &[8, 2], &[4, 2]),
+            (16, 1, Some(10), SplitTarget::None, &[8, 2], &[4, 1]),
+            (16, 1, None, SplitTarget::None, &[8, 4, 2, 1], &[]),
+            (16, 6, Some(10), SplitTarget::None, &[8, 2], &[]),
+            (16, 16, None, SplitTarget::None, &[], &[]),
+            (3, 1, Some(1), SplitTarget::None, &[1], &[1]),
+            (
+                64,
+                3,
+                Some(5),
+                SplitTarget::Value(Amount::from(16)),
+                &[4, 1],
+                &[16, 16, 16, 8],
+            ),
+            (
+                13,
+                2,
+                None,
+                SplitTarget::Value(Amount::from(4)),
+                &[4, 4, 2, 1],
+                &[],
+            ),
+            (100, 7, Some(0), SplitTarget::None, &[], &[64, 16, 8, 4, 1]),
+        ];
+
+        for (inputs, fee, send, target, send_outputs, keep_outputs) in cases {
+            let split = Amount::split_with_fee(
+                Amount::from(inputs),
+                Amount::from(fee),
+                send.map(Amount::from),
+                &target,
+            )
+            .unwrap();
+            assert_eq!(
+                split.send,
+                amounts(send_outputs),
+                "{inputs} - {fee} sending {send:?}"
+            );
+            assert_eq!(
+                split.keep,
+                amounts(keep_outputs),
+                "{inputs} - {fee} sending {send:?}"
+            );
+            // Balanced after the fee, as the mint checks [NUT-02]
+            assert_eq!(
+                split.send_total() + split.keep_total() + Amount::from(fee),
+                Amount::from(inputs)
+            );
+        }
+
+        // (inputs, fee, send)
+        let insufficient: [(u64, u64, Option<u64>); 4] = [
+            (16, 17, None),
+            (16, 17, Some(1)),
+            (16, 1, Some(16)),
+            (0, 1, Some(0)),
+        ];
+        for (inputs, fee, send) in insufficient {
+            assert_eq!(
+                Amount::split_with_fee(
+                    Amount::from(inputs),
+                    Amount::from(fee),
+                    send.map(Amount::from),
+                    &SplitTarget::None
+                ),
+                Err(Error::InsufficientInputs {
+                    inputs: Amount::from(inputs),
+                    send: Amount::from(send.unwrap_or_default()),
+                    fee: Amount::from(fee),
+                })
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn prop_split_random_parts_sum_to_amount(
+            amount in any::<Amount>(),
+            seed in any::<u64>(),
+            inflation in 0usize..5,
+        ) {
+            let parts = amount.split_random(Some(seed), inflation);
+            let canonical = amount.split().len();
+
+            prop_assert_eq!(parts.iter().sum::<Amount>(), amount);
+            prop_assert!(parts.iter().all(|part| u64::from(*part).is_power_of_two()));
+            prop_assert!(parts.windows(2).all(|pair| pair[0] >= pair[1]));
+            prop_assert!(parts.len() >= canonical);
+            prop_assert!(parts.len() <= canonical * inflation.max(1));
+            prop_assert_eq!(parts, amount.split_random(Some(seed), inflation));
+        }
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        let max = Amount::from(u64::MAX);
+
+        assert_eq!(
+            Amount::from(2).checked_add(Amount::from(3)),
+            Some(Amount::from(5))
+        );
+        assert_eq!(max.checked_add(Amount::from(1)), None);
+
+        assert_eq!(
+            Amount::from(3).checked_sub(Amount::from(2)),
+            Some(Amount::from(1))
+        );
+        assert_eq!(Amount::ZERO.checked_sub(Amount::from(1)), None);
+
+        assert_eq!(Amount::from(3).checked_mul(4), Some(Amount::from(12)));
+        assert_eq!(max.checked_mul(1), Some(max));
+        assert_eq!(max.checked_mul(2), None);
+    }
+
+    #[test]
+    fn test_saturating_arithmetic() {
+        let max = Amount::from(u64::MAX);
+
+        assert_eq!(max.saturating_add(Amount::from(1)), max);
+        assert_eq!(Amount::ZERO.saturating_sub(Amount::from(1)), Amount::ZERO);
+        assert_eq!(
+            Amount::from(3).saturating_sub(Amount::from(2)),
+            Amount::from(1)
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    #[cfg(debug_assertions)]
+    fn test_add_overflow_panics() {
+        let _ = Amount::from(u64::MAX) + Amount::from(1);
+    }
+
+    #[test]
+    #[should_panic]
+    #[cfg(debug_assertions)]
+    fn test_sub_underflow_panics() {
+        let mut amount = Amount::ZERO;
+        amount -= Amount::from(1);
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn test_add_overflow_wraps_without_overflow_checks() {
+        // What unchecked mint math computed in release builds
+        assert_eq!(Amount::from(u64::MAX) + Amount::from(2), Amount::from(1));
+        assert_eq!(Amount::ZERO - Amount::from(1), Amount::from(u64::MAX));
+    }
+
+    #[test]
+    fn test_unit_amount_arithmetic() {
+        let sat = UnitAmount::sat(Amount::from(2));
+        let msat = UnitAmount::msat(Amount::from(500));
+
+        assert_eq!(
+            sat.checked_add(&msat),
+            Err(Error::UnitMismatch(CurrencyUnit::Sat, CurrencyUnit::Msat))
+        );
+        assert_eq!(
+            msat.checked_sub(&sat),
+            Err(Error::UnitMismatch(CurrencyUnit::Msat, CurrencyUnit::Sat))
+        );
+
+        assert_eq!(
+            sat.to_msat().unwrap().checked_add(&msat),
+            Ok(UnitAmount::msat(Amount::from(2_500)))
+        );
+        assert_eq!(
+            sat.checked_add(&UnitAmount::sat(Amount::from(3))),
+            Ok(UnitAmount::sat(Amount::from(5)))
+        );
+        assert_eq!(
+            UnitAmount::sat(Amount::ZERO).checked_sub(&sat),
+            Err(Error::AmountOverflow)
+        );
+
+        let usd = UnitAmount::new(Amount::from(1), CurrencyUnit::Usd);
+        assert_eq!(
+            usd.checked_add(&UnitAmount::new(Amount::from(1), CurrencyUnit::Eur)),
+            Err(Error::UnitMismatch(CurrencyUnit::Usd, CurrencyUnit::Eur))
+        );
+        assert_eq!(usd.to_string(), "1 usd");
+    }
+
+    #[test]
+    fn test_unit_amount_conversion() {
+        let msat = UnitAmount::msat(Amount::from(1_500));
+        assert_eq!(msat.to_sat_ceil(), Ok(UnitAmount::sat(Amount::from(2))));
+        assert_eq!(msat.to_sat_floor(), Ok(UnitAmount::sat(Amount::from(1))));
+        assert_eq!(msat.to_msat(), Ok(msat.clone()));
+
+        let sat = UnitAmount::sat(Amount::from(3));
+        assert_eq!(sat.to_msat(), Ok(UnitAmount::msat(Amount::from(3_000))));
+        assert_eq!(sat.to_sat_ceil(), Ok(sat.clone()));
+        assert_eq!(
+            UnitAmount::sat(Amount::from(u64::MAX)).to_msat(),
+            Err(Error::AmountOverflow)
+        );
+
+        let usd = UnitAmount::new(Amount::from(100), CurrencyUnit::Usd);
+        assert_eq!(
+            usd.to_msat(),
+            Err(Error::UnsupportedConversion {
+                from: CurrencyUnit::Usd,
+                to: CurrencyUnit::Msat
+            })
+        );
+        assert!(matches!(
+            usd.to_sat_floor(),
+            Err(Error::UnsupportedConversion { .. })
+        ));
+
+        // 100 cents at 1500.5 sat per cent
+        assert_eq!(
+            usd.to_unit(CurrencyUnit::Sat, 1500.5),
+            Ok(UnitAmount::sat(Amount::from(150_050)))
+        );
+        assert_eq!(
+            sat.to_unit(CurrencyUnit::Usd, 0.6),
+            Ok(UnitAmount::new(Amount::from(1), CurrencyUnit::Usd))
+        );
+        for rate in [-1.0, f64::NAN, f64::INFINITY] {
+            assert_eq!(
+                usd.to_unit(CurrencyUnit::Sat, rate),
+                Err(Error::InvalidRate)
+            );
+        }
+        assert_eq!(
+            UnitAmount::sat(Amount::from(u64::MAX)).to_unit(CurrencyUnit::Usd, 2.0),
+            Err(Error::AmountOverflow)
+        );
+    }
+
+    #[test]
+    fn test_sum_and_ord() {
+        let amounts = vec![Amount::from(1), Amount::from(8), Amount::from(2)];
+
+        assert_eq!(amounts.iter().sum::<Amount>(), Amount::from(11));
+        assert_eq!(amounts.into_iter().sum::<Amount>(), Amount::from(11));
+        assert_eq!(Vec::<Amount>::new().iter().sum::<Amount>(), Amount::ZERO);
+        assert!(Amount::from(2) > Amount::from(1));
+    }
+
+    #[test]
+    fn test_amount_from_str() {
+        assert_eq!(Amount::from_str("21").unwrap(), Amount::from(21));
+        assert_eq!(Amount::from(21).to_string(), "21");
+        assert!(Amount::from_str("-1").is_err());
+        assert!(Amount::from_str("1.5").is_err());
+        assert!(Amount::from_str("").is_err());
+    }
+
+    #[test]
+    fn test_amount_deserialize_forms() {
+        for json in ["64", r#""64""#] {
+            assert_eq!(
+                serde_json::from_str::<Amount>(json).unwrap(),
+                Amount::from(64)
+            );
+        }
+
+        let max = u64::MAX.to_string();
+        for json in [max.clone(), format!(r#""{max}""#)] {
+            assert_eq!(
+                serde_json::from_str::<Amount>(&json).unwrap(),
+                Amount::from(u64::MAX)
+            );
+        }
+
+        // Always serialized back as a bare integer
+        let amount: Amount = serde_json::from_str(r#""18446744073709551615""#).unwrap();
+        assert_eq!(serde_json::to_string(&amount).unwrap(), max);
+
+        for json in [
+            "-1",
+            "1.5",
+            "1e3",
+            r#""-1""#,
+            r#""+1""#,
+            r#""1.5""#,
+            r#"" 1""#,
+            r#""""#,
+            r#""0x10""#,
+            "18446744073709551616",
+            r#""18446744073709551616""#,
+            "null",
+            "true",
+        ] {
+            assert!(serde_json::from_str::<Amount>(json).is_err(), "{json}");
+        }
+    }
+}
+
+#[cfg(bench)]
+mod benches {
+    use test::{black_box, Bencher};
+
+    use super::*;
+
+    /// Typical melt change values
+    const CHANGE: [u64; 6] = [0, 1, 21, 99, 1_000, 2_047];
+
+    #[bench]
+    pub fn split(bh: &mut Bencher) {
+        bh.iter(|| {
+            for sats in CHANGE {
+                black_box(Amount::from(sats).split());
+            }
+        });
+    }
+
+    #[bench]
+    pub fn split_iter(bh: &mut Bencher) {
+        bh.iter(|| {
+            for sats in CHANGE {
+                black_box(Amount::from(sats).split_iter().sum::<Amount>());
+            }
+        });
+    }
 }
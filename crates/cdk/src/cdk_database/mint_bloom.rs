@@ -0,0 +1,466 @@
+//! Mint database with a Bloom filter in front of spent proof lookups
+
+use std::collections::HashMap;
+use std::f64::consts::LN_2;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use async_trait::async_trait;
+
+use super::{Error, MintDatabase, MintTransaction};
+use crate::dhke::hash_to_curve;
+use crate::nuts::nut02::MintKeySet;
+use crate::nuts::{
+    BlindSignature, CurrencyUnit, Id, MintInfo, Proof, Proofs, PublicKey, SwapResponse,
+};
+use crate::secret::Secret;
+use crate::types::{MeltQuote, MintQuote};
+use crate::Sha256;
+
+/// Bloom filter over the compressed bytes of spent `Y`s
+///
+/// `Y` is the output of `hash_to_curve` so its x coordinate is already
+/// uniformly distributed and is used directly for double hashing.
+#[derive(Debug)]
+struct BloomFilter {
+    bits: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u32,
+}
+
+impl BloomFilter {
+    fn new(expected_items: usize, false_positive_rate: f64) -> Self {
+        let n = expected_items.max(1) as f64;
+        let p = false_positive_rate.clamp(1e-9, 0.5);
+
+        let num_bits = ((-n * p.ln()) / (LN_2 * LN_2)).ceil().max(64.0) as u64;
+        let num_hashes = ((num_bits as f64 / n) * LN_2).round().clamp(1.0, 32.0) as u32;
+
+        Self {
+            bits: vec![0; ((num_bits + 63) / 64) as usize],
+            num_bits,
+            num_hashes,
+        }
+    }
+
+    fn indexes(&self, y: &[u8; 33]) -> impl Iterator<Item = u64> {
+        let mut h1 = [0; 8];
+        let mut h2 = [0; 8];
+        h1.copy_from_slice(&y[1..9]);
+        h2.copy_from_slice(&y[9..17]);
+
+        let h1 = u64::from_le_bytes(h1);
+        let h2 = u64::from_le_bytes(h2) | 1;
+        let num_bits = self.num_bits;
+
+        (0..self.num_hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % num_bits)
+    }
+
+    fn insert(&mut self, y: &[u8; 33]) {
+        for index in self.indexes(y).collect::<Vec<u64>>() {
+            self.bits[(index / 64) as usize] |= 1 << (index % 64);
+        }
+    }
+
+    fn contains(&self, y: &[u8; 33]) -> bool {
+        self.indexes(y)
+            .all(|index| self.bits[(index / 64) as usize] & (1 << (index % 64)) != 0)
+    }
+}
+
+/// Wraps a [MintDatabase] and answers "not spent" from memory when possible
+///
+/// Every spent `Y` is added to a Bloom filter. A lookup the filter rejects is
+/// definitely unspent and never reaches the inner database; anything else
+/// falls through to the exact check. The filter is rebuilt from the inner
+/// database when this is created.
+#[derive(Clone)]
+pub struct MintBloomDatabase {
+    inner: Arc<dyn MintDatabase<Err = Error> + Send + Sync>,
+    filter: Arc<RwLock<BloomFilter>>,
+    skipped_lookups: Arc<AtomicU64>,
+}
+
+impl MintBloomDatabase {
+    /// Create the filter sized for `expected_spent_proofs` at
+    /// `false_positive_rate` and fill it from `inner`
+    ///
+    /// The rate is clamped to `1e-9..=0.5`. Exceeding the expected count only
+    /// raises the false positive rate.
+    pub async fn new(
+        inner: Arc<dyn MintDatabase<Err = Error> + Send + Sync>,
+        expected_spent_proofs: usize,
+        false_positive_rate: f64,
+    ) -> Result<Self, Error> {
+        let spent_ys = inner.get_spent_ys().await?;
+
+        let mut filter = BloomFilter::new(
+            expected_spent_proofs.max(spent_ys.len()),
+            false_positive_rate,
+        );
+        for y in &spent_ys {
+            filter.insert(&y.to_bytes());
+        }
+
+        Ok(Self {
+            inner,
+            filter: Arc::new(RwLock::new(filter)),
+            skipped_lookups: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    /// Number of spent proof lookups answered by the filter alone
+    pub fn skipped_lookups(&self) -> u64 {
+        self.skipped_lookups.load(Ordering::Relaxed)
+    }
+
+    fn may_be_spent(&self, y: &PublicKey) -> bool {
+        self.filter
+            .read()
+            .expect("Bloom filter lock poisoned")
+            .contains(&y.to_bytes())
+    }
+}
+
+#[async_trait]
+impl MintDatabase for MintBloomDatabase {
+    type Err = Error;
+
+    async fn set_mint_info(&self, mint_info: &MintInfo) -> Result<(), Self::Err> {
+        self.inner.set_mint_info(mint_info).await
+    }
+    async fn get_mint_info(&self) -> Result<MintInfo, Self::Err> {
+        self.inner.get_mint_info().await
+    }
+
+    async fn add_active_keyset(&self, unit: CurrencyUnit, id: Id) -> Result<(), Self::Err> {
+        self.inner.add_active_keyset(unit, id).await
+    }
+    async fn get_active_keyset_id(&self, unit: &CurrencyUnit) -> Result<Option<Id>, Self::Err> {
+        self.inner.get_active_keyset_id(unit).await
+    }
+    async fn get_active_keysets(&self) -> Result<HashMap<CurrencyUnit, Id>, Self::Err> {
+        self.inner.get_active_keysets().await
+    }
+
+    async fn add_mint_quote(&self, quote: MintQuote) -> Result<(), Self::Err> {
+        self.inner.add_mint_quote(quote).await
+    }
+    async fn get_mint_quote(&self, quote_id: &str) -> Result<Option<MintQuote>, Self::Err> {
+        self.inner.get_mint_quote(quote_id).await
+    }
+    async fn get_mint_quotes(&self) -> Result<Vec<MintQuote>, Self::Err> {
+        self.inner.get_mint_quotes().await
+    }
+    async fn get_mint_quote_by_payment_hash(
+        &self,
+        payment_hash: &Sha256,
+    ) -> Result<Option<MintQuote>, Self::Err> {
+        self.inner
+            .get_mint_quote_by_payment_hash(payment_hash)
+            .await
+    }
+    async fn remove_mint_quote(&self, quote_id: &str) -> Result<(), Self::Err> {
+        self.inner.remove_mint_quote(quote_id).await
+    }
+    async fn remove_mint_quotes(&self, quote_ids: &[String]) -> Result<(), Self::Err> {
+        self.inner.remove_mint_quotes(quote_ids).await
+    }
+
+    async fn add_melt_quote(&self, quote: MeltQuote) -> Result<(), Self::Err> {
+        self.inner.add_melt_quote(quote).await
+    }
+    async fn get_melt_quote(&self, quote_id: &str) -> Result<Option<MeltQuote>, Self::Err> {
+        self.inner.get_melt_quote(quote_id).await
+    }
+    async fn get_melt_quotes(&self) -> Result<Vec<MeltQuote>, Self::Err> {
+        self.inner.get_melt_quotes().await
+    }
+    async fn remove_melt_quote(&self, quote_id: &str) -> Result<(), Self::Err> {
+        self.inner.remove_melt_quote(quote_id).await
+    }
+    async fn remove_melt_quotes(&self, quote_ids: &[String]) -> Result<(), Self::Err> {
+        self.inner.remove_melt_quotes(quote_ids).await
+    }
+
+    async fn add_keyset(&self, keyset: MintKeySet) -> Result<(), Self::Err> {
+        self.inner.add_keyset(keyset).await
+    }
+    async fn get_keyset(&self, id: &Id) -> Result<Option<MintKeySet>, Self::Err> {
+        self.inner.get_keyset(id).await
+    }
+    async fn get_keysets(&self) -> Result<Vec<MintKeySet>, Self::Err> {
+        self.inner.get_keysets().await
+    }
+    async fn remove_keyset(&self, id: &Id) -> Result<(), Self::Err> {
+        self.inner.remove_keyset(id).await
+    }
+
+    async fn add_spent_proof(&self, proof: Proof) -> Result<(), Self::Err> {
+        let y = proof.y()?;
+
+        // Mark before persisting so a concurrent lookup never sees a false negative
+        self.filter
+            .write()
+            .expect("Bloom filter lock poisoned")
+            .insert(&y.to_bytes());
+
+        self.inner.add_spent_proof(proof).await
+    }
+    async fn add_spent_proofs_if_unspent(
+        &self,
+        proofs: Proofs,
+    ) -> Result<Vec<PublicKey>, Self::Err> {
+        let ys = proofs
+            .iter()
+            .map(Proof::y)
+            .collect::<Result<Vec<PublicKey>, _>>()?;
+
+        // A proof marked that ends up not recorded is only a false positive
+        {
+            let mut filter = self.filter.write().expect("Bloom filter lock poisoned");
+            for y in &ys {
+                filter.insert(&y.to_bytes());
+            }
+        }
+
+        self.inner.add_spent_proofs_if_unspent(proofs).await
+    }
+    async fn get_spent_proof_by_secret(&self, secret: &Secret) -> Result<Option<Proof>, Self::Err> {
+        let y = hash_to_curve(&secret.to_bytes())?;
+        self.get_spent_proof_by_y(&y).await
+    }
+    async fn get_spent_proof_by_y(&self, y: &PublicKey) -> Result<Option<Proof>, Self::Err> {
+        if !self.may_be_spent(y) {
+            self.skipped_lookups.fetch_add(1, Ordering::Relaxed);
+            return Ok(None);
+        }
+
+        self.inner.get_spent_proof_by_y(y).await
+    }
+    async fn get_spent_ys(&self) -> Result<Vec<PublicKey>, Self::Err> {
+        self.inner.get_spent_ys().await
+    }
+    async fn get_spent_proofs_by_keyset(&self, keyset_id: &Id) -> Result<Proofs, Self::Err> {
+        self.inner.get_spent_proofs_by_keyset(keyset_id).await
+    }
+    async fn get_spent_count_by_keyset(&self, keyset_id: &Id) -> Result<u64, Self::Err> {
+        self.inner.get_spent_count_by_keyset(keyset_id).await
+    }
+    async fn remove_spent_proofs(&self, ys: &[PublicKey]) -> Result<(), Self::Err> {
+        // Removed ys stay in the filter and fall through to the inner database
+        self.inner.remove_spent_proofs(ys).await
+    }
+
+    async fn add_pending_proof(&self, proof: Proof) -> Result<(), Self::Err> {
+        self.inner.add_pending_proof(proof).await
+    }
+    async fn add_pending_proofs_if_unspent(
+        &self,
+        proofs: Proofs,
+    ) -> Result<Vec<PublicKey>, Self::Err> {
+        self.inner.add_pending_proofs_if_unspent(proofs).await
+    }
+    async fn get_pending_proof_by_secret(
+        &self,
+        secret: &Secret,
+    ) -> Result<Option<Proof>, Self::Err> {
+        self.inner.get_pending_proof_by_secret(secret).await
+    }
+    async fn get_pending_proof_by_y(&self, y: &PublicKey) -> Result<Option<Proof>, Self::Err> {
+        self.inner.get_pending_proof_by_y(y).await
+    }
+    async fn remove_pending_proof(&self, secret: &Secret) -> Result<(), Self::Err> {
+        self.inner.remove_pending_proof(secret).await
+    }
+
+    async fn add_blinded_signature(
+        &self,
+        blinded_message: PublicKey,
+        blinded_signature: BlindSignature,
+    ) -> Result<(), Self::Err> {
+        self.inner
+            .add_blinded_signature(blinded_message, blinded_signature)
+            .await
+    }
+    async fn get_blinded_signature(
+        &self,
+        blinded_message: &PublicKey,
+    ) -> Result<Option<BlindSignature>, Self::Err> {
+        self.inner.get_blinded_signature(blinded_message).await
+    }
+    async fn get_blinded_signatures(
+        &self,
+        blinded_messages: Vec<PublicKey>,
+    ) -> Result<Vec<Option<BlindSignature>>, Self::Err> {
+        self.inner.get_blinded_signatures(blinded_messages).await
+    }
+    async fn get_blinded_signatures_by_keyset(
+        &self,
+        keyset_id: &Id,
+    ) -> Result<Vec<BlindSignature>, Self::Err> {
+        self.inner.get_blinded_signatures_by_keyset(keyset_id).await
+    }
+
+    async fn commit_transaction(
+        &self,
+        transaction: MintTransaction,
+    ) -> Result<Vec<PublicKey>, Self::Err> {
+        let ys = transaction
+            .spent_proofs
+            .iter()
+            .map(Proof::y)
+            .collect::<Result<Vec<PublicKey>, _>>()?;
+
+        // As for add_spent_proofs_if_unspent, marked before they are written
+        {
+            let mut filter = self.filter.write().expect("Bloom filter lock poisoned");
+            for y in &ys {
+                filter.insert(&y.to_bytes());
+            }
+        }
+
+        self.inner.commit_transaction(transaction).await
+    }
+    async fn get_swap_response(
+        &self,
+        request_hash: &Sha256,
+    ) -> Result<Option<SwapResponse>, Self::Err> {
+        self.inner.get_swap_response(request_hash).await
+    }
+
+    async fn remove_expired_swap_responses(&self, now: u64) -> Result<usize, Self::Err> {
+        self.inner.remove_expired_swap_responses(now).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::cdk_database::mint_memory::MintMemoryDatabase;
+    use crate::Amount;
+
+    fn proof() -> Proof {
+        Proof::new(
+            Amount::from(1),
+            Id::from_str("009a1f293253e41e").unwrap(),
+            Secret::generate(),
+            PublicKey::from_str(
+                "02194603ffa36356f4a56b7df9371fc3192472351453ec7398b8da8117e7c3e104",
+            )
+            .unwrap(),
+        )
+    }
+
+    fn memory_database() -> Arc<MintMemoryDatabase> {
+        Arc::new(
+            MintMemoryDatabase::new(
+                MintInfo::default(),
+                HashMap::new(),
+                vec![],
+                vec![],
+                vec![],
+                vec![],
+                vec![],
+                HashMap::new(),
+            )
+            .unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_no_false_negatives() {
+        let inner = memory_database();
+
+        // Some proofs are spent before the filter exists
+        let before: Vec<Proof> = (0..50).map(|_| proof()).collect();
+        for proof in &before {
+            inner.add_spent_proof(proof.clone()).await.unwrap();
+        }
+
+        // Undersized on purpose, only the false positive rate suffers
+        let db = MintBloomDatabase::new(inner.clone(), 100, 0.01)
+            .await
+            .unwrap();
+
+        let after: Vec<Proof> = (0..500).map(|_| proof()).collect();
+        for proof in &after {
+            db.add_spent_proof(proof.clone()).await.unwrap();
+        }
+
+        for proof in before.iter().chain(&after) {
+            assert_eq!(
+                db.get_spent_proof_by_secret(&proof.secret).await.unwrap(),
+                Some(proof.clone())
+            );
+        }
+        assert_eq!(db.skipped_lookups(), 0);
+
+        // Rebuilding from the store keeps every spent proof
+        let rebuilt = MintBloomDatabase::new(inner, 1_000, 0.01).await.unwrap();
+        for proof in before.iter().chain(&after) {
+            let y = proof.y().unwrap();
+            assert_eq!(
+                rebuilt.get_spent_proof_by_y(&y).await.unwrap(),
+                Some(proof.clone())
+            );
+        }
+
+        for _ in 0..1_000 {
+            assert!(rebuilt
+                .get_spent_proof_by_secret(&Secret::generate())
+                .await
+                .unwrap()
+                .is_none());
+        }
+        assert!(rebuilt.skipped_lookups() > 900);
+    }
+}
+
+#[cfg(bench)]
+mod benches {
+    use test::Bencher;
+
+    use super::*;
+    use crate::cdk_database::mint_memory::MintMemoryDatabase;
+
+    #[bench]
+    pub fn bloom_skip_rate(bh: &mut Bencher) {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        let inner = MintMemoryDatabase::new(
+            MintInfo::default(),
+            HashMap::new(),
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            HashMap::new(),
+        )
+        .unwrap();
+        let db = runtime
+            .block_on(MintBloomDatabase::new(Arc::new(inner), 10_000, 0.01))
+            .unwrap();
+
+        let secrets: Vec<Secret> = (0..100).map(|_| Secret::generate()).collect();
+        let mut lookups = 0;
+
+        bh.iter(|| {
+            runtime.block_on(async {
+                for secret in &secrets {
+                    db.get_spent_proof_by_secret(secret).await.unwrap();
+                }
+            });
+            lookups += secrets.len() as u64;
+        });
+
+        // None of the secrets was added, so only false positives reach inner
+        let skip_rate = db.skipped_lookups() as f64 / lookups as f64;
+        assert!(skip_rate > 0.95, "skip rate {skip_rate}");
+    }
+}
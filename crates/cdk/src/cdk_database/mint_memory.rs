@@ -1,26 +1,115 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use async_trait::async_trait;
 use tokio::sync::Mutex;
 
-use super::{Error, MintDatabase};
+use super::{CachedSwapResponse, Error, MintDatabase, MintTransaction};
 use crate::dhke::hash_to_curve;
 use crate::nuts::nut02::MintKeySet;
-use crate::nuts::{BlindSignature, CurrencyUnit, Id, MintInfo, Proof, Proofs, PublicKey};
+use crate::nuts::{
+    BlindSignature, CurrencyUnit, Id, MintInfo, Proof, Proofs, PublicKey, SwapResponse,
+};
 use crate::secret::Secret;
 use crate::types::{MeltQuote, MintQuote};
+use crate::Sha256;
+
+/// Spent proofs by `Y`, indexed by keyset
+#[derive(Debug, Default)]
+struct SpentProofs {
+    by_y: HashMap<[u8; 33], Proof>,
+    by_keyset: HashMap<Id, HashSet<[u8; 33]>>,
+}
+
+impl SpentProofs {
+    fn insert(&mut self, y: [u8; 33], proof: Proof) {
+        let keyset_id = proof.keyset_id;
+        if let Some(previous) = self.by_y.insert(y, proof) {
+            self.unindex(&previous.keyset_id, &y);
+        }
+        self.by_keyset.entry(keyset_id).or_default().insert(y);
+    }
+
+    /// `ys` that are spent, pending but not `released`, or appear more than
+    /// once
+    fn conflicts(
+        &self,
+        pending: &HashMap<[u8; 33], Proof>,
+        ys: &[PublicKey],
+        released: &[PublicKey],
+    ) -> Vec<PublicKey> {
+        let mut batch = HashSet::with_capacity(ys.len());
+        ys.iter()
+            .filter(|y| {
+                let key = y.to_bytes();
+                self.by_y.contains_key(&key)
+                    || (pending.contains_key(&key) && !released.contains(y))
+                    || !batch.insert(**y)
+            })
+            .copied()
+            .collect()
+    }
+
+    fn remove(&mut self, y: &[u8; 33]) {
+        if let Some(proof) = self.by_y.remove(y) {
+            self.unindex(&proof.keyset_id, y);
+        }
+    }
+
+    fn unindex(&mut self, keyset_id: &Id, y: &[u8; 33]) {
+        if let Some(ys) = self.by_keyset.get_mut(keyset_id) {
+            ys.remove(y);
+            if ys.is_empty() {
+                self.by_keyset.remove(keyset_id);
+            }
+        }
+    }
+}
+
+/// Mint quotes by id, indexed by payment hash
+#[derive(Debug, Default)]
+struct MintQuotes {
+    by_id: HashMap<String, MintQuote>,
+    by_payment_hash: HashMap<Sha256, String>,
+}
+
+impl MintQuotes {
+    fn insert(&mut self, quote: MintQuote) {
+        match self.by_id.get(&quote.id) {
+            // An update keeps the entry, which may be of a later quote
+            Some(stored) if stored.request == quote.request => (),
+            _ => {
+                self.remove(&quote.id);
+                if let Some(payment_hash) = quote.payment_hash() {
+                    self.by_payment_hash.insert(payment_hash, quote.id.clone());
+                }
+            }
+        }
+        self.by_id.insert(quote.id.clone(), quote);
+    }
+
+    fn remove(&mut self, quote_id: &str) {
+        let Some(payment_hash) = self.by_id.remove(quote_id).and_then(|q| q.payment_hash()) else {
+            return;
+        };
+        // A later quote for the same invoice keeps the entry
+        if self.by_payment_hash.get(&payment_hash).map(String::as_str) == Some(quote_id) {
+            self.by_payment_hash.remove(&payment_hash);
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct MintMemoryDatabase {
     mint_info: Arc<Mutex<MintInfo>>,
     active_keysets: Arc<Mutex<HashMap<CurrencyUnit, Id>>>,
     keysets: Arc<Mutex<HashMap<Id, MintKeySet>>>,
-    mint_quotes: Arc<Mutex<HashMap<String, MintQuote>>>,
+    mint_quotes: Arc<Mutex<MintQuotes>>,
     melt_quotes: Arc<Mutex<HashMap<String, MeltQuote>>>,
     pending_proofs: Arc<Mutex<HashMap<[u8; 33], Proof>>>,
-    spent_proofs: Arc<Mutex<HashMap<[u8; 33], Proof>>>,
+    spent_proofs: Arc<Mutex<SpentProofs>>,
     blinded_signatures: Arc<Mutex<HashMap<[u8; 33], BlindSignature>>>,
+    swap_responses: Arc<Mutex<HashMap<Sha256, CachedSwapResponse>>>,
 }
 
 impl MintMemoryDatabase {
@@ -35,29 +124,32 @@ impl MintMemoryDatabase {
         spent_proofs: Proofs,
         blinded_signatures: HashMap<[u8; 33], BlindSignature>,
     ) -> Result<Self, Error> {
+        let mut spent = SpentProofs::default();
+        for proof in spent_proofs {
+            spent.insert(proof.y()?.to_bytes(), proof);
+        }
+        let mut quotes = MintQuotes::default();
+        for quote in mint_quotes {
+            quotes.insert(quote);
+        }
+
         Ok(Self {
             mint_info: Arc::new(Mutex::new(mint_info)),
             active_keysets: Arc::new(Mutex::new(active_keysets)),
             keysets: Arc::new(Mutex::new(keysets.into_iter().map(|k| (k.id, k)).collect())),
-            mint_quotes: Arc::new(Mutex::new(
-                mint_quotes.into_iter().map(|q| (q.id.clone(), q)).collect(),
-            )),
+            mint_quotes: Arc::new(Mutex::new(quotes)),
             melt_quotes: Arc::new(Mutex::new(
                 melt_quotes.into_iter().map(|q| (q.id.clone(), q)).collect(),
             )),
             pending_proofs: Arc::new(Mutex::new(
                 pending_proofs
                     .into_iter()
-                    .map(|p| (hash_to_curve(&p.secret.to_bytes()).unwrap().to_bytes(), p))
-                    .collect(),
-            )),
-            spent_proofs: Arc::new(Mutex::new(
-                spent_proofs
-                    .into_iter()
-                    .map(|p| (hash_to_curve(&p.secret.to_bytes()).unwrap().to_bytes(), p))
+                    .map(|p| (p.y().unwrap().to_bytes(), p))
                     .collect(),
             )),
+            spent_proofs: Arc::new(Mutex::new(spent)),
             blinded_signatures: Arc::new(Mutex::new(blinded_signatures)),
+            swap_responses: Arc::new(Mutex::new(HashMap::new())),
         })
     }
 }
@@ -100,20 +192,41 @@ impl MintDatabase for MintMemoryDatabase {
         Ok(self.keysets.lock().await.values().cloned().collect())
     }
 
+    async fn remove_keyset(&self, id: &Id) -> Result<(), Error> {
+        self.keysets.lock().await.remove(id);
+        Ok(())
+    }
+
     async fn add_mint_quote(&self, quote: MintQuote) -> Result<(), Error> {
-        self.mint_quotes
-            .lock()
-            .await
-            .insert(quote.id.clone(), quote);
+        self.mint_quotes.lock().await.insert(quote);
         Ok(())
     }
 
     async fn get_mint_quote(&self, quote_id: &str) -> Result<Option<MintQuote>, Self::Err> {
-        Ok(self.mint_quotes.lock().await.get(quote_id).cloned())
+        Ok(self.mint_quotes.lock().await.by_id.get(quote_id).cloned())
     }
 
     async fn get_mint_quotes(&self) -> Result<Vec<MintQuote>, Self::Err> {
-        Ok(self.mint_quotes.lock().await.values().cloned().collect())
+        Ok(self
+            .mint_quotes
+            .lock()
+            .await
+            .by_id
+            .values()
+            .cloned()
+            .collect())
+    }
+
+    async fn get_mint_quote_by_payment_hash(
+        &self,
+        payment_hash: &Sha256,
+    ) -> Result<Option<MintQuote>, Self::Err> {
+        let mint_quotes = self.mint_quotes.lock().await;
+        Ok(mint_quotes
+            .by_payment_hash
+            .get(payment_hash)
+            .and_then(|quote_id| mint_quotes.by_id.get(quote_id))
+            .cloned())
     }
 
     async fn remove_mint_quote(&self, quote_id: &str) -> Result<(), Self::Err> {
@@ -122,6 +235,15 @@ impl MintDatabase for MintMemoryDatabase {
         Ok(())
     }
 
+    async fn remove_mint_quotes(&self, quote_ids: &[String]) -> Result<(), Self::Err> {
+        let mut mint_quotes = self.mint_quotes.lock().await;
+        for quote_id in quote_ids {
+            mint_quotes.remove(quote_id);
+        }
+
+        Ok(())
+    }
+
     async fn add_melt_quote(&self, quote: MeltQuote) -> Result<(), Self::Err> {
         self.melt_quotes
             .lock()
@@ -144,8 +266,17 @@ impl MintDatabase for MintMemoryDatabase {
         Ok(())
     }
 
+    async fn remove_melt_quotes(&self, quote_ids: &[String]) -> Result<(), Self::Err> {
+        let mut melt_quotes = self.melt_quotes.lock().await;
+        for quote_id in quote_ids {
+            melt_quotes.remove(quote_id);
+        }
+
+        Ok(())
+    }
+
     async fn add_spent_proof(&self, proof: Proof) -> Result<(), Self::Err> {
-        let secret_point = hash_to_curve(&proof.secret.to_bytes())?;
+        let secret_point = proof.y()?;
         self.spent_proofs
             .lock()
             .await
@@ -153,27 +284,115 @@ impl MintDatabase for MintMemoryDatabase {
         Ok(())
     }
 
+    async fn add_spent_proofs_if_unspent(
+        &self,
+        proofs: Proofs,
+    ) -> Result<Vec<PublicKey>, Self::Err> {
+        let ys = proofs
+            .iter()
+            .map(Proof::y)
+            .collect::<Result<Vec<PublicKey>, _>>()?;
+        let mut spent_proofs = self.spent_proofs.lock().await;
+        let pending_proofs = self.pending_proofs.lock().await;
+
+        let spent = spent_proofs.conflicts(&pending_proofs, &ys, &[]);
+        if spent.is_empty() {
+            for (y, proof) in ys.into_iter().zip(proofs) {
+                spent_proofs.insert(y.to_bytes(), proof);
+            }
+        }
+
+        Ok(spent)
+    }
+
     async fn get_spent_proof_by_secret(&self, secret: &Secret) -> Result<Option<Proof>, Self::Err> {
         Ok(self
             .spent_proofs
             .lock()
             .await
+            .by_y
             .get(&hash_to_curve(&secret.to_bytes())?.to_bytes())
             .cloned())
     }
 
     async fn get_spent_proof_by_y(&self, y: &PublicKey) -> Result<Option<Proof>, Self::Err> {
-        Ok(self.spent_proofs.lock().await.get(&y.to_bytes()).cloned())
+        Ok(self
+            .spent_proofs
+            .lock()
+            .await
+            .by_y
+            .get(&y.to_bytes())
+            .cloned())
+    }
+
+    async fn get_spent_ys(&self) -> Result<Vec<PublicKey>, Self::Err> {
+        self.spent_proofs
+            .lock()
+            .await
+            .by_y
+            .keys()
+            .map(|y| PublicKey::from_slice(y).map_err(|e| Error::Cdk(e.into())))
+            .collect()
+    }
+
+    async fn get_spent_proofs_by_keyset(&self, keyset_id: &Id) -> Result<Proofs, Self::Err> {
+        let spent_proofs = self.spent_proofs.lock().await;
+
+        Ok(spent_proofs
+            .by_keyset
+            .get(keyset_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|y| spent_proofs.by_y.get(y).cloned())
+            .collect())
+    }
+    async fn get_spent_count_by_keyset(&self, keyset_id: &Id) -> Result<u64, Self::Err> {
+        Ok(self
+            .spent_proofs
+            .lock()
+            .await
+            .by_keyset
+            .get(keyset_id)
+            .map_or(0, |ys| ys.len() as u64))
+    }
+
+    async fn remove_spent_proofs(&self, ys: &[PublicKey]) -> Result<(), Self::Err> {
+        let mut spent_proofs = self.spent_proofs.lock().await;
+        for y in ys {
+            spent_proofs.remove(&y.to_bytes());
+        }
+        Ok(())
     }
 
     async fn add_pending_proof(&self, proof: Proof) -> Result<(), Self::Err> {
         self.pending_proofs
             .lock()
             .await
-            .insert(hash_to_curve(&proof.secret.to_bytes())?.to_bytes(), proof);
+            .insert(proof.y()?.to_bytes(), proof);
         Ok(())
     }
 
+    async fn add_pending_proofs_if_unspent(
+        &self,
+        proofs: Proofs,
+    ) -> Result<Vec<PublicKey>, Self::Err> {
+        let ys = proofs
+            .iter()
+            .map(Proof::y)
+            .collect::<Result<Vec<PublicKey>, _>>()?;
+        let spent_proofs = self.spent_proofs.lock().await;
+        let mut pending_proofs = self.pending_proofs.lock().await;
+
+        let spent = spent_proofs.conflicts(&pending_proofs, &ys, &[]);
+        if spent.is_empty() {
+            for (y, proof) in ys.into_iter().zip(proofs) {
+                pending_proofs.insert(y.to_bytes(), proof);
+            }
+        }
+
+        Ok(spent)
+    }
+
     async fn get_pending_proof_by_secret(
         &self,
         secret: &Secret,
@@ -240,4 +459,73 @@ impl MintDatabase for MintMemoryDatabase {
 
         Ok(signatures)
     }
+
+    async fn get_blinded_signatures_by_keyset(
+        &self,
+        keyset_id: &Id,
+    ) -> Result<Vec<BlindSignature>, Self::Err> {
+        Ok(self
+            .blinded_signatures
+            .lock()
+            .await
+            .values()
+            .filter(|signature| signature.keyset_id == *keyset_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn commit_transaction(
+        &self,
+        transaction: MintTransaction,
+    ) -> Result<Vec<PublicKey>, Self::Err> {
+        let ys = transaction
+            .spent_proofs
+            .iter()
+            .map(Proof::y)
+            .collect::<Result<Vec<PublicKey>, _>>()?;
+
+        // Every table written is held until the last write, so readers see
+        // the transaction whole or not at all
+        let mut spent_proofs = self.spent_proofs.lock().await;
+        let mut pending_proofs = self.pending_proofs.lock().await;
+        let mut blinded_signatures = self.blinded_signatures.lock().await;
+        let mut swap_responses = self.swap_responses.lock().await;
+
+        let spent = spent_proofs.conflicts(&pending_proofs, &ys, &transaction.released_ys);
+        if !spent.is_empty() {
+            return Ok(spent);
+        }
+
+        for y in &transaction.released_ys {
+            pending_proofs.remove(&y.to_bytes());
+        }
+        for (y, proof) in ys.into_iter().zip(transaction.spent_proofs) {
+            spent_proofs.insert(y.to_bytes(), proof);
+        }
+        for (blinded_message, blinded_signature) in transaction.blinded_signatures {
+            blinded_signatures.insert(blinded_message.to_bytes(), blinded_signature);
+        }
+        swap_responses.extend(transaction.swap_responses);
+
+        Ok(spent)
+    }
+
+    async fn get_swap_response(
+        &self,
+        request_hash: &Sha256,
+    ) -> Result<Option<SwapResponse>, Self::Err> {
+        Ok(self
+            .swap_responses
+            .lock()
+            .await
+            .get(request_hash)
+            .map(|cached| cached.response.clone()))
+    }
+
+    async fn remove_expired_swap_responses(&self, now: u64) -> Result<usize, Self::Err> {
+        let mut swap_responses = self.swap_responses.lock().await;
+        let before = swap_responses.len();
+        swap_responses.retain(|_, cached| cached.expiry > now);
+        Ok(before - swap_responses.len())
+    }
 }
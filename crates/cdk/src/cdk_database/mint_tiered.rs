@@ -0,0 +1,405 @@
+//! Mint database with an in-memory cache of recent spent proofs
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tokio::sync::Mutex;
+
+use super::{Error, MintDatabase, MintTransaction};
+use crate::dhke::hash_to_curve;
+use crate::nuts::nut02::MintKeySet;
+use crate::nuts::{
+    BlindSignature, CurrencyUnit, Id, MintInfo, Proof, Proofs, PublicKey, SwapResponse,
+};
+use crate::secret::Secret;
+use crate::types::{MeltQuote, MintQuote};
+use crate::Sha256;
+
+/// Recently spent proofs, oldest first
+#[derive(Debug, Default)]
+struct HotSpentProofs {
+    proofs: HashMap<[u8; 33], Proof>,
+    order: VecDeque<[u8; 33]>,
+}
+
+impl HotSpentProofs {
+    /// Cache `proof`, evicting the oldest proofs over `max_cached_proofs`
+    fn insert(&mut self, y: [u8; 33], proof: Proof, max_cached_proofs: usize) {
+        if self.proofs.insert(y, proof).is_none() {
+            self.order.push_back(y);
+        }
+
+        while self.proofs.len() > max_cached_proofs {
+            match self.order.pop_front() {
+                Some(oldest) => {
+                    self.proofs.remove(&oldest);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+/// Wraps a [MintDatabase] and keeps the most recent spent proofs in memory
+///
+/// Spent proofs are always written through to the inner database, so
+/// proofs that have left the cache are still rejected. Only the cache is
+/// bounded by `max_cached_proofs`; once it is full the oldest entries are
+/// evicted.
+#[derive(Clone)]
+pub struct MintTieredDatabase {
+    inner: Arc<dyn MintDatabase<Err = Error> + Send + Sync>,
+    hot_spent_proofs: Arc<Mutex<HotSpentProofs>>,
+    max_cached_proofs: usize,
+}
+
+impl MintTieredDatabase {
+    pub fn new(
+        inner: Arc<dyn MintDatabase<Err = Error> + Send + Sync>,
+        max_cached_proofs: usize,
+    ) -> Self {
+        Self {
+            inner,
+            hot_spent_proofs: Arc::new(Mutex::new(HotSpentProofs::default())),
+            max_cached_proofs,
+        }
+    }
+
+    /// Number of spent proofs currently held in memory
+    pub async fn cached_spent_proofs(&self) -> usize {
+        self.hot_spent_proofs.lock().await.proofs.len()
+    }
+}
+
+#[async_trait]
+impl MintDatabase for MintTieredDatabase {
+    type Err = Error;
+
+    async fn set_mint_info(&self, mint_info: &MintInfo) -> Result<(), Self::Err> {
+        self.inner.set_mint_info(mint_info).await
+    }
+    async fn get_mint_info(&self) -> Result<MintInfo, Self::Err> {
+        self.inner.get_mint_info().await
+    }
+
+    async fn add_active_keyset(&self, unit: CurrencyUnit, id: Id) -> Result<(), Self::Err> {
+        self.inner.add_active_keyset(unit, id).await
+    }
+    async fn get_active_keyset_id(&self, unit: &CurrencyUnit) -> Result<Option<Id>, Self::Err> {
+        self.inner.get_active_keyset_id(unit).await
+    }
+    async fn get_active_keysets(&self) -> Result<HashMap<CurrencyUnit, Id>, Self::Err> {
+        self.inner.get_active_keysets().await
+    }
+
+    async fn add_mint_quote(&self, quote: MintQuote) -> Result<(), Self::Err> {
+        self.inner.add_mint_quote(quote).await
+    }
+    async fn get_mint_quote(&self, quote_id: &str) -> Result<Option<MintQuote>, Self::Err> {
+        self.inner.get_mint_quote(quote_id).await
+    }
+    async fn get_mint_quotes(&self) -> Result<Vec<MintQuote>, Self::Err> {
+        self.inner.get_mint_quotes().await
+    }
+    async fn get_mint_quote_by_payment_hash(
+        &self,
+        payment_hash: &Sha256,
+    ) -> Result<Option<MintQuote>, Self::Err> {
+        self.inner
+            .get_mint_quote_by_payment_hash(payment_hash)
+            .await
+    }
+    async fn remove_mint_quote(&self, quote_id: &str) -> Result<(), Self::Err> {
+        self.inner.remove_mint_quote(quote_id).await
+    }
+    async fn remove_mint_quotes(&self, quote_ids: &[String]) -> Result<(), Self::Err> {
+        self.inner.remove_mint_quotes(quote_ids).await
+    }
+
+    async fn add_melt_quote(&self, quote: MeltQuote) -> Result<(), Self::Err> {
+        self.inner.add_melt_quote(quote).await
+    }
+    async fn get_melt_quote(&self, quote_id: &str) -> Result<Option<MeltQuote>, Self::Err> {
+        self.inner.get_melt_quote(quote_id).await
+    }
+    async fn get_melt_quotes(&self) -> Result<Vec<MeltQuote>, Self::Err> {
+        self.inner.get_melt_quotes().await
+    }
+    async fn remove_melt_quote(&self, quote_id: &str) -> Result<(), Self::Err> {
+        self.inner.remove_melt_quote(quote_id).await
+    }
+    async fn remove_melt_quotes(&self, quote_ids: &[String]) -> Result<(), Self::Err> {
+        self.inner.remove_melt_quotes(quote_ids).await
+    }
+
+    async fn add_keyset(&self, keyset: MintKeySet) -> Result<(), Self::Err> {
+        self.inner.add_keyset(keyset).await
+    }
+    async fn get_keyset(&self, id: &Id) -> Result<Option<MintKeySet>, Self::Err> {
+        self.inner.get_keyset(id).await
+    }
+    async fn get_keysets(&self) -> Result<Vec<MintKeySet>, Self::Err> {
+        self.inner.get_keysets().await
+    }
+    async fn remove_keyset(&self, id: &Id) -> Result<(), Self::Err> {
+        self.inner.remove_keyset(id).await
+    }
+
+    async fn add_spent_proof(&self, proof: Proof) -> Result<(), Self::Err> {
+        let y = proof.y()?.to_bytes();
+
+        // Persist before caching so an evicted proof is never lost
+        self.inner.add_spent_proof(proof.clone()).await?;
+
+        self.hot_spent_proofs
+            .lock()
+            .await
+            .insert(y, proof, self.max_cached_proofs);
+
+        Ok(())
+    }
+    async fn add_spent_proofs_if_unspent(
+        &self,
+        proofs: Proofs,
+    ) -> Result<Vec<PublicKey>, Self::Err> {
+        // The inner database decides, the cache is not a complete spent set
+        let spent = self
+            .inner
+            .add_spent_proofs_if_unspent(proofs.clone())
+            .await?;
+        if !spent.is_empty() {
+            return Ok(spent);
+        }
+
+        let ys = proofs
+            .iter()
+            .map(Proof::y)
+            .collect::<Result<Vec<PublicKey>, _>>()?;
+        let mut hot = self.hot_spent_proofs.lock().await;
+        for (y, proof) in ys.into_iter().zip(proofs) {
+            hot.insert(y.to_bytes(), proof, self.max_cached_proofs);
+        }
+
+        Ok(spent)
+    }
+    async fn get_spent_proof_by_secret(&self, secret: &Secret) -> Result<Option<Proof>, Self::Err> {
+        let y = hash_to_curve(&secret.to_bytes())?;
+        self.get_spent_proof_by_y(&y).await
+    }
+    async fn get_spent_proof_by_y(&self, y: &PublicKey) -> Result<Option<Proof>, Self::Err> {
+        if let Some(proof) = self.hot_spent_proofs.lock().await.proofs.get(&y.to_bytes()) {
+            return Ok(Some(proof.clone()));
+        }
+
+        self.inner.get_spent_proof_by_y(y).await
+    }
+    async fn get_spent_ys(&self) -> Result<Vec<PublicKey>, Self::Err> {
+        self.inner.get_spent_ys().await
+    }
+    async fn get_spent_proofs_by_keyset(&self, keyset_id: &Id) -> Result<Proofs, Self::Err> {
+        self.inner.get_spent_proofs_by_keyset(keyset_id).await
+    }
+    async fn get_spent_count_by_keyset(&self, keyset_id: &Id) -> Result<u64, Self::Err> {
+        self.inner.get_spent_count_by_keyset(keyset_id).await
+    }
+    async fn remove_spent_proofs(&self, ys: &[PublicKey]) -> Result<(), Self::Err> {
+        let mut hot = self.hot_spent_proofs.lock().await;
+        for y in ys {
+            let y = y.to_bytes();
+            if hot.proofs.remove(&y).is_some() {
+                hot.order.retain(|cached| *cached != y);
+            }
+        }
+        drop(hot);
+
+        self.inner.remove_spent_proofs(ys).await
+    }
+
+    async fn add_pending_proof(&self, proof: Proof) -> Result<(), Self::Err> {
+        self.inner.add_pending_proof(proof).await
+    }
+    async fn add_pending_proofs_if_unspent(
+        &self,
+        proofs: Proofs,
+    ) -> Result<Vec<PublicKey>, Self::Err> {
+        self.inner.add_pending_proofs_if_unspent(proofs).await
+    }
+    async fn get_pending_proof_by_secret(
+        &self,
+        secret: &Secret,
+    ) -> Result<Option<Proof>, Self::Err> {
+        self.inner.get_pending_proof_by_secret(secret).await
+    }
+    async fn get_pending_proof_by_y(&self, y: &PublicKey) -> Result<Option<Proof>, Self::Err> {
+        self.inner.get_pending_proof_by_y(y).await
+    }
+    async fn remove_pending_proof(&self, secret: &Secret) -> Result<(), Self::Err> {
+        self.inner.remove_pending_proof(secret).await
+    }
+
+    async fn add_blinded_signature(
+        &self,
+        blinded_message: PublicKey,
+        blinded_signature: BlindSignature,
+    ) -> Result<(), Self::Err> {
+        self.inner
+            .add_blinded_signature(blinded_message, blinded_signature)
+            .await
+    }
+    async fn get_blinded_signature(
+        &self,
+        blinded_message: &PublicKey,
+    ) -> Result<Option<BlindSignature>, Self::Err> {
+        self.inner.get_blinded_signature(blinded_message).await
+    }
+    async fn get_blinded_signatures(
+        &self,
+        blinded_messages: Vec<PublicKey>,
+    ) -> Result<Vec<Option<BlindSignature>>, Self::Err> {
+        self.inner.get_blinded_signatures(blinded_messages).await
+    }
+    async fn get_blinded_signatures_by_keyset(
+        &self,
+        keyset_id: &Id,
+    ) -> Result<Vec<BlindSignature>, Self::Err> {
+        self.inner.get_blinded_signatures_by_keyset(keyset_id).await
+    }
+
+    async fn commit_transaction(
+        &self,
+        transaction: MintTransaction,
+    ) -> Result<Vec<PublicKey>, Self::Err> {
+        let proofs = transaction.spent_proofs.clone();
+        let spent = self.inner.commit_transaction(transaction).await?;
+        if !spent.is_empty() {
+            return Ok(spent);
+        }
+
+        let ys = proofs
+            .iter()
+            .map(Proof::y)
+            .collect::<Result<Vec<PublicKey>, _>>()?;
+        let mut hot = self.hot_spent_proofs.lock().await;
+        for (y, proof) in ys.into_iter().zip(proofs) {
+            hot.insert(y.to_bytes(), proof, self.max_cached_proofs);
+        }
+
+        Ok(spent)
+    }
+    async fn get_swap_response(
+        &self,
+        request_hash: &Sha256,
+    ) -> Result<Option<SwapResponse>, Self::Err> {
+        self.inner.get_swap_response(request_hash).await
+    }
+
+    async fn remove_expired_swap_responses(&self, now: u64) -> Result<usize, Self::Err> {
+        self.inner.remove_expired_swap_responses(now).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::cdk_database::mint_memory::MintMemoryDatabase;
+    use crate::Amount;
+
+    fn proof() -> Proof {
+        Proof::new(
+            Amount::from(1),
+            Id::from_str("009a1f293253e41e").unwrap(),
+            Secret::generate(),
+            PublicKey::from_str(
+                "02194603ffa36356f4a56b7df9371fc3192472351453ec7398b8da8117e7c3e104",
+            )
+            .unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_spent_proofs_survive_eviction() {
+        let inner = MintMemoryDatabase::new(
+            MintInfo::default(),
+            HashMap::new(),
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            HashMap::new(),
+        )
+        .unwrap();
+        let db = MintTieredDatabase::new(Arc::new(inner), 2);
+
+        let proofs: Vec<Proof> = (0..5).map(|_| proof()).collect();
+        for proof in &proofs {
+            db.add_spent_proof(proof.clone()).await.unwrap();
+        }
+
+        assert_eq!(db.cached_spent_proofs().await, 2);
+
+        // Oldest proof has been evicted from memory but is still spent
+        for proof in &proofs {
+            assert_eq!(
+                db.get_spent_proof_by_secret(&proof.secret).await.unwrap(),
+                Some(proof.clone())
+            );
+        }
+
+        assert!(db
+            .get_spent_proof_by_secret(&Secret::generate())
+            .await
+            .unwrap()
+            .is_none());
+
+        // Spending the same proof again does not grow the cache
+        db.add_spent_proof(proofs[4].clone()).await.unwrap();
+        assert_eq!(db.cached_spent_proofs().await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_add_spent_proofs_if_unspent() {
+        let inner = MintMemoryDatabase::new(
+            MintInfo::default(),
+            HashMap::new(),
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            HashMap::new(),
+        )
+        .unwrap();
+        let db = MintTieredDatabase::new(Arc::new(inner), 10);
+
+        let proofs: Vec<Proof> = (0..3).map(|_| proof()).collect();
+        let y = |i: usize| proofs[i].y().unwrap();
+
+        let spent = db
+            .add_spent_proofs_if_unspent(proofs[..2].to_vec())
+            .await
+            .unwrap();
+        assert!(spent.is_empty());
+        assert_eq!(db.cached_spent_proofs().await, 2);
+
+        // One spent proof keeps the whole batch unspent
+        let spent = db
+            .add_spent_proofs_if_unspent(vec![proofs[2].clone(), proofs[1].clone()])
+            .await
+            .unwrap();
+        assert_eq!(spent, vec![y(1)]);
+        assert!(db.get_spent_proof_by_y(&y(2)).await.unwrap().is_none());
+        assert_eq!(db.cached_spent_proofs().await, 2);
+
+        // As does a proof given twice
+        let spent = db
+            .add_spent_proofs_if_unspent(vec![proofs[2].clone(), proofs[2].clone()])
+            .await
+            .unwrap();
+        assert_eq!(spent, vec![y(2)]);
+        assert!(db.get_spent_proof_by_y(&y(2)).await.unwrap().is_none());
+    }
+}
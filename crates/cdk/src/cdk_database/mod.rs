@@ -3,18 +3,24 @@
 use std::collections::HashMap;
 
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::nuts::{
     BlindSignature, CurrencyUnit, Id, KeySetInfo, Keys, MintInfo, MintKeySet, Proof, Proofs,
-    PublicKey,
+    PublicKey, SwapResponse,
 };
 use crate::secret::Secret;
-use crate::types::{MeltQuote, MintQuote};
+use crate::types::{MeltQuote, MintQuote, ReceivedToken, Transaction};
 use crate::url::UncheckedUrl;
+use crate::Sha256;
 
+#[cfg(feature = "mint")]
+pub mod mint_bloom;
 #[cfg(feature = "mint")]
 pub mod mint_memory;
+#[cfg(feature = "mint")]
+pub mod mint_tiered;
 #[cfg(feature = "wallet")]
 pub mod wallet_memory;
 
@@ -77,8 +83,104 @@ pub trait WalletDatabase {
         proofs: &Proofs,
     ) -> Result<(), Self::Err>;
 
+    /// Inputs of a melt that has not finished, neither spendable nor sent
+    async fn add_reserved_proofs(
+        &self,
+        mint_url: UncheckedUrl,
+        proofs: Proofs,
+    ) -> Result<(), Self::Err>;
+    async fn get_reserved_proofs(
+        &self,
+        mint_url: UncheckedUrl,
+    ) -> Result<Option<Proofs>, Self::Err>;
+    async fn remove_reserved_proofs(
+        &self,
+        mint_url: UncheckedUrl,
+        proofs: &Proofs,
+    ) -> Result<(), Self::Err>;
+
     async fn increment_keyset_counter(&self, keyset_id: &Id, count: u64) -> Result<(), Self::Err>;
+    async fn set_keyset_counter(&self, keyset_id: &Id, counter: u64) -> Result<(), Self::Err>;
     async fn get_keyset_counter(&self, keyset_id: &Id) -> Result<Option<u64>, Self::Err>;
+
+    /// Add `transaction`, replacing the stored one with its id
+    async fn add_transaction(&self, transaction: Transaction) -> Result<(), Self::Err>;
+    async fn get_transaction(&self, id: &str) -> Result<Option<Transaction>, Self::Err>;
+    async fn get_transactions(&self) -> Result<Vec<Transaction>, Self::Err>;
+
+    /// Add `token` unless a stored received token shares a proof with it,
+    /// returning the stored tokens that do
+    ///
+    /// The check and the write are one step, so of concurrent calls with the
+    /// same proofs only one adds its token.
+    async fn add_received_token(
+        &self,
+        token: ReceivedToken,
+    ) -> Result<Vec<ReceivedToken>, Self::Err>;
+    async fn remove_received_token(&self, fingerprint: &Sha256) -> Result<(), Self::Err>;
+}
+
+/// Writes staged by an operation of a mint, applied together by
+/// [MintDatabase::commit_transaction]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MintTransaction {
+    /// Proofs to record spent, only if none of them is spent or pending
+    pub spent_proofs: Proofs,
+    /// `Y`s of proofs held pending by the operation itself, released as
+    /// they are spent
+    pub released_ys: Vec<PublicKey>,
+    /// Blind signatures by blinded message
+    pub blinded_signatures: Vec<(PublicKey, BlindSignature)>,
+    /// Swap responses by the hash of their request
+    pub swap_responses: Vec<(Sha256, CachedSwapResponse)>,
+}
+
+/// Swap response answered again to resubmissions of its request until it
+/// is pruned
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedSwapResponse {
+    #[serde(flatten)]
+    pub response: SwapResponse,
+    /// Unix time the response may be pruned at, `0` for responses cached
+    /// before they had an expiry
+    #[serde(default)]
+    pub expiry: u64,
+}
+
+impl MintTransaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spend(&mut self, proofs: Proofs) {
+        self.spent_proofs.extend(proofs);
+    }
+
+    /// Spend proofs pending under the reservation of this operation
+    pub fn release(&mut self, ys: Vec<PublicKey>) {
+        self.released_ys.extend(ys);
+    }
+
+    pub fn add_blinded_signature(
+        &mut self,
+        blinded_message: PublicKey,
+        blinded_signature: BlindSignature,
+    ) {
+        self.blinded_signatures
+            .push((blinded_message, blinded_signature));
+    }
+
+    /// Cache `response` for the swap request with `request_hash` until
+    /// `expiry`
+    pub fn cache_swap_response(
+        &mut self,
+        request_hash: Sha256,
+        response: SwapResponse,
+        expiry: u64,
+    ) {
+        self.swap_responses
+            .push((request_hash, CachedSwapResponse { response, expiry }));
+    }
 }
 
 #[async_trait]
@@ -95,22 +197,56 @@ pub trait MintDatabase {
     async fn add_mint_quote(&self, quote: MintQuote) -> Result<(), Self::Err>;
     async fn get_mint_quote(&self, quote_id: &str) -> Result<Option<MintQuote>, Self::Err>;
     async fn get_mint_quotes(&self) -> Result<Vec<MintQuote>, Self::Err>;
+    /// Mint quote for the invoice with `payment_hash`, the latest added if
+    /// several share it
+    async fn get_mint_quote_by_payment_hash(
+        &self,
+        payment_hash: &Sha256,
+    ) -> Result<Option<MintQuote>, Self::Err>;
     async fn remove_mint_quote(&self, quote_id: &str) -> Result<(), Self::Err>;
+    async fn remove_mint_quotes(&self, quote_ids: &[String]) -> Result<(), Self::Err>;
 
     async fn add_melt_quote(&self, quote: MeltQuote) -> Result<(), Self::Err>;
     async fn get_melt_quote(&self, quote_id: &str) -> Result<Option<MeltQuote>, Self::Err>;
     async fn get_melt_quotes(&self) -> Result<Vec<MeltQuote>, Self::Err>;
     async fn remove_melt_quote(&self, quote_id: &str) -> Result<(), Self::Err>;
+    async fn remove_melt_quotes(&self, quote_ids: &[String]) -> Result<(), Self::Err>;
 
     async fn add_keyset(&self, keyset: MintKeySet) -> Result<(), Self::Err>;
     async fn get_keyset(&self, id: &Id) -> Result<Option<MintKeySet>, Self::Err>;
     async fn get_keysets(&self) -> Result<Vec<MintKeySet>, Self::Err>;
+    async fn remove_keyset(&self, id: &Id) -> Result<(), Self::Err>;
 
+    /// Record `proof` as spent, keeping its witness for state checks
     async fn add_spent_proof(&self, proof: Proof) -> Result<(), Self::Err>;
+    /// Record `proofs` as spent if none of them is spent or pending, atomically
+    ///
+    /// Returns the `Y`s of the proofs that were already spent or pending, or
+    /// that appear more than once in `proofs`, in which case nothing is
+    /// recorded. Two concurrent calls with a proof in common never both
+    /// succeed.
+    async fn add_spent_proofs_if_unspent(
+        &self,
+        proofs: Proofs,
+    ) -> Result<Vec<PublicKey>, Self::Err>;
     async fn get_spent_proof_by_secret(&self, secret: &Secret) -> Result<Option<Proof>, Self::Err>;
     async fn get_spent_proof_by_y(&self, y: &PublicKey) -> Result<Option<Proof>, Self::Err>;
+    async fn get_spent_ys(&self) -> Result<Vec<PublicKey>, Self::Err>;
+    async fn get_spent_proofs_by_keyset(&self, keyset_id: &Id) -> Result<Proofs, Self::Err>;
+    async fn get_spent_count_by_keyset(&self, keyset_id: &Id) -> Result<u64, Self::Err>;
+    async fn remove_spent_proofs(&self, ys: &[PublicKey]) -> Result<(), Self::Err>;
 
     async fn add_pending_proof(&self, proof: Proof) -> Result<(), Self::Err>;
+    /// Record `proofs` as pending if none of them is spent or pending,
+    /// atomically
+    ///
+    /// Returns the `Y`s in conflict as
+    /// [MintDatabase::add_spent_proofs_if_unspent] does, in which case
+    /// nothing is recorded.
+    async fn add_pending_proofs_if_unspent(
+        &self,
+        proofs: Proofs,
+    ) -> Result<Vec<PublicKey>, Self::Err>;
     async fn get_pending_proof_by_secret(
         &self,
         secret: &Secret,
@@ -131,4 +267,28 @@ pub trait MintDatabase {
         &self,
         blinded_messages: Vec<PublicKey>,
     ) -> Result<Vec<Option<BlindSignature>>, Self::Err>;
+    async fn get_blinded_signatures_by_keyset(
+        &self,
+        keyset_id: &Id,
+    ) -> Result<Vec<BlindSignature>, Self::Err>;
+
+    /// Apply every write of `transaction` in one step, so an error or a
+    /// crash leaves all of them or none
+    ///
+    /// Returns the `Y`s of the proofs to spend that were already spent, or
+    /// pending other than as [released](MintTransaction::release), as
+    /// [MintDatabase::add_spent_proofs_if_unspent] does, in which case
+    /// nothing is written.
+    async fn commit_transaction(
+        &self,
+        transaction: MintTransaction,
+    ) -> Result<Vec<PublicKey>, Self::Err>;
+    /// Response cached for the swap request with `request_hash`
+    async fn get_swap_response(
+        &self,
+        request_hash: &Sha256,
+    ) -> Result<Option<SwapResponse>, Self::Err>;
+    /// Remove the cached swap responses whose expiry is at or before `now`,
+    /// returning how many were removed
+    async fn remove_expired_swap_responses(&self, now: u64) -> Result<usize, Self::Err>;
 }
@@ -8,9 +8,10 @@ use tokio::sync::Mutex;
 
 use super::WalletDatabase;
 use crate::cdk_database::Error;
-use crate::nuts::{Id, KeySetInfo, Keys, MintInfo, Proof, Proofs};
-use crate::types::{MeltQuote, MintQuote};
+use crate::nuts::{Id, KeySetInfo, Keys, MintInfo, Proof, Proofs, PublicKey};
+use crate::types::{MeltQuote, MintQuote, ReceivedToken, Transaction};
 use crate::url::UncheckedUrl;
+use crate::Sha256;
 
 #[derive(Default, Debug, Clone)]
 pub struct WalletMemoryDatabase {
@@ -19,9 +20,13 @@ pub struct WalletMemoryDatabase {
     mint_quotes: Arc<Mutex<HashMap<String, MintQuote>>>,
     melt_quotes: Arc<Mutex<HashMap<String, MeltQuote>>>,
     mint_keys: Arc<Mutex<HashMap<Id, Keys>>>,
-    proofs: Arc<Mutex<HashMap<UncheckedUrl, HashSet<Proof>>>>,
-    pending_proofs: Arc<Mutex<HashMap<UncheckedUrl, HashSet<Proof>>>>,
+    /// Proofs by their `Y`, so the same proof is only stored once
+    proofs: Arc<Mutex<HashMap<UncheckedUrl, HashMap<PublicKey, Proof>>>>,
+    pending_proofs: Arc<Mutex<HashMap<UncheckedUrl, HashMap<PublicKey, Proof>>>>,
+    reserved_proofs: Arc<Mutex<HashMap<UncheckedUrl, HashMap<PublicKey, Proof>>>>,
     keyset_counter: Arc<Mutex<HashMap<Id, u64>>>,
+    transactions: Arc<Mutex<HashMap<String, Transaction>>>,
+    received_tokens: Arc<Mutex<HashMap<Sha256, ReceivedToken>>>,
 }
 
 impl WalletMemoryDatabase {
@@ -45,7 +50,10 @@ impl WalletMemoryDatabase {
             )),
             proofs: Arc::new(Mutex::new(HashMap::new())),
             pending_proofs: Arc::new(Mutex::new(HashMap::new())),
+            reserved_proofs: Arc::new(Mutex::new(HashMap::new())),
             keyset_counter: Arc::new(Mutex::new(keyset_counter)),
+            transactions: Arc::new(Mutex::new(HashMap::new())),
+            received_tokens: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 }
@@ -79,6 +87,8 @@ impl WalletDatabase for WalletMemoryDatabase {
         let mut current_keysets = self.mint_keysets.lock().await;
 
         let mint_keysets = current_keysets.entry(mint_url).or_insert(HashSet::new());
+        // A keyset sent again replaces the stored one, which may have been active
+        mint_keysets.retain(|stored| keysets.iter().all(|keyset| keyset.id != stored.id));
         mint_keysets.extend(keysets);
 
         Ok(())
@@ -149,8 +159,10 @@ impl WalletDatabase for WalletMemoryDatabase {
     async fn add_proofs(&self, mint_url: UncheckedUrl, proofs: Proofs) -> Result<(), Error> {
         let mut all_proofs = self.proofs.lock().await;
 
-        let mint_proofs = all_proofs.entry(mint_url).or_insert(HashSet::new());
-        mint_proofs.extend(proofs);
+        let mint_proofs = all_proofs.entry(mint_url).or_default();
+        for proof in proofs {
+            mint_proofs.entry(proof.y()?).or_insert(proof);
+        }
 
         Ok(())
     }
@@ -161,7 +173,7 @@ impl WalletDatabase for WalletMemoryDatabase {
             .lock()
             .await
             .get(&mint_url)
-            .map(|p| p.iter().cloned().collect()))
+            .map(|p| p.values().cloned().collect()))
     }
 
     async fn remove_proofs(&self, mint_url: UncheckedUrl, proofs: &Proofs) -> Result<(), Error> {
@@ -169,7 +181,7 @@ impl WalletDatabase for WalletMemoryDatabase {
 
         if let Some(mint_proofs) = mint_proofs.get_mut(&mint_url) {
             for proof in proofs {
-                mint_proofs.remove(proof);
+                mint_proofs.remove(&proof.y()?);
             }
         }
 
@@ -183,8 +195,10 @@ impl WalletDatabase for WalletMemoryDatabase {
     ) -> Result<(), Error> {
         let mut all_proofs = self.pending_proofs.lock().await;
 
-        let mint_proofs = all_proofs.entry(mint_url).or_insert(HashSet::new());
-        mint_proofs.extend(proofs);
+        let mint_proofs = all_proofs.entry(mint_url).or_default();
+        for proof in proofs {
+            mint_proofs.entry(proof.y()?).or_insert(proof);
+        }
 
         Ok(())
     }
@@ -195,7 +209,7 @@ impl WalletDatabase for WalletMemoryDatabase {
             .lock()
             .await
             .get(&mint_url)
-            .map(|p| p.iter().cloned().collect()))
+            .map(|p| p.values().cloned().collect()))
     }
 
     async fn remove_pending_proofs(
@@ -207,24 +221,145 @@ impl WalletDatabase for WalletMemoryDatabase {
 
         if let Some(mint_proofs) = mint_proofs.get_mut(&mint_url) {
             for proof in proofs {
-                mint_proofs.remove(proof);
+                mint_proofs.remove(&proof.y()?);
             }
         }
 
         Ok(())
     }
 
-    async fn increment_keyset_counter(&self, keyset_id: &Id, count: u64) -> Result<(), Error> {
-        let keyset_counter = self.keyset_counter.lock().await;
-        let current_counter = keyset_counter.get(keyset_id).unwrap_or(&0);
-        self.keyset_counter
+    async fn add_reserved_proofs(
+        &self,
+        mint_url: UncheckedUrl,
+        proofs: Proofs,
+    ) -> Result<(), Error> {
+        let mut all_proofs = self.reserved_proofs.lock().await;
+
+        let mint_proofs = all_proofs.entry(mint_url).or_default();
+        for proof in proofs {
+            mint_proofs.entry(proof.y()?).or_insert(proof);
+        }
+
+        Ok(())
+    }
+
+    async fn get_reserved_proofs(&self, mint_url: UncheckedUrl) -> Result<Option<Proofs>, Error> {
+        Ok(self
+            .reserved_proofs
             .lock()
             .await
-            .insert(*keyset_id, current_counter + count);
+            .get(&mint_url)
+            .map(|p| p.values().cloned().collect()))
+    }
+
+    async fn remove_reserved_proofs(
+        &self,
+        mint_url: UncheckedUrl,
+        proofs: &Proofs,
+    ) -> Result<(), Error> {
+        let mut mint_proofs = self.reserved_proofs.lock().await;
+
+        if let Some(mint_proofs) = mint_proofs.get_mut(&mint_url) {
+            for proof in proofs {
+                mint_proofs.remove(&proof.y()?);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn increment_keyset_counter(&self, keyset_id: &Id, count: u64) -> Result<(), Error> {
+        let mut keyset_counter = self.keyset_counter.lock().await;
+        *keyset_counter.entry(*keyset_id).or_default() += count;
+        Ok(())
+    }
+
+    async fn set_keyset_counter(&self, keyset_id: &Id, counter: u64) -> Result<(), Error> {
+        self.keyset_counter.lock().await.insert(*keyset_id, counter);
         Ok(())
     }
 
     async fn get_keyset_counter(&self, id: &Id) -> Result<Option<u64>, Error> {
         Ok(self.keyset_counter.lock().await.get(id).cloned())
     }
+
+    async fn add_transaction(&self, transaction: Transaction) -> Result<(), Error> {
+        self.transactions
+            .lock()
+            .await
+            .insert(transaction.id.clone(), transaction);
+        Ok(())
+    }
+
+    async fn get_transaction(&self, id: &str) -> Result<Option<Transaction>, Error> {
+        Ok(self.transactions.lock().await.get(id).cloned())
+    }
+
+    async fn get_transactions(&self) -> Result<Vec<Transaction>, Error> {
+        Ok(self.transactions.lock().await.values().cloned().collect())
+    }
+
+    async fn add_received_token(&self, token: ReceivedToken) -> Result<Vec<ReceivedToken>, Error> {
+        let mut received_tokens = self.received_tokens.lock().await;
+
+        let overlapping: Vec<ReceivedToken> = received_tokens
+            .values()
+            .filter(|received| received.ys.iter().any(|y| token.ys.contains(y)))
+            .cloned()
+            .collect();
+        if overlapping.is_empty() {
+            received_tokens.insert(token.fingerprint, token);
+        }
+
+        Ok(overlapping)
+    }
+
+    async fn remove_received_token(&self, fingerprint: &Sha256) -> Result<(), Error> {
+        self.received_tokens.lock().await.remove(fingerprint);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::nuts::ProofsMethods;
+    use crate::secret::Secret;
+    use crate::Amount;
+
+    #[tokio::test]
+    async fn test_same_proof_is_stored_once() {
+        let db = WalletMemoryDatabase::default();
+        let mint_url = UncheckedUrl::from_str("https://mint.example.com").unwrap();
+
+        let proof = Proof::new(
+            Amount::from(8),
+            Id::from_str("009a1f293253e41e").unwrap(),
+            Secret::generate(),
+            PublicKey::from_str(
+                "02194603ffa36356f4a56b7df9371fc3192472351453ec7398b8da8117e7c3e104",
+            )
+            .unwrap(),
+        );
+        let mut reimported = proof.clone();
+        reimported.keyset_id = Id::from_str("00ad268c4d1f5826").unwrap();
+
+        db.add_proofs(mint_url.clone(), vec![proof.clone()])
+            .await
+            .unwrap();
+        db.add_proofs(mint_url.clone(), vec![reimported.clone()])
+            .await
+            .unwrap();
+
+        let stored = db.get_proofs(mint_url.clone()).await.unwrap().unwrap();
+        assert_eq!(stored, vec![proof]);
+        assert_eq!(stored.total_amount().unwrap(), Amount::from(8));
+
+        db.remove_proofs(mint_url.clone(), &vec![reimported])
+            .await
+            .unwrap();
+        assert!(db.get_proofs(mint_url).await.unwrap().unwrap().is_empty());
+    }
 }
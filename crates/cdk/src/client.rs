@@ -1,20 +1,32 @@
 //! Wallet client
 
-use reqwest::Client;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bitcoin::secp256k1::rand::{self, RngCore};
+use reqwest::{Client, Proxy, RequestBuilder, StatusCode};
+use serde::Serialize;
 use serde_json::Value;
 use thiserror::Error;
+use tokio::sync::Semaphore;
+use tracing::{error, warn};
 use url::Url;
 
 use crate::error::ErrorResponse;
 use crate::nuts::{
-    BlindedMessage, CheckStateRequest, CheckStateResponse, CurrencyUnit, Id, KeySet, KeysResponse,
-    KeysetResponse, MeltBolt11Request, MeltBolt11Response, MeltQuoteBolt11Request,
-    MeltQuoteBolt11Response, MintBolt11Request, MintBolt11Response, MintInfo,
-    MintQuoteBolt11Request, MintQuoteBolt11Response, PreMintSecrets, Proof, PublicKey,
+    CheckFeesRequest, CheckFeesResponse, CheckStateRequest, CheckStateResponse, CurrencyUnit, Id,
+    KeySet, KeysResponse, KeysetResponse, MeltBolt11Request, MeltBolt11Response,
+    MeltQuoteBolt11Request, MeltQuoteBolt11Response, MintBolt11Request, MintBolt11Response,
+    MintInfo, MintQuoteBolt11Request, MintQuoteBolt11Response, ProofState, PublicKey,
     RestoreRequest, RestoreResponse, SwapRequest, SwapResponse,
 };
 use crate::{Amount, Bolt11Invoice};
 
+#[cfg(feature = "ws")]
+pub mod ws;
+
 #[derive(Debug, Error)]
 pub enum Error {
     /// Unknown Keyset
@@ -29,6 +41,117 @@ pub enum Error {
     ///  Min req error
     #[error("Unknown Error response")]
     UnknownErrorResponse(crate::error::ErrorResponse),
+    /// NUT02 error, such as a keyset id that does not match its keys
+    #[error(transparent)]
+    NUT02(#[from] crate::nuts::nut02::Error),
+    /// Request still failed after being retried, the mint never answered it
+    #[error("Gave up after {attempts} attempts: {source}")]
+    RetriesExhausted {
+        attempts: u32,
+        #[source]
+        source: Box<Error>,
+    },
+    /// Proxy could not be reached or could not connect to the mint
+    #[error("Connection through proxy failed: {0}")]
+    Proxy(#[source] reqwest::Error),
+    /// Onion mint without a proxy, it is not looked up locally
+    #[error("Onion mint `{0}` can only be reached through a proxy")]
+    OnionWithoutProxy(String),
+    #[error("Invalid proxy `{0}`")]
+    InvalidProxy(String),
+    /// Request did not get a usable response from the mint
+    #[error(transparent)]
+    Network(#[from] NetworkError),
+    /// Check state response left out Ys that were asked for [NUT-07]
+    #[error("Check state response does not match the request")]
+    InvalidCheckStateResponse,
+    /// Task sending a batch of requests did not finish
+    #[error(transparent)]
+    Join(#[from] tokio::task::JoinError),
+    /// WebSocket could not be opened or broke the protocol
+    #[cfg(feature = "ws")]
+    #[error("WebSocket error: {0}")]
+    WebSocket(String),
+    /// Mint refused a subscribe or unsubscribe request [NUT-17]
+    #[cfg(feature = "ws")]
+    #[error("Subscription refused ({code}): {message}")]
+    SubscriptionRefused { code: i64, message: String },
+}
+
+/// Why a request did not get a usable response
+#[derive(Debug, Error)]
+pub enum NetworkError {
+    /// Connect or total timeout of the [ClientBuilder] elapsed
+    #[error("Request timed out")]
+    Timeout,
+    /// Nothing listens on the mint address
+    #[error("Connection refused")]
+    ConnectionRefused,
+    /// TLS handshake failed, e.g. an invalid certificate
+    #[error("TLS failure: {0}")]
+    Tls(String),
+    /// Mint hostname could not be resolved
+    #[error("DNS lookup failed: {0}")]
+    Dns(String),
+    /// Response body is not JSON
+    #[error("Malformed response: {0}")]
+    MalformedResponse(String),
+    #[error(transparent)]
+    Other(reqwest::Error),
+}
+
+impl From<reqwest::Error> for NetworkError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            return Self::Timeout;
+        }
+        if err.is_decode() {
+            return Self::MalformedResponse(error_chain(&err));
+        }
+
+        if err.is_connect() {
+            let mut source = std::error::Error::source(&err);
+            while let Some(cause) = source {
+                if let Some(io) = cause.downcast_ref::<std::io::Error>() {
+                    match io.kind() {
+                        std::io::ErrorKind::ConnectionRefused => return Self::ConnectionRefused,
+                        std::io::ErrorKind::TimedOut => return Self::Timeout,
+                        // TLS libraries report handshake failures as invalid data
+                        std::io::ErrorKind::InvalidData => {
+                            return Self::Tls(error_chain(&err));
+                        }
+                        _ => (),
+                    }
+                }
+                // hyper names the resolver failure, the io error under it is
+                // platform specific
+                if cause.to_string().starts_with("dns error") {
+                    return Self::Dns(error_chain(&err));
+                }
+                source = cause.source();
+            }
+
+            let chain = error_chain(&err);
+            if chain.contains("certificate") || chain.to_lowercase().contains("tls") {
+                return Self::Tls(chain);
+            }
+        }
+
+        Self::Other(err)
+    }
+}
+
+/// `err` and its sources, joined by `: `
+fn error_chain(err: &dyn std::error::Error) -> String {
+    let mut chain = err.to_string();
+    let mut source = err.source();
+    while let Some(cause) = source {
+        chain.push_str(": ");
+        chain.push_str(&cause.to_string());
+        source = cause.source();
+    }
+
+    chain
 }
 
 impl From<ErrorResponse> for Error {
@@ -55,9 +178,301 @@ fn join_url(url: Url, paths: &[&str]) -> Result<Url, Error> {
     Ok(url)
 }
 
+/// Refuse keysets whose id was not derived from their keys
+fn verify_keyset_ids(keysets: &[KeySet]) -> Result<(), Error> {
+    for keyset in keysets {
+        if let Err(err) = keyset.verify_id() {
+            error!("Mint sent keyset with mismatched id: {err}");
+            return Err(err.into());
+        }
+    }
+
+    Ok(())
+}
+
+/// When and how often failed requests are retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts per request, the first one included
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for every further one
+    pub base_delay: Duration,
+    /// Upper bound of the delay between two attempts
+    pub max_delay: Duration,
+    /// Mint answers a resubmitted request from its cache [NUT-19], so POSTs
+    /// that already reached it can be sent again without spending twice
+    pub cached_responses: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(5),
+            cached_responses: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Send every request once
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Exponential backoff before retry number `retry`, with jitter over
+    /// its upper half so wallets that failed together do not retry together
+    fn delay(&self, retry: u32) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay);
+        let jitter = f64::from(rand::thread_rng().next_u32()) / f64::from(u32::MAX);
+
+        backoff.mul_f64(0.5 + jitter / 2.0)
+    }
+}
+
+/// Failures a request may be retried after
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Retry {
+    /// Request has no effect on the mint, or the mint caches its response
+    Always,
+    /// Only failures before the request reached the mint, such as DNS or
+    /// connect errors
+    PreFlight,
+}
+
+/// Body of `res`, a body that is not JSON is [NetworkError::MalformedResponse]
+async fn read_json(res: reqwest::Response) -> Result<Value, Error> {
+    Ok(res.json::<Value>().await.map_err(NetworkError::from)?)
+}
+
+/// Status of a response that is worth asking again for
+fn is_transient(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// SOCKS5 proxy to reach mints through, e.g. a Tor daemon
+///
+/// Hostnames are resolved by the proxy, never locally, so `.onion` mints can
+/// be reached and their names do not leak to the local resolver.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Socks5 {
+    pub host: String,
+    pub port: u16,
+    pub auth: Option<Socks5Auth>,
+}
+
+/// Username and password of a [Socks5] proxy
+///
+/// Tor isolates the circuits of connections with different credentials.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Socks5Auth {
+    pub username: String,
+    pub password: String,
+}
+
+impl Socks5 {
+    /// Tor daemon on its default SOCKS port
+    pub fn tor() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 9050,
+            auth: None,
+        }
+    }
+
+    /// `socks5h` URL of the proxy, the `h` has it resolve hostnames
+    fn url(&self) -> Result<Url, Error> {
+        let invalid = || Error::InvalidProxy(format!("{}:{}", self.host, self.port));
+
+        let mut url =
+            Url::parse(&format!("socks5h://{}:{}", self.host, self.port)).map_err(|_| invalid())?;
+        if let Some(auth) = &self.auth {
+            url.set_username(&auth.username).map_err(|_| invalid())?;
+            url.set_password(Some(&auth.password))
+                .map_err(|_| invalid())?;
+        }
+
+        Ok(url)
+    }
+}
+
+/// Proxies of a [HttpClient] by mint host
+#[derive(Debug, Default)]
+struct Routes {
+    default: Option<Url>,
+    /// [None] for mints reached directly
+    mints: HashMap<String, Option<Url>>,
+}
+
+impl Routes {
+    fn proxy(&self, url: &Url) -> Option<&Url> {
+        match url.host_str().and_then(|host| self.mints.get(host)) {
+            Some(proxy) => proxy.as_ref(),
+            None => self.default.as_ref(),
+        }
+    }
+}
+
+/// Time allowed to connect to a mint, by default
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time allowed per request attempt, connecting and reading the response
+/// included, by default
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Ys per check state request, by default
+pub const DEFAULT_CHECK_STATE_BATCH_SIZE: usize = 100;
+
+/// Check state requests in flight at once, by default
+pub const DEFAULT_CHECK_STATE_CONCURRENCY: usize = 4;
+
+/// How [HttpClient::check_proofs_states] splits the Ys it checks [NUT-07]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CheckStateBatching {
+    /// Ys per request, mints cap the size of the requests they accept
+    pub batch_size: usize,
+    /// Requests in flight at once, `1` sends them one after the other
+    pub max_concurrent: usize,
+}
+
+impl Default for CheckStateBatching {
+    fn default() -> Self {
+        Self {
+            batch_size: DEFAULT_CHECK_STATE_BATCH_SIZE,
+            max_concurrent: DEFAULT_CHECK_STATE_CONCURRENCY,
+        }
+    }
+}
+
+/// States from [HttpClient::check_proofs_states]
+#[derive(Debug, Default)]
+pub struct BatchedCheckState {
+    /// State of each Y in the order asked, [None] for the Ys of failed
+    /// batches
+    pub states: Vec<Option<ProofState>>,
+    /// Batches the mint did not answer, in the order asked
+    pub failures: Vec<BatchFailure>,
+}
+
+impl BatchedCheckState {
+    /// States of all the Ys, or the error of the first failed batch
+    pub fn into_result(self) -> Result<Vec<ProofState>, Error> {
+        match self.failures.into_iter().next() {
+            Some(failure) => Err(failure.error),
+            None => Ok(self.states.into_iter().flatten().collect()),
+        }
+    }
+}
+
+/// Batch of a [BatchedCheckState] that failed
+#[derive(Debug)]
+pub struct BatchFailure {
+    /// Indexes of the Ys of the batch
+    pub ys: Range<usize>,
+    pub error: Error,
+}
+
+/// Builder of a [HttpClient]
+#[derive(Debug, Clone)]
+pub struct ClientBuilder {
+    retry_policy: RetryPolicy,
+    check_state_batching: CheckStateBatching,
+    connect_timeout: Duration,
+    timeout: Duration,
+    proxy: Option<Socks5>,
+    mint_proxies: HashMap<String, Option<Socks5>>,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        Self {
+            retry_policy: RetryPolicy::default(),
+            check_state_batching: CheckStateBatching::default(),
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            timeout: DEFAULT_TIMEOUT,
+            proxy: None,
+            mint_proxies: HashMap::new(),
+        }
+    }
+}
+
+impl ClientBuilder {
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn check_state_batching(mut self, check_state_batching: CheckStateBatching) -> Self {
+        self.check_state_batching = check_state_batching;
+        self
+    }
+
+    /// Time allowed to connect, to the proxy if there is one
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+
+    /// Time allowed per request attempt, retries get their own
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Reach mints through `proxy`
+    pub fn proxy(mut self, proxy: Socks5) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Reach mints on the host of `mint_url` through `proxy`, or directly
+    /// for [None], whatever [ClientBuilder::proxy] is
+    pub fn mint_proxy(mut self, mint_url: &Url, proxy: Option<Socks5>) -> Self {
+        if let Some(host) = mint_url.host_str() {
+            self.mint_proxies.insert(host.to_string(), proxy);
+        }
+        self
+    }
+
+    pub fn build(self) -> Result<HttpClient, Error> {
+        let mut mints = HashMap::with_capacity(self.mint_proxies.len());
+        for (host, proxy) in self.mint_proxies {
+            mints.insert(host, proxy.as_ref().map(Socks5::url).transpose()?);
+        }
+        let routes = Arc::new(Routes {
+            default: self.proxy.as_ref().map(Socks5::url).transpose()?,
+            mints,
+        });
+
+        let proxy_routes = Arc::clone(&routes);
+        let inner = Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.timeout)
+            .proxy(Proxy::custom(move |url| proxy_routes.proxy(url).cloned()))
+            .build()?;
+
+        Ok(HttpClient {
+            inner,
+            retry_policy: self.retry_policy,
+            check_state_batching: self.check_state_batching,
+            routes,
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct HttpClient {
     inner: Client,
+    retry_policy: RetryPolicy,
+    check_state_batching: CheckStateBatching,
+    routes: Arc<Routes>,
 }
 
 impl Default for HttpClient {
@@ -67,41 +482,146 @@ impl Default for HttpClient {
 }
 
 impl HttpClient {
+    /// Client with the defaults of [ClientBuilder]
     pub fn new() -> Self {
-        Self {
-            inner: Client::new(),
+        Self::builder()
+            .build()
+            .expect("Client without proxies always builds")
+    }
+
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::default()
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy
+    }
+
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
+    }
+
+    pub fn check_state_batching(&self) -> CheckStateBatching {
+        self.check_state_batching
+    }
+
+    pub fn set_check_state_batching(&mut self, check_state_batching: CheckStateBatching) {
+        self.check_state_batching = check_state_batching;
+    }
+
+    async fn get_json(&self, url: Url) -> Result<Value, Error> {
+        let proxied = self.proxied(&url)?;
+        self.send_json(Retry::Always, proxied, || self.inner.get(url.clone()))
+            .await
+    }
+
+    /// POST `body` that spends or creates something at the mint
+    async fn post_json<T: Serialize>(&self, url: Url, body: &T) -> Result<Value, Error> {
+        let retry = match self.retry_policy.cached_responses {
+            true => Retry::Always,
+            false => Retry::PreFlight,
+        };
+
+        let proxied = self.proxied(&url)?;
+        self.send_json(retry, proxied, || self.inner.post(url.clone()).json(body))
+            .await
+    }
+
+    /// POST `body` that only reads state of the mint
+    async fn post_read_json<T: Serialize>(&self, url: Url, body: &T) -> Result<Value, Error> {
+        let proxied = self.proxied(&url)?;
+        self.send_json(Retry::Always, proxied, || {
+            self.inner.post(url.clone()).json(body)
+        })
+        .await
+    }
+
+    /// Whether requests to `url` go through a proxy, refusing onion mints
+    /// that would not
+    fn proxied(&self, url: &Url) -> Result<bool, Error> {
+        let proxied = self.routes.proxy(url).is_some();
+        match url.host_str() {
+            Some(host) if !proxied && host.ends_with(".onion") => {
+                Err(Error::OnionWithoutProxy(host.to_string()))
+            }
+            _ => Ok(proxied),
+        }
+    }
+
+    /// Send the request built by `request`, retried per the [RetryPolicy]
+    ///
+    /// Error responses of the mint are returned as a body like any other
+    /// response and never retried. Connect errors of `proxied` requests are
+    /// [Error::Proxy].
+    async fn send_json<F>(&self, retry: Retry, proxied: bool, request: F) -> Result<Value, Error>
+    where
+        F: Fn() -> RequestBuilder,
+    {
+        let mut attempt = 1;
+
+        loop {
+            let err = match request().send().await {
+                Ok(res) if retry == Retry::Always && is_transient(res.status()) => {
+                    match res.error_for_status() {
+                        Ok(res) => return read_json(res).await,
+                        Err(err) => err,
+                    }
+                }
+                Ok(res) => return read_json(res).await,
+                Err(err) if err.is_connect() => err,
+                Err(err) if retry == Retry::Always && err.is_timeout() => err,
+                Err(err) => return Err(NetworkError::from(err).into()),
+            };
+
+            if attempt >= self.retry_policy.max_attempts {
+                let err = match proxied && err.is_connect() {
+                    true => Error::Proxy(err),
+                    false => NetworkError::from(err).into(),
+                };
+                return Err(match attempt {
+                    1 => err,
+                    attempts => Error::RetriesExhausted {
+                        attempts,
+                        source: Box::new(err),
+                    },
+                });
+            }
+
+            let delay = self.retry_policy.delay(attempt - 1);
+            warn!(attempt, ?delay, "Retrying request: {err}");
+            tokio::time::sleep(delay).await;
+
+            attempt += 1;
         }
     }
 
     /// Get Active Mint Keys [NUT-01]
     pub async fn get_mint_keys(&self, mint_url: Url) -> Result<Vec<KeySet>, Error> {
         let url = join_url(mint_url, &["v1", "keys"])?;
-        let keys = self.inner.get(url).send().await?.json::<Value>().await?;
+        let keys = self.get_json(url).await?;
 
         let keys: KeysResponse = serde_json::from_value(keys)?;
+        verify_keyset_ids(&keys.keysets)?;
+
         Ok(keys.keysets)
     }
 
     /// Get Keyset Keys [NUT-01]
     pub async fn get_mint_keyset(&self, mint_url: Url, keyset_id: Id) -> Result<KeySet, Error> {
         let url = join_url(mint_url, &["v1", "keys", &keyset_id.to_string()])?;
-        let keys = self
-            .inner
-            .get(url)
-            .send()
-            .await?
-            .json::<KeysResponse>()
-            .await?;
+        let keys: KeysResponse = serde_json::from_value(self.get_json(url).await?)?;
 
         // let keys: KeysResponse = serde_json::from_value(keys)?; //
         // serde_json::from_str(&keys.to_string())?;
+        verify_keyset_ids(&keys.keysets)?;
+
         Ok(keys.keysets[0].clone())
     }
 
     /// Get Keysets [NUT-02]
     pub async fn get_mint_keysets(&self, mint_url: Url) -> Result<KeysetResponse, Error> {
         let url = join_url(mint_url, &["v1", "keysets"])?;
-        let res = self.inner.get(url).send().await?.json::<Value>().await?;
+        let res = self.get_json(url).await?;
 
         let response: Result<KeysetResponse, serde_json::Error> =
             serde_json::from_value(res.clone());
@@ -118,21 +638,42 @@ impl HttpClient {
         mint_url: Url,
         amount: Amount,
         unit: CurrencyUnit,
+        description: Option<String>,
     ) -> Result<MintQuoteBolt11Response, Error> {
         let url = join_url(mint_url, &["v1", "mint", "quote", "bolt11"])?;
 
-        let request = MintQuoteBolt11Request { amount, unit };
+        let request = MintQuoteBolt11Request {
+            description,
+            ..MintQuoteBolt11Request::new(amount, unit)
+        };
+
+        let res = self.post_json(url, &request).await?;
+
+        let response: Result<MintQuoteBolt11Response, serde_json::Error> =
+            serde_json::from_value(res.clone());
+
+        match response {
+            Ok(res) => Ok(res),
+            Err(_) => Err(ErrorResponse::from_json(&res.to_string())?.into()),
+        }
+    }
 
-        let res = self.inner.post(url).json(&request).send().await?;
+    /// Mint Quote state [NUT-04]
+    pub async fn get_mint_quote_status(
+        &self,
+        mint_url: Url,
+        quote_id: &str,
+    ) -> Result<MintQuoteBolt11Response, Error> {
+        let url = join_url(mint_url, &["v1", "mint", "quote", "bolt11", quote_id])?;
 
-        let status = res.status();
+        let res = self.get_json(url).await?;
 
         let response: Result<MintQuoteBolt11Response, serde_json::Error> =
-            serde_json::from_value(res.json().await?);
+            serde_json::from_value(res.clone());
 
         match response {
             Ok(res) => Ok(res),
-            Err(_) => Err(ErrorResponse::from_json(&status.to_string())?.into()),
+            Err(_) => Err(ErrorResponse::from_json(&res.to_string())?.into()),
         }
     }
 
@@ -140,24 +681,11 @@ impl HttpClient {
     pub async fn post_mint(
         &self,
         mint_url: Url,
-        quote: &str,
-        premint_secrets: PreMintSecrets,
+        request: MintBolt11Request,
     ) -> Result<MintBolt11Response, Error> {
         let url = join_url(mint_url, &["v1", "mint", "bolt11"])?;
 
-        let request = MintBolt11Request {
-            quote: quote.to_string(),
-            outputs: premint_secrets.blinded_messages(),
-        };
-
-        let res = self
-            .inner
-            .post(url)
-            .json(&request)
-            .send()
-            .await?
-            .json::<Value>()
-            .await?;
+        let res = self.post_json(url, &request).await?;
 
         let response: Result<MintBolt11Response, serde_json::Error> =
             serde_json::from_value(res.clone());
@@ -174,16 +702,64 @@ impl HttpClient {
         mint_url: Url,
         unit: CurrencyUnit,
         request: Bolt11Invoice,
+        amount: Option<Amount>,
+    ) -> Result<MeltQuoteBolt11Response, Error> {
+        let mut request = MeltQuoteBolt11Request::new(request, unit);
+        request.amount = amount;
+
+        self.post_melt_quote_request(mint_url, &request).await
+    }
+
+    /// Melt Quote [NUT-05] for a request with options, such as [NUT-15]
+    /// partial payments
+    pub async fn post_melt_quote_request(
+        &self,
+        mint_url: Url,
+        request: &MeltQuoteBolt11Request,
     ) -> Result<MeltQuoteBolt11Response, Error> {
         let url = join_url(mint_url, &["v1", "melt", "quote", "bolt11"])?;
 
-        let request = MeltQuoteBolt11Request { request, unit };
+        let value = self.post_json(url, request).await?;
+
+        let response: Result<MeltQuoteBolt11Response, serde_json::Error> =
+            serde_json::from_value(value.clone());
+
+        match response {
+            Ok(res) => Ok(res),
+            Err(_) => Err(ErrorResponse::from_json(&value.to_string())?.into()),
+        }
+    }
 
-        let value = self.inner.post(url).json(&request).send().await?;
+    /// Melt Quote state [NUT-05]
+    pub async fn get_melt_quote_status(
+        &self,
+        mint_url: Url,
+        quote_id: &str,
+    ) -> Result<MeltQuoteBolt11Response, Error> {
+        let url = join_url(mint_url, &["v1", "melt", "quote", "bolt11", quote_id])?;
 
-        let value = value.json::<Value>().await?;
+        let res = self.get_json(url).await?;
 
         let response: Result<MeltQuoteBolt11Response, serde_json::Error> =
+            serde_json::from_value(res.clone());
+
+        match response {
+            Ok(res) => Ok(res),
+            Err(_) => Err(ErrorResponse::from_json(&res.to_string())?.into()),
+        }
+    }
+
+    /// Fee reserve for paying `request`, legacy `/checkfees`
+    pub async fn post_check_fees(
+        &self,
+        mint_url: Url,
+        request: Bolt11Invoice,
+    ) -> Result<CheckFeesResponse, Error> {
+        let url = join_url(mint_url, &["checkfees"])?;
+
+        let value = self.post_json(url, &CheckFeesRequest::new(request)).await?;
+
+        let response: Result<CheckFeesResponse, serde_json::Error> =
             serde_json::from_value(value.clone());
 
         match response {
@@ -197,21 +773,11 @@ impl HttpClient {
     pub async fn post_melt(
         &self,
         mint_url: Url,
-        quote: String,
-        inputs: Vec<Proof>,
-        outputs: Option<Vec<BlindedMessage>>,
+        request: MeltBolt11Request,
     ) -> Result<MeltBolt11Response, Error> {
         let url = join_url(mint_url, &["v1", "melt", "bolt11"])?;
 
-        let request = MeltBolt11Request {
-            quote,
-            inputs,
-            outputs,
-        };
-
-        let value = self.inner.post(url).json(&request).send().await?;
-
-        let value = value.json::<Value>().await?;
+        let value = self.post_json(url, &request).await?;
         let response: Result<MeltBolt11Response, serde_json::Error> =
             serde_json::from_value(value.clone());
 
@@ -229,9 +795,7 @@ impl HttpClient {
     ) -> Result<SwapResponse, Error> {
         let url = join_url(mint_url, &["v1", "swap"])?;
 
-        let res = self.inner.post(url).json(&swap_request).send().await?;
-
-        let value = res.json::<Value>().await?;
+        let value = self.post_json(url, &swap_request).await?;
         let response: Result<SwapResponse, serde_json::Error> =
             serde_json::from_value(value.clone());
 
@@ -245,7 +809,7 @@ impl HttpClient {
     pub async fn get_mint_info(&self, mint_url: Url) -> Result<MintInfo, Error> {
         let url = join_url(mint_url, &["v1", "info"])?;
 
-        let res = self.inner.get(url).send().await?.json::<Value>().await?;
+        let res = self.get_json(url).await?;
 
         let response: Result<MintInfo, serde_json::Error> = serde_json::from_value(res.clone());
 
@@ -264,14 +828,7 @@ impl HttpClient {
         let url = join_url(mint_url, &["v1", "checkstate"])?;
         let request = CheckStateRequest { ys };
 
-        let res = self
-            .inner
-            .post(url)
-            .json(&request)
-            .send()
-            .await?
-            .json::<Value>()
-            .await?;
+        let res = self.post_read_json(url, &request).await?;
 
         let response: Result<CheckStateResponse, serde_json::Error> =
             serde_json::from_value(res.clone());
@@ -282,6 +839,83 @@ impl HttpClient {
         }
     }
 
+    /// State of each of `ys`, checked in batches per the
+    /// [CheckStateBatching] [NUT-07]
+    ///
+    /// States are returned in the order of `ys`, whatever order the batches
+    /// finish in. A failed batch is reported in
+    /// [BatchedCheckState::failures] and does not discard the states of the
+    /// others.
+    pub async fn check_proofs_states(
+        &self,
+        mint_url: Url,
+        ys: Vec<PublicKey>,
+    ) -> BatchedCheckState {
+        let batch_size = self.check_state_batching.batch_size.max(1);
+        let permits = Arc::new(Semaphore::new(
+            self.check_state_batching.max_concurrent.max(1),
+        ));
+
+        let batches: Vec<_> = ys
+            .chunks(batch_size)
+            .enumerate()
+            .map(|(index, batch)| {
+                let ys = index * batch_size..index * batch_size + batch.len();
+                let client = self.clone();
+                let mint_url = mint_url.clone();
+                let batch = batch.to_vec();
+                let permits = Arc::clone(&permits);
+                let handle = tokio::spawn(async move {
+                    // Permits are never closed
+                    let _permit = permits.acquire_owned().await;
+                    client.check_state_batch(mint_url, batch).await
+                });
+
+                (ys, handle)
+            })
+            .collect();
+
+        let mut checked = BatchedCheckState {
+            states: Vec::with_capacity(ys.len()),
+            failures: Vec::new(),
+        };
+        for (range, handle) in batches {
+            let error = match handle.await {
+                Ok(Ok(states)) => {
+                    checked.states.extend(states.into_iter().map(Some));
+                    continue;
+                }
+                Ok(Err(err)) => err,
+                Err(err) => err.into(),
+            };
+
+            warn!(ys = ?range, "Check state batch failed: {error}");
+            checked.states.extend(range.clone().map(|_| None));
+            checked.failures.push(BatchFailure { ys: range, error });
+        }
+
+        checked
+    }
+
+    /// States of `ys` in their order, from a single request
+    async fn check_state_batch(
+        &self,
+        mint_url: Url,
+        ys: Vec<PublicKey>,
+    ) -> Result<Vec<ProofState>, Error> {
+        let mut states: HashMap<PublicKey, ProofState> = self
+            .post_check_state(mint_url, ys.clone())
+            .await?
+            .states
+            .into_iter()
+            .map(|state| (state.y, state))
+            .collect();
+
+        ys.iter()
+            .map(|y| states.remove(y).ok_or(Error::InvalidCheckStateResponse))
+            .collect()
+    }
+
     pub async fn post_restore(
         &self,
         mint_url: Url,
@@ -289,14 +923,7 @@ impl HttpClient {
     ) -> Result<RestoreResponse, Error> {
         let url = join_url(mint_url, &["v1", "restore"])?;
 
-        let res = self
-            .inner
-            .post(url)
-            .json(&request)
-            .send()
-            .await?
-            .json::<Value>()
-            .await?;
+        let res = self.post_read_json(url, &request).await?;
 
         let response: Result<RestoreResponse, serde_json::Error> =
             serde_json::from_value(res.clone());
@@ -307,3 +934,571 @@ impl HttpClient {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{SocketAddr, TcpListener, TcpStream};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use super::*;
+    use crate::dhke::hash_to_curve;
+    use crate::nuts::State;
+
+    const SWAP_RESPONSE: &str = r#"{"signatures":[]}"#;
+
+    /// Mint answering the `n`th request with `responses[n]`, the last one
+    /// repeated, and counting the requests that reached it
+    fn serve(listener: TcpListener, responses: Vec<(u16, &'static str)>) -> Arc<AtomicUsize> {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(length) = line.to_lowercase().strip_prefix("content-length:") {
+                        content_length = length.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                }
+                reader.read_exact(&mut vec![0; content_length]).unwrap();
+
+                let hit = counter.fetch_add(1, Ordering::SeqCst);
+                let (status, body) = responses[hit.min(responses.len() - 1)];
+                write!(
+                    stream,
+                    "HTTP/1.1 {status} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+            }
+        });
+
+        hits
+    }
+
+    /// Mint answering check state requests of at most `max_ys` Ys, in
+    /// reverse order, with an error for batches holding a Y of `failing`,
+    /// and recording the size of each request
+    fn serve_check_state(
+        listener: TcpListener,
+        max_ys: usize,
+        spent: Vec<PublicKey>,
+        failing: Vec<PublicKey>,
+    ) -> Arc<Mutex<Vec<usize>>> {
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let recorded = batches.clone();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(length) = line.to_lowercase().strip_prefix("content-length:") {
+                        content_length = length.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                let request: CheckStateRequest = serde_json::from_slice(&body).unwrap();
+                recorded.lock().unwrap().push(request.ys.len());
+
+                let (status, body) = if request.ys.len() > max_ys {
+                    (413, r#"{"code":0,"detail":"Too many Ys"}"#.to_string())
+                } else if request.ys.iter().any(|y| failing.contains(y)) {
+                    (500, r#"{"code":0,"detail":"Internal error"}"#.to_string())
+                } else {
+                    let states = request
+                        .ys
+                        .iter()
+                        .rev()
+                        .map(|y| {
+                            let state = match spent.contains(y) {
+                                true => State::Spent,
+                                false => State::Unspent,
+                            };
+                            ProofState::new(*y, state, None)
+                        })
+                        .collect();
+                    let response = CheckStateResponse::new(states);
+                    (200, serde_json::to_string(&response).unwrap())
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {status} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+            }
+        });
+
+        batches
+    }
+
+    fn ys(count: u32) -> Vec<PublicKey> {
+        (0..count)
+            .map(|i| hash_to_curve(&i.to_be_bytes()).unwrap())
+            .collect()
+    }
+
+    fn mint() -> (Url, TcpListener) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+
+        (url, listener)
+    }
+
+    fn client(cached_responses: bool) -> HttpClient {
+        let mut client = HttpClient::new();
+        client.set_retry_policy(RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            cached_responses,
+        });
+
+        client
+    }
+
+    /// Connection requested from a [socks_proxy]
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct SocksConnect {
+        /// Hostname as sent, [None] if the client sent a resolved address
+        host: Option<String>,
+        port: u16,
+        auth: Option<(String, String)>,
+    }
+
+    /// SOCKS5 proxy forwarding every connection to `upstream`, whatever the
+    /// requested destination, and recording what was requested
+    fn socks_proxy(upstream: SocketAddr) -> (Socks5, Arc<Mutex<Vec<SocksConnect>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy = Socks5 {
+            host: "127.0.0.1".to_string(),
+            port: listener.local_addr().unwrap().port(),
+            auth: None,
+        };
+        let connects = Arc::new(Mutex::new(Vec::new()));
+        let recorded = connects.clone();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut client = stream.unwrap();
+                let read = |stream: &mut TcpStream, len: usize| {
+                    let mut buf = vec![0; len];
+                    stream.read_exact(&mut buf).unwrap();
+                    buf
+                };
+
+                // Greeting, with username and password auth if offered
+                let header = read(&mut client, 2);
+                let methods = read(&mut client, header[1].into());
+                let auth = match methods.contains(&2) {
+                    true => {
+                        client.write_all(&[5, 2]).unwrap();
+                        let len = read(&mut client, 2)[1];
+                        let username = String::from_utf8(read(&mut client, len.into())).unwrap();
+                        let len = read(&mut client, 1)[0];
+                        let password = String::from_utf8(read(&mut client, len.into())).unwrap();
+                        client.write_all(&[1, 0]).unwrap();
+                        Some((username, password))
+                    }
+                    false => {
+                        client.write_all(&[5, 0]).unwrap();
+                        None
+                    }
+                };
+
+                let request = read(&mut client, 4);
+                let host = match request[3] {
+                    1 => {
+                        read(&mut client, 4);
+                        None
+                    }
+                    3 => {
+                        let len = read(&mut client, 1)[0];
+                        Some(String::from_utf8(read(&mut client, len.into())).unwrap())
+                    }
+                    _ => {
+                        read(&mut client, 16);
+                        None
+                    }
+                };
+                let port = read(&mut client, 2);
+                recorded.lock().unwrap().push(SocksConnect {
+                    host,
+                    port: u16::from_be_bytes([port[0], port[1]]),
+                    auth,
+                });
+
+                let mut server = TcpStream::connect(upstream).unwrap();
+                client.write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0]).unwrap();
+
+                let (mut client_read, mut server_write) =
+                    (client.try_clone().unwrap(), server.try_clone().unwrap());
+                thread::spawn(move || std::io::copy(&mut client_read, &mut server_write));
+                thread::spawn(move || std::io::copy(&mut server, &mut client));
+            }
+        });
+
+        (proxy, connects)
+    }
+
+    #[tokio::test]
+    async fn test_onion_mint_through_proxy() {
+        let (_, listener) = mint();
+        let (mut proxy, connects) = socks_proxy(listener.local_addr().unwrap());
+        let hits = serve(listener, vec![(200, r#"{"keysets":[]}"#)]);
+        proxy.auth = Some(Socks5Auth {
+            username: "wallet".to_string(),
+            password: "circuit-1".to_string(),
+        });
+
+        let client = HttpClient::builder()
+            .retry_policy(RetryPolicy::none())
+            .proxy(proxy)
+            .build()
+            .unwrap();
+        let onion = Url::parse("http://mintxyzabcdefghijklmnop.onion").unwrap();
+        client.get_mint_keysets(onion).await.unwrap();
+
+        // The proxy is given the name, it is never resolved locally
+        assert_eq!(
+            *connects.lock().unwrap(),
+            vec![SocksConnect {
+                host: Some("mintxyzabcdefghijklmnop.onion".to_string()),
+                port: 80,
+                auth: Some(("wallet".to_string(), "circuit-1".to_string())),
+            }]
+        );
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_mint_proxy_overrides() {
+        let (clearnet, listener) = mint();
+        let (proxy, connects) = socks_proxy(listener.local_addr().unwrap());
+        let hits = serve(listener, vec![(200, r#"{"keysets":[]}"#)]);
+
+        // Clearnet mint reached directly, everything else through the proxy
+        let client = HttpClient::builder()
+            .retry_policy(RetryPolicy::none())
+            .proxy(proxy)
+            .mint_proxy(&clearnet, None)
+            .build()
+            .unwrap();
+        client.get_mint_keysets(clearnet.clone()).await.unwrap();
+        assert!(connects.lock().unwrap().is_empty());
+        client
+            .get_mint_keysets(Url::parse("http://mintxyz.onion").unwrap())
+            .await
+            .unwrap();
+        assert_eq!(connects.lock().unwrap().len(), 1);
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+        // Only the onion mint through the proxy
+        let (_, listener) = mint();
+        let (proxy, connects) = socks_proxy(listener.local_addr().unwrap());
+        serve(listener, vec![(200, r#"{"keysets":[]}"#)]);
+        let onion = Url::parse("http://mintxyz.onion").unwrap();
+        let client = HttpClient::builder()
+            .retry_policy(RetryPolicy::none())
+            .mint_proxy(&onion, Some(proxy))
+            .build()
+            .unwrap();
+        client.get_mint_keysets(onion).await.unwrap();
+        client.get_mint_keysets(clearnet).await.unwrap();
+        assert_eq!(connects.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_onion_mint_without_proxy() {
+        let onion = Url::parse("http://mintxyz.onion").unwrap();
+
+        assert!(matches!(
+            HttpClient::new().get_mint_info(onion.clone()).await,
+            Err(Error::OnionWithoutProxy(host)) if host == "mintxyz.onion"
+        ));
+
+        let client = HttpClient::builder()
+            .proxy(Socks5::tor())
+            .mint_proxy(&onion, None)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            client.get_mint_info(onion).await,
+            Err(Error::OnionWithoutProxy(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_proxy_failure_is_not_a_mint_error() {
+        let (url, listener) = mint();
+        let hits = serve(listener, vec![(200, r#"{"keysets":[]}"#)]);
+
+        // Nothing listens on the proxy port
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = closed.local_addr().unwrap().port();
+        drop(closed);
+
+        let client = HttpClient::builder()
+            .retry_policy(RetryPolicy::none())
+            .proxy(Socks5 {
+                host: "127.0.0.1".to_string(),
+                port,
+                auth: None,
+            })
+            .build()
+            .unwrap();
+        assert!(matches!(
+            client.get_mint_keysets(url).await,
+            Err(Error::Proxy(_))
+        ));
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_slow_mint_times_out() {
+        let (url, listener) = mint();
+        // Reads requests but never answers
+        thread::spawn(move || {
+            let mut streams = Vec::new();
+            for stream in listener.incoming() {
+                streams.push(stream.unwrap());
+            }
+        });
+
+        let client = HttpClient::builder()
+            .retry_policy(RetryPolicy::none())
+            .timeout(Duration::from_millis(100))
+            .build()
+            .unwrap();
+        assert!(matches!(
+            client.get_mint_info(url).await,
+            Err(Error::Network(NetworkError::Timeout))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_network_error_classes() {
+        let client = HttpClient::builder()
+            .retry_policy(RetryPolicy::none())
+            .build()
+            .unwrap();
+
+        let (url, listener) = mint();
+        serve(listener, vec![(200, "<html>not json</html>")]);
+        assert!(matches!(
+            client.get_mint_info(url).await,
+            Err(Error::Network(NetworkError::MalformedResponse(_)))
+        ));
+
+        let (url, listener) = mint();
+        drop(listener);
+        assert!(matches!(
+            client.get_mint_info(url).await,
+            Err(Error::Network(NetworkError::ConnectionRefused))
+        ));
+
+        // Reserved so it never resolves [RFC 2606]
+        assert!(matches!(
+            client
+                .get_mint_info(Url::parse("http://mint.invalid").unwrap())
+                .await,
+            Err(Error::Network(NetworkError::Dns(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_timeouts_are_retried() {
+        let (url, listener) = mint();
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        thread::spawn(move || {
+            let mut streams = Vec::new();
+            for stream in listener.incoming() {
+                counter.fetch_add(1, Ordering::SeqCst);
+                streams.push(stream.unwrap());
+            }
+        });
+
+        let client = HttpClient::builder()
+            .retry_policy(RetryPolicy {
+                max_attempts: 2,
+                base_delay: Duration::from_millis(1),
+                max_delay: Duration::from_millis(1),
+                cached_responses: false,
+            })
+            .timeout(Duration::from_millis(50))
+            .build()
+            .unwrap();
+        match client.get_mint_keysets(url).await {
+            Err(Error::RetriesExhausted { attempts, source }) => {
+                assert_eq!(attempts, 2);
+                assert!(matches!(*source, Error::Network(NetworkError::Timeout)));
+            }
+            other => panic!("Expected retries to run out, got {other:?}"),
+        }
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    fn swap_request() -> SwapRequest {
+        SwapRequest::new(vec![], vec![])
+    }
+
+    #[tokio::test]
+    async fn test_get_retried_after_server_errors() {
+        let (url, listener) = mint();
+        let hits = serve(
+            listener,
+            vec![(503, "{}"), (502, "{}"), (200, r#"{"keysets":[]}"#)],
+        );
+
+        client(false).get_mint_keysets(url).await.unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let (url, listener) = mint();
+        let hits = serve(listener, vec![(503, "{}")]);
+
+        assert!(matches!(
+            client(false).get_mint_info(url).await,
+            Err(Error::RetriesExhausted { attempts: 3, .. })
+        ));
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_post_retried_only_with_cached_responses() {
+        let responses = vec![(503, "{}"), (503, "{}"), (200, SWAP_RESPONSE)];
+
+        // The swap may have been processed, so it is not sent again
+        let (url, listener) = mint();
+        let hits = serve(listener, responses.clone());
+        let result = client(false).post_swap(url, swap_request()).await;
+        assert!(result.is_err());
+        assert!(!matches!(result, Err(Error::RetriesExhausted { .. })));
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        let (url, listener) = mint();
+        let hits = serve(listener, responses);
+        client(true).post_swap(url, swap_request()).await.unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_mint_rejection_is_not_retried() {
+        let (url, listener) = mint();
+        let hits = serve(
+            listener,
+            vec![(400, r#"{"code":11001,"detail":"Token already spent"}"#)],
+        );
+
+        assert!(matches!(
+            client(true).post_swap(url, swap_request()).await,
+            Err(Error::UnknownErrorResponse(_))
+        ));
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_post_retried_before_reaching_mint() {
+        let (url, listener) = mint();
+        let addr: SocketAddr = listener.local_addr().unwrap();
+        drop(listener);
+
+        // Connections are refused until the mint comes up
+        let server = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            serve(TcpListener::bind(addr).unwrap(), vec![(200, SWAP_RESPONSE)])
+        });
+
+        let mut client = client(false);
+        client.set_retry_policy(RetryPolicy {
+            max_attempts: 50,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(20),
+            cached_responses: false,
+        });
+        client.post_swap(url, swap_request()).await.unwrap();
+
+        let hits = server.join().unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_check_state_in_batches_keeps_order() {
+        let (url, listener) = mint();
+        let ys = ys(250);
+        let spent: Vec<PublicKey> = ys.iter().step_by(3).copied().collect();
+        let batches = serve_check_state(listener, 100, spent.clone(), vec![]);
+
+        let checked = client(false).check_proofs_states(url, ys.clone()).await;
+        assert!(checked.failures.is_empty());
+        assert_eq!(*batches.lock().unwrap(), vec![100, 100, 50]);
+
+        let states = checked.into_result().unwrap();
+        assert_eq!(states.len(), ys.len());
+        for (y, state) in ys.iter().zip(states) {
+            assert_eq!(&state.y, y);
+            let expected = match spent.contains(y) {
+                true => State::Spent,
+                false => State::Unspent,
+            };
+            assert_eq!(state.state, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_state_partial_failure() {
+        let (url, listener) = mint();
+        let ys = ys(250);
+        let batches = serve_check_state(listener, 100, vec![], vec![ys[150]]);
+
+        let mut client = client(false);
+        client.set_retry_policy(RetryPolicy::none());
+        let checked = client.check_proofs_states(url.clone(), ys.clone()).await;
+        assert_eq!(batches.lock().unwrap().len(), 3);
+        assert_eq!(checked.states.len(), ys.len());
+        assert_eq!(checked.failures.len(), 1);
+        assert_eq!(checked.failures[0].ys, 100..200);
+        for (index, state) in checked.states.iter().enumerate() {
+            match index {
+                100..=199 => assert_eq!(state, &None),
+                _ => assert_eq!(state.as_ref().map(|state| state.y), Some(ys[index])),
+            }
+        }
+        assert!(checked.into_result().is_err());
+
+        // Batches over the limit of the mint fail, smaller ones still pass
+        let (url, listener) = mint();
+        serve_check_state(listener, 100, vec![], vec![]);
+        client.set_check_state_batching(CheckStateBatching {
+            batch_size: 150,
+            max_concurrent: 1,
+        });
+        let checked = client.check_proofs_states(url, ys.clone()).await;
+        assert_eq!(checked.failures.len(), 1);
+        assert_eq!(checked.failures[0].ys, 0..150);
+        assert!(checked.states[150..].iter().all(Option::is_some));
+    }
+}
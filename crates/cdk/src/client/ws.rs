@@ -0,0 +1,850 @@
+//! [NUT-17] subscriptions over a WebSocket
+//!
+//! Every [Subscription] has a connection of its own to the `v1/ws` endpoint
+//! of the mint, `wss` for `https` mints with the webpki roots. A dropped
+//! connection is reconnected with the backoff of the [RetryPolicy] of the
+//! client and the subscription sent again, then the current state of each
+//! filter is polled over HTTP so a change missed while disconnected is still
+//! notified.
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use base64::engine::general_purpose;
+use base64::Engine as _;
+use bitcoin::hashes::{sha1, Hash};
+use bitcoin::secp256k1::rand::{self, RngCore};
+use tokio::io::{
+    AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, ReadHalf,
+    WriteHalf,
+};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+use tracing::warn;
+use url::Url;
+
+use super::{join_url, Error, HttpClient, RetryPolicy};
+use crate::nuts::nut17::{
+    NotificationParams, Params, UnsubscribeParams, WsMessage, WsMethod, WsRequest,
+};
+use crate::nuts::{Notification, PublicKey, SubscriptionKind};
+use crate::util::hex;
+
+/// Appended to the key of the handshake before it is hashed [RFC 6455]
+const WS_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+pub(crate) const OPCODE_CONTINUATION: u8 = 0x0;
+pub(crate) const OPCODE_TEXT: u8 = 0x1;
+pub(crate) const OPCODE_CLOSE: u8 = 0x8;
+pub(crate) const OPCODE_PING: u8 = 0x9;
+pub(crate) const OPCODE_PONG: u8 = 0xa;
+
+/// Longest message accepted from a mint
+const MAX_MESSAGE_LEN: usize = 1 << 20;
+
+/// Notifications buffered per subscription before the connection is no
+/// longer read
+const NOTIFICATION_CAPACITY: usize = 64;
+
+/// Time a mint has to answer a subscribe or unsubscribe request
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+trait AsyncStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> AsyncStream for T {}
+
+type Stream = BufReader<Box<dyn AsyncStream>>;
+
+fn ws_error(err: impl std::fmt::Display) -> Error {
+    Error::WebSocket(err.to_string())
+}
+
+/// Frame of a message [RFC 6455]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Frame {
+    pub fin: bool,
+    pub opcode: u8,
+    pub payload: Vec<u8>,
+}
+
+/// Write a final frame of `payload`, `masked` as clients have to
+pub(crate) async fn write_frame<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    opcode: u8,
+    payload: &[u8],
+    masked: bool,
+) -> std::io::Result<()> {
+    let mask_bit = if masked { 0x80 } else { 0 };
+    let mut frame = Vec::with_capacity(payload.len() + 14);
+    frame.push(0x80 | opcode);
+    match payload.len() {
+        len @ 0..=125 => frame.push(mask_bit | len as u8),
+        len @ 126..=0xffff => {
+            frame.push(mask_bit | 126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(mask_bit | 127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+
+    if masked {
+        let mut mask = [0u8; 4];
+        rand::thread_rng().fill_bytes(&mut mask);
+        frame.extend_from_slice(&mask);
+        frame.extend(
+            payload
+                .iter()
+                .enumerate()
+                .map(|(index, byte)| byte ^ mask[index % 4]),
+        );
+    } else {
+        frame.extend_from_slice(payload);
+    }
+
+    writer.write_all(&frame).await?;
+    writer.flush().await
+}
+
+/// Read a frame, unmasking its payload
+pub(crate) async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Frame, Error> {
+    let mut head = [0u8; 2];
+    reader.read_exact(&mut head).await.map_err(ws_error)?;
+
+    let len = match head[1] & 0x7f {
+        126 => u64::from(reader.read_u16().await.map_err(ws_error)?),
+        127 => reader.read_u64().await.map_err(ws_error)?,
+        len => u64::from(len),
+    };
+    if len > MAX_MESSAGE_LEN as u64 {
+        return Err(ws_error(format!("Frame of {len} bytes is too large")));
+    }
+
+    let mask = match head[1] & 0x80 != 0 {
+        true => {
+            let mut mask = [0u8; 4];
+            reader.read_exact(&mut mask).await.map_err(ws_error)?;
+            Some(mask)
+        }
+        false => None,
+    };
+
+    let mut payload = vec![0; len as usize];
+    reader.read_exact(&mut payload).await.map_err(ws_error)?;
+    if let Some(mask) = mask {
+        for (index, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[index % 4];
+        }
+    }
+
+    Ok(Frame {
+        fin: head[0] & 0x80 != 0,
+        opcode: head[0] & 0x0f,
+        payload,
+    })
+}
+
+/// `Sec-WebSocket-Accept` of the handshake with `key`
+pub(crate) fn accept_key(key: &str) -> String {
+    general_purpose::STANDARD.encode(sha1::Hash::hash(format!("{key}{WS_GUID}").as_bytes()))
+}
+
+/// Message of a [Connection], fragments joined
+#[derive(Debug)]
+enum Incoming {
+    Text(String),
+    Ping(Vec<u8>),
+    Closed,
+}
+
+/// Read whole messages from `reader` until the connection closes
+async fn read_messages(mut reader: ReadHalf<Stream>, incoming: mpsc::Sender<Incoming>) {
+    let mut message = Vec::new();
+
+    loop {
+        let frame = match read_frame(&mut reader).await {
+            Ok(frame) => frame,
+            Err(_) => break,
+        };
+
+        let sent = match frame.opcode {
+            OPCODE_TEXT | OPCODE_CONTINUATION => {
+                message.extend_from_slice(&frame.payload);
+                if message.len() > MAX_MESSAGE_LEN {
+                    break;
+                }
+                if !frame.fin {
+                    continue;
+                }
+                match String::from_utf8(std::mem::take(&mut message)) {
+                    Ok(text) => incoming.send(Incoming::Text(text)).await,
+                    Err(_) => break,
+                }
+            }
+            OPCODE_PING => incoming.send(Incoming::Ping(frame.payload)).await,
+            OPCODE_CLOSE => break,
+            // Pongs and binary messages are not part of the protocol
+            _ => Ok(()),
+        };
+
+        if sent.is_err() {
+            return;
+        }
+    }
+
+    incoming.send(Incoming::Closed).await.ok();
+}
+
+/// Client end of a WebSocket to a mint
+struct Connection {
+    writer: WriteHalf<Stream>,
+    incoming: mpsc::Receiver<Incoming>,
+    reader: JoinHandle<()>,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+impl Connection {
+    async fn open(url: &Url) -> Result<Self, Error> {
+        let host = url
+            .host_str()
+            .ok_or_else(|| ws_error(format!("No host in `{url}`")))?
+            .to_string();
+        let port = url
+            .port_or_known_default()
+            .ok_or_else(|| ws_error(format!("No port for `{url}`")))?;
+
+        let tcp = TcpStream::connect((host.as_str(), port))
+            .await
+            .map_err(ws_error)?;
+        let stream: Box<dyn AsyncStream> = match url.scheme() {
+            "ws" => Box::new(tcp),
+            "wss" => {
+                let server_name = ServerName::try_from(host.clone()).map_err(ws_error)?;
+                Box::new(
+                    tls_connector()
+                        .connect(server_name, tcp)
+                        .await
+                        .map_err(ws_error)?,
+                )
+            }
+            scheme => return Err(ws_error(format!("Unsupported scheme `{scheme}`"))),
+        };
+
+        let mut stream = BufReader::new(stream);
+        handshake(&mut stream, url).await?;
+
+        let (reader, writer) = tokio::io::split(stream);
+        let (sender, incoming) = mpsc::channel(NOTIFICATION_CAPACITY);
+
+        Ok(Self {
+            writer,
+            incoming,
+            reader: tokio::spawn(read_messages(reader, sender)),
+        })
+    }
+
+    async fn send(&mut self, request: &WsRequest) -> Result<(), Error> {
+        let text = serde_json::to_string(request)?;
+        write_frame(&mut self.writer, OPCODE_TEXT, text.as_bytes(), true)
+            .await
+            .map_err(ws_error)
+    }
+
+    async fn pong(&mut self, payload: &[u8]) -> Result<(), Error> {
+        write_frame(&mut self.writer, OPCODE_PONG, payload, true)
+            .await
+            .map_err(ws_error)
+    }
+
+    async fn close(mut self) {
+        write_frame(&mut self.writer, OPCODE_CLOSE, &[], true)
+            .await
+            .ok();
+    }
+}
+
+fn tls_connector() -> TlsConnector {
+    let mut roots = RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+
+    TlsConnector::from(Arc::new(
+        ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth(),
+    ))
+}
+
+/// Upgrade `stream` to a WebSocket to `url`
+async fn handshake(stream: &mut Stream, url: &Url) -> Result<(), Error> {
+    let mut key = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut key);
+    let key = general_purpose::STANDARD.encode(key);
+
+    let host = url.host_str().unwrap_or_default();
+    let host = match url.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_string(),
+    };
+    let path = match url.query() {
+        Some(query) => format!("{}?{query}", url.path()),
+        None => url.path().to_string(),
+    };
+
+    stream
+        .write_all(
+            format!(
+                "GET {path} HTTP/1.1\r\nHost: {host}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: {key}\r\nSec-WebSocket-Version: 13\r\n\r\n"
+            )
+            .as_bytes(),
+        )
+        .await
+        .map_err(ws_error)?;
+    stream.flush().await.map_err(ws_error)?;
+
+    let mut status = String::new();
+    stream.read_line(&mut status).await.map_err(ws_error)?;
+    if status.split_whitespace().nth(1) != Some("101") {
+        return Err(ws_error(format!("Upgrade refused: {}", status.trim())));
+    }
+
+    let mut accepted = false;
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line).await.map_err(ws_error)? == 0 {
+            return Err(ws_error("Connection closed during the handshake"));
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("sec-websocket-accept") {
+                accepted = value.trim() == accept_key(&key);
+            }
+        }
+    }
+
+    match accepted {
+        true => Ok(()),
+        false => Err(ws_error("Invalid Sec-WebSocket-Accept")),
+    }
+}
+
+/// `v1/ws` endpoint of `mint_url`
+fn ws_url(mint_url: Url) -> Result<Url, Error> {
+    let mut url = mint_url;
+    let scheme = match url.scheme() {
+        "https" | "wss" => "wss",
+        "http" | "ws" => "ws",
+        scheme => return Err(ws_error(format!("Unsupported scheme `{scheme}`"))),
+    };
+    url.set_scheme(scheme)
+        .map_err(|_| ws_error(format!("Unsupported scheme `{scheme}`")))?;
+
+    join_url(url, &["v1", "ws"])
+}
+
+/// Notifications of a [NUT-17] subscription, see [HttpClient::subscribe]
+///
+/// The stream ends when the mint refuses a resubscription after a reconnect
+/// or the subscription is [unsubscribed](Subscription::unsubscribe).
+/// Dropping it unsubscribes in the background.
+#[derive(Debug)]
+pub struct Subscription {
+    sub_id: String,
+    kind: SubscriptionKind,
+    notifications: mpsc::Receiver<Notification>,
+    unsubscribe: oneshot::Sender<oneshot::Sender<Result<(), Error>>>,
+}
+
+impl Subscription {
+    pub fn sub_id(&self) -> &str {
+        &self.sub_id
+    }
+
+    pub fn kind(&self) -> SubscriptionKind {
+        self.kind
+    }
+
+    /// Next notification, [None] once the subscription ended
+    pub async fn next(&mut self) -> Option<Notification> {
+        self.notifications.recv().await
+    }
+
+    /// Unsubscribe and close the connection, returns once the mint answered
+    pub async fn unsubscribe(self) -> Result<(), Error> {
+        let (sender, receiver) = oneshot::channel();
+        if self.unsubscribe.send(sender).is_err() {
+            // Subscription already ended
+            return Ok(());
+        }
+
+        receiver.await.unwrap_or(Ok(()))
+    }
+}
+
+impl futures_core::Stream for Subscription {
+    type Item = Notification;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.notifications.poll_recv(cx)
+    }
+}
+
+/// Background task of a [Subscription]
+struct Subscriber {
+    client: HttpClient,
+    mint_url: Url,
+    ws_url: Url,
+    params: Params,
+    notifications: mpsc::Sender<Notification>,
+    next_id: u64,
+}
+
+impl Subscriber {
+    fn request(&mut self, method: WsMethod) -> WsRequest {
+        self.next_id += 1;
+        WsRequest::new(method, self.next_id - 1)
+    }
+
+    /// Connect and subscribe, notifications sent before the response are
+    /// delivered
+    async fn subscribe(&mut self) -> Result<Connection, Error> {
+        let mut connection = Connection::open(&self.ws_url).await?;
+        let request = self.request(WsMethod::Subscribe(self.params.clone()));
+        connection.send(&request).await?;
+        self.await_response(&mut connection, request.id).await?;
+
+        Ok(connection)
+    }
+
+    async fn await_response(&mut self, connection: &mut Connection, id: u64) -> Result<(), Error> {
+        let response = async {
+            loop {
+                match connection.incoming.recv().await {
+                    Some(Incoming::Text(text)) => match serde_json::from_str(&text) {
+                        Ok(WsMessage::Response {
+                            id: response_id, ..
+                        }) if response_id == id => {
+                            return Ok(());
+                        }
+                        Ok(WsMessage::Error {
+                            id: response_id,
+                            error,
+                            ..
+                        }) if response_id == id => {
+                            return Err(Error::SubscriptionRefused {
+                                code: error.code,
+                                message: error.message,
+                            });
+                        }
+                        Ok(WsMessage::Notification { params, .. }) => {
+                            self.notify(params).await;
+                        }
+                        _ => (),
+                    },
+                    Some(Incoming::Ping(payload)) => connection.pong(&payload).await?,
+                    Some(Incoming::Closed) | None => {
+                        return Err(ws_error("Connection closed before the response"));
+                    }
+                }
+            }
+        };
+
+        tokio::time::timeout(RESPONSE_TIMEOUT, response)
+            .await
+            .map_err(|_| ws_error("No response from the mint"))?
+    }
+
+    /// Deliver a notification of this subscription, false once the
+    /// [Subscription] is dropped
+    async fn notify(&self, params: NotificationParams) -> bool {
+        if params.sub_id != self.params.sub_id {
+            return true;
+        }
+
+        match Notification::from_payload(self.params.kind, params.payload) {
+            Ok(notification) => self.notifications.send(notification).await.is_ok(),
+            Err(err) => {
+                warn!(sub_id = %self.params.sub_id, "Invalid notification: {err}");
+                true
+            }
+        }
+    }
+
+    /// Poll the state of every filter after a reconnect, false once the
+    /// [Subscription] is dropped
+    async fn recover(&self) -> bool {
+        let mut notifications = Vec::new();
+
+        match self.params.kind {
+            SubscriptionKind::Bolt11MintQuote => {
+                for quote_id in &self.params.filters {
+                    match self
+                        .client
+                        .get_mint_quote_status(self.mint_url.clone(), quote_id)
+                        .await
+                    {
+                        Ok(quote) => notifications.push(Notification::MintQuote(quote)),
+                        Err(err) => warn!(quote_id, "Could not poll mint quote: {err}"),
+                    }
+                }
+            }
+            SubscriptionKind::Bolt11MeltQuote => {
+                for quote_id in &self.params.filters {
+                    match self
+                        .client
+                        .get_melt_quote_status(self.mint_url.clone(), quote_id)
+                        .await
+                    {
+                        Ok(quote) => notifications.push(Notification::MeltQuote(quote)),
+                        Err(err) => warn!(quote_id, "Could not poll melt quote: {err}"),
+                    }
+                }
+            }
+            SubscriptionKind::ProofState => {
+                let ys: Vec<PublicKey> = self
+                    .params
+                    .filters
+                    .iter()
+                    .filter_map(|y| y.parse().ok())
+                    .collect();
+                match self
+                    .client
+                    .post_check_state(self.mint_url.clone(), ys)
+                    .await
+                {
+                    Ok(response) => notifications
+                        .extend(response.states.into_iter().map(Notification::ProofState)),
+                    Err(err) => warn!("Could not poll proof states: {err}"),
+                }
+            }
+        }
+
+        for notification in notifications {
+            if self.notifications.send(notification).await.is_err() {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    async fn unsubscribe(&mut self, mut connection: Connection) -> Result<(), Error> {
+        let request = self.request(WsMethod::Unsubscribe(UnsubscribeParams {
+            sub_id: self.params.sub_id.clone(),
+        }));
+        connection.send(&request).await?;
+        let result = self.await_response(&mut connection, request.id).await;
+        connection.close().await;
+
+        result
+    }
+
+    async fn run(
+        mut self,
+        mut connection: Connection,
+        mut unsubscribe: oneshot::Receiver<oneshot::Sender<Result<(), Error>>>,
+        retry_policy: RetryPolicy,
+    ) {
+        loop {
+            loop {
+                tokio::select! {
+                    incoming = connection.incoming.recv() => match incoming {
+                        Some(Incoming::Text(text)) => {
+                            if let Ok(WsMessage::Notification { params, .. }) =
+                                serde_json::from_str(&text)
+                            {
+                                if !self.notify(params).await {
+                                    self.unsubscribe(connection).await.ok();
+                                    return;
+                                }
+                            }
+                        }
+                        Some(Incoming::Ping(payload)) => {
+                            if connection.pong(&payload).await.is_err() {
+                                break;
+                            }
+                        }
+                        Some(Incoming::Closed) | None => break,
+                    },
+                    request = &mut unsubscribe => {
+                        let result = self.unsubscribe(connection).await;
+                        if let Ok(ack) = request {
+                            ack.send(result).ok();
+                        }
+                        return;
+                    }
+                }
+            }
+
+            warn!(sub_id = %self.params.sub_id, "Subscription disconnected, reconnecting");
+            let mut retry = 0;
+            connection = loop {
+                tokio::select! {
+                    _ = tokio::time::sleep(retry_policy.delay(retry)) => (),
+                    request = &mut unsubscribe => {
+                        if let Ok(ack) = request {
+                            ack.send(Ok(())).ok();
+                        }
+                        return;
+                    }
+                }
+
+                match self.subscribe().await {
+                    Ok(connection) => break connection,
+                    Err(err @ Error::SubscriptionRefused { .. }) => {
+                        warn!(sub_id = %self.params.sub_id, "Resubscription refused: {err}");
+                        return;
+                    }
+                    Err(err) => {
+                        warn!(sub_id = %self.params.sub_id, retry, "Reconnect failed: {err}");
+                        retry = retry.saturating_add(1);
+                    }
+                }
+            };
+
+            if !self.recover().await {
+                self.unsubscribe(connection).await.ok();
+                return;
+            }
+        }
+    }
+}
+
+impl HttpClient {
+    /// Subscribe to the `filters` of `kind` at `mint_url` [NUT-17]
+    ///
+    /// Returns once the mint accepted the subscription. Mints reached
+    /// through a proxy cannot be subscribed to, WebSockets are not proxied.
+    pub async fn subscribe(
+        &self,
+        mint_url: Url,
+        kind: SubscriptionKind,
+        filters: Vec<String>,
+    ) -> Result<Subscription, Error> {
+        if self.proxied(&mint_url)? {
+            return Err(ws_error("Subscriptions are not made through a proxy"));
+        }
+
+        let mut sub_id = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut sub_id);
+        let (sender, notifications) = mpsc::channel(NOTIFICATION_CAPACITY);
+
+        let mut subscriber = Subscriber {
+            client: self.clone(),
+            ws_url: ws_url(mint_url.clone())?,
+            mint_url,
+            params: Params {
+                kind,
+                sub_id: hex::encode(sub_id),
+                filters,
+            },
+            notifications: sender,
+            next_id: 0,
+        };
+        let connection = subscriber.subscribe().await?;
+
+        let (unsubscribe, unsubscribe_receiver) = oneshot::channel();
+        let subscription = Subscription {
+            sub_id: subscriber.params.sub_id.clone(),
+            kind,
+            notifications,
+            unsubscribe,
+        };
+        tokio::spawn(subscriber.run(connection, unsubscribe_receiver, self.retry_policy));
+
+        Ok(subscription)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nuts::{MintQuoteBolt11Response, State};
+    use crate::test_utils::ws_mint::WsMint;
+
+    fn client() -> HttpClient {
+        HttpClient::builder()
+            .retry_policy(RetryPolicy {
+                base_delay: Duration::from_millis(10),
+                ..RetryPolicy::default()
+            })
+            .build()
+            .unwrap()
+    }
+
+    async fn next(subscription: &mut Subscription) -> Notification {
+        tokio::time::timeout(Duration::from_secs(5), subscription.next())
+            .await
+            .expect("Notification in time")
+            .expect("Subscription open")
+    }
+
+    fn mint_quote(notification: Notification) -> MintQuoteBolt11Response {
+        match notification {
+            Notification::MintQuote(quote) => quote,
+            notification => panic!("Not a mint quote: {notification:?}"),
+        }
+    }
+
+    #[test]
+    fn test_ws_url() {
+        assert_eq!(
+            ws_url(Url::parse("https://mint.example/cashu").unwrap())
+                .unwrap()
+                .as_str(),
+            "wss://mint.example/cashu/v1/ws"
+        );
+        assert_eq!(
+            ws_url(Url::parse("http://127.0.0.1:3338").unwrap())
+                .unwrap()
+                .as_str(),
+            "ws://127.0.0.1:3338/v1/ws"
+        );
+        // Example of RFC 6455
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[tokio::test]
+    async fn test_frames_round_trip() {
+        for len in [0, 125, 126, 0xffff, 0x10000] {
+            let payload: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            for masked in [true, false] {
+                let mut buffer = Vec::new();
+                write_frame(&mut buffer, OPCODE_TEXT, &payload, masked)
+                    .await
+                    .unwrap();
+                let frame = read_frame(&mut buffer.as_slice()).await.unwrap();
+                assert_eq!(
+                    frame,
+                    Frame {
+                        fin: true,
+                        opcode: OPCODE_TEXT,
+                        payload: payload.clone(),
+                    }
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_notification() {
+        let mint = WsMint::start().await;
+        let client = client();
+
+        let mut subscription = client
+            .subscribe(
+                mint.url(),
+                SubscriptionKind::Bolt11MintQuote,
+                vec!["quote".to_string()],
+            )
+            .await
+            .unwrap();
+
+        mint.notify_mint_quote("other", true);
+        mint.notify_mint_quote("quote", true);
+        let quote = mint_quote(next(&mut subscription).await);
+        assert_eq!(quote.quote, "quote");
+        assert!(quote.paid);
+
+        // Proof states are typed by the kind of the subscription
+        let y = PublicKey::from_hex(
+            "02194603ffa36356f4a56b7df9371fc3192472351453ec7398b8da8117e7c3e104",
+        )
+        .unwrap();
+        let mut subscription = client
+            .subscribe(mint.url(), SubscriptionKind::ProofState, vec![y.to_hex()])
+            .await
+            .unwrap();
+        mint.notify_proof_state(y, State::Spent);
+        let Notification::ProofState(state) = next(&mut subscription).await else {
+            panic!("Not a proof state");
+        };
+        assert_eq!((state.y, state.state), (y, State::Spent));
+    }
+
+    #[tokio::test]
+    async fn test_reconnect() {
+        let mint = WsMint::start().await;
+        let mut subscription = client()
+            .subscribe(
+                mint.url(),
+                SubscriptionKind::Bolt11MintQuote,
+                vec!["quote".to_string()],
+            )
+            .await
+            .unwrap();
+        assert_eq!(mint.polls(), 0);
+
+        // Paid while disconnected, the poll after the reconnect notifies it
+        mint.disconnect();
+        mint.set_paid(true);
+        let quote = mint_quote(next(&mut subscription).await);
+        assert!(quote.paid);
+        assert_eq!(mint.polls(), 1);
+
+        // Subscribed again under the same id
+        let subscribes: Vec<Params> = mint
+            .requests()
+            .into_iter()
+            .filter_map(|request| match request.method {
+                WsMethod::Subscribe(params) => Some(params),
+                WsMethod::Unsubscribe(_) => None,
+            })
+            .collect();
+        assert_eq!(subscribes.len(), 2);
+        assert!(subscribes
+            .iter()
+            .all(|params| params.sub_id == subscription.sub_id()));
+
+        // and notified over the new connection
+        mint.notify_mint_quote("quote", false);
+        assert!(!mint_quote(next(&mut subscription).await).paid);
+    }
+
+    #[tokio::test]
+    async fn test_unsubscribe() {
+        let mint = WsMint::start().await;
+        let client = client();
+        let subscription = client
+            .subscribe(
+                mint.url(),
+                SubscriptionKind::Bolt11MeltQuote,
+                vec!["quote".to_string()],
+            )
+            .await
+            .unwrap();
+        let sub_id = subscription.sub_id().to_string();
+
+        subscription.unsubscribe().await.unwrap();
+        assert!(mint.requests().iter().any(|request| request.method
+            == WsMethod::Unsubscribe(UnsubscribeParams {
+                sub_id: sub_id.clone()
+            })));
+        assert_eq!(mint.subscriptions(), 0);
+
+        // Subscriptions the mint refuses are not opened
+        assert!(matches!(
+            client
+                .subscribe(mint.url(), SubscriptionKind::Bolt11MeltQuote, vec![])
+                .await,
+            Err(Error::SubscriptionRefused { code: -32602, .. })
+        ));
+    }
+}
@@ -0,0 +1,763 @@
+//! Core Lightning backend
+//!
+//! Talks JSON-RPC to the `lightning-rpc` socket of a CLN node, one
+//! connection per call. Enabled with the `cln` feature.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer};
+use serde_json::{json, Value};
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+
+use crate::lightning::{self, LnBackend, Payment, PaymentStatus};
+use crate::nuts::CurrencyUnit;
+use crate::types::{InvoiceDescription, InvoiceStatus};
+use crate::util::unix_time;
+use crate::{Amount, Bolt11Invoice, Sha256};
+
+/// `pay` error codes of CLN after which `pay` has stopped and no part of
+/// the payment is in flight
+const PAY_TRY_OTHER_ROUTE: i64 = 204;
+const PAY_ROUTE_NOT_FOUND: i64 = 205;
+const PAY_ROUTE_TOO_EXPENSIVE: i64 = 206;
+const PAY_INVOICE_EXPIRED: i64 = 207;
+const PAY_STOPPED_RETRYING: i64 = 209;
+const PAY_DESTINATION_PERM_FAIL: i64 = 203;
+/// Invalid parameters, nothing was sent
+const JSONRPC_INVALID_PARAMS: i64 = -32602;
+/// `waitanyinvoice` reached its timeout
+const WAIT_TIMEOUT: i64 = 904;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    /// No response within [ClnConfig::rpc_timeout]
+    #[error("CLN did not answer `{0}` in time")]
+    Timeout(String),
+    /// Error response of the node
+    #[error("CLN error {code} on `{method}`: {message}")]
+    Rpc {
+        method: String,
+        code: i64,
+        message: String,
+    },
+    #[error("No invoice with payment hash `{0}`")]
+    UnknownInvoice(Sha256),
+}
+
+impl From<Error> for lightning::Error {
+    fn from(err: Error) -> Self {
+        Self::Backend(Box::new(err))
+    }
+}
+
+/// Connection settings of a [Cln] backend
+#[derive(Debug, Clone)]
+pub struct ClnConfig {
+    /// Path of the `lightning-rpc` socket
+    pub rpc_path: PathBuf,
+    /// Timeout of a call, other than `pay`
+    pub rpc_timeout: Duration,
+    /// How long `pay` keeps retrying before giving up
+    ///
+    /// Calls to `pay` time out a minute after this, leaving the payment
+    /// pending for [LnBackend::payment_status].
+    pub pay_timeout: Duration,
+}
+
+impl ClnConfig {
+    pub fn new(rpc_path: impl Into<PathBuf>) -> Self {
+        Self {
+            rpc_path: rpc_path.into(),
+            rpc_timeout: Duration::from_secs(30),
+            pay_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+/// [LnBackend] of a CLN node
+#[derive(Debug)]
+pub struct Cln {
+    config: ClnConfig,
+    next_id: AtomicU64,
+}
+
+/// Invoice paid, from [Cln::wait_any_invoice]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PaidInvoice {
+    pub label: String,
+    pub payment_hash: Sha256,
+    /// Index to pass to the next [Cln::wait_any_invoice]
+    pub pay_index: u64,
+}
+
+#[derive(Deserialize)]
+struct InvoiceResponse {
+    bolt11: String,
+}
+
+#[derive(Deserialize)]
+struct ListInvoicesResponse {
+    invoices: Vec<ListedInvoice>,
+}
+
+#[derive(Deserialize)]
+struct ListedInvoice {
+    status: String,
+}
+
+#[derive(Deserialize)]
+struct PayResponse {
+    payment_preimage: Option<String>,
+    #[serde(deserialize_with = "msat")]
+    amount_sent_msat: u64,
+    status: String,
+}
+
+#[derive(Deserialize)]
+struct ListPaysResponse {
+    pays: Vec<ListedPay>,
+}
+
+#[derive(Deserialize)]
+struct ListedPay {
+    status: String,
+    preimage: Option<String>,
+    #[serde(default, deserialize_with = "optional_msat")]
+    amount_sent_msat: Option<u64>,
+}
+
+/// Millisatoshis as a number, or as a `"1000msat"` string of older nodes
+fn msat<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Msat {
+        Number(u64),
+        String(String),
+    }
+
+    match Msat::deserialize(deserializer)? {
+        Msat::Number(msat) => Ok(msat),
+        Msat::String(msat) => msat
+            .strip_suffix("msat")
+            .unwrap_or(&msat)
+            .parse()
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+fn optional_msat<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    msat(deserializer).map(Some)
+}
+
+impl Cln {
+    pub fn new(config: ClnConfig) -> Self {
+        Self {
+            config,
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Call `method` and parse its result
+    pub async fn call<R>(&self, method: &str, params: Value) -> Result<R, Error>
+    where
+        R: DeserializeOwned,
+    {
+        self.call_with_timeout(method, params, self.config.rpc_timeout)
+            .await
+    }
+
+    async fn call_with_timeout<R>(
+        &self,
+        method: &str,
+        params: Value,
+        timeout: Duration,
+    ) -> Result<R, Error>
+    where
+        R: DeserializeOwned,
+    {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": self.next_id.fetch_add(1, Ordering::Relaxed),
+            "method": method,
+            "params": params,
+        });
+
+        let response = tokio::time::timeout(timeout, self.round_trip(&request))
+            .await
+            .map_err(|_| Error::Timeout(method.to_string()))??;
+
+        #[derive(Deserialize)]
+        struct RpcError {
+            code: i64,
+            message: String,
+        }
+
+        #[derive(Deserialize)]
+        struct Response {
+            result: Option<Value>,
+            error: Option<RpcError>,
+        }
+
+        let response: Response = serde_json::from_value(response)?;
+        match (response.result, response.error) {
+            (_, Some(error)) => Err(Error::Rpc {
+                method: method.to_string(),
+                code: error.code,
+                message: error.message,
+            }),
+            (Some(result), None) => Ok(serde_json::from_value(result)?),
+            (None, None) => Ok(serde_json::from_value(Value::Null)?),
+        }
+    }
+
+    /// Write `request` and read one JSON response
+    ///
+    /// Responses are not newline delimited, so reading stops once the bytes
+    /// read parse as a whole value.
+    async fn round_trip(&self, request: &Value) -> Result<Value, Error> {
+        let mut stream = UnixStream::connect(&self.config.rpc_path).await?;
+        stream.write_all(&serde_json::to_vec(request)?).await?;
+
+        let mut buf = Vec::new();
+        let mut chunk = [0; 4096];
+        loop {
+            let read = stream.read(&mut chunk).await?;
+            if read == 0 {
+                return Ok(serde_json::from_slice(&buf)?);
+            }
+            buf.extend_from_slice(&chunk[..read]);
+
+            match serde_json::from_slice(&buf) {
+                Ok(response) => return Ok(response),
+                Err(err) if err.is_eof() => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    /// Wait for the next invoice paid after `last_pay_index`, as an
+    /// alternative to polling [LnBackend::invoice_status]
+    ///
+    /// Waits at most `timeout`, `None` if no invoice was paid by then.
+    pub async fn wait_any_invoice(
+        &self,
+        last_pay_index: u64,
+        timeout: Duration,
+    ) -> Result<Option<PaidInvoice>, Error> {
+        let params = json!({
+            "lastpay_index": last_pay_index,
+            "timeout": timeout.as_secs(),
+        });
+
+        // `waitanyinvoice` answers with an error once its own timeout hits
+        match self
+            .call_with_timeout("waitanyinvoice", params, timeout + self.config.rpc_timeout)
+            .await
+        {
+            Ok(invoice) => Ok(Some(invoice)),
+            Err(Error::Rpc {
+                code: WAIT_TIMEOUT, ..
+            }) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[async_trait]
+impl LnBackend for Cln {
+    async fn create_invoice(
+        &self,
+        amount: Amount,
+        unit: &CurrencyUnit,
+        description: Option<InvoiceDescription>,
+        expiry: u64,
+    ) -> Result<Bolt11Invoice, lightning::Error> {
+        let amount_msat = match unit {
+            CurrencyUnit::Sat => amount
+                .checked_to_msat()
+                .ok_or(lightning::Error::Unsupported(
+                    "create_invoice over u64::MAX msat",
+                ))?,
+            CurrencyUnit::Msat => amount.into(),
+            _ => return Err(lightning::Error::Unsupported("create_invoice in this unit")),
+        };
+        // `invoice` hashes a description it is given, it takes no bare hash
+        let description = match description {
+            Some(InvoiceDescription::Text(description)) => description,
+            Some(InvoiceDescription::Hash(_)) => {
+                return Err(lightning::Error::Unsupported("description_hash"))
+            }
+            None => String::new(),
+        };
+
+        let response: InvoiceResponse = self
+            .call(
+                "invoice",
+                json!({
+                    "amount_msat": amount_msat,
+                    "label": uuid::Uuid::new_v4().to_string(),
+                    "description": description,
+                    "expiry": expiry.saturating_sub(unix_time()),
+                }),
+            )
+            .await?;
+
+        response
+            .bolt11
+            .parse()
+            .map_err(|err| lightning::Error::Backend(Box::new(err)))
+    }
+
+    async fn pay_invoice(
+        &self,
+        bolt11: &Bolt11Invoice,
+        amount_msat: u64,
+        max_fee: Amount,
+    ) -> Result<Payment, lightning::Error> {
+        let mut params = json!({
+            "bolt11": bolt11.to_string(),
+            "maxfee": max_fee.to_msat(),
+            "retry_for": self.config.pay_timeout.as_secs(),
+        });
+        // CLN refuses an amount for invoices that carry one
+        if bolt11.amount_milli_satoshis().is_none() {
+            params["amount_msat"] = amount_msat.into();
+        }
+
+        let timeout = self.config.pay_timeout + Duration::from_secs(60);
+        let response = match self.call_with_timeout("pay", params, timeout).await {
+            Ok(response) => response,
+            Err(Error::Rpc { code, message, .. })
+                if matches!(
+                    code,
+                    PAY_DESTINATION_PERM_FAIL
+                        | PAY_TRY_OTHER_ROUTE
+                        | PAY_ROUTE_NOT_FOUND
+                        | PAY_ROUTE_TOO_EXPENSIVE
+                        | PAY_INVOICE_EXPIRED
+                        | PAY_STOPPED_RETRYING
+                        | JSONRPC_INVALID_PARAMS
+                ) =>
+            {
+                // An earlier attempt for the same hash may still be in flight
+                return match self.payment_status(bolt11.payment_hash()).await? {
+                    PaymentStatus::Failed => Err(lightning::Error::PaymentFailed(message)),
+                    PaymentStatus::Paid(payment) => Ok(payment),
+                    PaymentStatus::Pending => Err(Error::Rpc {
+                        method: "pay".to_string(),
+                        code,
+                        message,
+                    }
+                    .into()),
+                };
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let PayResponse {
+            payment_preimage,
+            amount_sent_msat,
+            status,
+        } = response;
+
+        match (status.as_str(), payment_preimage) {
+            ("complete", Some(preimage)) => Ok(Payment {
+                preimage,
+                total_spent_msat: amount_sent_msat,
+            }),
+            ("failed", _) => Err(lightning::Error::PaymentFailed(
+                "CLN reported the payment failed".to_string(),
+            )),
+            // `pending`: `pay` gave up waiting but parts are still in flight
+            (status, _) => Err(lightning::Error::Backend(
+                format!("Payment is {status}").into(),
+            )),
+        }
+    }
+
+    async fn invoice_status(
+        &self,
+        payment_hash: &Sha256,
+    ) -> Result<InvoiceStatus, lightning::Error> {
+        let response: ListInvoicesResponse = self
+            .call(
+                "listinvoices",
+                json!({ "payment_hash": payment_hash.to_string() }),
+            )
+            .await?;
+
+        let invoice = response
+            .invoices
+            .first()
+            .ok_or(Error::UnknownInvoice(*payment_hash))?;
+
+        Ok(match invoice.status.as_str() {
+            "paid" => InvoiceStatus::Paid,
+            "expired" => InvoiceStatus::Expired,
+            _ => InvoiceStatus::Unpaid,
+        })
+    }
+
+    async fn payment_status(
+        &self,
+        payment_hash: &Sha256,
+    ) -> Result<PaymentStatus, lightning::Error> {
+        let response: ListPaysResponse = self
+            .call(
+                "listpays",
+                json!({ "payment_hash": payment_hash.to_string() }),
+            )
+            .await?;
+
+        if let Some(pay) = response
+            .pays
+            .iter()
+            .find(|pay| pay.status == "complete" && pay.preimage.is_some())
+        {
+            return Ok(PaymentStatus::Paid(Payment {
+                preimage: pay.preimage.clone().unwrap_or_default(),
+                total_spent_msat: pay.amount_sent_msat.unwrap_or_default(),
+            }));
+        }
+
+        if response.pays.iter().any(|pay| pay.status == "pending") {
+            return Ok(PaymentStatus::Pending);
+        }
+
+        Ok(PaymentStatus::Failed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::path::Path;
+    use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
+
+    use tokio::net::UnixListener;
+
+    use super::*;
+
+    const INVOICE: &str = "lnbc2500u1pj48ugqdq8w3jhxaqpp5qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqsp59g4z52329g4z52329g4z52329g4z52329g4z52329g4z52329g4q9qrsgqcqzysd6exwz2yjprkglq820pffp8wcu97sxuzcgx7raensa83dnc4kpmhkuqevwf6hmv6a3mc9aegn89jv7a8e9wggdxtfsjwrur7n6sx4rsq7rvjx3";
+
+    /// Node answering each method with a recorded response, in order
+    struct Fixtures {
+        path: PathBuf,
+        calls: Arc<Mutex<Vec<Value>>>,
+    }
+
+    impl Fixtures {
+        fn serve(responses: Vec<(&'static str, Value)>) -> Self {
+            let path = std::env::temp_dir().join(format!("cdk-cln-{}", uuid::Uuid::new_v4()));
+            let listener = UnixListener::bind(&path).unwrap();
+            let calls = Arc::new(Mutex::new(Vec::new()));
+
+            let mut responses: HashMap<&str, Vec<Value>> =
+                responses
+                    .into_iter()
+                    .fold(HashMap::new(), |mut map, (method, response)| {
+                        map.entry(method).or_default().push(response);
+                        map
+                    });
+
+            let recorded = Arc::clone(&calls);
+            tokio::spawn(async move {
+                loop {
+                    let (mut stream, _) = listener.accept().await.unwrap();
+                    let mut buf = vec![0; 65536];
+                    let read = stream.read(&mut buf).await.unwrap();
+                    let request: Value = serde_json::from_slice(&buf[..read]).unwrap();
+                    recorded.lock().unwrap().push(request.clone());
+
+                    let method = request["method"].as_str().unwrap();
+                    let Some(response) = responses.get_mut(method).map(|r| r.remove(0)) else {
+                        panic!("Unexpected call to {method}");
+                    };
+                    if response == Value::Null {
+                        // Never answer, for timeouts
+                        std::mem::forget(stream);
+                        continue;
+                    }
+
+                    let mut response = response;
+                    response["jsonrpc"] = "2.0".into();
+                    response["id"] = request["id"].clone();
+                    // Split in two writes, as a large response would be
+                    let bytes = serde_json::to_vec(&response).unwrap();
+                    let (first, rest) = bytes.split_at(bytes.len() / 2);
+                    stream.write_all(first).await.unwrap();
+                    stream.flush().await.unwrap();
+                    stream.write_all(rest).await.unwrap();
+                    stream.write_all(b"\n\n").await.unwrap();
+                }
+            });
+
+            Self { path, calls }
+        }
+
+        fn cln(&self) -> Cln {
+            let mut config = ClnConfig::new(&self.path);
+            config.rpc_timeout = Duration::from_millis(200);
+            Cln::new(config)
+        }
+
+        fn calls(&self) -> Vec<Value> {
+            self.calls.lock().unwrap().clone()
+        }
+    }
+
+    impl Drop for Fixtures {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    fn error(code: i64, message: &str) -> Value {
+        json!({ "error": { "code": code, "message": message } })
+    }
+
+    #[tokio::test]
+    async fn test_create_invoice_and_status() {
+        let fixtures = Fixtures::serve(vec![
+            (
+                "invoice",
+                json!({ "result": {
+                    "payment_hash": "80e02a38bae7c1613a08691b0757832fefc519c109f939ad017e89e8ce86832c",
+                    "expires_at": 1704110000u64,
+                    "bolt11": INVOICE,
+                }}),
+            ),
+            (
+                "listinvoices",
+                json!({ "result": { "invoices": [{ "status": "unpaid" }] } }),
+            ),
+            (
+                "listinvoices",
+                json!({ "result": { "invoices": [{ "status": "paid", "amount_received_msat": 1000 }] } }),
+            ),
+            ("listinvoices", json!({ "result": { "invoices": [] } })),
+        ]);
+        let cln = fixtures.cln();
+
+        let invoice = cln
+            .create_invoice(
+                Amount::from(1),
+                &CurrencyUnit::Sat,
+                Some(InvoiceDescription::Text("coffee".to_string())),
+                unix_time() + 600,
+            )
+            .await
+            .unwrap();
+        assert_eq!(invoice.to_string(), INVOICE);
+
+        let params = &fixtures.calls()[0]["params"];
+        assert_eq!(params["amount_msat"], 1000);
+        assert_eq!(params["description"], "coffee");
+        assert!(params["expiry"].as_u64().unwrap() <= 600);
+
+        let hash = invoice.payment_hash();
+        assert_eq!(
+            cln.invoice_status(hash).await.unwrap(),
+            InvoiceStatus::Unpaid
+        );
+        assert_eq!(cln.invoice_status(hash).await.unwrap(), InvoiceStatus::Paid);
+        assert!(matches!(
+            cln.invoice_status(hash).await,
+            Err(lightning::Error::Backend(_))
+        ));
+
+        assert!(matches!(
+            cln.create_invoice(Amount::from(1), &CurrencyUnit::Usd, None, 0)
+                .await,
+            Err(lightning::Error::Unsupported(_))
+        ));
+        assert!(!cln.supports_description_hash());
+        assert!(matches!(
+            cln.create_invoice(
+                Amount::from(1),
+                &CurrencyUnit::Sat,
+                Some(InvoiceDescription::Hash([7; 32])),
+                0
+            )
+            .await,
+            Err(lightning::Error::Unsupported("description_hash"))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_pay_invoice() {
+        let bolt11 = Bolt11Invoice::from_str(INVOICE).unwrap();
+        let fixtures = Fixtures::serve(vec![
+            (
+                "pay",
+                json!({ "result": {
+                    "payment_preimage": "0000000000000000000000000000000000000000000000000000000000000000",
+                    "amount_msat": 1000,
+                    "amount_sent_msat": "1002msat",
+                    "status": "complete",
+                }}),
+            ),
+            // `pay` returned while parts are still trying
+            (
+                "pay",
+                json!({ "result": { "amount_sent_msat": 1002, "status": "pending" } }),
+            ),
+            (
+                "pay",
+                error(PAY_ROUTE_TOO_EXPENSIVE, "Fee exceeds our fee budget"),
+            ),
+            (
+                "listpays",
+                json!({ "result": { "pays": [{ "status": "failed" }] } }),
+            ),
+            (
+                "pay",
+                error(PAY_STOPPED_RETRYING, "Ran out of routes to try"),
+            ),
+            (
+                "listpays",
+                json!({ "result": { "pays": [{ "status": "failed" }, { "status": "pending" }] } }),
+            ),
+            ("pay", error(210, "Payment status unexpected")),
+        ]);
+        let cln = fixtures.cln();
+
+        let payment = cln
+            .pay_invoice(&bolt11, 1000, Amount::from(2))
+            .await
+            .unwrap();
+        assert_eq!(payment.total_spent_msat, 1002);
+        let params = &fixtures.calls()[0]["params"];
+        assert_eq!(params["maxfee"], 2000);
+        assert!(params.get("amount_msat").is_none());
+
+        assert!(matches!(
+            cln.pay_invoice(&bolt11, 1000, Amount::from(2)).await,
+            Err(lightning::Error::Backend(_))
+        ));
+        assert!(matches!(
+            cln.pay_invoice(&bolt11, 1000, Amount::from(2)).await,
+            Err(lightning::Error::PaymentFailed(_))
+        ));
+        assert!(matches!(
+            cln.pay_invoice(&bolt11, 1000, Amount::from(2)).await,
+            Err(lightning::Error::Backend(_))
+        ));
+        assert!(matches!(
+            cln.pay_invoice(&bolt11, 1000, Amount::from(2)).await,
+            Err(lightning::Error::Backend(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_payment_status() {
+        let hash = *Bolt11Invoice::from_str(INVOICE).unwrap().payment_hash();
+        let fixtures = Fixtures::serve(vec![
+            ("listpays", json!({ "result": { "pays": [] } })),
+            (
+                "listpays",
+                json!({ "result": { "pays": [{ "status": "failed" }, { "status": "pending" }] } }),
+            ),
+            (
+                "listpays",
+                json!({ "result": { "pays": [
+                    { "status": "failed" },
+                    { "status": "complete", "preimage": "00", "amount_sent_msat": 1001 },
+                ]}}),
+            ),
+            ("listpays", Value::Null),
+        ]);
+        let cln = fixtures.cln();
+
+        assert_eq!(
+            cln.payment_status(&hash).await.unwrap(),
+            PaymentStatus::Failed
+        );
+        assert_eq!(
+            cln.payment_status(&hash).await.unwrap(),
+            PaymentStatus::Pending
+        );
+        assert_eq!(
+            cln.payment_status(&hash).await.unwrap(),
+            PaymentStatus::Paid(Payment {
+                preimage: "00".to_string(),
+                total_spent_msat: 1001,
+            })
+        );
+
+        // A node that does not answer leaves the state unknown
+        let err = cln.payment_status(&hash).await.unwrap_err();
+        assert!(err.to_string().contains("in time"));
+    }
+
+    #[tokio::test]
+    async fn test_wait_any_invoice() {
+        let fixtures = Fixtures::serve(vec![
+            (
+                "waitanyinvoice",
+                json!({ "result": {
+                    "label": "quote",
+                    "payment_hash": "80e02a38bae7c1613a08691b0757832fefc519c109f939ad017e89e8ce86832c",
+                    "status": "paid",
+                    "pay_index": 7,
+                }}),
+            ),
+            ("waitanyinvoice", error(WAIT_TIMEOUT, "Timed out")),
+        ]);
+        let cln = fixtures.cln();
+
+        let paid = cln
+            .wait_any_invoice(6, Duration::from_secs(1))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(paid.pay_index, 7);
+        assert_eq!(fixtures.calls()[0]["params"]["lastpay_index"], 6);
+
+        assert_eq!(
+            cln.wait_any_invoice(7, Duration::from_secs(1))
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    /// Runs against the node at `CLN_RPC_PATH`, skipped when it is not set
+    #[tokio::test]
+    async fn test_regtest_node() {
+        let Ok(rpc_path) = std::env::var("CLN_RPC_PATH") else {
+            return;
+        };
+        let cln = Cln::new(ClnConfig::new(Path::new(&rpc_path)));
+
+        let invoice = cln
+            .create_invoice(Amount::from(10), &CurrencyUnit::Sat, None, unix_time() + 60)
+            .await
+            .unwrap();
+        assert_eq!(
+            cln.invoice_status(invoice.payment_hash()).await.unwrap(),
+            InvoiceStatus::Unpaid
+        );
+        assert_eq!(
+            cln.payment_status(invoice.payment_hash()).await.unwrap(),
+            PaymentStatus::Failed
+        );
+    }
+}
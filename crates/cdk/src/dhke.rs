@@ -3,8 +3,8 @@
 use std::ops::Deref;
 
 use bitcoin::hashes::sha256::Hash as Sha256Hash;
-use bitcoin::hashes::Hash;
-use bitcoin::secp256k1::{Parity, PublicKey as NormalizedPublicKey, Scalar, XOnlyPublicKey};
+use bitcoin::hashes::{Hash, HashEngine};
+use bitcoin::secp256k1::{Scalar, XOnlyPublicKey};
 
 use crate::error::Error;
 use crate::nuts::nut01::{PublicKey, SecretKey};
@@ -17,22 +17,23 @@ use crate::SECP256K1;
 const DOMAIN_SEPARATOR: &[u8; 28] = b"Secp256k1_HashToCurve_Cashu_";
 
 pub fn hash_to_curve(message: &[u8]) -> Result<PublicKey, Error> {
-    let msg_to_hash: Vec<u8> = [DOMAIN_SEPARATOR, message].concat();
+    let mut engine = Sha256Hash::engine();
+    engine.input(DOMAIN_SEPARATOR);
+    engine.input(message);
+    let msg_hash: [u8; 32] = Sha256Hash::from_engine(engine).to_byte_array();
 
-    let msg_hash: [u8; 32] = Sha256Hash::hash(&msg_to_hash).to_byte_array();
+    // Hash of the message followed by the counter, only the counter changes
+    let mut bytes_to_hash = [0u8; 36];
+    bytes_to_hash[..32].copy_from_slice(&msg_hash);
 
     let mut counter: u32 = 0;
     while counter < 2_u32.pow(16) {
-        let mut bytes_to_hash: Vec<u8> = Vec::with_capacity(36);
-        bytes_to_hash.extend_from_slice(&msg_hash);
-        bytes_to_hash.extend_from_slice(&counter.to_le_bytes());
+        bytes_to_hash[32..].copy_from_slice(&counter.to_le_bytes());
         let hash: [u8; 32] = Sha256Hash::hash(&bytes_to_hash).to_byte_array();
 
         // Try to parse public key
         match XOnlyPublicKey::from_slice(&hash) {
-            Ok(pk) => {
-                return Ok(NormalizedPublicKey::from_x_only_public_key(pk, Parity::Even).into())
-            }
+            Ok(pk) => return Ok(pk.into()),
             Err(_) => {
                 counter += 1;
             }
@@ -42,6 +43,32 @@ pub fn hash_to_curve(message: &[u8]) -> Result<PublicKey, Error> {
     Err(Error::NoValidPoint)
 }
 
+/// [hash_to_curve] of each of `messages`, in order
+///
+/// With the `parallel` feature the messages are hashed on the rayon pool.
+#[cfg(not(feature = "parallel"))]
+pub fn hash_to_curve_many(messages: &[&[u8]]) -> Result<Vec<PublicKey>, Error> {
+    let mut ys = Vec::with_capacity(messages.len());
+    for message in messages {
+        ys.push(hash_to_curve(message)?);
+    }
+
+    Ok(ys)
+}
+
+/// [hash_to_curve] of each of `messages`, in order
+///
+/// With the `parallel` feature the messages are hashed on the rayon pool.
+#[cfg(feature = "parallel")]
+pub fn hash_to_curve_many(messages: &[&[u8]]) -> Result<Vec<PublicKey>, Error> {
+    use rayon::prelude::*;
+
+    messages
+        .par_iter()
+        .map(|message| hash_to_curve(message))
+        .collect()
+}
+
 pub fn hash_e<I>(public_keys: I) -> [u8; 32]
 where
     I: IntoIterator<Item = PublicKey>,
@@ -89,6 +116,11 @@ pub fn unblind_message(
 }
 
 /// Construct Proof
+///
+/// The DLEQ proof of each promise is kept with the blinding factor of its
+/// output, so the proofs can be verified offline with [Proof::verify_dleq].
+///
+/// [Proof::verify_dleq]: crate::nuts::Proof::verify_dleq
 pub fn construct_proofs(
     promises: Vec<BlindSignature>,
     rs: Vec<SecretKey>,
@@ -113,6 +145,7 @@ pub fn construct_proofs(
             c: unblinded_signature,
             witness: None,
             dleq,
+            y: Default::default(),
         };
 
         proofs.push(proof);
@@ -133,16 +166,20 @@ pub fn sign_message(k: &SecretKey, blinded_message: &PublicKey) -> Result<Public
 }
 
 /// Verify Message
+///
+/// `C == k * Y`, where `Y = hash_to_curve(msg)`
 pub fn verify_message(
     a: &SecretKey,
     unblinded_message: PublicKey,
     msg: &[u8],
 ) -> Result<(), Error> {
-    // Y
-    let y: PublicKey = hash_to_curve(msg)?;
+    verify_y(a, unblinded_message, hash_to_curve(msg)?)
+}
 
+/// [verify_message] of the message whose [hash_to_curve] is `y`
+pub fn verify_y(a: &SecretKey, unblinded_message: PublicKey, y: PublicKey) -> Result<(), Error> {
     // Compute the expected unblinded message
-    let expected_unblinded_message: PublicKey = y.combine(&a.public_key())?.into();
+    let expected_unblinded_message: PublicKey = y.mul_tweak(&SECP256K1, &a.as_scalar())?.into();
 
     // Compare the unblinded_message with the expected value
     if unblinded_message == expected_unblinded_message {
@@ -190,6 +227,38 @@ mod tests {
         assert_eq!(y, expected_y);
     }
 
+    #[test]
+    fn test_hash_to_curve_many() {
+        // NUT-00 test vectors
+        let secrets: Vec<Vec<u8>> = (0u8..3)
+            .map(|i| {
+                let mut secret = [0u8; 32];
+                secret[31] = i;
+                secret.to_vec()
+            })
+            .collect();
+        let expected: Vec<PublicKey> = [
+            "024cce997d3b518f739663b757deaec95bcd9473c30a14ac2fd04023a739d1a725",
+            "022e7158e11c9506f1aa4248bf531298daa7febd6194f003edcd9b93ade6253acf",
+            "026cdbe15362df59cd1dd3c9c11de8aedac2106eca69236ecd9fbe117af897be4f",
+        ]
+        .iter()
+        .map(|y| PublicKey::from_hex(y).unwrap())
+        .collect();
+
+        let messages: Vec<&[u8]> = secrets.iter().map(Vec::as_slice).collect();
+        assert_eq!(hash_to_curve_many(&messages).unwrap(), expected);
+        assert!(hash_to_curve_many(&[]).unwrap().is_empty());
+
+        // Order is kept over more messages than threads
+        let secrets: Vec<Secret> = (0..100).map(|_| Secret::generate()).collect();
+        let messages: Vec<&[u8]> = secrets.iter().map(Secret::as_bytes).collect();
+        let ys = hash_to_curve_many(&messages).unwrap();
+        for (message, y) in messages.iter().zip(ys) {
+            assert_eq!(hash_to_curve(message).unwrap(), y);
+        }
+    }
+
     #[test]
     fn test_hash_e() {
         let c = PublicKey::from_str(
@@ -328,7 +397,6 @@ mod tests {
         );
     }
 
-    #[ignore]
     #[test]
     fn test_blinded_dhke() {
         // a
@@ -341,11 +409,8 @@ mod tests {
 
         let x = Secret::generate();
 
-        // Y
-        let y = hash_to_curve(&x.to_bytes()).unwrap();
-
         // B_
-        let blinded = blind_message(&y.to_bytes(), None).unwrap();
+        let blinded = blind_message(&x.to_bytes(), None).unwrap();
 
         // C_
         let signed = sign_message(&bob_sec, &blinded.0).unwrap();
@@ -356,3 +421,35 @@ mod tests {
         assert!(verify_message(&bob_sec, c, &x.to_bytes()).is_ok());
     }
 }
+
+#[cfg(bench)]
+mod benches {
+    use test::{black_box, Bencher};
+
+    use super::*;
+
+    const SECRET_COUNT: usize = 500;
+
+    fn secrets() -> Vec<Secret> {
+        (0..SECRET_COUNT).map(|_| Secret::generate()).collect()
+    }
+
+    #[bench]
+    pub fn hash_to_curve_500(bh: &mut Bencher) {
+        let secrets = secrets();
+        bh.iter(|| {
+            for secret in &secrets {
+                black_box(hash_to_curve(secret.as_bytes())).unwrap();
+            }
+        });
+    }
+
+    #[bench]
+    pub fn hash_to_curve_many_500(bh: &mut Bencher) {
+        let secrets = secrets();
+        let messages: Vec<&[u8]> = secrets.iter().map(Secret::as_bytes).collect();
+        bh.iter(|| {
+            black_box(hash_to_curve_many(&messages)).unwrap();
+        });
+    }
+}
@@ -2,85 +2,205 @@
 
 use std::string::FromUtf8Error;
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use thiserror::Error;
 
+use crate::nuts::Id;
 use crate::util::hex;
 
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum Error {
     /// Mint does not have a key for amount
-    #[error("No Key for Amount")]
+    #[error("Mint has no key for this amount in the keyset")]
     AmountKey,
     /// Amount is not what expected
-    #[error("Amount miss match")]
+    #[error("Amount does not match the expected amount")]
     Amount,
     /// Token is already spent
     #[error("Token already spent")]
     TokenSpent,
     /// Token could not be validated
-    #[error("Token not verified")]
+    #[error("Token signature could not be verified by the mint key")]
     TokenNotVerifed,
     /// Bolt11 invoice does not have amount
-    #[error("Invoice Amount undefined")]
+    #[error("Invoice has no amount, an amount must be provided")]
     InvoiceAmountUndefined,
+    /// Amount provided for an invoice that has a different amount
+    #[error("Amount does not match the invoice amount")]
+    InvoiceAmountMismatch,
+    /// Partial payment of an invoice the mint cannot pay in parts
+    #[error("Mint does not support multi-path payments for this unit")]
+    MppUnsupported,
     /// Proof is missing a required field
     #[error("Proof missing required field")]
     MissingProofField,
     /// No valid point on curve
-    #[error("No valid point found")]
+    #[error("No valid curve point found for message")]
     NoValidPoint,
     /// Secp256k1 error
-    #[error(transparent)]
+    #[error("Invalid secp256k1 key or signature: {0}")]
     Secp256k1(#[from] bitcoin::secp256k1::Error),
     /// Secret error
-    #[error(transparent)]
+    #[error("Invalid secret: {0}")]
     Secret(#[from] super::secret::Error),
     /// Bip32 error
-    #[cfg(feature = "nut13")]
-    #[error(transparent)]
+    #[error("Invalid derivation: {0}")]
     Bip32(#[from] bitcoin::bip32::Error),
     /// Parse int error
-    #[error(transparent)]
+    #[error("Invalid integer: {0}")]
     ParseInt(#[from] std::num::ParseIntError),
     /// Parse Url Error
-    #[error(transparent)]
+    #[error("Invalid url: {0}")]
     UrlParseError(#[from] url::ParseError),
     /// Utf8 parse error
-    #[error(transparent)]
+    #[error("Invalid utf8: {0}")]
     Utf8ParseError(#[from] FromUtf8Error),
     /// Serde Json error
-    #[error(transparent)]
+    #[error("Invalid json: {0}")]
     SerdeJsonError(#[from] serde_json::Error),
     /// Base64 error
-    #[error(transparent)]
+    #[error("Invalid base64: {0}")]
     Base64Error(#[from] base64::DecodeError),
     /// From hex error
-    #[error(transparent)]
+    #[error("Invalid hex: {0}")]
     HexError(#[from] hex::Error),
     #[cfg(feature = "wallet")]
-    /// From hex error
-    #[error(transparent)]
+    /// Reqwest error
+    #[error("Request to mint failed: {0}")]
     ReqwestError(#[from] reqwest::Error),
     /// Nut01 error
-    #[error(transparent)]
+    #[error("Invalid key: {0}")]
     NUT01(#[from] crate::nuts::nut01::Error),
     /// NUT02 error
-    #[error(transparent)]
+    #[error("Invalid keyset: {0}")]
     NUT02(#[from] crate::nuts::nut02::Error),
     /// NUT11 Error
-    #[error(transparent)]
+    #[error("Spending condition not met: {0}")]
     NUT11(#[from] crate::nuts::nut11::Error),
     /// Custom error
     #[error("`{0}`")]
     CustomError(String),
 }
 
+/// Cashu protocol error codes
+///
+/// <https://github.com/cashubtc/nuts/blob/main/error_codes.md>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// Blinded message of output already signed
+    BlindedMessageAlreadySigned,
+    /// Token could not be verified
+    TokenNotVerified,
+    /// Token is already spent
+    TokenAlreadySpent,
+    /// Transaction is not balanced (inputs != outputs)
+    TransactionUnbalanced,
+    /// Unit in request is not supported
+    UnitUnsupported,
+    /// Amount outside of limit range
+    AmountOutofLimitRange,
+    /// Duplicate inputs provided
+    DuplicateInputs,
+    /// Keyset is not known
+    KeysetNotFound,
+    /// Keyset is inactive, cannot sign messages
+    KeysetInactive,
+    /// Quote request is not paid
+    QuoteNotPaid,
+    /// Tokens have already been issued for quote
+    TokensAlreadyIssued,
+    /// Minting is disabled
+    MintingDisabled,
+    /// Quote is pending
+    QuotePending,
+    /// Invoice already paid
+    InvoiceAlreadyPaid,
+    /// Quote is expired
+    QuoteExpired,
+    /// Code not defined by the protocol
+    Unknown(u32),
+}
+
+impl ErrorCode {
+    /// Error code from numeric protocol code
+    pub fn from_code(code: u32) -> Self {
+        match code {
+            10002 => Self::BlindedMessageAlreadySigned,
+            10003 => Self::TokenNotVerified,
+            11001 => Self::TokenAlreadySpent,
+            11002 => Self::TransactionUnbalanced,
+            11005 => Self::UnitUnsupported,
+            11006 => Self::AmountOutofLimitRange,
+            11007 => Self::DuplicateInputs,
+            12001 => Self::KeysetNotFound,
+            12002 => Self::KeysetInactive,
+            20001 => Self::QuoteNotPaid,
+            20002 => Self::TokensAlreadyIssued,
+            20003 => Self::MintingDisabled,
+            20005 => Self::QuotePending,
+            20006 => Self::InvoiceAlreadyPaid,
+            20007 => Self::QuoteExpired,
+            _ => Self::Unknown(code),
+        }
+    }
+
+    /// Numeric protocol code
+    pub fn to_code(&self) -> u32 {
+        match self {
+            Self::BlindedMessageAlreadySigned => 10002,
+            Self::TokenNotVerified => 10003,
+            Self::TokenAlreadySpent => 11001,
+            Self::TransactionUnbalanced => 11002,
+            Self::UnitUnsupported => 11005,
+            Self::AmountOutofLimitRange => 11006,
+            Self::DuplicateInputs => 11007,
+            Self::KeysetNotFound => 12001,
+            Self::KeysetInactive => 12002,
+            Self::QuoteNotPaid => 20001,
+            Self::TokensAlreadyIssued => 20002,
+            Self::MintingDisabled => 20003,
+            Self::QuotePending => 20005,
+            Self::InvoiceAlreadyPaid => 20006,
+            Self::QuoteExpired => 20007,
+            Self::Unknown(code) => *code,
+        }
+    }
+}
+
+impl Serialize for ErrorCode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u32(self.to_code())
+    }
+}
+
+impl<'de> Deserialize<'de> for ErrorCode {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let code: u32 = u32::deserialize(deserializer)?;
+        Ok(Self::from_code(code))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ErrorResponse {
-    pub code: u32,
+    pub code: ErrorCode,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub detail: Option<String>,
+    /// Active keyset of the unit when outputs are for a keyset that is not,
+    /// so the wallet can refresh its keys and retry
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active_keyset_id: Option<Id>,
 }
 
 impl ErrorResponse {
@@ -89,10 +209,80 @@ impl ErrorResponse {
             Ok(res)
         } else {
             Ok(Self {
-                code: 999,
+                code: ErrorCode::Unknown(999),
                 error: Some(json.to_string()),
                 detail: None,
+                active_keyset_id: None,
             })
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error as _;
+
+    use super::*;
+
+    fn parse_amount(json: &str) -> Result<u64, Error> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    fn application() -> anyhow::Result<u64> {
+        Ok(parse_amount("not json")?)
+    }
+
+    #[test]
+    fn test_error_source_survives_anyhow() {
+        let err = application().unwrap_err();
+
+        let cdk_err = err.downcast_ref::<Error>().unwrap();
+        assert!(matches!(cdk_err, Error::SerdeJsonError(_)));
+        assert!(cdk_err.to_string().starts_with("Invalid json: "));
+
+        let source = cdk_err.source().unwrap();
+        assert!(source.downcast_ref::<serde_json::Error>().is_some());
+        assert_eq!(err.chain().count(), 2);
+
+        let err = anyhow::Error::from(Error::from(bitcoin::secp256k1::Error::InvalidPublicKey));
+        assert!(err
+            .source()
+            .and_then(|source| source.downcast_ref::<bitcoin::secp256k1::Error>())
+            .is_some());
+    }
+
+    #[test]
+    fn test_error_code_round_trip() {
+        for code in [
+            10002, 10003, 11001, 11002, 11005, 11006, 11007, 12001, 12002, 20001, 20002, 20003,
+            20005, 20006, 20007, 9999,
+        ] {
+            assert_eq!(ErrorCode::from_code(code).to_code(), code);
+        }
+
+        assert_eq!(ErrorCode::from_code(42), ErrorCode::Unknown(42));
+    }
+
+    #[test]
+    fn test_error_response_serde() {
+        let response = ErrorResponse {
+            code: ErrorCode::TokenAlreadySpent,
+            error: None,
+            detail: Some("Token already spent".to_string()),
+            active_keyset_id: None,
+        };
+
+        assert_eq!(
+            serde_json::to_string(&response).unwrap(),
+            r#"{"code":11001,"detail":"Token already spent"}"#
+        );
+
+        let response =
+            ErrorResponse::from_json(r#"{"detail":"quote not paid","code":20001}"#).unwrap();
+        assert_eq!(response.code, ErrorCode::QuoteNotPaid);
+        assert_eq!(response.detail, Some("quote not paid".to_string()));
+
+        let response = ErrorResponse::from_json("Internal Server Error").unwrap();
+        assert_eq!(response.code, ErrorCode::Unknown(999));
+    }
+}
@@ -0,0 +1,613 @@
+//! C interface for token parsing and proof verification
+//!
+//! Declared in `include/cdk.h`. Tokens and proofs are passed to C as integer
+//! handles into a registry rather than as pointers, so a handle that was
+//! already freed, or never allocated, is reported as
+//! [CDK_ERROR_INVALID_HANDLE] instead of touching freed memory.
+//!
+//! Every fallible function returns a status code, `CDK_OK` on success, and
+//! stores a description of the failure for [cdk_last_error_message].
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+
+use crate::nuts::{KeySet, KeysResponse, Proofs, Token};
+
+pub const CDK_OK: i32 = 0;
+/// A required pointer argument was null
+pub const CDK_ERROR_NULL_POINTER: i32 = 1;
+/// A string argument was not valid UTF-8
+pub const CDK_ERROR_INVALID_UTF8: i32 = 2;
+/// The handle was freed or never allocated
+pub const CDK_ERROR_INVALID_HANDLE: i32 = 3;
+pub const CDK_ERROR_INVALID_TOKEN: i32 = 4;
+pub const CDK_ERROR_INVALID_KEYSET: i32 = 5;
+/// A proof has no DLEQ proof, or it does not verify
+pub const CDK_ERROR_DLEQ: i32 = 6;
+/// The output buffer is too small, the required length is still written
+pub const CDK_ERROR_BUFFER_TOO_SMALL: i32 = 7;
+/// The library panicked, this is a bug
+pub const CDK_ERROR_PANIC: i32 = 8;
+
+/// Handle to a parsed token
+pub type CdkToken = u64;
+/// Handle to a set of proofs
+pub type CdkProofs = u64;
+
+/// Handles are never reused and shared between the registries, so a token
+/// handle passed where proofs are expected is rejected
+static NEXT_HANDLE: AtomicU64 = AtomicU64::new(1);
+static TOKENS: Lazy<Mutex<HashMap<u64, Token>>> = Lazy::new(Default::default);
+static PROOFS: Lazy<Mutex<HashMap<u64, Proofs>>> = Lazy::new(Default::default);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+struct FfiError {
+    code: i32,
+    message: String,
+}
+
+impl FfiError {
+    fn new(code: i32, message: impl ToString) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+fn insert<T>(registry: &Mutex<HashMap<u64, T>>, value: T) -> u64 {
+    let handle = NEXT_HANDLE.fetch_add(1, Ordering::Relaxed);
+    lock(registry).insert(handle, value);
+    handle
+}
+
+fn lock<T>(registry: &Mutex<HashMap<u64, T>>) -> std::sync::MutexGuard<'_, HashMap<u64, T>> {
+    // A panic while holding the lock is caught at the boundary and leaves
+    // the map itself consistent
+    registry.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn with_handle<T, R>(
+    registry: &Mutex<HashMap<u64, T>>,
+    handle: u64,
+    f: impl FnOnce(&T) -> Result<R, FfiError>,
+) -> Result<R, FfiError> {
+    let registry = lock(registry);
+    let value = registry
+        .get(&handle)
+        .ok_or_else(|| FfiError::new(CDK_ERROR_INVALID_HANDLE, "Invalid handle"))?;
+    f(value)
+}
+
+/// Run `f`, turning errors and panics into a status code
+fn ffi_call(f: impl FnOnce() -> Result<(), FfiError>) -> i32 {
+    let result = catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|_| {
+        Err(FfiError::new(
+            CDK_ERROR_PANIC,
+            "Panic in cdk, please report a bug",
+        ))
+    });
+
+    match result {
+        Ok(()) => CDK_OK,
+        Err(err) => {
+            // Messages never contain interior NULs, fall back to an empty
+            // string if one slipped through
+            let message = CString::new(err.message).unwrap_or_default();
+            LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+            err.code
+        }
+    }
+}
+
+/// # Safety
+///
+/// `ptr` is null or points to a NUL-terminated string
+unsafe fn read_str<'a>(ptr: *const c_char) -> Result<&'a str, FfiError> {
+    if ptr.is_null() {
+        return Err(FfiError::new(CDK_ERROR_NULL_POINTER, "Null string"));
+    }
+
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|err| FfiError::new(CDK_ERROR_INVALID_UTF8, err))
+}
+
+/// # Safety
+///
+/// `ptr` is null or valid for a write of a `T`
+unsafe fn write_out<T>(ptr: *mut T, value: T) -> Result<(), FfiError> {
+    if ptr.is_null() {
+        return Err(FfiError::new(CDK_ERROR_NULL_POINTER, "Null output pointer"));
+    }
+
+    ptr.write(value);
+    Ok(())
+}
+
+/// Description of the last error on the calling thread
+///
+/// Null if no call on this thread failed yet. The string is owned by the
+/// library and valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn cdk_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Parse a serialized token
+///
+/// # Safety
+///
+/// `token` is null or a NUL-terminated string, `out` is null or valid for a
+/// write.
+#[no_mangle]
+pub unsafe extern "C" fn cdk_token_parse(token: *const c_char, out: *mut CdkToken) -> i32 {
+    ffi_call(|| {
+        let token = Token::from_str(read_str(token)?)
+            .map_err(|err| FfiError::new(CDK_ERROR_INVALID_TOKEN, err))?;
+
+        if token.token.is_empty() {
+            return Err(FfiError::new(
+                CDK_ERROR_INVALID_TOKEN,
+                "Token has no proofs",
+            ));
+        }
+
+        if out.is_null() {
+            return Err(FfiError::new(CDK_ERROR_NULL_POINTER, "Null output pointer"));
+        }
+
+        write_out(out, insert(&TOKENS, token))
+    })
+}
+
+/// Total amount of all proofs in the token
+///
+/// # Safety
+///
+/// `out` is null or valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn cdk_token_amount(token: CdkToken, out: *mut u64) -> i32 {
+    ffi_call(|| {
+        let (amount, _) = with_handle(&TOKENS, token, |token| Ok(token.token_info()))?;
+        write_out(out, amount)
+    })
+}
+
+/// Mint URL of the token, as a NUL-terminated string written to `buf`
+///
+/// The length of the URL without the terminating NUL is written to
+/// `out_len` even if `buf` is too small, so a call with a null `buf` and a
+/// `buf_len` of 0 queries the size to allocate.
+///
+/// # Safety
+///
+/// `buf` is null or valid for `buf_len` bytes of writes, `out_len` is null
+/// or valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn cdk_token_mint_url(
+    token: CdkToken,
+    buf: *mut c_char,
+    buf_len: usize,
+    out_len: *mut usize,
+) -> i32 {
+    ffi_call(|| {
+        let (_, mint_url) = with_handle(&TOKENS, token, |token| Ok(token.token_info()))?;
+
+        write_out(out_len, mint_url.len())?;
+
+        if buf_len <= mint_url.len() {
+            return Err(FfiError::new(
+                CDK_ERROR_BUFFER_TOO_SMALL,
+                format!(
+                    "Buffer of {} bytes for {} bytes",
+                    buf_len,
+                    mint_url.len() + 1
+                ),
+            ));
+        }
+
+        if buf.is_null() {
+            return Err(FfiError::new(CDK_ERROR_NULL_POINTER, "Null buffer"));
+        }
+
+        std::ptr::copy_nonoverlapping(mint_url.as_ptr().cast(), buf, mint_url.len());
+        buf.add(mint_url.len()).write(0);
+
+        Ok(())
+    })
+}
+
+/// Proofs of all mints in the token, as a new handle
+///
+/// The proofs handle is independent of the token and freed with
+/// [cdk_proofs_free].
+///
+/// # Safety
+///
+/// `out` is null or valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn cdk_token_proofs(token: CdkToken, out: *mut CdkProofs) -> i32 {
+    ffi_call(|| {
+        if out.is_null() {
+            return Err(FfiError::new(CDK_ERROR_NULL_POINTER, "Null output pointer"));
+        }
+
+        let proofs: Proofs = with_handle(&TOKENS, token, |token| {
+            Ok(token
+                .token
+                .iter()
+                .flat_map(|t| t.proofs.iter().cloned())
+                .collect())
+        })?;
+
+        write_out(out, insert(&PROOFS, proofs))
+    })
+}
+
+/// Verify the DLEQ proof of every proof against the keys of a keyset
+///
+/// `keyset_json` is a keyset as in `/v1/keys/{id}`, either the
+/// `{"id", "unit", "keys"}` object or the `{"keysets": [...]}` response.
+/// Fails with `CDK_ERROR_DLEQ` if any proof is of another keyset, has no DLEQ
+/// proof, or its DLEQ proof is invalid.
+///
+/// # Safety
+///
+/// `keyset_json` is null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn cdk_proofs_verify_dleq(
+    proofs: CdkProofs,
+    keyset_json: *const c_char,
+) -> i32 {
+    ffi_call(|| {
+        let keyset_json = read_str(keyset_json)?;
+        let keysets = match serde_json::from_str::<KeySet>(keyset_json) {
+            Ok(keyset) => vec![keyset],
+            Err(_) => {
+                serde_json::from_str::<KeysResponse>(keyset_json)
+                    .map_err(|err| FfiError::new(CDK_ERROR_INVALID_KEYSET, err))?
+                    .keysets
+            }
+        };
+
+        with_handle(&PROOFS, proofs, |proofs| {
+            for proof in proofs {
+                let keyset = keysets
+                    .iter()
+                    .find(|k| k.id == proof.keyset_id)
+                    .ok_or_else(|| {
+                        FfiError::new(
+                            CDK_ERROR_DLEQ,
+                            format!("No keys for keyset {}", proof.keyset_id),
+                        )
+                    })?;
+
+                let mint_pubkey = keyset.keys.amount_key(proof.amount).ok_or_else(|| {
+                    FfiError::new(
+                        CDK_ERROR_DLEQ,
+                        format!("No key for amount {} in keyset {}", proof.amount, keyset.id),
+                    )
+                })?;
+
+                proof
+                    .verify_dleq(mint_pubkey)
+                    .map_err(|err| FfiError::new(CDK_ERROR_DLEQ, err))?;
+            }
+
+            Ok(())
+        })
+    })
+}
+
+/// Total amount of the proofs
+///
+/// # Safety
+///
+/// `out` is null or valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn cdk_proofs_amount(proofs: CdkProofs, out: *mut u64) -> i32 {
+    ffi_call(|| {
+        let amount = with_handle(&PROOFS, proofs, |proofs| {
+            Ok(proofs.iter().map(|p| p.amount).sum::<crate::Amount>())
+        })?;
+        write_out(out, amount.into())
+    })
+}
+
+/// Free a token handle
+///
+/// Freeing a handle twice returns `CDK_ERROR_INVALID_HANDLE`.
+#[no_mangle]
+pub extern "C" fn cdk_token_free(token: CdkToken) -> i32 {
+    ffi_call(|| match lock(&TOKENS).remove(&token) {
+        Some(_) => Ok(()),
+        None => Err(FfiError::new(CDK_ERROR_INVALID_HANDLE, "Invalid handle")),
+    })
+}
+
+/// Free a proofs handle
+///
+/// Freeing a handle twice returns `CDK_ERROR_INVALID_HANDLE`.
+#[no_mangle]
+pub extern "C" fn cdk_proofs_free(proofs: CdkProofs) -> i32 {
+    ffi_call(|| match lock(&PROOFS).remove(&proofs) {
+        Some(_) => Ok(()),
+        None => Err(FfiError::new(CDK_ERROR_INVALID_HANDLE, "Invalid handle")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+
+    use super::*;
+    use crate::nuts::{CurrencyUnit, Proof};
+    use crate::url::UncheckedUrl;
+
+    // Proof of `nut12::tests::test_proof_dleq`, signed with the key 1
+    const PROOF: &str = r#"{"amount": 1,"id": "00882760bfa2eb41","secret": "daf4dd00a2b68a0858a80450f52c8a7d2ccf87d375e43e216e0c571f089f63e9","C": "024369d2d22a80ecf78f3937da9d5f30c1b9f74f0c32684d583cca0fa6a61cdcfc","dleq": {"e": "b31e58ac6527f34975ffab13e70a48b6d2b0d35abc4b03f0151f09ee1a9763d4","s": "8fbae004c59e754d71df67e392b6ae4e29293113ddc2ec86592a0431d16306d8","r": "a6d13fcd7a18442e6076f5e1e7c887ad5de40a019824bdfa9fe740d302e8d861"}}"#;
+    const KEYSET: &str = r#"{"id":"00882760bfa2eb41","unit":"sat","keys":{"1":"0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"}}"#;
+
+    fn token(proof: Proof) -> CString {
+        let token = Token::new(
+            UncheckedUrl::from_str("https://mint.example.com").unwrap(),
+            vec![proof],
+            None,
+            Some(CurrencyUnit::Sat),
+        )
+        .unwrap();
+
+        CString::new(token.to_string()).unwrap()
+    }
+
+    fn parse(token: &CStr) -> CdkToken {
+        let mut handle = 0;
+        assert_eq!(
+            unsafe { cdk_token_parse(token.as_ptr(), &mut handle) },
+            CDK_OK
+        );
+        handle
+    }
+
+    fn last_error() -> String {
+        let message = cdk_last_error_message();
+        assert!(!message.is_null());
+        unsafe { CStr::from_ptr(message) }
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_token_and_proofs() {
+        let token = parse(&token(serde_json::from_str(PROOF).unwrap()));
+
+        let mut amount = 0;
+        assert_eq!(unsafe { cdk_token_amount(token, &mut amount) }, CDK_OK);
+        assert_eq!(amount, 1);
+
+        let mut len = 0;
+        assert_eq!(
+            unsafe { cdk_token_mint_url(token, ptr::null_mut(), 0, &mut len) },
+            CDK_ERROR_BUFFER_TOO_SMALL
+        );
+        let mut buf = vec![0 as c_char; len + 1];
+        assert_eq!(
+            unsafe { cdk_token_mint_url(token, buf.as_mut_ptr(), buf.len(), &mut len) },
+            CDK_OK
+        );
+        let mint_url = unsafe { CStr::from_ptr(buf.as_ptr()) };
+        assert_eq!(mint_url.to_str().unwrap(), "https://mint.example.com");
+
+        let mut proofs = 0;
+        assert_eq!(unsafe { cdk_token_proofs(token, &mut proofs) }, CDK_OK);
+        assert_eq!(cdk_token_free(token), CDK_OK);
+
+        // Proofs outlive the token they came from
+        assert_eq!(unsafe { cdk_proofs_amount(proofs, &mut amount) }, CDK_OK);
+        assert_eq!(amount, 1);
+
+        let keyset = CString::new(KEYSET).unwrap();
+        assert_eq!(
+            unsafe { cdk_proofs_verify_dleq(proofs, keyset.as_ptr()) },
+            CDK_OK
+        );
+
+        let keys_response = CString::new(format!(r#"{{"keysets":[{}]}}"#, KEYSET)).unwrap();
+        assert_eq!(
+            unsafe { cdk_proofs_verify_dleq(proofs, keys_response.as_ptr()) },
+            CDK_OK
+        );
+
+        assert_eq!(cdk_proofs_free(proofs), CDK_OK);
+    }
+
+    #[test]
+    fn test_verify_dleq_failures() {
+        let mut proof: Proof = serde_json::from_str(PROOF).unwrap();
+        let keyset = CString::new(KEYSET).unwrap();
+
+        let proofs = insert(&PROOFS, vec![proof.clone()]);
+        // Key 2 instead of 1
+        let other_key = CString::new(KEYSET.replace(
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5",
+        ))
+        .unwrap();
+        assert_eq!(
+            unsafe { cdk_proofs_verify_dleq(proofs, other_key.as_ptr()) },
+            CDK_ERROR_DLEQ
+        );
+
+        let other_keyset =
+            CString::new(KEYSET.replace("00882760bfa2eb41", "009a1f293253e41e")).unwrap();
+        assert_eq!(
+            unsafe { cdk_proofs_verify_dleq(proofs, other_keyset.as_ptr()) },
+            CDK_ERROR_DLEQ
+        );
+        assert!(last_error().contains("00882760bfa2eb41"));
+
+        let not_json = CString::new("{").unwrap();
+        assert_eq!(
+            unsafe { cdk_proofs_verify_dleq(proofs, not_json.as_ptr()) },
+            CDK_ERROR_INVALID_KEYSET
+        );
+        cdk_proofs_free(proofs);
+
+        proof.dleq = None;
+        let proofs = insert(&PROOFS, vec![proof]);
+        assert_eq!(
+            unsafe { cdk_proofs_verify_dleq(proofs, keyset.as_ptr()) },
+            CDK_ERROR_DLEQ
+        );
+        cdk_proofs_free(proofs);
+    }
+
+    #[test]
+    fn test_double_free_and_stale_handles() {
+        let token = parse(&token(serde_json::from_str(PROOF).unwrap()));
+        let mut proofs = 0;
+        assert_eq!(unsafe { cdk_token_proofs(token, &mut proofs) }, CDK_OK);
+
+        assert_eq!(cdk_token_free(token), CDK_OK);
+        assert_eq!(cdk_token_free(token), CDK_ERROR_INVALID_HANDLE);
+        assert_eq!(last_error(), "Invalid handle");
+
+        let mut amount = 0;
+        assert_eq!(
+            unsafe { cdk_token_amount(token, &mut amount) },
+            CDK_ERROR_INVALID_HANDLE
+        );
+
+        // Handles of one kind are not valid as the other
+        assert_eq!(cdk_token_free(proofs), CDK_ERROR_INVALID_HANDLE);
+        assert_eq!(
+            unsafe { cdk_proofs_amount(token, &mut amount) },
+            CDK_ERROR_INVALID_HANDLE
+        );
+
+        assert_eq!(cdk_proofs_free(proofs), CDK_OK);
+        assert_eq!(cdk_proofs_free(proofs), CDK_ERROR_INVALID_HANDLE);
+        assert_eq!(cdk_proofs_free(0), CDK_ERROR_INVALID_HANDLE);
+    }
+
+    #[test]
+    fn test_null_inputs() {
+        let mut handle = 0;
+        assert_eq!(
+            unsafe { cdk_token_parse(ptr::null(), &mut handle) },
+            CDK_ERROR_NULL_POINTER
+        );
+
+        let token_str = token(serde_json::from_str(PROOF).unwrap());
+        assert_eq!(
+            unsafe { cdk_token_parse(token_str.as_ptr(), ptr::null_mut()) },
+            CDK_ERROR_NULL_POINTER
+        );
+
+        let token = parse(&token_str);
+        assert_eq!(
+            unsafe { cdk_token_amount(token, ptr::null_mut()) },
+            CDK_ERROR_NULL_POINTER
+        );
+        assert_eq!(
+            unsafe { cdk_token_mint_url(token, ptr::null_mut(), 64, ptr::null_mut()) },
+            CDK_ERROR_NULL_POINTER
+        );
+        let mut len = 0;
+        assert_eq!(
+            unsafe { cdk_token_mint_url(token, ptr::null_mut(), 64, &mut len) },
+            CDK_ERROR_NULL_POINTER
+        );
+        assert_eq!(
+            unsafe { cdk_token_proofs(token, ptr::null_mut()) },
+            CDK_ERROR_NULL_POINTER
+        );
+
+        let mut proofs = 0;
+        assert_eq!(unsafe { cdk_token_proofs(token, &mut proofs) }, CDK_OK);
+        assert_eq!(
+            unsafe { cdk_proofs_verify_dleq(proofs, ptr::null()) },
+            CDK_ERROR_NULL_POINTER
+        );
+
+        cdk_token_free(token);
+        cdk_proofs_free(proofs);
+    }
+
+    #[test]
+    fn test_invalid_strings() {
+        let mut handle = 0;
+
+        let invalid_utf8 = CString::new(vec![b'c', b'a', 0xff, 0xfe]).unwrap();
+        assert_eq!(
+            unsafe { cdk_token_parse(invalid_utf8.as_ptr(), &mut handle) },
+            CDK_ERROR_INVALID_UTF8
+        );
+
+        for invalid in ["", "cashuA", "cashuAnot-base64", "cashuBo2F0gA"] {
+            let invalid = CString::new(invalid).unwrap();
+            assert_eq!(
+                unsafe { cdk_token_parse(invalid.as_ptr(), &mut handle) },
+                CDK_ERROR_INVALID_TOKEN
+            );
+            assert!(!last_error().is_empty());
+        }
+        assert_eq!(handle, 0);
+
+        let proofs = insert(&PROOFS, vec![]);
+        assert_eq!(
+            unsafe { cdk_proofs_verify_dleq(proofs, invalid_utf8.as_ptr()) },
+            CDK_ERROR_INVALID_UTF8
+        );
+        cdk_proofs_free(proofs);
+    }
+
+    #[test]
+    fn test_header_declares_every_function() {
+        let header = include_str!("../include/cdk.h");
+        let source = include_str!("ffi.rs");
+
+        let functions: Vec<&str> = source
+            .split("extern \"C\" fn ")
+            .skip(1)
+            .filter_map(|rest| rest.split('(').next())
+            .collect();
+        assert!(functions.len() >= 9);
+
+        for function in functions {
+            assert!(
+                header.contains(&format!(" {}(", function)),
+                "{} missing from include/cdk.h",
+                function
+            );
+        }
+
+        for constant in source
+            .lines()
+            .filter_map(|l| l.strip_prefix("pub const "))
+            .filter_map(|l| l.split(':').next())
+        {
+            assert!(
+                header.contains(&format!("#define {} ", constant)),
+                "{} missing from include/cdk.h",
+                constant
+            );
+        }
+    }
+}
@@ -1,4 +1,8 @@
+#![cfg_attr(bench, feature(test))]
+
 extern crate core;
+#[cfg(bench)]
+extern crate test;
 
 #[cfg(any(feature = "mint", feature = "wallet"))]
 pub use bip39::Mnemonic;
@@ -6,25 +10,53 @@ pub use bitcoin::hashes::sha256::Hash as Sha256;
 pub use bitcoin::secp256k1;
 pub use lightning_invoice::{self, Bolt11Invoice};
 
+#[cfg(feature = "mint")]
+pub mod accounting;
 pub mod amount;
 pub mod cdk_database;
 #[cfg(feature = "wallet")]
 pub mod client;
+#[cfg(all(feature = "cln", unix))]
+pub mod cln;
 pub mod dhke;
 pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "mint")]
+pub mod lightning;
+#[cfg(feature = "lnbits")]
+pub mod lnbits;
+#[cfg(feature = "lnd")]
+pub mod lnd;
 #[cfg(feature = "mint")]
 pub mod mint;
+#[cfg(feature = "uniffi")]
+pub mod mobile;
+#[cfg(feature = "nostr")]
+pub mod nostr;
 pub mod nuts;
+#[cfg(feature = "router")]
+pub mod router;
 pub mod secret;
+pub mod serde_utils;
+#[cfg(feature = "mint")]
+pub mod signatory;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
 pub mod types;
 pub mod url;
 pub mod util;
 #[cfg(feature = "wallet")]
 pub mod wallet;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
-pub use self::amount::Amount;
+pub use self::amount::{Amount, SplitTarget, SwapSplit, UnitAmount};
 #[cfg(feature = "wallet")]
 pub use self::client::HttpClient;
 pub use self::util::SECP256K1;
 
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!();
+
 pub type Result<T, E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
@@ -0,0 +1,122 @@
+//! Lightning backend of a mint
+
+use async_trait::async_trait;
+use thiserror::Error;
+
+use crate::nuts::CurrencyUnit;
+use crate::types::{InvoiceDescription, InvoiceStatus};
+use crate::{Amount, Bolt11Invoice, Sha256};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// Backend does not implement the call
+    #[error("Lightning backend does not support `{0}`")]
+    Unsupported(&'static str),
+    /// Payment definitely failed, nothing was paid
+    #[error("Payment failed: {0}")]
+    PaymentFailed(String),
+    /// Backend error, the state of a payment is unknown
+    #[error(transparent)]
+    Backend(Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// Invoice paid by [LnBackend::pay_invoice]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Payment {
+    pub preimage: String,
+    /// Amount paid including routing fees
+    pub total_spent_msat: u64,
+}
+
+/// State of an outgoing payment, see [LnBackend::payment_status]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaymentStatus {
+    /// Payment is still in flight
+    Pending,
+    Paid(Payment),
+    /// Every attempt failed, nothing was paid
+    Failed,
+}
+
+/// Lightning node the mint pays invoices through
+#[async_trait]
+pub trait LnBackend: Send + Sync {
+    /// Routing fee in sats to pay `amount_msat` of `bolt11`, from a probe or
+    /// a route query
+    ///
+    /// `None` when no estimate can be made, the mint then falls back to its
+    /// [FeeReserve](crate::mint::FeeReserve) policy.
+    async fn estimate_fee(&self, _bolt11: &Bolt11Invoice, _amount_msat: u64) -> Option<Amount> {
+        None
+    }
+
+    /// Invoice for a mint quote of `amount` in `unit`, expiring at the unix
+    /// time `expiry`
+    async fn create_invoice(
+        &self,
+        _amount: Amount,
+        _unit: &CurrencyUnit,
+        _description: Option<InvoiceDescription>,
+        _expiry: u64,
+    ) -> Result<Bolt11Invoice, Error> {
+        Err(Error::Unsupported("create_invoice"))
+    }
+
+    /// Whether [LnBackend::create_invoice] can commit to an
+    /// [InvoiceDescription::Hash]
+    fn supports_description_hash(&self) -> bool {
+        false
+    }
+
+    /// Pay `amount_msat` of `bolt11` spending at most `max_fee` sats in
+    /// routing fees
+    ///
+    /// Returns [Error::PaymentFailed] only when the payment can no longer
+    /// succeed; any other error leaves the melt pending until it is resolved
+    /// with [Mint::resolve_pending_melt](crate::mint::Mint::resolve_pending_melt).
+    async fn pay_invoice(
+        &self,
+        _bolt11: &Bolt11Invoice,
+        _amount_msat: u64,
+        _max_fee: Amount,
+    ) -> Result<Payment, Error> {
+        Err(Error::Unsupported("pay_invoice"))
+    }
+
+    /// State of the invoice with `payment_hash` created by
+    /// [LnBackend::create_invoice]
+    async fn invoice_status(&self, _payment_hash: &Sha256) -> Result<InvoiceStatus, Error> {
+        Err(Error::Unsupported("invoice_status"))
+    }
+
+    /// Reusable BOLT12 offer for a mint quote in `unit`, of `amount` per
+    /// payment or of any amount the payer chooses for [None]
+    async fn create_offer(
+        &self,
+        _amount: Option<Amount>,
+        _unit: &CurrencyUnit,
+        _description: Option<String>,
+    ) -> Result<String, Error> {
+        Err(Error::Unsupported("create_offer"))
+    }
+
+    /// Total received in `unit` over every payment of `offer` created by
+    /// [LnBackend::create_offer]
+    ///
+    /// The total never decreases, so a repeated report credits nothing twice.
+    async fn offer_payment_received(
+        &self,
+        _offer: &str,
+        _unit: &CurrencyUnit,
+    ) -> Result<Amount, Error> {
+        Err(Error::Unsupported("offer_payment_received"))
+    }
+
+    /// State of the payment of `payment_hash` started by
+    /// [LnBackend::pay_invoice]
+    ///
+    /// A hash the backend never tried to pay is [PaymentStatus::Failed].
+    async fn payment_status(&self, _payment_hash: &Sha256) -> Result<PaymentStatus, Error> {
+        Err(Error::Unsupported("payment_status"))
+    }
+}
@@ -0,0 +1,883 @@
+//! LNbits backend
+//!
+//! Talks to the HTTP API of an LNbits wallet with its admin and invoice
+//! keys. Mint quotes are settled by polling [LnBackend::invoice_status], or
+//! from the webhooks LNbits sends through [Lnbits::handle_webhook]. Enabled
+//! with the `lnbits` feature.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bitcoin::hashes::Hash;
+use reqwest::{Client, Method, RequestBuilder};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use thiserror::Error;
+use url::Url;
+
+use crate::lightning::{self, LnBackend, Payment, PaymentStatus};
+use crate::nuts::CurrencyUnit;
+use crate::types::{InvoiceDescription, InvoiceStatus};
+use crate::util::unix_time;
+use crate::{Amount, Bolt11Invoice, Sha256};
+
+/// Status of LNbits for a payment hash it does not know
+const NOT_FOUND: u16 = 404;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Reqwest(#[from] reqwest::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Url(#[from] url::ParseError),
+    #[error("Invalid API key: {0}")]
+    Key(String),
+    /// Error reported by LNbits, with an error status or in a 200 body
+    #[error("LNbits error {status}: {message}")]
+    Api { status: u16, message: String },
+    /// Payment was sent but is not settled yet
+    #[error("Payment still pending")]
+    Pending,
+}
+
+impl From<Error> for lightning::Error {
+    fn from(err: Error) -> Self {
+        Self::Backend(Box::new(err))
+    }
+}
+
+/// Connection settings of an [Lnbits] backend
+#[derive(Debug, Clone)]
+pub struct LnbitsConfig {
+    /// Root of the instance, without `/api/v1`
+    pub url: Url,
+    /// Admin key of the wallet, used to pay invoices
+    pub admin_key: String,
+    /// Invoice/read key of the wallet, used for everything else
+    pub invoice_key: String,
+    /// URL LNbits calls once an invoice is paid, see [Lnbits::handle_webhook]
+    pub webhook_url: Option<Url>,
+    /// `LNBITS_RESERVE_FEE_MIN` of the instance, in msat
+    pub reserve_fee_min_msat: u64,
+    /// `LNBITS_RESERVE_FEE_PERCENT` of the instance
+    pub reserve_fee_percent: f64,
+    /// Timeout of a call, other than paying
+    pub rpc_timeout: Duration,
+    /// How long to wait for LNbits to answer a payment
+    pub pay_timeout: Duration,
+}
+
+impl LnbitsConfig {
+    /// Settings with the fee reserve of a default LNbits install
+    pub fn new(url: Url, admin_key: String, invoice_key: String) -> Self {
+        Self {
+            url,
+            admin_key,
+            invoice_key,
+            webhook_url: None,
+            reserve_fee_min_msat: 2000,
+            reserve_fee_percent: 1.0,
+            rpc_timeout: Duration::from_secs(30),
+            pay_timeout: Duration::from_secs(90),
+        }
+    }
+}
+
+/// [LnBackend] of an LNbits wallet
+#[derive(Debug, Clone)]
+pub struct Lnbits {
+    config: LnbitsConfig,
+    client: Client,
+}
+
+#[derive(Deserialize)]
+struct CreateInvoiceResponse {
+    /// `bolt11` on newer instances
+    #[serde(alias = "bolt11")]
+    payment_request: String,
+}
+
+#[derive(Deserialize)]
+struct PayResponse {
+    #[allow(dead_code)]
+    payment_hash: String,
+}
+
+/// Payment of a wallet, incoming or outgoing
+#[derive(Deserialize)]
+struct PaymentResponse {
+    paid: bool,
+    #[serde(default)]
+    preimage: Option<String>,
+    #[serde(default)]
+    details: Option<PaymentDetails>,
+}
+
+#[derive(Deserialize)]
+struct PaymentDetails {
+    /// `pending`, `success` or `failed`, missing on older instances
+    #[serde(default)]
+    status: Option<String>,
+    #[serde(default)]
+    pending: Option<bool>,
+    /// Negative for outgoing payments
+    #[serde(default)]
+    amount: i64,
+    #[serde(default)]
+    fee: i64,
+    #[serde(default)]
+    bolt11: Option<String>,
+}
+
+impl PaymentResponse {
+    fn details(&self) -> &PaymentDetails {
+        static EMPTY: PaymentDetails = PaymentDetails {
+            status: None,
+            pending: None,
+            amount: 0,
+            fee: 0,
+            bolt11: None,
+        };
+        self.details.as_ref().unwrap_or(&EMPTY)
+    }
+
+    fn failed(&self) -> bool {
+        let details = self.details();
+        match details.status.as_deref() {
+            Some(status) => status == "failed",
+            None => !self.paid && details.pending == Some(false),
+        }
+    }
+
+    fn incoming(&self) -> bool {
+        self.details().amount > 0
+    }
+
+    fn expired(&self) -> bool {
+        self.details()
+            .bolt11
+            .as_deref()
+            .and_then(|bolt11| bolt11.parse::<Bolt11Invoice>().ok())
+            .is_some_and(|invoice| invoice.is_expired())
+    }
+
+    fn payment_status(&self) -> PaymentStatus {
+        if self.paid {
+            let details = self.details();
+            PaymentStatus::Paid(Payment {
+                preimage: self.preimage.clone().unwrap_or_default(),
+                // The sign of the fee differs between versions
+                total_spent_msat: details.amount.unsigned_abs() + details.fee.unsigned_abs(),
+            })
+        } else if self.failed() {
+            PaymentStatus::Failed
+        } else {
+            PaymentStatus::Pending
+        }
+    }
+}
+
+/// Parse a response body as `R`
+///
+/// LNbits reports some failures with a 200 and an error in the body, so a
+/// body carrying an error message instead of the fields of `R` is an
+/// [Error::Api] whatever its status.
+fn parse<R>(status: u16, body: &[u8]) -> Result<R, Error>
+where
+    R: DeserializeOwned,
+{
+    let value: Value = match serde_json::from_slice(body) {
+        Ok(value) => value,
+        // Error page of a proxy in front of the instance
+        Err(_) => {
+            return Err(Error::Api {
+                status,
+                message: String::from_utf8_lossy(body)
+                    .trim()
+                    .chars()
+                    .take(256)
+                    .collect(),
+            })
+        }
+    };
+
+    if (200..300).contains(&status) {
+        match (R::deserialize(&value), error_message(&value)) {
+            (Ok(response), _) => return Ok(response),
+            (Err(err), None) => return Err(err.into()),
+            (Err(_), Some(_)) => (),
+        }
+    }
+
+    Err(Error::Api {
+        status,
+        message: error_message(&value).unwrap_or_else(|| value.to_string()),
+    })
+}
+
+/// Message of an error body, `detail` as FastAPI puts it or `error` or
+/// `message` of some extensions and proxies
+fn error_message(value: &Value) -> Option<String> {
+    ["detail", "error", "message"]
+        .iter()
+        .find_map(|key| match value.get(key)? {
+            Value::Null => None,
+            Value::String(message) => Some(message.clone()),
+            // Validation errors are a list of `{"loc": .., "msg": ..}`
+            Value::Array(errors) => Some(
+                errors
+                    .iter()
+                    .filter_map(|error| error.get("msg")?.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+            other => Some(other.to_string()),
+        })
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl Lnbits {
+    pub fn new(config: LnbitsConfig) -> Result<Self, Error> {
+        for key in [&config.admin_key, &config.invoice_key] {
+            if key.is_empty() || !key.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(Error::Key("not hex".to_string()));
+            }
+        }
+
+        Ok(Self {
+            config,
+            client: Client::builder().build()?,
+        })
+    }
+
+    fn request(&self, method: Method, path: &str, key: &str) -> Result<RequestBuilder, Error> {
+        Ok(self
+            .client
+            .request(method, self.config.url.join(path)?)
+            .header("X-Api-Key", key))
+    }
+
+    async fn call<R>(&self, request: RequestBuilder, timeout: Duration) -> Result<R, Error>
+    where
+        R: DeserializeOwned,
+    {
+        let response = request.timeout(timeout).send().await?;
+        let status = response.status().as_u16();
+        let body = response.bytes().await?;
+
+        parse(status, &body)
+    }
+
+    async fn payment(&self, payment_hash: &Sha256) -> Result<PaymentResponse, Error> {
+        let path = format!(
+            "api/v1/payments/{}",
+            hex_encode(payment_hash.as_byte_array())
+        );
+        let request = self.request(Method::GET, &path, &self.config.invoice_key)?;
+
+        self.call(request, self.config.rpc_timeout).await
+    }
+
+    /// Fee LNbits reserves from the balance to pay `amount_msat`, the most
+    /// it lets a payment spend in routing fees
+    fn fee_reserve(&self, amount_msat: u64) -> Amount {
+        let percent = (amount_msat as f64 * self.config.reserve_fee_percent / 100.0) as u64;
+        // What LNbits may take, rounded up so the reserve always covers it
+        Amount::from_msat_ceil(percent.max(self.config.reserve_fee_min_msat))
+    }
+
+    /// Payment hash of the invoice a webhook of LNbits reports paid
+    ///
+    /// Webhooks are not authenticated, so the invoice is looked up before it
+    /// is trusted; `None` if it is not a paid incoming payment. The quote of
+    /// that invoice, found with
+    /// [Mint::quote_by_payment_hash](crate::mint::Mint::quote_by_payment_hash),
+    /// is then settled with
+    /// [Mint::sync_mint_quote](crate::mint::Mint::sync_mint_quote).
+    pub async fn handle_webhook(&self, body: &[u8]) -> Result<Option<Sha256>, Error> {
+        #[derive(Deserialize)]
+        struct Webhook {
+            payment_hash: Sha256,
+        }
+
+        let webhook: Webhook = serde_json::from_slice(body)?;
+        let payment = self.payment(&webhook.payment_hash).await?;
+
+        Ok((payment.paid && payment.incoming()).then_some(webhook.payment_hash))
+    }
+}
+
+#[async_trait]
+impl LnBackend for Lnbits {
+    async fn estimate_fee(&self, _bolt11: &Bolt11Invoice, amount_msat: u64) -> Option<Amount> {
+        Some(self.fee_reserve(amount_msat))
+    }
+
+    async fn create_invoice(
+        &self,
+        amount: Amount,
+        unit: &CurrencyUnit,
+        description: Option<InvoiceDescription>,
+        expiry: u64,
+    ) -> Result<Bolt11Invoice, lightning::Error> {
+        let amount_sat = match unit {
+            CurrencyUnit::Sat => u64::from(amount),
+            // LNbits invoices whole sats, only exact msat amounts convert
+            CurrencyUnit::Msat if u64::from(amount) % Amount::MSAT_PER_SAT == 0 => {
+                Amount::from_msat_floor(amount.into()).into()
+            }
+            _ => return Err(lightning::Error::Unsupported("create_invoice in this unit")),
+        };
+
+        let mut body = json!({
+            "out": false,
+            "amount": amount_sat,
+            "unit": "sat",
+            "expiry": expiry.saturating_sub(unix_time()),
+        });
+        match description {
+            Some(InvoiceDescription::Text(description)) => body["memo"] = description.into(),
+            Some(InvoiceDescription::Hash(hash)) => {
+                body["description_hash"] = crate::util::hex::encode(hash).into()
+            }
+            None => body["memo"] = "".into(),
+        }
+        if let Some(webhook_url) = &self.config.webhook_url {
+            body["webhook"] = webhook_url.as_str().into();
+        }
+
+        let request = self
+            .request(Method::POST, "api/v1/payments", &self.config.invoice_key)?
+            .json(&body);
+        let response: CreateInvoiceResponse = self.call(request, self.config.rpc_timeout).await?;
+
+        response
+            .payment_request
+            .parse()
+            .map_err(|err| lightning::Error::Backend(Box::new(err)))
+    }
+
+    /// Pay through the wallet of the admin key
+    ///
+    /// LNbits takes no fee limit but caps routing fees to the fee it
+    /// reserves, so a payment whose reserve is over `max_fee` is refused
+    /// before it is sent.
+    fn supports_description_hash(&self) -> bool {
+        true
+    }
+
+    async fn pay_invoice(
+        &self,
+        bolt11: &Bolt11Invoice,
+        amount_msat: u64,
+        max_fee: Amount,
+    ) -> Result<Payment, lightning::Error> {
+        match bolt11.amount_milli_satoshis() {
+            Some(invoice_msat) if invoice_msat == amount_msat => (),
+            Some(_) => {
+                return Err(lightning::Error::Unsupported(
+                    "pay_invoice of part of an invoice",
+                ))
+            }
+            None => {
+                return Err(lightning::Error::Unsupported(
+                    "pay_invoice without an amount",
+                ))
+            }
+        }
+
+        let fee_reserve = self.fee_reserve(amount_msat);
+        if fee_reserve > max_fee {
+            return Err(lightning::Error::PaymentFailed(format!(
+                "LNbits reserves {} sat of fee, more than {}",
+                fee_reserve, max_fee
+            )));
+        }
+
+        let request = self
+            .request(Method::POST, "api/v1/payments", &self.config.admin_key)?
+            .json(&json!({ "out": true, "bolt11": bolt11.to_string() }));
+        let sent = match self
+            .call::<PayResponse>(request, self.config.pay_timeout)
+            .await
+        {
+            Ok(_) => Ok(()),
+            // Refused, or failed while LNbits waited for it
+            Err(Error::Api { status, message }) => Err(Error::Api { status, message }),
+            Err(err) => return Err(err.into()),
+        };
+
+        match (self.payment_status(bolt11.payment_hash()).await?, sent) {
+            (PaymentStatus::Paid(payment), _) => Ok(payment),
+            (PaymentStatus::Failed, Err(Error::Api { message, .. })) => {
+                Err(lightning::Error::PaymentFailed(message))
+            }
+            (PaymentStatus::Failed, _) => Err(lightning::Error::PaymentFailed(
+                "LNbits reports the payment failed".to_string(),
+            )),
+            (PaymentStatus::Pending, Err(err)) => Err(err.into()),
+            (PaymentStatus::Pending, Ok(())) => Err(Error::Pending.into()),
+        }
+    }
+
+    async fn invoice_status(
+        &self,
+        payment_hash: &Sha256,
+    ) -> Result<InvoiceStatus, lightning::Error> {
+        let payment = self.payment(payment_hash).await?;
+
+        Ok(if payment.paid {
+            InvoiceStatus::Paid
+        } else if payment.failed() || payment.expired() {
+            InvoiceStatus::Expired
+        } else {
+            InvoiceStatus::Unpaid
+        })
+    }
+
+    async fn payment_status(
+        &self,
+        payment_hash: &Sha256,
+    ) -> Result<PaymentStatus, lightning::Error> {
+        match self.payment(payment_hash).await {
+            // An invoice of the wallet was never paid from it
+            Ok(payment) if payment.incoming() => Ok(PaymentStatus::Failed),
+            Ok(payment) => Ok(payment.payment_status()),
+            Err(Error::Api {
+                status: NOT_FOUND, ..
+            }) => Ok(PaymentStatus::Failed),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    const INVOICE: &str = "lnbc2500u1pj48ugqdq8w3jhxaqpp5qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqsp59g4z52329g4z52329g4z52329g4z52329g4z52329g4z52329g4q9qrsgqcqzysd6exwz2yjprkglq820pffp8wcu97sxuzcgx7raensa83dnc4kpmhkuqevwf6hmv6a3mc9aegn89jv7a8e9wggdxtfsjwrur7n6sx4rsq7rvjx3";
+
+    const ADMIN_KEY: &str = "ad11c0ffee";
+    const INVOICE_KEY: &str = "1d0c0ffee5";
+
+    /// Request received by [serve]
+    #[derive(Debug, Clone)]
+    struct Recorded {
+        method: String,
+        path: String,
+        api_key: String,
+        body: Value,
+    }
+
+    /// Answer requests in order with `(status, body)`
+    fn serve(responses: Vec<(u16, String)>) -> (Lnbits, Arc<Mutex<Vec<Recorded>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+
+        let calls = Arc::clone(&recorded);
+        std::thread::spawn(move || {
+            for ((status, body), stream) in responses.into_iter().zip(listener.incoming()) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut parts = request_line.split_whitespace();
+                let method = parts.next().unwrap().to_string();
+                let path = parts.next().unwrap().to_string();
+
+                let mut content_length = 0;
+                let mut api_key = String::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let lower = line.to_lowercase();
+                    if let Some(length) = lower.strip_prefix("content-length:") {
+                        content_length = length.trim().parse().unwrap();
+                    }
+                    if let Some(value) = lower.strip_prefix("x-api-key:") {
+                        api_key = value.trim().to_string();
+                    }
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                }
+                let mut request_body = vec![0; content_length];
+                reader.read_exact(&mut request_body).unwrap();
+
+                calls.lock().unwrap().push(Recorded {
+                    method,
+                    path,
+                    api_key,
+                    body: serde_json::from_slice(&request_body).unwrap_or(Value::Null),
+                });
+
+                write!(
+                    stream,
+                    "HTTP/1.1 {status} OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+            }
+        });
+
+        let mut config = LnbitsConfig::new(url, ADMIN_KEY.to_string(), INVOICE_KEY.to_string());
+        config.webhook_url = Some(Url::parse("https://mint.example/lnbits").unwrap());
+        config.rpc_timeout = Duration::from_secs(5);
+        (Lnbits::new(config).unwrap(), recorded)
+    }
+
+    /// Payment of `GET /api/v1/payments/{payment_hash}`, outgoing for a
+    /// negative `amount`
+    fn payment(paid: bool, status: &str, amount: i64, bolt11: Option<&str>) -> String {
+        json!({
+            "paid": paid,
+            "preimage": "0000000000000000000000000000000000000000000000000000000000000000",
+            "details": {
+                "checking_id": "0000000000000000000000000000000000000000000000000000000000000000",
+                "pending": status == "pending",
+                "amount": amount,
+                "fee": if amount < 0 { -1500 } else { 0 },
+                "memo": "",
+                "time": 1700000000,
+                "bolt11": bolt11,
+                "payment_hash": "0000000000000000000000000000000000000000000000000000000000000000",
+                "expiry": 1700000600.0,
+                "extra": {},
+                "wallet_id": "4a3c1b2d",
+                "webhook": null,
+                "webhook_status": null,
+                "status": status,
+            }
+        })
+        .to_string()
+    }
+
+    fn detail(message: &str) -> String {
+        json!({ "detail": message }).to_string()
+    }
+
+    #[tokio::test]
+    async fn test_create_invoice_and_status() {
+        let (lnbits, calls) = serve(vec![
+            (
+                201,
+                json!({
+                    "payment_hash": "0000000000000000000000000000000000000000000000000000000000000000",
+                    "payment_request": INVOICE,
+                    "checking_id": "0000000000000000000000000000000000000000000000000000000000000000",
+                    "lnurl_response": null,
+                })
+                .to_string(),
+            ),
+            (200, payment(false, "pending", 250_000_000, None)),
+            (200, payment(true, "success", 250_000_000, Some(INVOICE))),
+            // Expired by its bolt11, or marked so
+            (200, payment(false, "pending", 250_000_000, Some(INVOICE))),
+            (200, payment(false, "failed", 250_000_000, None)),
+            (404, detail("Payment does not exist.")),
+        ]);
+
+        let invoice = lnbits
+            .create_invoice(
+                Amount::from(250_000),
+                &CurrencyUnit::Sat,
+                Some(InvoiceDescription::Text("coffee".to_string())),
+                unix_time() + 600,
+            )
+            .await
+            .unwrap();
+        assert_eq!(invoice.to_string(), INVOICE);
+
+        let hash = invoice.payment_hash();
+        for status in [
+            InvoiceStatus::Unpaid,
+            InvoiceStatus::Paid,
+            InvoiceStatus::Expired,
+            InvoiceStatus::Expired,
+        ] {
+            assert_eq!(lnbits.invoice_status(hash).await.unwrap(), status);
+        }
+        assert!(matches!(
+            lnbits.invoice_status(hash).await,
+            Err(lightning::Error::Backend(_))
+        ));
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls[0].method, "POST");
+        assert_eq!(calls[0].path, "/api/v1/payments");
+        assert_eq!(calls[0].api_key, INVOICE_KEY);
+        assert_eq!(calls[0].body["out"], false);
+        assert_eq!(calls[0].body["amount"], 250_000);
+        assert_eq!(calls[0].body["memo"], "coffee");
+        assert_eq!(calls[0].body["webhook"], "https://mint.example/lnbits");
+        assert_eq!(calls[1].method, "GET");
+        assert_eq!(
+            calls[1].path,
+            format!("/api/v1/payments/{}", hex_encode(hash.as_byte_array()))
+        );
+        assert_eq!(calls[1].api_key, INVOICE_KEY);
+    }
+
+    #[tokio::test]
+    async fn test_pay_invoice() {
+        let bolt11 = Bolt11Invoice::from_str(INVOICE).unwrap();
+        let sent = json!({
+            "payment_hash": "0000000000000000000000000000000000000000000000000000000000000000",
+            "checking_id": "0000000000000000000000000000000000000000000000000000000000000000",
+        })
+        .to_string();
+        let (lnbits, calls) = serve(vec![
+            // Paid
+            (201, sent.clone()),
+            (200, payment(true, "success", -250_000_000, Some(INVOICE))),
+            // Insufficient balance, with an error status and as a 200
+            (520, detail("Insufficient balance.")),
+            (404, detail("Payment does not exist.")),
+            (200, detail("Insufficient balance.")),
+            (404, detail("Payment does not exist.")),
+            // Failed while LNbits waited for it
+            (520, detail("Payment failed: no route")),
+            (200, payment(false, "failed", -250_000_000, Some(INVOICE))),
+            // Still in flight when LNbits answered
+            (201, sent),
+            (200, payment(false, "pending", -250_000_000, Some(INVOICE))),
+        ]);
+
+        let payment = lnbits
+            .pay_invoice(&bolt11, 250_000_000, Amount::from(2500))
+            .await
+            .unwrap();
+        assert_eq!(payment.total_spent_msat, 250_001_500);
+        assert_eq!(payment.preimage.len(), 64);
+
+        for reason in [
+            "Insufficient balance.",
+            "Insufficient balance.",
+            "Payment failed: no route",
+        ] {
+            assert!(matches!(
+                lnbits.pay_invoice(&bolt11, 250_000_000, Amount::from(2500)).await,
+                Err(lightning::Error::PaymentFailed(r)) if r == reason
+            ));
+        }
+        assert!(matches!(
+            lnbits
+                .pay_invoice(&bolt11, 250_000_000, Amount::from(2500))
+                .await,
+            Err(lightning::Error::Backend(_))
+        ));
+
+        // A reserve over the fee limit is refused without a request
+        assert!(matches!(
+            lnbits
+                .pay_invoice(&bolt11, 250_000_000, Amount::from(2499))
+                .await,
+            Err(lightning::Error::PaymentFailed(_))
+        ));
+        assert!(matches!(
+            lnbits.pay_invoice(&bolt11, 1000, Amount::from(2500)).await,
+            Err(lightning::Error::Unsupported(_))
+        ));
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 10);
+        assert_eq!(calls[0].method, "POST");
+        assert_eq!(calls[0].path, "/api/v1/payments");
+        assert_eq!(calls[0].api_key, ADMIN_KEY);
+        assert_eq!(calls[0].body["out"], true);
+        assert_eq!(calls[0].body["bolt11"], INVOICE);
+        assert_eq!(calls[1].api_key, INVOICE_KEY);
+    }
+
+    #[tokio::test]
+    async fn test_payment_status() {
+        let hash = *Bolt11Invoice::from_str(INVOICE).unwrap().payment_hash();
+        let (lnbits, _) = serve(vec![
+            (200, payment(false, "pending", -250_000_000, None)),
+            (200, payment(true, "success", -250_000_000, None)),
+            (200, payment(false, "failed", -250_000_000, None)),
+            // Older instances without a status
+            (
+                200,
+                json!({ "paid": false, "details": { "pending": false, "amount": -1000 } })
+                    .to_string(),
+            ),
+            // An invoice of the wallet
+            (200, payment(false, "pending", 250_000_000, None)),
+            (404, detail("Payment does not exist.")),
+            (200, json!({ "paid": false }).to_string()),
+            (502, "<html><body>Bad Gateway</body></html>".to_string()),
+            (200, detail("Wallet not found.")),
+        ]);
+
+        assert_eq!(
+            lnbits.payment_status(&hash).await.unwrap(),
+            PaymentStatus::Pending
+        );
+        assert!(matches!(
+            lnbits.payment_status(&hash).await.unwrap(),
+            PaymentStatus::Paid(Payment {
+                total_spent_msat: 250_001_500,
+                ..
+            })
+        ));
+        for _ in 0..4 {
+            assert_eq!(
+                lnbits.payment_status(&hash).await.unwrap(),
+                PaymentStatus::Failed
+            );
+        }
+        // Nothing tells it failed
+        assert_eq!(
+            lnbits.payment_status(&hash).await.unwrap(),
+            PaymentStatus::Pending
+        );
+
+        let err = lnbits.payment_status(&hash).await.unwrap_err();
+        assert!(err.to_string().contains("Bad Gateway"));
+        let err = lnbits.payment_status(&hash).await.unwrap_err();
+        assert!(err.to_string().contains("Wallet not found."));
+    }
+
+    #[tokio::test]
+    async fn test_handle_webhook() {
+        let webhook = json!({
+            "payment_hash": "0000000000000000000000000000000000000000000000000000000000000000",
+            "amount": 250_000_000,
+            "bolt11": INVOICE,
+        })
+        .to_string();
+        let (lnbits, calls) = serve(vec![
+            (200, payment(true, "success", 250_000_000, None)),
+            // Claimed paid, but not by LNbits
+            (200, payment(false, "pending", 250_000_000, None)),
+            (200, payment(true, "success", -250_000_000, None)),
+        ]);
+
+        assert_eq!(
+            lnbits.handle_webhook(webhook.as_bytes()).await.unwrap(),
+            Some(*Bolt11Invoice::from_str(INVOICE).unwrap().payment_hash())
+        );
+        for _ in 0..2 {
+            assert_eq!(
+                lnbits.handle_webhook(webhook.as_bytes()).await.unwrap(),
+                None
+            );
+        }
+        assert!(matches!(
+            lnbits.handle_webhook(b"not json").await,
+            Err(Error::Json(_))
+        ));
+        assert_eq!(calls.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_parse() {
+        #[derive(Debug, Deserialize)]
+        struct Response {
+            #[allow(dead_code)]
+            paid: bool,
+        }
+
+        assert!(parse::<Response>(200, br#"{"paid": true, "detail": null}"#).is_ok());
+        assert!(matches!(
+            parse::<Response>(200, br#"{"detail": "Invoice key required."}"#),
+            Err(Error::Api { status: 200, message }) if message == "Invoice key required."
+        ));
+        assert!(matches!(
+            parse::<Response>(
+                422,
+                br#"{"detail": [{"loc": ["body", "bolt11"], "msg": "field required"}]}"#
+            ),
+            Err(Error::Api { status: 422, message }) if message == "field required"
+        ));
+        assert!(matches!(
+            parse::<Response>(200, br#"{"unexpected": 1}"#),
+            Err(Error::Json(_))
+        ));
+        assert!(matches!(
+            parse::<Response>(401, br#"{"unexpected": 1}"#),
+            Err(Error::Api { status: 401, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_fee_reserve() {
+        let url = Url::parse("https://lnbits.example").unwrap();
+        assert!(matches!(
+            Lnbits::new(LnbitsConfig::new(
+                url.clone(),
+                "not hex".to_string(),
+                INVOICE_KEY.to_string()
+            )),
+            Err(Error::Key(_))
+        ));
+
+        let lnbits = Lnbits::new(LnbitsConfig::new(
+            url,
+            ADMIN_KEY.to_string(),
+            INVOICE_KEY.to_string(),
+        ))
+        .unwrap();
+        let bolt11 = Bolt11Invoice::from_str(INVOICE).unwrap();
+
+        // 1% of the amount, at least 2 sat
+        assert_eq!(
+            lnbits.estimate_fee(&bolt11, 250_000_000).await,
+            Some(Amount::from(2500))
+        );
+        assert_eq!(
+            lnbits.estimate_fee(&bolt11, 100_500).await,
+            Some(Amount::from(2))
+        );
+        assert_eq!(
+            lnbits.estimate_fee(&bolt11, 250_100).await,
+            Some(Amount::from(3))
+        );
+    }
+
+    /// Runs against the wallet at `LNBITS_URL` with `LNBITS_ADMIN_KEY` and
+    /// `LNBITS_INVOICE_KEY`, skipped when they are not set
+    #[tokio::test]
+    async fn test_regtest_instance() {
+        let (Ok(url), Ok(admin_key), Ok(invoice_key)) = (
+            std::env::var("LNBITS_URL"),
+            std::env::var("LNBITS_ADMIN_KEY"),
+            std::env::var("LNBITS_INVOICE_KEY"),
+        ) else {
+            return;
+        };
+        let lnbits = Lnbits::new(LnbitsConfig::new(
+            Url::parse(&url).unwrap(),
+            admin_key,
+            invoice_key,
+        ))
+        .unwrap();
+
+        let invoice = lnbits
+            .create_invoice(Amount::from(10), &CurrencyUnit::Sat, None, unix_time() + 60)
+            .await
+            .unwrap();
+        assert_eq!(
+            lnbits.invoice_status(invoice.payment_hash()).await.unwrap(),
+            InvoiceStatus::Unpaid
+        );
+        assert_eq!(
+            lnbits.payment_status(invoice.payment_hash()).await.unwrap(),
+            PaymentStatus::Failed
+        );
+    }
+}
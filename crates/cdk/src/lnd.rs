@@ -0,0 +1,742 @@
+//! LND backend
+//!
+//! Talks to the REST API of an LND node, authenticated with a macaroon.
+//! Mint quotes are settled by polling [LnBackend::invoice_status]. Enabled
+//! with the `lnd` feature.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use base64::engine::general_purpose;
+use base64::Engine as _;
+use bitcoin::hashes::Hash;
+use reqwest::{Certificate, Client, Method, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer};
+use serde_json::{json, Value};
+use thiserror::Error;
+use url::Url;
+
+use crate::lightning::{self, LnBackend, Payment, PaymentStatus};
+use crate::nuts::CurrencyUnit;
+use crate::types::{InvoiceDescription, InvoiceStatus};
+use crate::util::unix_time;
+use crate::{Amount, Bolt11Invoice, Sha256};
+
+/// gRPC status of LND when a payment or invoice does not exist
+const GRPC_NOT_FOUND: i64 = 5;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Reqwest(#[from] reqwest::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Url(#[from] url::ParseError),
+    #[error("Invalid macaroon: {0}")]
+    Macaroon(String),
+    /// Error response of the node
+    #[error("LND error {code}: {message}")]
+    Rpc { code: i64, message: String },
+    /// Stream of payment updates ended while the payment was in flight
+    #[error("Payment still in flight")]
+    InFlight,
+}
+
+impl From<Error> for lightning::Error {
+    fn from(err: Error) -> Self {
+        Self::Backend(Box::new(err))
+    }
+}
+
+/// Macaroon authenticating requests, `admin.macaroon` or one baked with
+/// invoice and offchain permissions
+#[derive(Debug, Clone)]
+pub enum Macaroon {
+    Path(PathBuf),
+    Hex(String),
+}
+
+/// Connection settings of an [Lnd] backend
+#[derive(Debug, Clone)]
+pub struct LndConfig {
+    /// REST endpoint, `https://127.0.0.1:8080` by default on a node
+    pub url: Url,
+    pub macaroon: Macaroon,
+    /// `tls.cert` of the node, needed as it is self-signed
+    pub tls_cert: Option<PathBuf>,
+    /// Timeout of a call, other than sending a payment
+    pub rpc_timeout: Duration,
+    /// How long LND keeps trying to pay before giving up
+    pub pay_timeout: Duration,
+}
+
+impl LndConfig {
+    pub fn new(url: Url, macaroon: Macaroon) -> Self {
+        Self {
+            url,
+            macaroon,
+            tls_cert: None,
+            rpc_timeout: Duration::from_secs(30),
+            pay_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+/// [LnBackend] of an LND node
+#[derive(Debug, Clone)]
+pub struct Lnd {
+    config: LndConfig,
+    macaroon: String,
+    client: Client,
+}
+
+#[derive(Deserialize)]
+struct AddInvoiceResponse {
+    payment_request: String,
+}
+
+#[derive(Deserialize)]
+struct LookupInvoiceResponse {
+    state: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+/// Message of a payment stream, either an update or an error
+#[derive(Deserialize)]
+struct StreamMessage {
+    result: Option<PaymentUpdate>,
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize)]
+struct PaymentUpdate {
+    status: String,
+    #[serde(default)]
+    payment_preimage: String,
+    #[serde(default, deserialize_with = "int64")]
+    value_msat: u64,
+    #[serde(default, deserialize_with = "int64")]
+    fee_msat: u64,
+    #[serde(default)]
+    failure_reason: String,
+}
+
+/// 64 bit integers, which the REST API encodes as strings
+fn int64<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Int64 {
+        Number(u64),
+        String(String),
+    }
+
+    match Int64::deserialize(deserializer)? {
+        Int64::Number(n) => Ok(n),
+        Int64::String(n) => n.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+impl PaymentUpdate {
+    fn status(&self) -> PaymentStatus {
+        match self.status.as_str() {
+            "SUCCEEDED" => PaymentStatus::Paid(Payment {
+                preimage: self.payment_preimage.clone(),
+                total_spent_msat: self.value_msat + self.fee_msat,
+            }),
+            "FAILED" => PaymentStatus::Failed,
+            // `IN_FLIGHT`, and `INITIATED` of newer nodes
+            _ => PaymentStatus::Pending,
+        }
+    }
+}
+
+impl Lnd {
+    /// Read the macaroon and certificate of `config`
+    pub fn new(config: LndConfig) -> Result<Self, Error> {
+        let macaroon = match &config.macaroon {
+            Macaroon::Path(path) => hex_encode(&std::fs::read(path)?),
+            Macaroon::Hex(hex) => {
+                if hex.is_empty() || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(Error::Macaroon("not hex".to_string()));
+                }
+                hex.clone()
+            }
+        };
+
+        let mut client = Client::builder();
+        if let Some(tls_cert) = &config.tls_cert {
+            client = client.add_root_certificate(Certificate::from_pem(&std::fs::read(tls_cert)?)?);
+        }
+
+        Ok(Self {
+            config,
+            macaroon,
+            client: client.build()?,
+        })
+    }
+
+    fn request(&self, method: Method, path: &str) -> Result<RequestBuilder, Error> {
+        Ok(self
+            .client
+            .request(method, self.config.url.join(path)?)
+            .header("Grpc-Metadata-macaroon", &self.macaroon))
+    }
+
+    async fn call<R>(&self, request: RequestBuilder) -> Result<R, Error>
+    where
+        R: DeserializeOwned,
+    {
+        let response = request.timeout(self.config.rpc_timeout).send().await?;
+        let success = response.status().is_success();
+        let body: Value = response.json().await?;
+
+        if !success {
+            let error: RpcError = serde_json::from_value(body)?;
+            return Err(Error::Rpc {
+                code: error.code,
+                message: error.message,
+            });
+        }
+
+        Ok(serde_json::from_value(body)?)
+    }
+
+    /// Read payment updates until `done` returns true for one, returning
+    /// the last update read
+    ///
+    /// Updates are JSON objects one per line, the stream ends when the
+    /// payment reaches a final state or the request times out.
+    async fn read_updates(
+        mut response: Response,
+        done: impl Fn(&PaymentUpdate) -> bool,
+    ) -> Result<Option<PaymentUpdate>, Error> {
+        if !response.status().is_success() {
+            let error: RpcError = response.json().await?;
+            return Err(Error::Rpc {
+                code: error.code,
+                message: error.message,
+            });
+        }
+
+        let mut buf = Vec::new();
+        let mut last = None;
+
+        loop {
+            let chunk = match response.chunk().await {
+                Ok(Some(chunk)) => chunk,
+                // Stream closed, or timed out while the payment is in flight
+                Ok(None) => return Ok(last),
+                Err(err) if err.is_timeout() => return Ok(last),
+                Err(err) => return Err(err.into()),
+            };
+            buf.extend_from_slice(&chunk);
+
+            while let Some(end) = buf.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buf.drain(..=end).collect();
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+
+                let message: StreamMessage = serde_json::from_slice(&line)?;
+                match (message.result, message.error) {
+                    (_, Some(error)) => {
+                        return Err(Error::Rpc {
+                            code: error.code,
+                            message: error.message,
+                        })
+                    }
+                    (Some(update), None) => {
+                        if done(&update) {
+                            return Ok(Some(update));
+                        }
+                        last = Some(update);
+                    }
+                    (None, None) => (),
+                }
+            }
+        }
+    }
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[async_trait]
+impl LnBackend for Lnd {
+    async fn create_invoice(
+        &self,
+        amount: Amount,
+        unit: &CurrencyUnit,
+        description: Option<InvoiceDescription>,
+        expiry: u64,
+    ) -> Result<Bolt11Invoice, lightning::Error> {
+        let amount_msat = match unit {
+            CurrencyUnit::Sat => amount
+                .checked_to_msat()
+                .ok_or(lightning::Error::Unsupported(
+                    "create_invoice over u64::MAX msat",
+                ))?,
+            CurrencyUnit::Msat => amount.into(),
+            _ => return Err(lightning::Error::Unsupported("create_invoice in this unit")),
+        };
+
+        let mut body = json!({
+            "value_msat": amount_msat.to_string(),
+            "expiry": expiry.saturating_sub(unix_time()).to_string(),
+        });
+        match description {
+            Some(InvoiceDescription::Text(description)) => body["memo"] = description.into(),
+            // Bytes fields are base64 in the REST API
+            Some(InvoiceDescription::Hash(hash)) => {
+                body["description_hash"] = general_purpose::STANDARD.encode(hash).into()
+            }
+            None => body["memo"] = "".into(),
+        }
+
+        let request = self.request(Method::POST, "v1/invoices")?.json(&body);
+        let response: AddInvoiceResponse = self.call(request).await?;
+
+        response
+            .payment_request
+            .parse()
+            .map_err(|err| lightning::Error::Backend(Box::new(err)))
+    }
+
+    fn supports_description_hash(&self) -> bool {
+        true
+    }
+
+    async fn pay_invoice(
+        &self,
+        bolt11: &Bolt11Invoice,
+        amount_msat: u64,
+        max_fee: Amount,
+    ) -> Result<Payment, lightning::Error> {
+        let mut body = json!({
+            "payment_request": bolt11.to_string(),
+            "fee_limit_msat": max_fee.to_msat().to_string(),
+            "timeout_seconds": self.config.pay_timeout.as_secs(),
+        });
+        // LND refuses an amount for invoices that carry one
+        if bolt11.amount_milli_satoshis().is_none() {
+            body["amt_msat"] = amount_msat.to_string().into();
+        }
+
+        let response = self
+            .request(Method::POST, "v2/router/send")?
+            .json(&body)
+            .timeout(self.config.pay_timeout + Duration::from_secs(60))
+            .send()
+            .await
+            .map_err(Error::from)?;
+
+        let update = match Self::read_updates(response, |update| {
+            !matches!(update.status(), PaymentStatus::Pending)
+        })
+        .await
+        {
+            Ok(update) => update,
+            // Refused before it was sent, unless an earlier attempt for the
+            // same hash exists
+            Err(Error::Rpc { code, message }) => {
+                return match self.payment_status(bolt11.payment_hash()).await? {
+                    PaymentStatus::Failed => Err(lightning::Error::PaymentFailed(message)),
+                    PaymentStatus::Paid(payment) => Ok(payment),
+                    PaymentStatus::Pending => Err(Error::Rpc { code, message }.into()),
+                };
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        match update.as_ref().map(|u| (u.status(), u)) {
+            Some((PaymentStatus::Paid(payment), _)) => Ok(payment),
+            Some((PaymentStatus::Failed, update)) => Err(lightning::Error::PaymentFailed(
+                update.failure_reason.clone(),
+            )),
+            _ => Err(Error::InFlight.into()),
+        }
+    }
+
+    async fn invoice_status(
+        &self,
+        payment_hash: &Sha256,
+    ) -> Result<InvoiceStatus, lightning::Error> {
+        let request = self.request(
+            Method::GET,
+            &format!("v1/invoice/{}", hex_encode(payment_hash.as_byte_array())),
+        )?;
+        let response: LookupInvoiceResponse = self.call(request).await?;
+
+        Ok(match response.state.as_str() {
+            "SETTLED" => InvoiceStatus::Paid,
+            "CANCELED" => InvoiceStatus::Expired,
+            // `OPEN`, and `ACCEPTED` of hold invoices
+            _ => InvoiceStatus::Unpaid,
+        })
+    }
+
+    async fn payment_status(
+        &self,
+        payment_hash: &Sha256,
+    ) -> Result<PaymentStatus, lightning::Error> {
+        let path = format!(
+            "v2/router/track/{}",
+            general_purpose::URL_SAFE.encode(payment_hash.as_byte_array())
+        );
+        let response = self
+            .request(Method::GET, &path)?
+            .timeout(self.config.rpc_timeout)
+            .send()
+            .await
+            .map_err(Error::from)?;
+
+        // The first update is the current state
+        match Self::read_updates(response, |_| true).await {
+            Ok(Some(update)) => Ok(update.status()),
+            Ok(None) => Err(Error::InFlight.into()),
+            Err(Error::Rpc {
+                code: GRPC_NOT_FOUND,
+                ..
+            }) => Ok(PaymentStatus::Failed),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    const INVOICE: &str = "lnbc2500u1pj48ugqdq8w3jhxaqpp5qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqsp59g4z52329g4z52329g4z52329g4z52329g4z52329g4z52329g4q9qrsgqcqzysd6exwz2yjprkglq820pffp8wcu97sxuzcgx7raensa83dnc4kpmhkuqevwf6hmv6a3mc9aegn89jv7a8e9wggdxtfsjwrur7n6sx4rsq7rvjx3";
+
+    /// Request received by [serve]
+    #[derive(Debug, Clone)]
+    struct Recorded {
+        method: String,
+        path: String,
+        macaroon: String,
+        body: Value,
+    }
+
+    /// Answer requests in order with `(status, body)`
+    ///
+    /// Bodies of streaming calls are several JSON lines.
+    fn serve(responses: Vec<(u16, String)>) -> (Lnd, Arc<Mutex<Vec<Recorded>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+
+        let calls = Arc::clone(&recorded);
+        std::thread::spawn(move || {
+            for ((status, body), stream) in responses.into_iter().zip(listener.incoming()) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut parts = request_line.split_whitespace();
+                let method = parts.next().unwrap().to_string();
+                let path = parts.next().unwrap().to_string();
+
+                let mut content_length = 0;
+                let mut macaroon = String::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let lower = line.to_lowercase();
+                    if let Some(length) = lower.strip_prefix("content-length:") {
+                        content_length = length.trim().parse().unwrap();
+                    }
+                    if let Some(value) = lower.strip_prefix("grpc-metadata-macaroon:") {
+                        macaroon = value.trim().to_string();
+                    }
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                }
+                let mut request_body = vec![0; content_length];
+                reader.read_exact(&mut request_body).unwrap();
+
+                calls.lock().unwrap().push(Recorded {
+                    method,
+                    path,
+                    macaroon,
+                    body: serde_json::from_slice(&request_body).unwrap_or(Value::Null),
+                });
+
+                write!(
+                    stream,
+                    "HTTP/1.1 {status} OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+            }
+        });
+
+        let mut config = LndConfig::new(url, Macaroon::Hex("0201036c6e64".to_string()));
+        config.rpc_timeout = Duration::from_secs(5);
+        (Lnd::new(config).unwrap(), recorded)
+    }
+
+    fn update(status: &str) -> String {
+        json!({ "result": {
+            "payment_hash": "00",
+            "value_msat": "250000000",
+            "fee_msat": "1500",
+            "payment_preimage": "0000000000000000000000000000000000000000000000000000000000000000",
+            "status": status,
+            "failure_reason": if status == "FAILED" {
+                "FAILURE_REASON_NO_ROUTE"
+            } else {
+                "FAILURE_REASON_NONE"
+            },
+        }})
+        .to_string()
+    }
+
+    fn error(code: i64, message: &str) -> String {
+        json!({ "code": code, "message": message, "details": [] }).to_string()
+    }
+
+    fn stream_error(code: i64, message: &str) -> String {
+        json!({ "error": { "code": code, "message": message, "details": [] } }).to_string()
+    }
+
+    #[tokio::test]
+    async fn test_create_invoice_and_status() {
+        let (lnd, calls) = serve(vec![
+            (
+                200,
+                json!({ "r_hash": "AAAA", "payment_request": INVOICE, "add_index": "3" })
+                    .to_string(),
+            ),
+            (200, json!({ "state": "OPEN" }).to_string()),
+            (
+                200,
+                json!({ "state": "SETTLED", "amt_paid_msat": "250000000" }).to_string(),
+            ),
+            (200, json!({ "state": "CANCELED" }).to_string()),
+            (404, error(GRPC_NOT_FOUND, "unable to locate invoice")),
+        ]);
+
+        let invoice = lnd
+            .create_invoice(
+                Amount::from(250_000),
+                &CurrencyUnit::Sat,
+                Some(InvoiceDescription::Text("coffee".to_string())),
+                unix_time() + 600,
+            )
+            .await
+            .unwrap();
+        assert_eq!(invoice.to_string(), INVOICE);
+
+        let hash = invoice.payment_hash();
+        assert_eq!(
+            lnd.invoice_status(hash).await.unwrap(),
+            InvoiceStatus::Unpaid
+        );
+        assert_eq!(lnd.invoice_status(hash).await.unwrap(), InvoiceStatus::Paid);
+        assert_eq!(
+            lnd.invoice_status(hash).await.unwrap(),
+            InvoiceStatus::Expired
+        );
+        assert!(matches!(
+            lnd.invoice_status(hash).await,
+            Err(lightning::Error::Backend(_))
+        ));
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls[0].method, "POST");
+        assert_eq!(calls[0].path, "/v1/invoices");
+        assert_eq!(calls[0].macaroon, "0201036c6e64");
+        assert_eq!(calls[0].body["value_msat"], "250000000");
+        assert_eq!(calls[0].body["memo"], "coffee");
+        assert_eq!(
+            calls[1].path,
+            format!("/v1/invoice/{}", hex_encode(hash.as_byte_array()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pay_invoice() {
+        let bolt11 = Bolt11Invoice::from_str(INVOICE).unwrap();
+        let (lnd, calls) = serve(vec![
+            (
+                200,
+                format!(
+                    "{}\n{}\n{}\n",
+                    update("INITIATED"),
+                    update("IN_FLIGHT"),
+                    update("SUCCEEDED")
+                ),
+            ),
+            (
+                200,
+                format!("{}\n{}\n", update("IN_FLIGHT"), update("FAILED")),
+            ),
+            // Stream closed while the payment is in flight
+            (200, format!("{}\n", update("IN_FLIGHT"))),
+            // Refused before sending, and never tried before
+            (200, format!("{}\n", stream_error(2, "invoice expired"))),
+            (
+                200,
+                format!(
+                    "{}\n",
+                    stream_error(GRPC_NOT_FOUND, "payment isn't initiated")
+                ),
+            ),
+            // Refused as an earlier attempt is in flight
+            (
+                200,
+                format!("{}\n", stream_error(6, "payment is in transition")),
+            ),
+            (200, format!("{}\n", update("IN_FLIGHT"))),
+        ]);
+
+        let payment = lnd
+            .pay_invoice(&bolt11, 250_000_000, Amount::from(2))
+            .await
+            .unwrap();
+        assert_eq!(payment.total_spent_msat, 250_001_500);
+        assert_eq!(payment.preimage.len(), 64);
+
+        assert!(matches!(
+            lnd.pay_invoice(&bolt11, 250_000_000, Amount::from(2)).await,
+            Err(lightning::Error::PaymentFailed(reason)) if reason == "FAILURE_REASON_NO_ROUTE"
+        ));
+        assert!(matches!(
+            lnd.pay_invoice(&bolt11, 250_000_000, Amount::from(2)).await,
+            Err(lightning::Error::Backend(_))
+        ));
+        assert!(matches!(
+            lnd.pay_invoice(&bolt11, 250_000_000, Amount::from(2)).await,
+            Err(lightning::Error::PaymentFailed(_))
+        ));
+        assert!(matches!(
+            lnd.pay_invoice(&bolt11, 250_000_000, Amount::from(2)).await,
+            Err(lightning::Error::Backend(_))
+        ));
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls[0].path, "/v2/router/send");
+        assert_eq!(calls[0].body["fee_limit_msat"], "2000");
+        assert!(calls[0].body.get("amt_msat").is_none());
+        assert_eq!(
+            calls[4].path,
+            format!(
+                "/v2/router/track/{}",
+                general_purpose::URL_SAFE.encode(bolt11.payment_hash().as_byte_array())
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_payment_status() {
+        let hash = *Bolt11Invoice::from_str(INVOICE).unwrap().payment_hash();
+        let (lnd, _) = serve(vec![
+            (200, format!("{}\n", update("IN_FLIGHT"))),
+            (200, format!("{}\n", update("SUCCEEDED"))),
+            (200, format!("{}\n", update("FAILED"))),
+            (
+                200,
+                format!(
+                    "{}\n",
+                    stream_error(GRPC_NOT_FOUND, "payment isn't initiated")
+                ),
+            ),
+            (404, error(GRPC_NOT_FOUND, "payment isn't initiated")),
+            (500, String::new()),
+        ]);
+
+        assert_eq!(
+            lnd.payment_status(&hash).await.unwrap(),
+            PaymentStatus::Pending
+        );
+        assert!(matches!(
+            lnd.payment_status(&hash).await.unwrap(),
+            PaymentStatus::Paid(Payment {
+                total_spent_msat: 250_001_500,
+                ..
+            })
+        ));
+        for _ in 0..3 {
+            assert_eq!(
+                lnd.payment_status(&hash).await.unwrap(),
+                PaymentStatus::Failed
+            );
+        }
+        assert!(lnd.payment_status(&hash).await.is_err());
+    }
+
+    #[test]
+    fn test_config() {
+        let url = Url::parse("https://127.0.0.1:8080").unwrap();
+        assert!(matches!(
+            Lnd::new(LndConfig::new(
+                url.clone(),
+                Macaroon::Hex("not hex".to_string())
+            )),
+            Err(Error::Macaroon(_))
+        ));
+        assert!(matches!(
+            Lnd::new(LndConfig::new(
+                url.clone(),
+                Macaroon::Path("/nonexistent/admin.macaroon".into())
+            )),
+            Err(Error::Io(_))
+        ));
+
+        let path = std::env::temp_dir().join(format!("cdk-lnd-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, [0x02, 0x01, 0xff]).unwrap();
+        let lnd = Lnd::new(LndConfig::new(url, Macaroon::Path(path.clone()))).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(lnd.macaroon, "0201ff");
+    }
+
+    /// Runs against the node at `LND_REST_URL` with `LND_MACAROON_HEX` and
+    /// `LND_TLS_CERT`, skipped when they are not set
+    #[tokio::test]
+    async fn test_regtest_node() {
+        let (Ok(url), Ok(macaroon)) = (
+            std::env::var("LND_REST_URL"),
+            std::env::var("LND_MACAROON_HEX"),
+        ) else {
+            return;
+        };
+        let mut config = LndConfig::new(Url::parse(&url).unwrap(), Macaroon::Hex(macaroon));
+        config.tls_cert = std::env::var("LND_TLS_CERT").ok().map(PathBuf::from);
+        let lnd = Lnd::new(config).unwrap();
+
+        let invoice = lnd
+            .create_invoice(Amount::from(10), &CurrencyUnit::Sat, None, unix_time() + 60)
+            .await
+            .unwrap();
+        assert_eq!(
+            lnd.invoice_status(invoice.payment_hash()).await.unwrap(),
+            InvoiceStatus::Unpaid
+        );
+        assert_eq!(
+            lnd.payment_status(invoice.payment_hash()).await.unwrap(),
+            PaymentStatus::Failed
+        );
+    }
+}
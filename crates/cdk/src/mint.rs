@@ -1,28 +1,49 @@
-use std::collections::HashSet;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::str::FromStr;
 use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
 
 use bip39::Mnemonic;
-use bitcoin::bip32::DerivationPath;
+use bitcoin::bip32::{ChildNumber, DerivationPath, ExtendedPrivKey};
+use bitcoin::secp256k1::schnorr::Signature;
 use http::StatusCode;
+#[cfg(target_arch = "wasm32")]
+use instant::Instant;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use tracing::{debug, error, info};
+use tokio::sync::{broadcast, Mutex, RwLock};
+use tracing::{debug, error, info, info_span, warn, Instrument};
+use uuid::Uuid;
 
-use crate::cdk_database::{self, MintDatabase};
-use crate::dhke::{hash_to_curve, sign_message, verify_message};
-use crate::error::ErrorResponse;
+use crate::accounting::Accounting;
+#[cfg(feature = "invariant-checks")]
+use crate::accounting::Ledger;
+use crate::cdk_database::{self, MintDatabase, MintTransaction};
+use crate::dhke::{hash_to_curve, sign_message};
+use crate::error::{ErrorCode, ErrorResponse};
+use crate::lightning::{self, LnBackend, PaymentStatus};
+use crate::nuts::nut02::{derivation_path_index, power_of_two_amounts};
 use crate::nuts::*;
-use crate::types::{MeltQuote, MintQuote};
-use crate::Amount;
+use crate::secret::Secret;
+use crate::signatory::{self, LocalSignatory, Signatory};
+use crate::types::{
+    FeeEstimator, InvoiceDescription, InvoiceStatus, MeltQuote, MeltSettlement, MintQuote,
+};
+use crate::util::{unix_time, Clock, SystemClock};
+use crate::{Amount, Bolt11Invoice, SECP256K1};
 
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum Error {
     /// Unknown Keyset
     #[error("Unknown Keyset")]
     UnknownKeySet,
-    /// Inactive Keyset
-    #[error("Inactive Keyset")]
-    InactiveKeyset,
+    /// Outputs for keyset `id` that is not the active keyset of its unit,
+    /// [None] if the unit has no active keyset
+    #[error("Inactive Keyset `{id}`")]
+    InactiveKeyset { id: Id, active_id: Option<Id> },
     #[error("No key for amount")]
     AmountKey,
     #[error("Amount")]
@@ -35,6 +56,8 @@ pub enum Error {
     TokenPending,
     #[error("Quote not paid")]
     UnpaidQuote,
+    #[error("Tokens already issued for quote")]
+    IssuedQuote,
     #[error("`{0}`")]
     Custom(String),
     #[error(transparent)]
@@ -44,6 +67,8 @@ pub enum Error {
     #[error(transparent)]
     NUT00(#[from] crate::nuts::nut00::Error),
     #[error(transparent)]
+    NUT02(#[from] crate::nuts::nut02::Error),
+    #[error(transparent)]
     NUT11(#[from] crate::nuts::nut11::Error),
     #[error(transparent)]
     Nut12(#[from] crate::nuts::nut12::Error),
@@ -52,12 +77,60 @@ pub enum Error {
     Database(#[from] crate::cdk_database::Error),
     #[error("Unknown quote")]
     UnknownQuote,
+    /// No mint quote is for the invoice a settlement was reported for
+    #[error("No mint quote for payment hash `{0}`")]
+    UnknownPaymentHash(crate::Sha256),
     #[error("Unknown secret kind")]
     UnknownSecretKind,
     #[error("Cannot have multiple units")]
     MultipleUnits,
     #[error("Blinded Message is already signed")]
     BlindedMessageAlreadySigned,
+    #[error("Payment method `{0}` unsupported")]
+    UnsupportedMethod(PaymentMethod),
+    #[error("Request exceeds `{limit}` of {max}")]
+    RequestTooLarge { limit: &'static str, max: u64 },
+    #[error("Keyset id does not match its keys")]
+    KeysetIdMismatch,
+    /// Keyset ids do not commit to the unit, so keys derived at the same
+    /// path for two units share an id
+    #[error("Keyset `{id}` already exists for unit {unit}")]
+    KeysetUnitConflict { id: Id, unit: CurrencyUnit },
+    /// Keysets info marks more than one keyset of the unit active
+    #[error("Multiple active keysets for unit {0}")]
+    MultipleActiveKeysets(CurrencyUnit),
+    #[error("Active keyset cannot be removed")]
+    ActiveKeyset,
+    #[error("Keyset denominations must be non-empty and non-zero")]
+    InvalidDenominations,
+    #[error("Proofs of keyset are no longer accepted")]
+    RefusedKeyset,
+    #[error("Keyset is still accepted")]
+    AcceptedKeyset,
+    #[error("Archive does not match its commitment")]
+    ArchiveCommitment,
+    #[error("Melt change outputs must have an amount of 0")]
+    NonBlankChangeOutput,
+    #[error("Signing key does not match the mint pubkey")]
+    AuditKeyMismatch,
+    #[error("Quote is pending")]
+    PendingQuote,
+    #[error("Quote already paid")]
+    PaidQuote,
+    #[error("Melt cannot be paid without a preimage")]
+    MissingPreimage,
+    #[error("Keyset is frozen: {0}")]
+    KeySetFrozen(String),
+    #[error("Melt is not pending")]
+    MeltNotPending,
+    #[error(transparent)]
+    Lightning(#[from] crate::lightning::Error),
+    /// Signatory failed or does not hold a keyset or key of the mint
+    #[error(transparent)]
+    Signatory(#[from] crate::signatory::Error),
+    /// Melt inputs do not cover the quote, its fee reserve and the input fee
+    #[error("Melt inputs of {provided} are less than the {required} required")]
+    InsufficientMeltInputs { provided: Amount, required: Amount },
 }
 
 impl From<Error> for cdk_database::Error {
@@ -66,19 +139,134 @@ impl From<Error> for cdk_database::Error {
     }
 }
 
-impl From<Error> for ErrorResponse {
-    fn from(err: Error) -> ErrorResponse {
+impl Error {
+    /// Cashu protocol error code
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::UnknownKeySet => ErrorCode::KeysetNotFound,
+            Self::InactiveKeyset { .. } | Self::RefusedKeyset | Self::KeySetFrozen(_) => {
+                ErrorCode::KeysetInactive
+            }
+            Self::Amount | Self::InsufficientMeltInputs { .. } => ErrorCode::TransactionUnbalanced,
+            Self::TokenSpent => ErrorCode::TokenAlreadySpent,
+            Self::UnpaidQuote => ErrorCode::QuoteNotPaid,
+            Self::IssuedQuote => ErrorCode::TokensAlreadyIssued,
+            Self::MultipleUnits => ErrorCode::UnitUnsupported,
+            Self::BlindedMessageAlreadySigned => ErrorCode::BlindedMessageAlreadySigned,
+            Self::PendingQuote => ErrorCode::QuotePending,
+            Self::PaidQuote => ErrorCode::InvoiceAlreadyPaid,
+            Self::DuplicateProofs => ErrorCode::DuplicateInputs,
+            Self::RequestTooLarge { .. } => ErrorCode::AmountOutofLimitRange,
+            Self::Cashu(crate::error::Error::TokenSpent) => ErrorCode::TokenAlreadySpent,
+            Self::Cashu(crate::error::Error::TokenNotVerifed) => ErrorCode::TokenNotVerified,
+            Self::NUT11(_) | Self::Nut12(_) => ErrorCode::TokenNotVerified,
+            Self::AmountKey
+            | Self::TokenPending
+            | Self::UnknownQuote
+            | Self::UnknownPaymentHash(_)
+            | Self::UnsupportedMethod(_)
+            | Self::Custom(_)
+            | Self::Cashu(_)
+            | Self::Secret(_)
+            | Self::NUT00(_)
+            | Self::NUT02(_)
+            | Self::Database(_)
+            | Self::UnknownSecretKind
+            | Self::KeysetIdMismatch
+            | Self::KeysetUnitConflict { .. }
+            | Self::MultipleActiveKeysets(_)
+            | Self::ActiveKeyset
+            | Self::InvalidDenominations
+            | Self::AcceptedKeyset
+            | Self::ArchiveCommitment
+            | Self::NonBlankChangeOutput
+            | Self::AuditKeyMismatch
+            | Self::MissingPreimage
+            | Self::MeltNotPending
+            | Self::Lightning(_)
+            | Self::Signatory(_) => ErrorCode::Unknown(9999),
+        }
+    }
+
+    /// HTTP status of a response refusing a request with this error
+    ///
+    /// As Nutshell does, 404 for unknown quotes and keysets, 500 for database,
+    /// Lightning backend and signatory failures and 400 with the
+    /// [Error::code] in the body otherwise. The signatory is only asked for
+    /// keysets and keys the mint holds, so any of its errors is a failure.
+    pub fn status_code(&self) -> u16 {
+        match self {
+            Self::UnknownQuote | Self::UnknownPaymentHash(_) | Self::UnknownKeySet => 404,
+            Self::Database(_)
+            | Self::Lightning(crate::lightning::Error::Backend(_))
+            | Self::Signatory(_)
+            | Self::Custom(_) => 500,
+            Self::InactiveKeyset { .. }
+            | Self::AmountKey
+            | Self::Amount
+            | Self::DuplicateProofs
+            | Self::TokenSpent
+            | Self::TokenPending
+            | Self::UnpaidQuote
+            | Self::IssuedQuote
+            | Self::Cashu(_)
+            | Self::Secret(_)
+            | Self::NUT00(_)
+            | Self::NUT02(_)
+            | Self::NUT11(_)
+            | Self::Nut12(_)
+            | Self::UnknownSecretKind
+            | Self::MultipleUnits
+            | Self::BlindedMessageAlreadySigned
+            | Self::UnsupportedMethod(_)
+            | Self::RequestTooLarge { .. }
+            | Self::KeysetIdMismatch
+            | Self::KeysetUnitConflict { .. }
+            | Self::MultipleActiveKeysets(_)
+            | Self::ActiveKeyset
+            | Self::InvalidDenominations
+            | Self::RefusedKeyset
+            | Self::AcceptedKeyset
+            | Self::ArchiveCommitment
+            | Self::NonBlankChangeOutput
+            | Self::AuditKeyMismatch
+            | Self::PendingQuote
+            | Self::PaidQuote
+            | Self::MissingPreimage
+            | Self::KeySetFrozen(_)
+            | Self::MeltNotPending
+            | Self::Lightning(_)
+            | Self::InsufficientMeltInputs { .. } => 400,
+        }
+    }
+}
+
+impl From<&Error> for ErrorResponse {
+    fn from(err: &Error) -> ErrorResponse {
         ErrorResponse {
-            code: 9999,
-            error: Some(err.to_string()),
-            detail: None,
+            code: err.code(),
+            error: None,
+            detail: Some(err.to_string()),
+            active_keyset_id: match err {
+                Error::InactiveKeyset { active_id, .. } => *active_id,
+                _ => None,
+            },
         }
     }
 }
 
+impl From<Error> for ErrorResponse {
+    fn from(err: Error) -> ErrorResponse {
+        (&err).into()
+    }
+}
+
 impl From<Error> for (StatusCode, ErrorResponse) {
     fn from(err: Error) -> (StatusCode, ErrorResponse) {
-        (StatusCode::NOT_FOUND, err.into())
+        let status =
+            StatusCode::from_u16(err.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+
+        (status, err.into())
     }
 }
 
@@ -86,8 +274,97 @@ impl From<Error> for (StatusCode, ErrorResponse) {
 pub struct Mint {
     //    pub pubkey: PublicKey
     mnemonic: Mnemonic,
-    pub fee_reserve: FeeReserve,
-    pub localstore: Arc<dyn MintDatabase<Err = cdk_database::Error> + Send + Sync>,
+    /// Signs on behalf of the mint, apart from the keyset keys
+    identity_key: SigningKey,
+    fee_reserve: Arc<std::sync::RwLock<FeeReserve>>,
+    request_limits: RequestLimits,
+    ln_backend: Option<Arc<dyn LnBackend>>,
+    signatory: Arc<dyn Signatory>,
+    localstore: Arc<dyn MintDatabase<Err = cdk_database::Error> + Send + Sync>,
+    /// Keysets shared between requests so keys are not cloned per lookup
+    keysets: Arc<RwLock<HashMap<Id, CachedKeySet>>>,
+    derivations: Arc<RwLock<HashMap<Id, KeySetDerivation>>>,
+    /// Current id of keysets by their legacy id
+    legacy_ids: Arc<RwLock<HashMap<Id, Id>>>,
+    /// Held while a mint quote is marked paid so it is only settled once,
+    /// and while a melt quote is reserved so it is only reserved once
+    quote_lock: Arc<Mutex<()>>,
+    events: broadcast::Sender<MintEvent>,
+    rotation_policies: HashMap<CurrencyUnit, RotationPolicy>,
+    clock: Arc<dyn Clock>,
+    swap_response_ttl: u64,
+    accounting: Arc<std::sync::RwLock<Accounting>>,
+    #[cfg(feature = "invariant-checks")]
+    ledger: Arc<std::sync::Mutex<Ledger>>,
+}
+
+/// Seconds a swap response is answered again to resubmissions of its
+/// request before it may be pruned
+pub const DEFAULT_SWAP_RESPONSE_TTL: u64 = 3600;
+
+/// Events buffered per subscriber before the oldest are dropped
+const EVENT_CAPACITY: usize = 1024;
+
+/// Quote state transition published by a [Mint]
+///
+/// Mint quote events are published under the quote lock, so for the same
+/// quote [MintEvent::QuotePaid] is always received before
+/// [MintEvent::QuoteIssued].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum MintEvent {
+    /// Mint quote was paid, the wallet can mint
+    ///
+    /// Published for every payment to the offer of a bolt12 quote, with the
+    /// amount that payment received.
+    QuotePaid {
+        quote_id: String,
+        amount: Amount,
+        unit: CurrencyUnit,
+    },
+    /// Ecash was issued for a mint quote
+    QuoteIssued {
+        quote_id: String,
+        amount: Amount,
+        unit: CurrencyUnit,
+    },
+    /// Melt quote was settled
+    MeltPaid {
+        quote_id: String,
+        amount: Amount,
+        unit: CurrencyUnit,
+    },
+    /// Active keyset of `unit` was rotated by its [RotationPolicy]
+    KeysetRotated {
+        unit: CurrencyUnit,
+        old_id: Id,
+        new_id: Id,
+        /// Unix time proofs of the old keyset are refused from
+        refused_from: u64,
+    },
+}
+
+/// How a keyset was generated from the mnemonic
+#[derive(Debug, Clone)]
+struct KeySetDerivation {
+    derivation_path: DerivationPath,
+    amounts: Vec<u64>,
+}
+
+/// Keyset with its public form, computed once
+#[derive(Debug, Clone)]
+struct CachedKeySet {
+    keyset: Arc<MintKeySet>,
+    public: Arc<KeySet>,
+}
+
+impl From<MintKeySet> for CachedKeySet {
+    fn from(keyset: MintKeySet) -> Self {
+        Self {
+            public: Arc::new(KeySet::from(&keyset)),
+            keyset: Arc::new(keyset),
+        }
+    }
 }
 
 impl Mint {
@@ -99,62 +376,360 @@ impl Mint {
         percent_fee_reserve: f32,
     ) -> Result<Self, Error> {
         let mut active_units: HashSet<CurrencyUnit> = HashSet::default();
+        let mut keysets = HashMap::new();
+        let mut derivations = HashMap::new();
+        let mut legacy_ids = HashMap::new();
 
         if keysets_info.is_empty() {
-            let keyset = MintKeySet::generate(
+            let derivation_path = derivation_path_from_unit(&CurrencyUnit::Sat, 0)?;
+            let mut keyset = MintKeySet::generate(
                 &mnemonic.to_seed_normalized(""),
                 CurrencyUnit::Sat,
-                &DerivationPath::default(),
-                64,
-            );
+                &derivation_path,
+                nut02::MAX_ORDER,
+            )?;
+            keyset.valid_from = first_seen(localstore.as_ref(), &keyset.id).await?;
 
             localstore
                 .add_active_keyset(CurrencyUnit::Sat, keyset.id)
                 .await?;
-            localstore.add_keyset(keyset).await?;
+            localstore.add_keyset(keyset.clone()).await?;
+            derivations.insert(
+                keyset.id,
+                KeySetDerivation {
+                    derivation_path,
+                    amounts: power_of_two_amounts(nut02::MAX_ORDER)?,
+                },
+            );
+            keysets.insert(keyset.id, keyset.into());
         } else {
             // Check that there is only one active keyset per unit
             for keyset_info in keysets_info {
                 if keyset_info.active && !active_units.insert(keyset_info.unit.clone()) {
-                    // TODO: Handle Error
-                    todo!()
+                    return Err(Error::MultipleActiveKeysets(keyset_info.unit));
                 }
 
-                let keyset = MintKeySet::generate(
+                let amounts = keyset_info.amounts()?;
+                check_denominations(&amounts)?;
+
+                let mut keyset = MintKeySet::generate_with_amounts(
                     &mnemonic.to_seed_normalized(""),
                     keyset_info.unit.clone(),
-                    &keyset_info.derivation_path.clone(),
-                    keyset_info.max_order,
+                    &keyset_info.derivation_path,
+                    &amounts,
                 );
+                check_keyset_unit(localstore.as_ref(), &keyset).await?;
+                keyset.valid_from = match keyset_info.valid_from {
+                    0 => first_seen(localstore.as_ref(), &keyset.id).await?,
+                    valid_from => valid_from,
+                };
+                keyset.valid_to = keyset_info.valid_to;
+                keyset.input_fee_ppk = keyset_info.input_fee_ppk;
+
+                if keyset_info.active {
+                    localstore
+                        .add_active_keyset(keyset_info.unit, keyset.id)
+                        .await?;
+                }
 
-                localstore.add_keyset(keyset).await?;
+                localstore.add_keyset(keyset.clone()).await?;
+                derivations.insert(
+                    keyset.id,
+                    KeySetDerivation {
+                        derivation_path: keyset_info.derivation_path,
+                        amounts,
+                    },
+                );
+                if let Some(legacy_id) = keyset_info.legacy_id {
+                    legacy_ids.insert(legacy_id, keyset.id);
+                }
+                keysets.insert(keyset.id, keyset.into());
             }
         }
 
-        Ok(Self {
+        let mint = Self {
+            signatory: Arc::new(LocalSignatory::new(Arc::clone(&localstore))),
             localstore,
+            identity_key: SigningKey::new(identity_key_from_mnemonic(&mnemonic)?),
             mnemonic,
-            fee_reserve: FeeReserve {
+            keysets: Arc::new(RwLock::new(keysets)),
+            derivations: Arc::new(RwLock::new(derivations)),
+            legacy_ids: Arc::new(RwLock::new(legacy_ids)),
+            fee_reserve: Arc::new(std::sync::RwLock::new(FeeReserve {
                 min_fee_reserve,
                 percent_fee_reserve,
-            },
-        })
+            })),
+            request_limits: RequestLimits::default(),
+            ln_backend: None,
+            quote_lock: Arc::new(Mutex::new(())),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            rotation_policies: HashMap::new(),
+            clock: Arc::new(SystemClock),
+            swap_response_ttl: DEFAULT_SWAP_RESPONSE_TTL,
+            accounting: Arc::default(),
+            #[cfg(feature = "invariant-checks")]
+            ledger: Arc::default(),
+        };
+
+        #[cfg(feature = "invariant-checks")]
+        mint.rebase_ledger().await?;
+
+        Ok(mint)
+    }
+
+    /// Database backing the mint
+    #[deprecated(
+        note = "Use the Mint methods, writing to the database directly bypasses the mint caches"
+    )]
+    pub fn localstore(&self) -> Arc<dyn MintDatabase<Err = cdk_database::Error> + Send + Sync> {
+        Arc::clone(&self.localstore)
+    }
+
+    pub fn request_limits(&self) -> RequestLimits {
+        self.request_limits
+    }
+
+    /// Pubkey of the identity key, the NUT-06 pubkey of the mint
+    pub fn identity_pubkey(&self) -> PublicKey {
+        self.identity_key.public_key()
+    }
+
+    /// Sign with `secret_key` instead of the key derived from the mnemonic
+    /// at [IDENTITY_DERIVATION_PATH]
+    pub fn set_identity_key(&mut self, secret_key: SecretKey) {
+        self.identity_key = SigningKey::new(secret_key);
+    }
+
+    /// Schnorr signature of the sha256 of `msg` by the identity key, see
+    /// [MintInfo::verify_signature]
+    pub fn sign_with_identity(&self, msg: &[u8]) -> Signature {
+        self.identity_key
+            .sign(msg)
+            .expect("sha256 is a valid message")
+    }
+
+    pub fn set_request_limits(&mut self, request_limits: RequestLimits) {
+        self.request_limits = request_limits;
+    }
+
+    /// Lightning backend estimating the fee reserve of sat melt quotes, and
+    /// creating and paying invoices for the HTTP router
+    ///
+    /// Quotes it cannot estimate use the [FeeReserve] policy.
+    pub fn set_ln_backend(&mut self, ln_backend: Arc<dyn LnBackend>) {
+        self.ln_backend = Some(ln_backend);
+    }
+
+    pub fn ln_backend(&self) -> Option<Arc<dyn LnBackend>> {
+        self.ln_backend.clone()
+    }
+
+    /// Signatory blind signing outputs and verifying inputs, a
+    /// [LocalSignatory] on the database of the mint by default
+    ///
+    /// The mint still checks keysets, amounts and spending conditions itself
+    /// and generates keysets from its mnemonic, `signatory` has to hold
+    /// every keyset of the mint.
+    pub fn set_signatory(&mut self, signatory: Arc<dyn Signatory>) {
+        self.signatory = signatory;
+    }
+
+    /// Time P2PK locktimes and refused keysets of inputs are checked at,
+    /// [SystemClock] by default
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Seconds a swap response is cached for resubmissions of its request,
+    /// [DEFAULT_SWAP_RESPONSE_TTL] by default
+    ///
+    /// Expired responses are removed by [Mint::prune_expired_quotes].
+    pub fn set_swap_response_ttl(&mut self, swap_response_ttl: u64) {
+        self.swap_response_ttl = swap_response_ttl;
+    }
+
+    /// Rotate the active keyset of `unit` on a schedule, see
+    /// [Mint::rotate_if_due]
+    ///
+    /// `None` removes the policy of `unit`. Fails if the max order of the
+    /// policy is out of bounds.
+    pub fn set_rotation_policy(
+        &mut self,
+        unit: CurrencyUnit,
+        policy: Option<RotationPolicy>,
+    ) -> Result<(), Error> {
+        match policy {
+            Some(policy) => {
+                power_of_two_amounts(policy.max_order)?;
+                self.rotation_policies.insert(unit, policy)
+            }
+            None => self.rotation_policies.remove(&unit),
+        };
+
+        Ok(())
+    }
+
+    pub fn rotation_policy(&self, unit: &CurrencyUnit) -> Option<RotationPolicy> {
+        self.rotation_policies.get(unit).copied()
+    }
+
+    /// Reject requests over [RequestLimits] before doing any curve math
+    fn check_request_limits(
+        &self,
+        inputs: &[Proof],
+        outputs: &[BlindedMessage],
+    ) -> Result<(), Error> {
+        let limits = &self.request_limits;
+
+        if inputs.len() > limits.max_inputs {
+            return Err(Error::RequestTooLarge {
+                limit: "max_inputs",
+                max: limits.max_inputs as u64,
+            });
+        }
+
+        if outputs.len() > limits.max_outputs {
+            return Err(Error::RequestTooLarge {
+                limit: "max_outputs",
+                max: limits.max_outputs as u64,
+            });
+        }
+
+        if let Some(max_request_amount) = limits.max_request_amount {
+            let too_large = Error::RequestTooLarge {
+                limit: "max_request_amount",
+                max: max_request_amount.into(),
+            };
+
+            let input_total = inputs
+                .iter()
+                .try_fold(Amount::ZERO, |total, p| total.checked_add(p.amount));
+            let output_total = outputs
+                .iter()
+                .try_fold(Amount::ZERO, |total, b| total.checked_add(b.amount));
+
+            for total in [input_total, output_total] {
+                match total {
+                    Some(total) if total <= max_request_amount => (),
+                    _ => return Err(too_large),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Bolt11 is the only payment method of [Mint::new_mint_quote] and the
+    /// melt quotes, bolt12 mint quotes are created by
+    /// [Mint::new_mint_quote_bolt12]
+    fn check_payment_method(method: &PaymentMethod) -> Result<(), Error> {
+        match method {
+            PaymentMethod::Bolt11 => Ok(()),
+            method => Err(Error::UnsupportedMethod(method.clone())),
+        }
     }
 
     pub async fn new_mint_quote(
         &self,
+        method: PaymentMethod,
         request: String,
         unit: CurrencyUnit,
         amount: Amount,
         expiry: u64,
+        description: Option<InvoiceDescription>,
+    ) -> Result<MintQuote, Error> {
+        Self::check_payment_method(&method)?;
+
+        let max_description_length = self.request_limits.max_description_length;
+        if let Some(InvoiceDescription::Text(description)) = &description {
+            if description.len() > max_description_length {
+                return Err(Error::RequestTooLarge {
+                    limit: "max_description_length",
+                    max: max_description_length as u64,
+                });
+            }
+        }
+
+        let quote = MintQuote::new(request, unit, amount, expiry, description);
+
+        self.add_new_mint_quote(quote).await
+    }
+
+    /// Mint quote paid to the reusable BOLT12 `offer`
+    ///
+    /// `amount` is what the offer asks of each payment, [None] if the payer
+    /// chooses. Either way ecash is issued for what the offer has received,
+    /// see [Mint::record_offer_payment].
+    pub async fn new_mint_quote_bolt12(
+        &self,
+        offer: String,
+        unit: CurrencyUnit,
+        amount: Option<Amount>,
+        expiry: u64,
+        description: Option<String>,
     ) -> Result<MintQuote, Error> {
-        let quote = MintQuote::new(request, unit, amount, expiry);
+        let max_description_length = self.request_limits.max_description_length;
+        if description
+            .as_ref()
+            .is_some_and(|description| description.len() > max_description_length)
+        {
+            return Err(Error::RequestTooLarge {
+                limit: "max_description_length",
+                max: max_description_length as u64,
+            });
+        }
+
+        let quote = MintQuote::new_bolt12(offer, unit, amount, expiry, description);
+
+        self.add_new_mint_quote(quote).await
+    }
+
+    /// Store `quote` under an id no stored mint quote has
+    async fn add_new_mint_quote(&self, mut quote: MintQuote) -> Result<MintQuote, Error> {
+        let _guard = self.quote_lock.lock().await;
+        while self.localstore.get_mint_quote(&quote.id).await?.is_some() {
+            quote.id = Uuid::new_v4().to_string();
+        }
 
         self.localstore.add_mint_quote(quote.clone()).await?;
 
         Ok(quote)
     }
 
+    /// Store `quote` under an id no stored melt quote has
+    async fn add_new_melt_quote(&self, mut quote: MeltQuote) -> Result<MeltQuote, Error> {
+        let _guard = self.quote_lock.lock().await;
+        while self.localstore.get_melt_quote(&quote.id).await?.is_some() {
+            quote.id = Uuid::new_v4().to_string();
+        }
+
+        self.localstore.add_melt_quote(quote.clone()).await?;
+
+        Ok(quote)
+    }
+
+    pub async fn mint_quote(&self, quote_id: &str) -> Result<Option<MintQuote>, Error> {
+        Ok(self.localstore.get_mint_quote(quote_id).await?)
+    }
+
+    pub async fn melt_quote(&self, quote_id: &str) -> Result<Option<MeltQuote>, Error> {
+        Ok(self.localstore.get_melt_quote(quote_id).await?)
+    }
+
+    /// Mint quote for the invoice with `payment_hash`, as reported paid by
+    /// the Lightning backend
+    ///
+    /// If several quotes were created for one invoice, the latest.
+    pub async fn quote_by_payment_hash(
+        &self,
+        payment_hash: &crate::Sha256,
+    ) -> Result<Option<MintQuote>, Error> {
+        Ok(self
+            .localstore
+            .get_mint_quote_by_payment_hash(payment_hash)
+            .await?)
+    }
+
     pub async fn check_mint_quote(&self, quote_id: &str) -> Result<MintQuoteBolt11Response, Error> {
         let quote = self
             .localstore
@@ -170,11 +745,91 @@ impl Mint {
         })
     }
 
+    /// State of a bolt12 mint quote, [Error::UnknownQuote] for a quote of
+    /// another method
+    pub async fn check_mint_quote_bolt12(
+        &self,
+        quote_id: &str,
+    ) -> Result<MintQuoteBolt12Response, Error> {
+        let quote = self
+            .localstore
+            .get_mint_quote(quote_id)
+            .await?
+            .ok_or(Error::UnknownQuote)?;
+
+        MintQuoteBolt12Response::try_from(quote).map_err(|_| Error::UnknownQuote)
+    }
+
     pub async fn update_mint_quote(&self, quote: MintQuote) -> Result<(), Error> {
         self.localstore.add_mint_quote(quote).await?;
         Ok(())
     }
 
+    pub async fn update_melt_quote(&self, quote: MeltQuote) -> Result<(), Error> {
+        self.localstore.add_melt_quote(quote).await?;
+        Ok(())
+    }
+
+    /// Remove quotes that expired before `now` and are of no further use
+    ///
+    /// Unpaid mint quotes and unpaid melt quotes that are not pending are
+    /// removed. Mint quotes that are paid but not yet issued, and pending
+    /// melt quotes, are liabilities of the mint and are always kept, as are
+    /// settled quotes and quotes without an expiry. Cached swap responses
+    /// that expired by `now` are removed too.
+    pub async fn prune_expired_quotes(&self, now: u64) -> Result<PruneReport, Error> {
+        let _guard = self.quote_lock.lock().await;
+
+        let mut report = PruneReport::default();
+        let expired = |expiry: u64| expiry != 0 && expiry <= now;
+
+        let mut mint_quotes = Vec::new();
+        for quote in self.localstore.get_mint_quotes().await? {
+            let reason = if quote.paid && !quote.issued {
+                RetainReason::PaidNotIssued
+            } else if quote.issued {
+                RetainReason::Settled
+            } else if !expired(quote.expiry) {
+                RetainReason::Unexpired
+            } else {
+                mint_quotes.push(quote.id);
+                continue;
+            };
+            *report.retained.entry(reason).or_default() += 1;
+        }
+
+        let mut melt_quotes = Vec::new();
+        for quote in self.localstore.get_melt_quotes().await? {
+            let reason = if quote.pending {
+                RetainReason::Pending
+            } else if quote.paid {
+                RetainReason::Settled
+            } else if !expired(quote.expiry) {
+                RetainReason::Unexpired
+            } else {
+                melt_quotes.push(quote.id);
+                continue;
+            };
+            *report.retained.entry(reason).or_default() += 1;
+        }
+
+        self.localstore.remove_mint_quotes(&mint_quotes).await?;
+        self.localstore.remove_melt_quotes(&melt_quotes).await?;
+
+        report.removed_mint_quotes = mint_quotes.len();
+        report.removed_melt_quotes = melt_quotes.len();
+        report.removed_swap_responses = self.localstore.remove_expired_swap_responses(now).await?;
+
+        info!(
+            removed_mint_quotes = report.removed_mint_quotes,
+            removed_melt_quotes = report.removed_melt_quotes,
+            removed_swap_responses = report.removed_swap_responses,
+            "Pruned expired quotes"
+        );
+
+        Ok(report)
+    }
+
     pub async fn mint_quotes(&self) -> Result<Vec<MintQuote>, Error> {
         let quotes = self.localstore.get_mint_quotes().await?;
         Ok(quotes)
@@ -188,31 +843,177 @@ impl Mint {
 
     pub async fn new_melt_quote(
         &self,
+        method: PaymentMethod,
         request: String,
         unit: CurrencyUnit,
         amount: Amount,
-        fee_reserve: Amount,
         expiry: u64,
     ) -> Result<MeltQuote, Error> {
-        let quote = MeltQuote::new(request, unit, amount, fee_reserve, expiry);
+        Self::check_payment_method(&method)?;
 
-        self.localstore.add_melt_quote(quote.clone()).await?;
+        let mut quote = MeltQuote::new(request, unit, amount, self.fee_reserve(amount), expiry);
+        if quote.unit == CurrencyUnit::Sat {
+            self.estimate_fee_reserve(&mut quote, amount.to_msat())
+                .await;
+        }
 
-        Ok(quote)
+        self.add_new_melt_quote(quote).await
+    }
+
+    /// Melt quote for a NUT-05 quote request
+    ///
+    /// The amount of an amountless invoice comes from the request, and is
+    /// the amount the fee reserve and the melt inputs are checked against.
+    /// So is the part of the invoice of a [NUT-15] partial payment, which is
+    /// refused unless the mint info lists the unit as supported.
+    pub async fn new_melt_quote_bolt11(
+        &self,
+        request: &MeltQuoteBolt11Request,
+        expiry: u64,
+    ) -> Result<MeltQuote, Error> {
+        let amount_msat = request.amount_msat()?;
+
+        if request.options.is_some()
+            && !self
+                .localstore
+                .get_mint_info()
+                .await?
+                .nuts
+                .nut15
+                .supports(&PaymentMethod::Bolt11, &request.unit)
+        {
+            return Err(crate::error::Error::MppUnsupported.into());
+        }
+
+        match request.unit {
+            CurrencyUnit::Sat => {
+                self.new_melt_quote_msat(
+                    PaymentMethod::Bolt11,
+                    request.request.to_string(),
+                    amount_msat,
+                    expiry,
+                )
+                .await
+            }
+            _ => {
+                let mut quote = MeltQuote::new(
+                    request.request.to_string(),
+                    request.unit.clone(),
+                    request.invoice_amount()?,
+                    self.fee_reserve(request.invoice_amount()?),
+                    expiry,
+                );
+                quote.amount_msat = Some(amount_msat);
+
+                self.add_new_melt_quote(quote).await
+            }
+        }
+    }
+
+    /// Sat melt quote for an invoice of `amount_msat`
+    ///
+    /// The msat are recorded on the quote, its amount is the msat rounded up
+    /// to the next sat and the fee reserve is computed on the msat.
+    pub async fn new_melt_quote_msat(
+        &self,
+        method: PaymentMethod,
+        request: String,
+        amount_msat: u64,
+        expiry: u64,
+    ) -> Result<MeltQuote, Error> {
+        Self::check_payment_method(&method)?;
+
+        let fee_reserve = self.fee_reserve_policy().fee_reserve_msat(amount_msat);
+        let mut quote =
+            MeltQuote::new_msat(request, CurrencyUnit::Sat, amount_msat, fee_reserve, expiry);
+        self.estimate_fee_reserve(&mut quote, amount_msat).await;
+
+        self.add_new_melt_quote(quote).await
+    }
+
+    /// Replace the policy fee reserve of `quote` with the estimate of the
+    /// Lightning backend, if there is one
+    async fn estimate_fee_reserve(&self, quote: &mut MeltQuote, amount_msat: u64) {
+        let Some(ln_backend) = &self.ln_backend else {
+            return;
+        };
+        let Ok(bolt11) = Bolt11Invoice::from_str(&quote.request) else {
+            return;
+        };
+
+        if let Some(estimate) = ln_backend.estimate_fee(&bolt11, amount_msat).await {
+            quote.fee_reserve = with_estimate_margin(estimate);
+            quote.fee_estimator = FeeEstimator::Backend;
+            debug!(quote = %quote.id, %estimate, fee_reserve = %quote.fee_reserve, "Backend fee estimate");
+        }
+    }
+
+    /// Fee reserve the mint requires to melt `request`
+    pub fn check_fees(&self, request: &CheckFeesRequest) -> Result<CheckFeesResponse, Error> {
+        let amount_msat = request
+            .pr
+            .amount_milli_satoshis()
+            .ok_or(crate::error::Error::InvoiceAmountUndefined)?;
+
+        Ok(CheckFeesResponse {
+            fee: self.fee_reserve_policy().fee_reserve_msat(amount_msat),
+        })
+    }
+
+    /// Fee reserve required to melt `amount` under the current policy
+    pub fn fee_reserve(&self, amount: Amount) -> Amount {
+        self.fee_reserve_policy().fee_reserve(amount)
+    }
+
+    /// Current fee reserve policy
+    pub fn fee_reserve_policy(&self) -> FeeReserve {
+        self.fee_reserve
+            .read()
+            .expect("Fee reserve lock poisoned")
+            .clone()
+    }
+
+    /// Replace the fee reserve policy
+    ///
+    /// Shared by every clone of the mint. Quotes that already exist keep the
+    /// reserve they were created with.
+    pub fn set_fee_reserve_policy(&self, fee_reserve: FeeReserve) {
+        *self.fee_reserve.write().expect("Fee reserve lock poisoned") = fee_reserve;
+    }
+
+    /// Accounting counters of this mint and all its clones
+    pub fn accounting(&self) -> Accounting {
+        self.accounting
+            .read()
+            .expect("Accounting lock poisoned")
+            .clone()
+    }
+
+    /// Receive the [MintEvent]s of this mint and all its clones
+    ///
+    /// Only events published after subscribing are received. A subscriber
+    /// that falls more than 1024 events behind misses the oldest ones.
+    pub fn subscribe(&self) -> broadcast::Receiver<MintEvent> {
+        self.events.subscribe()
+    }
+
+    fn publish(&self, event: MintEvent) {
+        // No subscribers is not an error
+        let _ = self.events.send(event);
     }
 
     /// Retrieve the public keys of the active keyset for distribution to
     /// wallet clients
     pub async fn keyset_pubkeys(&self, keyset_id: &Id) -> Result<KeysResponse, Error> {
-        let keyset = match self.localstore.get_keyset(keyset_id).await? {
-            Some(keyset) => keyset.clone(),
+        let keyset = match self.keyset(keyset_id).await? {
+            Some(keyset) => keyset,
             None => {
                 return Err(Error::UnknownKeySet);
             }
         };
 
         Ok(KeysResponse {
-            keysets: vec![keyset.into()],
+            keysets: vec![keyset.as_ref().clone()],
         })
     }
 
@@ -228,6 +1029,58 @@ impl Mint {
 
     /// Return a list of all supported keysets
     pub async fn keysets(&self) -> Result<KeysetResponse, Error> {
+        Ok(KeysetResponse {
+            keysets: self.keyset_infos().await?,
+        })
+    }
+
+    /// [Mint::keysets] with each keyset that has a legacy id listed a second
+    /// time under it, for wallets from before version `00` ids
+    pub async fn keysets_with_legacy_ids(&self) -> Result<KeysetResponse, Error> {
+        let legacy_ids = self.legacy_ids.read().await.clone();
+
+        let mut keysets = Vec::new();
+        for info in self.keyset_infos().await? {
+            let aliases: Vec<KeySetInfo> = legacy_ids
+                .iter()
+                .filter(|(_, id)| **id == info.id)
+                .map(|(legacy_id, _)| KeySetInfo {
+                    id: *legacy_id,
+                    ..info.clone()
+                })
+                .collect();
+
+            keysets.push(info);
+            keysets.extend(aliases);
+        }
+
+        Ok(KeysetResponse { keysets })
+    }
+
+    /// Accept the legacy id of keyset `id` for its proofs
+    ///
+    /// The legacy id is derived from the keys with [Id::legacy_from_keys]; set
+    /// [MintKeySetInfo::legacy_id] instead if the keyset was known by another
+    /// one.
+    pub async fn register_legacy_id(&self, id: &Id) -> Result<Id, Error> {
+        let keyset = self.keyset(id).await?.ok_or(Error::UnknownKeySet)?;
+        let legacy_id = Id::legacy_from_keys(&keyset.keys);
+
+        self.legacy_ids.write().await.insert(legacy_id, keyset.id);
+
+        Ok(legacy_id)
+    }
+
+    /// Info of every keyset known to the mint
+    pub async fn keyset_infos(&self) -> Result<Vec<KeySetInfo>, Error> {
+        self.keysets_info().await
+    }
+
+    /// Info of every keyset with its validity and derivation, oldest first
+    ///
+    /// Purely informational, proofs are verified regardless of these
+    /// timestamps.
+    pub async fn keysets_info(&self) -> Result<Vec<KeySetInfo>, Error> {
         let keysets = self.localstore.get_keysets().await?;
         let active_keysets: HashSet<Id> = self
             .localstore
@@ -237,390 +1090,739 @@ impl Mint {
             .cloned()
             .collect();
 
-        let keysets = keysets
-            .into_iter()
-            .map(|k| KeySetInfo {
-                id: k.id,
-                unit: k.unit,
-                active: active_keysets.contains(&k.id),
+        let mut infos: Vec<KeySetInfo> = keysets
+            .iter()
+            .map(|keyset| KeySetInfo {
+                active: active_keysets.contains(&keyset.id),
+                ..KeySetInfo::from(keyset)
             })
             .collect();
+        infos.sort_by_cached_key(|info| (info.valid_from, info.id.to_string()));
 
-        Ok(KeysetResponse { keysets })
+        Ok(infos)
     }
 
-    pub async fn keyset(&self, id: &Id) -> Result<Option<KeySet>, Error> {
-        Ok(self
-            .localstore
-            .get_keyset(id)
-            .await?
-            .map(|ks| ks.clone().into()))
+    /// Add `keyset` without making it active
+    ///
+    /// Proofs of an inactive keyset can still be spent but no new outputs are
+    /// signed with it.
+    pub async fn add_inactive_keyset(&self, keyset: MintKeySet) -> Result<(), Error> {
+        if Id::from(&Keys::from(&keyset.keys)) != keyset.id {
+            return Err(Error::KeysetIdMismatch);
+        }
+        check_keyset_unit(self.localstore.as_ref(), &keyset).await?;
+
+        self.localstore.add_keyset(keyset.clone()).await?;
+        self.keysets.write().await.insert(keyset.id, keyset.into());
+
+        #[cfg(feature = "invariant-checks")]
+        self.rebase_ledger().await?;
+
+        Ok(())
     }
 
-    /// Add current keyset to inactive keysets
-    /// Generate new keyset
-    pub async fn rotate_keyset(
-        &mut self,
-        unit: CurrencyUnit,
-        derivation_path: &DerivationPath,
-        max_order: u8,
-    ) -> Result<(), Error> {
-        let new_keyset = MintKeySet::generate(
-            &self.mnemonic.to_seed_normalized(""),
-            unit.clone(),
-            derivation_path,
-            max_order,
-        );
+    /// Remove an inactive keyset, its proofs can no longer be spent
+    pub async fn remove_keyset(&self, id: &Id) -> Result<(), Error> {
+        let active = self.localstore.get_active_keysets().await?;
+        if active.values().any(|active_id| active_id == id) {
+            return Err(Error::ActiveKeyset);
+        }
 
-        self.localstore.add_keyset(new_keyset.clone()).await?;
+        self.localstore.remove_keyset(id).await?;
+        self.keysets.write().await.remove(id);
+        self.derivations.write().await.remove(id);
 
-        self.localstore
-            .add_active_keyset(unit, new_keyset.id)
-            .await?;
+        #[cfg(feature = "invariant-checks")]
+        self.rebase_ledger().await?;
 
         Ok(())
     }
 
-    pub async fn process_mint_request(
-        &mut self,
-        mint_request: nut04::MintBolt11Request,
-    ) -> Result<nut04::MintBolt11Response, Error> {
-        for blinded_message in &mint_request.outputs {
-            if self
-                .localstore
-                .get_blinded_signature(&blinded_message.blinded_secret)
-                .await?
-                .is_some()
-            {
-                error!(
-                    "Output has already been signed: {}",
-                    blinded_message.blinded_secret
-                );
-                return Err(Error::BlindedMessageAlreadySigned);
-            }
+    /// Refuse proofs of keyset `id` from unix time `refused_from` on
+    ///
+    /// `None` accepts the keyset's proofs again. The active keyset of a unit
+    /// cannot be refused.
+    pub async fn set_keyset_refused_from(
+        &self,
+        id: &Id,
+        refused_from: Option<u64>,
+    ) -> Result<(), Error> {
+        let active = self.localstore.get_active_keysets().await?;
+        if refused_from.is_some() && active.values().any(|active_id| active_id == id) {
+            return Err(Error::ActiveKeyset);
         }
 
-        let quote = self
+        let mut keyset = self
             .localstore
-            .get_mint_quote(&mint_request.quote)
+            .get_keyset(id)
             .await?
-            .ok_or(Error::UnknownQuote)?;
+            .ok_or(Error::UnknownKeySet)?;
+        keyset.refused_from = refused_from;
 
-        if !quote.paid {
-            return Err(Error::UnpaidQuote);
-        }
+        self.localstore.add_keyset(keyset.clone()).await?;
+        self.keysets.write().await.insert(*id, keyset.into());
 
-        let mut blind_signatures = Vec::with_capacity(mint_request.outputs.len());
+        Ok(())
+    }
 
-        for blinded_message in mint_request.outputs.into_iter() {
-            let blinded_signature = self.blind_sign(&blinded_message).await?;
-            self.localstore
-                .add_blinded_signature(blinded_message.blinded_secret, blinded_signature.clone())
-                .await?;
-            blind_signatures.push(blinded_signature);
+    /// Stop signing with keyset `id` and reject its proofs, for keys that may
+    /// have leaked
+    ///
+    /// Takes effect immediately, unlike [Mint::set_keyset_refused_from]. The
+    /// active keyset of a unit has to be rotated out before it can be frozen.
+    pub async fn freeze_keyset(&self, id: &Id, reason: String) -> Result<(), Error> {
+        let active = self.localstore.get_active_keysets().await?;
+        if active.values().any(|active_id| active_id == id) {
+            return Err(Error::ActiveKeyset);
         }
 
-        Ok(nut04::MintBolt11Response {
-            signatures: blind_signatures,
-        })
+        self.set_keyset_frozen(id, Some(reason)).await?;
+        warn!(keyset = %id, "Froze keyset");
+
+        Ok(())
     }
 
-    async fn blind_sign(&self, blinded_message: &BlindedMessage) -> Result<BlindSignature, Error> {
-        let BlindedMessage {
-            amount,
-            blinded_secret,
-            keyset_id,
-            ..
-        } = blinded_message;
+    /// Accept proofs of a frozen keyset again
+    pub async fn unfreeze_keyset(&self, id: &Id) -> Result<(), Error> {
+        self.set_keyset_frozen(id, None).await?;
+        info!(keyset = %id, "Unfroze keyset");
 
-        let keyset = self
+        Ok(())
+    }
+
+    async fn set_keyset_frozen(&self, id: &Id, frozen: Option<String>) -> Result<(), Error> {
+        let mut keyset = self
             .localstore
-            .get_keyset(keyset_id)
+            .get_keyset(id)
             .await?
             .ok_or(Error::UnknownKeySet)?;
+        keyset.frozen = frozen;
 
-        let active = self
-            .localstore
-            .get_active_keyset_id(&keyset.unit)
-            .await?
-            .ok_or(Error::InactiveKeyset)?;
+        self.localstore.add_keyset(keyset.clone()).await?;
+        self.keysets.write().await.insert(*id, keyset.into());
 
-        // Check that the keyset is active and should be used to sign
-        if keyset.id.ne(&active) {
-            return Err(Error::InactiveKeyset);
+        Ok(())
+    }
+
+    /// Move the spent secrets of a refused keyset out of the live store
+    ///
+    /// Once a keyset is refused its proofs can never be valid again, so
+    /// their spent records only take up space. The returned archive can be
+    /// kept offline and restored with [Mint::import_archive] if the keyset
+    /// is accepted again.
+    pub async fn archive_spent_for_keyset(&self, id: &Id) -> Result<ArchivedSpend, Error> {
+        let active = self.localstore.get_active_keysets().await?;
+        if active.values().any(|active_id| active_id == id) {
+            return Err(Error::ActiveKeyset);
         }
 
-        let Some(key_pair) = keyset.keys.get(amount) else {
-            // No key for amount
-            return Err(Error::AmountKey);
-        };
+        let keyset = self.get_keyset(id).await?.ok_or(Error::UnknownKeySet)?;
+        let now = unix_time();
+        if !keyset.is_refused(now) {
+            return Err(Error::AcceptedKeyset);
+        }
 
-        let c = sign_message(&key_pair.secret_key, blinded_secret)?;
+        let proofs = self.localstore.get_spent_proofs_by_keyset(id).await?;
+        let archive = ArchivedSpend::new(*id, now, proofs)?;
 
-        let blinded_signature = BlindSignature::new(
-            *amount,
-            c,
-            keyset.id,
-            &blinded_message.blinded_secret,
-            key_pair.secret_key.clone(),
-        )?;
+        let ys = archive.ys()?;
+        self.localstore.remove_spent_proofs(&ys).await?;
 
-        Ok(blinded_signature)
+        #[cfg(feature = "invariant-checks")]
+        self.rebase_ledger().await?;
+
+        info!(keyset = %id, proofs = ys.len(), "Archived spent proofs");
+
+        Ok(archive)
     }
 
-    pub async fn process_swap_request(
-        &mut self,
-        swap_request: SwapRequest,
-    ) -> Result<SwapResponse, Error> {
-        for blinded_message in &swap_request.outputs {
-            if self
+    /// Restore the spent secrets of `archive` to the live store
+    pub async fn import_archive(&self, archive: &ArchivedSpend) -> Result<(), Error> {
+        if !archive.verify()? {
+            return Err(Error::ArchiveCommitment);
+        }
+
+        self.import_spent_proofs(archive.proofs.iter().cloned())
+            .await
+    }
+
+    /// Number of spent proofs recorded for keyset `id`
+    pub async fn spent_count_for_keyset(&self, id: &Id) -> Result<u64, Error> {
+        Ok(self.localstore.get_spent_count_by_keyset(id).await?)
+    }
+
+    /// Per keyset statement of issued, redeemed and outstanding ecash
+    ///
+    /// The report is signed by the [identity key](Mint::identity_pubkey) of
+    /// the mint, see [AuditReport::verify].
+    pub async fn generate_audit_report(&self, now: u64) -> Result<AuditReport, Error> {
+        let legacy_ids = self.legacy_ids.read().await.clone();
+
+        let mut keysets = Vec::new();
+        for info in self.keysets_info().await? {
+            let keyset = self
+                .get_keyset(&info.id)
+                .await?
+                .ok_or(Error::UnknownKeySet)?;
+
+            let issued = self
                 .localstore
-                .get_blinded_signature(&blinded_message.blinded_secret)
+                .get_blinded_signatures_by_keyset(&info.id)
                 .await?
-                .is_some()
-            {
-                error!(
-                    "Output has already been signed: {}",
-                    blinded_message.blinded_secret
+                .iter()
+                .try_fold(Amount::ZERO, |total, signature| {
+                    total.checked_add(signature.amount)
+                })
+                .ok_or(nut00::Error::AmountOverflow)?;
+            // Proofs spent under a legacy id are redeemed from the same keyset
+            let mut spent = self.localstore.get_spent_proofs_by_keyset(&info.id).await?;
+            for (legacy_id, _) in legacy_ids.iter().filter(|(_, id)| **id == info.id) {
+                spent.extend(
+                    self.localstore
+                        .get_spent_proofs_by_keyset(legacy_id)
+                        .await?,
                 );
-                return Err(Error::BlindedMessageAlreadySigned);
             }
+            let redeemed = spent.total_amount()?;
+
+            keysets.push(KeysetAudit {
+                id: info.id,
+                unit: info.unit,
+                active: info.active,
+                issued,
+                redeemed,
+                outstanding: issued.saturating_sub(redeemed),
+                spent_proofs: spent.len() as u64,
+                keys: Keys::from(&keyset.keys),
+            });
         }
+        keysets.sort_by_cached_key(|keyset| keyset.id.to_string());
 
-        let proofs_total = swap_request.input_amount();
+        let mut report = AuditReport {
+            generated_at: now,
+            pubkey: Some(self.identity_pubkey()),
+            keysets,
+            signature: None,
+        };
+        let signature = self.sign_with_identity(&report.canonical_bytes()?);
+        report.signature = Some(signature.to_string());
 
-        let output_total = swap_request.output_amount();
+        Ok(report)
+    }
 
-        if proofs_total != output_total {
-            return Err(Error::Amount);
-        }
+    /// Invariants of the mint's state that do not hold
+    ///
+    /// For every unit the issued ecash has to be the redeemed ecash plus the
+    /// outstanding ecash the mint's operations account for, and no input
+    /// reserved by a melt may be both pending and spent. Operations that
+    /// change the mint's liabilities outside of a mint, swap or melt, such as
+    /// importing spent proofs, take the state they leave as the new opening
+    /// balance. Operations running concurrently on clones of the mint can
+    /// be seen half done.
+    #[cfg(feature = "invariant-checks")]
+    pub async fn check_invariants(&self) -> Result<Vec<InvariantViolation>, Error> {
+        let totals = self.ledger_totals().await?;
+        let ledger = self.ledger.lock().expect("Ledger lock poisoned").clone();
 
-        let proof_count = swap_request.inputs.len();
+        let mut units: Vec<&CurrencyUnit> = totals.keys().chain(ledger.units()).collect();
+        units.sort_by_cached_key(|unit| unit.to_string());
+        units.dedup();
 
-        let secrets: HashSet<[u8; 33]> = swap_request
-            .inputs
-            .iter()
-            .flat_map(|p| hash_to_curve(&p.secret.to_bytes()))
-            .map(|p| p.to_bytes())
-            .collect();
+        let mut violations = Vec::new();
+        for unit in units {
+            let (issued, redeemed) = totals.get(unit).copied().unwrap_or_default();
+            let outstanding = ledger.outstanding(unit);
+            let difference =
+                i128::from(u64::from(issued)) - i128::from(u64::from(redeemed)) - outstanding;
 
-        // Check that there are no duplicate proofs in request
-        if secrets.len().ne(&proof_count) {
-            return Err(Error::DuplicateProofs);
+            if difference != 0 {
+                violations.push(InvariantViolation::Ledger {
+                    unit: unit.clone(),
+                    issued,
+                    redeemed,
+                    outstanding,
+                    difference,
+                });
+            }
         }
 
-        for proof in &swap_request.inputs {
-            self.verify_proof(proof).await?
+        for quote in self.localstore.get_melt_quotes().await? {
+            for secret in &quote.reserved_inputs {
+                let Some(pending) = self.localstore.get_pending_proof_by_secret(secret).await?
+                else {
+                    continue;
+                };
+                if self
+                    .localstore
+                    .get_spent_proof_by_secret(secret)
+                    .await?
+                    .is_some()
+                {
+                    violations.push(InvariantViolation::PendingAndSpent {
+                        quote_id: quote.id.clone(),
+                        y: pending.y()?,
+                    });
+                }
+            }
         }
 
-        let input_keyset_ids: HashSet<Id> =
-            swap_request.inputs.iter().map(|p| p.keyset_id).collect();
+        Ok(violations)
+    }
 
-        let mut keyset_units = HashSet::with_capacity(input_keyset_ids.capacity());
+    /// Panic in debug builds, or log an error otherwise, if
+    /// [Mint::check_invariants] finds a violation after `operation`
+    #[cfg(feature = "invariant-checks")]
+    async fn assert_invariants(&self, operation: &'static str) {
+        let violations = match self.check_invariants().await {
+            Ok(violations) if violations.is_empty() => return,
+            Ok(violations) => violations,
+            Err(err) => {
+                error!(operation, "Could not check mint invariants: {err}");
+                return;
+            }
+        };
 
-        for id in input_keyset_ids {
-            let keyset = self
-                .localstore
-                .get_keyset(&id)
-                .await?
-                .ok_or(Error::UnknownKeySet)?;
-            keyset_units.insert(keyset.unit);
+        let diff = serde_json::to_string(&violations).unwrap_or_else(|_| format!("{violations:?}"));
+        if cfg!(debug_assertions) {
+            panic!("Mint invariants violated after {operation}: {diff}");
         }
+        error!(operation, violations = %diff, "Mint invariants violated");
+    }
 
-        let output_keyset_ids: HashSet<Id> =
-            swap_request.outputs.iter().map(|p| p.keyset_id).collect();
+    /// Open the ledger again with the outstanding ecash in the database
+    #[cfg(feature = "invariant-checks")]
+    async fn rebase_ledger(&self) -> Result<(), Error> {
+        let outstanding = self
+            .ledger_totals()
+            .await?
+            .into_iter()
+            .map(|(unit, (issued, redeemed))| {
+                let outstanding = i128::from(u64::from(issued)) - i128::from(u64::from(redeemed));
+                (unit, outstanding)
+            })
+            .collect();
 
-        for id in &output_keyset_ids {
-            let keyset = self
-                .localstore
-                .get_keyset(id)
-                .await?
-                .ok_or(Error::UnknownKeySet)?;
+        *self.ledger.lock().expect("Ledger lock poisoned") = Ledger::open(outstanding);
 
-            keyset_units.insert(keyset.unit);
-        }
+        Ok(())
+    }
 
-        // Check that all proofs are the same unit
-        // in the future it maybe possible to support multiple units but unsupported for
-        // now
-        if keyset_units.len().gt(&1) {
-            error!("Only one unit is allowed in request: {:?}", keyset_units);
-            return Err(Error::MultipleUnits);
+    #[cfg(feature = "invariant-checks")]
+    fn record_ledger(&self, record: impl FnOnce(&mut Ledger)) {
+        record(&mut self.ledger.lock().expect("Ledger lock poisoned"));
+    }
+
+    /// Issued and redeemed ecash of each unit, from the [audit report](Mint::generate_audit_report)
+    #[cfg(feature = "invariant-checks")]
+    async fn ledger_totals(&self) -> Result<HashMap<CurrencyUnit, (Amount, Amount)>, Error> {
+        let mut totals: HashMap<CurrencyUnit, (Amount, Amount)> = HashMap::new();
+        for keyset in self.generate_audit_report(0).await?.keysets {
+            let (issued, redeemed) = totals.entry(keyset.unit).or_default();
+            *issued = issued
+                .checked_add(keyset.issued)
+                .ok_or(nut00::Error::AmountOverflow)?;
+            *redeemed = redeemed
+                .checked_add(keyset.redeemed)
+                .ok_or(nut00::Error::AmountOverflow)?;
         }
 
-        for proof in swap_request.inputs {
+        Ok(totals)
+    }
+
+    /// Mark `proofs` as spent, e.g. when migrating from another mint
+    ///
+    /// Imported proofs are no longer pending.
+    pub async fn import_spent_proofs<I>(&self, proofs: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = Proof>,
+    {
+        for proof in proofs {
+            self.localstore.remove_pending_proof(&proof.secret).await?;
             self.localstore.add_spent_proof(proof).await?;
         }
 
-        let mut promises = Vec::with_capacity(swap_request.outputs.len());
+        #[cfg(feature = "invariant-checks")]
+        self.rebase_ledger().await?;
 
-        for blinded_message in swap_request.outputs {
-            let blinded_signature = self.blind_sign(&blinded_message).await?;
-            self.localstore
-                .add_blinded_signature(blinded_message.blinded_secret, blinded_signature.clone())
-                .await?;
-            promises.push(blinded_signature);
-        }
+        Ok(())
+    }
 
-        Ok(SwapResponse::new(promises))
+    /// Number of spent proofs
+    pub async fn spent_count(&self) -> Result<usize, Error> {
+        Ok(self.localstore.get_spent_ys().await?.len())
     }
 
-    async fn verify_proof(&self, proof: &Proof) -> Result<(), Error> {
-        // Check if secret is a nut10 secret with conditions
-        if let Ok(secret) =
-            <&crate::secret::Secret as TryInto<crate::nuts::nut10::Secret>>::try_into(&proof.secret)
-        {
-            // Verify if p2pk
-            if secret.kind.eq(&Kind::P2PK) {
-                proof.verify_p2pk()?;
-            } else {
-                return Err(Error::UnknownSecretKind);
+    /// Keyset by `id`, read from the database on first use
+    ///
+    /// A legacy id is looked up by the current id it was registered for.
+    async fn cached_keyset(&self, id: &Id) -> Result<Option<CachedKeySet>, Error> {
+        let id = &if id.is_legacy() {
+            match self.legacy_ids.read().await.get(id) {
+                Some(id) => *id,
+                None => return Ok(None),
             }
-        }
-
-        let y: PublicKey = hash_to_curve(&proof.secret.to_bytes())?;
+        } else {
+            *id
+        };
 
-        if self.localstore.get_spent_proof_by_y(&y).await?.is_some() {
-            return Err(Error::TokenSpent);
+        if let Some(keyset) = self.keysets.read().await.get(id) {
+            return Ok(Some(keyset.clone()));
         }
 
-        if self.localstore.get_pending_proof_by_y(&y).await?.is_some() {
-            return Err(Error::TokenPending);
+        match self.localstore.get_keyset(id).await? {
+            Some(keyset) => {
+                let keyset = CachedKeySet::from(keyset);
+                self.keysets.write().await.insert(*id, keyset.clone());
+                Ok(Some(keyset))
+            }
+            None => Ok(None),
         }
+    }
 
+    /// Split melt change over the denominations of the change outputs' keyset
+    ///
+    /// Any remainder the denominations cannot make is burnt, like change
+    /// that does not fit in the outputs provided.
+    async fn split_change(
+        &self,
+        change: Amount,
+        outputs: &[BlindedMessage],
+    ) -> Result<Vec<Amount>, Error> {
+        let keyset_id = match outputs.first() {
+            Some(output) => output.keyset_id,
+            None => return Ok(change.split()),
+        };
         let keyset = self
-            .localstore
-            .get_keyset(&proof.keyset_id)
+            .cached_keyset(&keyset_id)
             .await?
             .ok_or(Error::UnknownKeySet)?;
+        let denominations: Vec<Amount> = keyset.keyset.keys.keys().copied().collect();
 
-        let Some(keypair) = keyset.keys.get(&proof.amount) else {
-            return Err(Error::AmountKey);
-        };
+        let mut amounts = Vec::new();
+        let mut remaining = change;
+        while let Some(&largest) = denominations.iter().rev().find(|d| **d <= remaining) {
+            amounts.push(largest);
+            remaining -= largest;
+        }
 
-        verify_message(&keypair.secret_key, proof.c, proof.secret.as_bytes())?;
+        Ok(amounts)
+    }
 
-        Ok(())
+    async fn get_keyset(&self, id: &Id) -> Result<Option<Arc<MintKeySet>>, Error> {
+        Ok(self.cached_keyset(id).await?.map(|cached| cached.keyset))
     }
 
-    pub async fn check_state(
-        &self,
-        check_state: &CheckStateRequest,
-    ) -> Result<CheckStateResponse, Error> {
-        let mut states = Vec::with_capacity(check_state.ys.len());
+    /// Fee for spending `proofs`, the sum of the `input_fee_ppk` of their
+    /// keysets rounded up to a whole unit [NUT-02]
+    pub async fn input_fee(&self, proofs: &Proofs) -> Result<Amount, Error> {
+        let mut fee_ppk: u64 = 0;
 
-        for y in &check_state.ys {
-            let state = if self.localstore.get_spent_proof_by_y(y).await?.is_some() {
-                State::Spent
-            } else if self.localstore.get_pending_proof_by_y(y).await?.is_some() {
-                State::Pending
-            } else {
-                State::Unspent
-            };
+        for proof in proofs {
+            let keyset = self
+                .get_keyset(&proof.keyset_id)
+                .await?
+                .ok_or(Error::UnknownKeySet)?;
+            fee_ppk = fee_ppk.saturating_add(keyset.input_fee_ppk);
+        }
 
-            states.push(ProofState {
-                y: *y,
-                state,
-                witness: None,
-            })
+        Ok(Amount::from(
+            fee_ppk / 1000 + u64::from(fee_ppk % 1000 != 0),
+        ))
+    }
+
+    /// Public keys of keyset, shared between calls
+    pub async fn keyset(&self, id: &Id) -> Result<Option<Arc<KeySet>>, Error> {
+        Ok(self.cached_keyset(id).await?.map(|cached| cached.public))
+    }
+
+    /// Public keys of the active keyset of `unit`
+    async fn active_keys(&self, unit: &CurrencyUnit) -> Result<Option<Arc<KeySet>>, Error> {
+        match self.localstore.get_active_keyset_id(unit).await? {
+            Some(id) => self.keyset(&id).await,
+            None => Ok(None),
         }
-        Ok(CheckStateResponse { states })
     }
 
-    pub async fn verify_melt_request(
-        &mut self,
-        melt_request: &MeltBolt11Request,
-    ) -> Result<MeltQuote, Error> {
-        let quote = self
-            .localstore
-            .get_melt_quote(&melt_request.quote)
+    /// Amounts the active keyset of `unit` signs, smallest first, none if
+    /// the unit has no active keyset
+    pub async fn amounts(&self, unit: &CurrencyUnit) -> Result<Vec<Amount>, Error> {
+        Ok(self
+            .active_keys(unit)
             .await?
-            .ok_or(Error::UnknownQuote)?;
+            .map(|keyset| keyset.keys.amounts())
+            .unwrap_or_default())
+    }
+
+    /// Largest single output the active keyset of `unit` signs
+    pub async fn max_amount(&self, unit: &CurrencyUnit) -> Result<Option<Amount>, Error> {
+        Ok(self
+            .active_keys(unit)
+            .await?
+            .and_then(|keyset| keyset.keys.max_amount()))
+    }
+
+    /// Whether the active keyset of `unit` signs outputs of `amount`
+    pub async fn contains_amount(
+        &self,
+        unit: &CurrencyUnit,
+        amount: Amount,
+    ) -> Result<bool, Error> {
+        Ok(self
+            .active_keys(unit)
+            .await?
+            .is_some_and(|keyset| keyset.keys.contains_amount(amount)))
+    }
+
+    /// Add current keyset to inactive keysets
+    /// Generate new keyset
+    pub async fn rotate_keyset(
+        &mut self,
+        unit: CurrencyUnit,
+        derivation_path: &DerivationPath,
+        max_order: u8,
+    ) -> Result<(), Error> {
+        self.rotate_keyset_with_amounts(unit, derivation_path, &power_of_two_amounts(max_order)?)
+            .await
+    }
 
-        let proofs_total = melt_request.proofs_amount();
+    /// Rotate to a new keyset with keys for exactly `amounts`
+    ///
+    /// For keysets with only a few large or decimal denominations, such as
+    /// 1, 5, 10 and 50 for fiat units.
+    pub async fn rotate_keyset_with_amounts(
+        &mut self,
+        unit: CurrencyUnit,
+        derivation_path: &DerivationPath,
+        amounts: &[u64],
+    ) -> Result<(), Error> {
+        self.rotate_keyset_at(unit, derivation_path, amounts, unix_time())
+            .await?;
 
-        let required_total = quote.amount + quote.fee_reserve;
+        Ok(())
+    }
 
-        if proofs_total < required_total {
-            debug!(
-                "Insufficient Proofs: Got: {}, Required: {}",
-                proofs_total, required_total
-            );
-            return Err(Error::Amount);
+    /// Whether the active keyset of a unit with a [RotationPolicy] is due to
+    /// be rotated at unix time `now`
+    pub async fn rotation_due(&self, now: u64) -> Result<bool, Error> {
+        for (unit, policy) in &self.rotation_policies {
+            if self.rotation_due_for(unit, policy, now).await?.is_some() {
+                return Ok(true);
+            }
         }
 
-        let input_keyset_ids: HashSet<Id> =
-            melt_request.inputs.iter().map(|p| p.keyset_id).collect();
+        Ok(false)
+    }
+
+    /// Rotate the active keysets that are due under their [RotationPolicy]
+    ///
+    /// Meant to be called periodically by the server embedding the mint. A
+    /// keyset is rotated once its validity is older than the policy interval,
+    /// however late this is called, and the next rotation is measured from
+    /// the new keyset. The new keyset is derived at the next unused index of
+    /// its unit, so the rotation is reproducible from the mnemonic. Proofs of
+    /// the old keyset are refused `keep_accepting_for` seconds after `now`,
+    /// and a [MintEvent::KeysetRotated] is published. Returns the ids of the
+    /// new keysets.
+    pub async fn rotate_if_due(&mut self, now: u64) -> Result<Vec<Id>, Error> {
+        let policies: Vec<(CurrencyUnit, RotationPolicy)> = self
+            .rotation_policies
+            .iter()
+            .map(|(unit, policy)| (unit.clone(), *policy))
+            .collect();
 
-        let mut keyset_units = HashSet::with_capacity(input_keyset_ids.capacity());
+        let mut rotated = Vec::new();
 
-        for id in input_keyset_ids {
-            let keyset = self
+        for (unit, policy) in policies {
+            let Some(old_id) = self.rotation_due_for(&unit, &policy, now).await? else {
+                continue;
+            };
+
+            let index = self
                 .localstore
-                .get_keyset(&id)
+                .get_keysets()
                 .await?
-                .ok_or(Error::UnknownKeySet)?;
-            keyset_units.insert(keyset.unit);
+                .iter()
+                .filter(|keyset| keyset.unit == unit)
+                .filter_map(|keyset| keyset.derivation_path_index)
+                .max()
+                .map_or(0, |index| index + 1);
+            let derivation_path = derivation_path_from_unit(&unit, index)?;
+
+            let new_id = self
+                .rotate_keyset_at(
+                    unit.clone(),
+                    &derivation_path,
+                    &power_of_two_amounts(policy.max_order)?,
+                    now,
+                )
+                .await?;
+
+            let refused_from = now.saturating_add(policy.keep_accepting_for);
+            self.set_keyset_refused_from(&old_id, Some(refused_from))
+                .await?;
+
+            info!(unit = %unit, old_keyset = %old_id, keyset = %new_id, refused_from, "Rotated keyset on schedule");
+            self.publish(MintEvent::KeysetRotated {
+                unit,
+                old_id,
+                new_id,
+                refused_from,
+            });
+            rotated.push(new_id);
         }
 
-        if let Some(outputs) = &melt_request.outputs {
-            let output_keysets_ids: HashSet<Id> = outputs.iter().map(|b| b.keyset_id).collect();
-            for id in output_keysets_ids {
-                let keyset = self
-                    .localstore
-                    .get_keyset(&id)
-                    .await?
-                    .ok_or(Error::UnknownKeySet)?;
+        Ok(rotated)
+    }
 
-                // Get the active keyset for the unit
-                let active_keyset_id = self
-                    .localstore
-                    .get_active_keyset_id(&keyset.unit)
-                    .await?
-                    .ok_or(Error::InactiveKeyset)?;
+    /// Active keyset of `unit` if it is due under `policy` at `now`
+    async fn rotation_due_for(
+        &self,
+        unit: &CurrencyUnit,
+        policy: &RotationPolicy,
+        now: u64,
+    ) -> Result<Option<Id>, Error> {
+        let Some(id) = self.localstore.get_active_keyset_id(unit).await? else {
+            return Ok(None);
+        };
+        let Some(keyset) = self.localstore.get_keyset(&id).await? else {
+            return Ok(None);
+        };
+
+        Ok((keyset.valid_from.saturating_add(policy.interval) <= now).then_some(id))
+    }
 
-                // Check output is for current active keyset
-                if id.ne(&active_keyset_id) {
-                    return Err(Error::InactiveKeyset);
+    /// Rotate to a new keyset valid from unix time `now`, returning its id
+    async fn rotate_keyset_at(
+        &mut self,
+        unit: CurrencyUnit,
+        derivation_path: &DerivationPath,
+        amounts: &[u64],
+        now: u64,
+    ) -> Result<Id, Error> {
+        let span = info_span!("rotate_keyset", unit = %unit, denominations = amounts.len());
+
+        async move {
+            check_denominations(amounts)?;
+
+            let mut new_keyset = MintKeySet::generate_with_amounts(
+                &self.mnemonic.to_seed_normalized(""),
+                unit.clone(),
+                derivation_path,
+                amounts,
+            );
+            new_keyset.valid_from = now;
+            check_keyset_unit(self.localstore.as_ref(), &new_keyset).await?;
+
+            if let Some(old_id) = self.localstore.get_active_keyset_id(&unit).await? {
+                if let Some(mut old_keyset) = self.localstore.get_keyset(&old_id).await? {
+                    // The fee carries over to the new keyset
+                    new_keyset.input_fee_ppk = old_keyset.input_fee_ppk;
+                    if old_id != new_keyset.id && old_keyset.valid_to.is_none() {
+                        old_keyset.valid_to = Some(now);
+                        self.localstore.add_keyset(old_keyset.clone()).await?;
+                        self.keysets.write().await.insert(old_id, old_keyset.into());
+                    }
                 }
-                keyset_units.insert(keyset.unit);
             }
-        }
 
-        // Check that all input and output proofs are the same unit
-        if keyset_units.len().gt(&1) {
-            return Err(Error::MultipleUnits);
+            self.localstore.add_keyset(new_keyset.clone()).await?;
+
+            self.localstore
+                .add_active_keyset(unit, new_keyset.id)
+                .await?;
+
+            self.derivations.write().await.insert(
+                new_keyset.id,
+                KeySetDerivation {
+                    derivation_path: derivation_path.clone(),
+                    amounts: amounts.to_vec(),
+                },
+            );
+            info!(keyset = %new_keyset.id, "Rotated keyset");
+
+            let id = new_keyset.id;
+            self.keysets.write().await.insert(id, new_keyset.into());
+
+            Ok(id)
         }
+        .instrument(span)
+        .await
+    }
 
-        let secrets: HashSet<[u8; 33]> = melt_request
-            .inputs
-            .iter()
-            .flat_map(|p| hash_to_curve(&p.secret.to_bytes()))
-            .map(|p| p.to_bytes())
+    /// Check the stored keysets are intact before serving requests
+    ///
+    /// For every keyset in the database the id is recomputed from its public
+    /// keys, every secret key signs a throwaway message whose DLEQ proof is
+    /// verified against the stored public key, and keysets generated by this
+    /// mint are re-derived from the mnemonic and compared.
+    pub async fn self_check(&self) -> Result<SelfCheckReport, Error> {
+        let active_ids: HashSet<Id> = self
+            .localstore
+            .get_active_keysets()
+            .await?
+            .into_values()
             .collect();
+        let derivations = self.derivations.read().await;
+        let seed = self.mnemonic.to_seed_normalized("");
+        let message = hash_to_curve(b"cdk mint self check")?;
 
-        // Ensure proofs are unique and not being double spent
-        if melt_request.inputs.len().ne(&secrets.len()) {
-            return Err(Error::DuplicateProofs);
-        }
+        let mut keysets = Vec::new();
+
+        for keyset in self.localstore.get_keysets().await? {
+            let id_matches = Id::from(&Keys::from(&keyset.keys)) == keyset.id;
+
+            let keys_valid = keyset.keys.iter().all(|(amount, keypair)| {
+                sign_message(&keypair.secret_key, &message)
+                    .ok()
+                    .and_then(|c| {
+                        BlindSignature::new(*amount, c, keyset.id, &message, &keypair.secret_key)
+                            .ok()
+                    })
+                    .is_some_and(|signature| {
+                        signature.verify_dleq(keypair.public_key, message).is_ok()
+                    })
+            });
 
-        for proof in &melt_request.inputs {
-            self.verify_proof(proof).await?;
+            let derivation_matches = derivations.get(&keyset.id).map(|derivation| {
+                let derived = MintKeySet::generate_with_amounts(
+                    &seed,
+                    keyset.unit.clone(),
+                    &derivation.derivation_path,
+                    &derivation.amounts,
+                );
+                derived.id == keyset.id && derived.keys == keyset.keys
+            });
+
+            keysets.push(KeySetCheck {
+                id: keyset.id,
+                unit: keyset.unit,
+                active: active_ids.contains(&keyset.id),
+                id_matches,
+                keys_valid,
+                derivation_matches,
+            });
         }
 
-        Ok(quote)
+        Ok(SelfCheckReport { keysets })
     }
 
-    pub async fn process_melt_request(
+    pub async fn process_mint_request(
         &mut self,
-        melt_request: &MeltBolt11Request,
-        preimage: &str,
-        total_spent: Amount,
-    ) -> Result<MeltBolt11Response, Error> {
-        self.verify_melt_request(melt_request).await?;
+        mint_request: nut04::MintBolt11Request,
+    ) -> Result<nut04::MintBolt11Response, Error> {
+        let amount = mint_request.total_amount()?;
+        let span = info_span!(
+            "process_mint_request",
+            quote = %mint_request.quote,
+            outputs = mint_request.outputs.len(),
+            %amount,
+            keysets = %keyset_ids(mint_request.outputs.iter().map(|b| b.keyset_id)),
+        );
+
+        let result = async {
+            self.check_request_limits(&[], &mint_request.outputs)?;
 
-        if let Some(outputs) = &melt_request.outputs {
-            for blinded_message in outputs {
+            for blinded_message in &mint_request.outputs {
                 if self
                     .localstore
                     .get_blinded_signature(&blinded_message.blinded_secret)
@@ -634,136 +1836,7170 @@ impl Mint {
                     return Err(Error::BlindedMessageAlreadySigned);
                 }
             }
-        }
-
-        for input in &melt_request.inputs {
-            self.localstore.add_spent_proof(input.clone()).await?;
-        }
-
-        let mut change = None;
 
-        if let Some(outputs) = melt_request.outputs.clone() {
-            let change_target = melt_request.proofs_amount() - total_spent;
-            let mut amounts = change_target.split();
-            let mut change_sigs = Vec::with_capacity(amounts.len());
+            let quote_lock = Arc::clone(&self.quote_lock);
+            let _guard = quote_lock.lock().await;
 
-            if outputs.len().lt(&amounts.len()) {
-                debug!(
-                    "Providing change requires {} blinded messages, but only {} provided",
-                    amounts.len(),
-                    outputs.len()
-                );
+            let mut quote = self
+                .localstore
+                .get_mint_quote(&mint_request.quote)
+                .await?
+                .ok_or(Error::UnknownQuote)?;
 
-                // In the case that not enough outputs are provided to return all change
-                // Reverse sort the amounts so that the most amount of change possible is
-                // returned. The rest is burnt
-                amounts.sort_by(|a, b| b.cmp(a));
+            if !quote.paid {
+                return Err(Error::UnpaidQuote);
             }
 
-            for (amount, blinded_message) in amounts.iter().zip(outputs) {
-                let mut blinded_message = blinded_message;
-                blinded_message.amount = *amount;
-
-                let blinded_signature = self.blind_sign(&blinded_message).await?;
-                self.localstore
-                    .add_blinded_signature(
-                        blinded_message.blinded_secret,
-                        blinded_signature.clone(),
-                    )
-                    .await?;
-                change_sigs.push(blinded_signature)
+            if quote.method == PaymentMethod::Bolt12 {
+                // Any part of what the offer received can be issued
+                let mintable = quote.mintable();
+                if mintable == Amount::ZERO {
+                    return Err(Error::IssuedQuote);
+                }
+                if amount == Amount::ZERO || amount > mintable {
+                    debug!(%mintable, "Outputs exceed the amount received and not issued");
+                    return Err(Error::Amount);
+                }
+            } else {
+                if quote.issued {
+                    return Err(Error::IssuedQuote);
+                }
+
+                if amount != quote.amount {
+                    debug!(quote_amount = %quote.amount, "Outputs do not match the quote amount");
+                    return Err(Error::Amount);
+                }
+            }
+
+            // Outputs are signed only by the active keyset of the quote's unit
+            let output_keyset_ids: HashSet<Id> =
+                mint_request.outputs.iter().map(|b| b.keyset_id).collect();
+            for id in &output_keyset_ids {
+                let keyset = self.signable_keyset(id).await?;
+                if keyset.unit != quote.unit {
+                    return Err(Error::MultipleUnits);
+                }
+            }
+
+            let blind_signatures = self.sign_and_store(&mint_request.outputs).await?;
+
+            match quote.method {
+                // Checked against the amount received, so it cannot overflow
+                PaymentMethod::Bolt12 => quote.amount_issued += amount,
+                _ => quote.issued = true,
+            }
+            self.localstore.add_mint_quote(quote.clone()).await?;
+
+            #[cfg(feature = "invariant-checks")]
+            self.record_ledger(|ledger| ledger.deposit(&quote.unit, amount));
+
+            self.publish(MintEvent::QuoteIssued {
+                quote_id: quote.id,
+                amount,
+                unit: quote.unit,
+            });
+
+            info!(signatures = blind_signatures.len(), "Mint request signed");
+
+            Ok(nut04::MintBolt11Response {
+                signatures: blind_signatures,
+            })
+        }
+        .instrument(span)
+        .await;
+
+        #[cfg(feature = "invariant-checks")]
+        {
+            // Issuance signs and records its deposit under the quote lock
+            let _guard = self.quote_lock.lock().await;
+            self.assert_invariants("process_mint_request").await;
+        }
+
+        result
+    }
+
+    /// Sign a single blinded message
+    pub async fn blind_sign(
+        &self,
+        blinded_message: &BlindedMessage,
+    ) -> Result<BlindSignature, Error> {
+        let mut signatures = self
+            .blind_sign_many(std::slice::from_ref(blinded_message))
+            .await?;
+
+        signatures.pop().ok_or(Error::AmountKey)
+    }
+
+    /// Sign `blinded_messages` in order
+    ///
+    /// Keysets are resolved once and every message is checked for an active
+    /// keyset and a key for its amount before the signatory is asked, in a
+    /// single batch, so either all messages are signed or none are.
+    pub async fn blind_sign_many(
+        &self,
+        blinded_messages: &[BlindedMessage],
+    ) -> Result<Vec<BlindSignature>, Error> {
+        let mut keysets: HashMap<Id, Arc<MintKeySet>> = HashMap::new();
+
+        for blinded_message in blinded_messages {
+            if let Entry::Vacant(entry) = keysets.entry(blinded_message.keyset_id) {
+                entry.insert(self.signable_keyset(&blinded_message.keyset_id).await?);
+            }
+        }
+
+        let resolved = blinded_messages
+            .iter()
+            .map(|blinded_message| {
+                let keyset = &keysets[&blinded_message.keyset_id];
+                match keyset.contains_amount(blinded_message.amount) {
+                    true => Ok(BlindedMessage {
+                        keyset_id: keyset.id,
+                        ..blinded_message.clone()
+                    }),
+                    // No key for amount
+                    false => Err(Error::AmountKey),
+                }
+            })
+            .collect::<Result<Vec<BlindedMessage>, Error>>()?;
+
+        let mut signatures = self.signatory.blind_sign_many(&resolved).await?;
+        if signatures.len() != blinded_messages.len() {
+            return Err(signatory::Error::Backend(
+                format!(
+                    "{} signatures for {} blinded messages",
+                    signatures.len(),
+                    blinded_messages.len()
+                )
+                .into(),
+            )
+            .into());
+        }
+
+        // Signed under the id the outputs were sent with, legacy or not
+        for (signature, blinded_message) in signatures.iter_mut().zip(blinded_messages) {
+            signature.keyset_id = blinded_message.keyset_id;
+        }
+
+        Ok(signatures)
+    }
+
+    /// Sign `blinded_messages` and record the signatures
+    async fn sign_and_store(
+        &self,
+        blinded_messages: &[BlindedMessage],
+    ) -> Result<Vec<BlindSignature>, Error> {
+        let signatures = self.blind_sign_many(blinded_messages).await?;
+
+        for (blinded_message, blinded_signature) in blinded_messages.iter().zip(&signatures) {
+            self.localstore
+                .add_blinded_signature(blinded_message.blinded_secret, blinded_signature.clone())
+                .await?;
+        }
+
+        Ok(signatures)
+    }
+
+    /// Keyset `id` if it is the active keyset of its unit
+    async fn signable_keyset(&self, id: &Id) -> Result<Arc<MintKeySet>, Error> {
+        let keyset = self.get_keyset(id).await?.ok_or(Error::UnknownKeySet)?;
+        check_not_frozen(&keyset)?;
+
+        let active = self.localstore.get_active_keyset_id(&keyset.unit).await?;
+
+        // Check that the keyset is active and should be used to sign
+        if active != Some(keyset.id) {
+            return Err(Error::InactiveKeyset {
+                id: keyset.id,
+                active_id: active,
+            });
+        }
+
+        Ok(keyset)
+    }
+
+    pub async fn process_swap_request(
+        &mut self,
+        swap_request: SwapRequest,
+    ) -> Result<SwapResponse, Error> {
+        let span = info_span!(
+            "process_swap_request",
+            inputs = swap_request.inputs.len(),
+            outputs = swap_request.outputs.len(),
+            input_keysets = %keyset_ids(swap_request.inputs.iter().map(|p| p.keyset_id)),
+            output_keysets = %keyset_ids(swap_request.outputs.iter().map(|b| b.keyset_id)),
+        );
+
+        let result = async {
+            self.check_request_limits(&swap_request.inputs, &swap_request.outputs)?;
+
+            // A swap resubmitted after its response was lost is answered again
+            let request_hash = swap_request_hash(&swap_request)?;
+            if let Some(response) = self.localstore.get_swap_response(&request_hash).await? {
+                debug!("Swap answered from the response cache");
+                return Ok(response);
+            }
+
+            for blinded_message in &swap_request.outputs {
+                if self
+                    .localstore
+                    .get_blinded_signature(&blinded_message.blinded_secret)
+                    .await?
+                    .is_some()
+                {
+                    error!(
+                        "Output has already been signed: {}",
+                        blinded_message.blinded_secret
+                    );
+                    return Err(Error::BlindedMessageAlreadySigned);
+                }
+            }
+
+            let proofs_total = swap_request.input_amount()?;
+
+            let output_total = swap_request.output_amount()?;
+
+            let fee = self.input_fee(&swap_request.inputs).await?;
+
+            if output_total.checked_add(fee) != Some(proofs_total) {
+                debug!(
+                    inputs = %proofs_total,
+                    outputs = %output_total,
+                    %fee,
+                    "Swap unbalanced"
+                );
+                return Err(Error::Amount);
+            }
+
+            let proof_count = swap_request.inputs.len();
+
+            let ys = swap_request.inputs.ys()?;
+
+            // Check that there are no duplicate proofs in request
+            if ys.iter().collect::<HashSet<_>>().len().ne(&proof_count) {
+                return Err(Error::DuplicateProofs);
+            }
+
+            self.verify_inputs(&swap_request.inputs).await?;
+
+            let input_keyset_ids: HashSet<Id> =
+                swap_request.inputs.iter().map(|p| p.keyset_id).collect();
+
+            let mut keyset_units = HashSet::with_capacity(input_keyset_ids.capacity());
+
+            for id in input_keyset_ids {
+                let keyset = self.get_keyset(&id).await?.ok_or(Error::UnknownKeySet)?;
+                keyset_units.insert(keyset.unit.clone());
+            }
+
+            let output_keyset_ids: HashSet<Id> =
+                swap_request.outputs.iter().map(|p| p.keyset_id).collect();
+
+            // Outputs are checked before the inputs are spent
+            for id in &output_keyset_ids {
+                let keyset = self.signable_keyset(id).await?;
+
+                keyset_units.insert(keyset.unit.clone());
+            }
+
+            // Check that all proofs are the same unit
+            // in the future it maybe possible to support multiple units but unsupported for
+            // now
+            if keyset_units.len().gt(&1) {
+                error!("Only one unit is allowed in request: {:?}", keyset_units);
+                return Err(Error::MultipleUnits);
+            }
+
+            // Signed before anything is written, then the spent inputs, the
+            // signatures and the cached response are committed together, so
+            // a crash leaves the whole swap or none of it. Of concurrent
+            // swaps sharing an input only one commits.
+            let promises = self.blind_sign_many(&swap_request.outputs).await?;
+            let response = SwapResponse::new(promises);
+
+            let mut transaction = MintTransaction::new();
+            transaction.spend(swap_request.inputs);
+            for (blinded_message, signature) in
+                swap_request.outputs.iter().zip(&response.signatures)
+            {
+                transaction
+                    .add_blinded_signature(blinded_message.blinded_secret, signature.clone());
+            }
+            transaction.cache_swap_response(
+                request_hash,
+                response.clone(),
+                self.clock.now().saturating_add(self.swap_response_ttl),
+            );
+
+            let spent = self.localstore.commit_transaction(transaction).await?;
+            if !spent.is_empty() {
+                debug!(spent = spent.len(), "Swap inputs spent concurrently");
+                return Err(self.conflict_error(&spent).await?);
+            }
+
+            #[cfg(feature = "invariant-checks")]
+            if let Some(unit) = keyset_units.iter().next() {
+                self.record_ledger(|ledger| ledger.withdraw(unit, fee));
+            }
+
+            info!(
+                amount = %proofs_total,
+                signatures = response.signatures.len(),
+                "Swap processed"
+            );
+
+            Ok(response)
+        }
+        .instrument(span)
+        .await;
+
+        #[cfg(feature = "invariant-checks")]
+        self.assert_invariants("process_swap_request").await;
+
+        result
+    }
+
+    /// Verify every proof in `proofs` without marking any as spent
+    ///
+    /// Checks the keyset, spent and pending state, spending conditions and
+    /// signature of each proof. The result at each index belongs to the proof
+    /// at the same index.
+    pub async fn verify_proofs(&self, proofs: &Proofs) -> Vec<Result<(), Error>> {
+        let mut results = Vec::with_capacity(proofs.len());
+
+        for proof in proofs {
+            results.push(self.verify_proof(proof).await);
+        }
+
+        results
+    }
+
+    /// Verify every proof of `token`, in the order they appear in the token
+    pub async fn verify_token(&self, token: &Token) -> Vec<Result<(), Error>> {
+        let proofs: Proofs = token
+            .token
+            .iter()
+            .flat_map(|mint_proofs| mint_proofs.proofs.iter().cloned())
+            .collect();
+
+        self.verify_proofs(&proofs).await
+    }
+
+    async fn verify_proof(&self, proof: &Proof) -> Result<(), Error> {
+        let y: PublicKey = proof.y()?;
+
+        if self.localstore.get_spent_proof_by_y(&y).await?.is_some() {
+            return Err(Error::TokenSpent);
+        }
+
+        if self.localstore.get_pending_proof_by_y(&y).await?.is_some() {
+            return Err(Error::TokenPending);
+        }
+
+        let keyset = self
+            .get_keyset(&proof.keyset_id)
+            .await?
+            .ok_or(Error::UnknownKeySet)?;
+        let now = self.clock.now();
+        check_not_frozen(&keyset)?;
+        if keyset.is_refused(now) {
+            return Err(Error::RefusedKeyset);
+        }
+        if !keyset.contains_amount(proof.amount) {
+            return Err(Error::AmountKey);
+        }
+
+        verify_conditions(proof, now)?;
+        let proof = Proof {
+            keyset_id: keyset.id,
+            ..proof.clone()
+        };
+        match self.verify_signatures(std::slice::from_ref(&proof)).await? {
+            true => Ok(()),
+            false => Err(crate::error::Error::TokenNotVerifed.into()),
+        }
+    }
+
+    /// Whether every signature of `proofs` is valid, asked of the signatory
+    /// in a single batch
+    ///
+    /// Keyset ids of `proofs` are current ids.
+    async fn verify_signatures(&self, proofs: &[Proof]) -> Result<bool, Error> {
+        if proofs.is_empty() {
+            return Ok(true);
+        }
+
+        let verified = self.signatory.verify_proofs(proofs).await?;
+        if verified.len() != proofs.len() {
+            return Err(signatory::Error::Backend(
+                format!("{} results for {} proofs", verified.len(), proofs.len()).into(),
+            )
+            .into());
+        }
+
+        Ok(verified.into_iter().all(|verified| verified))
+    }
+
+    /// Verify `proofs` as the inputs of a request
+    ///
+    /// Spent and pending checks run for every proof before any curve math.
+    /// With the `parallel` feature spending conditions are checked on the
+    /// rayon pool, and the error of the lowest index invalid proof is returned
+    /// either way: signatures are verified up to the first proof whose
+    /// conditions do not hold.
+    async fn verify_inputs(&self, proofs: &Proofs) -> Result<(), Error> {
+        self.verify_reserved_inputs(proofs, &[]).await
+    }
+
+    /// Verify `proofs` as [Mint::verify_inputs] does, those with a `Y` in
+    /// `reserved` may be pending
+    async fn verify_reserved_inputs(
+        &self,
+        proofs: &Proofs,
+        reserved: &[PublicKey],
+    ) -> Result<(), Error> {
+        let start = Instant::now();
+        let now = self.clock.now();
+        let mut keysets: HashMap<Id, Arc<MintKeySet>> = HashMap::new();
+
+        for proof in proofs {
+            let y: PublicKey = proof.y()?;
+
+            if self.localstore.get_spent_proof_by_y(&y).await?.is_some() {
+                return Err(Error::TokenSpent);
+            }
+
+            if !reserved.contains(&y) && self.localstore.get_pending_proof_by_y(&y).await?.is_some()
+            {
+                return Err(Error::TokenPending);
+            }
+
+            if let Entry::Vacant(entry) = keysets.entry(proof.keyset_id) {
+                let keyset = self
+                    .get_keyset(&proof.keyset_id)
+                    .await?
+                    .ok_or(Error::UnknownKeySet)?;
+                check_not_frozen(&keyset)?;
+                if keyset.is_refused(now) {
+                    return Err(Error::RefusedKeyset);
+                }
+                entry.insert(keyset);
+            }
+        }
+
+        let resolved = proofs
+            .iter()
+            .map(|proof| {
+                let keyset = &keysets[&proof.keyset_id];
+                match keyset.contains_amount(proof.amount) {
+                    true => Ok(Proof {
+                        keyset_id: keyset.id,
+                        ..proof.clone()
+                    }),
+                    false => Err(Error::AmountKey),
+                }
+            })
+            .collect::<Result<Proofs, Error>>()?;
+
+        let condition_error = first_condition_error(&resolved, now);
+        let checked = condition_error
+            .as_ref()
+            .map_or(resolved.len(), |(index, _)| *index);
+        if !self.verify_signatures(&resolved[..checked]).await? {
+            return Err(crate::error::Error::TokenNotVerifed.into());
+        }
+        if let Some((_, err)) = condition_error {
+            return Err(err);
+        }
+
+        debug!(
+            proofs = proofs.len(),
+            elapsed_us = start.elapsed().as_micros() as u64,
+            "Verified proofs"
+        );
+
+        Ok(())
+    }
+
+    /// State of the proofs of a [NUT-07] request
+    pub async fn check_state(
+        &self,
+        check_state: &CheckStateRequest,
+    ) -> Result<CheckStateResponse, Error> {
+        self.check_state_by_ys(&check_state.ys).await
+    }
+
+    /// State of `proofs`, by their `Y`s
+    pub async fn check_proofs_state(&self, proofs: &Proofs) -> Result<CheckStateResponse, Error> {
+        self.check_state_by_ys(&proofs.ys()?).await
+    }
+
+    /// State of the proofs with `ys`, in the same order
+    ///
+    /// Only the `Y` indexed spent and pending proofs are read. A `Y` the mint
+    /// has never seen is [State::Unspent]. Spent proofs come with the witness
+    /// they were spent with, so the co-signers of a locked proof can see who
+    /// signed.
+    pub async fn check_state_by_ys(&self, ys: &[PublicKey]) -> Result<CheckStateResponse, Error> {
+        let mut states = Vec::with_capacity(ys.len());
+
+        for y in ys {
+            let (state, witness) =
+                if let Some(proof) = self.localstore.get_spent_proof_by_y(y).await? {
+                    (
+                        State::Spent,
+                        proof.witness.map(|witness| witness.to_string()),
+                    )
+                } else if self.localstore.get_pending_proof_by_y(y).await?.is_some() {
+                    (State::Pending, None)
+                } else {
+                    (State::Unspent, None)
+                };
+
+            states.push(ProofState {
+                y: *y,
+                state,
+                witness,
+            })
+        }
+        Ok(CheckStateResponse { states })
+    }
+
+    /// Inputs [Mint::verify_melt_request] requires for `melt_request`
+    ///
+    /// The quoted amount, the fee reserve of the quote and the input fee of
+    /// the request's inputs.
+    pub async fn required_melt_total(
+        &self,
+        melt_request: &MeltBolt11Request,
+    ) -> Result<Amount, Error> {
+        let quote = self
+            .localstore
+            .get_melt_quote(&melt_request.quote)
+            .await?
+            .ok_or(Error::UnknownQuote)?;
+        let input_fee = self.input_fee(&melt_request.inputs).await?;
+
+        melt_total(&quote, input_fee)
+    }
+
+    pub async fn verify_melt_request(
+        &mut self,
+        melt_request: &MeltBolt11Request,
+    ) -> Result<MeltQuote, Error> {
+        self.verify_reserved_melt_request(melt_request, &[]).await
+    }
+
+    /// Verify `melt_request` as [Mint::verify_melt_request] does, inputs with
+    /// a `Y` in `reserved` may be pending
+    async fn verify_reserved_melt_request(
+        &mut self,
+        melt_request: &MeltBolt11Request,
+        reserved: &[PublicKey],
+    ) -> Result<MeltQuote, Error> {
+        let span = info_span!(
+            "verify_melt_request",
+            quote = %melt_request.quote,
+            inputs = melt_request.inputs.len(),
+            outputs = melt_request.outputs.as_ref().map_or(0, |outputs| outputs.len()),
+            input_keysets = %keyset_ids(melt_request.inputs.iter().map(|p| p.keyset_id)),
+        );
+
+        async move {
+            self.check_request_limits(
+                &melt_request.inputs,
+                melt_request.outputs.as_deref().unwrap_or_default(),
+            )?;
+
+            let quote = self
+                .localstore
+                .get_melt_quote(&melt_request.quote)
+                .await?
+                .ok_or(Error::UnknownQuote)?;
+
+            let proofs_total = melt_request.proofs_amount()?;
+            let input_fee = self.input_fee(&melt_request.inputs).await?;
+            let required_total = melt_total(&quote, input_fee)?;
+
+            if proofs_total < required_total {
+                debug!(
+                    "Insufficient Proofs: Got: {}, Required: {}",
+                    proofs_total, required_total
+                );
+                return Err(Error::InsufficientMeltInputs {
+                    provided: proofs_total,
+                    required: required_total,
+                });
+            }
+
+            let input_keyset_ids: HashSet<Id> =
+                melt_request.inputs.iter().map(|p| p.keyset_id).collect();
+
+            let mut keyset_units = HashSet::with_capacity(input_keyset_ids.capacity());
+
+            for id in input_keyset_ids {
+                let keyset = self.get_keyset(&id).await?.ok_or(Error::UnknownKeySet)?;
+                keyset_units.insert(keyset.unit.clone());
+            }
+
+            if let Some(outputs) = &melt_request.outputs {
+                // NUT-08: the mint assigns change amounts to blank outputs
+                if outputs.iter().any(|output| output.amount != Amount::ZERO) {
+                    return Err(Error::NonBlankChangeOutput);
+                }
+
+                let output_keysets_ids: HashSet<Id> = outputs.iter().map(|b| b.keyset_id).collect();
+                for id in output_keysets_ids {
+                    let keyset = self.get_keyset(&id).await?.ok_or(Error::UnknownKeySet)?;
+
+                    // Get the active keyset for the unit
+                    let active_keyset_id =
+                        self.localstore.get_active_keyset_id(&keyset.unit).await?;
+
+                    // Check output is for current active keyset
+                    if active_keyset_id != Some(id) {
+                        return Err(Error::InactiveKeyset {
+                            id,
+                            active_id: active_keyset_id,
+                        });
+                    }
+                    keyset_units.insert(keyset.unit.clone());
+                }
+            }
+
+            // Check that all input and output proofs are the same unit
+            if keyset_units.len().gt(&1) {
+                return Err(Error::MultipleUnits);
+            }
+
+            let secrets: HashSet<PublicKey> = melt_request.inputs.ys()?.into_iter().collect();
+
+            // Ensure proofs are unique and not being double spent
+            if melt_request.inputs.len().ne(&secrets.len()) {
+                return Err(Error::DuplicateProofs);
+            }
+
+            self.verify_reserved_inputs(&melt_request.inputs, reserved)
+                .await?;
+
+            debug!(amount = %proofs_total, "Melt request verified");
+
+            Ok(quote)
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Hold the inputs of a melt pending before paying its invoice over
+    /// Lightning
+    ///
+    /// Once the payment succeeded the melt is finished with
+    /// [`process_melt_request`](Self::process_melt_request); if it failed,
+    /// [`abort_melt`](Self::abort_melt) makes the inputs spendable again.
+    pub async fn reserve_melt_request(
+        &mut self,
+        melt_request: &MeltBolt11Request,
+    ) -> Result<MeltQuote, Error> {
+        self.verify_melt_request(melt_request).await?;
+
+        // Held from the pending check until the reservation is stored, so of
+        // concurrent reserves of the quote only one passes the check
+        let quote_lock = Arc::clone(&self.quote_lock);
+        let _guard = quote_lock.lock().await;
+
+        let mut quote = self
+            .localstore
+            .get_melt_quote(&melt_request.quote)
+            .await?
+            .ok_or(Error::UnknownQuote)?;
+        if quote.paid {
+            return Err(Error::PaidQuote);
+        }
+        if quote.pending {
+            return Err(Error::PendingQuote);
+        }
+
+        // Of concurrent melts or swaps sharing an input only one takes it
+        let conflicts = self
+            .localstore
+            .add_pending_proofs_if_unspent(melt_request.inputs.clone())
+            .await?;
+        if !conflicts.is_empty() {
+            debug!(
+                conflicts = conflicts.len(),
+                "Melt inputs taken concurrently"
+            );
+            return Err(self.conflict_error(&conflicts).await?);
+        }
+
+        quote.pending = true;
+        quote.reserved_inputs = melt_request
+            .inputs
+            .iter()
+            .map(|proof| proof.secret.clone())
+            .collect();
+        quote.reserved_outputs = melt_request.outputs.clone();
+        self.localstore.add_melt_quote(quote.clone()).await?;
+
+        info!(quote = %quote.id, inputs = quote.reserved_inputs.len(), "Melt reserved");
+
+        Ok(quote)
+    }
+
+    /// Give up on a melt whose Lightning payment failed
+    ///
+    /// Inputs reserved by [`reserve_melt_request`](Self::reserve_melt_request)
+    /// are released and the quote is unpaid again, so it can be retried.
+    pub async fn abort_melt(&self, quote_id: &str) -> Result<MeltBolt11Response, Error> {
+        let mut quote = self
+            .localstore
+            .get_melt_quote(quote_id)
+            .await?
+            .ok_or(Error::UnknownQuote)?;
+
+        if quote.paid {
+            return Err(Error::PaidQuote);
+        }
+
+        self.release_melt_inputs(&mut quote).await?;
+
+        info!(quote = %quote_id, "Melt aborted");
+
+        Ok(MeltBolt11Response::unpaid())
+    }
+
+    /// Error for inputs another request spent or reserved first
+    async fn conflict_error(&self, ys: &[PublicKey]) -> Result<Error, Error> {
+        for y in ys {
+            if self.localstore.get_spent_proof_by_y(y).await?.is_some() {
+                return Ok(Error::TokenSpent);
+            }
+        }
+
+        Ok(Error::TokenPending)
+    }
+
+    async fn release_melt_inputs(&self, quote: &mut MeltQuote) -> Result<(), Error> {
+        for secret in &quote.reserved_inputs {
+            self.localstore.remove_pending_proof(secret).await?;
+        }
+
+        quote.reserved_inputs.clear();
+        quote.reserved_outputs = None;
+        quote.pending = false;
+        self.localstore.add_melt_quote(quote.clone()).await?;
+
+        Ok(())
+    }
+
+    /// Melts reserved by [`reserve_melt_request`](Self::reserve_melt_request)
+    /// whose payment was never resolved, such as after a restart
+    ///
+    /// The embedding server looks up each payment on its Lightning node and
+    /// finishes the melt with [`resolve_pending_melt`](Self::resolve_pending_melt).
+    pub async fn pending_melts(&self) -> Result<Vec<PendingMelt>, Error> {
+        let quotes = self.localstore.get_melt_quotes().await?;
+
+        Ok(quotes
+            .into_iter()
+            .filter(|quote| quote.pending && !quote.paid)
+            .map(|quote| PendingMelt {
+                payment_hash: Bolt11Invoice::from_str(&quote.request)
+                    .ok()
+                    .map(|invoice| *invoice.payment_hash()),
+                quote,
+            })
+            .collect())
+    }
+
+    /// Finish a pending melt with the final `outcome` of its payment
+    ///
+    /// [MeltOutcome::Settled] spends the reserved inputs and signs change for
+    /// the reserved outputs, [MeltOutcome::Failed] releases the inputs as
+    /// [`abort_melt`](Self::abort_melt) does. Resolving a melt again with the
+    /// same outcome changes nothing and returns the same paid state.
+    pub async fn resolve_pending_melt(
+        &mut self,
+        quote_id: &str,
+        outcome: MeltOutcome,
+    ) -> Result<MeltBolt11Response, Error> {
+        let quote = self
+            .localstore
+            .get_melt_quote(quote_id)
+            .await?
+            .ok_or(Error::UnknownQuote)?;
+
+        match outcome {
+            MeltOutcome::Failed if quote.paid => Err(Error::PaidQuote),
+            MeltOutcome::Failed if !quote.pending => Ok(MeltBolt11Response::unpaid()),
+            MeltOutcome::Failed => self.abort_melt(quote_id).await,
+            MeltOutcome::Settled { preimage, .. } if quote.paid => {
+                Ok(MeltBolt11Response::new(true, Some(preimage), None))
+            }
+            MeltOutcome::Settled { .. } if !quote.pending => Err(Error::MeltNotPending),
+            MeltOutcome::Settled { preimage, fee } => {
+                let amount_msat = quote.amount_msat.unwrap_or(quote.amount.to_msat());
+                let total_spent_msat = amount_msat.saturating_add(fee.to_msat());
+
+                let mut inputs = Proofs::with_capacity(quote.reserved_inputs.len());
+                for secret in &quote.reserved_inputs {
+                    let input = self
+                        .localstore
+                        .get_pending_proof_by_secret(secret)
+                        .await?
+                        .ok_or(Error::MeltNotPending)?;
+                    inputs.push(input);
+                }
+
+                let melt_request = MeltBolt11Request::new(
+                    quote.id.clone(),
+                    inputs,
+                    quote.reserved_outputs.clone(),
+                );
+
+                self.process_melt_request_msat(&melt_request, &preimage, total_spent_msat)
+                    .await
+            }
+        }
+    }
+
+    /// Resolve [pending melts](Self::pending_melts) with the payment state
+    /// reported by [LnBackend::payment_status]
+    ///
+    /// Melts still in flight, or that the backend cannot look up, stay
+    /// pending. Returns the responses of the melts resolved by quote id.
+    pub async fn recover_pending_melts(
+        &mut self,
+    ) -> Result<Vec<(String, MeltBolt11Response)>, Error> {
+        let Some(ln_backend) = self.ln_backend.clone() else {
+            return Ok(Vec::new());
+        };
+
+        let mut resolved = Vec::new();
+        for PendingMelt {
+            quote,
+            payment_hash,
+        } in self.pending_melts().await?
+        {
+            let Some(payment_hash) = payment_hash else {
+                continue;
+            };
+
+            let outcome = match ln_backend.payment_status(&payment_hash).await {
+                Ok(PaymentStatus::Paid(payment)) => {
+                    let amount_msat = quote.amount_msat.unwrap_or(quote.amount.to_msat());
+                    let fee_msat = payment.total_spent_msat.saturating_sub(amount_msat);
+
+                    MeltOutcome::Settled {
+                        preimage: payment.preimage,
+                        // Charged, as in the change of a settled melt
+                        fee: Amount::from_msat_ceil(fee_msat),
+                    }
+                }
+                Ok(PaymentStatus::Failed) => MeltOutcome::Failed,
+                Ok(PaymentStatus::Pending) => continue,
+                Err(err) => {
+                    warn!(quote = %quote.id, "Could not look up melt payment: {err}");
+                    continue;
+                }
+            };
+
+            let response = self.resolve_pending_melt(&quote.id, outcome).await?;
+            resolved.push((quote.id, response));
+        }
+
+        Ok(resolved)
+    }
+
+    /// Spend the inputs of a melt paid over Lightning and sign its change
+    ///
+    /// `preimage` proves the payment and must not be empty. Inputs reserved
+    /// by [`reserve_melt_request`](Self::reserve_melt_request) have to be the
+    /// inputs of `melt_request`.
+    pub async fn process_melt_request(
+        &mut self,
+        melt_request: &MeltBolt11Request,
+        preimage: &str,
+        total_spent: Amount,
+    ) -> Result<MeltBolt11Response, Error> {
+        self.process_melt_request_msat(melt_request, preimage, total_spent.to_msat())
+            .await
+    }
+
+    /// [`process_melt_request`](Self::process_melt_request) for a sat quote
+    /// whose payment, fee included, cost `total_spent_msat`
+    ///
+    /// The wallet pays no more than the fee reserve: a payment whose fee
+    /// exceeds it is settled as if the fee were the whole reserve, without
+    /// fee reserve change, and the mint covers the rest.
+    ///
+    /// Change is computed in msat and rounded down to whole sats, the
+    /// fraction of a sat is kept by the mint.
+    pub async fn process_melt_request_msat(
+        &mut self,
+        melt_request: &MeltBolt11Request,
+        preimage: &str,
+        total_spent_msat: u64,
+    ) -> Result<MeltBolt11Response, Error> {
+        if preimage.is_empty() {
+            return Err(Error::MissingPreimage);
+        }
+
+        self.settle_melt_request(melt_request, Some(preimage), total_spent_msat)
+            .await
+    }
+
+    /// Settle a melt whose invoice was issued by this mint, without Lightning
+    ///
+    /// Returns `None` when the melt quote's invoice does not belong to an
+    /// unpaid mint quote of this mint, in which case the invoice has to be
+    /// paid over Lightning and the melt finished with
+    /// [`process_melt_request`](Self::process_melt_request). Otherwise the
+    /// inputs are spent, no Lightning fee is charged so the whole fee reserve
+    /// is returned as change, and both the melt and the mint quote are marked
+    /// paid.
+    ///
+    /// Runs under the same lock as [`pay_mint_quote`](Self::pay_mint_quote),
+    /// so a mint quote is never settled both internally and over Lightning.
+    pub async fn process_internal_melt_request(
+        &mut self,
+        melt_request: &MeltBolt11Request,
+    ) -> Result<Option<MeltBolt11Response>, Error> {
+        let melt_quote = self.verify_melt_request(melt_request).await?;
+
+        let quote_lock = Arc::clone(&self.quote_lock);
+        let _guard = quote_lock.lock().await;
+
+        let Some(mut mint_quote) = self.internal_mint_quote(&melt_quote).await? else {
+            return Ok(None);
+        };
+
+        let response = self
+            .settle_melt_request(
+                melt_request,
+                None,
+                melt_quote
+                    .amount_msat
+                    .unwrap_or(melt_quote.amount.to_msat()),
+            )
+            .await?;
+
+        mint_quote.paid = true;
+        self.localstore.add_mint_quote(mint_quote.clone()).await?;
+
+        self.publish(MintEvent::QuotePaid {
+            quote_id: mint_quote.id.clone(),
+            amount: mint_quote.amount,
+            unit: mint_quote.unit.clone(),
+        });
+
+        info!(mint_quote = %mint_quote.id, "Melt settled internally");
+
+        Ok(Some(response))
+    }
+
+    /// Unpaid mint quote of this mint for the invoice of `melt_quote`
+    async fn internal_mint_quote(
+        &self,
+        melt_quote: &MeltQuote,
+    ) -> Result<Option<MintQuote>, Error> {
+        let Ok(invoice) = Bolt11Invoice::from_str(&melt_quote.request) else {
+            return Ok(None);
+        };
+
+        let mint_quote = self
+            .localstore
+            .get_mint_quote_by_payment_hash(invoice.payment_hash())
+            .await?
+            .filter(|mint_quote| {
+                !mint_quote.paid
+                    && mint_quote.unit == melt_quote.unit
+                    && mint_quote.amount == melt_quote.amount
+            });
+
+        Ok(mint_quote)
+    }
+
+    /// Mark an unpaid mint quote paid if [LnBackend::invoice_status] reports
+    /// its invoice paid
+    ///
+    /// Returns whether the quote is paid. Without a backend, or with one that
+    /// cannot look invoices up, the quote is left for [`pay_mint_quote`](Self::pay_mint_quote).
+    ///
+    /// A bolt12 quote is credited what [LnBackend::offer_payment_received]
+    /// reports for its offer, and is paid while ecash can be issued for it.
+    pub async fn sync_mint_quote(&self, quote_id: &str) -> Result<bool, Error> {
+        let quote = self
+            .localstore
+            .get_mint_quote(quote_id)
+            .await?
+            .ok_or(Error::UnknownQuote)?;
+
+        if quote.method == PaymentMethod::Bolt12 {
+            let Some(ln_backend) = &self.ln_backend else {
+                return Ok(quote.mintable() > Amount::ZERO);
+            };
+            return match ln_backend
+                .offer_payment_received(&quote.request, &quote.unit)
+                .await
+            {
+                Ok(amount_paid) => {
+                    self.record_offer_payment(quote_id, amount_paid).await?;
+                    Ok(self
+                        .mint_quote(quote_id)
+                        .await?
+                        .is_some_and(|quote| quote.mintable() > Amount::ZERO))
+                }
+                Err(lightning::Error::Unsupported(_)) => Ok(quote.mintable() > Amount::ZERO),
+                Err(err) => Err(err.into()),
+            };
+        }
+
+        let (false, Some(ln_backend)) = (quote.paid, &self.ln_backend) else {
+            return Ok(quote.paid);
+        };
+        let Ok(invoice) = Bolt11Invoice::from_str(&quote.request) else {
+            return Ok(false);
+        };
+
+        match ln_backend.invoice_status(invoice.payment_hash()).await {
+            Ok(InvoiceStatus::Paid) => {
+                self.pay_mint_quote(quote_id).await?;
+                Ok(true)
+            }
+            Ok(_) | Err(lightning::Error::Unsupported(_)) => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Mark a mint quote paid once its invoice has been paid over Lightning
+    ///
+    /// Returns `false` if the quote was already paid, for example because it
+    /// was settled by [`process_internal_melt_request`](Self::process_internal_melt_request).
+    pub async fn pay_mint_quote(&self, quote_id: &str) -> Result<bool, Error> {
+        let _guard = self.quote_lock.lock().await;
+
+        let mut quote = self
+            .localstore
+            .get_mint_quote(quote_id)
+            .await?
+            .ok_or(Error::UnknownQuote)?;
+
+        if quote.method == PaymentMethod::Bolt12 {
+            return Err(Error::UnsupportedMethod(PaymentMethod::Bolt12));
+        }
+
+        if quote.paid {
+            return Ok(false);
+        }
+
+        quote.paid = true;
+        self.localstore.add_mint_quote(quote.clone()).await?;
+
+        self.publish(MintEvent::QuotePaid {
+            quote_id: quote.id,
+            amount: quote.amount,
+            unit: quote.unit,
+        });
+
+        Ok(true)
+    }
+
+    /// Credit a bolt12 mint quote with the `amount_paid` its offer has
+    /// received in total
+    ///
+    /// Returns the amount newly received, zero when `amount_paid` is no more
+    /// than already credited, so a repeated report changes nothing. Every
+    /// credit publishes a [MintEvent::QuotePaid] of the amount received.
+    pub async fn record_offer_payment(
+        &self,
+        quote_id: &str,
+        amount_paid: Amount,
+    ) -> Result<Amount, Error> {
+        let _guard = self.quote_lock.lock().await;
+
+        let mut quote = self
+            .localstore
+            .get_mint_quote(quote_id)
+            .await?
+            .ok_or(Error::UnknownQuote)?;
+
+        if quote.method != PaymentMethod::Bolt12 {
+            return Err(Error::UnsupportedMethod(quote.method));
+        }
+
+        let received = amount_paid.saturating_sub(quote.amount_paid);
+        if received == Amount::ZERO {
+            return Ok(received);
+        }
+
+        quote.amount_paid = amount_paid;
+        quote.paid = true;
+        self.localstore.add_mint_quote(quote.clone()).await?;
+
+        info!(quote = %quote.id, %received, "Offer payment received");
+        self.publish(MintEvent::QuotePaid {
+            quote_id: quote.id,
+            amount: received,
+            unit: quote.unit,
+        });
+
+        Ok(received)
+    }
+
+    /// Mark the mint quote of the invoice with `payment_hash` paid, when the
+    /// Lightning node reports the invoice settled
+    ///
+    /// Returns the id of the quote whether or not it was already paid, so a
+    /// repeated notification changes nothing.
+    /// [Error::UnknownPaymentHash] if no quote is for the invoice.
+    pub async fn mark_paid_by_payment_hash(
+        &self,
+        payment_hash: &crate::Sha256,
+    ) -> Result<String, Error> {
+        let quote = self
+            .quote_by_payment_hash(payment_hash)
+            .await?
+            .ok_or(Error::UnknownPaymentHash(*payment_hash))?;
+
+        if !self.pay_mint_quote(&quote.id).await? {
+            debug!(quote = %quote.id, "Mint quote already paid");
+        }
+
+        Ok(quote.id)
+    }
+
+    async fn settle_melt_request(
+        &mut self,
+        melt_request: &MeltBolt11Request,
+        preimage: Option<&str>,
+        total_spent_msat: u64,
+    ) -> Result<MeltBolt11Response, Error> {
+        // The preimage is returned to the wallet but never logged
+        let span = info_span!(
+            "process_melt_request",
+            quote = %melt_request.quote,
+            inputs = melt_request.inputs.len(),
+            total_spent_msat,
+            internal = preimage.is_none(),
+        );
+
+        let result = async {
+            // Reserved inputs go from pending to spent in one step
+            let mut reserved = Vec::new();
+            if let Some(quote) = self.localstore.get_melt_quote(&melt_request.quote).await? {
+                if !quote.reserved_inputs.is_empty() {
+                    let inputs: HashSet<&Secret> =
+                        melt_request.inputs.iter().map(|p| &p.secret).collect();
+                    if inputs.len() != quote.reserved_inputs.len()
+                        || !quote.reserved_inputs.iter().all(|s| inputs.contains(s))
+                    {
+                        return Err(Error::TokenPending);
+                    }
+
+                    reserved = melt_request.inputs.ys()?;
+                }
+            }
+
+            let mut quote = self
+                .verify_reserved_melt_request(melt_request, &reserved)
+                .await?;
+
+            if let Some(outputs) = &melt_request.outputs {
+                for blinded_message in outputs {
+                    if self
+                        .localstore
+                        .get_blinded_signature(&blinded_message.blinded_secret)
+                        .await?
+                        .is_some()
+                    {
+                        error!(
+                            "Output has already been signed: {}",
+                            blinded_message.blinded_secret
+                        );
+                        return Err(Error::BlindedMessageAlreadySigned);
+                    }
+                }
+            }
+
+            // The fraction of a sat left over is kept by the mint
+            let amount_msat = quote.amount_msat.unwrap_or(quote.amount.to_msat());
+            let mut fee_msat = total_spent_msat.saturating_sub(amount_msat);
+
+            // The payment has been sent, so a fee over the reserve is covered
+            // by the mint rather than leaving the melt pending
+            if let Ok(fee_reserve) = quote.unit_fee_reserve().to_msat() {
+                let fee_reserve_msat = u64::from(fee_reserve.amount);
+                if fee_msat > fee_reserve_msat {
+                    error!(
+                        fee_msat,
+                        fee_reserve_msat,
+                        overrun_msat = fee_msat - fee_reserve_msat,
+                        "Lightning fee exceeds fee reserve, no fee reserve is returned"
+                    );
+                    fee_msat = fee_reserve_msat;
+                }
+            }
+
+            // Input fees are kept whatever the payment cost
+            let input_fee = self.input_fee(&melt_request.inputs).await?;
+            let settlement = MeltSettlement::new(
+                melt_request
+                    .proofs_amount()?
+                    .checked_sub(input_fee)
+                    .ok_or(Error::Amount)?,
+                amount_msat,
+                fee_msat,
+            )
+            .ok_or(Error::Amount)?;
+            let change_target = settlement.change;
+
+            // The payment has been sent, so the inputs stay spent whatever follows
+            let mut transaction = MintTransaction::new();
+            transaction.spend(melt_request.inputs.clone());
+            transaction.release(reserved);
+            let spent = self.localstore.commit_transaction(transaction).await?;
+            if !spent.is_empty() {
+                error!(spent = spent.len(), "Melt inputs spent concurrently");
+                return Err(self.conflict_error(&spent).await?);
+            }
+
+            let mut change = None;
+            let mut returned = Amount::ZERO;
+
+            if let Some(outputs) = melt_request.outputs.clone() {
+                let mut amounts = self.split_change(change_target, &outputs).await?;
+
+                if outputs.len().lt(&amounts.len()) {
+                    debug!(
+                        "Providing change requires {} blinded messages, but only {} provided",
+                        amounts.len(),
+                        outputs.len()
+                    );
+
+                    // In the case that not enough outputs are provided to return all change
+                    // Reverse sort the amounts so that the most amount of change possible is
+                    // returned. The rest is burnt
+                    amounts.sort_by(|a, b| b.cmp(a));
+                }
+
+                let change_messages: Vec<BlindedMessage> = amounts
+                    .iter()
+                    .zip(outputs)
+                    .map(|(amount, mut blinded_message)| {
+                        blinded_message.amount = *amount;
+                        blinded_message
+                    })
+                    .collect();
+
+                let change_sigs = self.sign_and_store(&change_messages).await?;
+
+                returned = change_messages.iter().map(|b| b.amount).sum();
+                info!(
+                    change = %returned,
+                    signatures = change_sigs.len(),
+                    "Melt change signed"
+                );
+
+                change = Some(change_sigs);
+            } else {
+                info!(
+                    "No change outputs provided. Burnt: {:?} sats",
+                    change_target
+                );
+            }
+
+            quote.paid = true;
+            quote.pending = false;
+            quote.reserved_inputs.clear();
+            quote.reserved_outputs = None;
+            quote.payment_preimage = preimage.map(|preimage| preimage.to_string());
+            self.localstore.add_melt_quote(quote.clone()).await?;
+
+            self.accounting
+                .write()
+                .expect("Accounting lock poisoned")
+                .record_melt(quote.unit.clone(), &settlement, returned);
+
+            #[cfg(feature = "invariant-checks")]
+            {
+                let withdrawn = melt_request
+                    .proofs_amount()?
+                    .checked_sub(returned)
+                    .ok_or(Error::Amount)?;
+                self.record_ledger(|ledger| ledger.withdraw(&quote.unit, withdrawn));
+            }
+
+            self.publish(MintEvent::MeltPaid {
+                quote_id: quote.id,
+                amount: quote.amount,
+                unit: quote.unit,
+            });
+
+            info!("Melt quote paid");
+
+            Ok(MeltBolt11Response {
+                paid: true,
+                payment_preimage: preimage.map(|preimage| preimage.to_string()),
+                change,
+                fee_paid: Some(settlement.fee_paid()),
+            })
+        }
+        .instrument(span)
+        .await;
+
+        #[cfg(feature = "invariant-checks")]
+        self.assert_invariants("process_melt_request").await;
+
+        result
+    }
+
+    /// Status of melt quote `quote_id`, [Error::UnknownQuote] if there is
+    /// none
+    ///
+    /// The quote is [MeltQuoteState::Pending] while its payment is in
+    /// flight, the preimage is only known once it is paid.
+    pub async fn check_melt_quote(&self, quote_id: &str) -> Result<MeltQuoteBolt11Response, Error> {
+        let quote = self
+            .localstore
+            .get_melt_quote(quote_id)
+            .await?
+            .ok_or(Error::UnknownQuote)?;
+
+        Ok(quote.into())
+    }
+
+    /// Mint info with the [identity pubkey](Mint::identity_pubkey) and
+    /// `max_amount` of every method capped at
+    /// [RequestLimits::max_request_amount]
+    pub async fn mint_info(&self) -> Result<MintInfo, Error> {
+        let mut mint_info = self.localstore.get_mint_info().await?;
+        mint_info.pubkey = Some(self.identity_pubkey());
+
+        let description = self.request_limits.max_description_length > 0;
+        let description_hash = self
+            .ln_backend
+            .as_ref()
+            .is_some_and(|ln_backend| ln_backend.supports_description_hash());
+        for settings in mint_info.nuts.nut04.methods.iter_mut() {
+            settings.description = description;
+            settings.description_hash = description_hash;
+        }
+
+        if let Some(max_request_amount) = self.request_limits.max_request_amount {
+            let max_amounts = mint_info
+                .nuts
+                .nut04
+                .methods
+                .iter_mut()
+                .map(|settings| &mut settings.max_amount)
+                .chain(
+                    mint_info
+                        .nuts
+                        .nut05
+                        .methods
+                        .iter_mut()
+                        .map(|settings| &mut settings.max_amount),
+                );
+
+            for max_amount in max_amounts {
+                if *max_amount == Amount::ZERO || *max_amount > max_request_amount {
+                    *max_amount = max_request_amount;
+                }
+            }
+        }
+
+        Ok(mint_info)
+    }
+
+    pub async fn restore(&self, request: RestoreRequest) -> Result<RestoreResponse, Error> {
+        let output_len = request.outputs.len();
+
+        let mut outputs = Vec::with_capacity(output_len);
+        let mut signatures = Vec::with_capacity(output_len);
+
+        let blinded_message: Vec<PublicKey> =
+            request.outputs.iter().map(|b| b.blinded_secret).collect();
+
+        let blinded_signatures = self
+            .localstore
+            .get_blinded_signatures(blinded_message)
+            .await?;
+
+        assert_eq!(blinded_signatures.len(), output_len);
+
+        for (blinded_message, blinded_signature) in
+            request.outputs.into_iter().zip(blinded_signatures)
+        {
+            if let Some(blinded_signature) = blinded_signature {
+                outputs.push(blinded_message);
+                signatures.push(blinded_signature);
+            }
+        }
+
+        Ok(RestoreResponse {
+            outputs,
+            signatures,
+        })
+    }
+}
+
+/// Lightning fee reserve policy, `max(min_fee_reserve, amount * percent_fee_reserve)`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeeReserve {
+    pub min_fee_reserve: Amount,
+    /// Fraction of the amount, `0.01` is 1%
+    pub percent_fee_reserve: f32,
+}
+
+/// Melt quote left pending, see [Mint::pending_melts]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingMelt {
+    pub quote: MeltQuote,
+    /// Payment hash of the quote's invoice, to look the payment up by
+    pub payment_hash: Option<crate::Sha256>,
+}
+
+/// Final state of the payment of a pending melt, see
+/// [Mint::resolve_pending_melt]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MeltOutcome {
+    /// Invoice was paid with `fee` in routing fees
+    Settled { preimage: String, fee: Amount },
+    /// Payment failed and will not be retried
+    Failed,
+}
+
+/// Result of [Mint::self_check]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SelfCheckReport {
+    pub keysets: Vec<KeySetCheck>,
+}
+
+impl SelfCheckReport {
+    /// Every keyset passed
+    pub fn passed(&self) -> bool {
+        self.keysets.iter().all(KeySetCheck::passed)
+    }
+}
+
+/// Checks of a single stored keyset
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeySetCheck {
+    pub id: Id,
+    pub unit: CurrencyUnit,
+    pub active: bool,
+    /// Id recomputed from the public keys matches
+    pub id_matches: bool,
+    /// Every secret key signs for its public key
+    pub keys_valid: bool,
+    /// Re-derived keyset matches, [None] when the derivation is unknown
+    pub derivation_matches: Option<bool>,
+}
+
+impl KeySetCheck {
+    pub fn passed(&self) -> bool {
+        self.id_matches && self.keys_valid && self.derivation_matches != Some(false)
+    }
+}
+
+/// Invariant found not to hold by [Mint::check_invariants]
+#[cfg(feature = "invariant-checks")]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "invariant", rename_all = "snake_case")]
+pub enum InvariantViolation {
+    /// Issued ecash of `unit` is not the redeemed plus the `outstanding`
+    /// ecash accounted for by the operations of the mint
+    Ledger {
+        unit: CurrencyUnit,
+        /// Total value of blind signatures
+        issued: Amount,
+        /// Total value of spent proofs
+        redeemed: Amount,
+        outstanding: i128,
+        /// `issued - redeemed - outstanding`
+        difference: i128,
+    },
+    /// Input reserved by the melt `quote_id` is also spent
+    PendingAndSpent { quote_id: String, y: PublicKey },
+}
+
+/// Spent proofs of a refused keyset, removed by [Mint::archive_spent_for_keyset]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivedSpend {
+    pub keyset_id: Id,
+    /// Unix time the proofs were archived
+    pub archived_at: u64,
+    /// Hex SHA-256 of the sorted, concatenated `Y`s of `proofs`
+    pub commitment: String,
+    pub proofs: Proofs,
+}
+
+impl ArchivedSpend {
+    fn new(keyset_id: Id, archived_at: u64, proofs: Proofs) -> Result<Self, Error> {
+        let commitment = Self::commit(&proofs)?;
+
+        Ok(Self {
+            keyset_id,
+            archived_at,
+            commitment,
+            proofs,
+        })
+    }
+
+    /// `Y`s of the archived proofs
+    pub fn ys(&self) -> Result<Vec<PublicKey>, Error> {
+        Ok(self.proofs.iter().map(Proof::y).collect::<Result<_, _>>()?)
+    }
+
+    /// Whether the proofs still match the commitment and all belong to the
+    /// archived keyset
+    pub fn verify(&self) -> Result<bool, Error> {
+        Ok(self
+            .proofs
+            .iter()
+            .all(|proof| proof.keyset_id == self.keyset_id)
+            && Self::commit(&self.proofs)? == self.commitment)
+    }
+
+    /// Whether the secret with `y` is recorded in the archive
+    pub fn contains(&self, y: &PublicKey) -> Result<bool, Error> {
+        Ok(self.ys()?.contains(y))
+    }
+
+    fn commit(proofs: &Proofs) -> Result<String, Error> {
+        use bitcoin::hashes::Hash;
+
+        let mut ys = proofs
+            .iter()
+            .map(|proof| Ok(proof.y()?.to_bytes()))
+            .collect::<Result<Vec<[u8; 33]>, Error>>()?;
+        ys.sort_unstable();
+
+        Ok(crate::Sha256::hash(&ys.concat()).to_string())
+    }
+}
+
+/// Liabilities of one keyset in an [AuditReport]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeysetAudit {
+    pub id: Id,
+    pub unit: CurrencyUnit,
+    pub active: bool,
+    /// Total value of blind signatures
+    pub issued: Amount,
+    /// Total value of spent proofs
+    pub redeemed: Amount,
+    /// `issued - redeemed`, or zero if more was redeemed than issued, e.g.
+    /// after importing spent proofs from another mint
+    pub outstanding: Amount,
+    pub spent_proofs: u64,
+    pub keys: Keys,
+}
+
+/// Statement of the mint's liabilities, see [Mint::generate_audit_report]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditReport {
+    /// Unix time the report was generated
+    pub generated_at: u64,
+    /// NUT-06 pubkey of the mint
+    pub pubkey: Option<PublicKey>,
+    /// Keysets ordered by id
+    pub keysets: Vec<KeysetAudit>,
+    /// Hex Schnorr signature of [AuditReport::canonical_bytes]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+impl AuditReport {
+    /// Total outstanding ecash of `unit`
+    pub fn outstanding(&self, unit: &CurrencyUnit) -> Amount {
+        self.keysets
+            .iter()
+            .filter(|keyset| keyset.unit == *unit)
+            .fold(Amount::ZERO, |total, keyset| {
+                total.saturating_add(keyset.outstanding)
+            })
+    }
+
+    /// JSON encoding of the report without its signature
+    pub fn canonical_bytes(&self) -> Result<Vec<u8>, Error> {
+        let unsigned = Self {
+            signature: None,
+            ..self.clone()
+        };
+
+        Ok(serde_json::to_vec(&unsigned).map_err(crate::error::Error::from)?)
+    }
+
+    /// Sign the report with the secret key of the mint pubkey
+    ///
+    /// Sets the pubkey of the report if it has none.
+    pub fn sign(&mut self, secret_key: SecretKey) -> Result<(), Error> {
+        let pubkey = secret_key.public_key();
+        match self.pubkey {
+            Some(mint_pubkey) if mint_pubkey != pubkey => return Err(Error::AuditKeyMismatch),
+            _ => self.pubkey = Some(pubkey),
+        }
+
+        let signature = SigningKey::new(secret_key).sign(&self.canonical_bytes()?)?;
+        self.signature = Some(signature.to_string());
+
+        Ok(())
+    }
+
+    /// Whether the report is signed by its pubkey
+    pub fn verify(&self) -> Result<bool, Error> {
+        let (Some(pubkey), Some(signature)) = (self.pubkey, &self.signature) else {
+            return Ok(false);
+        };
+        let signature = bitcoin::secp256k1::schnorr::Signature::from_str(signature)
+            .map_err(|_| crate::nuts::nut11::Error::InvalidSignature)?;
+
+        Ok(VerifyingKey::from_public_key(pubkey)
+            .verify(&self.canonical_bytes()?, &signature)
+            .is_ok())
+    }
+}
+
+/// Why [Mint::prune_expired_quotes] kept a quote
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum RetainReason {
+    /// Quote has not expired or has no expiry
+    Unexpired,
+    /// Mint quote is paid but its ecash has not been issued
+    PaidNotIssued,
+    /// Melt quote payment is in flight
+    Pending,
+    /// Mint quote is issued or melt quote is paid
+    Settled,
+}
+
+/// Result of [Mint::prune_expired_quotes]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    pub removed_mint_quotes: usize,
+    pub removed_melt_quotes: usize,
+    pub removed_swap_responses: usize,
+    /// Number of quotes kept for each reason
+    pub retained: BTreeMap<RetainReason, usize>,
+}
+
+/// Upper bounds on a single mint, swap or melt request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RequestLimits {
+    /// Max number of proofs spent in one request
+    pub max_inputs: usize,
+    /// Max number of blinded messages signed in one request
+    pub max_outputs: usize,
+    /// Max value of the inputs or outputs of one request
+    pub max_request_amount: Option<Amount>,
+    /// Max length in bytes of a mint quote invoice description, `0` disables
+    /// descriptions
+    pub max_description_length: usize,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        Self {
+            max_inputs: 1_000,
+            max_outputs: 1_000,
+            max_request_amount: None,
+            // Longest description a BOLT11 invoice can carry
+            max_description_length: 639,
+        }
+    }
+}
+
+/// Schedule for rotating the active keyset of a unit, see
+/// [Mint::rotate_if_due]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RotationPolicy {
+    /// Seconds a keyset stays active
+    pub interval: u64,
+    /// Max order of the power of two denominations of new keysets
+    pub max_order: u8,
+    /// Seconds proofs of a rotated out keyset are still accepted
+    pub keep_accepting_for: u64,
+}
+
+#[derive(Debug, Hash, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MintKeySetInfo {
+    pub id: Id,
+    pub unit: CurrencyUnit,
+    pub active: bool,
+    pub valid_from: u64,
+    pub valid_to: Option<u64>,
+    pub derivation_path: DerivationPath,
+    pub max_order: u8,
+    /// Denominations to generate keys for instead of the powers of two
+    /// below `max_order`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amounts: Option<Vec<u64>>,
+    /// Id the keyset had before version `00` ids, still accepted on proofs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub legacy_id: Option<Id>,
+    /// Fee in thousandths of a unit per proof spent [NUT-02]
+    #[serde(default)]
+    pub input_fee_ppk: u64,
+}
+
+impl MintKeySetInfo {
+    /// Denominations of the keyset
+    ///
+    /// Fails if there are no [MintKeySetInfo::amounts] and
+    /// [MintKeySetInfo::max_order] is out of bounds.
+    pub fn amounts(&self) -> Result<Vec<u64>, nut02::Error> {
+        match &self.amounts {
+            Some(amounts) => Ok(amounts.clone()),
+            None => power_of_two_amounts(self.max_order),
+        }
+    }
+}
+
+/// Share of a backend fee estimate added on top of it, in percent
+const ESTIMATE_MARGIN_PERCENT: u64 = 10;
+
+/// Backend fee estimate with a margin of [ESTIMATE_MARGIN_PERCENT], at least
+/// one sat
+fn with_estimate_margin(estimate: Amount) -> Amount {
+    let estimate = u64::from(estimate);
+    let margin = estimate
+        .saturating_mul(ESTIMATE_MARGIN_PERCENT)
+        .saturating_add(99)
+        / 100;
+
+    Amount::from(estimate.saturating_add(margin.max(1)))
+}
+
+/// Inputs a melt of `quote` needs, its amount, fee reserve and `input_fee`
+fn melt_total(quote: &MeltQuote, input_fee: Amount) -> Result<Amount, Error> {
+    Ok(quote
+        .amount
+        .checked_add(quote.fee_reserve)
+        .and_then(|total| total.checked_add(input_fee))
+        .ok_or(nut00::Error::AmountOverflow)?)
+}
+
+fn check_not_frozen(keyset: &MintKeySet) -> Result<(), Error> {
+    match &keyset.frozen {
+        Some(reason) => Err(Error::KeySetFrozen(reason.clone())),
+        None => Ok(()),
+    }
+}
+
+fn check_denominations(amounts: &[u64]) -> Result<(), Error> {
+    if amounts.is_empty() || amounts.contains(&0) {
+        return Err(Error::InvalidDenominations);
+    }
+
+    Ok(())
+}
+
+impl FeeReserve {
+    /// Lightning fee reserve required to pay `amount`
+    ///
+    /// The percentage is applied in whole parts per million and any
+    /// fraction of a sat is rounded up.
+    pub fn fee_reserve(&self, amount: Amount) -> Amount {
+        self.fee_reserve_for(u128::from(u64::from(amount)) * u128::from(Amount::MSAT_PER_SAT))
+    }
+
+    /// Lightning fee reserve in sats required to pay `amount_msat`
+    ///
+    /// Same rounding as [FeeReserve::fee_reserve], the fraction of a sat is
+    /// only rounded up once.
+    pub fn fee_reserve_msat(&self, amount_msat: u64) -> Amount {
+        self.fee_reserve_for(u128::from(amount_msat))
+    }
+
+    fn fee_reserve_for(&self, amount_msat: u128) -> Amount {
+        const PPM: u128 = 1_000_000;
+        const PPM_MSAT: u128 = PPM * Amount::MSAT_PER_SAT as u128;
+
+        let ppm = (f64::from(self.percent_fee_reserve) * PPM as f64)
+            .round()
+            .max(0.0) as u128;
+        // Only a percentage far above 100 can overflow, which clamps the
+        // reserve at `u64::MAX` sats either way
+        let percent_fee_reserve =
+            amount_msat.saturating_mul(ppm).saturating_add(PPM_MSAT - 1) / PPM_MSAT;
+
+        Amount::from(u64::try_from(percent_fee_reserve).unwrap_or(u64::MAX))
+            .max(self.min_fee_reserve)
+    }
+}
+
+impl From<MintKeySetInfo> for KeySetInfo {
+    fn from(keyset_info: MintKeySetInfo) -> Self {
+        Self {
+            id: keyset_info.id,
+            unit: keyset_info.unit,
+            active: keyset_info.active,
+            input_fee_ppk: keyset_info.input_fee_ppk,
+            valid_from: Some(keyset_info.valid_from),
+            valid_to: keyset_info.valid_to,
+            derivation_path_index: derivation_path_index(&keyset_info.derivation_path),
+            frozen: false,
+        }
+    }
+}
+
+/// When `id` was first stored, or now for a new keyset
+async fn first_seen(
+    localstore: &(dyn MintDatabase<Err = cdk_database::Error> + Send + Sync),
+    id: &Id,
+) -> Result<u64, Error> {
+    Ok(match localstore.get_keyset(id).await? {
+        Some(keyset) if keyset.valid_from != 0 => keyset.valid_from,
+        _ => unix_time(),
+    })
+}
+
+/// Ensure no keyset of another unit is stored under the id of `keyset`
+async fn check_keyset_unit(
+    localstore: &(dyn MintDatabase<Err = cdk_database::Error> + Send + Sync),
+    keyset: &MintKeySet,
+) -> Result<(), Error> {
+    match localstore.get_keyset(&keyset.id).await? {
+        Some(stored) if stored.unit != keyset.unit => Err(Error::KeysetUnitConflict {
+            id: keyset.id,
+            unit: stored.unit,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Distinct keyset ids as a sorted, comma separated list for log fields
+/// Key of the response to `swap_request` in the response cache
+fn swap_request_hash(swap_request: &SwapRequest) -> Result<crate::Sha256, Error> {
+    use bitcoin::hashes::Hash;
+
+    let json = serde_json::to_vec(swap_request).map_err(crate::error::Error::from)?;
+
+    Ok(crate::Sha256::hash(&json))
+}
+
+fn keyset_ids(ids: impl Iterator<Item = Id>) -> String {
+    let ids: BTreeSet<String> = ids.map(|id| id.to_string()).collect();
+    ids.into_iter().collect::<Vec<_>>().join(",")
+}
+
+/// Spending conditions of a single proof at `now`
+fn verify_conditions(proof: &Proof, now: u64) -> Result<(), Error> {
+    // Check if secret is a nut10 secret with conditions
+    if let Ok(secret) =
+        <&crate::secret::Secret as TryInto<crate::nuts::nut10::Secret>>::try_into(&proof.secret)
+    {
+        // Verify if p2pk
+        if secret.kind.eq(&Kind::P2PK) {
+            proof.verify_p2pk_at(now)?;
+        } else {
+            return Err(Error::UnknownSecretKind);
+        }
+    }
+
+    Ok(())
+}
+
+/// Index and error of the first of `proofs` whose spending conditions do not
+/// hold at `now`
+#[cfg(not(feature = "parallel"))]
+fn first_condition_error(proofs: &Proofs, now: u64) -> Option<(usize, Error)> {
+    proofs
+        .iter()
+        .enumerate()
+        .find_map(|(index, proof)| verify_conditions(proof, now).err().map(|err| (index, err)))
+}
+
+#[cfg(feature = "parallel")]
+fn first_condition_error(proofs: &Proofs, now: u64) -> Option<(usize, Error)> {
+    use rayon::prelude::*;
+
+    proofs
+        .par_iter()
+        .enumerate()
+        .find_map_first(|(index, proof)| {
+            verify_conditions(proof, now).err().map(|err| (index, err))
+        })
+}
+
+/// BIP-32 path of the identity key of a [Mint], keysets are derived with
+/// NUT-02 from the mnemonic instead
+pub const IDENTITY_DERIVATION_PATH: &str = "m/1'/0'";
+
+fn identity_key_from_mnemonic(mnemonic: &Mnemonic) -> Result<SecretKey, Error> {
+    let path = DerivationPath::from_str(IDENTITY_DERIVATION_PATH)
+        .map_err(|e| Error::Custom(e.to_string()))?;
+    let xpriv = ExtendedPrivKey::new_master(bitcoin::Network::Bitcoin, &mnemonic.to_seed(""))
+        .and_then(|root| root.derive_priv(&SECP256K1, &path))
+        .map_err(|e| Error::Custom(e.to_string()))?;
+
+    Ok(xpriv.private_key.into())
+}
+
+/// Derivation path `m/0'/{unit}'/{index}'` of the `index`th keyset of `unit`
+pub fn derivation_path_from_unit(unit: &CurrencyUnit, index: u32) -> Result<DerivationPath, Error> {
+    let unit_index = unit
+        .derivation_index()
+        .ok_or_else(|| Error::Custom(format!("No derivation index for unit `{unit}`")))?;
+
+    Ok(DerivationPath::from(vec![
+        ChildNumber::from_hardened_idx(0).map_err(|e| Error::Custom(e.to_string()))?,
+        ChildNumber::from_hardened_idx(unit_index).map_err(|e| Error::Custom(e.to_string()))?,
+        ChildNumber::from_hardened_idx(index).map_err(|e| Error::Custom(e.to_string()))?,
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::cdk_database::mint_memory::MintMemoryDatabase;
+    use crate::cdk_database::mint_tiered::MintTieredDatabase;
+    use crate::dhke::{blind_message, construct_proofs};
+    use crate::secret::Secret;
+    use crate::test_utils::fake_ln::{FakeLnBackend, InvoiceScript};
+    use crate::test_utils::slow_signatory::SlowSignatory;
+    use crate::util::unix_time;
+
+    const MNEMONIC: &str =
+        "half depart obvious quality work element tank gorilla view sugar picture humble";
+
+    const INVOICE: &str = "lnbc2500u1pj48ugqdq8w3jhxaqpp5qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqsp59g4z52329g4z52329g4z52329g4z52329g4z52329g4z52329g4q9qrsgqcqzysd6exwz2yjprkglq820pffp8wcu97sxuzcgx7raensa83dnc4kpmhkuqevwf6hmv6a3mc9aegn89jv7a8e9wggdxtfsjwrur7n6sx4rsq7rvjx3";
+
+    fn keyset_info(unit: CurrencyUnit, path: &str, active: bool) -> MintKeySetInfo {
+        MintKeySetInfo {
+            id: Id::from_str("009a1f293253e41e").unwrap(),
+            unit,
+            active,
+            valid_from: 0,
+            valid_to: None,
+            derivation_path: DerivationPath::from_str(path).unwrap(),
+            max_order: 32,
+            amounts: None,
+            legacy_id: None,
+            input_fee_ppk: 0,
+        }
+    }
+
+    /// Mint signing through a [SlowSignatory], as if it were remote
+    async fn create_mint(keysets_info: HashSet<MintKeySetInfo>) -> Mint {
+        let localstore = Arc::new(
+            MintMemoryDatabase::new(
+                MintInfo::default(),
+                HashMap::new(),
+                vec![],
+                vec![],
+                vec![],
+                vec![],
+                vec![],
+                HashMap::new(),
+            )
+            .unwrap(),
+        );
+
+        let mut mint = Mint::new(
+            Arc::clone(&localstore)
+                as Arc<dyn MintDatabase<Err = cdk_database::Error> + Send + Sync>,
+            Mnemonic::from_str(MNEMONIC).unwrap(),
+            keysets_info,
+            Amount::from(1),
+            0.01,
+        )
+        .await
+        .unwrap();
+        mint.set_signatory(Arc::new(SlowSignatory::new(
+            LocalSignatory::new(localstore),
+            std::time::Duration::from_millis(1),
+        )));
+
+        mint
+    }
+
+    fn blinded_message(amount: Amount, keyset_id: Id) -> BlindedMessage {
+        let secret = Secret::generate();
+        let (blinded_secret, _r) = blind_message(&secret.to_bytes(), None).unwrap();
+        BlindedMessage::new(amount, keyset_id, blinded_secret)
+    }
+
+    async fn active_keyset_id(mint: &Mint, unit: &CurrencyUnit) -> Id {
+        mint.localstore
+            .get_active_keyset_id(unit)
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    async fn mint_proofs(mint: &Mint, keyset_id: Id, amount: Amount) -> Proofs {
+        let pre_mint = PreMintSecrets::random(keyset_id, amount).unwrap();
+
+        let mut signatures = Vec::new();
+        for blinded_message in pre_mint.blinded_messages() {
+            signatures.push(mint.blind_sign(&blinded_message).await.unwrap());
+        }
+
+        let keyset = mint
+            .localstore
+            .get_keyset(&keyset_id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        construct_proofs(
+            signatures,
+            pre_mint.rs(),
+            pre_mint.secrets(),
+            &keyset.keys.into(),
+        )
+        .unwrap()
+    }
+
+    /// Subscriber that records spans with their parent and every field
+    #[derive(Clone, Default)]
+    struct Recorder {
+        state: Arc<std::sync::Mutex<RecorderState>>,
+    }
+
+    #[derive(Default)]
+    struct RecorderState {
+        /// Name and parent name of each span, indexed by span id - 1
+        spans: Vec<(&'static str, Option<&'static str>)>,
+        entered: Vec<u64>,
+        output: String,
+    }
+
+    struct FieldWriter<'a>(&'a mut String);
+
+    impl tracing::field::Visit for FieldWriter<'_> {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0.push_str(&format!(" {}={:?}", field.name(), value));
+        }
+    }
+
+    impl tracing::Subscriber for Recorder {
+        fn enabled(&self, _metadata: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            let mut guard = self.state.lock().unwrap();
+            let state = &mut *guard;
+            let parent = state
+                .entered
+                .last()
+                .map(|id| state.spans[*id as usize - 1].0);
+            state.spans.push((span.metadata().name(), parent));
+            state.output.push_str(span.metadata().name());
+            span.record(&mut FieldWriter(&mut state.output));
+            state.output.push('\n');
+
+            tracing::span::Id::from_u64(state.spans.len() as u64)
+        }
+
+        fn record(&self, _span: &tracing::span::Id, values: &tracing::span::Record<'_>) {
+            let mut state = self.state.lock().unwrap();
+            values.record(&mut FieldWriter(&mut state.output));
+            state.output.push('\n');
+        }
+
+        fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+
+        fn event(&self, event: &tracing::Event<'_>) {
+            let mut state = self.state.lock().unwrap();
+            event.record(&mut FieldWriter(&mut state.output));
+            state.output.push('\n');
+        }
+
+        fn enter(&self, span: &tracing::span::Id) {
+            self.state.lock().unwrap().entered.push(span.into_u64());
+        }
+
+        fn exit(&self, _span: &tracing::span::Id) {
+            self.state.lock().unwrap().entered.pop();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blind_sign_selects_keyset_by_id() {
+        let mint = create_mint(HashSet::from([
+            keyset_info(CurrencyUnit::Sat, "m/0'/0'/0'", true),
+            keyset_info(CurrencyUnit::Usd, "m/0'/1'/0'", true),
+        ]))
+        .await;
+
+        let usd_id = mint
+            .localstore
+            .get_active_keyset_id(&CurrencyUnit::Usd)
+            .await
+            .unwrap()
+            .unwrap();
+        let sat_id = mint
+            .localstore
+            .get_active_keyset_id(&CurrencyUnit::Sat)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_ne!(usd_id, sat_id);
+
+        let message = blinded_message(Amount::from(8), usd_id);
+        let signature = mint.blind_sign(&message).await.unwrap();
+
+        assert_eq!(signature.keyset_id, usd_id);
+        assert_eq!(signature.amount, Amount::from(8));
+
+        let usd_keyset = mint.localstore.get_keyset(&usd_id).await.unwrap().unwrap();
+        let sat_keyset = mint.localstore.get_keyset(&sat_id).await.unwrap().unwrap();
+        assert!(signature
+            .verify_dleq(
+                usd_keyset.keys[&Amount::from(8)].public_key,
+                message.blinded_secret
+            )
+            .is_ok());
+        assert!(signature
+            .verify_dleq(
+                sat_keyset.keys[&Amount::from(8)].public_key,
+                message.blinded_secret
+            )
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mint_requests_sign_with_keyset_of_each_unit() {
+        let mut mint = create_mint(HashSet::from([
+            keyset_info(CurrencyUnit::Sat, "m/0'/0'/0'", true),
+            keyset_info(CurrencyUnit::Usd, "m/0'/1'/0'", true),
+        ]))
+        .await;
+
+        for unit in [CurrencyUnit::Sat, CurrencyUnit::Usd] {
+            let keyset_id = mint
+                .localstore
+                .get_active_keyset_id(&unit)
+                .await
+                .unwrap()
+                .unwrap();
+
+            let mut quote = mint
+                .new_mint_quote(
+                    PaymentMethod::Bolt11,
+                    INVOICE.to_string(),
+                    unit.clone(),
+                    Amount::from(12),
+                    unix_time() + 600,
+                    None,
+                )
+                .await
+                .unwrap();
+            quote.paid = true;
+            mint.update_mint_quote(quote.clone()).await.unwrap();
+
+            let pre_mint = PreMintSecrets::random(keyset_id, Amount::from(12)).unwrap();
+            let response = mint
+                .process_mint_request(nut04::MintBolt11Request::new(
+                    quote.id,
+                    pre_mint.blinded_messages(),
+                ))
+                .await
+                .unwrap();
+
+            let keyset = mint
+                .localstore
+                .get_keyset(&keyset_id)
+                .await
+                .unwrap()
+                .unwrap();
+            for (signature, message) in response.signatures.iter().zip(pre_mint.blinded_messages())
+            {
+                assert_eq!(signature.keyset_id, keyset_id);
+                assert!(signature
+                    .verify_dleq(
+                        keyset.keys[&signature.amount].public_key,
+                        message.blinded_secret
+                    )
+                    .is_ok());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_signatory_failures() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let keyset_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+        let signatory = Arc::new(SlowSignatory::new(
+            LocalSignatory::new(Arc::clone(&mint.localstore)),
+            std::time::Duration::from_millis(20),
+        ));
+        mint.set_signatory(Arc::clone(&signatory) as Arc<dyn Signatory>);
+        let keys: Keys = mint
+            .localstore
+            .get_keyset(&keyset_id)
+            .await
+            .unwrap()
+            .unwrap()
+            .keys
+            .into();
+
+        // Outputs are signed in a single call, and not stored when it fails
+        let mut quote = mint
+            .new_mint_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                Amount::from(100),
+                unix_time() + 600,
+                None,
+            )
+            .await
+            .unwrap();
+        quote.paid = true;
+        mint.update_mint_quote(quote.clone()).await.unwrap();
+        let pre_mint = PreMintSecrets::random(keyset_id, Amount::from(100)).unwrap();
+        let request = nut04::MintBolt11Request::new(quote.id, pre_mint.blinded_messages());
+
+        signatory.set_failing(true);
+        assert!(matches!(
+            mint.process_mint_request(request.clone()).await,
+            Err(Error::Signatory(crate::signatory::Error::Backend(_)))
+        ));
+        signatory.set_failing(false);
+        let signatures = mint.process_mint_request(request).await.unwrap().signatures;
+        assert_eq!(signatory.calls(), 2);
+        let inputs =
+            construct_proofs(signatures, pre_mint.rs(), pre_mint.secrets(), &keys).unwrap();
+
+        // A failed swap leaves its inputs unspent
+        let pre_swap = PreMintSecrets::random(keyset_id, Amount::from(100)).unwrap();
+        let swap_request = SwapRequest::new(inputs.clone(), pre_swap.blinded_messages());
+        signatory.set_failing(true);
+        for _ in 0..2 {
+            assert!(matches!(
+                mint.process_swap_request(swap_request.clone()).await,
+                Err(Error::Signatory(crate::signatory::Error::Backend(_)))
+            ));
+        }
+        signatory.set_failing(false);
+        assert!(mint.verify_proofs(&inputs).await.iter().all(Result::is_ok));
+
+        // Inputs are verified and outputs signed in one call each
+        let calls = signatory.calls();
+        let signatures = mint
+            .process_swap_request(swap_request)
+            .await
+            .unwrap()
+            .signatures;
+        assert_eq!(signatory.calls(), calls + 2);
+        let inputs =
+            construct_proofs(signatures, pre_swap.rs(), pre_swap.secrets(), &keys).unwrap();
+
+        // A melt whose inputs cannot be verified is not paid
+        let melt_quote = mint
+            .new_melt_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                Amount::from(90),
+                unix_time() + 600,
+            )
+            .await
+            .unwrap();
+        let melt_request = MeltBolt11Request::new(melt_quote.id.clone(), inputs.clone(), None);
+        signatory.set_failing(true);
+        assert!(matches!(
+            mint.process_melt_request(&melt_request, "preimage", Amount::from(91))
+                .await,
+            Err(Error::Signatory(crate::signatory::Error::Backend(_)))
+        ));
+        signatory.set_failing(false);
+        assert!(mint.verify_proofs(&inputs).await.iter().all(Result::is_ok));
+        assert_eq!(mint.accounting().melts(&CurrencyUnit::Sat).melts, 0);
+
+        mint.process_melt_request(&melt_request, "preimage", Amount::from(91))
+            .await
+            .unwrap();
+        for result in mint.verify_proofs(&inputs).await {
+            assert!(matches!(result, Err(Error::TokenSpent)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blind_sign_rejects_unknown_and_inactive_keyset() {
+        let mint = create_mint(HashSet::from([
+            keyset_info(CurrencyUnit::Sat, "m/0'/0'/0'", true),
+            keyset_info(CurrencyUnit::Sat, "m/0'/0'/1'", false),
+        ]))
+        .await;
+
+        let unknown_id = Id::from_str("009a1f293253e41e").unwrap();
+        assert!(matches!(
+            mint.blind_sign(&blinded_message(Amount::from(1), unknown_id))
+                .await,
+            Err(Error::UnknownKeySet)
+        ));
+
+        let active_id = mint
+            .localstore
+            .get_active_keyset_id(&CurrencyUnit::Sat)
+            .await
+            .unwrap()
+            .unwrap();
+        let inactive_id = mint
+            .localstore
+            .get_keysets()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|k| k.id)
+            .find(|id| id.ne(&active_id))
+            .unwrap();
+
+        assert!(matches!(
+            mint.blind_sign(&blinded_message(Amount::from(1), inactive_id))
+                .await,
+            Err(Error::InactiveKeyset { .. })
+        ));
+    }
+
+    #[cfg(feature = "wallet")]
+    #[tokio::test]
+    async fn test_swap_inputs_from_several_keyset_generations() {
+        let mut mint = create_mint(HashSet::new()).await;
+
+        let first_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+        let mut inputs = mint_proofs(&mint, first_id, Amount::from(5)).await;
+
+        for index in 1..=2 {
+            mint.rotate_keyset(
+                CurrencyUnit::Sat,
+                &derivation_path_from_unit(&CurrencyUnit::Sat, index).unwrap(),
+                32,
+            )
+            .await
+            .unwrap();
+            let keyset_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+            inputs.extend(mint_proofs(&mint, keyset_id, Amount::from(5)).await);
+        }
+
+        let current_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+        let generations: HashSet<Id> = inputs.iter().map(|proof| proof.keyset_id).collect();
+        assert_eq!(generations.len(), 3);
+        assert!(generations.contains(&first_id));
+
+        let pre_swap = SwapRequestBuilder::new()
+            .inputs(inputs.clone())
+            .outputs_for(Amount::from(15), current_id)
+            .unwrap()
+            .build()
+            .unwrap();
+        let response = mint
+            .process_swap_request(pre_swap.swap_request)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response
+                .signatures
+                .iter()
+                .map(|signature| signature.amount)
+                .sum::<Amount>(),
+            Amount::from(15)
+        );
+        assert!(response
+            .signatures
+            .iter()
+            .all(|signature| signature.keyset_id == current_id));
+        for result in mint.verify_proofs(&inputs).await {
+            assert!(matches!(result, Err(Error::TokenSpent)));
+        }
+    }
+
+    #[cfg(feature = "wallet")]
+    #[tokio::test]
+    async fn test_swap_rejects_inputs_of_different_units() {
+        let mut mint = create_mint(HashSet::from([
+            keyset_info(CurrencyUnit::Sat, "m/0'/0'/0'", true),
+            keyset_info(CurrencyUnit::Usd, "m/0'/1'/0'", true),
+        ]))
+        .await;
+        let sat_id = mint
+            .localstore
+            .get_active_keyset_id(&CurrencyUnit::Sat)
+            .await
+            .unwrap()
+            .unwrap();
+        let usd_id = mint
+            .localstore
+            .get_active_keyset_id(&CurrencyUnit::Usd)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let mut inputs = mint_proofs(&mint, sat_id, Amount::from(4)).await;
+        inputs.extend(mint_proofs(&mint, usd_id, Amount::from(4)).await);
+
+        let pre_swap = SwapRequestBuilder::new()
+            .inputs(inputs.clone())
+            .outputs_for(Amount::from(8), sat_id)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert!(matches!(
+            mint.process_swap_request(pre_swap.swap_request).await,
+            Err(Error::MultipleUnits)
+        ));
+        assert!(mint.verify_proofs(&inputs).await.iter().all(Result::is_ok));
+    }
+
+    #[tokio::test]
+    async fn test_output_keyset_ids_must_be_signable() {
+        let mut mint = create_mint(HashSet::from([
+            keyset_info(CurrencyUnit::Sat, "m/0'/0'/0'", true),
+            keyset_info(CurrencyUnit::Sat, "m/0'/0'/1'", false),
+            keyset_info(CurrencyUnit::Usd, "m/0'/1'/0'", true),
+        ]))
+        .await;
+        let active_id = mint
+            .localstore
+            .get_active_keyset_id(&CurrencyUnit::Sat)
+            .await
+            .unwrap()
+            .unwrap();
+        let usd_id = mint
+            .localstore
+            .get_active_keyset_id(&CurrencyUnit::Usd)
+            .await
+            .unwrap()
+            .unwrap();
+        let inactive_id = mint
+            .localstore
+            .get_keysets()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|k| k.id)
+            .find(|id| *id != active_id && *id != usd_id)
+            .unwrap();
+        let unknown_id = Id::from_str("009a1f293253e41e").unwrap();
+
+        let mut quote = mint
+            .new_mint_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                Amount::from(3),
+                unix_time() + 600,
+                None,
+            )
+            .await
+            .unwrap();
+        quote.paid = true;
+        mint.update_mint_quote(quote.clone()).await.unwrap();
+
+        // A valid output does not rescue an invalid one, nothing is signed
+        for bad_id in [inactive_id, unknown_id, usd_id] {
+            let outputs = vec![
+                blinded_message(Amount::from(2), active_id),
+                blinded_message(Amount::from(1), bad_id),
+            ];
+            let result = mint
+                .process_mint_request(nut04::MintBolt11Request::new(
+                    quote.id.clone(),
+                    outputs.clone(),
+                ))
+                .await;
+            if bad_id == inactive_id {
+                assert!(matches!(
+                    result,
+                    Err(Error::InactiveKeyset { id, active_id: Some(active) })
+                        if id == inactive_id && active == active_id
+                ));
+            } else if bad_id == unknown_id {
+                assert!(matches!(result, Err(Error::UnknownKeySet)));
+            } else {
+                assert!(matches!(result, Err(Error::MultipleUnits)));
+            }
+            for output in &outputs {
+                assert!(mint
+                    .localstore
+                    .get_blinded_signature(&output.blinded_secret)
+                    .await
+                    .unwrap()
+                    .is_none());
+            }
+        }
+
+        let inputs = mint_proofs(&mint, active_id, Amount::from(3)).await;
+        for bad_id in [inactive_id, unknown_id] {
+            let swap_request = SwapRequest::new(
+                inputs.clone(),
+                vec![
+                    blinded_message(Amount::from(2), active_id),
+                    blinded_message(Amount::from(1), bad_id),
+                ],
+            );
+            let result = mint.process_swap_request(swap_request).await;
+            if bad_id == inactive_id {
+                assert!(matches!(
+                    result,
+                    Err(Error::InactiveKeyset { id, active_id: Some(active) })
+                        if id == inactive_id && active == active_id
+                ));
+            } else {
+                assert!(matches!(result, Err(Error::UnknownKeySet)));
+            }
+        }
+        assert!(mint.verify_proofs(&inputs).await.iter().all(Result::is_ok));
+
+        let response = mint
+            .process_mint_request(nut04::MintBolt11Request::new(
+                quote.id.clone(),
+                vec![
+                    blinded_message(Amount::from(2), active_id),
+                    blinded_message(Amount::from(1), active_id),
+                ],
+            ))
+            .await
+            .unwrap();
+        assert!(response
+            .signatures
+            .iter()
+            .all(|signature| signature.keyset_id == active_id));
+    }
+
+    #[tokio::test]
+    async fn test_mint_request_signs_all_outputs_or_none() {
+        let mut mint = create_mint(HashSet::from([keyset_info(
+            CurrencyUnit::Sat,
+            "m/0'/0'/0'",
+            true,
+        )]))
+        .await;
+        let keyset_id = mint
+            .localstore
+            .get_active_keyset_id(&CurrencyUnit::Sat)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let mut quote = mint
+            .new_mint_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                Amount::from(8),
+                unix_time() + 600,
+                None,
+            )
+            .await
+            .unwrap();
+        quote.paid = true;
+        mint.update_mint_quote(quote.clone()).await.unwrap();
+
+        // No key for an amount of 3
+        let outputs = vec![
+            blinded_message(Amount::from(4), keyset_id),
+            blinded_message(Amount::from(1), keyset_id),
+            blinded_message(Amount::from(3), keyset_id),
+        ];
+        let mint_request = nut04::MintBolt11Request {
+            quote: quote.id.clone(),
+            outputs: outputs.clone(),
+        };
+
+        assert!(matches!(
+            mint.process_mint_request(mint_request).await,
+            Err(Error::AmountKey)
+        ));
+        for output in &outputs {
+            assert!(mint
+                .localstore
+                .get_blinded_signature(&output.blinded_secret)
+                .await
+                .unwrap()
+                .is_none());
+        }
+
+        // Signatures are returned in the order of the outputs
+        let outputs = &outputs[..2];
+        let signatures = mint.blind_sign_many(outputs).await.unwrap();
+        assert_eq!(signatures.len(), 2);
+        for (output, signature) in outputs.iter().zip(&signatures) {
+            assert_eq!(signature.amount, output.amount);
+        }
+    }
+
+    #[test]
+    fn test_derivation_path_from_unit() {
+        assert_eq!(
+            derivation_path_from_unit(&CurrencyUnit::Sat, 0).unwrap(),
+            DerivationPath::from_str("m/0'/0'/0'").unwrap()
+        );
+        assert_eq!(
+            derivation_path_from_unit(&CurrencyUnit::Eur, 2).unwrap(),
+            DerivationPath::from_str("m/0'/3'/2'").unwrap()
+        );
+        assert!(derivation_path_from_unit(&CurrencyUnit::Custom("btc".to_string()), 0).is_err());
+
+        // Each unit gets its own keys
+        let seed = Mnemonic::from_str(MNEMONIC).unwrap().to_seed_normalized("");
+        let sat = MintKeySet::generate(
+            &seed,
+            CurrencyUnit::Sat,
+            &derivation_path_from_unit(&CurrencyUnit::Sat, 0).unwrap(),
+            8,
+        )
+        .unwrap();
+        let msat = MintKeySet::generate(
+            &seed,
+            CurrencyUnit::Msat,
+            &derivation_path_from_unit(&CurrencyUnit::Msat, 0).unwrap(),
+            8,
+        )
+        .unwrap();
+        assert_ne!(sat.id, msat.id);
+    }
+
+    #[tokio::test]
+    async fn test_quote_rejects_unsupported_method() {
+        let mint = create_mint(HashSet::from([keyset_info(
+            CurrencyUnit::Sat,
+            "m/0'/0'/0'",
+            true,
+        )]))
+        .await;
+
+        for method in [PaymentMethod::Bolt12, PaymentMethod::from("onchain")] {
+            let mint_quote = mint
+                .new_mint_quote(
+                    method.clone(),
+                    INVOICE.to_string(),
+                    CurrencyUnit::Sat,
+                    Amount::from(100),
+                    unix_time() + 600,
+                    None,
+                )
+                .await;
+            assert!(matches!(mint_quote, Err(Error::UnsupportedMethod(m)) if m == method));
+
+            let melt_quote = mint
+                .new_melt_quote(
+                    method.clone(),
+                    INVOICE.to_string(),
+                    CurrencyUnit::Sat,
+                    Amount::from(100),
+                    unix_time() + 600,
+                )
+                .await;
+            assert!(matches!(melt_quote, Err(Error::UnsupportedMethod(m)) if m == method));
+        }
+
+        assert!(mint.mint_quotes().await.unwrap().is_empty());
+
+        let quote = mint
+            .new_mint_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                Amount::from(100),
+                unix_time() + 600,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            mint.check_mint_quote(&quote.id).await.unwrap().request,
+            INVOICE
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_fees_matches_melt_verification() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let keyset_id = mint
+            .localstore
+            .get_active_keyset_id(&CurrencyUnit::Sat)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let invoice = Bolt11Invoice::from_str(INVOICE).unwrap();
+        let amount = Amount::from_msat_floor(invoice.amount_milli_satoshis().unwrap());
+
+        let fees = mint
+            .check_fees(&CheckFeesRequest {
+                pr: invoice.clone(),
+            })
+            .unwrap();
+        assert_eq!(fees.fee, Amount::from(2_500));
+
+        let quote = mint
+            .new_melt_quote(
+                PaymentMethod::Bolt11,
+                invoice.to_string(),
+                CurrencyUnit::Sat,
+                amount,
+                unix_time() + 600,
+            )
+            .await
+            .unwrap();
+
+        let required = amount + fees.fee;
+        let short = MeltBolt11Request {
+            quote: quote.id.clone(),
+            inputs: mint_proofs(&mint, keyset_id, required - Amount::from(1)).await,
+            outputs: None,
+        };
+        assert_eq!(mint.required_melt_total(&short).await.unwrap(), required);
+        assert!(matches!(
+            mint.verify_melt_request(&short).await,
+            Err(Error::InsufficientMeltInputs { provided, required: r })
+                if provided == required - Amount::from(1) && r == required
+        ));
+
+        let exact = MeltBolt11Request {
+            quote: quote.id.clone(),
+            inputs: mint_proofs(&mint, keyset_id, required).await,
+            outputs: None,
+        };
+        assert_eq!(mint.required_melt_total(&exact).await.unwrap(), required);
+        mint.verify_melt_request(&exact).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_required_melt_total_includes_input_fee() {
+        // A fee of one sat for up to 20 proofs
+        let mut info = keyset_info(CurrencyUnit::Sat, "m/0'/0'/0'", true);
+        info.input_fee_ppk = 50;
+        let mut mint = create_mint(HashSet::from([info])).await;
+        let keyset_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+
+        let invoice = Bolt11Invoice::from_str(INVOICE).unwrap();
+        let amount = Amount::from_msat_floor(invoice.amount_milli_satoshis().unwrap());
+        let quote = mint
+            .new_melt_quote(
+                PaymentMethod::Bolt11,
+                invoice.to_string(),
+                CurrencyUnit::Sat,
+                amount,
+                unix_time() + 600,
+            )
+            .await
+            .unwrap();
+        let required = quote.amount + quote.fee_reserve + Amount::from(1);
+
+        // Funded at the invoice amount and fee reserve, but not the input fee
+        let short = MeltBolt11Request::new(
+            quote.id.clone(),
+            mint_proofs(&mint, keyset_id, required - Amount::from(1)).await,
+            None,
+        );
+        assert_eq!(mint.required_melt_total(&short).await.unwrap(), required);
+        assert!(matches!(
+            mint.verify_melt_request(&short).await,
+            Err(Error::InsufficientMeltInputs { provided, required: r })
+                if provided == required - Amount::from(1) && r == required
+        ));
+
+        let exact = MeltBolt11Request::new(
+            quote.id.clone(),
+            mint_proofs(&mint, keyset_id, required).await,
+            None,
+        );
+        assert_eq!(mint.required_melt_total(&exact).await.unwrap(), required);
+        mint.verify_melt_request(&exact).await.unwrap();
+
+        assert!(matches!(
+            mint.required_melt_total(&MeltBolt11Request::new("unknown".to_string(), vec![], None))
+                .await,
+            Err(Error::UnknownQuote)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_melt_required_total_overflow() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let keyset_id = mint
+            .localstore
+            .get_active_keyset_id(&CurrencyUnit::Sat)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let quote = mint
+            .new_melt_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                Amount::from(u64::MAX - 1),
+                unix_time() + 600,
+            )
+            .await
+            .unwrap();
+
+        // Without overflow checks the required total wrapped to less than
+        // the inputs, which passed the balance check
+        let wrapped = u64::from(quote.amount).wrapping_add(u64::from(quote.fee_reserve));
+        assert!(wrapped < u64::from(quote.fee_reserve));
+
+        let request = MeltBolt11Request {
+            quote: quote.id,
+            inputs: mint_proofs(&mint, keyset_id, Amount::from(wrapped.max(1))).await,
+            outputs: None,
+        };
+        assert!(matches!(
+            mint.verify_melt_request(&request).await,
+            Err(Error::NUT00(nut00::Error::AmountOverflow))
+        ));
+    }
+
+    #[test]
+    fn test_fee_reserve_minimum() {
+        let fee_reserve = FeeReserve {
+            min_fee_reserve: Amount::from(2),
+            percent_fee_reserve: 0.01,
+        };
+
+        assert_eq!(fee_reserve.fee_reserve(Amount::from(100)), Amount::from(2));
+        assert_eq!(
+            fee_reserve.fee_reserve(Amount::from(10_000)),
+            Amount::from(100)
+        );
+    }
+
+    #[test]
+    fn test_fee_reserve_rounds_sub_sat_up() {
+        let fee_reserve = FeeReserve {
+            min_fee_reserve: Amount::ZERO,
+            percent_fee_reserve: 0.001,
+        };
+
+        assert_eq!(fee_reserve.fee_reserve(Amount::ZERO), Amount::ZERO);
+        assert_eq!(fee_reserve.fee_reserve(Amount::from(1)), Amount::from(1));
+        assert_eq!(
+            fee_reserve.fee_reserve(Amount::from(1_000)),
+            Amount::from(1)
+        );
+        assert_eq!(
+            fee_reserve.fee_reserve(Amount::from(1_001)),
+            Amount::from(2)
+        );
+
+        // 0.1 is not exact as f32 but must not round 10 sats up to 2
+        let fee_reserve = FeeReserve {
+            min_fee_reserve: Amount::ZERO,
+            percent_fee_reserve: 0.1,
+        };
+        assert_eq!(fee_reserve.fee_reserve(Amount::from(10)), Amount::from(1));
+        assert_eq!(
+            fee_reserve.fee_reserve(Amount::from(u64::MAX)),
+            Amount::from(u64::MAX / 10 + 1)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fee_reserve_policy_is_adjustable() {
+        let mint = create_mint(HashSet::new()).await;
+        let shared = mint.clone();
+
+        let old_quote = mint
+            .new_melt_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                Amount::from(1_000),
+                unix_time() + 600,
+            )
+            .await
+            .unwrap();
+        assert_eq!(old_quote.fee_reserve, Amount::from(10));
+
+        shared.set_fee_reserve_policy(FeeReserve {
+            min_fee_reserve: Amount::from(50),
+            percent_fee_reserve: 0.02,
+        });
+        assert_eq!(mint.fee_reserve(Amount::from(1_000)), Amount::from(50));
+        assert_eq!(mint.fee_reserve(Amount::from(10_000)), Amount::from(200));
+
+        let new_quote = mint
+            .new_melt_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                Amount::from(1_000),
+                unix_time() + 600,
+            )
+            .await
+            .unwrap();
+        assert_eq!(new_quote.fee_reserve, Amount::from(50));
+        assert_eq!(
+            mint.check_melt_quote(&old_quote.id)
+                .await
+                .unwrap()
+                .fee_reserve,
+            10
+        );
+    }
+
+    #[test]
+    fn test_error_codes() {
+        use bitcoin::hashes::Hash;
+
+        // Fails to build when a variant is added, so it is pinned below
+        fn variant(error: &Error) -> &'static str {
+            match error {
+                Error::UnknownKeySet => "UnknownKeySet",
+                Error::InactiveKeyset { .. } => "InactiveKeyset",
+                Error::AmountKey => "AmountKey",
+                Error::Amount => "Amount",
+                Error::DuplicateProofs => "DuplicateProofs",
+                Error::TokenSpent => "TokenSpent",
+                Error::TokenPending => "TokenPending",
+                Error::UnpaidQuote => "UnpaidQuote",
+                Error::IssuedQuote => "IssuedQuote",
+                Error::Custom(_) => "Custom",
+                Error::Cashu(_) => "Cashu",
+                Error::Secret(_) => "Secret",
+                Error::NUT00(_) => "NUT00",
+                Error::NUT02(_) => "NUT02",
+                Error::NUT11(_) => "NUT11",
+                Error::Nut12(_) => "Nut12",
+                Error::Database(_) => "Database",
+                Error::UnknownQuote => "UnknownQuote",
+                Error::UnknownPaymentHash(_) => "UnknownPaymentHash",
+                Error::UnknownSecretKind => "UnknownSecretKind",
+                Error::MultipleUnits => "MultipleUnits",
+                Error::BlindedMessageAlreadySigned => "BlindedMessageAlreadySigned",
+                Error::UnsupportedMethod(_) => "UnsupportedMethod",
+                Error::RequestTooLarge { .. } => "RequestTooLarge",
+                Error::KeysetIdMismatch => "KeysetIdMismatch",
+                Error::KeysetUnitConflict { .. } => "KeysetUnitConflict",
+                Error::MultipleActiveKeysets(_) => "MultipleActiveKeysets",
+                Error::ActiveKeyset => "ActiveKeyset",
+                Error::InvalidDenominations => "InvalidDenominations",
+                Error::RefusedKeyset => "RefusedKeyset",
+                Error::AcceptedKeyset => "AcceptedKeyset",
+                Error::ArchiveCommitment => "ArchiveCommitment",
+                Error::NonBlankChangeOutput => "NonBlankChangeOutput",
+                Error::AuditKeyMismatch => "AuditKeyMismatch",
+                Error::PendingQuote => "PendingQuote",
+                Error::PaidQuote => "PaidQuote",
+                Error::MissingPreimage => "MissingPreimage",
+                Error::KeySetFrozen(_) => "KeySetFrozen",
+                Error::MeltNotPending => "MeltNotPending",
+                Error::Lightning(_) => "Lightning",
+                Error::Signatory(_) => "Signatory",
+                Error::InsufficientMeltInputs { .. } => "InsufficientMeltInputs",
+            }
+        }
+        const VARIANTS: usize = 42;
+
+        let id = Id::from_str("009a1f293253e41e").unwrap();
+        // (error, code, status)
+        let errors = [
+            (Error::UnknownKeySet, 12001, 404),
+            (
+                Error::InactiveKeyset {
+                    id,
+                    active_id: None,
+                },
+                12002,
+                400,
+            ),
+            (Error::AmountKey, 9999, 400),
+            (Error::Amount, 11002, 400),
+            (Error::DuplicateProofs, 11007, 400),
+            (Error::TokenSpent, 11001, 400),
+            (Error::TokenPending, 9999, 400),
+            (Error::UnpaidQuote, 20001, 400),
+            (Error::IssuedQuote, 20002, 400),
+            (Error::Custom("custom".to_string()), 9999, 500),
+            (Error::Cashu(crate::error::Error::TokenSpent), 11001, 400),
+            (
+                Error::Cashu(crate::error::Error::TokenNotVerifed),
+                10003,
+                400,
+            ),
+            (Error::Cashu(crate::error::Error::AmountKey), 9999, 400),
+            (
+                Error::Secret(crate::secret::Error::InvalidLength(0)),
+                9999,
+                400,
+            ),
+            (
+                Error::NUT00(crate::nuts::nut00::Error::ProofsRequired),
+                9999,
+                400,
+            ),
+            (Error::NUT02(crate::nuts::nut02::Error::Length), 9999, 400),
+            (
+                Error::NUT11(crate::nuts::nut11::Error::InvalidSignature),
+                10003,
+                400,
+            ),
+            (
+                Error::Nut12(crate::nuts::nut12::Error::InvalidDleqProof),
+                10003,
+                400,
+            ),
+            (
+                Error::Database(cdk_database::Error::Cdk(crate::error::Error::AmountKey)),
+                9999,
+                500,
+            ),
+            (Error::UnknownQuote, 9999, 404),
+            (
+                Error::UnknownPaymentHash(crate::Sha256::hash(b"unknown")),
+                9999,
+                404,
+            ),
+            (Error::UnknownSecretKind, 9999, 400),
+            (Error::MultipleUnits, 11005, 400),
+            (Error::BlindedMessageAlreadySigned, 10002, 400),
+            (Error::UnsupportedMethod(PaymentMethod::Bolt12), 9999, 400),
+            (
+                Error::RequestTooLarge {
+                    limit: "max_inputs",
+                    max: 1,
+                },
+                11006,
+                400,
+            ),
+            (Error::KeysetIdMismatch, 9999, 400),
+            (
+                Error::KeysetUnitConflict {
+                    id,
+                    unit: CurrencyUnit::Sat,
+                },
+                9999,
+                400,
+            ),
+            (Error::MultipleActiveKeysets(CurrencyUnit::Sat), 9999, 400),
+            (Error::ActiveKeyset, 9999, 400),
+            (Error::InvalidDenominations, 9999, 400),
+            (Error::RefusedKeyset, 12002, 400),
+            (Error::AcceptedKeyset, 9999, 400),
+            (Error::ArchiveCommitment, 9999, 400),
+            (Error::NonBlankChangeOutput, 9999, 400),
+            (Error::AuditKeyMismatch, 9999, 400),
+            (Error::PendingQuote, 20005, 400),
+            (Error::PaidQuote, 20006, 400),
+            (Error::MissingPreimage, 9999, 400),
+            (Error::KeySetFrozen("audit".to_string()), 12002, 400),
+            (Error::MeltNotPending, 9999, 400),
+            (
+                Error::Lightning(crate::lightning::Error::Unsupported("pay_invoice")),
+                9999,
+                400,
+            ),
+            (
+                Error::Lightning(crate::lightning::Error::PaymentFailed(
+                    "no route".to_string(),
+                )),
+                9999,
+                400,
+            ),
+            (
+                Error::Lightning(crate::lightning::Error::Backend("offline".into())),
+                9999,
+                500,
+            ),
+            (
+                Error::Signatory(crate::signatory::Error::UnknownKeyset(id)),
+                9999,
+                500,
+            ),
+            (
+                Error::Signatory(crate::signatory::Error::Backend("offline".into())),
+                9999,
+                500,
+            ),
+            (
+                Error::InsufficientMeltInputs {
+                    provided: Amount::from(1),
+                    required: Amount::from(2),
+                },
+                11002,
+                400,
+            ),
+        ];
+
+        let pinned: HashSet<&str> = errors.iter().map(|(error, _, _)| variant(error)).collect();
+        assert_eq!(pinned.len(), VARIANTS);
+
+        for (error, code, status) in errors {
+            let description = format!("{error:?}");
+            let detail = error.to_string();
+            assert_eq!(error.status_code(), status, "{description}");
+
+            let (http_status, response): (StatusCode, ErrorResponse) = error.into();
+            assert_eq!(http_status.as_u16(), status, "{description}");
+            assert_eq!(response.code.to_code(), code, "{description}");
+            assert_eq!(response.detail, Some(detail));
+        }
+        assert_eq!(
+            serde_json::to_string(&ErrorResponse::from(Error::UnpaidQuote)).unwrap(),
+            r#"{"code":20001,"detail":"Quote not paid"}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_multiple_active_keysets() {
+        let localstore = MintMemoryDatabase::new(
+            MintInfo::default(),
+            HashMap::new(),
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            HashMap::new(),
+        )
+        .unwrap();
+        let result = Mint::new(
+            Arc::new(localstore),
+            Mnemonic::from_str(MNEMONIC).unwrap(),
+            HashSet::from([
+                keyset_info(CurrencyUnit::Sat, "m/0'/0'/0'", true),
+                keyset_info(CurrencyUnit::Sat, "m/0'/0'/1'", true),
+                keyset_info(CurrencyUnit::Usd, "m/0'/1'/0'", true),
+            ]),
+            Amount::from(1),
+            0.01,
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(Error::MultipleActiveKeysets(CurrencyUnit::Sat))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_keyset_unit_conflict() {
+        let localstore = MintMemoryDatabase::new(
+            MintInfo::default(),
+            HashMap::new(),
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            HashMap::new(),
+        )
+        .unwrap();
+        // Ids commit to the keys only, so both units would share one id
+        let result = Mint::new(
+            Arc::new(localstore),
+            Mnemonic::from_str(MNEMONIC).unwrap(),
+            HashSet::from([
+                keyset_info(CurrencyUnit::Sat, "m/0'/0'/0'", true),
+                keyset_info(CurrencyUnit::Usd, "m/0'/0'/0'", true),
+            ]),
+            Amount::from(1),
+            0.01,
+        )
+        .await;
+        assert!(matches!(result, Err(Error::KeysetUnitConflict { .. })));
+
+        let mut mint = create_mint(HashSet::from([keyset_info(
+            CurrencyUnit::Sat,
+            "m/0'/0'/0'",
+            true,
+        )]))
+        .await;
+        let sat_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+        let path = DerivationPath::from_str("m/0'/0'/0'").unwrap();
+
+        assert!(matches!(
+            mint.rotate_keyset(CurrencyUnit::Usd, &path, 32).await,
+            Err(Error::KeysetUnitConflict { id, unit: CurrencyUnit::Sat }) if id == sat_id
+        ));
+        let seed = Mnemonic::from_str(MNEMONIC).unwrap().to_seed_normalized("");
+        let keyset = MintKeySet::generate(&seed, CurrencyUnit::Usd, &path, 32).unwrap();
+        assert!(matches!(
+            mint.add_inactive_keyset(keyset).await,
+            Err(Error::KeysetUnitConflict { .. })
+        ));
+
+        // The sat keyset is untouched and usd can use its own path
+        let keyset = mint.localstore.get_keyset(&sat_id).await.unwrap().unwrap();
+        assert_eq!(keyset.unit, CurrencyUnit::Sat);
+        assert_eq!(
+            mint.keyset(&sat_id).await.unwrap().unwrap().unit,
+            CurrencyUnit::Sat
+        );
+        mint.rotate_keyset(
+            CurrencyUnit::Usd,
+            &DerivationPath::from_str("m/0'/2'/0'").unwrap(),
+            8,
+        )
+        .await
+        .unwrap();
+        let usd_id = active_keyset_id(&mint, &CurrencyUnit::Usd).await;
+        assert_ne!(usd_id, sat_id);
+    }
+
+    #[tokio::test]
+    async fn test_inactive_keyset_error_response() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let old_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+        mint.rotate_keyset(
+            CurrencyUnit::Sat,
+            &DerivationPath::from_str("m/0'/0'/1'").unwrap(),
+            32,
+        )
+        .await
+        .unwrap();
+        let new_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+
+        // Outputs built from keys cached before the rotation
+        let inputs = mint_proofs(&mint, new_id, Amount::from(1)).await;
+        let err = mint
+            .process_swap_request(SwapRequest::new(
+                inputs,
+                vec![blinded_message(Amount::from(1), old_id)],
+            ))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InactiveKeyset { id, active_id: Some(active_id) }
+                if id == old_id && active_id == new_id
+        ));
+
+        let json = serde_json::to_value(ErrorResponse::from(&err)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "code": 12002,
+                "detail": format!("Inactive Keyset `{old_id}`"),
+                "active_keyset_id": new_id.to_string(),
+            })
+        );
+        let response: ErrorResponse = serde_json::from_value(json).unwrap();
+        assert_eq!(response.code, ErrorCode::KeysetInactive);
+        assert_eq!(response.active_keyset_id, Some(new_id));
+    }
+
+    #[tokio::test]
+    async fn test_keysets_after_rotation() {
+        let mut mint = create_mint(HashSet::new()).await;
+
+        let old_id = mint
+            .localstore
+            .get_active_keyset_id(&CurrencyUnit::Sat)
+            .await
+            .unwrap()
+            .unwrap();
+
+        mint.rotate_keyset(
+            CurrencyUnit::Sat,
+            &DerivationPath::from_str("m/0'/0'/1'").unwrap(),
+            32,
+        )
+        .await
+        .unwrap();
+
+        let new_id = mint
+            .localstore
+            .get_active_keyset_id(&CurrencyUnit::Sat)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_ne!(old_id, new_id);
+
+        let keysets = mint.keysets().await.unwrap().keysets;
+        assert_eq!(keysets.len(), 2);
+
+        let old = keysets.iter().find(|k| k.id == old_id).unwrap();
+        assert!(!old.active);
+        assert_eq!(old.unit, CurrencyUnit::Sat);
+
+        let new = keysets.iter().find(|k| k.id == new_id).unwrap();
+        assert!(new.active);
+        assert_eq!(new.unit, CurrencyUnit::Sat);
+    }
+
+    #[tokio::test]
+    async fn test_verify_inputs_reports_lowest_index_error() {
+        let mint = create_mint(HashSet::from([keyset_info(
+            CurrencyUnit::Sat,
+            "m/0'/0'/0'",
+            true,
+        )]))
+        .await;
+        let keyset_id = mint
+            .localstore
+            .get_active_keyset_id(&CurrencyUnit::Sat)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let proofs = mint_proofs(&mint, keyset_id, Amount::from(255)).await;
+        assert_eq!(proofs.len(), 8);
+        assert!(mint.verify_inputs(&proofs).await.is_ok());
+
+        // Signature does not match the secret
+        let mut bad_signature = proofs[0].clone();
+        bad_signature.c = proofs[1].c;
+
+        // Locked to a key without a witness
+        let mut missing_witness = proofs[0].clone();
+        missing_witness.secret = crate::nuts::nut10::Secret::new(
+            Kind::P2PK,
+            SecretKey::generate().public_key().to_hex(),
+            vec![],
+        )
+        .try_into()
+        .unwrap();
+
+        for _ in 0..20 {
+            let mut invalid = proofs.clone();
+            invalid[2] = bad_signature.clone();
+            invalid[5] = missing_witness.clone();
+            assert!(matches!(
+                mint.verify_inputs(&invalid).await,
+                Err(Error::Cashu(crate::error::Error::TokenNotVerifed))
+            ));
+
+            invalid.swap(2, 5);
+            assert!(matches!(
+                mint.verify_inputs(&invalid).await,
+                Err(Error::NUT11(_))
+            ));
+        }
+
+        // Spent proofs are caught before any signature is checked
+        mint.localstore
+            .add_spent_proof(proofs[7].clone())
+            .await
+            .unwrap();
+        let mut invalid = proofs.clone();
+        invalid[0] = bad_signature;
+        assert!(matches!(
+            mint.verify_inputs(&invalid).await,
+            Err(Error::TokenSpent)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_verify_proofs_reports_each_proof() {
+        let mint = create_mint(HashSet::from([keyset_info(
+            CurrencyUnit::Sat,
+            "m/0'/0'/0'",
+            true,
+        )]))
+        .await;
+        let keyset_id = mint
+            .localstore
+            .get_active_keyset_id(&CurrencyUnit::Sat)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let proofs = mint_proofs(&mint, keyset_id, Amount::from(15)).await;
+        mint.localstore
+            .add_spent_proof(proofs[1].clone())
+            .await
+            .unwrap();
+
+        let mut forged = proofs[2].clone();
+        forged.c = proofs[3].c;
+
+        let mut unknown_keyset = proofs[3].clone();
+        unknown_keyset.keyset_id = Id::from_str("00ffffffffffffff").unwrap();
+
+        let batch = vec![
+            proofs[0].clone(),
+            proofs[1].clone(),
+            forged,
+            unknown_keyset,
+            proofs[3].clone(),
+        ];
+        let results = mint.verify_proofs(&batch).await;
+
+        assert_eq!(results.len(), batch.len());
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(Error::TokenSpent)));
+        assert!(matches!(
+            results[2],
+            Err(Error::Cashu(crate::error::Error::TokenNotVerifed))
+        ));
+        assert!(matches!(results[3], Err(Error::UnknownKeySet)));
+        assert!(results[4].is_ok());
+
+        // Nothing was marked as spent
+        assert!(mint.verify_proofs(&batch).await[0].is_ok());
+
+        let token = Token::new(
+            crate::url::UncheckedUrl::new("https://mint.example"),
+            vec![proofs[0].clone(), proofs[1].clone()],
+            None,
+            None,
+        )
+        .unwrap();
+        let results = mint.verify_token(&token).await;
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(Error::TokenSpent)));
+    }
+
+    #[tokio::test]
+    async fn test_dleq_survives_unblinding_and_tokens() {
+        use crate::nuts::{ProofDleq, TokenV4};
+
+        let mint = create_mint(HashSet::new()).await;
+        let keyset_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+        let keys: Keys = mint
+            .localstore
+            .get_keyset(&keyset_id)
+            .await
+            .unwrap()
+            .unwrap()
+            .keys
+            .into();
+
+        let pre_mint = PreMintSecrets::random(keyset_id, Amount::from(13)).unwrap();
+        let mut signatures = Vec::new();
+        for blinded_message in pre_mint.blinded_messages() {
+            let signature = mint.blind_sign(&blinded_message).await.unwrap();
+            let mint_key = keys.amount_key(signature.amount).unwrap();
+            signature
+                .verify_dleq(mint_key, blinded_message.blinded_secret)
+                .unwrap();
+
+            // `{e, s}` as hex strings on the wire
+            let dleq = signature.dleq.clone().unwrap();
+            let json = serde_json::to_value(&signature).unwrap();
+            assert_eq!(
+                json["dleq"],
+                serde_json::json!({
+                    "e": dleq.e.to_secret_hex(),
+                    "s": dleq.s.to_secret_hex(),
+                })
+            );
+            let parsed: BlindSignature = serde_json::from_value(json).unwrap();
+            assert_eq!(parsed, signature);
+
+            signatures.push(parsed);
+        }
+
+        // Unblinding adds the blinding factor of each output
+        let proofs =
+            construct_proofs(signatures.clone(), pre_mint.rs(), pre_mint.secrets(), &keys).unwrap();
+        assert_eq!(proofs.len(), signatures.len());
+        for ((proof, signature), r) in proofs.iter().zip(&signatures).zip(pre_mint.rs()) {
+            let dleq = signature.dleq.clone().unwrap();
+            assert_eq!(proof.dleq, Some(ProofDleq::new(dleq.e, dleq.s, r)));
+            proof
+                .verify_dleq(keys.amount_key(proof.amount).unwrap())
+                .unwrap();
+
+            let json = serde_json::to_value(proof).unwrap();
+            let dleq = proof.dleq.as_ref().unwrap();
+            assert_eq!(
+                json["dleq"],
+                serde_json::json!({
+                    "e": dleq.e.to_secret_hex(),
+                    "s": dleq.s.to_secret_hex(),
+                    "r": dleq.r.to_secret_hex(),
+                })
+            );
+            assert_eq!(&serde_json::from_value::<Proof>(json).unwrap(), proof);
+        }
+
+        let verify_offline = |received: &Proofs| {
+            assert_eq!(received, &proofs);
+            for proof in received {
+                proof
+                    .verify_dleq(keys.amount_key(proof.amount).unwrap())
+                    .unwrap();
+            }
+        };
+
+        let token = Token::new(
+            crate::url::UncheckedUrl::new("https://mint.example"),
+            proofs.clone(),
+            None,
+            Some(CurrencyUnit::Sat),
+        )
+        .unwrap();
+        let v3 = Token::from_str(&token.to_string()).unwrap();
+        verify_offline(&v3.token[0].proofs);
+
+        let v4 = TokenV4::try_from(token).unwrap();
+        let v4 = Token::from(TokenV4::from_str(&v4.to_string()).unwrap());
+        verify_offline(&v4.token[0].proofs);
+    }
+
+    #[tokio::test]
+    async fn test_swap_rejects_proof_evicted_from_cache() {
+        let inner = MintMemoryDatabase::new(
+            MintInfo::default(),
+            HashMap::new(),
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            HashMap::new(),
+        )
+        .unwrap();
+        let localstore = MintTieredDatabase::new(Arc::new(inner), 1);
+
+        let mut mint = Mint::new(
+            Arc::new(localstore.clone()),
+            Mnemonic::from_str(MNEMONIC).unwrap(),
+            HashSet::from([keyset_info(CurrencyUnit::Sat, "m/0'/0'/0'", true)]),
+            Amount::from(1),
+            0.01,
+        )
+        .await
+        .unwrap();
+        let keyset_id = mint
+            .localstore
+            .get_active_keyset_id(&CurrencyUnit::Sat)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let old_proofs = mint_proofs(&mint, keyset_id, Amount::from(8)).await;
+        let swap = SwapRequest::new(
+            old_proofs.clone(),
+            vec![blinded_message(Amount::from(8), keyset_id)],
+        );
+        mint.process_swap_request(swap).await.unwrap();
+
+        // Newer spends push the old proof out of memory
+        let newer_proofs = mint_proofs(&mint, keyset_id, Amount::from(3)).await;
+        let swap = SwapRequest::new(
+            newer_proofs,
+            vec![
+                blinded_message(Amount::from(2), keyset_id),
+                blinded_message(Amount::from(1), keyset_id),
+            ],
+        );
+        mint.process_swap_request(swap).await.unwrap();
+        assert_eq!(localstore.cached_spent_proofs().await, 1);
+
+        let swap = SwapRequest::new(
+            old_proofs,
+            vec![blinded_message(Amount::from(8), keyset_id)],
+        );
+        assert!(matches!(
+            mint.process_swap_request(swap).await,
+            Err(Error::TokenSpent)
+        ));
+    }
+
+    #[cfg(feature = "wallet")]
+    #[tokio::test]
+    async fn test_builder_requests_pass_verification() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let keyset_id = mint
+            .localstore
+            .get_active_keyset_id(&CurrencyUnit::Sat)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let mut quote = mint
+            .new_mint_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                Amount::from(13),
+                unix_time() + 600,
+                None,
+            )
+            .await
+            .unwrap();
+        quote.paid = true;
+        mint.update_mint_quote(quote.clone()).await.unwrap();
+
+        let pre_mint = MintRequestBuilder::new(&quote.id, quote.amount)
+            .outputs_for(keyset_id)
+            .unwrap()
+            .build()
+            .unwrap();
+        let response = mint
+            .process_mint_request(pre_mint.mint_request)
+            .await
+            .unwrap();
+        assert_eq!(response.signatures.len(), pre_mint.pre_mint_secrets.len());
+
+        let pre_swap = SwapRequestBuilder::new()
+            .inputs(mint_proofs(&mint, keyset_id, Amount::from(10)).await)
+            .outputs_for(Amount::from(3), keyset_id)
+            .unwrap()
+            .outputs_for(Amount::from(7), keyset_id)
+            .unwrap()
+            .sort_outputs()
+            .build()
+            .unwrap();
+        let response = mint
+            .process_swap_request(pre_swap.swap_request)
+            .await
+            .unwrap();
+        assert_eq!(response.signatures.len(), pre_swap.pre_mint_secrets.len());
+
+        let invoice = Bolt11Invoice::from_str(INVOICE).unwrap();
+        let amount = Amount::from_msat_floor(invoice.amount_milli_satoshis().unwrap());
+        let melt_quote = mint
+            .new_melt_quote(
+                PaymentMethod::Bolt11,
+                invoice.to_string(),
+                CurrencyUnit::Sat,
+                amount,
+                unix_time() + 600,
+            )
+            .await
+            .unwrap();
+        let pre_melt =
+            MeltRequestBuilder::new(&melt_quote.id, melt_quote.amount, melt_quote.fee_reserve)
+                .inputs(
+                    mint_proofs(&mint, keyset_id, melt_quote.amount + melt_quote.fee_reserve).await,
+                )
+                .blank_outputs(keyset_id)
+                .unwrap()
+                .build()
+                .unwrap();
+        mint.verify_melt_request(&pre_melt.melt_request)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_tracing_spans_omit_secrets() {
+        let recorder = Recorder::default();
+        let _guard = tracing::subscriber::set_default(recorder.clone());
+
+        let mut mint = create_mint(HashSet::new()).await;
+        let keyset_id = mint
+            .localstore
+            .get_active_keyset_id(&CurrencyUnit::Sat)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let mut quote = mint
+            .new_mint_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                Amount::from(4),
+                unix_time() + 600,
+                None,
+            )
+            .await
+            .unwrap();
+        quote.paid = true;
+        mint.update_mint_quote(quote.clone()).await.unwrap();
+        mint.process_mint_request(nut04::MintBolt11Request {
+            quote: quote.id.clone(),
+            outputs: vec![blinded_message(Amount::from(4), keyset_id)],
+        })
+        .await
+        .unwrap();
+
+        let swap_inputs = mint_proofs(&mint, keyset_id, Amount::from(8)).await;
+        mint.process_swap_request(SwapRequest::new(
+            swap_inputs.clone(),
+            vec![blinded_message(Amount::from(8), keyset_id)],
+        ))
+        .await
+        .unwrap();
+
+        let invoice = Bolt11Invoice::from_str(INVOICE).unwrap();
+        let amount = Amount::from_msat_floor(invoice.amount_milli_satoshis().unwrap());
+        let melt_quote = mint
+            .new_melt_quote(
+                PaymentMethod::Bolt11,
+                invoice.to_string(),
+                CurrencyUnit::Sat,
+                amount,
+                unix_time() + 600,
+            )
+            .await
+            .unwrap();
+        let melt_inputs =
+            mint_proofs(&mint, keyset_id, melt_quote.amount + melt_quote.fee_reserve).await;
+        let preimage = "3a1d4d1f00c6d6d0a2bd0c3d78ef85a7a1b1d8ee7e2f2eaa0c9e5b7a4f466221";
+        mint.process_melt_request(
+            &MeltBolt11Request {
+                quote: melt_quote.id.clone(),
+                inputs: melt_inputs.clone(),
+                outputs: Some(vec![blinded_message(Amount::ZERO, keyset_id)]),
+            },
+            preimage,
+            amount,
+        )
+        .await
+        .unwrap();
+
+        let keyset = mint.get_keyset(&keyset_id).await.unwrap().unwrap();
+        mint.rotate_keyset(
+            CurrencyUnit::Sat,
+            &DerivationPath::from_str("m/0'/0'/1'").unwrap(),
+            32,
+        )
+        .await
+        .unwrap();
+
+        let state = recorder.state.lock().unwrap();
+        for span in [
+            ("process_mint_request", None),
+            ("process_swap_request", None),
+            ("process_melt_request", None),
+            ("verify_melt_request", Some("process_melt_request")),
+            ("rotate_keyset", None),
+        ] {
+            assert!(state.spans.contains(&span), "missing span {span:?}");
+        }
+
+        let output = &state.output;
+        assert!(output.contains(&format!("quote={}", quote.id)));
+        assert!(output.contains(&format!("quote={}", melt_quote.id)));
+        assert!(output.contains(&format!("keysets={keyset_id}")));
+        assert!(output.contains("elapsed_us="));
+
+        assert!(!output.contains(preimage));
+        for proof in swap_inputs.iter().chain(&melt_inputs) {
+            assert!(!output.contains(&proof.secret.to_string()));
+        }
+        for keypair in keyset.keys.values() {
+            assert!(!output.contains(&keypair.secret_key.to_secret_hex()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_melt_change_outputs_must_be_blank() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let keyset_id = mint
+            .localstore
+            .get_active_keyset_id(&CurrencyUnit::Sat)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let invoice = Bolt11Invoice::from_str(INVOICE).unwrap();
+        let amount = Amount::from_msat_floor(invoice.amount_milli_satoshis().unwrap());
+        let melt_quote = mint
+            .new_melt_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                amount,
+                unix_time() + 600,
+            )
+            .await
+            .unwrap();
+        let inputs = mint_proofs(&mint, keyset_id, amount + melt_quote.fee_reserve).await;
+
+        let mut outputs = PreMintSecrets::blank(keyset_id, melt_quote.fee_reserve)
+            .unwrap()
+            .blinded_messages();
+        outputs[0].amount = Amount::from(64);
+        let melt_request =
+            MeltBolt11Request::new(melt_quote.id.clone(), inputs.clone(), Some(outputs.clone()));
+
+        assert!(matches!(
+            mint.process_melt_request(&melt_request, "preimage", amount)
+                .await,
+            Err(Error::NonBlankChangeOutput)
+        ));
+        assert!(mint.verify_proofs(&inputs).await.iter().all(Result::is_ok));
+
+        outputs[0].amount = Amount::ZERO;
+        let melt_request = MeltBolt11Request::new(melt_quote.id.clone(), inputs, Some(outputs));
+        let response = mint
+            .process_melt_request(&melt_request, "preimage", amount)
+            .await
+            .unwrap();
+        let change: Amount = response.change.unwrap().iter().map(|s| s.amount).sum();
+        assert_eq!(change, melt_quote.fee_reserve);
+    }
+
+    /// Signed bolt11 invoice for `amount_msat`
+    fn invoice_msat(amount_msat: u64) -> String {
+        FakeLnBackend::new().invoice(Some(amount_msat)).to_string()
+    }
+
+    #[tokio::test]
+    async fn test_melt_quote_msat_rounding() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let keyset_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+        mint.set_fee_reserve_policy(FeeReserve {
+            min_fee_reserve: Amount::ZERO,
+            percent_fee_reserve: 0.01,
+        });
+
+        // 1000.5 sat
+        let request = invoice_msat(1_000_500);
+        let melt_quote = mint
+            .new_melt_quote_msat(
+                PaymentMethod::Bolt11,
+                request.clone(),
+                1_000_500,
+                unix_time() + 600,
+            )
+            .await
+            .unwrap();
+        assert_eq!(melt_quote.amount_msat, Some(1_000_500));
+        assert_eq!(melt_quote.amount, Amount::from(1001));
+        // 1% of 1000.5 sat is 10.005 sat
+        assert_eq!(melt_quote.fee_reserve, Amount::from(11));
+        assert_eq!(
+            mint.check_fees(&CheckFeesRequest {
+                pr: Bolt11Invoice::from_str(&request).unwrap()
+            })
+            .unwrap()
+            .fee,
+            Amount::from(11)
+        );
+        assert_eq!(
+            MeltQuoteBolt11Request::new(
+                Bolt11Invoice::from_str(&request).unwrap(),
+                CurrencyUnit::Sat
+            )
+            .invoice_amount()
+            .unwrap(),
+            Amount::from(1001)
+        );
+
+        // A whole number of sats is not rounded
+        let melt_quote_whole = mint
+            .new_melt_quote_msat(
+                PaymentMethod::Bolt11,
+                invoice_msat(1_000_000),
+                1_000_000,
+                unix_time() + 600,
+            )
+            .await
+            .unwrap();
+        assert_eq!(melt_quote_whole.amount, Amount::from(1000));
+        assert_eq!(melt_quote_whole.fee_reserve, Amount::from(10));
+
+        // 1000.5 sat invoice with a fee of 2.25 sat, change is 9.25 sat
+        let inputs = mint_proofs(&mint, keyset_id, Amount::from(1012)).await;
+        let melt_request = MeltBolt11Request::new(
+            melt_quote.id.clone(),
+            inputs,
+            Some(
+                PreMintSecrets::blank(keyset_id, melt_quote.fee_reserve)
+                    .unwrap()
+                    .blinded_messages(),
+            ),
+        );
+        let response = mint
+            .process_melt_request_msat(&melt_request, "preimage", 1_002_750)
+            .await
+            .unwrap();
+        let change: Amount = response.change.unwrap().iter().map(|s| s.amount).sum();
+        assert_eq!(change, Amount::from(9));
+        // The fee is reported as charged, the quarter sat included
+        assert_eq!(response.fee_paid, Some(Amount::from(2)));
+        assert_eq!(
+            Amount::from(1012),
+            melt_quote.amount + response.fee_paid.unwrap() + change
+        );
+
+        // The quarter sat left over is accounted for
+        let totals = mint.accounting().melts(&CurrencyUnit::Sat);
+        assert_eq!(totals.melts, 1);
+        assert_eq!(totals.amount_msat, 1_000_500);
+        assert_eq!(totals.fee_msat, 2_250);
+        assert_eq!(totals.charged, Amount::from(1003));
+        assert_eq!(totals.change, Amount::from(9));
+        assert_eq!(totals.remainder_msat, 250);
+        assert_eq!(totals.profit_msat(), 250);
+
+        // A fee over the reserve is charged as the reserve
+        let inputs = mint_proofs(&mint, keyset_id, Amount::from(1010)).await;
+        let melt_request =
+            MeltBolt11Request::new(melt_quote_whole.id.clone(), inputs.clone(), None);
+        let response = mint
+            .process_melt_request_msat(&melt_request, "preimage", 1_011_001)
+            .await
+            .unwrap();
+        assert_eq!(response.fee_paid, Some(Amount::from(10)));
+        for result in mint.verify_proofs(&inputs).await {
+            assert!(matches!(result, Err(Error::TokenSpent)));
+        }
+        let totals = mint.accounting().melts(&CurrencyUnit::Sat);
+        assert_eq!(totals.melts, 2);
+        assert_eq!(totals.fee_msat, 2_250 + 10_000);
+    }
+
+    #[tokio::test]
+    async fn test_backend_fee_estimate() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let keyset_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+        let backend = Arc::new(FakeLnBackend::new());
+        mint.set_ln_backend(Arc::clone(&backend) as Arc<dyn LnBackend>);
+
+        let amount = Amount::from(2500);
+        let mut quotes = Vec::new();
+        for estimate in [Some(Amount::from(2)), Some(Amount::from(500)), None] {
+            backend.set_fee_estimate(estimate);
+            quotes.push(
+                mint.new_melt_quote(
+                    PaymentMethod::Bolt11,
+                    INVOICE.to_string(),
+                    CurrencyUnit::Sat,
+                    amount,
+                    unix_time() + 600,
+                )
+                .await
+                .unwrap(),
+            );
+        }
+
+        // Low and high estimates get a margin, no estimate falls back to the policy
+        assert_eq!(quotes[0].fee_reserve, Amount::from(3));
+        assert_eq!(quotes[0].fee_estimator, FeeEstimator::Backend);
+        assert_eq!(quotes[1].fee_reserve, Amount::from(550));
+        assert_eq!(quotes[1].fee_estimator, FeeEstimator::Backend);
+        assert_eq!(quotes[2].fee_reserve, mint.fee_reserve(amount));
+        assert_eq!(quotes[2].fee_estimator, FeeEstimator::Policy);
+
+        let stored = mint.check_melt_quote(&quotes[0].id).await.unwrap();
+        assert_eq!(stored.fee_reserve, 3);
+
+        // A fee over the estimated reserve is charged as the reserve, the
+        // inputs over the quote and reserve are still returned
+        let inputs = mint_proofs(&mint, keyset_id, Amount::from(2600)).await;
+        let outputs = PreMintSecrets::blank(keyset_id, Amount::from(97))
+            .unwrap()
+            .blinded_messages();
+        let melt_request =
+            MeltBolt11Request::new(quotes[0].id.clone(), inputs.clone(), Some(outputs));
+        let response = mint
+            .process_melt_request(&melt_request, "preimage", Amount::from(2504))
+            .await
+            .unwrap();
+        assert!(response.paid);
+        assert_eq!(response.fee_paid, Some(Amount::from(3)));
+        let change: Amount = response.change.unwrap().iter().map(|s| s.amount).sum();
+        assert_eq!(change, Amount::from(97));
+        for result in mint.verify_proofs(&inputs).await {
+            assert!(matches!(result, Err(Error::TokenSpent)));
+        }
+    }
+
+    #[test]
+    fn test_estimate_margin() {
+        assert_eq!(with_estimate_margin(Amount::ZERO), Amount::from(1));
+        assert_eq!(with_estimate_margin(Amount::from(2)), Amount::from(3));
+        assert_eq!(with_estimate_margin(Amount::from(101)), Amount::from(112));
+        assert_eq!(
+            with_estimate_margin(Amount::from(u64::MAX)),
+            Amount::from(u64::MAX)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_quote_events_are_ordered() {
+        let mint = create_mint(HashSet::new()).await;
+        let keyset_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+        let mut events = mint.subscribe();
+
+        let mut quote_ids = Vec::new();
+        for _ in 0..20 {
+            let quote = mint
+                .new_mint_quote(
+                    PaymentMethod::Bolt11,
+                    INVOICE.to_string(),
+                    CurrencyUnit::Sat,
+                    Amount::from(8),
+                    unix_time() + 600,
+                    None,
+                )
+                .await
+                .unwrap();
+            quote_ids.push(quote.id);
+        }
+
+        let mut tasks = Vec::new();
+        for quote_id in &quote_ids {
+            // Issuing races the payment and retries until the quote is paid
+            let mut issuer = mint.clone();
+            let id = quote_id.clone();
+            tasks.push(tokio::spawn(async move {
+                loop {
+                    match issuer
+                        .process_mint_request(nut04::MintBolt11Request {
+                            quote: id.clone(),
+                            outputs: vec![blinded_message(Amount::from(8), keyset_id)],
+                        })
+                        .await
+                    {
+                        Ok(_) => break,
+                        Err(Error::UnpaidQuote) => tokio::task::yield_now().await,
+                        Err(err) => panic!("{err}"),
+                    }
+                }
+            }));
+
+            let payer = mint.clone();
+            let id = quote_id.clone();
+            tasks.push(tokio::spawn(async move {
+                assert!(payer.pay_mint_quote(&id).await.unwrap());
+            }));
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        let mut paid = HashSet::new();
+        let mut issued = HashSet::new();
+        for _ in 0..quote_ids.len() * 2 {
+            match events.try_recv().unwrap() {
+                MintEvent::QuotePaid {
+                    quote_id,
+                    amount,
+                    unit,
+                } => {
+                    assert_eq!((amount, unit), (Amount::from(8), CurrencyUnit::Sat));
+                    assert!(paid.insert(quote_id));
+                }
+                MintEvent::QuoteIssued { quote_id, .. } => {
+                    assert!(paid.contains(&quote_id), "issued before paid");
+                    assert!(issued.insert(quote_id));
+                }
+                event => panic!("unexpected {event:?}"),
+            }
+        }
+        assert!(events.try_recv().is_err());
+        assert_eq!(paid.len(), quote_ids.len());
+        assert_eq!(issued, paid);
+    }
+
+    #[tokio::test]
+    async fn test_amountless_invoice_melt_quote() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let keyset_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+        let amountless = Bolt11Invoice::from_str("lnbc1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdpl2pkx2ctnv5sxxmmwwd5kgetjypeh2ursdae8g6twvus8g6rfwvs8qun0dfjkxaq9qrsgq357wnc5r2ueh7ck6q93dj32dlqnls087fxdwk8qakdyafkq3yap9us6v52vjjsrvywa6rt52cm9r9zqt8r2t7mlcwspyetp5h2tztugp9lfyql").unwrap();
+
+        let request = MeltQuoteBolt11Request::new(amountless.clone(), CurrencyUnit::Sat);
+        assert!(matches!(
+            mint.new_melt_quote_bolt11(&request, unix_time() + 600)
+                .await,
+            Err(Error::Cashu(crate::error::Error::InvoiceAmountUndefined))
+        ));
+
+        let quote = mint
+            .new_melt_quote_bolt11(&request.with_amount(Amount::from(2_000)), unix_time() + 600)
+            .await
+            .unwrap();
+        assert_eq!(quote.amount, Amount::from(2_000));
+        assert_eq!(quote.amount_msat, Some(2_000_000));
+        assert_eq!(quote.fee_reserve, mint.fee_reserve(Amount::from(2_000)));
+
+        // Inputs are checked against the requested amount
+        let short = mint_proofs(&mint, keyset_id, Amount::from(2_000)).await;
+        assert!(matches!(
+            mint.verify_melt_request(&MeltBolt11Request::new(quote.id.clone(), short, None))
+                .await,
+            Err(Error::InsufficientMeltInputs { .. })
+        ));
+        let inputs = mint_proofs(&mint, keyset_id, quote.amount + quote.fee_reserve).await;
+        mint.verify_melt_request(&MeltBolt11Request::new(quote.id.clone(), inputs, None))
+            .await
+            .unwrap();
+
+        // Amountful invoice with a different amount
+        let request = MeltQuoteBolt11Request::new(
+            Bolt11Invoice::from_str(INVOICE).unwrap(),
+            CurrencyUnit::Sat,
+        );
+        assert!(matches!(
+            mint.new_melt_quote_bolt11(
+                &request.clone().with_amount(Amount::from(2_000)),
+                unix_time() + 600
+            )
+            .await,
+            Err(Error::Cashu(crate::error::Error::InvoiceAmountMismatch))
+        ));
+        let quote = mint
+            .new_melt_quote_bolt11(&request, unix_time() + 600)
+            .await
+            .unwrap();
+        assert_eq!(quote.amount, Amount::from(250_000));
+    }
+
+    #[tokio::test]
+    async fn test_check_state_by_ys() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let keyset_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+
+        let spent = mint_proofs(&mint, keyset_id, Amount::from(8)).await;
+        let outputs = vec![blinded_message(Amount::from(8), keyset_id)];
+        mint.process_swap_request(SwapRequest::new(spent.clone(), outputs))
+            .await
+            .unwrap();
+
+        let pending = mint_proofs(&mint, keyset_id, Amount::from(2)).await;
+        mint.localstore
+            .add_pending_proof(pending[0].clone())
+            .await
+            .unwrap();
+
+        let unknown = mint_proofs(&mint, keyset_id, Amount::from(1)).await;
+
+        let proofs: Proofs = vec![
+            unknown[0].clone(),
+            spent[0].clone(),
+            pending[0].clone(),
+            unknown[0].clone(),
+        ];
+        let ys = proofs.ys().unwrap();
+
+        let response = mint.check_state_by_ys(&ys).await.unwrap();
+        assert_eq!(response.states.iter().map(|s| s.y).collect::<Vec<_>>(), ys);
+        assert_eq!(
+            response.states.iter().map(|s| s.state).collect::<Vec<_>>(),
+            vec![State::Unspent, State::Spent, State::Pending, State::Unspent]
+        );
+
+        // Request and proof forms give the same answer
+        assert_eq!(
+            mint.check_state(&CheckStateRequest::new(ys)).await.unwrap(),
+            response
+        );
+        assert_eq!(mint.check_proofs_state(&proofs).await.unwrap(), response);
+        assert!(mint.check_state_by_ys(&[]).await.unwrap().states.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_check_state_returns_witness() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let keyset_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+        let keyset = mint
+            .localstore
+            .get_keyset(&keyset_id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Proof locked to `secret_key`
+        let secret_key = SecretKey::generate();
+        let secret: Secret =
+            crate::nuts::nut10::Secret::new(Kind::P2PK, secret_key.public_key().to_hex(), vec![])
+                .try_into()
+                .unwrap();
+        let (blinded_secret, r) = blind_message(&secret.to_bytes(), None).unwrap();
+        let signature = mint
+            .blind_sign(&BlindedMessage::new(
+                Amount::from(8),
+                keyset_id,
+                blinded_secret,
+            ))
+            .await
+            .unwrap();
+        let mut proofs =
+            construct_proofs(vec![signature], vec![r], vec![secret], &keyset.keys.into()).unwrap();
+        proofs[0].sign_p2pk(SigningKey::new(secret_key)).unwrap();
+        let submitted = proofs[0].witness.clone().unwrap();
+
+        let outputs = vec![blinded_message(Amount::from(8), keyset_id)];
+        mint.process_swap_request(SwapRequest::new(proofs.clone(), outputs))
+            .await
+            .unwrap();
+
+        let unlocked = mint_proofs(&mint, keyset_id, Amount::from(8)).await;
+        let outputs = vec![blinded_message(Amount::from(8), keyset_id)];
+        mint.process_swap_request(SwapRequest::new(unlocked.clone(), outputs))
+            .await
+            .unwrap();
+
+        let response = mint
+            .check_state_by_ys(&[proofs.ys().unwrap()[0], unlocked.ys().unwrap()[0]])
+            .await
+            .unwrap();
+        assert_eq!(response.states[0].state, State::Spent);
+        let witness = Witness::from_str(response.states[0].witness.as_ref().unwrap()).unwrap();
+        assert_eq!(witness, submitted);
+        assert_eq!(witness.signatures(), submitted.signatures());
+        assert_eq!(response.states[1].state, State::Spent);
+        assert_eq!(response.states[1].witness, None);
+    }
+
+    #[test]
+    fn test_htlc_secret_is_refused() {
+        // HTLC locks are not verified, so they must not pass as plain secrets
+        let secret: Secret = crate::nuts::nut10::Secret::new(
+            Kind::HTLC,
+            SecretKey::generate().public_key().to_hex(),
+            vec![],
+        )
+        .try_into()
+        .unwrap();
+        let proof = Proof::new(
+            Amount::from(8),
+            Id::from_str("009a1f293253e41e").unwrap(),
+            secret,
+            SecretKey::generate().public_key(),
+        );
+
+        assert!(matches!(
+            verify_conditions(&proof, unix_time()),
+            Err(Error::UnknownSecretKind)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_swap_rejects_restyled_duplicate() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let keyset_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+        let proofs = mint_proofs(&mint, keyset_id, Amount::from(4)).await;
+
+        let mut duplicate = proofs[0].clone();
+        duplicate.dleq = Some(ProofDleq::new(
+            SecretKey::generate(),
+            SecretKey::generate(),
+            SecretKey::generate(),
+        ));
+        assert_ne!(duplicate, proofs[0]);
+
+        let swap_request = SwapRequest::new(
+            vec![proofs[0].clone(), duplicate],
+            vec![blinded_message(Amount::from(8), keyset_id)],
+        );
+        assert!(matches!(
+            mint.process_swap_request(swap_request).await,
+            Err(Error::DuplicateProofs)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_swap_response_is_cached() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let keyset_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+        let proofs = mint_proofs(&mint, keyset_id, Amount::from(4)).await;
+
+        let swap_request = SwapRequest::new(
+            proofs.clone(),
+            vec![blinded_message(Amount::from(4), keyset_id)],
+        );
+        let response = mint
+            .process_swap_request(swap_request.clone())
+            .await
+            .unwrap();
+
+        // Resubmitted as is the swap is answered again, signing nothing new
+        let localstore = Arc::clone(&mint.localstore);
+        let signed = || async {
+            localstore
+                .get_blinded_signatures_by_keyset(&keyset_id)
+                .await
+                .unwrap()
+                .len()
+        };
+        let before = signed().await;
+        assert_eq!(
+            mint.process_swap_request(swap_request).await.unwrap(),
+            response
+        );
+        assert_eq!(signed().await, before);
+
+        let swap_request =
+            SwapRequest::new(proofs, vec![blinded_message(Amount::from(4), keyset_id)]);
+        assert!(matches!(
+            mint.process_swap_request(swap_request).await,
+            Err(Error::TokenSpent)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_swap_responses_are_pruned() {
+        use crate::test_utils::clock::ManualClock;
+
+        let mut mint = create_mint(HashSet::new()).await;
+        let clock = Arc::new(ManualClock::new(1_000));
+        mint.set_clock(Arc::clone(&clock) as Arc<dyn Clock>);
+        mint.set_swap_response_ttl(60);
+        let keyset_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+        let proofs = mint_proofs(&mint, keyset_id, Amount::from(4)).await;
+
+        let swap_request =
+            SwapRequest::new(proofs, vec![blinded_message(Amount::from(4), keyset_id)]);
+        let response = mint
+            .process_swap_request(swap_request.clone())
+            .await
+            .unwrap();
+
+        // Kept until its ttl is over
+        let report = mint.prune_expired_quotes(1_059).await.unwrap();
+        assert_eq!(report.removed_swap_responses, 0);
+        assert_eq!(
+            mint.process_swap_request(swap_request.clone())
+                .await
+                .unwrap(),
+            response
+        );
+
+        let report = mint.prune_expired_quotes(1_060).await.unwrap();
+        assert_eq!(report.removed_swap_responses, 1);
+        assert!(mint
+            .localstore
+            .get_swap_response(&swap_request_hash(&swap_request).unwrap())
+            .await
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            mint.process_swap_request(swap_request).await,
+            Err(Error::BlindedMessageAlreadySigned)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_failed_melt_proofs_can_be_swapped() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let keyset_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+
+        let invoice = Bolt11Invoice::from_str(INVOICE).unwrap();
+        let amount = Amount::from_msat_floor(invoice.amount_milli_satoshis().unwrap());
+        let melt_quote = mint
+            .new_melt_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                amount,
+                unix_time() + 600,
+            )
+            .await
+            .unwrap();
+        let total = amount + melt_quote.fee_reserve;
+        let inputs = mint_proofs(&mint, keyset_id, total).await;
+        let melt_request = MeltBolt11Request::new(melt_quote.id.clone(), inputs.clone(), None);
+
+        mint.reserve_melt_request(&melt_request).await.unwrap();
+        assert!(matches!(
+            mint.reserve_melt_request(&melt_request).await,
+            Err(Error::TokenPending)
+        ));
+        let swap_request = SwapRequest::new(
+            inputs.clone(),
+            PreMintSecrets::random(keyset_id, total)
+                .unwrap()
+                .blinded_messages(),
+        );
+        assert!(matches!(
+            mint.process_swap_request(swap_request.clone()).await,
+            Err(Error::TokenPending)
+        ));
+
+        // Lightning payment failed
+        let response = mint.abort_melt(&melt_quote.id).await.unwrap();
+        assert_eq!(response, MeltBolt11Response::unpaid());
+        assert!(!response.paid);
+        assert!(response.payment_preimage.is_none());
+        assert!(response.change.is_none());
+
+        let quote = mint
+            .localstore
+            .get_melt_quote(&melt_quote.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!quote.paid);
+        assert!(!quote.pending);
+        assert!(quote.reserved_inputs.is_empty());
+
+        mint.process_swap_request(swap_request).await.unwrap();
+
+        // Retrying the quote with fresh inputs
+        let inputs = mint_proofs(&mint, keyset_id, total).await;
+        let melt_request = MeltBolt11Request::new(melt_quote.id.clone(), inputs, None);
+        mint.reserve_melt_request(&melt_request).await.unwrap();
+        assert!(matches!(
+            mint.process_melt_request(&melt_request, "", amount).await,
+            Err(Error::MissingPreimage)
+        ));
+        let mut events = mint.subscribe();
+        let response = mint
+            .process_melt_request(&melt_request, "preimage", amount)
+            .await
+            .unwrap();
+        assert!(response.paid);
+        assert_eq!(response.payment_preimage.as_deref(), Some("preimage"));
+        assert_eq!(
+            events.try_recv().unwrap(),
+            MintEvent::MeltPaid {
+                quote_id: melt_quote.id.clone(),
+                amount,
+                unit: CurrencyUnit::Sat,
+            }
+        );
+        assert!(matches!(
+            mint.abort_melt(&melt_quote.id).await,
+            Err(Error::PaidQuote)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_recover_pending_melts() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let keyset_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+
+        let amount = Amount::from(2500);
+        let melt_quote = mint
+            .new_melt_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                amount,
+                unix_time() + 600,
+            )
+            .await
+            .unwrap();
+        let inputs = mint_proofs(&mint, keyset_id, amount + melt_quote.fee_reserve).await;
+        let outputs = (0..12)
+            .map(|_| blinded_message(Amount::ZERO, keyset_id))
+            .collect();
+        let melt_request =
+            MeltBolt11Request::new(melt_quote.id.clone(), inputs.clone(), Some(outputs));
+        mint.reserve_melt_request(&melt_request).await.unwrap();
+
+        // Without a backend nothing can be looked up
+        assert!(mint.recover_pending_melts().await.unwrap().is_empty());
+
+        let backend = Arc::new(FakeLnBackend::new());
+        mint.set_ln_backend(Arc::clone(&backend) as Arc<dyn LnBackend>);
+        let payment_hash = *Bolt11Invoice::from_str(INVOICE).unwrap().payment_hash();
+
+        // Still pending, then the node is offline
+        backend.set_payment_status(payment_hash, PaymentStatus::Pending);
+        assert!(mint.recover_pending_melts().await.unwrap().is_empty());
+        assert_eq!(mint.pending_melts().await.unwrap().len(), 1);
+        backend.set_offline(true);
+        assert!(mint.recover_pending_melts().await.unwrap().is_empty());
+        assert_eq!(mint.pending_melts().await.unwrap().len(), 1);
+
+        // 1 msat of fee is charged as a whole sat
+        backend.set_offline(false);
+        backend.set_payment_status(
+            payment_hash,
+            PaymentStatus::Paid(lightning::Payment {
+                preimage: "preimage".to_string(),
+                total_spent_msat: amount.to_msat() + 1,
+            }),
+        );
+        let resolved = mint.recover_pending_melts().await.unwrap();
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].0, melt_quote.id);
+        assert!(resolved[0].1.paid);
+        let change: Amount = resolved[0]
+            .1
+            .change
+            .iter()
+            .flatten()
+            .map(|s| s.amount)
+            .sum();
+        assert_eq!(change, melt_quote.fee_reserve - Amount::from(1));
+        assert!(mint.pending_melts().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_sync_mint_quote() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let quote = mint
+            .new_mint_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                Amount::from(12),
+                unix_time() + 600,
+                None,
+            )
+            .await
+            .unwrap();
+
+        // Left for pay_mint_quote without a backend
+        assert!(!mint.sync_mint_quote(&quote.id).await.unwrap());
+
+        let backend = Arc::new(FakeLnBackend::new());
+        mint.set_ln_backend(Arc::clone(&backend) as Arc<dyn LnBackend>);
+        let payment_hash = *Bolt11Invoice::from_str(INVOICE).unwrap().payment_hash();
+        assert!(!mint.sync_mint_quote(&quote.id).await.unwrap());
+
+        backend.script_invoice(payment_hash, InvoiceScript::SettleAfter(1));
+        assert!(!mint.sync_mint_quote(&quote.id).await.unwrap());
+        assert!(mint.sync_mint_quote(&quote.id).await.unwrap());
+        assert!(mint.check_mint_quote(&quote.id).await.unwrap().paid);
+
+        assert!(matches!(
+            mint.sync_mint_quote("nope").await,
+            Err(Error::UnknownQuote)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_bolt12_quote_issues_incrementally() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let keyset_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+        let backend = Arc::new(FakeLnBackend::new());
+        mint.set_ln_backend(Arc::clone(&backend) as Arc<dyn LnBackend>);
+
+        let offer = backend
+            .create_offer(None, &CurrencyUnit::Sat, None)
+            .await
+            .unwrap();
+        let quote = mint
+            .new_mint_quote_bolt12(
+                offer.clone(),
+                CurrencyUnit::Sat,
+                None,
+                unix_time() + 600,
+                Some("tips".to_string()),
+            )
+            .await
+            .unwrap();
+        let mint_request = |amounts: &[u64]| nut04::MintBolt11Request {
+            quote: quote.id.clone(),
+            outputs: amounts
+                .iter()
+                .map(|amount| blinded_message(Amount::from(*amount), keyset_id))
+                .collect(),
+        };
+        let check = |mint: &Mint| {
+            let mint = mint.clone();
+            let quote_id = quote.id.clone();
+            async move {
+                let response = mint.check_mint_quote_bolt12(&quote_id).await.unwrap();
+                (response.amount_paid, response.amount_issued)
+            }
+        };
+
+        assert!(!mint.sync_mint_quote(&quote.id).await.unwrap());
+        assert!(matches!(
+            mint.process_mint_request(mint_request(&[1])).await,
+            Err(Error::UnpaidQuote)
+        ));
+
+        // First payment, issued in two parts
+        let mut events = mint.subscribe();
+        backend.pay_offer(&offer, Amount::from(7));
+        assert!(mint.sync_mint_quote(&quote.id).await.unwrap());
+        assert_eq!(
+            events.try_recv().unwrap(),
+            MintEvent::QuotePaid {
+                quote_id: quote.id.clone(),
+                amount: Amount::from(7),
+                unit: CurrencyUnit::Sat,
+            }
+        );
+        // Reported again, nothing new is credited
+        assert!(mint.sync_mint_quote(&quote.id).await.unwrap());
+        assert!(events.try_recv().is_err());
+        assert_eq!(check(&mint).await, (Amount::from(7), Amount::ZERO));
+
+        assert!(matches!(
+            mint.process_mint_request(mint_request(&[8])).await,
+            Err(Error::Amount)
+        ));
+        assert!(matches!(
+            mint.process_mint_request(mint_request(&[])).await,
+            Err(Error::Amount)
+        ));
+        let response = mint.process_mint_request(mint_request(&[4])).await.unwrap();
+        assert_eq!(response.signatures.len(), 1);
+        assert_eq!(check(&mint).await, (Amount::from(7), Amount::from(4)));
+        mint.process_mint_request(mint_request(&[2, 1]))
+            .await
+            .unwrap();
+        assert!(matches!(
+            mint.process_mint_request(mint_request(&[1])).await,
+            Err(Error::IssuedQuote)
+        ));
+        assert!(!mint.sync_mint_quote(&quote.id).await.unwrap());
+
+        // Second payment to the same offer
+        backend.pay_offer(&offer, Amount::from(5));
+        assert!(mint.sync_mint_quote(&quote.id).await.unwrap());
+        assert_eq!(check(&mint).await, (Amount::from(12), Amount::from(7)));
+        assert!(matches!(
+            mint.process_mint_request(mint_request(&[4, 2])).await,
+            Err(Error::Amount)
+        ));
+        mint.process_mint_request(mint_request(&[4, 1]))
+            .await
+            .unwrap();
+        assert_eq!(check(&mint).await, (Amount::from(12), Amount::from(12)));
+
+        // Paid quotes with nothing left to issue are kept for later payments
+        let report = mint.prune_expired_quotes(u64::MAX).await.unwrap();
+        assert_eq!(report.removed_mint_quotes, 0);
+
+        // Bolt12 payment reports do not apply to bolt11 quotes and the reverse
+        assert!(matches!(
+            mint.pay_mint_quote(&quote.id).await,
+            Err(Error::UnsupportedMethod(PaymentMethod::Bolt12))
+        ));
+        let bolt11 = mint
+            .new_mint_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                Amount::from(12),
+                unix_time() + 600,
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(matches!(
+            mint.record_offer_payment(&bolt11.id, Amount::from(12))
+                .await,
+            Err(Error::UnsupportedMethod(PaymentMethod::Bolt11))
+        ));
+        assert!(matches!(
+            mint.check_mint_quote_bolt12(&bolt11.id).await,
+            Err(Error::UnknownQuote)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_quote_lookups() {
+        use bitcoin::hashes::Hash;
+
+        let mint = create_mint(HashSet::new()).await;
+        let mint_quote = mint
+            .new_mint_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                Amount::from(2500),
+                unix_time() + 600,
+                None,
+            )
+            .await
+            .unwrap();
+        let melt_quote = mint
+            .new_melt_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                Amount::from(2500),
+                unix_time() + 600,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            mint.mint_quote(&mint_quote.id).await.unwrap(),
+            Some(mint_quote.clone())
+        );
+        assert_eq!(
+            mint.melt_quote(&melt_quote.id).await.unwrap(),
+            Some(melt_quote.clone())
+        );
+        // Quote ids are per kind
+        assert!(mint.mint_quote(&melt_quote.id).await.unwrap().is_none());
+        assert!(mint.melt_quote("nope").await.unwrap().is_none());
+
+        let payment_hash = *Bolt11Invoice::from_str(INVOICE).unwrap().payment_hash();
+        assert_eq!(
+            mint.quote_by_payment_hash(&payment_hash).await.unwrap(),
+            Some(mint_quote.clone())
+        );
+        assert!(mint
+            .quote_by_payment_hash(&crate::Sha256::hash(b"unknown"))
+            .await
+            .unwrap()
+            .is_none());
+
+        // Settling the quote keeps it found by its invoice
+        mint.pay_mint_quote(&mint_quote.id).await.unwrap();
+        assert!(
+            mint.quote_by_payment_hash(&payment_hash)
+                .await
+                .unwrap()
+                .unwrap()
+                .paid
+        );
+
+        mint.remove_mint_quote(&mint_quote.id).await.unwrap();
+        assert!(mint
+            .quote_by_payment_hash(&payment_hash)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_mark_paid_by_payment_hash() {
+        use bitcoin::hashes::Hash;
+
+        let mint = create_mint(HashSet::new()).await;
+        let mint_quote = mint
+            .new_mint_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                Amount::from(2500),
+                unix_time() + 600,
+                None,
+            )
+            .await
+            .unwrap();
+        let payment_hash = *Bolt11Invoice::from_str(INVOICE).unwrap().payment_hash();
+        let mut events = mint.subscribe();
+
+        assert_eq!(
+            mint.mark_paid_by_payment_hash(&payment_hash).await.unwrap(),
+            mint_quote.id
+        );
+        assert!(mint.check_mint_quote(&mint_quote.id).await.unwrap().paid);
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            MintEvent::QuotePaid { quote_id, .. } if quote_id == mint_quote.id
+        ));
+
+        // The node notifying the settlement again changes nothing
+        assert_eq!(
+            mint.mark_paid_by_payment_hash(&payment_hash).await.unwrap(),
+            mint_quote.id
+        );
+        assert!(mint.check_mint_quote(&mint_quote.id).await.unwrap().paid);
+        assert!(events.try_recv().is_err());
+
+        let unknown = crate::Sha256::hash(b"unknown");
+        assert!(matches!(
+            mint.mark_paid_by_payment_hash(&unknown).await,
+            Err(Error::UnknownPaymentHash(hash)) if hash == unknown
+        ));
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_new_quote_ids_do_not_collide() {
+        let mint = create_mint(HashSet::new()).await;
+        let mint_quote = mint
+            .new_mint_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                Amount::from(2500),
+                0,
+                None,
+            )
+            .await
+            .unwrap();
+        let melt_quote = mint
+            .new_melt_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                Amount::from(2500),
+                0,
+            )
+            .await
+            .unwrap();
+
+        // A generated id that is taken is replaced instead of overwriting
+        let mut paid = mint_quote.clone();
+        paid.paid = true;
+        let stored = mint.add_new_mint_quote(paid).await.unwrap();
+        assert_ne!(stored.id, mint_quote.id);
+        assert_eq!(
+            mint.mint_quote(&mint_quote.id).await.unwrap(),
+            Some(mint_quote)
+        );
+        assert!(mint.mint_quote(&stored.id).await.unwrap().unwrap().paid);
+
+        let stored = mint.add_new_melt_quote(melt_quote.clone()).await.unwrap();
+        assert_ne!(stored.id, melt_quote.id);
+        assert_eq!(mint.localstore.get_melt_quotes().await.unwrap().len(), 2);
+    }
+
+    /// Mint started on a copy of the database of `mint`
+    async fn restart(mint: &Mint, pending_proofs: Proofs) -> Mint {
+        let localstore = MintMemoryDatabase::new(
+            mint.localstore.get_mint_info().await.unwrap(),
+            mint.localstore.get_active_keysets().await.unwrap(),
+            mint.localstore.get_keysets().await.unwrap(),
+            mint.localstore.get_mint_quotes().await.unwrap(),
+            mint.localstore.get_melt_quotes().await.unwrap(),
+            pending_proofs,
+            vec![],
+            HashMap::new(),
+        )
+        .unwrap();
+
+        Mint::new(
+            Arc::new(localstore),
+            Mnemonic::from_str(MNEMONIC).unwrap(),
+            HashSet::new(),
+            Amount::from(1),
+            0.01,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_check_melt_quote_states() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let keyset_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+
+        assert!(matches!(
+            mint.check_melt_quote("unknown").await,
+            Err(Error::UnknownQuote)
+        ));
+
+        let invoice = Bolt11Invoice::from_str(INVOICE).unwrap();
+        let amount = Amount::from_msat_floor(invoice.amount_milli_satoshis().unwrap());
+        let expiry = unix_time() + 600;
+        let melt_quote = mint
+            .new_melt_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                amount,
+                expiry,
+            )
+            .await
+            .unwrap();
+        let status = |state: &str| {
+            serde_json::json!({
+                "quote": melt_quote.id,
+                "amount": 250_000,
+                "fee_reserve": u64::from(melt_quote.fee_reserve),
+                "paid": state == "PAID",
+                "state": state,
+                "expiry": expiry,
+            })
+        };
+        let check = |mint: &Mint| {
+            let quote_id = melt_quote.id.clone();
+            let mint = mint.clone();
+            async move {
+                let status = mint.check_melt_quote(&quote_id).await.unwrap();
+                let json = serde_json::to_value(&status).unwrap();
+                assert_eq!(
+                    serde_json::from_value::<MeltQuoteBolt11Response>(json.clone()).unwrap(),
+                    status
+                );
+                (status.state(), json)
+            }
+        };
+
+        assert_eq!(
+            check(&mint).await,
+            (MeltQuoteState::Unpaid, status("UNPAID"))
+        );
+
+        let inputs = mint_proofs(&mint, keyset_id, amount + melt_quote.fee_reserve).await;
+        let melt_request = MeltBolt11Request::new(melt_quote.id.clone(), inputs, None);
+        mint.reserve_melt_request(&melt_request).await.unwrap();
+        assert_eq!(
+            check(&mint).await,
+            (MeltQuoteState::Pending, status("PENDING"))
+        );
+
+        mint.resolve_pending_melt(
+            &melt_quote.id,
+            MeltOutcome::Settled {
+                preimage: "preimage".to_string(),
+                fee: Amount::ZERO,
+            },
+        )
+        .await
+        .unwrap();
+        let mut paid = status("PAID");
+        paid["payment_preimage"] = "preimage".into();
+        assert_eq!(check(&mint).await, (MeltQuoteState::Paid, paid));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_pending_melt_after_restart() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let keyset_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+
+        let invoice = Bolt11Invoice::from_str(INVOICE).unwrap();
+        let amount = Amount::from_msat_floor(invoice.amount_milli_satoshis().unwrap());
+        let melt_quote = mint
+            .new_melt_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                amount,
+                unix_time() + 600,
+            )
+            .await
+            .unwrap();
+        let total = amount + melt_quote.fee_reserve;
+        let inputs = mint_proofs(&mint, keyset_id, total).await;
+        let outputs = (0..12)
+            .map(|_| blinded_message(Amount::ZERO, keyset_id))
+            .collect();
+        let melt_request =
+            MeltBolt11Request::new(melt_quote.id.clone(), inputs.clone(), Some(outputs));
+        mint.reserve_melt_request(&melt_request).await.unwrap();
+
+        // Payment settled with a fee of 1 sat while the mint was down
+        let mut restarted = restart(&mint, inputs.clone()).await;
+        let pending = restarted.pending_melts().await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].quote.id, melt_quote.id);
+        assert_eq!(pending[0].payment_hash, Some(*invoice.payment_hash()));
+
+        let settled = MeltOutcome::Settled {
+            preimage: "preimage".to_string(),
+            fee: Amount::from(1),
+        };
+        let response = restarted
+            .resolve_pending_melt(&melt_quote.id, settled.clone())
+            .await
+            .unwrap();
+        assert!(response.paid);
+        assert_eq!(response.payment_preimage.as_deref(), Some("preimage"));
+        let change: Amount = response.change.unwrap().iter().map(|s| s.amount).sum();
+        assert_eq!(change, melt_quote.fee_reserve - Amount::from(1));
+        for input in &inputs {
+            assert!(restarted
+                .localstore
+                .get_spent_proof_by_secret(&input.secret)
+                .await
+                .unwrap()
+                .is_some());
+        }
+        assert!(restarted.pending_melts().await.unwrap().is_empty());
+
+        let response = restarted
+            .resolve_pending_melt(&melt_quote.id, settled)
+            .await
+            .unwrap();
+        assert!(response.paid);
+        assert!(matches!(
+            restarted
+                .resolve_pending_melt(&melt_quote.id, MeltOutcome::Failed)
+                .await,
+            Err(Error::PaidQuote)
+        ));
+
+        // Payment failed while the mint was down
+        let mut restarted = restart(&mint, inputs.clone()).await;
+        let response = restarted
+            .resolve_pending_melt(&melt_quote.id, MeltOutcome::Failed)
+            .await
+            .unwrap();
+        assert!(!response.paid);
+        assert!(restarted.pending_melts().await.unwrap().is_empty());
+        for input in &inputs {
+            assert!(restarted
+                .localstore
+                .get_pending_proof_by_secret(&input.secret)
+                .await
+                .unwrap()
+                .is_none());
+        }
+
+        let response = restarted
+            .resolve_pending_melt(&melt_quote.id, MeltOutcome::Failed)
+            .await
+            .unwrap();
+        assert!(!response.paid);
+        assert!(matches!(
+            restarted
+                .resolve_pending_melt(
+                    &melt_quote.id,
+                    MeltOutcome::Settled {
+                        preimage: "preimage".to_string(),
+                        fee: Amount::ZERO,
+                    },
+                )
+                .await,
+            Err(Error::MeltNotPending)
+        ));
+
+        // Inputs are spendable again
+        let swap_request = SwapRequest::new(
+            inputs,
+            PreMintSecrets::random(keyset_id, total)
+                .unwrap()
+                .blinded_messages(),
+        );
+        restarted.process_swap_request(swap_request).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resolve_pending_melt_over_fee_reserve() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let keyset_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+
+        let amount = Amount::from(2500);
+        let melt_quote = mint
+            .new_melt_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                amount,
+                unix_time() + 600,
+            )
+            .await
+            .unwrap();
+        let inputs = mint_proofs(&mint, keyset_id, amount + melt_quote.fee_reserve).await;
+        let outputs = (0..12)
+            .map(|_| blinded_message(Amount::ZERO, keyset_id))
+            .collect();
+        let melt_request =
+            MeltBolt11Request::new(melt_quote.id.clone(), inputs.clone(), Some(outputs));
+        mint.reserve_melt_request(&melt_request).await.unwrap();
+
+        // The payment went out with a fee over the reserve
+        let response = mint
+            .resolve_pending_melt(
+                &melt_quote.id,
+                MeltOutcome::Settled {
+                    preimage: "preimage".to_string(),
+                    fee: melt_quote.fee_reserve + Amount::from(5),
+                },
+            )
+            .await
+            .unwrap();
+        assert!(response.paid);
+        assert_eq!(response.fee_paid, Some(melt_quote.fee_reserve));
+        let change: Amount = response.change.unwrap().iter().map(|s| s.amount).sum();
+        assert_eq!(change, Amount::ZERO);
+
+        assert!(mint.pending_melts().await.unwrap().is_empty());
+        for result in mint.verify_proofs(&inputs).await {
+            assert!(matches!(result, Err(Error::TokenSpent)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_internal_melt_settles_mint_quote() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let keyset_id = mint
+            .localstore
+            .get_active_keyset_id(&CurrencyUnit::Sat)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let invoice = Bolt11Invoice::from_str(INVOICE).unwrap();
+        let amount = Amount::from_msat_floor(invoice.amount_milli_satoshis().unwrap());
+        let melt_quote = mint
+            .new_melt_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                amount,
+                unix_time() + 600,
+            )
+            .await
+            .unwrap();
+        let melt_request = MeltBolt11Request {
+            quote: melt_quote.id.clone(),
+            inputs: mint_proofs(&mint, keyset_id, amount + melt_quote.fee_reserve).await,
+            outputs: Some(
+                PreMintSecrets::blank(keyset_id, melt_quote.fee_reserve)
+                    .unwrap()
+                    .blinded_messages(),
+            ),
+        };
+
+        // Not an invoice of this mint, so it has to go over Lightning
+        assert!(mint
+            .process_internal_melt_request(&melt_request)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(mint
+            .verify_proofs(&melt_request.inputs)
+            .await
+            .iter()
+            .all(Result::is_ok));
+
+        let mint_quote = mint
+            .new_mint_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                amount,
+                unix_time() + 600,
+                None,
+            )
+            .await
+            .unwrap();
+
+        let response = mint
+            .process_internal_melt_request(&melt_request)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(response.paid);
+        assert!(response.payment_preimage.is_none());
+        // No Lightning fee, the whole reserve comes back
+        let change: Amount = response.change.unwrap().iter().map(|s| s.amount).sum();
+        assert_eq!(change, melt_quote.fee_reserve);
+
+        assert!(mint.check_melt_quote(&melt_quote.id).await.unwrap().paid);
+        assert!(mint.check_mint_quote(&mint_quote.id).await.unwrap().paid);
+
+        // The Lightning path can no longer pay the quote a second time
+        assert!(!mint.pay_mint_quote(&mint_quote.id).await.unwrap());
+        assert!(matches!(
+            mint.process_internal_melt_request(&melt_request).await,
+            Err(Error::TokenSpent)
+        ));
+
+        // Outputs have to add up to the quote amount
+        assert!(matches!(
+            mint.process_mint_request(nut04::MintBolt11Request {
+                quote: mint_quote.id.clone(),
+                outputs: vec![blinded_message(Amount::from(1), keyset_id)],
+            })
+            .await,
+            Err(Error::Amount)
+        ));
+        mint.process_mint_request(nut04::MintBolt11Request {
+            quote: mint_quote.id,
+            outputs: PreMintSecrets::random(keyset_id, amount)
+                .unwrap()
+                .blinded_messages(),
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_request_limits() {
+        let mut mint = create_mint(HashSet::from([keyset_info(
+            CurrencyUnit::Sat,
+            "m/0'/0'/0'",
+            true,
+        )]))
+        .await;
+        mint.set_request_limits(RequestLimits {
+            max_inputs: 2,
+            max_outputs: 2,
+            max_request_amount: Some(Amount::from(4)),
+            ..Default::default()
+        });
+        assert_eq!(mint.request_limits().max_inputs, 2);
+        let keyset_id = mint
+            .localstore
+            .get_active_keyset_id(&CurrencyUnit::Sat)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Exactly at every limit
+        let swap = SwapRequest::new(
+            mint_proofs(&mint, keyset_id, Amount::from(3)).await,
+            vec![
+                blinded_message(Amount::from(1), keyset_id),
+                blinded_message(Amount::from(2), keyset_id),
+            ],
+        );
+        mint.process_swap_request(swap).await.unwrap();
+
+        let swap = SwapRequest::new(
+            mint_proofs(&mint, keyset_id, Amount::from(4)).await,
+            vec![blinded_message(Amount::from(4), keyset_id)],
+        );
+        mint.process_swap_request(swap).await.unwrap();
+
+        // One over each limit
+        let swap = SwapRequest::new(
+            mint_proofs(&mint, keyset_id, Amount::from(7)).await,
+            vec![blinded_message(Amount::from(4), keyset_id)],
+        );
+        assert!(matches!(
+            mint.process_swap_request(swap).await,
+            Err(Error::RequestTooLarge {
+                limit: "max_inputs",
+                max: 2
+            })
+        ));
+
+        let swap = SwapRequest::new(
+            mint_proofs(&mint, keyset_id, Amount::from(3)).await,
+            vec![
+                blinded_message(Amount::from(1), keyset_id),
+                blinded_message(Amount::from(1), keyset_id),
+                blinded_message(Amount::from(1), keyset_id),
+            ],
+        );
+        assert!(matches!(
+            mint.process_swap_request(swap).await,
+            Err(Error::RequestTooLarge {
+                limit: "max_outputs",
+                max: 2
+            })
+        ));
+
+        let swap = SwapRequest::new(
+            mint_proofs(&mint, keyset_id, Amount::from(5)).await,
+            vec![blinded_message(Amount::from(4), keyset_id)],
+        );
+        assert!(matches!(
+            mint.process_swap_request(swap).await,
+            Err(Error::RequestTooLarge {
+                limit: "max_request_amount",
+                max: 4
+            })
+        ));
+
+        // Checked before the quote is looked up
+        let mint_request = nut04::MintBolt11Request {
+            quote: "unknown".to_string(),
+            outputs: vec![blinded_message(Amount::from(1), keyset_id); 3],
+        };
+        assert!(matches!(
+            mint.process_mint_request(mint_request).await,
+            Err(Error::RequestTooLarge {
+                limit: "max_outputs",
+                ..
+            })
+        ));
+
+        let melt_request = MeltBolt11Request {
+            quote: "unknown".to_string(),
+            inputs: mint_proofs(&mint, keyset_id, Amount::from(8)).await,
+            outputs: None,
+        };
+        assert!(matches!(
+            mint.verify_melt_request(&melt_request).await,
+            Err(Error::RequestTooLarge {
+                limit: "max_request_amount",
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_mint_info_caps_max_amount() {
+        let mut mint = create_mint(HashSet::new()).await;
+
+        let mut mint_info = MintInfo::default();
+        mint_info.nuts.nut04.methods = vec![nut04::MintMethodSettings {
+            method: PaymentMethod::Bolt11,
+            unit: CurrencyUnit::Sat,
+            min_amount: Amount::from(1),
+            max_amount: Amount::from(1_000),
+            description: true,
+            description_hash: false,
+        }];
+        mint.localstore.set_mint_info(&mint_info).await.unwrap();
+
+        mint_info.pubkey = Some(mint.identity_pubkey());
+        assert_eq!(mint.mint_info().await.unwrap(), mint_info);
+
+        mint.request_limits.max_request_amount = Some(Amount::from(100));
+        assert_eq!(
+            mint.mint_info().await.unwrap().nuts.nut04.methods[0].max_amount,
+            Amount::from(100)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_archive_spent_for_keyset() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let old_id = mint
+            .localstore
+            .get_active_keyset_id(&CurrencyUnit::Sat)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let spent = mint_proofs(&mint, old_id, Amount::from(5)).await;
+        mint.import_spent_proofs(spent.clone()).await.unwrap();
+        let unspent = mint_proofs(&mint, old_id, Amount::from(3)).await;
+
+        mint.rotate_keyset(
+            CurrencyUnit::Sat,
+            &DerivationPath::from_str("m/0'/0'/1'").unwrap(),
+            32,
+        )
+        .await
+        .unwrap();
+        let new_id = mint
+            .localstore
+            .get_active_keyset_id(&CurrencyUnit::Sat)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Keysets still accepted by verification cannot be archived
+        assert!(matches!(
+            mint.archive_spent_for_keyset(&old_id).await,
+            Err(Error::AcceptedKeyset)
+        ));
+        assert!(matches!(
+            mint.set_keyset_refused_from(&new_id, Some(0)).await,
+            Err(Error::ActiveKeyset)
+        ));
+        assert!(matches!(
+            mint.archive_spent_for_keyset(&new_id).await,
+            Err(Error::ActiveKeyset)
+        ));
+
+        mint.set_keyset_refused_from(&old_id, Some(unix_time()))
+            .await
+            .unwrap();
+        let archive = mint.archive_spent_for_keyset(&old_id).await.unwrap();
+
+        assert_eq!(archive.keyset_id, old_id);
+        assert_eq!(archive.proofs.len(), spent.len());
+        assert!(archive.verify().unwrap());
+        for proof in &spent {
+            let y = hash_to_curve(&proof.secret.to_bytes()).unwrap();
+            assert!(archive.contains(&y).unwrap());
+            assert!(mint
+                .localstore
+                .get_spent_proof_by_y(&y)
+                .await
+                .unwrap()
+                .is_none());
+        }
+
+        // Archived proofs are rejected because the keyset is refused
+        for result in mint.verify_proofs(&spent).await {
+            assert!(matches!(result, Err(Error::RefusedKeyset)));
+        }
+        let swap_request = SwapRequest::new(
+            spent.clone(),
+            vec![
+                blinded_message(Amount::from(4), new_id),
+                blinded_message(Amount::from(1), new_id),
+            ],
+        );
+        assert!(matches!(
+            mint.process_swap_request(swap_request).await,
+            Err(Error::RefusedKeyset)
+        ));
+        for result in mint.verify_proofs(&unspent).await {
+            assert!(matches!(result, Err(Error::RefusedKeyset)));
+        }
+
+        // The archive round trips and is checked before import
+        let json = serde_json::to_string(&archive).unwrap();
+        let restored: ArchivedSpend = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, archive);
+
+        let mut tampered = restored.clone();
+        tampered.proofs.pop();
+        assert!(matches!(
+            mint.import_archive(&tampered).await,
+            Err(Error::ArchiveCommitment)
+        ));
+
+        mint.set_keyset_refused_from(&old_id, None).await.unwrap();
+        mint.import_archive(&restored).await.unwrap();
+        for result in mint.verify_proofs(&spent).await {
+            assert!(matches!(result, Err(Error::TokenSpent)));
+        }
+        for result in mint.verify_proofs(&unspent).await {
+            assert!(result.is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_freeze_keyset() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let old_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+        let proofs = mint_proofs(&mint, old_id, Amount::from(3)).await;
+
+        // The only active keyset has to be rotated out first
+        assert!(matches!(
+            mint.freeze_keyset(&old_id, "leaked".to_string()).await,
+            Err(Error::ActiveKeyset)
+        ));
+
+        mint.rotate_keyset(
+            CurrencyUnit::Sat,
+            &DerivationPath::from_str("m/0'/0'/1'").unwrap(),
+            32,
+        )
+        .await
+        .unwrap();
+        let new_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+
+        mint.freeze_keyset(&old_id, "leaked".to_string())
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            mint.blind_sign(&blinded_message(Amount::from(1), old_id)).await,
+            Err(Error::KeySetFrozen(reason)) if reason == "leaked"
+        ));
+        for result in mint.verify_proofs(&proofs).await {
+            assert!(matches!(result, Err(Error::KeySetFrozen(ref reason)) if reason == "leaked"));
+        }
+        let swap_request = SwapRequest::new(
+            proofs.clone(),
+            vec![blinded_message(Amount::from(3), new_id)],
+        );
+        assert!(matches!(
+            mint.process_swap_request(swap_request).await,
+            Err(Error::KeySetFrozen(_))
+        ));
+
+        let frozen: HashMap<Id, bool> = mint
+            .keysets()
+            .await
+            .unwrap()
+            .keysets
+            .into_iter()
+            .map(|info| (info.id, info.frozen))
+            .collect();
+        assert!(frozen[&old_id]);
+        assert!(!frozen[&new_id]);
+
+        mint.unfreeze_keyset(&old_id).await.unwrap();
+
+        for result in mint.verify_proofs(&proofs).await {
+            assert!(result.is_ok());
+        }
+        assert!(matches!(
+            mint.blind_sign(&blinded_message(Amount::from(1), old_id))
+                .await,
+            Err(Error::InactiveKeyset { .. })
+        ));
+        assert!(mint
+            .keysets()
+            .await
+            .unwrap()
+            .keysets
+            .iter()
+            .all(|info| !info.frozen));
+    }
+
+    #[tokio::test]
+    async fn test_generate_audit_report() {
+        // Only signatures of mint, swap and melt requests are recorded
+        async fn mint_with_quote(mint: &mut Mint, keyset_id: Id, amount: Amount) -> Proofs {
+            let mut quote = mint
+                .new_mint_quote(
+                    PaymentMethod::Bolt11,
+                    INVOICE.to_string(),
+                    CurrencyUnit::Sat,
+                    amount,
+                    unix_time() + 600,
+                    None,
+                )
+                .await
+                .unwrap();
+            quote.paid = true;
+            mint.update_mint_quote(quote.clone()).await.unwrap();
+
+            let pre_mint = PreMintSecrets::random(keyset_id, amount).unwrap();
+            let response = mint
+                .process_mint_request(nut04::MintBolt11Request::new(
+                    quote.id,
+                    pre_mint.blinded_messages(),
+                ))
+                .await
+                .unwrap();
+            let keyset = mint
+                .localstore
+                .get_keyset(&keyset_id)
+                .await
+                .unwrap()
+                .unwrap();
+
+            construct_proofs(
+                response.signatures,
+                pre_mint.rs(),
+                pre_mint.secrets(),
+                &keyset.keys.into(),
+            )
+            .unwrap()
+        }
+
+        let mut mint = create_mint(HashSet::new()).await;
+        let old_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+        let old_proofs = mint_with_quote(&mut mint, old_id, Amount::from(13)).await;
+
+        mint.rotate_keyset(
+            CurrencyUnit::Sat,
+            &DerivationPath::from_str("m/0'/0'/1'").unwrap(),
+            32,
+        )
+        .await
+        .unwrap();
+        let new_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+
+        let swap_request = SwapRequest::new(
+            old_proofs,
+            vec![
+                blinded_message(Amount::from(8), new_id),
+                blinded_message(Amount::from(4), new_id),
+                blinded_message(Amount::from(1), new_id),
+            ],
+        );
+        mint.process_swap_request(swap_request).await.unwrap();
+
+        let redeemed = mint_with_quote(&mut mint, new_id, Amount::from(2)).await;
+        mint.import_spent_proofs(redeemed).await.unwrap();
+
+        let report = mint.generate_audit_report(1_700_000_000).await.unwrap();
+        assert_eq!(report.generated_at, 1_700_000_000);
+        assert_eq!(report.pubkey, Some(mint.identity_pubkey()));
+
+        let old = report.keysets.iter().find(|k| k.id == old_id).unwrap();
+        assert!(!old.active);
+        assert_eq!(old.issued, Amount::from(13));
+        assert_eq!(old.redeemed, Amount::from(13));
+        assert_eq!(old.outstanding, Amount::ZERO);
+        assert_eq!(old.spent_proofs, 3);
+
+        let new = report.keysets.iter().find(|k| k.id == new_id).unwrap();
+        assert!(new.active);
+        assert_eq!(new.issued, Amount::from(15));
+        assert_eq!(new.redeemed, Amount::from(2));
+        assert_eq!(new.outstanding, Amount::from(13));
+        assert_eq!(new.spent_proofs, 1);
+        assert_eq!(
+            Id::from(&new.keys),
+            new_id,
+            "report carries the keyset public keys"
+        );
+
+        assert_eq!(report.outstanding(&CurrencyUnit::Sat), Amount::from(13));
+
+        // Signed by the identity key, covering the canonical encoding and
+        // surviving serde
+        assert!(report.verify().unwrap());
+        assert!(mint.mint_info().await.unwrap().verify_signature(
+            &report.canonical_bytes().unwrap(),
+            &report.signature.as_ref().unwrap().parse().unwrap()
+        ));
+        assert!(matches!(
+            report.clone().sign(SecretKey::generate()),
+            Err(Error::AuditKeyMismatch)
+        ));
+
+        let json = serde_json::to_string(&report).unwrap();
+        let restored: AuditReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, report);
+        assert!(restored.verify().unwrap());
+
+        let mut tampered = restored;
+        tampered.keysets[0].outstanding += Amount::from(1);
+        assert!(!tampered.verify().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_identity_key() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let info = mint.mint_info().await.unwrap();
+        assert_eq!(info.pubkey, Some(mint.identity_pubkey()));
+
+        let msg = b"Signed by the mint, not by a keyset";
+        let signature = mint.sign_with_identity(msg);
+        assert!(info.verify_signature(msg, &signature));
+        assert!(!info.verify_signature(b"Another message", &signature));
+        assert!(!MintInfo::default().verify_signature(msg, &signature));
+
+        // Served by /v1/info after a round trip
+        let info: MintInfo = serde_json::from_str(&serde_json::to_string(&info).unwrap()).unwrap();
+        assert!(info.verify_signature(msg, &signature));
+
+        // Regenerated from the same mnemonic, apart from the keyset keys
+        let regenerated = create_mint(HashSet::new()).await;
+        assert_eq!(regenerated.identity_pubkey(), mint.identity_pubkey());
+        let keyset_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+        let keys = mint.keyset_pubkeys(&keyset_id).await.unwrap().keysets[0]
+            .keys
+            .clone();
+        assert!(!keys.iter().any(|(_, key)| *key == mint.identity_pubkey()));
+
+        let secret_key = SecretKey::generate();
+        mint.set_identity_key(secret_key.clone());
+        assert_eq!(mint.identity_pubkey(), secret_key.public_key());
+        let info = mint.mint_info().await.unwrap();
+        assert!(!info.verify_signature(msg, &signature));
+        assert!(info.verify_signature(msg, &mint.sign_with_identity(msg)));
+    }
+
+    #[tokio::test]
+    async fn test_spent_count_for_keyset() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let old_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+        let old_proofs = mint_proofs(&mint, old_id, Amount::from(7)).await;
+
+        mint.rotate_keyset(
+            CurrencyUnit::Sat,
+            &DerivationPath::from_str("m/0'/0'/1'").unwrap(),
+            32,
+        )
+        .await
+        .unwrap();
+        let new_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+        let new_proofs = mint_proofs(&mint, new_id, Amount::from(2)).await;
+
+        let swap_request = SwapRequest::new(
+            old_proofs.clone(),
+            vec![
+                blinded_message(Amount::from(4), new_id),
+                blinded_message(Amount::from(2), new_id),
+                blinded_message(Amount::from(1), new_id),
+            ],
+        );
+        mint.process_swap_request(swap_request).await.unwrap();
+        mint.import_spent_proofs(new_proofs.clone()).await.unwrap();
+
+        assert_eq!(
+            mint.spent_count_for_keyset(&old_id).await.unwrap(),
+            old_proofs.len() as u64
+        );
+        assert_eq!(
+            mint.spent_count_for_keyset(&new_id).await.unwrap(),
+            new_proofs.len() as u64
+        );
+
+        // A flat list of spent proofs is indexed by keyset on load
+        let spent: Proofs = old_proofs.iter().chain(&new_proofs).cloned().collect();
+        let localstore = MintMemoryDatabase::new(
+            MintInfo::default(),
+            HashMap::new(),
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            spent.clone(),
+            HashMap::new(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            localstore.get_spent_count_by_keyset(&old_id).await.unwrap(),
+            old_proofs.len() as u64
+        );
+        assert_eq!(
+            localstore
+                .get_spent_proofs_by_keyset(&new_id)
+                .await
+                .unwrap(),
+            new_proofs
+        );
+        for proof in &spent {
+            assert!(localstore
+                .get_spent_proof_by_secret(&proof.secret)
+                .await
+                .unwrap()
+                .is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_proofs_with_legacy_id() {
+        let current = MintKeySetInfo {
+            legacy_id: Some(Id::from_str("I2yN+iRYfkzT").unwrap()),
+            ..keyset_info(CurrencyUnit::Sat, "m/0'/0'/0'", true)
+        };
+        let mut mint = create_mint(HashSet::from([current])).await;
+        let id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+
+        let keys = mint.keyset(&id).await.unwrap().unwrap().keys.clone();
+        let derived = mint.register_legacy_id(&id).await.unwrap();
+        assert_eq!(derived, Id::legacy_from_keys(&keys));
+
+        let mut proofs = mint_proofs(&mint, id, Amount::from(24)).await;
+        for proof in proofs.iter_mut() {
+            proof.keyset_id = derived;
+        }
+        proofs[0].keyset_id = Id::from_str("I2yN+iRYfkzT").unwrap();
+
+        // Both legacy ids route to the keyset registered under the current id
+        assert!(mint
+            .verify_proofs(&proofs)
+            .await
+            .iter()
+            .all(|result| result.is_ok()));
+        assert_eq!(mint.keyset(&derived).await.unwrap().unwrap().id, id);
+
+        // Unknown legacy ids are unknown keysets
+        let mut unknown = proofs[1].clone();
+        unknown.keyset_id = Id::from_str("yjzQhxghPdrr").unwrap();
+        assert!(matches!(
+            mint.verify_proof(&unknown).await,
+            Err(Error::UnknownKeySet)
+        ));
+
+        // Legacy proofs can be swapped for current ones
+        let outputs = (0..3)
+            .map(|_| blinded_message(Amount::from(8), id))
+            .collect();
+        let response = mint
+            .process_swap_request(SwapRequest::new(proofs, outputs))
+            .await
+            .unwrap();
+        assert!(response
+            .signatures
+            .iter()
+            .all(|signature| signature.keyset_id == id));
+
+        // Only listed under the legacy ids when asked for
+        assert_eq!(mint.keysets().await.unwrap().keysets.len(), 1);
+        let listed: HashSet<String> = mint
+            .keysets_with_legacy_ids()
+            .await
+            .unwrap()
+            .keysets
+            .iter()
+            .map(|info| info.id.to_string())
+            .collect();
+        assert_eq!(
+            listed,
+            HashSet::from([
+                id.to_string(),
+                derived.to_string(),
+                "I2yN+iRYfkzT".to_string()
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_keysets_info() {
+        let retired = MintKeySetInfo {
+            valid_from: 100,
+            valid_to: Some(200),
+            ..keyset_info(CurrencyUnit::Sat, "m/0'/0'/0'", false)
+        };
+        let current = MintKeySetInfo {
+            valid_from: 200,
+            ..keyset_info(CurrencyUnit::Sat, "m/0'/0'/1'", true)
+        };
+        let mut mint = create_mint(HashSet::from([current, retired])).await;
+
+        let infos = mint.keysets_info().await.unwrap();
+        assert_eq!(
+            serde_json::to_string(&infos).unwrap(),
+            r#"[{"id":"00f2caebf3e1012a","unit":"sat","active":false,"input_fee_ppk":0,"valid_from":100,"valid_to":200,"derivation_path_index":0},{"id":"00951873bfc05f34","unit":"sat","active":true,"input_fee_ppk":0,"valid_from":200,"derivation_path_index":1}]"#
+        );
+        assert_eq!(mint.keysets().await.unwrap().keysets, infos);
+
+        let before = unix_time();
+        mint.rotate_keyset(
+            CurrencyUnit::Sat,
+            &DerivationPath::from_str("m/0'/0'/2'").unwrap(),
+            32,
+        )
+        .await
+        .unwrap();
+
+        let infos = mint.keysets_info().await.unwrap();
+        assert_eq!(infos.len(), 3);
+        assert!(infos
+            .windows(2)
+            .all(|pair| pair[0].valid_from <= pair[1].valid_from));
+
+        // Rotation retires the previous keyset and stamps the new one
+        let rotated = &infos[1];
+        assert!(!rotated.active);
+        assert!(rotated.valid_to.unwrap() >= before);
+        let new = &infos[2];
+        assert!(new.active);
+        assert!(new.valid_from.unwrap() >= before);
+        assert_eq!(new.valid_to, None);
+        assert_eq!(new.derivation_path_index, Some(2));
+
+        // Metadata is persisted with the keyset
+        let stored = mint.localstore.get_keyset(&new.id).await.unwrap().unwrap();
+        assert_eq!(Some(stored.valid_from), new.valid_from);
+        assert_eq!(stored.derivation_path_index, Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_max_order_bounds() {
+        for max_order in [0, 65] {
+            let info = MintKeySetInfo {
+                max_order,
+                ..keyset_info(CurrencyUnit::Sat, "m/0'/0'/0'", true)
+            };
+            let localstore = MintMemoryDatabase::new(
+                MintInfo::default(),
+                HashMap::new(),
+                vec![],
+                vec![],
+                vec![],
+                vec![],
+                vec![],
+                HashMap::new(),
+            )
+            .unwrap();
+            let mint = Mint::new(
+                Arc::new(localstore),
+                Mnemonic::from_str(MNEMONIC).unwrap(),
+                HashSet::from([info]),
+                Amount::from(1),
+                0.01,
+            )
+            .await;
+            assert!(matches!(
+                mint,
+                Err(Error::NUT02(nut02::Error::InvalidMaxOrder(order))) if order == max_order
+            ));
+        }
+
+        let mut mint = create_mint(HashSet::new()).await;
+        let path = derivation_path_from_unit(&CurrencyUnit::Sat, 1).unwrap();
+        assert!(matches!(
+            mint.rotate_keyset(CurrencyUnit::Sat, &path, 65).await,
+            Err(Error::NUT02(nut02::Error::InvalidMaxOrder(65)))
+        ));
+        let policy = RotationPolicy {
+            interval: 1,
+            max_order: 0,
+            keep_accepting_for: 0,
+        };
+        assert!(matches!(
+            mint.set_rotation_policy(CurrencyUnit::Sat, Some(policy)),
+            Err(Error::NUT02(nut02::Error::InvalidMaxOrder(0)))
+        ));
+        assert_eq!(mint.rotation_policy(&CurrencyUnit::Sat), None);
+
+        mint.rotate_keyset(CurrencyUnit::Sat, &path, 1)
+            .await
+            .unwrap();
+        let keyset = mint
+            .get_keyset(&active_keyset_id(&mint, &CurrencyUnit::Sat).await)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(keyset.max_amount(), Some(Amount::from(1)));
+    }
+
+    #[tokio::test]
+    async fn test_rotation_policy() {
+        const DAY: u64 = 24 * 60 * 60;
+        let start = 1_700_000_000;
+        let first = MintKeySetInfo {
+            valid_from: start,
+            ..keyset_info(CurrencyUnit::Sat, "m/0'/0'/0'", true)
+        };
+        let mut mint = create_mint(HashSet::from([first])).await;
+        let first_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+        let mut events = mint.subscribe();
+
+        // Nothing is rotated without a policy
+        assert!(!mint.rotation_due(start + 1000 * DAY).await.unwrap());
+
+        let policy = RotationPolicy {
+            interval: 90 * DAY,
+            max_order: 32,
+            keep_accepting_for: 7 * DAY,
+        };
+        mint.set_rotation_policy(CurrencyUnit::Sat, Some(policy))
+            .unwrap();
+
+        let due = start + 90 * DAY;
+        assert!(!mint.rotation_due(due - 1).await.unwrap());
+        assert!(mint.rotate_if_due(due - 1).await.unwrap().is_empty());
+        assert!(mint.rotation_due(due).await.unwrap());
+
+        // Called late, the keyset is still only rotated once
+        let late = due + DAY;
+        let rotated = mint.rotate_if_due(late).await.unwrap();
+        assert_eq!(rotated.len(), 1);
+        let second_id = rotated[0];
+        assert_eq!(active_keyset_id(&mint, &CurrencyUnit::Sat).await, second_id);
+        assert!(mint.rotate_if_due(late).await.unwrap().is_empty());
+        assert!(!mint.rotation_due(late + 90 * DAY - 1).await.unwrap());
+
+        let old = mint
+            .localstore
+            .get_keyset(&first_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(old.valid_to, Some(late));
+        assert_eq!(old.refused_from, Some(late + 7 * DAY));
+        let new = mint
+            .localstore
+            .get_keyset(&second_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(new.valid_from, late);
+        assert_eq!(new.derivation_path_index, Some(1));
+        assert_eq!(
+            events.try_recv().unwrap(),
+            MintEvent::KeysetRotated {
+                unit: CurrencyUnit::Sat,
+                old_id: first_id,
+                new_id: second_id,
+                refused_from: late + 7 * DAY,
             }
+        );
+        assert!(events.try_recv().is_err());
+
+        // The next interval is measured from the new keyset
+        let rotated = mint.rotate_if_due(late + 90 * DAY).await.unwrap();
+        assert_eq!(rotated.len(), 1);
+        let old = mint
+            .localstore
+            .get_keyset(&second_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(old.refused_from, Some(late + 97 * DAY));
+
+        // Rotated keysets are derived from the mnemonic at the next index
+        let seed = Mnemonic::from_str(MNEMONIC).unwrap().to_seed_normalized("");
+        for (index, id) in [(1, second_id), (2, rotated[0])] {
+            let derived = MintKeySet::generate(
+                &seed,
+                CurrencyUnit::Sat,
+                &derivation_path_from_unit(&CurrencyUnit::Sat, index).unwrap(),
+                32,
+            )
+            .unwrap();
+            assert_eq!(derived.id, id);
+        }
+
+        mint.set_rotation_policy(CurrencyUnit::Sat, None).unwrap();
+        assert!(!mint.rotation_due(late + 1000 * DAY).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_non_power_of_two_keyset() {
+        let mut mint = create_mint(HashSet::new()).await;
+
+        // Powers of two up to 2^63 in sat, nothing in a unit without keyset
+        let amounts = mint.amounts(&CurrencyUnit::Sat).await.unwrap();
+        assert_eq!(
+            amounts,
+            (0..64).map(|i| Amount::from(1 << i)).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            mint.max_amount(&CurrencyUnit::Sat).await.unwrap(),
+            Some(Amount::from(1 << 63))
+        );
+        assert!(mint.amounts(&CurrencyUnit::Usd).await.unwrap().is_empty());
+        assert_eq!(mint.max_amount(&CurrencyUnit::Usd).await.unwrap(), None);
+        assert!(!mint
+            .contains_amount(&CurrencyUnit::Usd, Amount::from(1))
+            .await
+            .unwrap());
+
+        assert!(matches!(
+            mint.rotate_keyset_with_amounts(
+                CurrencyUnit::Usd,
+                &DerivationPath::from_str("m/0'/2'/0'").unwrap(),
+                &[0, 5],
+            )
+            .await,
+            Err(Error::InvalidDenominations)
+        ));
+
+        mint.rotate_keyset_with_amounts(
+            CurrencyUnit::Usd,
+            &DerivationPath::from_str("m/0'/2'/0'").unwrap(),
+            &[50, 10, 5, 1],
+        )
+        .await
+        .unwrap();
+        let keyset_id = mint
+            .localstore
+            .get_active_keyset_id(&CurrencyUnit::Usd)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let keyset = mint.keyset(&keyset_id).await.unwrap().unwrap();
+        assert_eq!(Id::from(&keyset.keys), keyset_id);
+        let denominations = mint.amounts(&CurrencyUnit::Usd).await.unwrap();
+        assert_eq!(denominations, [1, 5, 10, 50].map(Amount::from).to_vec());
+        assert_eq!(
+            mint.max_amount(&CurrencyUnit::Usd).await.unwrap(),
+            Some(Amount::from(50))
+        );
+        for (amount, signed) in [(5, true), (50, true), (2, false), (64, false)] {
+            assert_eq!(
+                mint.contains_amount(&CurrencyUnit::Usd, Amount::from(amount))
+                    .await
+                    .unwrap(),
+                signed,
+                "{amount}"
+            );
+        }
+
+        let mut quote = mint
+            .new_mint_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Usd,
+                Amount::from(73),
+                unix_time() + 600,
+                None,
+            )
+            .await
+            .unwrap();
+        quote.paid = true;
+        mint.update_mint_quote(quote.clone()).await.unwrap();
 
-            change = Some(change_sigs);
-        } else {
-            info!(
-                "No change outputs provided. Burnt: {:?} sats",
-                (melt_request.proofs_amount() - total_spent)
+        // Powers of two other than 1 have no key in this keyset
+        let mint_request = nut04::MintBolt11Request::new(
+            quote.id.clone(),
+            [64, 8, 1]
+                .into_iter()
+                .map(|amount| blinded_message(Amount::from(amount), keyset_id))
+                .collect(),
+        );
+        assert!(matches!(
+            mint.process_mint_request(mint_request).await,
+            Err(Error::AmountKey)
+        ));
+
+        let amounts = Amount::from(73).split_into(&denominations).unwrap();
+        assert_eq!(amounts, [50, 10, 10, 1, 1, 1].map(Amount::from).to_vec());
+
+        let mint_request = nut04::MintBolt11Request::new(
+            quote.id.clone(),
+            amounts
+                .iter()
+                .map(|amount| blinded_message(*amount, keyset_id))
+                .collect(),
+        );
+        let response = mint.process_mint_request(mint_request).await.unwrap();
+        let signed: Vec<Amount> = response.signatures.iter().map(|s| s.amount).collect();
+        assert_eq!(signed, amounts);
+        assert!(response.signatures.iter().all(|s| s.keyset_id == keyset_id));
+    }
+
+    #[tokio::test]
+    async fn test_prune_expired_quotes() {
+        let mint = create_mint(HashSet::new()).await;
+        let now = 1_000;
+
+        let mint_quote = |expiry, paid, issued| {
+            let mut quote = MintQuote::new(
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                Amount::from(10),
+                expiry,
+                None,
+            );
+            quote.paid = paid;
+            quote.issued = issued;
+            quote
+        };
+        let melt_quote = |expiry, paid, pending| {
+            let mut quote = MeltQuote::new(
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                Amount::from(10),
+                Amount::from(1),
+                expiry,
             );
+            quote.paid = paid;
+            quote.pending = pending;
+            quote
+        };
+
+        let expired_unpaid = mint_quote(now - 1, false, false);
+        let unexpired_unpaid = mint_quote(now + 1, false, false);
+        let no_expiry = mint_quote(0, false, false);
+        let paid_not_issued = mint_quote(now - 1, true, false);
+        let issued = mint_quote(now - 1, true, true);
+
+        let expired_melt = melt_quote(now, false, false);
+        let unexpired_melt = melt_quote(now + 1, false, false);
+        let pending_melt = melt_quote(now - 1, false, true);
+        let paid_melt = melt_quote(now - 1, true, false);
+
+        for quote in [
+            &expired_unpaid,
+            &unexpired_unpaid,
+            &no_expiry,
+            &paid_not_issued,
+            &issued,
+        ] {
+            mint.update_mint_quote(quote.clone()).await.unwrap();
+        }
+        for quote in [&expired_melt, &unexpired_melt, &pending_melt, &paid_melt] {
+            mint.update_melt_quote(quote.clone()).await.unwrap();
         }
 
-        Ok(MeltBolt11Response {
-            paid: true,
-            payment_preimage: Some(preimage.to_string()),
-            change,
-        })
+        let report = mint.prune_expired_quotes(now).await.unwrap();
+
+        assert_eq!(report.removed_mint_quotes, 1);
+        assert_eq!(report.removed_melt_quotes, 1);
+        assert_eq!(
+            report.retained,
+            BTreeMap::from([
+                (RetainReason::Unexpired, 3),
+                (RetainReason::PaidNotIssued, 1),
+                (RetainReason::Pending, 1),
+                (RetainReason::Settled, 2),
+            ])
+        );
+
+        let mut mint_ids: Vec<String> = mint
+            .mint_quotes()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|q| q.id)
+            .collect();
+        let mut expected: Vec<String> = [&unexpired_unpaid, &no_expiry, &paid_not_issued, &issued]
+            .iter()
+            .map(|q| q.id.clone())
+            .collect();
+        mint_ids.sort();
+        expected.sort();
+        assert_eq!(mint_ids, expected);
+
+        let mut melt_ids: Vec<String> = mint
+            .localstore
+            .get_melt_quotes()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|q| q.id)
+            .collect();
+        let mut expected: Vec<String> = [&unexpired_melt, &pending_melt, &paid_melt]
+            .iter()
+            .map(|q| q.id.clone())
+            .collect();
+        melt_ids.sort();
+        expected.sort();
+        assert_eq!(melt_ids, expected);
+
+        // Pruning again removes nothing
+        let report = mint.prune_expired_quotes(now).await.unwrap();
+        assert_eq!(report.removed_mint_quotes, 0);
+        assert_eq!(report.removed_melt_quotes, 0);
     }
 
-    pub async fn check_melt_quote(&self, quote_id: &str) -> Result<MeltQuoteBolt11Response, Error> {
-        let quote = self
+    #[tokio::test]
+    async fn test_mint_quote_description() {
+        let mut mint = create_mint(HashSet::new()).await;
+
+        let request: nut04::MintQuoteBolt11Request =
+            serde_json::from_str(r#"{"amount":10,"unit":"sat","description":"top-up from Alice"}"#)
+                .unwrap();
+        let quote = mint
+            .new_mint_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                request.unit.clone(),
+                request.amount,
+                unix_time() + 600,
+                request.invoice_description().unwrap(),
+            )
+            .await
+            .unwrap();
+        let stored = mint.localstore.get_mint_quote(&quote.id).await.unwrap();
+        assert_eq!(
+            stored.unwrap().description.as_deref(),
+            Some("top-up from Alice")
+        );
+
+        let request: nut04::MintQuoteBolt11Request =
+            serde_json::from_str(r#"{"amount":10,"unit":"sat"}"#).unwrap();
+        assert!(request.description.is_none());
+        let quote = mint
+            .new_mint_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                request.unit.clone(),
+                request.amount,
+                unix_time() + 600,
+                request.invoice_description().unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(quote.description.is_none());
+
+        let too_long = "a".repeat(mint.request_limits().max_description_length + 1);
+        assert!(matches!(
+            mint.new_mint_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                Amount::from(10),
+                unix_time() + 600,
+                Some(InvoiceDescription::Text(too_long)),
+            )
+            .await,
+            Err(Error::RequestTooLarge {
+                limit: "max_description_length",
+                max: 639
+            })
+        ));
+
+        // A hash is stored as is, and not held to the description length
+        let request: nut04::MintQuoteBolt11Request = serde_json::from_str(&format!(
+            r#"{{"amount":10,"unit":"sat","description_hash":"{}"}}"#,
+            "ab".repeat(32)
+        ))
+        .unwrap();
+        assert_eq!(request.description_hash, Some([0xab; 32]));
+        let quote = mint
+            .new_mint_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                request.unit.clone(),
+                request.amount,
+                unix_time() + 600,
+                request.invoice_description().unwrap(),
+            )
+            .await
+            .unwrap();
+        let stored = mint
             .localstore
-            .get_melt_quote(quote_id)
-            .await?
-            .ok_or(Error::UnknownQuote)?;
+            .get_mint_quote(&quote.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.description_hash, Some([0xab; 32]));
+        assert!(stored.description.is_none());
 
-        Ok(MeltQuoteBolt11Response {
-            quote: quote.id,
-            paid: quote.paid,
-            expiry: quote.expiry,
-            amount: u64::from(quote.amount),
-            fee_reserve: u64::from(quote.fee_reserve),
-        })
+        let request: nut04::MintQuoteBolt11Request = serde_json::from_str(&format!(
+            r#"{{"amount":10,"unit":"sat","description":"top-up","description_hash":"{}"}}"#,
+            "ab".repeat(32)
+        ))
+        .unwrap();
+        assert!(matches!(
+            request.invoice_description(),
+            Err(nut00::Error::DescriptionAndHash)
+        ));
+
+        // Support is advertised unless descriptions are disabled
+        let mut mint_info = MintInfo::default();
+        mint_info.nuts.nut04.methods = vec![nut04::MintMethodSettings::default()];
+        mint.localstore.set_mint_info(&mint_info).await.unwrap();
+        assert!(mint.mint_info().await.unwrap().nuts.nut04.methods[0].description);
+
+        // Hashes only with a backend that can commit to them
+        assert!(!mint.mint_info().await.unwrap().nuts.nut04.methods[0].description_hash);
+        mint.set_ln_backend(Arc::new(FakeLnBackend::new()));
+        assert!(mint.mint_info().await.unwrap().nuts.nut04.methods[0].description_hash);
+
+        mint.set_request_limits(RequestLimits {
+            max_description_length: 0,
+            ..Default::default()
+        });
+        assert!(!mint.mint_info().await.unwrap().nuts.nut04.methods[0].description);
     }
 
-    pub async fn mint_info(&self) -> Result<MintInfo, Error> {
-        Ok(self.localstore.get_mint_info().await?)
+    #[tokio::test]
+    async fn test_keyset_and_spent_proof_accessors() {
+        let mint = create_mint(HashSet::from([keyset_info(
+            CurrencyUnit::Sat,
+            "m/0'/0'/0'",
+            true,
+        )]))
+        .await;
+        let active_id = mint
+            .localstore
+            .get_active_keyset_id(&CurrencyUnit::Sat)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let keyset = MintKeySet::generate(
+            &Mnemonic::from_str(MNEMONIC).unwrap().to_seed_normalized(""),
+            CurrencyUnit::Sat,
+            &DerivationPath::from_str("m/0'/0'/1'").unwrap(),
+            8,
+        )
+        .unwrap();
+        let inactive_id = keyset.id;
+
+        let mut mismatched = keyset.clone();
+        mismatched.id = active_id;
+        assert!(matches!(
+            mint.add_inactive_keyset(mismatched).await,
+            Err(Error::KeysetIdMismatch)
+        ));
+
+        mint.add_inactive_keyset(keyset.clone()).await.unwrap();
+        let infos = mint.keyset_infos().await.unwrap();
+        assert_eq!(infos.len(), 2);
+        assert!(infos.iter().any(|k| k.id == inactive_id && !k.active));
+        assert_eq!(mint.keysets().await.unwrap().keysets.len(), 2);
+
+        // Inactive keysets cannot sign new outputs
+        assert!(matches!(
+            mint.blind_sign(&blinded_message(Amount::from(1), inactive_id))
+                .await,
+            Err(Error::InactiveKeyset { .. })
+        ));
+
+        assert!(matches!(
+            mint.remove_keyset(&active_id).await,
+            Err(Error::ActiveKeyset)
+        ));
+        mint.remove_keyset(&inactive_id).await.unwrap();
+        assert!(mint.keyset(&inactive_id).await.unwrap().is_none());
+        assert_eq!(mint.keyset_infos().await.unwrap().len(), 1);
+
+        // Removed keysets can be added back
+        mint.add_inactive_keyset(keyset).await.unwrap();
+        assert!(mint.keyset(&inactive_id).await.unwrap().is_some());
+
+        assert_eq!(mint.spent_count().await.unwrap(), 0);
+        let proofs = mint_proofs(&mint, active_id, Amount::from(7)).await;
+        mint.localstore
+            .add_pending_proof(proofs[0].clone())
+            .await
+            .unwrap();
+
+        mint.import_spent_proofs(proofs.clone()).await.unwrap();
+        assert_eq!(mint.spent_count().await.unwrap(), 3);
+        assert!(mint
+            .localstore
+            .get_pending_proof_by_secret(&proofs[0].secret)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            mint.verify_proofs(&proofs).await[0],
+            Err(Error::TokenSpent)
+        ));
+
+        #[allow(deprecated)]
+        let localstore = mint.localstore();
+        assert_eq!(localstore.get_spent_ys().await.unwrap().len(), 3);
     }
 
-    pub async fn restore(&self, request: RestoreRequest) -> Result<RestoreResponse, Error> {
-        let output_len = request.outputs.len();
+    #[tokio::test]
+    async fn test_mint_keysets_are_reproducible() {
+        let keysets_info = HashSet::from([
+            keyset_info(CurrencyUnit::Sat, "m/0'/0'/0'", true),
+            keyset_info(CurrencyUnit::Usd, "m/0'/2'/0'", true),
+            keyset_info(CurrencyUnit::Sat, "m/0'/0'/1'", false),
+        ]);
 
-        let mut outputs = Vec::with_capacity(output_len);
-        let mut signatures = Vec::with_capacity(output_len);
+        let first = create_mint(keysets_info.clone()).await;
+        let second = create_mint(keysets_info).await;
 
-        let blinded_message: Vec<PublicKey> =
-            request.outputs.iter().map(|b| b.blinded_secret).collect();
+        let mut first_keysets = first.localstore.get_keysets().await.unwrap();
+        let mut second_keysets = second.localstore.get_keysets().await.unwrap();
+        first_keysets.sort_by_key(|k| k.id.to_string());
+        second_keysets.sort_by_key(|k| k.id.to_string());
 
-        let blinded_signatures = self
+        assert_eq!(first_keysets.len(), 3);
+        for (first, second) in first_keysets.iter().zip(&second_keysets) {
+            assert_eq!(first.id, second.id);
+            assert_eq!(
+                serde_json::to_vec(&Keys::from(&first.keys)).unwrap(),
+                serde_json::to_vec(&Keys::from(&second.keys)).unwrap()
+            );
+        }
+        assert_eq!(
+            first.localstore.get_active_keysets().await.unwrap(),
+            second.localstore.get_active_keysets().await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_self_check() {
+        let mut mint = create_mint(HashSet::from([
+            keyset_info(CurrencyUnit::Sat, "m/0'/0'/0'", true),
+            keyset_info(CurrencyUnit::Usd, "m/0'/2'/0'", true),
+        ]))
+        .await;
+        mint.rotate_keyset(
+            CurrencyUnit::Sat,
+            &DerivationPath::from_str("m/0'/0'/1'").unwrap(),
+            8,
+        )
+        .await
+        .unwrap();
+
+        let report = mint.self_check().await.unwrap();
+        assert_eq!(report.keysets.len(), 3);
+        assert_eq!(report.keysets.iter().filter(|k| k.active).count(), 2);
+        assert!(report.passed());
+        assert!(report
+            .keysets
+            .iter()
+            .all(|k| k.derivation_matches == Some(true)));
+
+        // Flip a byte of one stored secret key
+        let usd_id = mint
             .localstore
-            .get_blinded_signatures(blinded_message)
-            .await?;
+            .get_active_keyset_id(&CurrencyUnit::Usd)
+            .await
+            .unwrap()
+            .unwrap();
+        let mut corrupted = mint.localstore.get_keyset(&usd_id).await.unwrap().unwrap();
+        let keypair = corrupted.keys.get_mut(&Amount::from(4)).unwrap();
+        let mut bytes = keypair.secret_key.to_secret_bytes();
+        bytes[31] ^= 1;
+        keypair.secret_key = SecretKey::from_slice(&bytes).unwrap();
+        mint.localstore.add_keyset(corrupted).await.unwrap();
 
-        assert_eq!(blinded_signatures.len(), output_len);
+        let report = mint.self_check().await.unwrap();
+        assert!(!report.passed());
+        for check in report.keysets {
+            if check.id == usd_id {
+                assert!(check.active);
+                assert!(check.id_matches);
+                assert!(!check.keys_valid);
+                assert_eq!(check.derivation_matches, Some(false));
+            } else {
+                assert!(check.passed());
+            }
+        }
+    }
 
-        for (blinded_message, blinded_signature) in
-            request.outputs.into_iter().zip(blinded_signatures)
-        {
-            if let Some(blinded_signature) = blinded_signature {
-                outputs.push(blinded_message);
-                signatures.push(blinded_signature);
+    #[tokio::test]
+    async fn test_keysets_are_shared_between_requests() {
+        let mut mint = create_mint(HashSet::from([keyset_info(
+            CurrencyUnit::Sat,
+            "m/0'/0'/0'",
+            true,
+        )]))
+        .await;
+        let keyset_id = mint
+            .localstore
+            .get_active_keyset_id(&CurrencyUnit::Sat)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let first = mint.get_keyset(&keyset_id).await.unwrap().unwrap();
+        let second = mint.clone().get_keyset(&keyset_id).await.unwrap().unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        let keys = mint.keyset_pubkeys(&keyset_id).await.unwrap();
+        assert_eq!(keys.keysets[0], KeySet::from(first.as_ref()));
+
+        // Public keys are converted once and handed out by reference
+        let public = mint.keyset(&keyset_id).await.unwrap().unwrap();
+        assert!(Arc::ptr_eq(
+            &public,
+            &mint.keyset(&keyset_id).await.unwrap().unwrap()
+        ));
+        assert_eq!(public.as_ref(), &keys.keysets[0]);
+
+        mint.rotate_keyset(
+            CurrencyUnit::Sat,
+            &DerivationPath::from_str("m/0'/0'/1'").unwrap(),
+            8,
+        )
+        .await
+        .unwrap();
+        let rotated_id = mint
+            .localstore
+            .get_active_keyset_id(&CurrencyUnit::Sat)
+            .await
+            .unwrap()
+            .unwrap();
+        let rotated = mint.keyset(&rotated_id).await.unwrap().unwrap();
+        assert_eq!(rotated.id, rotated_id);
+        assert_ne!(rotated.id, keyset_id);
+        assert!(Arc::ptr_eq(
+            &rotated,
+            &mint.keyset(&rotated_id).await.unwrap().unwrap()
+        ));
+
+        // Keysets only known to the database are loaded on first use
+        let stored = MintKeySet::generate(
+            &Mnemonic::from_str(MNEMONIC).unwrap().to_seed_normalized(""),
+            CurrencyUnit::Usd,
+            &DerivationPath::from_str("m/0'/2'/0'").unwrap(),
+            8,
+        )
+        .unwrap();
+        mint.localstore.add_keyset(stored.clone()).await.unwrap();
+        assert_eq!(
+            mint.keyset(&stored.id).await.unwrap(),
+            Some(Arc::new(KeySet::from(stored)))
+        );
+
+        assert!(mint
+            .keyset(&Id::from_str("00ffffffffffffff").unwrap())
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn prop_process_swap_request_never_panics(
+            mut swap_request in any::<SwapRequest>(),
+            use_mint_keyset in any::<bool>(),
+        ) {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+
+            runtime.block_on(async {
+                let mut mint =
+                    create_mint(HashSet::from([keyset_info(CurrencyUnit::Sat, "m/0'/0'/0'", true)]))
+                        .await;
+
+                // Reach past the keyset lookup into verification
+                if use_mint_keyset {
+                    let keyset_id = mint
+                        .localstore
+                        .get_active_keyset_id(&CurrencyUnit::Sat)
+                        .await
+                        .unwrap()
+                        .unwrap();
+
+                    for proof in swap_request.inputs.iter_mut() {
+                        proof.keyset_id = keyset_id;
+                    }
+                    for output in swap_request.outputs.iter_mut() {
+                        output.keyset_id = keyset_id;
+                    }
+                }
+
+                // Arbitrary proofs are never valid
+                let has_inputs = !swap_request.inputs.is_empty();
+                let result = mint.process_swap_request(swap_request).await;
+                assert!(!has_inputs || result.is_err());
+            });
+        }
+    }
+
+    #[cfg(feature = "invariant-checks")]
+    #[tokio::test]
+    async fn test_invariants_detect_corrupted_state() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let keyset_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+
+        let swap = |proofs: Proofs| {
+            let amount = proofs.total_amount().unwrap();
+            SwapRequest::new(
+                proofs,
+                PreMintSecrets::random(keyset_id, amount)
+                    .unwrap()
+                    .blinded_messages(),
+            )
+        };
+        mint.process_swap_request(swap(mint_proofs(&mint, keyset_id, Amount::from(8)).await))
+            .await
+            .unwrap();
+        assert_eq!(mint.check_invariants().await.unwrap(), vec![]);
+
+        // Imported spent proofs open the ledger again
+        mint.import_spent_proofs(mint_proofs(&mint, keyset_id, Amount::from(2)).await)
+            .await
+            .unwrap();
+        assert_eq!(mint.check_invariants().await.unwrap(), vec![]);
+
+        // Spent behind the mint's back
+        let proofs = mint_proofs(&mint, keyset_id, Amount::from(4)).await;
+        mint.localstore
+            .add_spent_proof(proofs[0].clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            mint.check_invariants().await.unwrap(),
+            vec![InvariantViolation::Ledger {
+                unit: CurrencyUnit::Sat,
+                issued: Amount::from(8),
+                redeemed: Amount::from(14),
+                outstanding: -2,
+                difference: -4,
+            }]
+        );
+
+        // A melt input both pending and spent
+        let invoice = Bolt11Invoice::from_str(INVOICE).unwrap();
+        let amount = Amount::from_msat_floor(invoice.amount_milli_satoshis().unwrap());
+        let melt_quote = mint
+            .new_melt_quote(
+                PaymentMethod::Bolt11,
+                INVOICE.to_string(),
+                CurrencyUnit::Sat,
+                amount,
+                unix_time() + 600,
+            )
+            .await
+            .unwrap();
+        let inputs = mint_proofs(&mint, keyset_id, amount + melt_quote.fee_reserve).await;
+        mint.reserve_melt_request(&MeltBolt11Request::new(
+            melt_quote.id.clone(),
+            inputs.clone(),
+            None,
+        ))
+        .await
+        .unwrap();
+        mint.localstore
+            .add_spent_proof(inputs[0].clone())
+            .await
+            .unwrap();
+
+        let violations = mint.check_invariants().await.unwrap();
+        assert_eq!(violations.len(), 2);
+        assert_eq!(
+            violations[1],
+            InvariantViolation::PendingAndSpent {
+                quote_id: melt_quote.id,
+                y: inputs[0].y().unwrap(),
+            }
+        );
+    }
+
+    // Invariants are only checked between operations, not while they overlap
+    #[cfg(not(feature = "invariant-checks"))]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_concurrent_swaps_spend_once() {
+        const TASKS: usize = 32;
+
+        let mint = create_mint(HashSet::new()).await;
+        let keyset_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+
+        for round in 0..10 {
+            let proofs = mint_proofs(&mint, keyset_id, Amount::from(15)).await;
+            let barrier = Arc::new(tokio::sync::Barrier::new(TASKS));
+
+            let tasks: Vec<_> = (0..TASKS)
+                .map(|_| {
+                    let mut mint = mint.clone();
+                    let barrier = barrier.clone();
+                    let swap = SwapRequest::new(
+                        proofs.clone(),
+                        Amount::from(15)
+                            .split()
+                            .into_iter()
+                            .map(|amount| blinded_message(amount, keyset_id))
+                            .collect(),
+                    );
+                    tokio::spawn(async move {
+                        barrier.wait().await;
+                        mint.process_swap_request(swap).await
+                    })
+                })
+                .collect();
+
+            let mut swapped = 0;
+            for task in tasks {
+                match task.await.unwrap() {
+                    Ok(_) => swapped += 1,
+                    Err(Error::TokenSpent) => (),
+                    Err(err) => panic!("Unexpected error: {err}"),
+                }
             }
+            assert_eq!(swapped, 1, "round {round}");
         }
 
-        Ok(RestoreResponse {
-            outputs,
-            signatures,
-        })
+        // Every input is recorded once, and only the winning outputs are signed
+        assert_eq!(mint.spent_count().await.unwrap(), 40);
+        assert_eq!(
+            mint.localstore
+                .get_blinded_signatures_by_keyset(&keyset_id)
+                .await
+                .unwrap()
+                .len(),
+            40
+        );
     }
-}
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct FeeReserve {
-    pub min_fee_reserve: Amount,
-    pub percent_fee_reserve: f32,
-}
+    #[cfg(not(feature = "invariant-checks"))]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_concurrent_melts_reserve_once() {
+        const TASKS: usize = 16;
 
-#[derive(Debug, Hash, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct MintKeySetInfo {
-    pub id: Id,
-    pub unit: CurrencyUnit,
-    pub active: bool,
-    pub valid_from: u64,
-    pub valid_to: Option<u64>,
-    pub derivation_path: DerivationPath,
-    pub max_order: u8,
+        let mint = create_mint(HashSet::new()).await;
+        let keyset_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+        let amount = Amount::from(2500);
+
+        for round in 0..5 {
+            let mut quote_ids = Vec::with_capacity(TASKS);
+            let mut fee_reserve = Amount::ZERO;
+            for _ in 0..TASKS {
+                let quote = mint
+                    .new_melt_quote(
+                        PaymentMethod::Bolt11,
+                        INVOICE.to_string(),
+                        CurrencyUnit::Sat,
+                        amount,
+                        unix_time() + 600,
+                    )
+                    .await
+                    .unwrap();
+                fee_reserve = quote.fee_reserve;
+                quote_ids.push(quote.id);
+            }
+            let proofs = mint_proofs(&mint, keyset_id, amount + fee_reserve).await;
+            let barrier = Arc::new(tokio::sync::Barrier::new(TASKS));
+
+            let tasks: Vec<_> = quote_ids
+                .iter()
+                .map(|quote_id| {
+                    let mut mint = mint.clone();
+                    let barrier = barrier.clone();
+                    let melt = MeltBolt11Request::new(quote_id.clone(), proofs.clone(), None);
+                    tokio::spawn(async move {
+                        barrier.wait().await;
+                        mint.reserve_melt_request(&melt).await
+                    })
+                })
+                .collect();
+
+            let mut reserved = Vec::new();
+            for task in tasks {
+                match task.await.unwrap() {
+                    Ok(quote) => reserved.push(quote.id),
+                    Err(Error::TokenPending) => (),
+                    Err(err) => panic!("Unexpected error: {err}"),
+                }
+            }
+            assert_eq!(reserved.len(), 1, "round {round}");
+
+            // Only the winning quote is paid over Lightning
+            for quote_id in &quote_ids {
+                let quote = mint
+                    .localstore
+                    .get_melt_quote(quote_id)
+                    .await
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(quote.pending, reserved.contains(quote_id));
+            }
+        }
+    }
+
+    #[cfg(not(feature = "invariant-checks"))]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_concurrent_reserves_of_a_quote_reserve_once() {
+        const TASKS: usize = 16;
+
+        let mint = create_mint(HashSet::new()).await;
+        let keyset_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+        let amount = Amount::from(2500);
+
+        for round in 0..5 {
+            let quote = mint
+                .new_melt_quote(
+                    PaymentMethod::Bolt11,
+                    INVOICE.to_string(),
+                    CurrencyUnit::Sat,
+                    amount,
+                    unix_time() + 600,
+                )
+                .await
+                .unwrap();
+            let mut inputs = Vec::with_capacity(TASKS);
+            for _ in 0..TASKS {
+                inputs.push(mint_proofs(&mint, keyset_id, amount + quote.fee_reserve).await);
+            }
+            let barrier = Arc::new(tokio::sync::Barrier::new(TASKS));
+
+            let tasks: Vec<_> = inputs
+                .iter()
+                .map(|proofs| {
+                    let mut mint = mint.clone();
+                    let barrier = barrier.clone();
+                    let melt = MeltBolt11Request::new(quote.id.clone(), proofs.clone(), None);
+                    tokio::spawn(async move {
+                        barrier.wait().await;
+                        mint.reserve_melt_request(&melt).await
+                    })
+                })
+                .collect();
+
+            let mut reserved = Vec::new();
+            for (proofs, task) in inputs.iter().zip(tasks) {
+                match task.await.unwrap() {
+                    Ok(_) => reserved.push(proofs),
+                    Err(Error::PendingQuote) => (),
+                    Err(err) => panic!("Unexpected error: {err}"),
+                }
+            }
+            assert_eq!(reserved.len(), 1, "round {round}");
+
+            // The quote holds the inputs of the winner, no others are pending
+            let stored = mint
+                .localstore
+                .get_melt_quote(&quote.id)
+                .await
+                .unwrap()
+                .unwrap();
+            let winner: Vec<Secret> = reserved[0].iter().map(|p| p.secret.clone()).collect();
+            assert_eq!(stored.reserved_inputs, winner);
+            for proofs in &inputs {
+                let pending = mint
+                    .localstore
+                    .get_pending_proof_by_secret(&proofs[0].secret)
+                    .await
+                    .unwrap()
+                    .is_some();
+                assert_eq!(pending, std::ptr::eq(proofs, reserved[0]));
+            }
+        }
+    }
+
+    #[cfg(not(feature = "invariant-checks"))]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_concurrent_swap_and_melt_spend_once() {
+        const TASKS: usize = 16;
+
+        let mint = create_mint(HashSet::new()).await;
+        let keyset_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+        let amount = Amount::from(2500);
+
+        for round in 0..10 {
+            let quote = mint
+                .new_melt_quote(
+                    PaymentMethod::Bolt11,
+                    INVOICE.to_string(),
+                    CurrencyUnit::Sat,
+                    amount,
+                    unix_time() + 600,
+                )
+                .await
+                .unwrap();
+            let total = amount + quote.fee_reserve;
+            let proofs = mint_proofs(&mint, keyset_id, total).await;
+            let barrier = Arc::new(tokio::sync::Barrier::new(TASKS));
+
+            let tasks: Vec<_> = (0..TASKS)
+                .map(|task| {
+                    let mut mint = mint.clone();
+                    let barrier = barrier.clone();
+                    let melt = MeltBolt11Request::new(quote.id.clone(), proofs.clone(), None);
+                    let swap = SwapRequest::new(
+                        proofs.clone(),
+                        PreMintSecrets::random(keyset_id, total)
+                            .unwrap()
+                            .blinded_messages(),
+                    );
+                    tokio::spawn(async move {
+                        barrier.wait().await;
+                        match task % 2 {
+                            0 => mint.reserve_melt_request(&melt).await.map(|_| false),
+                            _ => mint.process_swap_request(swap).await.map(|_| true),
+                        }
+                    })
+                })
+                .collect();
+
+            let mut winners = Vec::new();
+            for task in tasks {
+                match task.await.unwrap() {
+                    Ok(swapped) => winners.push(swapped),
+                    Err(Error::TokenSpent | Error::TokenPending | Error::PendingQuote) => (),
+                    Err(err) => panic!("Unexpected error: {err}"),
+                }
+            }
+            assert_eq!(winners.len(), 1, "round {round}");
+
+            // A reserved melt leaves its inputs pending, a swap spends them
+            let expected = match winners[0] {
+                true => State::Spent,
+                false => State::Pending,
+            };
+            let states = mint.check_proofs_state(&proofs).await.unwrap().states;
+            assert!(states.iter().all(|state| state.state == expected));
+        }
+    }
+
+    #[cfg(feature = "invariant-checks")]
+    #[tokio::test]
+    #[should_panic(expected = "Mint invariants violated after process_swap_request")]
+    async fn test_invariants_checked_after_swap() {
+        let mut mint = create_mint(HashSet::new()).await;
+        let keyset_id = active_keyset_id(&mint, &CurrencyUnit::Sat).await;
+
+        let spent = mint_proofs(&mint, keyset_id, Amount::from(1)).await;
+        mint.localstore
+            .add_spent_proof(spent[0].clone())
+            .await
+            .unwrap();
+
+        let _ = mint
+            .process_swap_request(SwapRequest::new(
+                mint_proofs(&mint, keyset_id, Amount::from(1)).await,
+                vec![blinded_message(Amount::from(1), keyset_id)],
+            ))
+            .await;
+    }
 }
 
-impl From<MintKeySetInfo> for KeySetInfo {
-    fn from(keyset_info: MintKeySetInfo) -> Self {
-        Self {
-            id: keyset_info.id,
-            unit: keyset_info.unit,
-            active: keyset_info.active,
+#[cfg(bench)]
+mod benches {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+    use std::str::FromStr;
+
+    use test::{black_box, Bencher};
+
+    use super::*;
+    use crate::cdk_database::mint_memory::MintMemoryDatabase;
+    use crate::dhke::{blind_message, construct_proofs};
+
+    const PROOF_COUNT: usize = 500;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// System allocator counting the allocations made on each thread
+    struct CountingAllocator;
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATIONS.with(|count| count.set(count.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// Allocations made on this thread while running `f`
+    fn allocations<T>(f: impl FnOnce() -> T) -> usize {
+        let before = ALLOCATIONS.with(Cell::get);
+        black_box(f());
+        ALLOCATIONS.with(Cell::get) - before
+    }
+
+    const INVOICE: &str = "lnbc2500u1pj48ugqdq8w3jhxaqpp5qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqsp59g4z52329g4z52329g4z52329g4z52329g4z52329g4z52329g4q9qrsgqcqzysd6exwz2yjprkglq820pffp8wcu97sxuzcgx7raensa83dnc4kpmhkuqevwf6hmv6a3mc9aegn89jv7a8e9wggdxtfsjwrur7n6sx4rsq7rvjx3";
+
+    fn keyset_and_proofs() -> (MintKeySet, Proofs) {
+        let mnemonic = Mnemonic::from_str(
+            "half depart obvious quality work element tank gorilla view sugar picture humble",
+        )
+        .unwrap();
+        let keyset = MintKeySet::generate(
+            &mnemonic.to_seed_normalized(""),
+            CurrencyUnit::Sat,
+            &DerivationPath::default(),
+            1,
+        )
+        .unwrap();
+        let keypair = keyset.keys.get(&Amount::from(1)).unwrap();
+
+        let mut signatures = Vec::with_capacity(PROOF_COUNT);
+        let mut rs = Vec::with_capacity(PROOF_COUNT);
+        let mut secrets = Vec::with_capacity(PROOF_COUNT);
+        for _ in 0..PROOF_COUNT {
+            let secret = crate::secret::Secret::generate();
+            let (blinded, r) = blind_message(secret.as_bytes(), None).unwrap();
+            let c = sign_message(&keypair.secret_key, &blinded).unwrap();
+            signatures.push(BlindSignature {
+                amount: Amount::from(1),
+                keyset_id: keyset.id,
+                c,
+                dleq: None,
+            });
+            rs.push(r);
+            secrets.push(secret);
         }
+
+        let proofs =
+            construct_proofs(signatures, rs, secrets, &keyset.keys.clone().into()).unwrap();
+
+        (keyset, proofs)
+    }
+
+    #[cfg(feature = "parallel")]
+    fn bench_threads(bh: &mut Bencher, threads: usize) {
+        let (keyset, proofs) = keyset_and_proofs();
+        let secret_key = &keyset.keys.get(&Amount::from(1)).unwrap().secret_key;
+        let keys = vec![secret_key; proofs.len()];
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+
+        bh.iter(|| {
+            pool.install(|| black_box(crate::signatory::verify_signatures(&proofs, &keys)));
+        });
+    }
+
+    #[cfg(feature = "parallel")]
+    #[bench]
+    pub fn verify_500_proofs_1_thread(bh: &mut Bencher) {
+        bench_threads(bh, 1);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[bench]
+    pub fn verify_500_proofs_all_threads(bh: &mut Bencher) {
+        bench_threads(bh, 0);
+    }
+
+    /// Copies of `proofs` without their cached `Y`s, as they arrive in a
+    /// request
+    fn uncached(proofs: &Proofs) -> Proofs {
+        proofs
+            .iter()
+            .map(|p| Proof::new(p.amount, p.keyset_id, p.secret.clone(), p.c))
+            .collect()
+    }
+
+    /// Swap verification hashing each secret for the duplicate check and
+    /// again for the signature
+    #[bench]
+    pub fn verify_500_swap_inputs_rehashing(bh: &mut Bencher) {
+        let (keyset, proofs) = keyset_and_proofs();
+        let secret_key = &keyset.keys.get(&Amount::from(1)).unwrap().secret_key;
+
+        bh.iter(|| {
+            let proofs = uncached(&proofs);
+            let ys: HashSet<PublicKey> = proofs
+                .iter()
+                .map(|p| hash_to_curve(p.secret.as_bytes()).unwrap())
+                .collect();
+            assert_eq!(ys.len(), PROOF_COUNT);
+            for proof in &proofs {
+                crate::dhke::verify_message(secret_key, proof.c, proof.secret.as_bytes()).unwrap();
+            }
+        });
+    }
+
+    async fn bench_mint() -> Mint {
+        let localstore = MintMemoryDatabase::new(
+            MintInfo::default(),
+            HashMap::new(),
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            HashMap::new(),
+        )
+        .unwrap();
+
+        Mint::new(
+            Arc::new(localstore),
+            Mnemonic::from_str(
+                "half depart obvious quality work element tank gorilla view sugar picture humble",
+            )
+            .unwrap(),
+            HashSet::new(),
+            Amount::from(1),
+            0.01,
+        )
+        .await
+        .unwrap()
+    }
+
+    /// Keyset lookup served from the mint's cache
+    ///
+    /// Checks that a cached lookup does not allocate, while the deep copy out
+    /// of the database that every lookup made before the cache does.
+    #[bench]
+    pub fn keyset_lookup(bh: &mut Bencher) {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let mint = runtime.block_on(bench_mint());
+        let id = runtime
+            .block_on(mint.localstore.get_active_keyset_id(&CurrencyUnit::Sat))
+            .unwrap()
+            .unwrap();
+        runtime.block_on(mint.keyset(&id)).unwrap().unwrap();
+
+        let copied = allocations(|| {
+            runtime.block_on(async {
+                let keyset = mint.localstore.get_keyset(&id).await.unwrap().unwrap();
+                KeySet::from(keyset)
+            })
+        });
+        let shared = allocations(|| runtime.block_on(mint.keyset(&id)).unwrap().unwrap());
+        assert_eq!(shared, 0);
+        assert!(copied > 0);
+
+        bh.iter(|| black_box(runtime.block_on(mint.keyset(&id)).unwrap()));
+    }
+
+    /// Melt of 2500 sat settled for 1 sat of Lightning fee, the rest of the
+    /// fee reserve is returned as change to 12 blank outputs
+    ///
+    /// Each iteration unspends the inputs, reopens the quote and blinds fresh
+    /// outputs, as a spent melt cannot be processed again.
+    #[bench]
+    pub fn process_melt_request_with_change(bh: &mut Bencher) {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let amount = Amount::from(2500);
+
+        let (mut mint, quote, inputs) = runtime.block_on(async {
+            let mint = bench_mint().await;
+
+            let quote = mint
+                .new_melt_quote(
+                    PaymentMethod::Bolt11,
+                    INVOICE.to_string(),
+                    CurrencyUnit::Sat,
+                    amount,
+                    unix_time() + 3600,
+                )
+                .await
+                .unwrap();
+
+            let keyset_id = mint
+                .localstore
+                .get_active_keyset_id(&CurrencyUnit::Sat)
+                .await
+                .unwrap()
+                .unwrap();
+            let pre_mint = PreMintSecrets::random(keyset_id, amount + quote.fee_reserve).unwrap();
+            let mut signatures = Vec::new();
+            for blinded_message in pre_mint.blinded_messages() {
+                signatures.push(mint.blind_sign(&blinded_message).await.unwrap());
+            }
+            let keyset = mint
+                .localstore
+                .get_keyset(&keyset_id)
+                .await
+                .unwrap()
+                .unwrap();
+            let inputs = construct_proofs(
+                signatures,
+                pre_mint.rs(),
+                pre_mint.secrets(),
+                &keyset.keys.into(),
+            )
+            .unwrap();
+
+            (mint, quote, inputs)
+        });
+        let keyset_id = inputs[0].keyset_id;
+        let ys = inputs.ys().unwrap();
+
+        bh.iter(|| {
+            runtime.block_on(async {
+                let outputs = (0..12)
+                    .map(|_| {
+                        let secret = crate::secret::Secret::generate();
+                        let (blinded_secret, _r) = blind_message(secret.as_bytes(), None).unwrap();
+                        BlindedMessage::new(Amount::ZERO, keyset_id, blinded_secret)
+                    })
+                    .collect();
+                let melt_request =
+                    MeltBolt11Request::new(quote.id.clone(), inputs.clone(), Some(outputs));
+
+                let response = mint
+                    .process_melt_request(&melt_request, "preimage", amount + Amount::from(1))
+                    .await
+                    .unwrap();
+                black_box(response);
+
+                mint.localstore.remove_spent_proofs(&ys).await.unwrap();
+                mint.localstore.add_melt_quote(quote.clone()).await.unwrap();
+            })
+        });
+    }
+
+    /// Swap verification computing the `Y`s once in a batch
+    #[bench]
+    pub fn verify_500_swap_inputs(bh: &mut Bencher) {
+        let (keyset, proofs) = keyset_and_proofs();
+        let secret_key = &keyset.keys.get(&Amount::from(1)).unwrap().secret_key;
+        let keys = vec![secret_key; proofs.len()];
+
+        bh.iter(|| {
+            let proofs = uncached(&proofs);
+            let ys: HashSet<PublicKey> = proofs.ys().unwrap().into_iter().collect();
+            assert_eq!(ys.len(), PROOF_COUNT);
+            black_box(crate::signatory::verify_signatures(&proofs, &keys));
+        });
     }
 }
@@ -0,0 +1,716 @@
+//! Kotlin and Swift interface through uniffi
+//!
+//! A curated surface for mobile wallets: tokens, outputs and their
+//! unblinding, DLEQ and P2PK operations on proofs, and the client of a mint.
+//! Nothing here holds or derives the private keys of a mint.
+//!
+//! The bindings are generated from the built library:
+//!
+//! ```sh
+//! cargo rustc -p cdk --no-default-features --features uniffi --crate-type cdylib
+//! uniffi-bindgen generate --library target/debug/libcdk.so --language kotlin --out-dir out
+//! ```
+//!
+//! Keys, secrets and ids cross the interface as hex strings, amounts as
+//! `u64` and protocol messages without a type of their own as JSON. Objects
+//! are immutable, operations that change a proof return a new one.
+
+use std::str::FromStr;
+use std::sync::Arc;
+
+use url::Url;
+
+use crate::dhke::construct_proofs;
+use crate::nuts::{self, CurrencyUnit, Id, SecretKey, SigningKey, SwapRequest};
+use crate::url::UncheckedUrl;
+use crate::{Amount, HttpClient};
+
+/// Every error of the interface, as seen from Kotlin and Swift
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+#[uniffi(flat_error)]
+pub enum FfiError {
+    #[error("Invalid token: {0}")]
+    InvalidToken(String),
+    /// A key, id, secret or url could not be parsed
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+    /// A JSON argument is not the protocol message it should be
+    #[error("Invalid JSON: {0}")]
+    InvalidJson(String),
+    #[error("Invalid amount: {0}")]
+    InvalidAmount(String),
+    /// A proof has no DLEQ proof, or it does not verify
+    #[error("DLEQ verification failed: {0}")]
+    Dleq(String),
+    /// Blinding, unblinding or signing failed
+    #[error("Crypto error: {0}")]
+    Crypto(String),
+    /// The mint could not be reached or refused the request
+    #[error("Mint request failed: {0}")]
+    Client(String),
+}
+
+fn invalid_argument(err: impl ToString) -> FfiError {
+    FfiError::InvalidArgument(err.to_string())
+}
+
+fn invalid_json(err: impl ToString) -> FfiError {
+    FfiError::InvalidJson(err.to_string())
+}
+
+fn crypto(err: impl ToString) -> FfiError {
+    FfiError::Crypto(err.to_string())
+}
+
+fn client(err: impl ToString) -> FfiError {
+    FfiError::Client(err.to_string())
+}
+
+fn mint_url(mint_url: &str) -> Result<Url, FfiError> {
+    Url::parse(mint_url).map_err(invalid_argument)
+}
+
+/// Powers of two `amount` is split into, largest first
+#[uniffi::export]
+pub fn amount_split(amount: u64) -> Vec<u64> {
+    Amount::from(amount)
+        .split()
+        .into_iter()
+        .map(u64::from)
+        .collect()
+}
+
+/// Proof of a mint, immutable
+#[derive(Debug, uniffi::Object)]
+pub struct Proof {
+    inner: nuts::Proof,
+}
+
+impl From<nuts::Proof> for Proof {
+    fn from(inner: nuts::Proof) -> Self {
+        Self { inner }
+    }
+}
+
+fn to_proofs(proofs: &[Arc<Proof>]) -> nuts::Proofs {
+    proofs.iter().map(|proof| proof.inner.clone()).collect()
+}
+
+fn from_proofs(proofs: nuts::Proofs) -> Vec<Arc<Proof>> {
+    proofs
+        .into_iter()
+        .map(|proof| Arc::new(proof.into()))
+        .collect()
+}
+
+#[uniffi::export]
+impl Proof {
+    /// Proof of its NUT-00 JSON form
+    #[uniffi::constructor]
+    pub fn from_json(json: String) -> Result<Arc<Self>, FfiError> {
+        let inner = serde_json::from_str(&json).map_err(invalid_json)?;
+        Ok(Arc::new(Self { inner }))
+    }
+
+    pub fn to_json(&self) -> Result<String, FfiError> {
+        serde_json::to_string(&self.inner).map_err(invalid_json)
+    }
+
+    pub fn amount(&self) -> u64 {
+        self.inner.amount.into()
+    }
+
+    pub fn keyset_id(&self) -> String {
+        self.inner.keyset_id.to_string()
+    }
+
+    pub fn secret(&self) -> String {
+        self.inner.secret.to_string()
+    }
+
+    /// `Y` of the proof, which the mint tracks its state by
+    pub fn y(&self) -> Result<String, FfiError> {
+        Ok(self.inner.y().map_err(crypto)?.to_hex())
+    }
+
+    /// Verify the DLEQ proof against the key of the proof amount in
+    /// `keyset`
+    pub fn verify_dleq(&self, keyset: Arc<KeySet>) -> Result<(), FfiError> {
+        if keyset.inner.id != self.inner.keyset_id {
+            return Err(FfiError::Dleq(format!(
+                "Proof of keyset {} checked against keyset {}",
+                self.inner.keyset_id, keyset.inner.id
+            )));
+        }
+        let mint_pubkey = keyset
+            .inner
+            .keys
+            .amount_key(self.inner.amount)
+            .ok_or_else(|| {
+                FfiError::Dleq(format!(
+                    "No key for amount {} in keyset {}",
+                    self.inner.amount, keyset.inner.id
+                ))
+            })?;
+
+        self.inner
+            .verify_dleq(mint_pubkey)
+            .map_err(|err| FfiError::Dleq(err.to_string()))
+    }
+
+    /// Copy of the proof with a [NUT-11] signature of the hex
+    /// `secret_key` added to its witness
+    pub fn sign_p2pk(&self, secret_key: String) -> Result<Arc<Self>, FfiError> {
+        let secret_key = SecretKey::from_hex(secret_key).map_err(invalid_argument)?;
+        let mut inner = self.inner.clone();
+        inner
+            .sign_p2pk(SigningKey::new(secret_key))
+            .map_err(crypto)?;
+        Ok(Arc::new(Self { inner }))
+    }
+}
+
+/// Public keys of a keyset of a mint
+#[derive(Debug, uniffi::Object)]
+pub struct KeySet {
+    inner: nuts::KeySet,
+}
+
+#[uniffi::export]
+impl KeySet {
+    /// Keyset of its NUT-01 JSON form
+    #[uniffi::constructor]
+    pub fn from_json(json: String) -> Result<Arc<Self>, FfiError> {
+        let inner = serde_json::from_str(&json).map_err(invalid_json)?;
+        Ok(Arc::new(Self { inner }))
+    }
+
+    pub fn to_json(&self) -> Result<String, FfiError> {
+        serde_json::to_string(&self.inner).map_err(invalid_json)
+    }
+
+    pub fn id(&self) -> String {
+        self.inner.id.to_string()
+    }
+
+    pub fn unit(&self) -> String {
+        self.inner.unit.to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct TokenSummary {
+    pub mints: Vec<String>,
+    pub unit: String,
+    /// Total amount, saturating at the max of `u64`
+    pub amount: u64,
+    pub proof_count: u64,
+    /// Keysets in order of first use
+    pub keyset_ids: Vec<String>,
+    pub memo: Option<String>,
+}
+
+impl From<nuts::TokenSummary> for TokenSummary {
+    fn from(summary: nuts::TokenSummary) -> Self {
+        Self {
+            mints: summary.mints.iter().map(|m| m.to_string()).collect(),
+            unit: summary.unit.to_string(),
+            amount: summary.amount.into(),
+            proof_count: summary.proof_count as u64,
+            keyset_ids: summary.keyset_ids.iter().map(|id| id.to_string()).collect(),
+            memo: summary.memo,
+        }
+    }
+}
+
+/// Cashu token, immutable
+#[derive(Debug, uniffi::Object)]
+pub struct Token {
+    inner: nuts::Token,
+}
+
+#[uniffi::export]
+impl Token {
+    /// Token of `proofs` of the mint at `mint_url`
+    #[uniffi::constructor]
+    pub fn new(
+        mint_url: String,
+        proofs: Vec<Arc<Proof>>,
+        memo: Option<String>,
+        unit: Option<String>,
+    ) -> Result<Arc<Self>, FfiError> {
+        let unit = unit
+            .map(|unit| CurrencyUnit::from_str(&unit))
+            .transpose()
+            .map_err(invalid_argument)?;
+        let inner = nuts::Token::new(UncheckedUrl::from(mint_url), to_proofs(&proofs), memo, unit)
+            .map_err(|err| FfiError::InvalidToken(err.to_string()))?;
+        Ok(Arc::new(Self { inner }))
+    }
+
+    /// Token of any encoding the crate reads
+    #[uniffi::constructor]
+    pub fn decode(token: String) -> Result<Arc<Self>, FfiError> {
+        let inner =
+            nuts::Token::from_str(&token).map_err(|err| FfiError::InvalidToken(err.to_string()))?;
+        Ok(Arc::new(Self { inner }))
+    }
+
+    pub fn encode(&self) -> String {
+        self.inner.to_string()
+    }
+
+    pub fn summary(&self) -> TokenSummary {
+        self.inner.summary().into()
+    }
+
+    /// Total amount of the proofs
+    pub fn value(&self) -> Result<u64, FfiError> {
+        let value = self
+            .inner
+            .value()
+            .map_err(|err| FfiError::InvalidAmount(err.to_string()))?;
+        Ok(value.into())
+    }
+
+    /// Proofs of the token, of every mint
+    pub fn proofs(&self) -> Vec<Arc<Proof>> {
+        from_proofs(
+            self.inner
+                .token
+                .iter()
+                .flat_map(|mint_proofs| mint_proofs.proofs.clone())
+                .collect(),
+        )
+    }
+}
+
+/// Blinded outputs for a mint to sign, with the secrets and blinding
+/// factors to unblind its signatures
+#[derive(Debug, uniffi::Object)]
+pub struct PreMintSecrets {
+    inner: nuts::PreMintSecrets,
+}
+
+#[uniffi::export]
+impl PreMintSecrets {
+    /// Outputs of random secrets for `amount` in the keyset with the hex
+    /// `keyset_id`
+    #[uniffi::constructor]
+    pub fn random(keyset_id: String, amount: u64) -> Result<Arc<Self>, FfiError> {
+        let keyset_id = Id::from_str(&keyset_id).map_err(invalid_argument)?;
+        let inner =
+            nuts::PreMintSecrets::random(keyset_id, Amount::from(amount)).map_err(crypto)?;
+        Ok(Arc::new(Self { inner }))
+    }
+
+    pub fn amounts(&self) -> Vec<u64> {
+        self.inner.amounts().into_iter().map(u64::from).collect()
+    }
+
+    /// NUT-00 JSON array of the blinded messages
+    pub fn blinded_messages_json(&self) -> Result<String, FfiError> {
+        serde_json::to_string(&self.inner.blinded_messages()).map_err(invalid_json)
+    }
+
+    /// Proofs of the NUT-00 JSON array of blind signatures of the
+    /// outputs, in their order, by `keyset`
+    pub fn unblind(
+        &self,
+        signatures_json: String,
+        keyset: Arc<KeySet>,
+    ) -> Result<Vec<Arc<Proof>>, FfiError> {
+        let signatures: Vec<nuts::BlindSignature> =
+            serde_json::from_str(&signatures_json).map_err(invalid_json)?;
+        let proofs = construct_proofs(
+            signatures,
+            self.inner.rs(),
+            self.inner.secrets(),
+            &keyset.inner.keys,
+        )
+        .map_err(crypto)?;
+        Ok(from_proofs(proofs))
+    }
+}
+
+/// State of a proof at its mint [NUT-07]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum ProofState {
+    Spent,
+    Unspent,
+    Pending,
+}
+
+impl From<nuts::State> for ProofState {
+    fn from(state: nuts::State) -> Self {
+        match state {
+            nuts::State::Spent => Self::Spent,
+            nuts::State::Unspent => Self::Unspent,
+            nuts::State::Pending => Self::Pending,
+        }
+    }
+}
+
+/// Client of mints over HTTP
+#[derive(Debug, uniffi::Object)]
+pub struct Client {
+    inner: HttpClient,
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl Client {
+    #[uniffi::constructor]
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            inner: HttpClient::new(),
+        })
+    }
+
+    /// NUT-06 JSON info of the mint
+    pub async fn get_mint_info(&self, mint_url: String) -> Result<String, FfiError> {
+        let info = self
+            .inner
+            .get_mint_info(self::mint_url(&mint_url)?)
+            .await
+            .map_err(client)?;
+        serde_json::to_string(&info).map_err(invalid_json)
+    }
+
+    /// Active keysets of the mint
+    pub async fn get_mint_keys(&self, mint_url: String) -> Result<Vec<Arc<KeySet>>, FfiError> {
+        let keysets = self
+            .inner
+            .get_mint_keys(self::mint_url(&mint_url)?)
+            .await
+            .map_err(client)?;
+        Ok(keysets
+            .into_iter()
+            .map(|inner| Arc::new(KeySet { inner }))
+            .collect())
+    }
+
+    /// Keyset of the mint with the hex `keyset_id`
+    pub async fn get_mint_keyset(
+        &self,
+        mint_url: String,
+        keyset_id: String,
+    ) -> Result<Arc<KeySet>, FfiError> {
+        let keyset_id = Id::from_str(&keyset_id).map_err(invalid_argument)?;
+        let inner = self
+            .inner
+            .get_mint_keyset(self::mint_url(&mint_url)?, keyset_id)
+            .await
+            .map_err(client)?;
+        Ok(Arc::new(KeySet { inner }))
+    }
+
+    /// Swap `inputs` for `outputs`, returning the proofs of `outputs`
+    /// unblinded by `keyset`
+    pub async fn swap(
+        &self,
+        mint_url: String,
+        inputs: Vec<Arc<Proof>>,
+        outputs: Arc<PreMintSecrets>,
+        keyset: Arc<KeySet>,
+    ) -> Result<Vec<Arc<Proof>>, FfiError> {
+        let request = SwapRequest::new(to_proofs(&inputs), outputs.inner.blinded_messages());
+        let response = self
+            .inner
+            .post_swap(self::mint_url(&mint_url)?, request)
+            .await
+            .map_err(client)?;
+        let proofs = construct_proofs(
+            response.signatures,
+            outputs.inner.rs(),
+            outputs.inner.secrets(),
+            &keyset.inner.keys,
+        )
+        .map_err(crypto)?;
+        Ok(from_proofs(proofs))
+    }
+
+    /// State of each of `proofs`, in their order
+    pub async fn check_state(
+        &self,
+        mint_url: String,
+        proofs: Vec<Arc<Proof>>,
+    ) -> Result<Vec<ProofState>, FfiError> {
+        let ys = proofs
+            .iter()
+            .map(|proof| proof.inner.y())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(crypto)?;
+        let response = self
+            .inner
+            .post_check_state(self::mint_url(&mint_url)?, ys)
+            .await
+            .map_err(client)?;
+        Ok(response
+            .states
+            .into_iter()
+            .map(|state| state.state.into())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dhke::sign_message;
+
+    const KEYSET_ID: &str = "00882760bfa2eb41";
+
+    /// Mint key 1 for every amount, so its public key is the generator
+    fn mint_key() -> SecretKey {
+        SecretKey::from_hex("0000000000000000000000000000000000000000000000000000000000000001")
+            .unwrap()
+    }
+
+    fn keyset() -> Arc<KeySet> {
+        let pubkey = mint_key().public_key().to_hex();
+        let keys: Vec<String> = [1, 2, 4, 8]
+            .iter()
+            .map(|amount| format!(r#""{amount}":"{pubkey}""#))
+            .collect();
+        KeySet::from_json(format!(
+            r#"{{"id":"{KEYSET_ID}","unit":"sat","keys":{{{}}}}}"#,
+            keys.join(",")
+        ))
+        .unwrap()
+    }
+
+    /// Proofs of 13 sat, signed by [mint_key] with DLEQ proofs
+    fn proofs() -> Vec<Arc<Proof>> {
+        let outputs = PreMintSecrets::random(KEYSET_ID.to_string(), 13).unwrap();
+        assert_eq!(outputs.amounts(), amount_split(13));
+
+        let blinded_messages: Vec<nuts::BlindedMessage> =
+            serde_json::from_str(&outputs.blinded_messages_json().unwrap()).unwrap();
+        let signatures: Vec<nuts::BlindSignature> = blinded_messages
+            .iter()
+            .map(|output| {
+                let c = sign_message(&mint_key(), &output.blinded_secret).unwrap();
+                nuts::BlindSignature::new(
+                    output.amount,
+                    c,
+                    output.keyset_id,
+                    &output.blinded_secret,
+                    &mint_key(),
+                )
+                .unwrap()
+            })
+            .collect();
+
+        outputs
+            .unblind(serde_json::to_string(&signatures).unwrap(), keyset())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_amount_split() {
+        assert_eq!(amount_split(13), vec![8, 4, 1]);
+        assert!(amount_split(0).is_empty());
+    }
+
+    #[test]
+    fn test_unblind_and_verify_dleq() {
+        let proofs = proofs();
+        assert_eq!(
+            proofs
+                .iter()
+                .map(|proof| proof.amount())
+                .collect::<Vec<_>>(),
+            vec![8, 4, 1]
+        );
+        for proof in &proofs {
+            proof.verify_dleq(keyset()).unwrap();
+            assert_eq!(proof.keyset_id(), KEYSET_ID);
+            let json = Proof::from_json(proof.to_json().unwrap()).unwrap();
+            assert_eq!(json.inner, proof.inner);
+        }
+
+        // Another mint key does not verify
+        let other = SecretKey::generate().public_key().to_hex();
+        let other = KeySet::from_json(format!(
+            r#"{{"id":"{KEYSET_ID}","unit":"sat","keys":{{"8":"{other}"}}}}"#
+        ))
+        .unwrap();
+        assert!(matches!(
+            proofs[0].verify_dleq(Arc::clone(&other)),
+            Err(FfiError::Dleq(_))
+        ));
+        assert!(matches!(
+            proofs[1].verify_dleq(other),
+            Err(FfiError::Dleq(message)) if message.contains("No key for amount 4")
+        ));
+    }
+
+    #[test]
+    fn test_token_round_trip() {
+        let proofs = proofs();
+        let token = Token::new(
+            "https://mint.example.com".to_string(),
+            proofs.clone(),
+            Some("thanks".to_string()),
+            Some("sat".to_string()),
+        )
+        .unwrap();
+
+        let decoded = Token::decode(token.encode()).unwrap();
+        assert_eq!(decoded.value().unwrap(), 13);
+        assert_eq!(
+            decoded.summary(),
+            TokenSummary {
+                mints: vec!["https://mint.example.com".to_string()],
+                unit: "sat".to_string(),
+                amount: 13,
+                proof_count: 3,
+                keyset_ids: vec![KEYSET_ID.to_string()],
+                memo: Some("thanks".to_string()),
+            }
+        );
+        let secrets = |proofs: &[Arc<Proof>]| -> Vec<String> {
+            proofs.iter().map(|proof| proof.secret()).collect()
+        };
+        assert_eq!(secrets(&decoded.proofs()), secrets(&proofs));
+    }
+
+    #[test]
+    fn test_sign_p2pk() {
+        let proof = &proofs()[0];
+        let key = SecretKey::generate();
+
+        let signed = proof.sign_p2pk(key.to_secret_hex()).unwrap();
+        assert!(proof.inner.witness.is_none());
+        let signatures = signed.inner.witness.as_ref().unwrap().signatures().unwrap();
+        assert_eq!(signatures.len(), 1);
+        assert_eq!(signed.y().unwrap(), proof.y().unwrap());
+
+        assert!(matches!(
+            proof.sign_p2pk("not a key".to_string()),
+            Err(FfiError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(
+            Token::decode("cashuAnope".to_string()),
+            Err(FfiError::InvalidToken(_))
+        ));
+        assert!(matches!(
+            PreMintSecrets::random("zz".to_string(), 1),
+            Err(FfiError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            Token::new(
+                "https://mint.example.com".to_string(),
+                vec![],
+                None,
+                Some("sat".to_string())
+            ),
+            Err(FfiError::InvalidToken(_))
+        ));
+        let outputs = PreMintSecrets::random(KEYSET_ID.to_string(), 1).unwrap();
+        assert!(matches!(
+            outputs.unblind("{}".to_string(), keyset()),
+            Err(FfiError::InvalidJson(_))
+        ));
+        assert!(matches!(
+            KeySet::from_json("[]".to_string()),
+            Err(FfiError::InvalidJson(_))
+        ));
+    }
+
+    /// Every item of the interface is in the scaffolding the bindings are
+    /// generated from, so removing or renaming one fails to link here
+    /// before it breaks a foreign binding
+    #[test]
+    fn test_scaffolding_exports_interface() {
+        extern "C" {
+            fn uniffi_cdk_checksum_func_amount_split() -> u16;
+            fn uniffi_cdk_checksum_constructor_proof_from_json() -> u16;
+            fn uniffi_cdk_checksum_method_proof_to_json() -> u16;
+            fn uniffi_cdk_checksum_method_proof_amount() -> u16;
+            fn uniffi_cdk_checksum_method_proof_keyset_id() -> u16;
+            fn uniffi_cdk_checksum_method_proof_secret() -> u16;
+            fn uniffi_cdk_checksum_method_proof_y() -> u16;
+            fn uniffi_cdk_checksum_method_proof_verify_dleq() -> u16;
+            fn uniffi_cdk_checksum_method_proof_sign_p2pk() -> u16;
+            fn uniffi_cdk_checksum_constructor_keyset_from_json() -> u16;
+            fn uniffi_cdk_checksum_method_keyset_to_json() -> u16;
+            fn uniffi_cdk_checksum_method_keyset_id() -> u16;
+            fn uniffi_cdk_checksum_method_keyset_unit() -> u16;
+            fn uniffi_cdk_checksum_constructor_token_new() -> u16;
+            fn uniffi_cdk_checksum_constructor_token_decode() -> u16;
+            fn uniffi_cdk_checksum_method_token_encode() -> u16;
+            fn uniffi_cdk_checksum_method_token_summary() -> u16;
+            fn uniffi_cdk_checksum_method_token_value() -> u16;
+            fn uniffi_cdk_checksum_method_token_proofs() -> u16;
+            fn uniffi_cdk_checksum_constructor_premintsecrets_random() -> u16;
+            fn uniffi_cdk_checksum_method_premintsecrets_amounts() -> u16;
+            fn uniffi_cdk_checksum_method_premintsecrets_blinded_messages_json() -> u16;
+            fn uniffi_cdk_checksum_method_premintsecrets_unblind() -> u16;
+            fn uniffi_cdk_checksum_constructor_client_new() -> u16;
+            fn uniffi_cdk_checksum_method_client_get_mint_info() -> u16;
+            fn uniffi_cdk_checksum_method_client_get_mint_keys() -> u16;
+            fn uniffi_cdk_checksum_method_client_get_mint_keyset() -> u16;
+            fn uniffi_cdk_checksum_method_client_swap() -> u16;
+            fn uniffi_cdk_checksum_method_client_check_state() -> u16;
+        }
+
+        let checksums = unsafe {
+            [
+                uniffi_cdk_checksum_func_amount_split(),
+                uniffi_cdk_checksum_constructor_proof_from_json(),
+                uniffi_cdk_checksum_method_proof_to_json(),
+                uniffi_cdk_checksum_method_proof_amount(),
+                uniffi_cdk_checksum_method_proof_keyset_id(),
+                uniffi_cdk_checksum_method_proof_secret(),
+                uniffi_cdk_checksum_method_proof_y(),
+                uniffi_cdk_checksum_method_proof_verify_dleq(),
+                uniffi_cdk_checksum_method_proof_sign_p2pk(),
+                uniffi_cdk_checksum_constructor_keyset_from_json(),
+                uniffi_cdk_checksum_method_keyset_to_json(),
+                uniffi_cdk_checksum_method_keyset_id(),
+                uniffi_cdk_checksum_method_keyset_unit(),
+                uniffi_cdk_checksum_constructor_token_new(),
+                uniffi_cdk_checksum_constructor_token_decode(),
+                uniffi_cdk_checksum_method_token_encode(),
+                uniffi_cdk_checksum_method_token_summary(),
+                uniffi_cdk_checksum_method_token_value(),
+                uniffi_cdk_checksum_method_token_proofs(),
+                uniffi_cdk_checksum_constructor_premintsecrets_random(),
+                uniffi_cdk_checksum_method_premintsecrets_amounts(),
+                uniffi_cdk_checksum_method_premintsecrets_blinded_messages_json(),
+                uniffi_cdk_checksum_method_premintsecrets_unblind(),
+                uniffi_cdk_checksum_constructor_client_new(),
+                uniffi_cdk_checksum_method_client_get_mint_info(),
+                uniffi_cdk_checksum_method_client_get_mint_keys(),
+                uniffi_cdk_checksum_method_client_get_mint_keyset(),
+                uniffi_cdk_checksum_method_client_swap(),
+                uniffi_cdk_checksum_method_client_check_state(),
+            ]
+        };
+        assert_eq!(checksums.len(), 29);
+    }
+
+    #[tokio::test]
+    async fn test_client_errors() {
+        let client = Client::new();
+        assert!(matches!(
+            client.get_mint_info("not a url".to_string()).await,
+            Err(FfiError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            client
+                .get_mint_keyset("http://127.0.0.1:1".to_string(), "zz".to_string())
+                .await,
+            Err(FfiError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            client.get_mint_keys("http://127.0.0.1:1".to_string()).await,
+            Err(FfiError::Client(_))
+        ));
+    }
+}
@@ -0,0 +1,595 @@
+//! Cashu tokens over Nostr direct messages
+//!
+//! Messages are kind 4 events encrypted with
+//! [NIP-44](https://github.com/nostr-protocol/nips/blob/master/44.md) v2.
+//! NIP-04 payloads are not supported. Relays are reached through
+//! [NostrRelay], this crate does not ship a websocket client.
+
+use async_trait::async_trait;
+use base64::engine::general_purpose;
+use base64::Engine as _;
+use bitcoin::bech32::{self, FromBase32, ToBase32, Variant};
+use bitcoin::hashes::hmac::{Hmac, HmacEngine};
+use bitcoin::hashes::sha256::Hash as Sha256Hash;
+use bitcoin::hashes::{Hash, HashEngine};
+use bitcoin::secp256k1::rand::{self, RngCore};
+use bitcoin::secp256k1::schnorr::Signature;
+use bitcoin::secp256k1::{ecdh, KeyPair, Message, XOnlyPublicKey};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::nuts::{SigningKey, VerifyingKey};
+use crate::util::hex;
+use crate::SECP256K1;
+
+/// Kind of encrypted direct message events
+pub const KIND_DIRECT_MESSAGE: u64 = 4;
+
+/// NIP-44 payload version
+const NIP44_VERSION: u8 = 2;
+/// Longest NIP-44 plaintext
+const MAX_PLAINTEXT_LEN: usize = 65535;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// Not a bech32 `npub`
+    #[error("Invalid npub")]
+    InvalidNpub,
+    /// Bech32 error
+    #[error(transparent)]
+    Bech32(#[from] bech32::Error),
+    /// NUT11 error
+    #[error(transparent)]
+    NUT11(#[from] crate::nuts::nut11::Error),
+    /// Secp256k1 error
+    #[error(transparent)]
+    Secp256k1(#[from] bitcoin::secp256k1::Error),
+    /// Hex error
+    #[error(transparent)]
+    HexError(#[from] hex::Error),
+    /// Base64 error
+    #[error(transparent)]
+    Base64Error(#[from] base64::DecodeError),
+    /// Utf8 error
+    #[error(transparent)]
+    Utf8(#[from] std::string::FromUtf8Error),
+    /// Payload is not NIP-44 v2
+    #[error("Unsupported encryption, only NIP-44 v2 is supported")]
+    UnsupportedEncryption,
+    /// Plaintext or payload too short or too long
+    #[error("Invalid message length")]
+    InvalidLength,
+    /// Decrypted padding does not match the plaintext length
+    #[error("Invalid padding")]
+    InvalidPadding,
+    /// Payload was not encrypted for this conversation or was tampered with
+    #[error("Invalid MAC")]
+    InvalidMac,
+    /// Event id is not the hash of the event
+    #[error("Invalid event id")]
+    InvalidEventId,
+    /// Event is not addressed to the key decrypting it
+    #[error("Event is not addressed to `{0}`")]
+    NotRecipient(String),
+    /// Error of a [NostrRelay]
+    #[error("Relay `{0}`")]
+    Relay(String),
+    /// No relay to publish to or fetch from
+    #[error("No relays given")]
+    NoRelays,
+}
+
+/// Relay events are published to and fetched from
+#[async_trait]
+pub trait NostrRelay: Send + Sync {
+    /// Url of the relay, used in reports
+    fn url(&self) -> &str;
+
+    /// Publish `event`, [Ok] once the relay accepted it
+    async fn publish(&self, event: &Event) -> Result<(), Error>;
+
+    /// Stored events matching `filter`
+    async fn fetch(&self, filter: &Filter) -> Result<Vec<Event>, Error>;
+}
+
+/// Signed Nostr event [NIP-01]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+    /// Hex sha256 of the serialized event
+    pub id: String,
+    /// Hex x-only key of the author
+    pub pubkey: String,
+    pub created_at: u64,
+    pub kind: u64,
+    pub tags: Vec<Vec<String>>,
+    pub content: String,
+    /// Hex schnorr signature of [Event::id]
+    pub sig: String,
+}
+
+impl Event {
+    /// Direct message to `recipient` with `plaintext` encrypted [NIP-44]
+    pub fn direct_message(
+        keys: &SigningKey,
+        recipient: &VerifyingKey,
+        plaintext: &str,
+        created_at: u64,
+    ) -> Result<Self, Error> {
+        let content = encrypt(keys, recipient, plaintext)?;
+        let tags = vec![vec!["p".to_string(), recipient.to_string()]];
+        Self::sign(keys, created_at, KIND_DIRECT_MESSAGE, tags, content)
+    }
+
+    /// Event by `keys`
+    pub fn sign(
+        keys: &SigningKey,
+        created_at: u64,
+        kind: u64,
+        tags: Vec<Vec<String>>,
+        content: String,
+    ) -> Result<Self, Error> {
+        let pubkey = keys.verifying_key().to_string();
+        let id = event_hash(&pubkey, created_at, kind, &tags, &content);
+        let key_pair = KeyPair::from_secret_key(&SECP256K1, keys);
+        let msg = Message::from_slice(id.as_ref())?;
+        let sig = SECP256K1.sign_schnorr(&msg, &key_pair);
+
+        Ok(Self {
+            id: hex::encode(id),
+            pubkey,
+            created_at,
+            kind,
+            tags,
+            content,
+            sig: sig.to_string(),
+        })
+    }
+
+    /// Check that the id is the hash of the event and it is signed by
+    /// [Event::pubkey]
+    pub fn verify(&self) -> Result<(), Error> {
+        let id = event_hash(
+            &self.pubkey,
+            self.created_at,
+            self.kind,
+            &self.tags,
+            &self.content,
+        );
+        if hex::encode(id) != self.id {
+            return Err(Error::InvalidEventId);
+        }
+
+        let pubkey: XOnlyPublicKey = self.pubkey.parse()?;
+        let sig: Signature = self.sig.parse()?;
+        let msg = Message::from_slice(id.as_ref())?;
+        SECP256K1.verify_schnorr(&sig, &msg, &pubkey)?;
+
+        Ok(())
+    }
+
+    /// Hex keys of the `p` tags
+    pub fn recipients(&self) -> impl Iterator<Item = &str> {
+        self.tags
+            .iter()
+            .filter(|tag| tag.first().map(String::as_str) == Some("p"))
+            .filter_map(|tag| tag.get(1).map(String::as_str))
+    }
+
+    /// Decrypt the content of a direct message to `keys`
+    pub fn decrypt(&self, keys: &SigningKey) -> Result<String, Error> {
+        let own = keys.verifying_key().to_string();
+        if !self.recipients().any(|recipient| recipient == own) {
+            return Err(Error::NotRecipient(own));
+        }
+
+        let sender = VerifyingKey::from_bytes(&hex::decode(&self.pubkey)?)?;
+        decrypt(keys, &sender, &self.content)
+    }
+}
+
+/// Hash of the event serialization [NIP-01]
+fn event_hash(
+    pubkey: &str,
+    created_at: u64,
+    kind: u64,
+    tags: &[Vec<String>],
+    content: &str,
+) -> Sha256Hash {
+    let serialized = serde_json::json!([0, pubkey, created_at, kind, tags, content]);
+    Sha256Hash::hash(serialized.to_string().as_bytes())
+}
+
+/// Events a [NostrRelay] is asked for
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Filter {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub kinds: Vec<u64>,
+    /// Hex keys of the `p` tags
+    #[serde(rename = "#p", default, skip_serializing_if = "Vec::is_empty")]
+    pub p: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since: Option<u64>,
+}
+
+impl Filter {
+    /// Direct messages to `recipient` created at or after `since`
+    pub fn direct_messages(recipient: &VerifyingKey, since: Option<u64>) -> Self {
+        Self {
+            kinds: vec![KIND_DIRECT_MESSAGE],
+            p: vec![recipient.to_string()],
+            since,
+        }
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        (self.kinds.is_empty() || self.kinds.contains(&event.kind))
+            && (self.p.is_empty()
+                || event
+                    .recipients()
+                    .any(|recipient| self.p.iter().any(|p| p == recipient)))
+            && self.since.map_or(true, |since| event.created_at >= since)
+    }
+}
+
+/// Key of a bech32 `npub` [NIP-19]
+pub fn parse_npub(npub: &str) -> Result<VerifyingKey, Error> {
+    let (hrp, data, variant) = bech32::decode(npub)?;
+    if hrp != "npub" || variant != Variant::Bech32 {
+        return Err(Error::InvalidNpub);
+    }
+
+    let bytes = Vec::<u8>::from_base32(&data)?;
+    if bytes.len() != 32 {
+        return Err(Error::InvalidNpub);
+    }
+
+    Ok(VerifyingKey::from_bytes(&bytes)?)
+}
+
+/// Bech32 `npub` of `key` [NIP-19]
+pub fn to_npub(key: &VerifyingKey) -> String {
+    let bytes = key.clone().to_normalized_public_key().to_bytes();
+    bech32::encode("npub", (&bytes[1..]).to_base32(), Variant::Bech32).expect("npub is a valid hrp")
+}
+
+/// Tokens in `text`, words starting with `cashuA` or `cashuB`
+pub fn find_tokens(text: &str) -> Vec<&str> {
+    text.split_whitespace()
+        .map(|word| word.strip_prefix("cashu:").unwrap_or(word))
+        .map(|word| {
+            word.trim_end_matches(|c: char| !(c.is_ascii_alphanumeric() || "-_=".contains(c)))
+        })
+        .filter(|word| word.starts_with("cashuA") || word.starts_with("cashuB"))
+        .collect()
+}
+
+/// Key shared by `keys` and `other` [NIP-44]
+pub fn conversation_key(keys: &SigningKey, other: &VerifyingKey) -> [u8; 32] {
+    let other = other.clone().to_normalized_public_key();
+    let point = ecdh::shared_secret_point(&other, keys);
+    hmac_sha256(b"nip44-v2", &point[..32])
+}
+
+/// Encrypt `plaintext` from `keys` to `recipient` [NIP-44]
+pub fn encrypt(
+    keys: &SigningKey,
+    recipient: &VerifyingKey,
+    plaintext: &str,
+) -> Result<String, Error> {
+    let mut nonce = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    encrypt_with_nonce(&conversation_key(keys, recipient), &nonce, plaintext)
+}
+
+/// Decrypt `payload` sent from `sender` to `keys` [NIP-44]
+pub fn decrypt(keys: &SigningKey, sender: &VerifyingKey, payload: &str) -> Result<String, Error> {
+    decrypt_with_key(&conversation_key(keys, sender), payload)
+}
+
+fn encrypt_with_nonce(
+    conversation_key: &[u8; 32],
+    nonce: &[u8; 32],
+    plaintext: &str,
+) -> Result<String, Error> {
+    let (chacha_key, chacha_nonce, hmac_key) = message_keys(conversation_key, nonce);
+
+    let mut buffer = pad(plaintext.as_bytes())?;
+    chacha20(&chacha_key, &chacha_nonce, 0, &mut buffer);
+    let mac = hmac_sha256(&hmac_key, &[&nonce[..], &buffer].concat());
+
+    let payload = [&[NIP44_VERSION][..], nonce, &buffer, &mac].concat();
+    Ok(general_purpose::STANDARD.encode(payload))
+}
+
+fn decrypt_with_key(conversation_key: &[u8; 32], payload: &str) -> Result<String, Error> {
+    if payload.starts_with('#') || payload.contains("?iv=") {
+        return Err(Error::UnsupportedEncryption);
+    }
+    if !(132..=87472).contains(&payload.len()) {
+        return Err(Error::InvalidLength);
+    }
+
+    let payload = general_purpose::STANDARD.decode(payload)?;
+    if !(99..=65603).contains(&payload.len()) {
+        return Err(Error::InvalidLength);
+    }
+    if payload[0] != NIP44_VERSION {
+        return Err(Error::UnsupportedEncryption);
+    }
+
+    let nonce: [u8; 32] = payload[1..33].try_into().expect("length checked");
+    let (ciphertext, mac) = payload[33..].split_at(payload.len() - 33 - 32);
+
+    let (chacha_key, chacha_nonce, hmac_key) = message_keys(conversation_key, &nonce);
+    let expected = hmac_sha256(&hmac_key, &[&nonce[..], ciphertext].concat());
+    // Compare every byte so the time taken does not leak the MAC
+    if expected
+        .iter()
+        .zip(mac)
+        .fold(0, |diff, (a, b)| diff | (a ^ b))
+        != 0
+    {
+        return Err(Error::InvalidMac);
+    }
+
+    let mut buffer = ciphertext.to_vec();
+    chacha20(&chacha_key, &chacha_nonce, 0, &mut buffer);
+    unpad(buffer)
+}
+
+/// ChaCha20 key, ChaCha20 nonce and HMAC key of a message
+fn message_keys(conversation_key: &[u8; 32], nonce: &[u8; 32]) -> ([u8; 32], [u8; 12], [u8; 32]) {
+    // HKDF-expand to 76 bytes, three HMAC blocks
+    let mut okm = Vec::with_capacity(96);
+    let mut previous: Vec<u8> = Vec::new();
+    for counter in 1..=3u8 {
+        let block = hmac_sha256(
+            conversation_key,
+            &[&previous[..], nonce, &[counter]].concat(),
+        );
+        okm.extend_from_slice(&block);
+        previous = block.to_vec();
+    }
+
+    (
+        okm[..32].try_into().expect("32 bytes"),
+        okm[32..44].try_into().expect("12 bytes"),
+        okm[44..76].try_into().expect("32 bytes"),
+    )
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut engine = HmacEngine::<Sha256Hash>::new(key);
+    engine.input(data);
+    Hmac::<Sha256Hash>::from_engine(engine).to_byte_array()
+}
+
+/// Length plaintexts of `len` bytes are padded to
+fn padded_len(len: usize) -> usize {
+    if len <= 32 {
+        return 32;
+    }
+
+    let next_power = 1usize << (usize::BITS - (len - 1).leading_zeros());
+    let chunk = if next_power <= 256 {
+        32
+    } else {
+        next_power / 8
+    };
+    chunk * ((len - 1) / chunk + 1)
+}
+
+/// Big endian length, plaintext and zeros
+fn pad(plaintext: &[u8]) -> Result<Vec<u8>, Error> {
+    let len = plaintext.len();
+    if !(1..=MAX_PLAINTEXT_LEN).contains(&len) {
+        return Err(Error::InvalidLength);
+    }
+
+    let mut padded = Vec::with_capacity(2 + padded_len(len));
+    padded.extend_from_slice(&(len as u16).to_be_bytes());
+    padded.extend_from_slice(plaintext);
+    padded.resize(2 + padded_len(len), 0);
+    Ok(padded)
+}
+
+fn unpad(padded: Vec<u8>) -> Result<String, Error> {
+    let len = u16::from_be_bytes([padded[0], padded[1]]) as usize;
+    if len == 0 || padded.len() != 2 + padded_len(len) {
+        return Err(Error::InvalidPadding);
+    }
+
+    Ok(String::from_utf8(padded[2..2 + len].to_vec())?)
+}
+
+/// XOR `data` with the ChaCha20 keystream starting at block `counter`
+/// [RFC 8439]
+fn chacha20(key: &[u8; 32], nonce: &[u8; 12], counter: u32, data: &mut [u8]) {
+    let mut state = [0u32; 16];
+    state[..4].copy_from_slice(&[0x61707865, 0x3320646e, 0x79622d32, 0x6b206574]);
+    for (word, bytes) in state[4..12].iter_mut().zip(key.chunks_exact(4)) {
+        *word = u32::from_le_bytes(bytes.try_into().expect("4 bytes"));
+    }
+    for (word, bytes) in state[13..].iter_mut().zip(nonce.chunks_exact(4)) {
+        *word = u32::from_le_bytes(bytes.try_into().expect("4 bytes"));
+    }
+
+    for (i, chunk) in data.chunks_mut(64).enumerate() {
+        state[12] = counter.wrapping_add(i as u32);
+        let mut block = state;
+        for _ in 0..10 {
+            quarter_round(&mut block, 0, 4, 8, 12);
+            quarter_round(&mut block, 1, 5, 9, 13);
+            quarter_round(&mut block, 2, 6, 10, 14);
+            quarter_round(&mut block, 3, 7, 11, 15);
+            quarter_round(&mut block, 0, 5, 10, 15);
+            quarter_round(&mut block, 1, 6, 11, 12);
+            quarter_round(&mut block, 2, 7, 8, 13);
+            quarter_round(&mut block, 3, 4, 9, 14);
+        }
+
+        let keystream = block
+            .iter()
+            .zip(state.iter())
+            .flat_map(|(word, initial)| word.wrapping_add(*initial).to_le_bytes());
+        for (byte, key) in chunk.iter_mut().zip(keystream) {
+            *byte ^= key;
+        }
+    }
+}
+
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::nuts::SecretKey;
+
+    fn keys(n: u8) -> SigningKey {
+        let mut secret = [0u8; 32];
+        secret[31] = n;
+        SigningKey::new(SecretKey::from_slice(&secret).unwrap())
+    }
+
+    #[test]
+    fn test_chacha20_rfc8439() {
+        let key: [u8; 32] = core::array::from_fn(|i| i as u8);
+        let nonce = [0, 0, 0, 0, 0, 0, 0, 0x4a, 0, 0, 0, 0];
+        let plaintext = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+
+        let mut buffer = plaintext.to_vec();
+        chacha20(&key, &nonce, 1, &mut buffer);
+        assert_eq!(
+            hex::encode(&buffer[..16]),
+            "6e2e359a2568f98041ba0728dd0d6981"
+        );
+        assert_eq!(hex::encode(&buffer[buffer.len() - 2..]), "874d");
+
+        chacha20(&key, &nonce, 1, &mut buffer);
+        assert_eq!(buffer, plaintext);
+    }
+
+    #[test]
+    fn test_nip44_vector() {
+        let conversation = conversation_key(&keys(1), &keys(2).verifying_key());
+        assert_eq!(
+            hex::encode(conversation),
+            "c41c775356fd92eadc63ff5a0dc1da211b268cbea22316767095b2871ea1412d"
+        );
+        // Both sides of the conversation derive the same key
+        assert_eq!(
+            conversation,
+            conversation_key(&keys(2), &keys(1).verifying_key())
+        );
+
+        let mut nonce = [0u8; 32];
+        nonce[31] = 1;
+        let payload = encrypt_with_nonce(&conversation, &nonce, "a").unwrap();
+        assert_eq!(payload, "AgAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABee0G5VSK0/9YypIObAtDKfYEAjD35uVkHyB0F4DwrcNaCXlCWZKaArsGrY6M9wnuTMxWfp1RTN9Xga8no+kF5Vsb");
+        assert_eq!(decrypt_with_key(&conversation, &payload).unwrap(), "a");
+    }
+
+    #[test]
+    fn test_nip44_round_trip() {
+        let (alice, bob) = (keys(1), keys(2));
+        for len in [1, 32, 33, 255, 257, 1000, MAX_PLAINTEXT_LEN] {
+            let plaintext = "x".repeat(len);
+            let payload = encrypt(&alice, &bob.verifying_key(), &plaintext).unwrap();
+            assert_eq!(
+                decrypt(&bob, &alice.verifying_key(), &payload).unwrap(),
+                plaintext
+            );
+            // A third key cannot read it
+            assert!(matches!(
+                decrypt(&keys(3), &alice.verifying_key(), &payload),
+                Err(Error::InvalidMac)
+            ));
+        }
+
+        assert!(matches!(
+            encrypt(&alice, &bob.verifying_key(), ""),
+            Err(Error::InvalidLength)
+        ));
+        assert!(matches!(
+            decrypt(&bob, &alice.verifying_key(), "abc?iv=def"),
+            Err(Error::UnsupportedEncryption)
+        ));
+    }
+
+    #[test]
+    fn test_padded_len() {
+        let cases = [
+            (1, 32),
+            (32, 32),
+            (33, 64),
+            (64, 64),
+            (65, 96),
+            (256, 256),
+            (257, 320),
+            (1000, 1024),
+            (65535, 65536),
+        ];
+        for (len, padded) in cases {
+            assert_eq!(padded_len(len), padded, "{len}");
+        }
+    }
+
+    #[test]
+    fn test_direct_message() {
+        let (alice, bob) = (keys(1), keys(2));
+        let event = Event::direct_message(&alice, &bob.verifying_key(), "hello bob", 1_700_000_000)
+            .unwrap();
+        event.verify().unwrap();
+        assert_eq!(event.kind, KIND_DIRECT_MESSAGE);
+        assert_eq!(event.decrypt(&bob).unwrap(), "hello bob");
+        assert!(matches!(event.decrypt(&alice), Err(Error::NotRecipient(_))));
+        assert!(Filter::direct_messages(&bob.verifying_key(), Some(1_700_000_000)).matches(&event));
+        assert!(
+            !Filter::direct_messages(&bob.verifying_key(), Some(1_700_000_001)).matches(&event)
+        );
+        assert!(!Filter::direct_messages(&alice.verifying_key(), None).matches(&event));
+
+        let mut tampered = event.clone();
+        tampered.content = encrypt(&alice, &bob.verifying_key(), "hello eve").unwrap();
+        assert!(matches!(tampered.verify(), Err(Error::InvalidEventId)));
+    }
+
+    #[test]
+    fn test_npub() {
+        // NIP-19 example
+        let npub = "npub10elfcs4fr0l0r8af98jlmgdh9c8tcxjvz9qkw038js35mp4dma8qzvjptg";
+        let key = parse_npub(npub).unwrap();
+        assert_eq!(
+            key,
+            VerifyingKey::from_str(
+                "7e7e9c42a91bfef19fa929e5fda1b72e0ebc1a4c1141673e2794234d86addf4e"
+            )
+            .unwrap()
+        );
+        assert_eq!(to_npub(&key), npub);
+
+        let nsec = bech32::encode("nsec", [1u8; 32].to_base32(), Variant::Bech32).unwrap();
+        assert!(matches!(parse_npub(&nsec), Err(Error::InvalidNpub)));
+        assert!(parse_npub("npub1").is_err());
+    }
+
+    #[test]
+    fn test_find_tokens() {
+        assert_eq!(
+            find_tokens("here you go: cashuAeyJ0b2tlbiI6W10, and cashu:cashuBo2F0gA== thanks"),
+            vec!["cashuAeyJ0b2tlbiI6W10", "cashuBo2F0gA=="]
+        );
+        assert!(find_tokens("no token, just cashu talk").is_empty());
+    }
+}
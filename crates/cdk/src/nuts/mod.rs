@@ -13,25 +13,42 @@ pub mod nut11;
 pub mod nut12;
 #[cfg(feature = "nut13")]
 pub mod nut13;
+pub mod nut14;
+pub mod nut15;
+pub mod nut17;
+pub mod nut25;
+pub mod v1;
 
 pub use nut00::{
-    BlindSignature, BlindedMessage, CurrencyUnit, PaymentMethod, PreMint, PreMintSecrets, Proof,
-    Proofs, Token,
+    BlindSignature, BlindedMessage, CurrencyUnit, LegacyFields, PaymentMethod, PreMint,
+    PreMintSecrets, Proof, ProofV4, Proofs, ProofsMethods, Request, Strictness, Token,
+    TokenSummary, TokenV4, TokenV4Keyset, Vintage, Witness,
 };
 pub use nut01::{Keys, KeysResponse, PublicKey, SecretKey};
-pub use nut02::{Id, KeySet, KeySetInfo, KeysetResponse, MintKeySet};
+pub use nut02::{
+    Id, KeySet, KeySetInfo, KeysetResponse, MintKeySet, MintKeySetPublic, MintKeySetSecret,
+};
 #[cfg(feature = "wallet")]
-pub use nut03::PreSwap;
-pub use nut03::{SwapRequest, SwapResponse};
+pub use nut03::{PreSwap, SwapRequestBuilder};
+pub use nut03::{SwapRequest, SwapResponse, SwapResponseForm};
 pub use nut04::{
     MintBolt11Request, MintBolt11Response, MintQuoteBolt11Request, MintQuoteBolt11Response,
 };
+#[cfg(feature = "wallet")]
+pub use nut04::{MintRequestBuilder, PreMintRequest};
 pub use nut05::{
-    MeltBolt11Request, MeltBolt11Response, MeltQuoteBolt11Request, MeltQuoteBolt11Response,
+    CheckFeesRequest, CheckFeesResponse, MeltBolt11Request, MeltBolt11Response,
+    MeltQuoteBolt11Request, MeltQuoteBolt11Response, MeltQuoteState,
 };
-pub use nut06::{MintInfo, MintVersion, Nuts};
+#[cfg(feature = "wallet")]
+pub use nut05::{MeltRequestBuilder, PreMelt};
+pub use nut06::{ContactInfo, ContactMethod, MintInfo, MintVersion, Nuts};
 pub use nut07::{CheckStateRequest, CheckStateResponse, ProofState, State};
 pub use nut09::{RestoreRequest, RestoreResponse};
 pub use nut10::{Kind, Secret as Nut10Secret, SecretData};
-pub use nut11::{P2PKConditions, SigFlag, Signatures, SigningKey, VerifyingKey};
+pub use nut11::{P2PKConditions, P2PKWitness, SigFlag, SigningKey, VerifyingKey};
 pub use nut12::{BlindSignatureDleq, ProofDleq};
+pub use nut14::HTLCWitness;
+pub use nut15::{MeltOptions, Mpp};
+pub use nut17::{Notification, SubscriptionKind};
+pub use nut25::{MintQuoteBolt12Request, MintQuoteBolt12Response};
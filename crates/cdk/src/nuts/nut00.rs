@@ -3,30 +3,49 @@
 //! <https://github.com/cashubtc/nuts/blob/main/00.md>
 
 use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::str::FromStr;
 use std::string::FromUtf8Error;
+use std::sync::OnceLock;
 
 use base64::engine::{general_purpose, GeneralPurpose};
 use base64::{alphabet, Engine as _};
-use serde::{Deserialize, Deserializer, Serialize};
+use serde::de::DeserializeOwned;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use thiserror::Error;
 use url::Url;
 
-use crate::dhke::blind_message;
+use crate::dhke::{blind_message, hash_to_curve, hash_to_curve_many};
 use crate::nuts::nut01::{PublicKey, SecretKey};
-use crate::nuts::nut11::{witness_deserialize, witness_serialize, Signatures};
+use crate::nuts::nut11::P2PKWitness;
 use crate::nuts::nut12::BlindSignatureDleq;
-use crate::nuts::{Id, P2PKConditions, ProofDleq};
+use crate::nuts::nut14::HTLCWitness;
+use crate::nuts::{nut02, Id, P2PKConditions, ProofDleq};
 use crate::secret::Secret;
 use crate::url::UncheckedUrl;
-use crate::Amount;
+use crate::util::{cbor, hex};
+use crate::{Amount, SplitTarget, UnitAmount};
 
 /// List of [Proof]
 pub type Proofs = Vec<Proof>;
 
+/// Max length of a serialized token that is parsed
+///
+/// Checked before anything is decoded, holds over a thousand proofs.
+pub const MAX_TOKEN_LEN: usize = 1 << 20;
+
+/// Max length of a proof secret in a token
+///
+/// Well over any NUT-10 secret with a reasonable number of keys and tags.
+pub const MAX_SECRET_LEN: usize = 8 << 10;
+
+/// Max length of a request body that is parsed, see [Request::parse_request]
+pub const MAX_REQUEST_LEN: usize = 4 << 20;
+
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum Error {
     /// Proofs required
     #[error("Proofs required in token")]
@@ -37,6 +56,39 @@ pub enum Error {
     /// Invalid Url
     #[error("Invalid Url")]
     InvalidUrl,
+    /// Amount overflow
+    #[error("Amount Overflow")]
+    AmountOverflow,
+    /// Token entry of a mint without proofs
+    #[error("No proofs for mint `{0}` in token")]
+    EmptyMintProofs(UncheckedUrl),
+    /// Proof of a zero amount, which no keyset signs
+    #[error("Proof of zero amount in token")]
+    ZeroAmountProof,
+    /// Keyset entry of a V4 token without proofs
+    #[error("No proofs for keyset `{0}` in token")]
+    EmptyKeysetProofs(Id),
+    /// Legacy keyset ids have no byte form for V4 tokens
+    #[error("Legacy keyset id `{0}` in V4 token")]
+    LegacyKeysetId(Id),
+    /// V4 tokens hold the proofs of a single mint
+    #[error("Proofs of more than one mint for a V4 token")]
+    MultipleMints,
+    /// Field of a V4 token missing or of the wrong type
+    #[error("Invalid field `{0}` in V4 token")]
+    InvalidTokenV4(&'static str),
+    /// CBOR error
+    #[error(transparent)]
+    Cbor(#[from] crate::util::cbor::Error),
+    /// NUT02 error
+    #[error(transparent)]
+    NUT02(#[from] crate::nuts::nut02::Error),
+    /// Inputs and outputs of a request do not balance
+    #[error("Unbalanced request, inputs: {inputs} outputs: {outputs}")]
+    Unbalanced { inputs: Amount, outputs: Amount },
+    /// The last signatures of a swap do not add up to its split amount
+    #[error("No signatures add up to the split amount {0}")]
+    SplitAmount(Amount),
     /// Serde Json error
     #[error(transparent)]
     SerdeJsonError(#[from] serde_json::Error),
@@ -55,10 +107,23 @@ pub enum Error {
     /// NUT11 error
     #[error(transparent)]
     NUT11(#[from] crate::nuts::nut11::Error),
+    /// Mint quote request with both a description and a description hash
+    #[error("Mint quote request cannot have both a description and a description hash")]
+    DescriptionAndHash,
+    /// Field not known to the request, rejected in [Strictness::Strict]
+    #[error("Unknown field `{0}`")]
+    UnknownField(String),
+    /// Token or request longer than is parsed
+    #[error("Input of {len} bytes is over the limit of {max}")]
+    InputTooLarge { len: usize, max: usize },
+    /// Proof secret longer than [MAX_SECRET_LEN]
+    #[error("Secret of {len} bytes is over the limit of {max}")]
+    SecretTooLong { len: usize, max: usize },
 }
 
 /// Blinded Message (also called `output`)
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct BlindedMessage {
     /// Amount
     ///
@@ -79,9 +144,7 @@ pub struct BlindedMessage {
     /// <https://github.com/cashubtc/nuts/blob/main/11.md>
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
-    //#[serde(serialize_with = "witness_serialize")]
-    //#[serde(deserialize_with = "witness_deserialize")]
-    pub witness: Option<Signatures>,
+    pub witness: Option<Witness>,
 }
 
 impl BlindedMessage {
@@ -97,14 +160,162 @@ impl BlindedMessage {
     }
 
     /// Add witness
-    pub fn witness(mut self, witness: Signatures) -> Self {
+    pub fn witness(mut self, witness: Witness) -> Self {
         self.witness = Some(witness);
         self
     }
 }
 
+/// Witness
+///
+/// Carried on the wire as a stringified JSON object
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Witness {
+    /// P2PK Witness
+    ///
+    /// <https://github.com/cashubtc/nuts/blob/main/11.md>
+    P2PKWitness(P2PKWitness),
+    /// HTLC Witness
+    ///
+    /// <https://github.com/cashubtc/nuts/blob/main/14.md>
+    HTLCWitness(HTLCWitness),
+}
+
+impl Witness {
+    /// Signatures of witness
+    pub fn signatures(&self) -> Option<&Vec<String>> {
+        match self {
+            Self::P2PKWitness(witness) => Some(&witness.signatures),
+            Self::HTLCWitness(witness) => witness.signatures.as_ref(),
+        }
+    }
+
+    /// Add signatures to witness
+    pub fn add_signatures(&mut self, signatures: Vec<String>) {
+        match self {
+            Self::P2PKWitness(witness) => witness.signatures.extend(signatures),
+            Self::HTLCWitness(witness) => match &mut witness.signatures {
+                Some(sigs) => sigs.extend(signatures),
+                None => witness.signatures = Some(signatures),
+            },
+        }
+    }
+
+    /// Preimage of HTLC witness
+    pub fn preimage(&self) -> Option<&String> {
+        match self {
+            Self::P2PKWitness(_) => None,
+            Self::HTLCWitness(witness) => Some(&witness.preimage),
+        }
+    }
+}
+
+impl From<P2PKWitness> for Witness {
+    fn from(witness: P2PKWitness) -> Self {
+        Self::P2PKWitness(witness)
+    }
+}
+
+impl From<HTLCWitness> for Witness {
+    fn from(witness: HTLCWitness) -> Self {
+        Self::HTLCWitness(witness)
+    }
+}
+
+impl Witness {
+    fn from_json_value(value: serde_json::Value) -> Result<Self, serde_json::Error> {
+        // Only an HTLC witness carries a preimage
+        match value.get("preimage") {
+            Some(_) => Ok(Self::HTLCWitness(serde_json::from_value(value)?)),
+            None => Ok(Self::P2PKWitness(serde_json::from_value(value)?)),
+        }
+    }
+
+    fn to_json_value(&self) -> Result<serde_json::Value, serde_json::Error> {
+        match self {
+            Self::P2PKWitness(witness) => serde_json::to_value(witness),
+            Self::HTLCWitness(witness) => serde_json::to_value(witness),
+        }
+    }
+}
+
+/// Stringified JSON object, as carried on the wire
+impl fmt::Display for Witness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let json = serde_json::to_string(&self.to_json_value().map_err(|_| fmt::Error)?)
+            .map_err(|_| fmt::Error)?;
+
+        f.write_str(&json)
+    }
+}
+
+impl FromStr for Witness {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_json_value(serde_json::from_str(s)?)
+    }
+}
+
+impl Serialize for Witness {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Witness {
+    /// Accepts both the stringified form and a bare JSON object
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = serde_json::Value::deserialize(deserializer)?;
+
+        match value {
+            serde_json::Value::String(s) => Self::from_str(&s).map_err(de::Error::custom),
+            serde_json::Value::Object(_) => Self::from_json_value(value).map_err(de::Error::custom),
+            _ => Err(de::Error::custom("witness must be a string or an object")),
+        }
+    }
+}
+
+/// Serde helpers to send an optional [Witness] as a bare JSON object
+///
+/// Use with `#[serde(with = "witness_object")]` on an `Option<Witness>` field
+pub mod witness_object {
+    use serde::{ser, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Witness;
+
+    /// Serialize witness as a JSON object
+    pub fn serialize<S>(witness: &Option<Witness>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match witness {
+            Some(witness) => witness
+                .to_json_value()
+                .map_err(ser::Error::custom)?
+                .serialize(serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    /// Deserialize witness from either its object or stringified form
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Witness>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Option::<Witness>::deserialize(deserializer)
+    }
+}
+
 /// Blind Signature (also called `promise`)
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct BlindSignature {
     /// Amount
     ///
@@ -130,6 +341,7 @@ pub struct BlindSignature {
 
 /// Proofs
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct Proof {
     /// Amount
     pub amount: Amount,
@@ -144,11 +356,14 @@ pub struct Proof {
     /// Witness
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
-    #[serde(serialize_with = "witness_serialize")]
-    #[serde(deserialize_with = "witness_deserialize")]
-    pub witness: Option<Signatures>,
+    pub witness: Option<Witness>,
     /// DLEQ Proof
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub dleq: Option<ProofDleq>,
+    /// `Y` of the secret, once computed
+    #[serde(skip)]
+    pub(crate) y: CachedY,
 }
 
 impl Proof {
@@ -160,7 +375,142 @@ impl Proof {
             c,
             witness: None,
             dleq: None,
+            y: CachedY::default(),
+        }
+    }
+
+    /// `Y` of the proof, hash to curve of its secret
+    ///
+    /// Two proofs are the same ecash iff their `Y`s are equal, whatever their
+    /// other fields. Computed once per secret.
+    pub fn y(&self) -> Result<PublicKey, crate::error::Error> {
+        if let Some((secret, y)) = self.y.0.get() {
+            if *secret == self.secret {
+                return Ok(*y);
+            }
+
+            // Secret was changed after the cached `Y` was computed
+            return hash_to_curve(self.secret.as_bytes());
+        }
+
+        let y = hash_to_curve(self.secret.as_bytes())?;
+        let _ = self.y.0.set((self.secret.clone(), y));
+
+        Ok(y)
+    }
+
+    /// Deserialize a proof of an early wallet, whose secret may be in the
+    /// legacy base64 form
+    ///
+    /// The secret is normalized to the current plain string, see
+    /// [crate::serde_utils::legacy_secret] for when a secret is decoded.
+    pub fn deserialize_legacy<'de, D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let mut proof = serde_json::Value::deserialize(deserializer)?;
+        normalize_legacy_secret(&mut proof);
+
+        Proof::deserialize(proof).map_err(de::Error::custom)
+    }
+}
+
+fn normalize_legacy_secret(proof: &mut serde_json::Value) {
+    if let Some(secret) = proof.get_mut("secret") {
+        if let Some(decoded) = secret
+            .as_str()
+            .and_then(crate::serde_utils::legacy_secret::decode)
+        {
+            *secret = serde_json::Value::String(decoded);
+        }
+    }
+}
+
+/// Cache of [Proof::y], ignored when comparing proofs
+#[derive(Clone, Default)]
+pub(crate) struct CachedY(OnceLock<(Secret, PublicKey)>);
+
+impl PartialEq for CachedY {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for CachedY {}
+
+impl fmt::Debug for CachedY {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CachedY")
+    }
+}
+
+/// Helpers shared by everything that handles [Proofs]
+pub trait ProofsMethods {
+    /// Total amount of proofs, erroring on overflow
+    fn total_amount(&self) -> Result<Amount, Error>;
+
+    /// `Y`s of proofs (hash to curve of each secret)
+    ///
+    /// `Y`s not cached yet are computed in one [hash_to_curve_many] batch and
+    /// cached, so later [Proof::y] calls on the same proofs are free.
+    fn ys(&self) -> Result<Vec<PublicKey>, Error>;
+
+    /// Number of proofs per keyset
+    fn count_by_keyset(&self) -> HashMap<Id, usize>;
+
+    /// Remove proofs with a duplicate secret, keeping the first occurrence
+    fn dedup_by_secret(&mut self);
+
+    /// Remove proofs with a duplicate `Y`, keeping the first occurrence
+    fn dedup_by_y(&mut self) -> Result<(), Error>;
+}
+
+impl ProofsMethods for Proofs {
+    fn total_amount(&self) -> Result<Amount, Error> {
+        self.iter().try_fold(Amount::ZERO, |total, proof| {
+            total.checked_add(proof.amount).ok_or(Error::AmountOverflow)
+        })
+    }
+
+    fn ys(&self) -> Result<Vec<PublicKey>, Error> {
+        let uncached: Vec<&Proof> = self
+            .iter()
+            .filter(|proof| proof.y.0.get().is_none())
+            .collect();
+        if !uncached.is_empty() {
+            let messages: Vec<&[u8]> = uncached.iter().map(|p| p.secret.as_bytes()).collect();
+            let ys = hash_to_curve_many(&messages)?;
+            for (proof, y) in uncached.into_iter().zip(ys) {
+                let _ = proof.y.0.set((proof.secret.clone(), y));
+            }
+        }
+
+        Ok(self
+            .iter()
+            .map(Proof::y)
+            .collect::<Result<Vec<PublicKey>, _>>()?)
+    }
+
+    fn count_by_keyset(&self) -> HashMap<Id, usize> {
+        let mut counts = HashMap::new();
+        for proof in self {
+            *counts.entry(proof.keyset_id).or_insert(0) += 1;
         }
+        counts
+    }
+
+    fn dedup_by_secret(&mut self) {
+        let mut seen = HashSet::with_capacity(self.len());
+        self.retain(|proof| seen.insert(proof.secret.clone()));
+    }
+
+    fn dedup_by_y(&mut self) -> Result<(), Error> {
+        let ys = self.ys()?;
+        let mut seen = HashSet::with_capacity(ys.len());
+        let mut ys = ys.into_iter();
+        self.retain(|_| ys.next().is_some_and(|y| seen.insert(y)));
+
+        Ok(())
     }
 }
 
@@ -182,14 +532,35 @@ impl PartialOrd for Proof {
     }
 }
 
+/// Unit of a keyset, quote or token
 #[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
 pub enum CurrencyUnit {
+    /// Satoshi
     #[default]
     Sat,
+    /// Millisatoshi
+    Msat,
+    /// US Dollar
     Usd,
+    /// Euro
+    Eur,
+    /// Any other unit, kept as sent
     Custom(String),
 }
 
+impl CurrencyUnit {
+    /// BIP32 index giving each known unit its own keyset derivation tree
+    pub fn derivation_index(&self) -> Option<u32> {
+        match self {
+            Self::Sat => Some(0),
+            Self::Msat => Some(1),
+            Self::Usd => Some(2),
+            Self::Eur => Some(3),
+            Self::Custom(_) => None,
+        }
+    }
+}
+
 impl<S> From<S> for CurrencyUnit
 where
     S: AsRef<str>,
@@ -197,17 +568,29 @@ where
     fn from(currency: S) -> Self {
         match currency.as_ref() {
             "sat" => Self::Sat,
+            "msat" => Self::Msat,
             "usd" => Self::Usd,
+            "eur" => Self::Eur,
             o => Self::Custom(o.to_string()),
         }
     }
 }
 
+impl FromStr for CurrencyUnit {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::from(s))
+    }
+}
+
 impl fmt::Display for CurrencyUnit {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             CurrencyUnit::Sat => write!(f, "sat"),
+            CurrencyUnit::Msat => write!(f, "msat"),
             CurrencyUnit::Usd => write!(f, "usd"),
+            CurrencyUnit::Eur => write!(f, "eur"),
             CurrencyUnit::Custom(unit) => write!(f, "{unit}"),
         }
     }
@@ -232,10 +615,15 @@ impl<'de> Deserialize<'de> for CurrencyUnit {
     }
 }
 
+/// Payment method used to fund or pay out a quote
 #[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
 pub enum PaymentMethod {
+    /// Bolt11 invoice
     #[default]
     Bolt11,
+    /// Bolt12 offer
+    Bolt12,
+    /// Any other method
     Custom(String),
 }
 
@@ -246,6 +634,7 @@ where
     fn from(method: S) -> Self {
         match method.as_ref() {
             "bolt11" => Self::Bolt11,
+            "bolt12" => Self::Bolt12,
             o => Self::Custom(o.to_string()),
         }
     }
@@ -255,6 +644,7 @@ impl fmt::Display for PaymentMethod {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             PaymentMethod::Bolt11 => write!(f, "bolt11"),
+            PaymentMethod::Bolt12 => write!(f, "bolt12"),
             PaymentMethod::Custom(unit) => write!(f, "{}", unit),
         }
     }
@@ -311,11 +701,23 @@ pub struct PreMintSecrets {
 impl PreMintSecrets {
     /// Outputs for speceifed amount with random secret
     pub fn random(keyset_id: Id, amount: Amount) -> Result<Self, Error> {
-        let amount_split = amount.split();
+        Self::random_with_target(keyset_id, amount, &SplitTarget::None)
+    }
 
-        let mut output = Vec::with_capacity(amount_split.len());
+    /// Outputs with random secrets for `amount` split as `target` asks
+    pub fn random_with_target(
+        keyset_id: Id,
+        amount: Amount,
+        target: &SplitTarget,
+    ) -> Result<Self, Error> {
+        Self::random_with_amounts(keyset_id, &amount.split_targeted(target))
+    }
 
-        for amount in amount_split {
+    /// Outputs with random secrets, one for each of `amounts`
+    pub fn random_with_amounts(keyset_id: Id, amounts: &[Amount]) -> Result<Self, Error> {
+        let mut output = Vec::with_capacity(amounts.len());
+
+        for &amount in amounts {
             let secret = Secret::generate();
             let (blinded, r) = blind_message(&secret.to_bytes(), None)?;
 
@@ -355,9 +757,14 @@ impl PreMintSecrets {
         Ok(PreMintSecrets { secrets: output })
     }
 
+    /// Number of outputs [PreMintSecrets::blank] creates for `fee_reserve`
+    pub fn blank_count(fee_reserve: Amount) -> u64 {
+        ((u64::from(fee_reserve) as f64).log2().ceil() as u64).max(1)
+    }
+
     /// Blank Outputs used for NUT-08 change
     pub fn blank(keyset_id: Id, fee_reserve: Amount) -> Result<Self, Error> {
-        let count = ((u64::from(fee_reserve) as f64).log2().ceil() as u64).max(1);
+        let count = Self::blank_count(fee_reserve);
 
         let mut output = Vec::with_capacity(count as usize);
 
@@ -502,34 +909,234 @@ impl Token {
         })
     }
 
-    pub fn token_info(&self) -> (u64, String) {
-        let mut amount = Amount::ZERO;
+    /// Add `proofs` of `mint_url`
+    ///
+    /// Proofs of a mint already in the token are merged into its entry, so
+    /// each mint url appears once. Urls are compared as written, see
+    /// [UncheckedUrl].
+    pub fn add_mint(&mut self, mint_url: UncheckedUrl, proofs: Proofs) -> Result<(), Error> {
+        if proofs.is_empty() {
+            return Err(Error::ProofsRequired);
+        }
+
+        let _: Url = (&mint_url).try_into().map_err(|_| Error::InvalidUrl)?;
 
-        for proofs in &self.token {
-            for proof in &proofs.proofs {
-                amount += proof.amount;
+        match self.token.iter_mut().find(|t| t.mint == mint_url) {
+            Some(mint_proofs) => mint_proofs.proofs.extend(proofs),
+            None => self.token.push(MintProofs::new(mint_url, proofs)),
+        }
+
+        Ok(())
+    }
+
+    /// Remove the DLEQ proofs of every proof
+    ///
+    /// DLEQ proofs let the receiver check the mint signatures offline, see
+    /// [Proof::verify_dleq], but make the token about twice as long. Stripped
+    /// proofs are still spendable.
+    pub fn strip_dleq(&mut self) {
+        for proof in self.token.iter_mut().flat_map(|t| t.proofs.iter_mut()) {
+            proof.dleq = None;
+        }
+    }
+
+    /// Total amount of the proofs
+    pub fn value(&self) -> Result<Amount, Error> {
+        self.token
+            .iter()
+            .flat_map(|t| t.proofs.iter())
+            .try_fold(Amount::ZERO, |total, proof| {
+                total.checked_add(proof.amount).ok_or(Error::AmountOverflow)
+            })
+    }
+
+    /// Unit of every proof, [CurrencyUnit::Sat] if the token does not say
+    pub fn unit(&self) -> CurrencyUnit {
+        self.unit.clone().unwrap_or_default()
+    }
+
+    /// [Token::value] in [Token::unit]
+    pub fn unit_value(&self) -> Result<UnitAmount, Error> {
+        Ok(UnitAmount::new(self.value()?, self.unit()))
+    }
+
+    /// Check the token is internally consistent
+    ///
+    /// It has to hold proofs, every mint entry at least one, none of them of
+    /// zero amount or with a secret over [MAX_SECRET_LEN], and their total
+    /// has to fit an [Amount]. Whether the proofs are valid can only be
+    /// checked by the mint.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.token.is_empty() {
+            return Err(Error::ProofsRequired);
+        }
+
+        for mint_proofs in &self.token {
+            if mint_proofs.proofs.is_empty() {
+                return Err(Error::EmptyMintProofs(mint_proofs.mint.clone()));
             }
+            validate_proofs(mint_proofs.proofs.iter().map(|p| (p.amount, &p.secret)))?;
         }
 
+        self.value()?;
+
+        Ok(())
+    }
+
+    pub fn token_info(&self) -> (u64, String) {
+        let amount: Amount = self
+            .token
+            .iter()
+            .flat_map(|t| t.proofs.iter().map(|p| p.amount))
+            .sum();
+
         (amount.into(), self.token[0].mint.to_string())
     }
+
+    /// Overview of the token for logs, without secrets or signatures
+    pub fn summary(&self) -> TokenSummary {
+        let proofs = self.token.iter().flat_map(|t| t.proofs.iter());
+
+        let mut keyset_ids: Vec<Id> = Vec::new();
+        for proof in proofs.clone() {
+            if !keyset_ids.contains(&proof.keyset_id) {
+                keyset_ids.push(proof.keyset_id);
+            }
+        }
+
+        TokenSummary {
+            mints: self.token.iter().map(|t| t.mint.clone()).collect(),
+            unit: self.unit(),
+            amount: proofs
+                .clone()
+                .fold(Amount::ZERO, |total, p| total.saturating_add(p.amount)),
+            proof_count: proofs.count(),
+            keyset_ids,
+            memo: self.memo.clone(),
+        }
+    }
+}
+
+/// Secret free overview of a [Token]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenSummary {
+    pub mints: Vec<UncheckedUrl>,
+    /// Unit of every proof, [CurrencyUnit::Sat] if the token does not say
+    pub unit: CurrencyUnit,
+    /// Total amount, saturating at [u64::MAX]
+    pub amount: Amount,
+    pub proof_count: usize,
+    /// Keysets in order of first use
+    pub keyset_ids: Vec<Id>,
+    pub memo: Option<String>,
+}
+
+impl fmt::Display for TokenSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mints: Vec<String> = self.mints.iter().map(|m| m.to_string()).collect();
+        let keyset_ids: Vec<String> = self.keyset_ids.iter().map(|id| id.to_string()).collect();
+
+        write!(
+            f,
+            "{} {} in {} proofs from {} (keysets {})",
+            self.amount,
+            self.unit,
+            self.proof_count,
+            mints.join(", "),
+            keyset_ids.join(", ")
+        )?;
+
+        if let Some(memo) = &self.memo {
+            write!(f, " memo: {memo:?}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Token {
+    /// Parse a token of an early wallet, whose secrets may be in the legacy
+    /// base64 form
+    ///
+    /// Secrets are normalized as by [Proof::deserialize_legacy], so the token
+    /// is written in the current form. The token is checked with
+    /// [Token::validate].
+    pub fn from_legacy_str(s: &str) -> Result<Self, Error> {
+        let mut token: serde_json::Value = serde_json::from_str(&decode_token_json(s)?)?;
+
+        if let Some(mint_proofs) = token.get_mut("token").and_then(|t| t.as_array_mut()) {
+            for proofs in mint_proofs {
+                if let Some(proofs) = proofs.get_mut("proofs").and_then(|p| p.as_array_mut()) {
+                    proofs.iter_mut().for_each(normalize_legacy_secret);
+                }
+            }
+        }
+
+        let token: Token = serde_json::from_value(token)?;
+        token.validate()?;
+        Ok(token)
+    }
+}
+
+/// Proofs of a token entry, given as `(amount, secret)`, are of a non zero
+/// amount with a secret of at most [MAX_SECRET_LEN]
+fn validate_proofs<'a>(
+    proofs: impl IntoIterator<Item = (Amount, &'a Secret)>,
+) -> Result<(), Error> {
+    for (amount, secret) in proofs {
+        if amount == Amount::ZERO {
+            return Err(Error::ZeroAmountProof);
+        }
+        let len = secret.as_bytes().len();
+        if len > MAX_SECRET_LEN {
+            return Err(Error::SecretTooLong {
+                len,
+                max: MAX_SECRET_LEN,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Bytes of a serialized token after its `prefix`, padded or not
+///
+/// The length is checked against [MAX_TOKEN_LEN] before anything is
+/// allocated.
+fn decode_token_base64(
+    s: &str,
+    prefix: &str,
+    alphabet: &alphabet::Alphabet,
+) -> Result<Vec<u8>, Error> {
+    if s.len() > MAX_TOKEN_LEN {
+        return Err(Error::InputTooLarge {
+            len: s.len(),
+            max: MAX_TOKEN_LEN,
+        });
+    }
+    let s = s.strip_prefix(prefix).ok_or(Error::UnsupportedToken)?;
+
+    let decode_config = general_purpose::GeneralPurposeConfig::new()
+        .with_decode_padding_mode(base64::engine::DecodePaddingMode::Indifferent);
+    Ok(GeneralPurpose::new(alphabet, decode_config).decode(s)?)
+}
+
+/// JSON of a `cashuA` token
+fn decode_token_json(s: &str) -> Result<String, Error> {
+    let decoded = decode_token_base64(s, "cashuA", &alphabet::STANDARD)?;
+    Ok(String::from_utf8(decoded)?)
 }
 
 impl FromStr for Token {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let s = if s.starts_with("cashuA") {
-            s.replace("cashuA", "")
-        } else {
-            return Err(Error::UnsupportedToken);
-        };
+        if s.starts_with("cashuB") {
+            return Ok(TokenV4::from_str(s)?.into());
+        }
 
-        let decode_config = general_purpose::GeneralPurposeConfig::new()
-            .with_decode_padding_mode(base64::engine::DecodePaddingMode::Indifferent);
-        let decoded = GeneralPurpose::new(&alphabet::STANDARD, decode_config).decode(s)?;
-        let decoded_str = String::from_utf8(decoded)?;
-        let token: Token = serde_json::from_str(&decoded_str)?;
+        let token: Token = serde_json::from_str(&decode_token_json(s)?)?;
+        token.validate()?;
         Ok(token)
     }
 }
@@ -557,27 +1164,765 @@ impl MintProofs {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use std::str::FromStr;
-
-    use super::*;
+/// Token of a single mint in the `cashuB` format
+///
+/// CBOR encoded and then base64url encoded without padding, see NUT-00.
+/// Proofs are grouped by keyset, so each keyset id is written once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenV4 {
+    pub mint_url: UncheckedUrl,
+    pub unit: CurrencyUnit,
+    pub memo: Option<String>,
+    /// Proofs by keyset, in order of keyset id
+    pub token: Vec<TokenV4Keyset>,
+}
 
-    #[test]
-    fn test_proof_serialize() {
-        let proof = "[{\"id\":\"009a1f293253e41e\",\"amount\":2,\"secret\":\"407915bc212be61a77e3e6d2aeb4c727980bda51cd06a6afc29e2861768a7837\",\"C\":\"02bc9097997d81afb2cc7346b5e4345a9346bd2a506eb7958598a72f0cf85163ea\"},{\"id\":\"009a1f293253e41e\",\"amount\":8,\"secret\":\"fe15109314e61d7756b0f8ee0f23a624acaa3f4e042f61433c728c7057b931be\",\"C\":\"029e8e5050b890a7d6c0968db16bc1d5d5fa040ea1de284f6ec69d61299f671059\"}]";
-        let proof: Proofs = serde_json::from_str(proof).unwrap();
+/// Proofs of one keyset of a [TokenV4]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenV4Keyset {
+    pub keyset_id: Id,
+    pub proofs: Vec<ProofV4>,
+}
 
-        assert_eq!(
-            proof[0].clone().keyset_id,
-            Id::from_str("009a1f293253e41e").unwrap()
-        );
+/// [Proof] without its keyset id, which is kept by its [TokenV4Keyset]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofV4 {
+    pub amount: Amount,
+    pub secret: Secret,
+    pub c: PublicKey,
+    pub witness: Option<Witness>,
+    pub dleq: Option<ProofDleq>,
+}
 
-        assert_eq!(proof.len(), 2);
+impl ProofV4 {
+    fn into_proof(self, keyset_id: Id) -> Proof {
+        Proof {
+            amount: self.amount,
+            keyset_id,
+            secret: self.secret,
+            c: self.c,
+            witness: self.witness,
+            dleq: self.dleq,
+            y: CachedY::default(),
+        }
     }
+}
 
-    #[test]
-    fn test_token_str_round_trip() {
+impl From<Proof> for ProofV4 {
+    fn from(proof: Proof) -> Self {
+        Self {
+            amount: proof.amount,
+            secret: proof.secret,
+            c: proof.c,
+            witness: proof.witness,
+            dleq: proof.dleq,
+        }
+    }
+}
+
+impl TokenV4 {
+    /// Token of `proofs`, grouped by keyset id
+    ///
+    /// Proofs of a keyset keep their order. Legacy keyset ids cannot be
+    /// written in a V4 token.
+    pub fn new(
+        mint_url: UncheckedUrl,
+        proofs: Proofs,
+        memo: Option<String>,
+        unit: CurrencyUnit,
+    ) -> Result<Self, Error> {
+        if proofs.is_empty() {
+            return Err(Error::ProofsRequired);
+        }
+
+        let _: Url = (&mint_url).try_into().map_err(|_| Error::InvalidUrl)?;
+
+        // Version `00` ids sort the same as strings and as bytes
+        let mut keysets: BTreeMap<String, TokenV4Keyset> = BTreeMap::new();
+        for proof in proofs {
+            if proof.keyset_id.is_legacy() {
+                return Err(Error::LegacyKeysetId(proof.keyset_id));
+            }
+
+            keysets
+                .entry(proof.keyset_id.to_string())
+                .or_insert_with(|| TokenV4Keyset {
+                    keyset_id: proof.keyset_id,
+                    proofs: Vec::new(),
+                })
+                .proofs
+                .push(proof.into());
+        }
+
+        Ok(Self {
+            mint_url,
+            unit,
+            memo,
+            token: keysets.into_values().collect(),
+        })
+    }
+
+    /// Proofs with their keyset ids, keyset by keyset
+    pub fn proofs(&self) -> Proofs {
+        self.token
+            .iter()
+            .flat_map(|keyset| {
+                keyset
+                    .proofs
+                    .iter()
+                    .map(|proof| proof.clone().into_proof(keyset.keyset_id))
+            })
+            .collect()
+    }
+
+    /// Total amount of the proofs
+    pub fn value(&self) -> Result<Amount, Error> {
+        self.token
+            .iter()
+            .flat_map(|keyset| keyset.proofs.iter())
+            .try_fold(Amount::ZERO, |total, proof| {
+                total.checked_add(proof.amount).ok_or(Error::AmountOverflow)
+            })
+    }
+
+    /// [TokenV4::value] in [TokenV4::unit]
+    pub fn unit_value(&self) -> Result<UnitAmount, Error> {
+        Ok(UnitAmount::new(self.value()?, self.unit.clone()))
+    }
+
+    /// Check the token is internally consistent, as [Token::validate] with
+    /// every keyset holding at least one proof
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.token.is_empty() {
+            return Err(Error::ProofsRequired);
+        }
+
+        for keyset in &self.token {
+            if keyset.keyset_id.is_legacy() {
+                return Err(Error::LegacyKeysetId(keyset.keyset_id));
+            }
+            if keyset.proofs.is_empty() {
+                return Err(Error::EmptyKeysetProofs(keyset.keyset_id));
+            }
+            validate_proofs(keyset.proofs.iter().map(|p| (p.amount, &p.secret)))?;
+        }
+
+        self.value()?;
+
+        Ok(())
+    }
+
+    fn to_cbor(&self) -> Result<cbor::Value, Error> {
+        let mut keysets = Vec::with_capacity(self.token.len());
+        for keyset in &self.token {
+            if keyset.keyset_id.is_legacy() {
+                return Err(Error::LegacyKeysetId(keyset.keyset_id));
+            }
+            let id = hex::decode(keyset.keyset_id.to_string()).map_err(nut02::Error::from)?;
+
+            let proofs = keyset.proofs.iter().map(|proof| {
+                let mut fields = vec![
+                    ("a", cbor::Value::Unsigned(proof.amount.into())),
+                    ("s", cbor::Value::Text(proof.secret.to_string())),
+                    ("c", cbor::Value::Bytes(proof.c.to_bytes().to_vec())),
+                ];
+                if let Some(dleq) = &proof.dleq {
+                    let key = |key: &SecretKey| cbor::Value::Bytes(key.to_secret_bytes().to_vec());
+                    fields.push((
+                        "d",
+                        cbor::Value::map([
+                            ("e", key(&dleq.e)),
+                            ("s", key(&dleq.s)),
+                            ("r", key(&dleq.r)),
+                        ]),
+                    ));
+                }
+                if let Some(witness) = &proof.witness {
+                    fields.push(("w", cbor::Value::Text(witness.to_string())));
+                }
+                cbor::Value::map(fields)
+            });
+
+            keysets.push(cbor::Value::map([
+                ("i", cbor::Value::Bytes(id)),
+                ("p", cbor::Value::Array(proofs.collect())),
+            ]));
+        }
+
+        let mut fields = vec![
+            ("m", cbor::Value::Text(self.mint_url.to_string())),
+            ("u", cbor::Value::Text(self.unit.to_string())),
+        ];
+        if let Some(memo) = &self.memo {
+            fields.push(("d", cbor::Value::Text(memo.clone())));
+        }
+        fields.push(("t", cbor::Value::Array(keysets)));
+
+        Ok(cbor::Value::map(fields))
+    }
+
+    fn from_cbor(value: &cbor::Value) -> Result<Self, Error> {
+        fn field<'a>(value: &'a cbor::Value, key: &'static str) -> Result<&'a cbor::Value, Error> {
+            value.get(key).ok_or(Error::InvalidTokenV4(key))
+        }
+        fn text<'a>(value: &'a cbor::Value, key: &'static str) -> Result<&'a str, Error> {
+            field(value, key)?
+                .as_text()
+                .ok_or(Error::InvalidTokenV4(key))
+        }
+        fn bytes<'a>(value: &'a cbor::Value, key: &'static str) -> Result<&'a [u8], Error> {
+            field(value, key)?
+                .as_bytes()
+                .ok_or(Error::InvalidTokenV4(key))
+        }
+        fn array<'a>(
+            value: &'a cbor::Value,
+            key: &'static str,
+        ) -> Result<&'a [cbor::Value], Error> {
+            field(value, key)?
+                .as_array()
+                .ok_or(Error::InvalidTokenV4(key))
+        }
+        fn secret_key(value: &cbor::Value, key: &'static str) -> Result<SecretKey, Error> {
+            SecretKey::from_slice(bytes(value, key)?).map_err(|_| Error::InvalidTokenV4("d"))
+        }
+
+        let memo = match value.get("d") {
+            Some(memo) => Some(memo.as_text().ok_or(Error::InvalidTokenV4("d"))?),
+            None => None,
+        };
+
+        let mut token = Vec::new();
+        for keyset in array(value, "t")? {
+            let id = bytes(keyset, "i")?;
+            if id.len() != 8 {
+                return Err(nut02::Error::Length.into());
+            }
+            let keyset_id = Id::from_str(&hex::encode(id))?;
+
+            let mut proofs = Vec::new();
+            for proof in array(keyset, "p")? {
+                let dleq = match proof.get("d") {
+                    Some(dleq) => Some(ProofDleq::new(
+                        secret_key(dleq, "e")?,
+                        secret_key(dleq, "s")?,
+                        secret_key(dleq, "r")?,
+                    )),
+                    None => None,
+                };
+                let witness = match proof.get("w") {
+                    Some(witness) => Some(Witness::from_str(
+                        witness.as_text().ok_or(Error::InvalidTokenV4("w"))?,
+                    )?),
+                    None => None,
+                };
+
+                proofs.push(ProofV4 {
+                    amount: field(proof, "a")?
+                        .as_u64()
+                        .ok_or(Error::InvalidTokenV4("a"))?
+                        .into(),
+                    secret: Secret::new(text(proof, "s")?),
+                    c: PublicKey::from_slice(bytes(proof, "c")?)
+                        .map_err(|_| Error::InvalidTokenV4("c"))?,
+                    witness,
+                    dleq,
+                });
+            }
+
+            token.push(TokenV4Keyset { keyset_id, proofs });
+        }
+
+        Ok(Self {
+            mint_url: UncheckedUrl::new(text(value, "m")?),
+            unit: CurrencyUnit::from(text(value, "u")?),
+            memo: memo.map(str::to_string),
+            token,
+        })
+    }
+}
+
+impl FromStr for TokenV4 {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let decoded = decode_token_base64(s, "cashuB", &alphabet::URL_SAFE)?;
+
+        let token = Self::from_cbor(&cbor::decode(&decoded)?)?;
+        token.validate()?;
+        Ok(token)
+    }
+}
+
+impl fmt::Display for TokenV4 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cbor = self.to_cbor().map_err(|_| fmt::Error)?;
+        let encoded = general_purpose::URL_SAFE_NO_PAD.encode(cbor::encode(&cbor));
+        write!(f, "cashuB{}", encoded)
+    }
+}
+
+impl From<TokenV4> for Token {
+    fn from(token: TokenV4) -> Self {
+        Self {
+            token: vec![MintProofs::new(token.mint_url.clone(), token.proofs())],
+            memo: token.memo,
+            unit: Some(token.unit),
+        }
+    }
+}
+
+impl TryFrom<Token> for TokenV4 {
+    type Error = Error;
+
+    fn try_from(token: Token) -> Result<Self, Self::Error> {
+        let unit = token.unit();
+        let mut mints = token.token.into_iter();
+        let mint_proofs = mints.next().ok_or(Error::ProofsRequired)?;
+        if mints.next().is_some() {
+            return Err(Error::MultipleMints);
+        }
+
+        Self::new(mint_proofs.mint, mint_proofs.proofs, token.memo, unit)
+    }
+}
+
+/// Generation of field names on the wire
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Vintage {
+    /// Field names of the v1 API
+    #[default]
+    Current,
+    /// Field names of wallets predating the v1 API
+    Legacy,
+}
+
+/// Body whose fields were renamed by the v1 API
+///
+/// Either name is accepted when parsing; [LegacyFields::to_json_vintage]
+/// picks the names that are emitted.
+pub trait LegacyFields: Serialize {
+    /// `(current, legacy)` names of the renamed fields
+    const RENAMED: &'static [(&'static str, &'static str)];
+
+    /// JSON of `self` with the field names of `vintage`
+    fn to_json_vintage(&self, vintage: Vintage) -> Result<serde_json::Value, serde_json::Error> {
+        let mut value = serde_json::to_value(self)?;
+
+        if let (Vintage::Legacy, Some(object)) = (vintage, value.as_object_mut()) {
+            for (current, legacy) in Self::RENAMED {
+                if let Some(field) = object.remove(*current) {
+                    object.insert(legacy.to_string(), field);
+                }
+            }
+        }
+
+        Ok(value)
+    }
+}
+
+/// How unknown fields in a request body are treated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Strictness {
+    /// Unknown fields are ignored
+    #[default]
+    Permissive,
+    /// Unknown fields are an error naming the field
+    Strict,
+}
+
+/// Body a wallet sends to the mint
+///
+/// Servers choose the [Strictness] they parse with; the derived
+/// [Deserialize] impls are always permissive.
+pub trait Request: Serialize + DeserializeOwned {
+    /// `(current, legacy)` names accepted for the same field
+    const ALIASES: &'static [(&'static str, &'static str)] = &[];
+
+    /// Parse a JSON body with the given [Strictness]
+    ///
+    /// Bodies over [MAX_REQUEST_LEN] are refused unparsed.
+    fn parse_request(json: &str, strictness: Strictness) -> Result<Self, Error> {
+        if json.len() > MAX_REQUEST_LEN {
+            return Err(Error::InputTooLarge {
+                len: json.len(),
+                max: MAX_REQUEST_LEN,
+            });
+        }
+
+        let request: Self = serde_json::from_str(json)?;
+
+        if strictness == Strictness::Strict {
+            let mut received: serde_json::Value = serde_json::from_str(json)?;
+            if let Some(object) = received.as_object_mut() {
+                for (current, legacy) in Self::ALIASES {
+                    if let Some(field) = object.remove(*legacy) {
+                        object.insert(current.to_string(), field);
+                    }
+                }
+            }
+
+            if let Some(field) = unknown_field(&received, &serde_json::to_value(&request)?) {
+                return Err(Error::UnknownField(field));
+            }
+        }
+
+        Ok(request)
+    }
+}
+
+/// Path of the first field of `received` that did not survive a round trip
+///
+/// `known` is the reserialized request, so every field serde understood is
+/// present in it unless it was `null` or skipped as empty.
+fn unknown_field(received: &serde_json::Value, known: &serde_json::Value) -> Option<String> {
+    use serde_json::Value;
+
+    match (received, known) {
+        (Value::Object(received), Value::Object(known)) => {
+            received
+                .iter()
+                .find_map(|(key, value)| match known.get(key) {
+                    Some(known) => unknown_field(value, known).map(|path| format!("{key}.{path}")),
+                    None if is_empty(value) => None,
+                    None => Some(key.clone()),
+                })
+        }
+        (Value::Array(received), Value::Array(known)) => received
+            .iter()
+            .zip(known)
+            .enumerate()
+            .find_map(|(i, (value, known))| {
+                unknown_field(value, known).map(|path| format!("{i}.{path}"))
+            }),
+        _ => None,
+    }
+}
+
+/// Value a skipped field may legitimately have been sent with
+fn is_empty(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Null => true,
+        serde_json::Value::Array(values) => values.is_empty(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn proof(amount: u64, keyset_id: &str) -> Proof {
+        Proof::new(
+            Amount::from(amount),
+            Id::from_str(keyset_id).unwrap(),
+            Secret::generate(),
+            PublicKey::from_str(
+                "02194603ffa36356f4a56b7df9371fc3192472351453ec7398b8da8117e7c3e104",
+            )
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_proofs_methods() {
+        let mut proofs: Proofs = vec![
+            proof(1, "009a1f293253e41e"),
+            proof(2, "009a1f293253e41e"),
+            proof(4, "00ad268c4d1f5826"),
+        ];
+
+        assert_eq!(proofs.total_amount().unwrap(), Amount::from(7));
+
+        // One `Y` cached before, the others computed in a batch and cached
+        proofs[1].y().unwrap();
+        let ys = proofs.ys().unwrap();
+        assert_eq!(ys.len(), 3);
+        for (proof, y) in proofs.iter().zip(&ys) {
+            assert_eq!(*y, hash_to_curve(proof.secret.as_bytes()).unwrap());
+            assert_eq!(proof.y.0.get(), Some(&(proof.secret.clone(), *y)));
+        }
+
+        // A stale cached `Y` is not used
+        let mut changed = proofs.clone();
+        changed[2].secret = Secret::generate();
+        assert_eq!(
+            changed.ys().unwrap()[2],
+            hash_to_curve(changed[2].secret.as_bytes()).unwrap()
+        );
+
+        let counts = proofs.count_by_keyset();
+        assert_eq!(counts[&Id::from_str("009a1f293253e41e").unwrap()], 2);
+        assert_eq!(counts[&Id::from_str("00ad268c4d1f5826").unwrap()], 1);
+
+        let mut duplicate = proofs[1].clone();
+        duplicate.amount = Amount::from(8);
+        proofs.push(duplicate);
+        proofs.dedup_by_secret();
+        assert_eq!(proofs.len(), 3);
+        assert_eq!(proofs.total_amount().unwrap(), Amount::from(7));
+    }
+
+    #[test]
+    fn test_proof_identity_by_y() {
+        let original = proof(2, "009a1f293253e41e");
+        let mut restyled = original.clone();
+        restyled.keyset_id = Id::from_str("00ad268c4d1f5826").unwrap();
+        restyled.dleq = Some(ProofDleq::new(
+            SecretKey::generate(),
+            SecretKey::generate(),
+            SecretKey::generate(),
+        ));
+        let other = proof(2, "009a1f293253e41e");
+
+        // Structurally different but the same ecash
+        assert_ne!(original, restyled);
+        assert_eq!(original.y().unwrap(), restyled.y().unwrap());
+        assert_ne!(original.y().unwrap(), other.y().unwrap());
+
+        let mut proofs: Proofs = vec![original.clone(), other.clone(), restyled];
+        proofs.dedup_by_y().unwrap();
+        assert_eq!(proofs, vec![original.clone(), other]);
+
+        // The cached `Y` follows a changed secret
+        let mut changed = original.clone();
+        changed.secret = Secret::generate();
+        assert_eq!(
+            changed.y().unwrap(),
+            hash_to_curve(changed.secret.as_bytes()).unwrap()
+        );
+        assert_eq!(
+            serde_json::to_string(&original).unwrap(),
+            serde_json::to_string(&Proof::new(
+                original.amount,
+                original.keyset_id,
+                original.secret.clone(),
+                original.c
+            ))
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_proofs_methods_empty() {
+        let mut proofs: Proofs = vec![];
+
+        assert_eq!(proofs.total_amount().unwrap(), Amount::ZERO);
+        assert!(proofs.ys().unwrap().is_empty());
+        assert!(proofs.count_by_keyset().is_empty());
+        proofs.dedup_by_secret();
+        assert!(proofs.is_empty());
+    }
+
+    #[test]
+    fn test_proofs_total_amount_overflow() {
+        let proofs: Proofs = vec![
+            proof(u64::MAX, "009a1f293253e41e"),
+            proof(1, "009a1f293253e41e"),
+        ];
+
+        assert!(matches!(proofs.total_amount(), Err(Error::AmountOverflow)));
+    }
+
+    #[test]
+    fn test_p2pk_witness_deserialize() {
+        let proof = r#"{"amount":1,"secret":"[\"P2PK\",{\"nonce\":\"859d4935c4907062a6297cf4e663e2835d90d97ecdd510745d32f6816323a41f\",\"data\":\"0249098aa8b9d2fbec49ff8598feb17b592b986e62319a4fa488a3dc36387157a7\",\"tags\":[[\"sigflag\",\"SIG_INPUTS\"]]}]","C":"02698c4e2b5f9534cd0687d87513c759790cf829aa5739184a3e3735471fbda904","id":"009a1f293253e41e","witness":"{\"signatures\":[\"60f3c9b766770b46caac1d27e1ae6b77c8866ebaeba0b9489fe6a15a837eaa6fcd6eaa825499c72ac342983983fd3ba3a8a41f56677cc99ffd73da68b59e1383\"]}"}"#;
+        let proof: Proof = serde_json::from_str(proof).unwrap();
+
+        let witness = proof.witness.clone().unwrap();
+        assert_eq!(
+            witness,
+            Witness::P2PKWitness(P2PKWitness {
+                signatures: vec!["60f3c9b766770b46caac1d27e1ae6b77c8866ebaeba0b9489fe6a15a837eaa6fcd6eaa825499c72ac342983983fd3ba3a8a41f56677cc99ffd73da68b59e1383".to_string()]
+            })
+        );
+        assert!(witness.preimage().is_none());
+
+        // Round trip keeps the witness as a stringified JSON object
+        let json = serde_json::to_value(&proof).unwrap();
+        assert!(json["witness"].is_string());
+        let round_trip: Proof = serde_json::from_value(json).unwrap();
+        assert_eq!(round_trip.witness, proof.witness);
+    }
+
+    #[test]
+    fn test_htlc_witness_deserialize() {
+        let witness = r#""{\"preimage\":\"0000000000000000000000000000000000000000000000000000000000000000\",\"signatures\":[\"60f3c9b766770b46caac1d27e1ae6b77c8866ebaeba0b9489fe6a15a837eaa6fcd6eaa825499c72ac342983983fd3ba3a8a41f56677cc99ffd73da68b59e1383\"]}""#;
+        let witness: Witness = serde_json::from_str(witness).unwrap();
+
+        assert_eq!(
+            witness.preimage(),
+            Some(&"0000000000000000000000000000000000000000000000000000000000000000".to_string())
+        );
+        assert_eq!(witness.signatures().map(|s| s.len()), Some(1));
+
+        let witness = r#""{\"preimage\":\"0000000000000000000000000000000000000000000000000000000000000000\"}""#;
+        let witness: Witness = serde_json::from_str(witness).unwrap();
+        assert!(matches!(witness, Witness::HTLCWitness(_)));
+        assert!(witness.signatures().is_none());
+        assert_eq!(
+            serde_json::to_string(&witness).unwrap(),
+            r#""{\"preimage\":\"0000000000000000000000000000000000000000000000000000000000000000\"}""#
+        );
+    }
+
+    #[test]
+    fn test_malformed_witness() {
+        let proof = r#"{"amount":1,"secret":"407915bc212be61a77e3e6d2aeb4c727980bda51cd06a6afc29e2861768a7837","C":"02bc9097997d81afb2cc7346b5e4345a9346bd2a506eb7958598a72f0cf85163ea","id":"009a1f293253e41e","witness":"{\"signatures\":[\"60f3c9b7\""}"#;
+        assert!(serde_json::from_str::<Proof>(proof).is_err());
+
+        // String containing invalid JSON
+        assert!(serde_json::from_str::<Witness>(r#""{\"signatures\":""#).is_err());
+        assert!(serde_json::from_str::<Witness>(r#""not json""#).is_err());
+
+        // Neither a string nor an object
+        assert!(serde_json::from_str::<Witness>("[]").is_err());
+        assert!(serde_json::from_str::<Witness>("1").is_err());
+
+        assert!(Witness::from_str("{\"preimage\":1}").is_err());
+    }
+
+    #[test]
+    fn test_witness_object_form() {
+        let proof = r#"{"amount":1,"secret":"407915bc212be61a77e3e6d2aeb4c727980bda51cd06a6afc29e2861768a7837","C":"02bc9097997d81afb2cc7346b5e4345a9346bd2a506eb7958598a72f0cf85163ea","id":"009a1f293253e41e","witness":{"signatures":["60f3c9b7"]}}"#;
+        let proof: Proof = serde_json::from_str(proof).unwrap();
+
+        assert_eq!(
+            proof.witness,
+            Some(Witness::P2PKWitness(P2PKWitness {
+                signatures: vec!["60f3c9b7".to_string()]
+            }))
+        );
+
+        // Default serialization is the stringified form
+        let json = serde_json::to_value(&proof).unwrap();
+        assert_eq!(json["witness"], r#"{"signatures":["60f3c9b7"]}"#);
+
+        #[derive(Serialize, Deserialize)]
+        struct ObjectWitness {
+            #[serde(with = "witness_object")]
+            witness: Option<Witness>,
+        }
+
+        let object = ObjectWitness {
+            witness: proof.witness.clone(),
+        };
+        let json = serde_json::to_string(&object).unwrap();
+        assert_eq!(json, r#"{"witness":{"signatures":["60f3c9b7"]}}"#);
+
+        let object: ObjectWitness = serde_json::from_str(&json).unwrap();
+        assert_eq!(object.witness, proof.witness);
+
+        let object: ObjectWitness =
+            serde_json::from_str(r#"{"witness":"{\"signatures\":[\"60f3c9b7\"]}"}"#).unwrap();
+        assert_eq!(object.witness, proof.witness);
+
+        let object: ObjectWitness = serde_json::from_str(r#"{"witness":null}"#).unwrap();
+        assert!(object.witness.is_none());
+    }
+
+    #[test]
+    fn test_witness_escaping() {
+        // Signature with characters that must be escaped inside the embedded string
+        let signature = "quote\" backslash\\ newline\n unicode\u{e9}".to_string();
+        let witness = Witness::P2PKWitness(P2PKWitness {
+            signatures: vec![signature.clone()],
+        });
+
+        let json = serde_json::to_string(&witness).unwrap();
+        let decoded: Witness = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, witness);
+
+        // The outer string holds the inner JSON document
+        let inner: String = serde_json::from_str(&json).unwrap();
+        let inner: serde_json::Value = serde_json::from_str(&inner).unwrap();
+        assert_eq!(inner["signatures"][0], signature.as_str());
+
+        // Double encoding round trips
+        let double = serde_json::to_string(&json).unwrap();
+        let single: String = serde_json::from_str(&double).unwrap();
+        assert_eq!(single, json);
+        assert_eq!(serde_json::from_str::<Witness>(&single).unwrap(), witness);
+    }
+
+    #[test]
+    fn test_currency_unit_round_trip() {
+        for (unit, s) in [
+            (CurrencyUnit::Sat, "sat"),
+            (CurrencyUnit::Msat, "msat"),
+            (CurrencyUnit::Usd, "usd"),
+            (CurrencyUnit::Eur, "eur"),
+        ] {
+            assert_eq!(CurrencyUnit::from_str(s).unwrap(), unit);
+            assert_eq!(unit.to_string(), s);
+            assert_eq!(serde_json::to_string(&unit).unwrap(), format!("\"{s}\""));
+        }
+
+        // Unknown units are kept verbatim
+        for s in ["btc", "SAT", "gbp ", "ünit"] {
+            let unit: CurrencyUnit = serde_json::from_str(&format!("\"{s}\"")).unwrap();
+            assert_eq!(unit, CurrencyUnit::Custom(s.to_string()));
+            assert_eq!(unit.to_string(), s);
+            assert_eq!(
+                serde_json::from_str::<CurrencyUnit>(&serde_json::to_string(&unit).unwrap())
+                    .unwrap(),
+                unit
+            );
+        }
+    }
+
+    #[test]
+    fn test_currency_unit_derivation_index() {
+        let indexes: HashSet<u32> = [
+            CurrencyUnit::Sat,
+            CurrencyUnit::Msat,
+            CurrencyUnit::Usd,
+            CurrencyUnit::Eur,
+        ]
+        .iter()
+        .flat_map(|unit| unit.derivation_index())
+        .collect();
+
+        assert_eq!(indexes.len(), 4);
+        assert!(CurrencyUnit::Custom("btc".to_string())
+            .derivation_index()
+            .is_none());
+    }
+
+    #[test]
+    fn test_proof_serialize() {
+        let proof = "[{\"id\":\"009a1f293253e41e\",\"amount\":2,\"secret\":\"407915bc212be61a77e3e6d2aeb4c727980bda51cd06a6afc29e2861768a7837\",\"C\":\"02bc9097997d81afb2cc7346b5e4345a9346bd2a506eb7958598a72f0cf85163ea\"},{\"id\":\"009a1f293253e41e\",\"amount\":8,\"secret\":\"fe15109314e61d7756b0f8ee0f23a624acaa3f4e042f61433c728c7057b931be\",\"C\":\"029e8e5050b890a7d6c0968db16bc1d5d5fa040ea1de284f6ec69d61299f671059\"}]";
+        let proof: Proofs = serde_json::from_str(proof).unwrap();
+
+        assert_eq!(
+            proof[0].clone().keyset_id,
+            Id::from_str("009a1f293253e41e").unwrap()
+        );
+
+        assert_eq!(proof.len(), 2);
+    }
+
+    #[test]
+    fn test_unknown_fields_are_ignored() {
+        let proof = r#"{"id":"009a1f293253e41e","amount":2,"secret":"407915bc212be61a77e3e6d2aeb4c727980bda51cd06a6afc29e2861768a7837","C":"02bc9097997d81afb2cc7346b5e4345a9346bd2a506eb7958598a72f0cf85163ea","future_field":{"nested":[1,2]}}"#;
+        let proof: Proof = serde_json::from_str(proof).unwrap();
+        assert_eq!(proof.amount, Amount::from(2));
+
+        let output = r#"{"amount":2,"id":"009a1f293253e41e","B_":"02634a2c2b34bec9e8a4aba4361f6bf202d7fa2365379b0840afe249a7a9d71239","future_field":true}"#;
+        let output: BlindedMessage = serde_json::from_str(output).unwrap();
+        assert_eq!(output.keyset_id, proof.keyset_id);
+
+        let signature = r#"{"amount":8,"id":"00ffd48b8f5ecf80","C_":"02e9701b804dc05a5294b5a580b428237a27c7ee1690a0177868016799b1761c81","future_field":"x"}"#;
+        let signature: BlindSignature = serde_json::from_str(signature).unwrap();
+        assert_eq!(signature.amount, Amount::from(8));
+    }
+
+    #[test]
+    fn test_token_str_round_trip() {
         let token_str = "cashuAeyJ0b2tlbiI6W3sibWludCI6Imh0dHBzOi8vODMzMy5zcGFjZTozMzM4IiwicHJvb2ZzIjpbeyJhbW91bnQiOjIsImlkIjoiMDA5YTFmMjkzMjUzZTQxZSIsInNlY3JldCI6IjQwNzkxNWJjMjEyYmU2MWE3N2UzZTZkMmFlYjRjNzI3OTgwYmRhNTFjZDA2YTZhZmMyOWUyODYxNzY4YTc4MzciLCJDIjoiMDJiYzkwOTc5OTdkODFhZmIyY2M3MzQ2YjVlNDM0NWE5MzQ2YmQyYTUwNmViNzk1ODU5OGE3MmYwY2Y4NTE2M2VhIn0seyJhbW91bnQiOjgsImlkIjoiMDA5YTFmMjkzMjUzZTQxZSIsInNlY3JldCI6ImZlMTUxMDkzMTRlNjFkNzc1NmIwZjhlZTBmMjNhNjI0YWNhYTNmNGUwNDJmNjE0MzNjNzI4YzcwNTdiOTMxYmUiLCJDIjoiMDI5ZThlNTA1MGI4OTBhN2Q2YzA5NjhkYjE2YmMxZDVkNWZhMDQwZWExZGUyODRmNmVjNjlkNjEyOTlmNjcxMDU5In1dfV0sInVuaXQiOiJzYXQiLCJtZW1vIjoiVGhhbmsgeW91LiJ9";
 
         let token = Token::from_str(token_str).unwrap();
@@ -599,6 +1944,607 @@ mod tests {
         assert_eq!(token_data, token);
     }
 
+    #[test]
+    fn test_token_validate() {
+        let mint = UncheckedUrl::from_str("https://8333.space:3338").unwrap();
+        let other_mint = UncheckedUrl::from_str("https://mint.example").unwrap();
+        let token = |entries: Vec<MintProofs>| Token {
+            token: entries,
+            memo: None,
+            unit: None,
+        };
+
+        let valid = token(vec![
+            MintProofs::new(
+                mint.clone(),
+                vec![proof(2, "009a1f293253e41e"), proof(8, "009a1f293253e41e")],
+            ),
+            MintProofs::new(other_mint.clone(), vec![proof(5, "00ad268c4d1f5826")]),
+        ]);
+        assert_eq!(valid.value().unwrap(), Amount::from(15));
+        assert_eq!(valid.unit(), CurrencyUnit::Sat);
+        assert!(valid.validate().is_ok());
+        assert_eq!(Token::from_str(&valid.to_string()).unwrap(), valid);
+
+        let usd = Token {
+            unit: Some(CurrencyUnit::Usd),
+            ..valid.clone()
+        };
+        assert_eq!(usd.unit(), CurrencyUnit::Usd);
+
+        // Values of tokens of different units do not add up
+        let sats = valid.unit_value().unwrap();
+        assert_eq!(sats, UnitAmount::sat(Amount::from(15)));
+        assert!(matches!(
+            sats.checked_add(&usd.unit_value().unwrap()),
+            Err(crate::amount::Error::UnitMismatch(..))
+        ));
+
+        // Displayed as is, refused when parsed
+        let parse = |token: Token| {
+            let encoded = token.to_string();
+            assert!(Token::from_legacy_str(&encoded).is_err());
+            Token::from_str(&encoded).unwrap_err()
+        };
+
+        assert!(matches!(parse(token(vec![])), Error::ProofsRequired));
+
+        let empty_entry = token(vec![
+            MintProofs::new(mint.clone(), vec![proof(2, "009a1f293253e41e")]),
+            MintProofs::new(other_mint.clone(), vec![]),
+        ]);
+        assert!(matches!(
+            parse(empty_entry),
+            Error::EmptyMintProofs(url) if url == other_mint
+        ));
+
+        let zero = token(vec![MintProofs::new(
+            mint.clone(),
+            vec![proof(2, "009a1f293253e41e"), proof(0, "009a1f293253e41e")],
+        )]);
+        assert!(matches!(parse(zero), Error::ZeroAmountProof));
+
+        let overflow = token(vec![
+            MintProofs::new(mint.clone(), vec![proof(u64::MAX, "009a1f293253e41e")]),
+            MintProofs::new(other_mint, vec![proof(1, "00ad268c4d1f5826")]),
+        ]);
+        assert!(matches!(overflow.value(), Err(Error::AmountOverflow)));
+        assert!(matches!(parse(overflow), Error::AmountOverflow));
+    }
+
+    #[test]
+    fn test_token_dleq() {
+        // Proof of `nut12::tests::test_proof_dleq`, signed with the key 1
+        let proof: Proof = serde_json::from_str(r#"{"amount": 1,"id": "00882760bfa2eb41","secret": "daf4dd00a2b68a0858a80450f52c8a7d2ccf87d375e43e216e0c571f089f63e9","C": "024369d2d22a80ecf78f3937da9d5f30c1b9f74f0c32684d583cca0fa6a61cdcfc","dleq": {"e": "b31e58ac6527f34975ffab13e70a48b6d2b0d35abc4b03f0151f09ee1a9763d4","s": "8fbae004c59e754d71df67e392b6ae4e29293113ddc2ec86592a0431d16306d8","r": "a6d13fcd7a18442e6076f5e1e7c887ad5de40a019824bdfa9fe740d302e8d861"}}"#).unwrap();
+        let mint_key = PublicKey::from_str(
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        )
+        .unwrap();
+
+        let mut token = Token::new(
+            UncheckedUrl::from_str("https://8333.space:3338").unwrap(),
+            vec![proof],
+            None,
+            Some(CurrencyUnit::Sat),
+        )
+        .unwrap();
+
+        // Included DLEQ proofs survive the round trip and verify offline
+        let included = Token::from_str(&token.to_string()).unwrap();
+        assert_eq!(included, token);
+        assert!(included.token[0].proofs[0].verify_dleq(mint_key).is_ok());
+
+        token.strip_dleq();
+        let encoded = token.to_string();
+        assert!(encoded.len() < included.to_string().len());
+
+        let stripped = Token::from_str(&encoded).unwrap();
+        assert_eq!(stripped, token);
+        assert_eq!(stripped.token[0].proofs[0].dleq, None);
+        assert_eq!(stripped.token[0].proofs[0].c, included.token[0].proofs[0].c);
+    }
+
+    #[test]
+    fn test_token_v4_spec_vector() {
+        let token_str = "cashuBpGF0gaJhaUgArSaMTR9YJmFwgaNhYQFhc3hAOWE2ZGJiODQ3YmQyMzJiYTc2ZGIwZGYxOTcyMTZiMjlkM2I4Y2MxNDU1M2NkMjc4MjdmYzFjYzk0MmZlZGI0ZWFjWCEDhhhUP_trhpXfStS6vN6So0qWvc2X3O4NfM-Y1HISZ5JhZGlUaGFuayB5b3VhbXVodHRwOi8vbG9jYWxob3N0OjMzMzhhdWNzYXQ=";
+
+        let token = TokenV4::from_str(token_str).unwrap();
+        assert_eq!(
+            token.mint_url,
+            UncheckedUrl::from_str("http://localhost:3338").unwrap()
+        );
+        assert_eq!(token.unit, CurrencyUnit::Sat);
+        assert_eq!(token.memo.as_deref(), Some("Thank you"));
+
+        let proofs = token.proofs();
+        assert_eq!(proofs.len(), 1);
+        assert_eq!(
+            proofs[0].keyset_id,
+            Id::from_str("00ad268c4d1f5826").unwrap()
+        );
+        assert_eq!(proofs[0].amount, Amount::from(1));
+        assert_eq!(
+            proofs[0].secret,
+            Secret::new("9a6dbb847bd232ba76db0df197216b29d3b8cc14553cd27827fc1cc942fedb4e")
+        );
+        assert_eq!(
+            proofs[0].c,
+            PublicKey::from_hex(
+                "038618543ffb6b8695df4ad4babcde92a34a96bdcd97dcee0d7ccf98d472126792"
+            )
+            .unwrap()
+        );
+
+        // Written with the keys in a different order, read back the same
+        let encoded = token.to_string();
+        assert!(encoded.starts_with("cashuB"));
+        assert!(!encoded.contains('='));
+        assert_eq!(TokenV4::from_str(&encoded).unwrap(), token);
+
+        // Parsed as a V3 token with the same proofs
+        let v3 = Token::from_str(token_str).unwrap();
+        assert_eq!(v3, Token::from(token.clone()));
+        assert_eq!(TokenV4::try_from(v3).unwrap(), token);
+    }
+
+    #[test]
+    fn test_token_v4_groups_by_keyset() {
+        let keyset_ids = ["00ad268c4d1f5826", "009a1f293253e41e", "00882760bfa2eb41"];
+        let mut proofs: Proofs = (0..50)
+            .map(|i| proof(1 << (i % 8), keyset_ids[i % 3]))
+            .collect();
+        // Optional fields survive the grouping
+        let dleq_proof: Proof = serde_json::from_str(r#"{"amount": 1,"id": "00882760bfa2eb41","secret": "daf4dd00a2b68a0858a80450f52c8a7d2ccf87d375e43e216e0c571f089f63e9","C": "024369d2d22a80ecf78f3937da9d5f30c1b9f74f0c32684d583cca0fa6a61cdcfc","dleq": {"e": "b31e58ac6527f34975ffab13e70a48b6d2b0d35abc4b03f0151f09ee1a9763d4","s": "8fbae004c59e754d71df67e392b6ae4e29293113ddc2ec86592a0431d16306d8","r": "a6d13fcd7a18442e6076f5e1e7c887ad5de40a019824bdfa9fe740d302e8d861"}}"#).unwrap();
+        proofs[7] = dleq_proof;
+        proofs[10].witness = Some(
+            P2PKWitness {
+                signatures: vec!["60f3c9b766770b46caac1d27e1ae6b77c8866ebaeba0b9489fe6a15a837eaa6fcd6eaa825499c72ac342983983fd3ba3a8a41f56677cc99ffd73da68b59e1383".to_string()],
+            }
+            .into(),
+        );
+
+        let mint_url = UncheckedUrl::from_str("https://8333.space:3338").unwrap();
+        let token = TokenV4::new(
+            mint_url.clone(),
+            proofs.clone(),
+            Some("split".to_string()),
+            CurrencyUnit::Sat,
+        )
+        .unwrap();
+
+        let grouped: Vec<String> = token
+            .token
+            .iter()
+            .map(|k| k.keyset_id.to_string())
+            .collect();
+        assert_eq!(
+            grouped,
+            ["00882760bfa2eb41", "009a1f293253e41e", "00ad268c4d1f5826"]
+        );
+        assert_eq!(token.value().unwrap(), proofs.total_amount().unwrap());
+
+        // Lossless, only reordered by keyset
+        let mut expected = proofs.clone();
+        expected.sort_by_key(|p| p.keyset_id.to_string());
+        assert_eq!(token.proofs(), expected);
+        for (got, want) in token.proofs().iter().zip(&expected) {
+            assert_eq!((&got.witness, &got.dleq), (&want.witness, &want.dleq));
+        }
+
+        let parsed = TokenV4::from_str(&token.to_string()).unwrap();
+        assert_eq!(parsed, token);
+        assert_eq!(parsed.proofs(), expected);
+
+        // Each extra group of the naive encoding repeats its keyset id and
+        // the `i` and `p` keys, 15 bytes, and the 50 groups need a longer
+        // array head
+        let naive = TokenV4 {
+            token: proofs
+                .iter()
+                .map(|p| TokenV4Keyset {
+                    keyset_id: p.keyset_id,
+                    proofs: vec![p.clone().into()],
+                })
+                .collect(),
+            ..token.clone()
+        };
+        let grouped_len = cbor::encode(&token.to_cbor().unwrap()).len();
+        let naive_len = cbor::encode(&naive.to_cbor().unwrap()).len();
+        assert_eq!(naive_len - grouped_len, 47 * 15 + 1);
+        assert!(token.to_string().len() < naive.to_string().len());
+    }
+
+    #[test]
+    fn test_token_v4_invalid() {
+        let mint_url = UncheckedUrl::from_str("https://8333.space:3338").unwrap();
+        let encode = |value: cbor::Value| {
+            format!(
+                "cashuB{}",
+                general_purpose::URL_SAFE_NO_PAD.encode(cbor::encode(&value))
+            )
+        };
+        let token_with_id = |id: Vec<u8>| {
+            let token = TokenV4::new(
+                mint_url.clone(),
+                vec![proof(1, "009a1f293253e41e")],
+                None,
+                CurrencyUnit::Sat,
+            )
+            .unwrap();
+            let cbor::Value::Map(mut fields) = token.to_cbor().unwrap() else {
+                unreachable!()
+            };
+            let group = cbor::Value::map([
+                ("i", cbor::Value::Bytes(id)),
+                (
+                    "p",
+                    token
+                        .to_cbor()
+                        .unwrap()
+                        .get("t")
+                        .unwrap()
+                        .as_array()
+                        .unwrap()[0]
+                        .get("p")
+                        .unwrap()
+                        .clone(),
+                ),
+            ]);
+            fields.retain(|(k, _)| k.as_text() != Some("t"));
+            fields.push((
+                cbor::Value::Text("t".to_string()),
+                cbor::Value::Array(vec![group]),
+            ));
+            encode(cbor::Value::Map(fields))
+        };
+
+        // A group id is validated like any other keyset id
+        assert!(
+            TokenV4::from_str(&token_with_id(hex::decode("009a1f293253e41e").unwrap())).is_ok()
+        );
+        assert!(matches!(
+            TokenV4::from_str(&token_with_id(hex::decode("009a1f293253e4").unwrap())),
+            Err(Error::NUT02(nut02::Error::Length))
+        ));
+        assert!(matches!(
+            TokenV4::from_str(&token_with_id(hex::decode("019a1f293253e41e").unwrap())),
+            Err(Error::NUT02(nut02::Error::UnknownVersion(_)))
+        ));
+
+        let empty_group = encode(cbor::Value::map([
+            ("m", cbor::Value::Text(mint_url.to_string())),
+            ("u", cbor::Value::Text("sat".to_string())),
+            (
+                "t",
+                cbor::Value::Array(vec![cbor::Value::map([
+                    (
+                        "i",
+                        cbor::Value::Bytes(hex::decode("009a1f293253e41e").unwrap()),
+                    ),
+                    ("p", cbor::Value::Array(vec![])),
+                ])]),
+            ),
+        ]));
+        assert!(matches!(
+            TokenV4::from_str(&empty_group),
+            Err(Error::EmptyKeysetProofs(id)) if id.to_string() == "009a1f293253e41e"
+        ));
+
+        let no_unit = encode(cbor::Value::map([
+            ("m", cbor::Value::Text(mint_url.to_string())),
+            ("t", cbor::Value::Array(vec![])),
+        ]));
+        assert!(matches!(
+            TokenV4::from_str(&no_unit),
+            Err(Error::InvalidTokenV4("u"))
+        ));
+        assert!(matches!(
+            Token::from_str("cashuBnotcbor"),
+            Err(Error::Base64Error(_) | Error::Cbor(_))
+        ));
+
+        assert!(matches!(
+            TokenV4::new(
+                mint_url.clone(),
+                vec![proof(1, "I2yN+iRYfkzT")],
+                None,
+                CurrencyUnit::Sat
+            ),
+            Err(Error::LegacyKeysetId(_))
+        ));
+
+        let mut two_mints =
+            Token::new(mint_url, vec![proof(1, "009a1f293253e41e")], None, None).unwrap();
+        two_mints
+            .add_mint(
+                UncheckedUrl::from_str("https://mint.example").unwrap(),
+                vec![proof(2, "009a1f293253e41e")],
+            )
+            .unwrap();
+        assert!(matches!(
+            TokenV4::try_from(two_mints),
+            Err(Error::MultipleMints)
+        ));
+    }
+
+    #[test]
+    fn test_token_pathological_inputs() {
+        let mint_url = UncheckedUrl::from_str("https://8333.space:3338").unwrap();
+        let proofs = vec![proof(1, "009a1f293253e41e"), proof(8, "009a1f293253e41e")];
+        let v3 = Token::new(mint_url.clone(), proofs.clone(), None, None)
+            .unwrap()
+            .to_string();
+        let v4 = TokenV4::new(mint_url.clone(), proofs, None, CurrencyUnit::Sat)
+            .unwrap()
+            .to_string();
+        let cashu_a = |bytes: &[u8]| format!("cashuA{}", general_purpose::STANDARD.encode(bytes));
+        let cashu_b =
+            |bytes: &[u8]| format!("cashuB{}", general_purpose::URL_SAFE_NO_PAD.encode(bytes));
+
+        // Every truncation is refused, padding aside
+        for encoded in [&v3, &v4] {
+            assert!(Token::from_str(encoded).is_ok());
+            for len in 0..encoded.trim_end_matches('=').len() {
+                assert!(Token::from_str(&encoded[..len]).is_err(), "{len}");
+                assert!(Token::from_legacy_str(&encoded[..len]).is_err(), "{len}");
+            }
+        }
+
+        let oversized = |prefix: &str| format!("{prefix}{}", "A".repeat(MAX_TOKEN_LEN));
+        for prefix in ["cashuA", "cashuB", "cashuC"] {
+            assert!(matches!(
+                Token::from_str(&oversized(prefix)),
+                Err(Error::InputTooLarge {
+                    max: MAX_TOKEN_LEN,
+                    ..
+                })
+            ));
+        }
+        assert!(matches!(
+            Token::from_legacy_str(&oversized("cashuA")),
+            Err(Error::InputTooLarge { .. })
+        ));
+
+        for (token, error) in [
+            ("", "unsupported"),
+            ("cashu", "unsupported"),
+            ("cashuA", "json"),
+            ("cashuB", "cbor"),
+            ("cashuA!!!!", "base64"),
+            ("cashuB\u{1f95c}", "base64"),
+        ] {
+            let parsed = Token::from_str(token);
+            assert!(
+                match error {
+                    "unsupported" => matches!(parsed, Err(Error::UnsupportedToken)),
+                    "json" => matches!(parsed, Err(Error::SerdeJsonError(_))),
+                    "cbor" => matches!(parsed, Err(Error::Cbor(cbor::Error::UnexpectedEnd))),
+                    _ => matches!(parsed, Err(Error::Base64Error(_))),
+                },
+                "{token:?}: {parsed:?}"
+            );
+        }
+
+        // Not UTF-8, nested past the JSON recursion limit
+        assert!(matches!(
+            Token::from_str(&cashu_a(&[0xff, 0xfe])),
+            Err(Error::Utf8ParseError(_))
+        ));
+        let nested = format!(r#"{{"token":{}"#, "[".repeat(100_000));
+        assert!(matches!(
+            Token::from_str(&cashu_a(nested.as_bytes())),
+            Err(Error::SerdeJsonError(_))
+        ));
+        assert!(matches!(
+            Token::from_legacy_str(&cashu_a(nested.as_bytes())),
+            Err(Error::SerdeJsonError(_))
+        ));
+
+        // CBOR claiming huge strings and containers, or nested too deep
+        for cbor in [
+            "7bffffffffffffffff",
+            "5b0000000100000000",
+            "9bffffffffffffffff",
+            "a1617480",
+            "a161749b7fffffffffffffff",
+        ] {
+            let parsed = Token::from_str(&cashu_b(&hex::decode(cbor).unwrap()));
+            assert!(
+                matches!(
+                    parsed,
+                    Err(Error::Cbor(cbor::Error::UnexpectedEnd) | Error::InvalidTokenV4(_))
+                ),
+                "{cbor}: {parsed:?}"
+            );
+        }
+        assert!(matches!(
+            Token::from_str(&cashu_b(&[0x81; 100_000])),
+            Err(Error::Cbor(cbor::Error::TooDeep(_)))
+        ));
+        let mut wide = vec![0x9a];
+        wide.extend_from_slice(&(cbor::MAX_ITEMS as u32).to_be_bytes());
+        wide.extend(std::iter::repeat(0xf6).take(cbor::MAX_ITEMS));
+        assert!(matches!(
+            Token::from_str(&cashu_b(&wide)),
+            Err(Error::Cbor(cbor::Error::TooManyItems(_)))
+        ));
+
+        // Keyset id of sixteen bytes splitting a char
+        let id = "0\u{e9}a1f293253e41e";
+        let json = format!(
+            r#"{{"token":[{{"mint":"{mint_url}","proofs":[{{"amount":1,"id":"{id}","secret":"00","C":"02194603ffa36356f4a56b7df9371fc3192472351453ec7398b8da8117e7c3e104"}}]}}]}}"#
+        );
+        assert!(matches!(
+            Token::from_str(&cashu_a(json.as_bytes())),
+            Err(Error::SerdeJsonError(_))
+        ));
+
+        let mut long_secret = proof(1, "009a1f293253e41e");
+        long_secret.secret = Secret::new("a".repeat(MAX_SECRET_LEN + 1));
+        for token in [
+            Token::new(mint_url.clone(), vec![long_secret.clone()], None, None)
+                .unwrap()
+                .to_string(),
+            TokenV4::new(mint_url, vec![long_secret], None, CurrencyUnit::Sat)
+                .unwrap()
+                .to_string(),
+        ] {
+            assert!(matches!(
+                Token::from_str(&token),
+                Err(Error::SecretTooLong {
+                    max: MAX_SECRET_LEN,
+                    ..
+                })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_token_legacy_secrets() {
+        // Secrets base64 encoded as early wallets did, the second unpadded.
+        // Built for this test, keyset ids are in the current form.
+        let token_str = "cashuAeyJ0b2tlbiI6W3sibWludCI6Imh0dHBzOi8vODMzMy5zcGFjZTozMzM4IiwicHJvb2ZzIjpbeyJhbW91bnQiOjIsImlkIjoiMDA5YTFmMjkzMjUzZTQxZSIsInNlY3JldCI6IloyWnJOM0pDVG5jd1VUSkZMVjl3U1ZwdVozbFFRUT09IiwiQyI6IjAyYmM5MDk3OTk3ZDgxYWZiMmNjNzM0NmI1ZTQzNDVhOTM0NmJkMmE1MDZlYjc5NTg1OThhNzJmMGNmODUxNjNlYSJ9LHsiYW1vdW50Ijo4LCJpZCI6IjAwOWExZjI5MzI1M2U0MWUiLCJzZWNyZXQiOiJUbWMxVm5wR2ExZGtUM05JVDJKWFpFaFViakZIZHciLCJDIjoiMDI5ZThlNTA1MGI4OTBhN2Q2YzA5NjhkYjE2YmMxZDVkNWZhMDQwZWExZGUyODRmNmVjNjlkNjEyOTlmNjcxMDU5In1dfV0sIm1lbW8iOiJUaGFuayB5b3UuIn0=";
+
+        // Parsed as current token the secrets are kept as written
+        let as_written = Token::from_str(token_str).unwrap();
+        assert_eq!(
+            as_written.token[0].proofs[0].secret,
+            Secret::new("Z2ZrN3JCTncwUTJFLV9wSVpuZ3lQQQ==")
+        );
+
+        let token = Token::from_legacy_str(token_str).unwrap();
+        let proofs = &token.token[0].proofs;
+        assert_eq!(proofs[0].secret, Secret::new("gfk7rBNw0Q2E-_pIZngyPA"));
+        assert_eq!(proofs[1].secret, Secret::new("Ng5VzFkWdOsHObWdHTn1Gw"));
+
+        // Everything but the secrets is as in the legacy token
+        for (proof, written) in proofs.iter().zip(&as_written.token[0].proofs) {
+            assert_eq!(proof.amount, written.amount);
+            assert_eq!(proof.keyset_id, written.keyset_id);
+            assert_eq!(proof.c, written.c);
+        }
+        assert_eq!(token.memo, as_written.memo);
+
+        // Written in the current form, which reads back the same
+        let encoded = token.to_string();
+        assert_eq!(Token::from_str(&encoded).unwrap(), token);
+        assert_eq!(Token::from_legacy_str(&encoded).unwrap(), token);
+
+        // A proof on its own
+        let proof = Proof::deserialize_legacy(serde_json::json!({
+            "amount": 2,
+            "id": "009a1f293253e41e",
+            "secret": "Z2ZrN3JCTncwUTJFLV9wSVpuZ3lQQQ==",
+            "C": "02bc9097997d81afb2cc7346b5e4345a9346bd2a506eb7958598a72f0cf85163ea"
+        }))
+        .unwrap();
+        assert_eq!(proof, proofs[0]);
+    }
+
+    #[test]
+    fn test_token_add_mint() {
+        let a = UncheckedUrl::from_str("https://a.example").unwrap();
+        let b = UncheckedUrl::from_str("https://b.example/").unwrap();
+
+        let mut token = Token::new(
+            a.clone(),
+            vec![proof(2, "009a1f293253e41e")],
+            None,
+            Some(CurrencyUnit::Sat),
+        )
+        .unwrap();
+
+        token
+            .add_mint(b.clone(), vec![proof(4, "00ad268c4d1f5826")])
+            .unwrap();
+        token
+            .add_mint(a.clone(), vec![proof(8, "009a1f293253e41e")])
+            .unwrap();
+
+        assert_eq!(token.token.len(), 2);
+        assert_eq!(token.token[0].mint, a);
+        assert_eq!(
+            token.token[0].proofs.total_amount().unwrap(),
+            Amount::from(10)
+        );
+        assert_eq!(token.token[1].mint, b);
+
+        assert!(matches!(
+            token.add_mint(a.clone(), vec![]),
+            Err(Error::ProofsRequired)
+        ));
+        assert!(matches!(
+            token.add_mint(
+                UncheckedUrl::from("not a url"),
+                vec![proof(1, "009a1f293253e41e")]
+            ),
+            Err(Error::InvalidUrl)
+        ));
+
+        // Urls keep their exact spelling, so these are different mints
+        token
+            .add_mint(
+                UncheckedUrl::from("https://a.example/"),
+                vec![proof(1, "009a1f293253e41e")],
+            )
+            .unwrap();
+        assert_eq!(token.token.len(), 3);
+
+        let decoded = Token::from_str(&token.to_string()).unwrap();
+        assert_eq!(decoded, token);
+        assert_eq!(decoded.token[2].mint.to_string(), "https://a.example/");
+    }
+
+    #[test]
+    fn test_token_summary() {
+        let token = Token {
+            token: vec![
+                MintProofs::new(
+                    UncheckedUrl::from_str("https://a.example").unwrap(),
+                    vec![proof(2, "009a1f293253e41e"), proof(8, "009a1f293253e41e")],
+                ),
+                MintProofs::new(
+                    UncheckedUrl::from_str("https://b.example").unwrap(),
+                    vec![proof(4, "00ad268c4d1f5826")],
+                ),
+            ],
+            memo: Some("Thank you".to_string()),
+            unit: None,
+        };
+
+        let summary = token.summary();
+        assert_eq!(
+            summary,
+            TokenSummary {
+                mints: vec![
+                    UncheckedUrl::from_str("https://a.example").unwrap(),
+                    UncheckedUrl::from_str("https://b.example").unwrap(),
+                ],
+                unit: CurrencyUnit::Sat,
+                amount: Amount::from(14),
+                proof_count: 3,
+                keyset_ids: vec![
+                    Id::from_str("009a1f293253e41e").unwrap(),
+                    Id::from_str("00ad268c4d1f5826").unwrap(),
+                ],
+                memo: Some("Thank you".to_string()),
+            }
+        );
+
+        let summary = summary.to_string();
+        assert_eq!(
+            summary,
+            "14 sat in 3 proofs from https://a.example, https://b.example \
+             (keysets 009a1f293253e41e, 00ad268c4d1f5826) memo: \"Thank you\""
+        );
+        for proof in token.token.iter().flat_map(|t| &t.proofs) {
+            assert!(!summary.contains(&proof.secret.to_string()));
+            assert!(!summary.contains(&proof.c.to_hex()));
+        }
+
+        assert_eq!(Token::from_str(&format!("{token}")).unwrap(), token);
+    }
+
     #[test]
     fn test_blank_blinded_messages() {
         // TODO: Need to update id to new type in proof
@@ -19,13 +19,18 @@ use super::nut02::KeySet;
 use crate::amount::Amount;
 
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum Error {
     #[error(transparent)]
     Secp256k1(#[from] secp256k1::Error),
     #[error(transparent)]
     Json(#[from] serde_json::Error),
-    #[error("Invalid public key size: expected={expected}, found={found}")]
-    InvalidPublicKeySize { expected: usize, found: usize },
+    #[error(transparent)]
+    Hex(#[from] crate::util::hex::Error),
+    #[error("Invalid public key size: expected 33 (compressed) or 65 (uncompressed) bytes, found={found}")]
+    InvalidPublicKeySize { found: usize },
+    #[error("Invalid secret key size: expected 32 bytes, found={found}")]
+    InvalidSecretKeySize { found: usize },
 }
 
 /// Mint Keys [NUT-01]
@@ -34,6 +39,12 @@ pub struct Keys(BTreeMap<Amount, PublicKey>);
 
 impl From<MintKeys> for Keys {
     fn from(keys: MintKeys) -> Self {
+        Self::from(&keys)
+    }
+}
+
+impl From<&MintKeys> for Keys {
+    fn from(keys: &MintKeys) -> Self {
         Self(
             keys.0
                 .iter()
@@ -59,6 +70,21 @@ impl Keys {
         self.0.get(&amount).copied()
     }
 
+    /// Amounts with a key, smallest first
+    pub fn amounts(&self) -> Vec<Amount> {
+        self.0.keys().copied().collect()
+    }
+
+    /// Largest amount with a key, the largest single output
+    pub fn max_amount(&self) -> Option<Amount> {
+        self.0.keys().next_back().copied()
+    }
+
+    /// Whether there is a key for `amount`
+    pub fn contains_amount(&self, amount: Amount) -> bool {
+        self.0.contains_key(&amount)
+    }
+
     /// Iterate through the (`Amount`, `PublicKey`) entries in the Map
     #[inline]
     pub fn iter(&self) -> impl Iterator<Item = (&Amount, &PublicKey)> {
@@ -68,10 +94,17 @@ impl Keys {
 
 /// Mint Public Keys [NUT-01]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
 pub struct KeysResponse {
     pub keysets: Vec<KeySet>,
 }
 
+impl KeysResponse {
+    pub fn new(keysets: Vec<KeySet>) -> Self {
+        Self { keysets }
+    }
+}
+
 impl<'de> Deserialize<'de> for KeysResponse {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -85,9 +118,11 @@ impl<'de> Deserialize<'de> for KeysResponse {
             .as_array()
             .ok_or(de::Error::custom("Keysets not found"))?;
 
+        // Legacy aliases of keysets are skipped, wallets only use the current ids
         let keysets = keysets
             .iter()
-            .flat_map(|keyset| serde_json::from_value(keyset.clone()))
+            .flat_map(|keyset| serde_json::from_value::<KeySet>(keyset.clone()))
+            .filter(|keyset| !keyset.id.is_legacy())
             .collect();
 
         Ok(KeysResponse { keysets })
@@ -95,7 +130,9 @@ impl<'de> Deserialize<'de> for KeysResponse {
 }
 
 /// Mint keys
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+///
+/// Not [Serialize], see [MintKeySet::serialize_secret](super::MintKeySet::serialize_secret)
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 pub struct MintKeys(BTreeMap<Amount, MintKeyPair>);
 
 impl Deref for MintKeys {
@@ -119,7 +156,7 @@ impl MintKeys {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 pub struct MintKeyPair {
     pub public_key: PublicKey,
     pub secret_key: SecretKey,
@@ -174,6 +211,63 @@ mod tests {
         assert_eq!(&serde_json::to_string(&response).unwrap(), &res)
     }
 
+    #[test]
+    fn test_keys_amount_keys() {
+        let key = "02f71e2d93aa95fc52b938735a24774ad926406c81e9dc9d2aa699fb89281548fd";
+
+        let keys: Keys = serde_json::from_str(&format!(
+            r#"{{"1":"{key}","18446744073709551615":"{key}"}}"#
+        ))
+        .unwrap();
+        assert!(keys.amount_key(Amount::from(u64::MAX)).is_some());
+        assert_eq!(
+            serde_json::to_string(&keys).unwrap(),
+            format!(r#"{{"1":"{key}","18446744073709551615":"{key}"}}"#)
+        );
+
+        for amount in ["-1", "1.5", "+1", "", "18446744073709551616"] {
+            let json = format!(r#"{{"{amount}":"{key}"}}"#);
+            assert!(serde_json::from_str::<Keys>(&json).is_err(), "{amount}");
+        }
+    }
+
+    #[test]
+    fn test_keys_amounts() {
+        let path = bitcoin::bip32::DerivationPath::from_str("m/0'/0'/0'").unwrap();
+
+        let keys = Keys::from(
+            crate::nuts::MintKeySet::generate(b"secret", Default::default(), &path, 8)
+                .unwrap()
+                .keys,
+        );
+        assert_eq!(
+            keys.amounts(),
+            [1, 2, 4, 8, 16, 32, 64, 128].map(Amount::from).to_vec()
+        );
+        assert_eq!(keys.max_amount(), Some(Amount::from(128)));
+        assert!(keys.contains_amount(Amount::from(64)));
+        assert!(!keys.contains_amount(Amount::from(3)));
+        assert!(!keys.contains_amount(Amount::from(256)));
+
+        let keys = Keys::from(
+            crate::nuts::MintKeySet::generate_with_amounts(
+                b"secret",
+                Default::default(),
+                &path,
+                &[50, 1, 10, 5, 10],
+            )
+            .keys,
+        );
+        assert_eq!(keys.amounts(), [1, 5, 10, 50].map(Amount::from).to_vec());
+        assert_eq!(keys.max_amount(), Some(Amount::from(50)));
+        assert!(keys.contains_amount(Amount::from(5)));
+        assert!(!keys.contains_amount(Amount::from(2)));
+
+        let keys = Keys::new(BTreeMap::new());
+        assert!(keys.amounts().is_empty());
+        assert_eq!(keys.max_amount(), None);
+    }
+
     #[test]
     fn test_ser_der_secret() {
         let secret = SecretKey::generate();
@@ -198,7 +292,12 @@ mod tests {
   "1":"03a40f20667ed53513075dc51e715ff2046cad64eb68960632269ba7f0210e38bc","2":"04fd4ce5a16b65576145949e6f99f445f8249fee17c606b688b504a849cdc452de3625246cb2c27dac965cb7200a5986467eee92eb7d496bbf1453b074e223e481","4":"02648eccfa4c026960966276fa5a4cae46ce0fd432211a4f449bf84f13aa5f8303","8":"02fdfd6796bfeac490cbee12f778f867f0a2c68f6508d17c649759ea0dc3547528"
 }"#;
         let response: Result<Keys, serde_json::Error> = serde_json::from_str(incorrect_1);
-        assert!(response.is_err());
+        // Uncompressed keys are normalized rather than rejected
+        let keys = response.unwrap();
+        assert_eq!(
+            keys.amount_key(Amount::from(2)).unwrap().to_hex(),
+            "03fd4ce5a16b65576145949e6f99f445f8249fee17c606b688b504a849cdc452de"
+        );
 
         let incorrect_1 = r#"{
          "1":"03a40f20667ed53513075dc51e715ff2046cad64eb68960632269ba7f0210e38bc","2":"03fd4ce5a16b65576145949e6f99f445f8249fee17c606b688b504a849cdc452de","4":"02648eccfa4c026960966276fa5a4cae46ce0fd432211a4f449bf84f13aa5f8303","8":"02fdfd6796bfeac490cbee12f778f867f0a2c68f6508d17c649759ea0dc3547528"
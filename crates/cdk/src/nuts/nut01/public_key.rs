@@ -6,6 +6,7 @@ use bitcoin::secp256k1;
 use serde::{Deserialize, Deserializer, Serialize};
 
 use super::Error;
+use crate::util::hex;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct PublicKey {
@@ -26,6 +27,28 @@ impl From<secp256k1::PublicKey> for PublicKey {
     }
 }
 
+impl From<PublicKey> for secp256k1::PublicKey {
+    fn from(public_key: PublicKey) -> Self {
+        public_key.inner
+    }
+}
+
+/// Lift an x-only key to the point with an even `y`, as NUT-11 does
+impl From<secp256k1::XOnlyPublicKey> for PublicKey {
+    fn from(x_only: secp256k1::XOnlyPublicKey) -> Self {
+        Self {
+            inner: secp256k1::PublicKey::from_x_only_public_key(x_only, secp256k1::Parity::Even),
+        }
+    }
+}
+
+/// Drop the parity of `y`, so a key and its negation map to the same x-only key
+impl From<PublicKey> for secp256k1::XOnlyPublicKey {
+    fn from(public_key: PublicKey) -> Self {
+        public_key.inner.x_only_public_key().0
+    }
+}
+
 impl PublicKey {
     /// Parse from `bytes`
     #[inline]
@@ -36,6 +59,8 @@ impl PublicKey {
     }
 
     /// Parse from `hex` string
+    ///
+    /// Uncompressed keys are accepted and normalized to their compressed form
     #[inline]
     pub fn from_hex<S>(hex: S) -> Result<Self, Error>
     where
@@ -44,16 +69,13 @@ impl PublicKey {
         let hex: &str = hex.as_ref();
 
         // Check size
-        if hex.len() != 33 * 2 {
+        if hex.len() != 33 * 2 && hex.len() != 65 * 2 {
             return Err(Error::InvalidPublicKeySize {
-                expected: 33,
                 found: hex.len() / 2,
             });
         }
 
-        Ok(Self {
-            inner: secp256k1::PublicKey::from_str(hex)?,
-        })
+        Self::from_slice(&hex::decode(hex)?)
     }
 
     #[inline]
@@ -66,7 +88,7 @@ impl PublicKey {
         self.inner.serialize_uncompressed()
     }
 
-    /// Get public key as `hex` string
+    /// Get compressed public key as `hex` string
     #[inline]
     pub fn to_hex(&self) -> String {
         self.inner.to_string()
@@ -121,11 +143,94 @@ mod tests {
         );
     }
 
+    #[test]
+    pub fn test_uncompressed_public_key_from_hex() {
+        // Uncompressed is normalized to compressed
+        let public_key = PublicKey::from_hex("04fd4ce5a16b65576145949e6f99f445f8249fee17c606b688b504a849cdc452de3625246cb2c27dac965cb7200a5986467eee92eb7d496bbf1453b074e223e481").unwrap();
+
+        assert_eq!(
+            public_key.to_hex(),
+            "03fd4ce5a16b65576145949e6f99f445f8249fee17c606b688b504a849cdc452de"
+        );
+        assert_eq!(
+            serde_json::to_string(&public_key).unwrap(),
+            r#""03fd4ce5a16b65576145949e6f99f445f8249fee17c606b688b504a849cdc452de""#
+        );
+    }
+
+    #[test]
+    pub fn test_public_key_round_trip() {
+        // Even and odd parity
+        for hex in [
+            "02194603ffa36356f4a56b7df9371fc3192472351453ec7398b8da8117e7c3e104",
+            "03fd4ce5a16b65576145949e6f99f445f8249fee17c606b688b504a849cdc452de",
+        ] {
+            let public_key = PublicKey::from_str(hex).unwrap();
+            assert_eq!(public_key.to_string(), hex);
+            assert_eq!(
+                PublicKey::from_slice(&public_key.to_bytes()).unwrap(),
+                public_key
+            );
+        }
+    }
+
+    #[test]
+    pub fn test_secp256k1_conversions() {
+        let even = PublicKey::from_hex(
+            "02194603ffa36356f4a56b7df9371fc3192472351453ec7398b8da8117e7c3e104",
+        )
+        .unwrap();
+        let odd = PublicKey::from_hex(
+            "03fd4ce5a16b65576145949e6f99f445f8249fee17c606b688b504a849cdc452de",
+        )
+        .unwrap();
+
+        for public_key in [even, odd] {
+            let inner = secp256k1::PublicKey::from(public_key);
+            assert_eq!(inner.serialize(), public_key.to_bytes());
+            assert_eq!(PublicKey::from(inner), public_key);
+        }
+
+        // An even key survives the x-only round trip
+        let x_only = secp256k1::XOnlyPublicKey::from(even);
+        assert_eq!(x_only.serialize(), even.to_bytes()[1..]);
+        assert_eq!(PublicKey::from(x_only), even);
+
+        // An odd key comes back as its even negation
+        let x_only = secp256k1::XOnlyPublicKey::from(odd);
+        assert_eq!(x_only.serialize(), odd.to_bytes()[1..]);
+        let normalized = PublicKey::from(x_only);
+        assert_ne!(normalized, odd);
+        assert_eq!(
+            normalized.to_hex(),
+            "02fd4ce5a16b65576145949e6f99f445f8249fee17c606b688b504a849cdc452de"
+        );
+        assert_eq!(secp256k1::XOnlyPublicKey::from(normalized), x_only);
+    }
+
     #[test]
     pub fn test_invalid_public_key_from_hex() {
-        // Uncompressed (is valid but is cashu must be compressed?)
-        assert!((PublicKey::from_hex("04fd4ce5a16b65576145949e6f99f445f8249fee17c606b688b504a849cdc452de3625246cb2c27dac965cb7200a5986467eee92eb7d496bbf1453b074e223e481")
-            .is_err()))
+        // x-only
+        assert!(matches!(
+            PublicKey::from_hex("fd4ce5a16b65576145949e6f99f445f8249fee17c606b688b504a849cdc452de"),
+            Err(Error::InvalidPublicKeySize { found: 32 })
+        ));
+
+        // Non hex
+        assert!(matches!(
+            PublicKey::from_hex(
+                "02fd4ce5a16b65576145949e6f99f445f8249fee17c606b688b504a849cdc452dg"
+            ),
+            Err(Error::Hex(hex::Error::InvalidHexCharacter { c: 'g', .. }))
+        ));
+
+        // Right length but not on curve
+        assert!(matches!(
+            PublicKey::from_hex(
+                "02fd4ce5a16b65576145949e6f99f445f8249fee17c606b688b504a849cdc452df"
+            ),
+            Err(Error::Secp256k1(_))
+        ));
     }
 }
 
@@ -8,6 +8,7 @@ use bitcoin::secp256k1::Scalar;
 use serde::{Deserialize, Deserializer, Serialize};
 
 use super::{Error, PublicKey};
+use crate::util::hex;
 use crate::SECP256K1;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -29,6 +30,19 @@ impl From<secp256k1::SecretKey> for SecretKey {
     }
 }
 
+/// A copy of the key, which is not erased when dropped
+impl From<&SecretKey> for secp256k1::SecretKey {
+    fn from(secret_key: &SecretKey) -> Self {
+        secret_key.inner
+    }
+}
+
+impl From<SecretKey> for secp256k1::SecretKey {
+    fn from(secret_key: SecretKey) -> Self {
+        secret_key.inner
+    }
+}
+
 impl fmt::Display for SecretKey {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.to_secret_hex())
@@ -48,9 +62,16 @@ impl SecretKey {
     where
         S: AsRef<str>,
     {
-        Ok(Self {
-            inner: secp256k1::SecretKey::from_str(hex.as_ref())?,
-        })
+        let hex: &str = hex.as_ref();
+
+        // Check size
+        if hex.len() != 32 * 2 {
+            return Err(Error::InvalidSecretKeySize {
+                found: hex.len() / 2,
+            });
+        }
+
+        Self::from_slice(&hex::decode(hex)?)
     }
 
     /// Generate random secret key
@@ -124,3 +145,65 @@ impl Drop for SecretKey {
         tracing::trace!("Secret Key dropped.");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_key_round_trip() {
+        let hex = "0000000000000000000000000000000000000000000000000000000000000001";
+        let secret_key = SecretKey::from_str(hex).unwrap();
+
+        assert_eq!(secret_key.to_string(), hex);
+        assert_eq!(
+            SecretKey::from_slice(&secret_key.to_secret_bytes()).unwrap(),
+            secret_key
+        );
+        assert_eq!(
+            secret_key.public_key().to_hex(),
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"
+        );
+
+        let secret_key = SecretKey::generate();
+        assert_eq!(
+            SecretKey::from_hex(secret_key.to_secret_hex()).unwrap(),
+            secret_key
+        );
+    }
+
+    #[test]
+    fn test_secp256k1_conversions() {
+        let secret_key = SecretKey::generate();
+
+        let inner = secp256k1::SecretKey::from(&secret_key);
+        assert_eq!(inner.secret_bytes(), secret_key.to_secret_bytes());
+        assert_eq!(SecretKey::from(inner), secret_key);
+        assert_eq!(
+            PublicKey::from(inner.public_key(&SECP256K1)),
+            secret_key.public_key()
+        );
+
+        let bytes = secret_key.to_secret_bytes();
+        assert_eq!(secp256k1::SecretKey::from(secret_key).secret_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_invalid_secret_key() {
+        assert!(matches!(
+            SecretKey::from_hex("0001"),
+            Err(Error::InvalidSecretKeySize { found: 2 })
+        ));
+
+        assert!(matches!(
+            SecretKey::from_hex("000000000000000000000000000000000000000000000000000000000000000z"),
+            Err(Error::Hex(_))
+        ));
+
+        // Zero is not a valid scalar
+        assert!(matches!(
+            SecretKey::from_hex("0000000000000000000000000000000000000000000000000000000000000000"),
+            Err(Error::Secp256k1(_))
+        ));
+    }
+}
@@ -4,37 +4,62 @@
 
 use core::fmt;
 use core::str::FromStr;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
-use bitcoin::bip32::DerivationPath;
+use base64::engine::general_purpose;
+use base64::Engine as _;
+use bitcoin::bip32::{ChildNumber, DerivationPath};
 use bitcoin::hashes::sha256::Hash as Sha256;
 use bitcoin::hashes::{Hash, HashEngine};
-use serde::{Deserialize, Deserializer, Serialize};
+use bitcoin::secp256k1::rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_with::{serde_as, VecSkipError};
 use thiserror::Error;
 
-use super::nut01::{Keys, MintKeyPair, MintKeys, SecretKey};
+use super::nut01::{Keys, MintKeyPair, MintKeys, PublicKey, SecretKey};
 use crate::nuts::nut00::CurrencyUnit;
 use crate::util::hex;
-use crate::Amount;
+use crate::{Amount, UnitAmount};
 
 #[derive(Debug, Error, PartialEq)]
+#[non_exhaustive]
 pub enum Error {
     #[error(transparent)]
     HexError(#[from] hex::Error),
     #[error("NUT02: ID length invalid")]
     Length,
+    #[error("NUT02: Unknown keyset version `{0}`")]
+    UnknownVersion(String),
+    #[error("NUT02: Legacy ID is not base64")]
+    LegacyId,
+    #[error("NUT02: Keyset ID `{claimed}` does not match `{derived}` derived from its keys")]
+    KeysetIdMismatch { claimed: Id, derived: Id },
+    /// Public and secret part of a keyset do not have keys for the same amounts
+    #[error("NUT02: No key pair for amount {0}")]
+    MissingKey(Amount),
+    /// Public key of an amount is not the public key of its secret key
+    #[error("NUT02: Public key for amount {0} does not match its secret key")]
+    KeyPairMismatch(Amount),
+    /// Max order of power of two denominations outside of `1..=MAX_ORDER`
+    #[error("NUT02: Max order {0} is not between 1 and {MAX_ORDER}")]
+    InvalidMaxOrder(u8),
 }
 
+/// Largest max order of a keyset, its largest denomination is `2^63`
+pub const MAX_ORDER: u8 = 64;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum KeySetVersion {
     Version00,
+    /// Unversioned base64 ids of keysets from before version `00`
+    Legacy,
 }
 
 impl fmt::Display for KeySetVersion {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             KeySetVersion::Version00 => f.write_str("00"),
+            KeySetVersion::Legacy => Ok(()),
         }
     }
 }
@@ -43,14 +68,70 @@ impl fmt::Display for KeySetVersion {
 /// anyone who knows the set of public keys of a mint. The keyset ID **CAN**
 /// be stored in a Cashu token such that the token can be used to identify
 /// which mint or keyset it was generated from.
+///
+/// Legacy ids, 12 base64 characters, are still parsed so proofs of old
+/// wallets can be routed to their keyset, see [Id::legacy_from_keys].
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Id {
     version: KeySetVersion,
+    /// Characters after the version, a legacy id leaves the last two zero
     id: [u8; Self::STRLEN],
 }
 
 impl Id {
     const STRLEN: usize = 14;
+    const LEGACY_STRLEN: usize = 12;
+
+    /// Whether this is a legacy base64 id
+    pub fn is_legacy(&self) -> bool {
+        self.version == KeySetVersion::Legacy
+    }
+
+    /// Legacy id of `keys`, as mints derived it before version `00`
+    ///
+    /// The first 12 characters of the base64 SHA256 of the hex public keys
+    /// concatenated in ascending amount order.
+    pub fn legacy_from_keys(keys: &Keys) -> Self {
+        let pubkeys_concat: String = keys.iter().map(|(_, pubkey)| pubkey.to_hex()).collect();
+        let hash = Sha256::hash(pubkeys_concat.as_bytes());
+        let encoded = general_purpose::STANDARD.encode(hash.to_byte_array());
+
+        let mut id = [0; Self::STRLEN];
+        id[..Self::LEGACY_STRLEN].copy_from_slice(&encoded.as_bytes()[..Self::LEGACY_STRLEN]);
+
+        Self {
+            version: KeySetVersion::Legacy,
+            id,
+        }
+    }
+
+    fn from_legacy_str(s: &str) -> Result<Self, Error> {
+        if !s
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'+' || b == b'/')
+        {
+            return Err(Error::LegacyId);
+        }
+
+        let mut id = [0; Self::STRLEN];
+        id[..Self::LEGACY_STRLEN].copy_from_slice(s.as_bytes());
+
+        Ok(Self {
+            version: KeySetVersion::Legacy,
+            id,
+        })
+    }
+
+    /// Integer representation used for NUT-13 derivation paths
+    ///
+    /// The id is read as a big endian integer modulo `2^31 - 1`, a legacy id
+    /// is not hex and maps to `0`.
+    pub fn to_u32(&self) -> u32 {
+        // Hex is checked when the id is constructed and 16 hex chars fit in a u64
+        let int = u64::from_str_radix(&self.to_string(), 16).unwrap_or_default();
+
+        (int % (2_u64.pow(31) - 1)) as u32
+    }
 }
 
 impl TryFrom<Id> for u64 {
@@ -68,10 +149,15 @@ impl TryFrom<Id> for u64 {
 
 impl fmt::Display for Id {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let id = match self.version {
+            KeySetVersion::Version00 => &self.id[..],
+            KeySetVersion::Legacy => &self.id[..Self::LEGACY_STRLEN],
+        };
+
         f.write_str(&format!(
             "{}{}",
             self.version,
-            String::from_utf8(self.id.to_vec()).map_err(|_| fmt::Error)?
+            std::str::from_utf8(id).map_err(|_| fmt::Error)?
         ))
     }
 }
@@ -80,14 +166,27 @@ impl FromStr for Id {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() == Self::LEGACY_STRLEN {
+            return Self::from_legacy_str(s);
+        }
+
         // Check if the string length is valid
         if s.len() != 16 {
             return Err(Error::Length);
         }
 
+        if !s.starts_with("00") {
+            // The first two bytes need not be a whole char
+            let version = String::from_utf8_lossy(&s.as_bytes()[..2]);
+            return Err(Error::UnknownVersion(version.into_owned()));
+        }
+
+        // Check the id is valid hex
+        hex::decode(s)?;
+
         Ok(Self {
             version: KeySetVersion::Version00,
-            id: s[2..].as_bytes().try_into().map_err(|_| Error::Length)?,
+            id: s.as_bytes()[2..].try_into().map_err(|_| Error::Length)?,
         })
     }
 }
@@ -127,7 +226,7 @@ impl<'de> Deserialize<'de> for Id {
                         v.len(),
                         v
                     )),
-                    Error::HexError(e) => E::custom(e),
+                    e => E::custom(e),
                 })
             }
         }
@@ -160,8 +259,7 @@ impl From<&Keys> for Id {
         // First 9 bytes of hash will encode as the first 12 Base64 characters later
         Self {
             version: KeySetVersion::Version00,
-            id: hex_of_hash[0..Self::STRLEN]
-                .as_bytes()
+            id: hex_of_hash.as_bytes()[0..Self::STRLEN]
                 .to_owned()
                 .try_into()
                 .unwrap(),
@@ -171,14 +269,33 @@ impl From<&Keys> for Id {
 
 /// Mint Keysets [NUT-02]
 /// Ids of mints keyset ids
-#[serde_as]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct KeysetResponse {
     /// set of public key ids that the mint generates
-    #[serde_as(as = "VecSkipError<_>")]
+    ///
+    /// Keysets that cannot be parsed and legacy aliases of keysets are
+    /// skipped, wallets only use the current ids.
+    #[serde(deserialize_with = "current_keyset_infos")]
     pub keysets: Vec<KeySetInfo>,
 }
 
+fn current_keyset_infos<'de, D>(deserializer: D) -> Result<Vec<KeySetInfo>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[serde_as]
+    #[derive(Deserialize)]
+    struct Infos(#[serde_as(as = "VecSkipError<_>")] Vec<KeySetInfo>);
+
+    let Infos(infos) = Infos::deserialize(deserializer)?;
+
+    Ok(infos
+        .into_iter()
+        .filter(|info| !info.id.is_legacy())
+        .collect())
+}
+
 impl KeysetResponse {
     pub fn new(keysets: Vec<KeySet>) -> Self {
         Self {
@@ -194,6 +311,36 @@ pub struct KeySet {
     pub keys: Keys,
 }
 
+impl KeySet {
+    /// Check [KeySet::id] is the id derived from [KeySet::keys]
+    ///
+    /// Legacy ids are checked against [Id::legacy_from_keys]. A mint that
+    /// hands out ids it did not derive could use them to tell wallets apart.
+    pub fn verify_id(&self) -> Result<(), Error> {
+        let derived = if self.id.is_legacy() {
+            Id::legacy_from_keys(&self.keys)
+        } else {
+            Id::from(&self.keys)
+        };
+
+        if derived != self.id {
+            return Err(Error::KeysetIdMismatch {
+                claimed: self.id,
+                derived,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Amounts the keyset signs, in its unit
+    pub fn denominations(&self) -> impl Iterator<Item = UnitAmount> + '_ {
+        self.keys
+            .iter()
+            .map(|(amount, _)| UnitAmount::new(*amount, self.unit.clone()))
+    }
+}
+
 impl From<MintKeySet> for KeySet {
     fn from(keyset: MintKeySet) -> Self {
         Self {
@@ -204,11 +351,41 @@ impl From<MintKeySet> for KeySet {
     }
 }
 
+impl From<&MintKeySet> for KeySet {
+    fn from(keyset: &MintKeySet) -> Self {
+        Self {
+            id: keyset.id,
+            unit: keyset.unit.clone(),
+            keys: Keys::from(&keyset.keys),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Hash, PartialEq, Eq, Deserialize, Serialize)]
 pub struct KeySetInfo {
     pub id: Id,
     pub unit: CurrencyUnit,
+    /// Mint will only sign outputs for active keysets
     pub active: bool,
+    /// Input fee in parts per thousand
+    #[serde(default)]
+    pub input_fee_ppk: u64,
+    /// Unix time the keyset was first used by the mint
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_from: Option<u64>,
+    /// Unix time the keyset was rotated out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_to: Option<u64>,
+    /// Last index of the derivation path the keyset was generated from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub derivation_path_index: Option<u32>,
+    /// Keyset was frozen by the mint, its proofs are not accepted
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub frozen: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
 }
 
 impl From<KeySet> for KeySetInfo {
@@ -217,31 +394,96 @@ impl From<KeySet> for KeySetInfo {
             id: keyset.id,
             unit: keyset.unit,
             active: false,
+            input_fee_ppk: 0,
+            valid_from: None,
+            valid_to: None,
+            derivation_path_index: None,
+            frozen: false,
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+impl From<&MintKeySet> for KeySetInfo {
+    fn from(keyset: &MintKeySet) -> KeySetInfo {
+        Self {
+            id: keyset.id,
+            unit: keyset.unit.clone(),
+            active: false,
+            input_fee_ppk: keyset.input_fee_ppk,
+            valid_from: Some(keyset.valid_from),
+            valid_to: keyset.valid_to,
+            derivation_path_index: keyset.derivation_path_index,
+            frozen: keyset.frozen.is_some(),
+        }
+    }
+}
+
+/// Mint keyset
+///
+/// Serializes as its [MintKeySetPublic] part only, use
+/// [MintKeySet::serialize_secret] to write the secret keys too, in the form
+/// it deserializes from.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 pub struct MintKeySet {
     pub id: Id,
     pub unit: CurrencyUnit,
     pub keys: MintKeys,
+    /// Unix time the keyset was first used, set by the mint
+    #[serde(default)]
+    pub valid_from: u64,
+    /// Unix time the keyset was rotated out, set by the mint
+    #[serde(default)]
+    pub valid_to: Option<u64>,
+    /// Last index of the derivation path the keyset was generated from
+    #[serde(default)]
+    pub derivation_path_index: Option<u32>,
+    /// Unix time from which proofs of the keyset are refused, set by the mint
+    #[serde(default)]
+    pub refused_from: Option<u64>,
+    /// Reason the keyset was frozen, set by the mint
+    #[serde(default)]
+    pub frozen: Option<String>,
+    /// Fee in thousandths of a unit per proof spent
+    #[serde(default)]
+    pub input_fee_ppk: u64,
 }
 
 impl MintKeySet {
+    /// Derive keyset from `secret` and `derivation_path`
+    ///
+    /// Fully deterministic, the same inputs always give the same keys and id.
+    /// Fails if `max_order` is not between 1 and [MAX_ORDER].
     pub fn generate(
         secret: &[u8],
         unit: CurrencyUnit,
         derivation_path: &DerivationPath,
         max_order: u8,
-    ) -> Self {
-        // Elliptic curve math context
+    ) -> Result<Self, Error> {
+        Ok(Self::generate_with_amounts(
+            secret,
+            unit,
+            derivation_path,
+            &power_of_two_amounts(max_order)?,
+        ))
+    }
 
+    /// Derive keyset with keys for exactly `amounts`
+    ///
+    /// The key for the `i`th smallest amount is derived from index `i`, so
+    /// the powers of two below `max_order` give the same keyset as
+    /// [MintKeySet::generate]. Duplicate amounts are ignored.
+    pub fn generate_with_amounts(
+        secret: &[u8],
+        unit: CurrencyUnit,
+        derivation_path: &DerivationPath,
+        amounts: &[u64],
+    ) -> Self {
         /* NUT-02 § 2.1
             for i in range(MAX_ORDER):
                 k_i = HASH_SHA256(s + D + i)[:32]
         */
 
+        let amounts: BTreeSet<u64> = amounts.iter().copied().collect();
         let mut map = BTreeMap::new();
 
         // SHA-256 midstate, for quicker hashing
@@ -249,8 +491,8 @@ impl MintKeySet {
         engine.input(secret);
         engine.input(derivation_path.to_string().as_bytes());
 
-        for i in 0..max_order {
-            let amount = Amount::from(2_u64.pow(i as u32));
+        for (i, amount) in amounts.into_iter().enumerate() {
+            let amount = Amount::from(amount);
 
             // Reuse midstate
             let mut e = engine.clone();
@@ -267,10 +509,258 @@ impl MintKeySet {
             id: (&keys).into(),
             unit,
             keys,
+            valid_from: 0,
+            valid_to: None,
+            derivation_path_index: derivation_path_index(derivation_path),
+            refused_from: None,
+            frozen: None,
+            input_fee_ppk: 0,
+        }
+    }
+
+    /// Amounts the keyset has keys for, smallest first
+    pub fn amounts(&self) -> impl Iterator<Item = Amount> + '_ {
+        self.keys.keys().copied()
+    }
+
+    /// Largest amount the keyset has a key for
+    pub fn max_amount(&self) -> Option<Amount> {
+        self.keys.keys().next_back().copied()
+    }
+
+    /// Whether the keyset has a key for `amount`
+    pub fn contains_amount(&self, amount: Amount) -> bool {
+        self.keys.contains_key(&amount)
+    }
+
+    /// Whether proofs of the keyset are refused at `now`
+    pub fn is_refused(&self, now: u64) -> bool {
+        self.refused_from
+            .is_some_and(|refused_from| now >= refused_from)
+    }
+
+    /// Keyset from a secret drawn from `rng`
+    ///
+    /// For ephemeral keysets that never need to be restored from a backup.
+    pub fn generate_with_rng<R>(
+        rng: &mut R,
+        unit: CurrencyUnit,
+        derivation_path: &DerivationPath,
+        max_order: u8,
+    ) -> Result<Self, Error>
+    where
+        R: RngCore + CryptoRng,
+    {
+        let mut secret = [0u8; 32];
+        rng.fill_bytes(&mut secret);
+
+        Self::generate(&secret, unit, derivation_path, max_order)
+    }
+}
+
+impl MintKeySet {
+    /// Everything but the secret keys
+    pub fn public(&self) -> MintKeySetPublic {
+        MintKeySetPublic {
+            id: self.id,
+            unit: self.unit.clone(),
+            keys: Keys::from(&self.keys),
+            valid_from: self.valid_from,
+            valid_to: self.valid_to,
+            derivation_path_index: self.derivation_path_index,
+            refused_from: self.refused_from,
+            frozen: self.frozen.clone(),
+            input_fee_ppk: self.input_fee_ppk,
+        }
+    }
+
+    /// Secret keys by amount
+    pub fn secret(&self) -> MintKeySetSecret {
+        MintKeySetSecret::new(
+            self.keys
+                .iter()
+                .map(|(amount, pair)| (*amount, pair.secret_key.clone()))
+                .collect(),
+        )
+    }
+
+    /// Join the parts returned by [MintKeySet::public] and [MintKeySet::secret]
+    ///
+    /// Both parts must have keys for the same amounts, every public key must
+    /// be the public key of its secret key and the id must be derived from
+    /// the keys.
+    pub fn from_parts(public: MintKeySetPublic, secret: MintKeySetSecret) -> Result<Self, Error> {
+        if let Some(amount) = secret
+            .keys
+            .keys()
+            .find(|amount| !public.keys.contains_amount(**amount))
+        {
+            return Err(Error::MissingKey(*amount));
+        }
+
+        let mut keys = BTreeMap::new();
+        for (amount, public_key) in public.keys.iter() {
+            let secret_key = secret.keys.get(amount).ok_or(Error::MissingKey(*amount))?;
+            let pair = MintKeyPair::from_secret_key(secret_key.clone());
+            if pair.public_key != *public_key {
+                return Err(Error::KeyPairMismatch(*amount));
+            }
+            keys.insert(*amount, pair);
+        }
+
+        let derived = Id::from(&public.keys);
+        if derived != public.id {
+            return Err(Error::KeysetIdMismatch {
+                claimed: public.id,
+                derived,
+            });
         }
+
+        Ok(Self {
+            id: public.id,
+            unit: public.unit,
+            keys: MintKeys::new(keys),
+            valid_from: public.valid_from,
+            valid_to: public.valid_to,
+            derivation_path_index: public.derivation_path_index,
+            refused_from: public.refused_from,
+            frozen: public.frozen,
+            input_fee_ppk: public.input_fee_ppk,
+        })
+    }
+
+    /// Serialize with the secret keys, the way the keyset deserializes
+    ///
+    /// For databases and backups, never for anything sent to wallets.
+    pub fn serialize_secret<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        #[derive(Serialize)]
+        struct SecretKeyPair<'a> {
+            public_key: PublicKey,
+            secret_key: &'a SecretKey,
+        }
+
+        #[derive(Serialize)]
+        struct SecretMintKeySet<'a> {
+            id: Id,
+            unit: &'a CurrencyUnit,
+            keys: BTreeMap<Amount, SecretKeyPair<'a>>,
+            valid_from: u64,
+            valid_to: Option<u64>,
+            derivation_path_index: Option<u32>,
+            refused_from: Option<u64>,
+            frozen: &'a Option<String>,
+            input_fee_ppk: u64,
+        }
+
+        SecretMintKeySet {
+            id: self.id,
+            unit: &self.unit,
+            keys: self
+                .keys
+                .iter()
+                .map(|(amount, pair)| {
+                    let pair = SecretKeyPair {
+                        public_key: pair.public_key,
+                        secret_key: &pair.secret_key,
+                    };
+                    (*amount, pair)
+                })
+                .collect(),
+            valid_from: self.valid_from,
+            valid_to: self.valid_to,
+            derivation_path_index: self.derivation_path_index,
+            refused_from: self.refused_from,
+            frozen: &self.frozen,
+            input_fee_ppk: self.input_fee_ppk,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl Serialize for MintKeySet {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.public().serialize(serializer)
     }
 }
 
+/// Public part of a [MintKeySet], its id, public keys and metadata
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MintKeySetPublic {
+    pub id: Id,
+    pub unit: CurrencyUnit,
+    pub keys: Keys,
+    #[serde(default)]
+    pub valid_from: u64,
+    #[serde(default)]
+    pub valid_to: Option<u64>,
+    #[serde(default)]
+    pub derivation_path_index: Option<u32>,
+    #[serde(default)]
+    pub refused_from: Option<u64>,
+    #[serde(default)]
+    pub frozen: Option<String>,
+    #[serde(default)]
+    pub input_fee_ppk: u64,
+}
+
+/// Secret keys of a [MintKeySet] by amount
+///
+/// Not [Serialize], so it is never written out by accident, use
+/// [MintKeySetSecret::serialize_secret].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct MintKeySetSecret {
+    keys: BTreeMap<Amount, SecretKey>,
+}
+
+impl MintKeySetSecret {
+    pub fn new(keys: BTreeMap<Amount, SecretKey>) -> Self {
+        Self { keys }
+    }
+
+    pub fn keys(&self) -> &BTreeMap<Amount, SecretKey> {
+        &self.keys
+    }
+
+    /// Serialize the secret keys by amount, as they deserialize
+    pub fn serialize_secret<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.keys.serialize(serializer)
+    }
+}
+
+/// Powers of two below `2^max_order`, the standard keyset denominations
+///
+/// Fails if `max_order` is not between 1 and [MAX_ORDER].
+pub fn power_of_two_amounts(max_order: u8) -> Result<Vec<u64>, Error> {
+    if !(1..=MAX_ORDER).contains(&max_order) {
+        return Err(Error::InvalidMaxOrder(max_order));
+    }
+
+    (0..max_order)
+        .map(|i| {
+            1_u64
+                .checked_shl(u32::from(i))
+                .ok_or(Error::InvalidMaxOrder(max_order))
+        })
+        .collect()
+}
+
+/// Index of the last child in `derivation_path`, hardened or not
+pub fn derivation_path_index(derivation_path: &DerivationPath) -> Option<u32> {
+    derivation_path.into_iter().last().map(|child| match child {
+        ChildNumber::Normal { index } | ChildNumber::Hardened { index } => *index,
+    })
+}
+
 impl From<MintKeySet> for Id {
     fn from(keyset: MintKeySet) -> Id {
         let keys: super::KeySet = keyset.into();
@@ -292,11 +782,28 @@ mod test {
 
     use std::str::FromStr;
 
-    use super::{KeySetInfo, Keys, KeysetResponse};
+    use bitcoin::bip32::DerivationPath;
+    use bitcoin::secp256k1::rand::rngs::StdRng;
+    use bitcoin::secp256k1::rand::SeedableRng;
+
+    use super::{
+        power_of_two_amounts, Error, KeySet, KeySetInfo, Keys, KeysetResponse, MintKeySet,
+        MintKeySetPublic, MintKeySetSecret, MAX_ORDER,
+    };
+    use crate::nuts::nut00::CurrencyUnit;
     use crate::nuts::nut02::Id;
     use crate::nuts::KeysResponse;
+    use crate::Amount;
 
     const SHORT_KEYSET_ID: &str = "00456a94ab4e1c46";
+    const SHORT_KEYSET_UNCOMPRESSED: &str = r#"
+        {
+            "1":"04a40f20667ed53513075dc51e715ff2046cad64eb68960632269ba7f0210e38bca6d42eaaa3b8041d7bcf04202cde9d5192b00ef7a6c1b435ddefafc66b4a2903",
+            "2":"04fd4ce5a16b65576145949e6f99f445f8249fee17c606b688b504a849cdc452de3625246cb2c27dac965cb7200a5986467eee92eb7d496bbf1453b074e223e481",
+            "4":"04648eccfa4c026960966276fa5a4cae46ce0fd432211a4f449bf84f13aa5f8303d9c080a1954030812589500060fbe49eb2c0973a809a636336233f31010ce9be",
+            "8":"04fdfd6796bfeac490cbee12f778f867f0a2c68f6508d17c649759ea0dc35475281be62372eb9ea3104a39d653c0350c4d77ed42aafde46c67528aa648dbbd32b4"
+        }
+    "#;
     const SHORT_KEYSET: &str = r#"
         {
             "1":"03a40f20667ed53513075dc51e715ff2046cad64eb68960632269ba7f0210e38bc",
@@ -386,6 +893,13 @@ mod test {
 
         assert_eq!(id, Id::from_str(SHORT_KEYSET_ID).unwrap());
 
+        // Uncompressed keys derive the same id as their compressed form
+        let uncompressed_keys: Keys = serde_json::from_str(SHORT_KEYSET_UNCOMPRESSED).unwrap();
+        assert_eq!(uncompressed_keys, keys);
+
+        let id: Id = (&uncompressed_keys).into();
+        assert_eq!(id, Id::from_str(SHORT_KEYSET_ID).unwrap());
+
         let keys: Keys = serde_json::from_str(KEYSET).unwrap();
 
         let id: Id = (&keys).into();
@@ -397,7 +911,136 @@ mod test {
     fn de_keyset_info() {
         let h = r#"{"id":"009a1f293253e41e","unit":"sat","active":true}"#;
 
-        let _keyset_response: KeySetInfo = serde_json::from_str(h).unwrap();
+        let keyset_info: KeySetInfo = serde_json::from_str(h).unwrap();
+        assert_eq!(keyset_info.input_fee_ppk, 0);
+
+        let h = r#"{"id":"009a1f293253e41e","unit":"sat","active":false,"input_fee_ppk":100}"#;
+        let keyset_info: KeySetInfo = serde_json::from_str(h).unwrap();
+        assert!(!keyset_info.active);
+        assert_eq!(keyset_info.input_fee_ppk, 100);
+        assert_eq!(serde_json::to_string(&keyset_info).unwrap(), h);
+    }
+
+    #[test]
+    fn test_keyset_info_metadata() {
+        let h = r#"{"id":"009a1f293253e41e","unit":"sat","active":false,"input_fee_ppk":0,"valid_from":1700000000,"valid_to":1710000000,"derivation_path_index":3}"#;
+
+        let keyset_info: KeySetInfo = serde_json::from_str(h).unwrap();
+        assert_eq!(keyset_info.valid_from, Some(1700000000));
+        assert_eq!(keyset_info.valid_to, Some(1710000000));
+        assert_eq!(keyset_info.derivation_path_index, Some(3));
+        assert_eq!(serde_json::to_string(&keyset_info).unwrap(), h);
+
+        let path = DerivationPath::from_str("m/0'/0'/3'").unwrap();
+        let mut keyset = MintKeySet::generate(b"secret", CurrencyUnit::Sat, &path, 4).unwrap();
+        assert_eq!(keyset.derivation_path_index, Some(3));
+        keyset.valid_from = 1700000000;
+
+        let keyset_info = KeySetInfo::from(&keyset);
+        let json = serde_json::to_string(&keyset_info).unwrap();
+        assert_eq!(
+            json,
+            format!(
+                r#"{{"id":"{}","unit":"sat","active":false,"input_fee_ppk":0,"valid_from":1700000000,"derivation_path_index":3}}"#,
+                keyset.id
+            )
+        );
+
+        // Keysets stored before the metadata existed still load
+        let mut stored = keyset
+            .serialize_secret(serde_json::value::Serializer)
+            .unwrap();
+        let stored = stored.as_object_mut().unwrap();
+        stored.remove("valid_from");
+        stored.remove("valid_to");
+        stored.remove("derivation_path_index");
+        let loaded: MintKeySet = serde_json::from_value(stored.clone().into()).unwrap();
+        assert_eq!(loaded.valid_from, 0);
+        assert_eq!(loaded.keys, keyset.keys);
+    }
+
+    #[test]
+    fn test_keyset_serialization_has_no_secrets() {
+        let path = DerivationPath::from_str("m/0'/0'/0'").unwrap();
+        let keyset = MintKeySet::generate(b"secret", CurrencyUnit::Sat, &path, 8).unwrap();
+        let secret_hex: Vec<String> = keyset
+            .keys
+            .values()
+            .map(|pair| pair.secret_key.to_secret_hex())
+            .collect();
+
+        let json = serde_json::to_string(&keyset).unwrap();
+        assert!(secret_hex.iter().all(|hex| !json.contains(hex)));
+        assert!(!json.contains("secret_key"));
+        let public: MintKeySetPublic = serde_json::from_str(&json).unwrap();
+        assert_eq!(public, keyset.public());
+        assert_eq!(serde_json::to_string(&public).unwrap(), json);
+
+        // Secret keys are only written when asked for
+        let stored = keyset
+            .serialize_secret(serde_json::value::Serializer)
+            .unwrap()
+            .to_string();
+        assert!(secret_hex.iter().all(|hex| stored.contains(hex)));
+        assert_eq!(serde_json::from_str::<MintKeySet>(&stored).unwrap(), keyset);
+
+        let secret = keyset
+            .secret()
+            .serialize_secret(serde_json::value::Serializer)
+            .unwrap();
+        let secret: MintKeySetSecret = serde_json::from_value(secret).unwrap();
+        assert_eq!(secret, keyset.secret());
+        assert_eq!(MintKeySet::from_parts(public, secret).unwrap(), keyset);
+    }
+
+    #[test]
+    fn test_keyset_from_mismatched_parts() {
+        let path = DerivationPath::from_str("m/0'/0'/0'").unwrap();
+        let keyset = MintKeySet::generate(b"secret", CurrencyUnit::Sat, &path, 4).unwrap();
+        let other = MintKeySet::generate(b"other secret", CurrencyUnit::Sat, &path, 4).unwrap();
+
+        assert_eq!(
+            MintKeySet::from_parts(keyset.public(), other.secret()),
+            Err(Error::KeyPairMismatch(Amount::from(1)))
+        );
+
+        // Secret keys of another keyset for one amount
+        let mut keys = keyset.secret().keys().clone();
+        keys.insert(
+            Amount::from(4),
+            other.secret().keys()[&Amount::from(4)].clone(),
+        );
+        assert_eq!(
+            MintKeySet::from_parts(keyset.public(), MintKeySetSecret::new(keys)),
+            Err(Error::KeyPairMismatch(Amount::from(4)))
+        );
+
+        let mut keys = keyset.secret().keys().clone();
+        keys.remove(&Amount::from(2));
+        assert_eq!(
+            MintKeySet::from_parts(keyset.public(), MintKeySetSecret::new(keys)),
+            Err(Error::MissingKey(Amount::from(2)))
+        );
+
+        let mut keys = keyset.secret().keys().clone();
+        keys.insert(
+            Amount::from(16),
+            other.secret().keys()[&Amount::from(1)].clone(),
+        );
+        assert_eq!(
+            MintKeySet::from_parts(keyset.public(), MintKeySetSecret::new(keys)),
+            Err(Error::MissingKey(Amount::from(16)))
+        );
+
+        let mut public = keyset.public();
+        public.id = other.id;
+        assert_eq!(
+            MintKeySet::from_parts(public, keyset.secret()),
+            Err(Error::KeysetIdMismatch {
+                claimed: other.id,
+                derived: keyset.id,
+            })
+        );
     }
 
     #[test]
@@ -415,9 +1058,196 @@ mod test {
         assert_eq!(864559728, id_int)
     }
 
+    #[test]
+    fn test_id_to_u32() {
+        for id in ["009a1f293253e41e", "00ad268c4d1f5826", "00ffffffffffffff"] {
+            let id = Id::from_str(id).unwrap();
+            assert_eq!(u64::from(id.to_u32()), u64::try_from(id).unwrap());
+        }
+
+        assert_eq!(
+            Id::from_str("009a1f293253e41e").unwrap().to_u32(),
+            864559728
+        );
+    }
+
+    #[test]
+    fn test_generate_is_deterministic() {
+        let path = DerivationPath::from_str("m/0'/0'/0'").unwrap();
+
+        let keyset = MintKeySet::generate(b"secret", CurrencyUnit::Sat, &path, 8).unwrap();
+        assert_eq!(
+            keyset,
+            MintKeySet::generate(b"secret", CurrencyUnit::Sat, &path, 8).unwrap()
+        );
+        assert_ne!(
+            keyset.id,
+            MintKeySet::generate(b"other secret", CurrencyUnit::Sat, &path, 8)
+                .unwrap()
+                .id
+        );
+
+        let from_rng = |seed| {
+            MintKeySet::generate_with_rng(
+                &mut StdRng::seed_from_u64(seed),
+                CurrencyUnit::Sat,
+                &path,
+                8,
+            )
+            .unwrap()
+        };
+        assert_eq!(from_rng(1), from_rng(1));
+        assert_ne!(from_rng(1).id, from_rng(2).id);
+    }
+
+    #[test]
+    fn test_generate_with_amounts() {
+        let path = DerivationPath::from_str("m/0'/0'/0'").unwrap();
+
+        // Powers of two match the max order derivation
+        assert_eq!(
+            MintKeySet::generate_with_amounts(b"secret", CurrencyUnit::Sat, &path, &[8, 1, 4, 2]),
+            MintKeySet::generate(b"secret", CurrencyUnit::Sat, &path, 4).unwrap()
+        );
+
+        let keyset =
+            MintKeySet::generate_with_amounts(b"secret", CurrencyUnit::Usd, &path, &[1, 5, 10, 50]);
+        let keys = Keys::from(&keyset.keys);
+        assert_eq!(Id::from(&keys), keyset.id);
+
+        let json: serde_json::Value = serde_json::to_value(&keys).unwrap();
+        let mut amounts: Vec<&String> = json.as_object().unwrap().keys().collect();
+        amounts.sort_by_key(|amount| amount.parse::<u64>().unwrap());
+        assert_eq!(amounts, ["1", "5", "10", "50"]);
+
+        let parsed: Keys = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, keys);
+    }
+
+    #[test]
+    fn test_generate_max_order_bounds() {
+        let path = DerivationPath::from_str("m/0'/0'/0'").unwrap();
+
+        for max_order in [0, 65, u8::MAX] {
+            assert_eq!(
+                MintKeySet::generate(b"secret", CurrencyUnit::Sat, &path, max_order),
+                Err(Error::InvalidMaxOrder(max_order))
+            );
+            assert_eq!(
+                power_of_two_amounts(max_order),
+                Err(Error::InvalidMaxOrder(max_order))
+            );
+        }
+
+        let keyset = MintKeySet::generate(b"secret", CurrencyUnit::Sat, &path, 1).unwrap();
+        assert_eq!(keyset.amounts().collect::<Vec<_>>(), vec![Amount::from(1)]);
+        assert_eq!(keyset.max_amount(), Some(Amount::from(1)));
+
+        let keyset = MintKeySet::generate(b"secret", CurrencyUnit::Sat, &path, MAX_ORDER).unwrap();
+        let amounts: Vec<Amount> = keyset.amounts().collect();
+        assert_eq!(amounts.len(), 64);
+        assert_eq!(amounts[0], Amount::from(1));
+        assert!(amounts.windows(2).all(|pair| pair[1] == pair[0] + pair[0]));
+        assert_eq!(keyset.max_amount(), Some(Amount::from(1 << 63)));
+    }
+
+    #[test]
+    fn test_invalid_id() {
+        assert!(matches!(Id::from_str("009a1f293253e4"), Err(Error::Length)));
+
+        assert!(matches!(Id::from_str("I2yN+iRYfkz"), Err(Error::Length)));
+        assert!(matches!(Id::from_str("I2yN-iRYfkzT"), Err(Error::LegacyId)));
+
+        assert!(matches!(
+            Id::from_str("019a1f293253e41e"),
+            Err(Error::UnknownVersion(v)) if v == "01"
+        ));
+
+        assert!(matches!(
+            Id::from_str("009a1f293253e41g"),
+            Err(Error::HexError(_))
+        ));
+        // Sixteen bytes with a char across the first two
+        assert!(matches!(
+            Id::from_str("0\u{e9}a1f293253e41e"),
+            Err(Error::UnknownVersion(_))
+        ));
+
+        assert!(serde_json::from_str::<Id>(r#""009a1f293253e41g""#).is_err());
+    }
+
+    /// Keysets of a mint listing both the legacy and the current id of its
+    /// keysets
+    const KEYS_RESPONSE: &str = r#"{"keysets":[{"id":"I2yN+iRYfkzT","unit":"sat","keys":{"1":"03ba786a2c0745f8c30e490288acd7a72dd53d65afd292ddefa326a4a3fa14c566","2":"03361cd8bd1329fea797a6add1cf1990ffcf2270ceb9fc81eeee0e8e9c1bd0cdf5","4":"036e378bcf78738ddf68859293c69778035740e41138ab183c94f8fee7572214c7","8":"03909d73beaf28edfb283dbeb8da321afd40651e8902fcf5454ecc7d69788626c0","16":"028a36f0e6638ea7466665fe174d958212723019ec08f9ce6898d897f88e68aa5d","32":"03a97a40e146adee2687ac60c2ba2586a90f970de92a9d0e6cae5a4b9965f54612","64":"03ce86f0c197aab181ddba0cfc5c5576e11dfd5164d9f3d4a3fc3ffbbf2e069664","128":"0284f2c06d938a6f78794814c687560a0aabab19fe5e6f30ede38e113b132a3cb9","256":"03b99f475b68e5b4c0ba809cdecaae64eade2d9787aa123206f91cd61f76c01459","512":"03d4db82ea19a44d35274de51f78af0a710925fe7d9e03620b84e3e9976e3ac2eb","1024":"031fbd4ba801870871d46cf62228a1b748905ebc07d3b210daf48de229e683f2dc","2048":"0276cedb9a3b160db6a158ad4e468d2437f021293204b3cd4bf6247970d8aff54b","4096":"02fc6b89b403ee9eb8a7ed457cd3973638080d6e04ca8af7307c965c166b555ea2","8192":"0320265583e916d3a305f0d2687fcf2cd4e3cd03a16ea8261fda309c3ec5721e21","16384":"036e41de58fdff3cb1d8d713f48c63bc61fa3b3e1631495a444d178363c0d2ed50","32768":"0365438f613f19696264300b069d1dad93f0c60a37536b72a8ab7c7366a5ee6c04","65536":"02408426cfb6fc86341bac79624ba8708a4376b2d92debdf4134813f866eb57a8d","131072":"031063e9f11c94dc778c473e968966eac0e70b7145213fbaff5f7a007e71c65f41","262144":"02f2a3e808f9cd168ec71b7f328258d0c1dda250659c1aced14c7f5cf05aab4328","524288":"038ac10de9f1ff9395903bb73077e94dbf91e9ef98fd77d9a2debc5f74c575bc86","1048576":"0203eaee4db749b0fc7c49870d082024b2c31d889f9bc3b32473d4f1dfa3625788","2097152":"033cdb9d36e1e82ae652b7b6a08e0204569ec7ff9ebf85d80a02786dc7fe00b04c","4194304":"02c8b73f4e3a470ae05e5f2fe39984d41e9f6ae7be9f3b09c9ac31292e403ac512","8388608":"025bbe0cfce8a1f4fbd7f3a0d4a09cb6badd73ef61829dc827aa8a98c270bc25b0","16777216":"037eec3d1651a30a90182d9287a5c51386fe35d4a96839cf7969c6e2a03db1fc21","33554432":"03280576b81a04e6abd7197f305506476f5751356b7643988495ca5c3e14e5c262","67108864":"03268bfb05be1dbb33ab6e7e00e438373ca2c9b9abc018fdb452d0e1a0935e10d3","134217728":"02573b68784ceba9617bbcc7c9487836d296aa7c628c3199173a841e7a19798020","268435456":"0234076b6e70f7fbf755d2227ecc8d8169d662518ee3a1401f729e2a12ccb2b276","536870912":"03015bd88961e2a466a2163bd4248d1d2b42c7c58a157e594785e7eb34d880efc9","1073741824":"02c9b076d08f9020ebee49ac8ba2610b404d4e553a4f800150ceb539e9421aaeee","2147483648":"034d592f4c366afddc919a509600af81b489a03caf4f7517c2b3f4f2b558f9a41a","4294967296":"037c09ecb66da082981e4cbdb1ac65c0eb631fc75d85bed13efb2c6364148879b5","8589934592":"02b4ebb0dda3b9ad83b39e2e31024b777cc0ac205a96b9a6cfab3edea2912ed1b3","17179869184":"026cc4dacdced45e63f6e4f62edbc5779ccd802e7fabb82d5123db879b636176e9","34359738368":"02b2cee01b7d8e90180254459b8f09bbea9aad34c3a2fd98c85517ecfc9805af75","68719476736":"037a0c0d564540fc574b8bfa0253cca987b75466e44b295ed59f6f8bd41aace754","137438953472":"021df6585cae9b9ca431318a713fd73dbb76b3ef5667957e8633bca8aaa7214fb6","274877906944":"02b8f53dde126f8c85fa5bb6061c0be5aca90984ce9b902966941caf963648d53a","549755813888":"029cc8af2840d59f1d8761779b2496623c82c64be8e15f9ab577c657c6dd453785","1099511627776":"03e446fdb84fad492ff3a25fc1046fb9a93a5b262ebcd0151caa442ea28959a38a","2199023255552":"02d6b25bd4ab599dd0818c55f75702fde603c93f259222001246569018842d3258","4398046511104":"03397b522bb4e156ec3952d3f048e5a986c20a00718e5e52cd5718466bf494156a","8796093022208":"02d1fb9e78262b5d7d74028073075b80bb5ab281edcfc3191061962c1346340f1e","17592186044416":"030d3f2ad7a4ca115712ff7f140434f802b19a4c9b2dd1c76f3e8e80c05c6a9310","35184372088832":"03e325b691f292e1dfb151c3fb7cad440b225795583c32e24e10635a80e4221c06","70368744177664":"03bee8f64d88de3dee21d61f89efa32933da51152ddbd67466bef815e9f93f8fd1","140737488355328":"0327244c9019a4892e1f04ba3bf95fe43b327479e2d57c25979446cc508cd379ed","281474976710656":"02fb58522cd662f2f8b042f8161caae6e45de98283f74d4e99f19b0ea85e08a56d","562949953421312":"02adde4b466a9d7e59386b6a701a39717c53f30c4810613c1b55e6b6da43b7bc9a","1125899906842624":"038eeda11f78ce05c774f30e393cda075192b890d68590813ff46362548528dca9","2251799813685248":"02ec13e0058b196db80f7079d329333b330dc30c000dbdd7397cbbc5a37a664c4f","4503599627370496":"02d2d162db63675bd04f7d56df04508840f41e2ad87312a3c93041b494efe80a73","9007199254740992":"0356969d6aef2bb40121dbd07c68b6102339f4ea8e674a9008bb69506795998f49","18014398509481984":"02f4e667567ebb9f4e6e180a4113bb071c48855f657766bb5e9c776a880335d1d6","36028797018963968":"0385b4fe35e41703d7a657d957c67bb536629de57b7e6ee6fe2130728ef0fc90b0","72057594037927936":"02b2bc1968a6fddbcc78fb9903940524824b5f5bed329c6ad48a19b56068c144fd","144115188075855872":"02e0dbb24f1d288a693e8a49bc14264d1276be16972131520cf9e055ae92fba19a","288230376151711744":"03efe75c106f931a525dc2d653ebedddc413a2c7d8cb9da410893ae7d2fa7d19cc","576460752303423488":"02c7ec2bd9508a7fc03f73c7565dc600b30fd86f3d305f8f139c45c404a52d958a","1152921504606846976":"035a6679c6b25e68ff4e29d1c7ef87f21e0a8fc574f6a08c1aa45ff352c1d59f06","2305843009213693952":"033cdc225962c052d485f7cfbf55a5b2367d200fe1fe4373a347deb4cc99e9a099","4611686018427387904":"024a4b806cf413d14b294719090a9da36ba75209c7657135ad09bc65328fba9e6f","9223372036854775808":"0377a6fe114e291a8d8e991627c38001c8305b23b9e98b1c7b1893f5cd0dda6cad"}},{"id":"00759e3f8b06b36f","unit":"sat","keys":{"1":"038a935c51c76c780ff9731cfbe9ab477f38346775809fa4c514340feabbec4b3a","2":"038288b12ebf2db3645e5d58835bd100398b6b19dfef338c698b55c05d0d41fb0a","4":"02fc8201cf4ea29abac0495d1304064f0e698762b8c0db145c1737b38a9d61c7e2","8":"02274243e03ca19f969acc7072812405b38adc672d1d753e65c63746b3f31cc6eb","16":"025f07cb2493351e7d5202f05eaf3934d5c9d17e73385e9de5bfab802f7d8caf92","32":"03afce0a897c858d7c88c1454d492eac43011e3396dda5b778ba1fcab381c748b1","64":"037b2178f42507f0c95e09d9b435a127df4b3e23ccd20af8075817d3abe90947ad","128":"02ebce8457b48407d4d248dba5a31b3eabf08a6285d09d08e40681c4adaf77bd40","256":"03c89713d27d6f8e328597b43dd87623efdcb251a484932f9e095ebfb6dbf4bdf2","512":"02df10f3ebba69916d03ab1754488770498f2e5466224d6df6d12811a13e46776c","1024":"02f5d9cba0502c21c6b39938a09dcb0390f124a2fd65e45dfeccd153cc1864273d","2048":"039de1dad91761b194e7674fb6ba212241aaf7f49dcb578a8fe093196ad1b20d1c","4096":"03cc694ba22e455f1c22b2cee4a40ecdd4f3bb4da0745411adb456158372d3efbb","8192":"029d66c24450fc315e046010df6870d61daa90c5c486c5ec4d7d3b99c5c2bce923","16384":"0387d063821010c7bd5cf79441870182f70cd432d13d3fc255e7b6ffd82c9d3c5a","32768":"021a94c6c03f7de8feb25b8a8b8d1f1c6f56af4bc533eb97c9e8b89c76b616ff11","65536":"038989c6ed91a7c577953115b465ee400a270a64e95eda8f7ee9d6bf30b8fe4908","131072":"03c3d3cd2523f004ee479a170b0ec5c74c060edb8356fc1b0a9ed8087cf6345172","262144":"02e54a7546f1a9194f30baa593a13d4e2949eb866593445d89675d7d394ef6320b","524288":"034e91037b3f1d3258d1e871dede80e98ef83e307c2e5ff589f38bd046f97546f8","1048576":"03306d42752a1adcfa394af2a690961ac9b80b1ac0f5fdc0890f66f8dc7d25ac6e","2097152":"03ec114332fe798c3e36675566c4748fda7d881000a01864ec48486512d7901e76","4194304":"02095e3e443d98ca3dfabcebc2f9154f3656b889783f7edb8290cfb01f497e63cf","8388608":"03c90f31525a4f9ab6562ec3edbf2bafc6662256ea6ce82ab19a45d2aee80b2f15","16777216":"03c0ae897a45724465c713c1379671ac5ff0a81c32e5f2dd27ea7e5530c7af484c","33554432":"034bcf793b70ba511e9c84cd07fc0c73c061e912bc02df4cac7871d048bad653b6","67108864":"021c6826c23a181d14962f43121943569a54f9d5af556eb839aee42d3f62debee6","134217728":"030e1bc651b6496922978d6cd3ed923cbf12b4332c496f841f506f5abf9d186d35","268435456":"03e3219e50cf389a75794f82ab4f880f5ffe9ca227b992c3e93cb4bb659d8e3353","536870912":"03879ad42536c410511ac6956b9da2d0da59ce7fbb6068bd9b25dd7cccddcc8096","1073741824":"03c4d3755a17904c0cfa7d7a21cc5b4e85fca8ac85369fcb12a6e2177525117dee","2147483648":"02e7a5d5cd3ea24f05f741dddad3dc8c5e24db60eb9bf9ad888b1c5dfbd792665e","4294967296":"03c783d24d8c9e51207eb3d6199bf48d6eb81a4b34103b422724be15501ff921bd","8589934592":"03200234495725455f4c4e6b6cb7b7936eb7cd1d1c9bb73d2ce032bae7d728b3ca","17179869184":"02eafa50ac67de2c206d1a67245b72ec20fac081c2a550294cc0a711246ed65a41","34359738368":"024c153c2a56de05860006aff9dc35ec9cafd7ac68708442a3a326c858b0c1a146","68719476736":"035a890c2d5c8bf259b98ac67d0d813b87778bcb0c0ea1ee9717ac804b0be3f563","137438953472":"025184ca832f08b105fdb471e2caf14025a1daa6f44ce90b4c7703878ccb6b26e8","274877906944":"039d19a41abdd49949c60672430018c63f27c5a28991f9fbb760499daccc63146c","549755813888":"03a138ac626dd3e6753459903aa128a13c052ed0058f2ead707c203bd4a7565237","1099511627776":"0298c8ef2eab728613103481167102efaf2d4b7a303cb94b9393da37a034a95c53","2199023255552":"02d88f8fc93cd2edf303fdebfecb70e59b5373cb8f746a1d075a9c86bc9382ac07","4398046511104":"02afd89ee23eee7d5fe6687fee898f64e9b01913ec71b5c596762b215e040c701f","8796093022208":"02196b461f3c804259e597c50e514920427aab4beaef0c666185fb2ff4399813db","17592186044416":"037b33746a6fd7a71d4cf17c85d13a64b98620614c0028d4995163f1b8484ee337","35184372088832":"036cce0a1878bbc63b3108c379ef4e6529fbf20ed675d80d91ca3ccc55fde4bdbd","70368744177664":"039c81dccb319ba70597cdf9db33b459164a1515c27366c8f667b01d988874e554","140737488355328":"036b2dd85a3c44c4458f0b246ce19a1524a191f1716834cfb452c6e1f946172c19","281474976710656":"022c84722c31a2b3d8cfd9b6a9e6199515fd97d6a9c390fc3d82f123bfc501ad04","562949953421312":"0355e2be85ee599b8fa7e6e68a9954573d032e89aa9e65c2e1231991664c200bf3","1125899906842624":"024b10818cd27f3eec6c9daf82b9dfa53928ab0711b711070bd39892ac10dee765","2251799813685248":"02a6d726432bb18c3145eba4fc0b587bf64f3be8617c0070dda33944474b3f8740","4503599627370496":"0248304be3cbaf31ec320bc636bb936c5984caf773df950fc44c6237ec09c557a1","9007199254740992":"03a3c0e9da7ece7d7b132c53662c0389bd87db801dff5ac9edd9f46699cb1dc065","18014398509481984":"03b6c4c874e2392072e17fbfd181afbd40d6766a8ca4cf932264ba98d98de1328c","36028797018963968":"0370dca4416ec6e30ff02f8e9db7804348b42e3f5c22099dfc896fa1b2ccbe7a69","72057594037927936":"0226250140aedb79de91cb4cc7350884bde229063f34ee0849081bb391a37c273e","144115188075855872":"02baef3a94d241aee9d6057c7a7ee7424f8a0bcb910daf6c49ddcabf70ffbc77d8","288230376151711744":"030f95a12369f1867ce0dbf2a6322c27d70c61b743064d76cfc81dd43f1a052ae6","576460752303423488":"021bc89118ab6eb1fbebe0fa6cc76da8236a7991163475a73a22d8efd016a45800","1152921504606846976":"03b0c1e658d7ca12830a0b590ea5a4d6db51084ae80b6d8abf27ad2d762209acd1","2305843009213693952":"0266926ce658a0bdae934071f22e09dbb6ecaff2a4dc4b1f8e23626570d993b48e","4611686018427387904":"03ac17f10f9bb745ebd8ee9cdca1b6981f5a356147d431196c21c6d4869402bde0","9223372036854775808":"037ab5b88c8ce34c4a3970be5c6f75b8a7a5493a12ef56a1c9ba9ff5f90de46fcc"}},{"id":"000f01df73ea149a","unit":"sat","keys":{"1":"03ba786a2c0745f8c30e490288acd7a72dd53d65afd292ddefa326a4a3fa14c566","2":"03361cd8bd1329fea797a6add1cf1990ffcf2270ceb9fc81eeee0e8e9c1bd0cdf5","4":"036e378bcf78738ddf68859293c69778035740e41138ab183c94f8fee7572214c7","8":"03909d73beaf28edfb283dbeb8da321afd40651e8902fcf5454ecc7d69788626c0","16":"028a36f0e6638ea7466665fe174d958212723019ec08f9ce6898d897f88e68aa5d","32":"03a97a40e146adee2687ac60c2ba2586a90f970de92a9d0e6cae5a4b9965f54612","64":"03ce86f0c197aab181ddba0cfc5c5576e11dfd5164d9f3d4a3fc3ffbbf2e069664","128":"0284f2c06d938a6f78794814c687560a0aabab19fe5e6f30ede38e113b132a3cb9","256":"03b99f475b68e5b4c0ba809cdecaae64eade2d9787aa123206f91cd61f76c01459","512":"03d4db82ea19a44d35274de51f78af0a710925fe7d9e03620b84e3e9976e3ac2eb","1024":"031fbd4ba801870871d46cf62228a1b748905ebc07d3b210daf48de229e683f2dc","2048":"0276cedb9a3b160db6a158ad4e468d2437f021293204b3cd4bf6247970d8aff54b","4096":"02fc6b89b403ee9eb8a7ed457cd3973638080d6e04ca8af7307c965c166b555ea2","8192":"0320265583e916d3a305f0d2687fcf2cd4e3cd03a16ea8261fda309c3ec5721e21","16384":"036e41de58fdff3cb1d8d713f48c63bc61fa3b3e1631495a444d178363c0d2ed50","32768":"0365438f613f19696264300b069d1dad93f0c60a37536b72a8ab7c7366a5ee6c04","65536":"02408426cfb6fc86341bac79624ba8708a4376b2d92debdf4134813f866eb57a8d","131072":"031063e9f11c94dc778c473e968966eac0e70b7145213fbaff5f7a007e71c65f41","262144":"02f2a3e808f9cd168ec71b7f328258d0c1dda250659c1aced14c7f5cf05aab4328","524288":"038ac10de9f1ff9395903bb73077e94dbf91e9ef98fd77d9a2debc5f74c575bc86","1048576":"0203eaee4db749b0fc7c49870d082024b2c31d889f9bc3b32473d4f1dfa3625788","2097152":"033cdb9d36e1e82ae652b7b6a08e0204569ec7ff9ebf85d80a02786dc7fe00b04c","4194304":"02c8b73f4e3a470ae05e5f2fe39984d41e9f6ae7be9f3b09c9ac31292e403ac512","8388608":"025bbe0cfce8a1f4fbd7f3a0d4a09cb6badd73ef61829dc827aa8a98c270bc25b0","16777216":"037eec3d1651a30a90182d9287a5c51386fe35d4a96839cf7969c6e2a03db1fc21","33554432":"03280576b81a04e6abd7197f305506476f5751356b7643988495ca5c3e14e5c262","67108864":"03268bfb05be1dbb33ab6e7e00e438373ca2c9b9abc018fdb452d0e1a0935e10d3","134217728":"02573b68784ceba9617bbcc7c9487836d296aa7c628c3199173a841e7a19798020","268435456":"0234076b6e70f7fbf755d2227ecc8d8169d662518ee3a1401f729e2a12ccb2b276","536870912":"03015bd88961e2a466a2163bd4248d1d2b42c7c58a157e594785e7eb34d880efc9","1073741824":"02c9b076d08f9020ebee49ac8ba2610b404d4e553a4f800150ceb539e9421aaeee","2147483648":"034d592f4c366afddc919a509600af81b489a03caf4f7517c2b3f4f2b558f9a41a","4294967296":"037c09ecb66da082981e4cbdb1ac65c0eb631fc75d85bed13efb2c6364148879b5","8589934592":"02b4ebb0dda3b9ad83b39e2e31024b777cc0ac205a96b9a6cfab3edea2912ed1b3","17179869184":"026cc4dacdced45e63f6e4f62edbc5779ccd802e7fabb82d5123db879b636176e9","34359738368":"02b2cee01b7d8e90180254459b8f09bbea9aad34c3a2fd98c85517ecfc9805af75","68719476736":"037a0c0d564540fc574b8bfa0253cca987b75466e44b295ed59f6f8bd41aace754","137438953472":"021df6585cae9b9ca431318a713fd73dbb76b3ef5667957e8633bca8aaa7214fb6","274877906944":"02b8f53dde126f8c85fa5bb6061c0be5aca90984ce9b902966941caf963648d53a","549755813888":"029cc8af2840d59f1d8761779b2496623c82c64be8e15f9ab577c657c6dd453785","1099511627776":"03e446fdb84fad492ff3a25fc1046fb9a93a5b262ebcd0151caa442ea28959a38a","2199023255552":"02d6b25bd4ab599dd0818c55f75702fde603c93f259222001246569018842d3258","4398046511104":"03397b522bb4e156ec3952d3f048e5a986c20a00718e5e52cd5718466bf494156a","8796093022208":"02d1fb9e78262b5d7d74028073075b80bb5ab281edcfc3191061962c1346340f1e","17592186044416":"030d3f2ad7a4ca115712ff7f140434f802b19a4c9b2dd1c76f3e8e80c05c6a9310","35184372088832":"03e325b691f292e1dfb151c3fb7cad440b225795583c32e24e10635a80e4221c06","70368744177664":"03bee8f64d88de3dee21d61f89efa32933da51152ddbd67466bef815e9f93f8fd1","140737488355328":"0327244c9019a4892e1f04ba3bf95fe43b327479e2d57c25979446cc508cd379ed","281474976710656":"02fb58522cd662f2f8b042f8161caae6e45de98283f74d4e99f19b0ea85e08a56d","562949953421312":"02adde4b466a9d7e59386b6a701a39717c53f30c4810613c1b55e6b6da43b7bc9a","1125899906842624":"038eeda11f78ce05c774f30e393cda075192b890d68590813ff46362548528dca9","2251799813685248":"02ec13e0058b196db80f7079d329333b330dc30c000dbdd7397cbbc5a37a664c4f","4503599627370496":"02d2d162db63675bd04f7d56df04508840f41e2ad87312a3c93041b494efe80a73","9007199254740992":"0356969d6aef2bb40121dbd07c68b6102339f4ea8e674a9008bb69506795998f49","18014398509481984":"02f4e667567ebb9f4e6e180a4113bb071c48855f657766bb5e9c776a880335d1d6","36028797018963968":"0385b4fe35e41703d7a657d957c67bb536629de57b7e6ee6fe2130728ef0fc90b0","72057594037927936":"02b2bc1968a6fddbcc78fb9903940524824b5f5bed329c6ad48a19b56068c144fd","144115188075855872":"02e0dbb24f1d288a693e8a49bc14264d1276be16972131520cf9e055ae92fba19a","288230376151711744":"03efe75c106f931a525dc2d653ebedddc413a2c7d8cb9da410893ae7d2fa7d19cc","576460752303423488":"02c7ec2bd9508a7fc03f73c7565dc600b30fd86f3d305f8f139c45c404a52d958a","1152921504606846976":"035a6679c6b25e68ff4e29d1c7ef87f21e0a8fc574f6a08c1aa45ff352c1d59f06","2305843009213693952":"033cdc225962c052d485f7cfbf55a5b2367d200fe1fe4373a347deb4cc99e9a099","4611686018427387904":"024a4b806cf413d14b294719090a9da36ba75209c7657135ad09bc65328fba9e6f","9223372036854775808":"0377a6fe114e291a8d8e991627c38001c8305b23b9e98b1c7b1893f5cd0dda6cad"}},{"id":"yjzQhxghPdrr","unit":"sat","keys":{"1":"038a935c51c76c780ff9731cfbe9ab477f38346775809fa4c514340feabbec4b3a","2":"038288b12ebf2db3645e5d58835bd100398b6b19dfef338c698b55c05d0d41fb0a","4":"02fc8201cf4ea29abac0495d1304064f0e698762b8c0db145c1737b38a9d61c7e2","8":"02274243e03ca19f969acc7072812405b38adc672d1d753e65c63746b3f31cc6eb","16":"025f07cb2493351e7d5202f05eaf3934d5c9d17e73385e9de5bfab802f7d8caf92","32":"03afce0a897c858d7c88c1454d492eac43011e3396dda5b778ba1fcab381c748b1","64":"037b2178f42507f0c95e09d9b435a127df4b3e23ccd20af8075817d3abe90947ad","128":"02ebce8457b48407d4d248dba5a31b3eabf08a6285d09d08e40681c4adaf77bd40","256":"03c89713d27d6f8e328597b43dd87623efdcb251a484932f9e095ebfb6dbf4bdf2","512":"02df10f3ebba69916d03ab1754488770498f2e5466224d6df6d12811a13e46776c","1024":"02f5d9cba0502c21c6b39938a09dcb0390f124a2fd65e45dfeccd153cc1864273d","2048":"039de1dad91761b194e7674fb6ba212241aaf7f49dcb578a8fe093196ad1b20d1c","4096":"03cc694ba22e455f1c22b2cee4a40ecdd4f3bb4da0745411adb456158372d3efbb","8192":"029d66c24450fc315e046010df6870d61daa90c5c486c5ec4d7d3b99c5c2bce923","16384":"0387d063821010c7bd5cf79441870182f70cd432d13d3fc255e7b6ffd82c9d3c5a","32768":"021a94c6c03f7de8feb25b8a8b8d1f1c6f56af4bc533eb97c9e8b89c76b616ff11","65536":"038989c6ed91a7c577953115b465ee400a270a64e95eda8f7ee9d6bf30b8fe4908","131072":"03c3d3cd2523f004ee479a170b0ec5c74c060edb8356fc1b0a9ed8087cf6345172","262144":"02e54a7546f1a9194f30baa593a13d4e2949eb866593445d89675d7d394ef6320b","524288":"034e91037b3f1d3258d1e871dede80e98ef83e307c2e5ff589f38bd046f97546f8","1048576":"03306d42752a1adcfa394af2a690961ac9b80b1ac0f5fdc0890f66f8dc7d25ac6e","2097152":"03ec114332fe798c3e36675566c4748fda7d881000a01864ec48486512d7901e76","4194304":"02095e3e443d98ca3dfabcebc2f9154f3656b889783f7edb8290cfb01f497e63cf","8388608":"03c90f31525a4f9ab6562ec3edbf2bafc6662256ea6ce82ab19a45d2aee80b2f15","16777216":"03c0ae897a45724465c713c1379671ac5ff0a81c32e5f2dd27ea7e5530c7af484c","33554432":"034bcf793b70ba511e9c84cd07fc0c73c061e912bc02df4cac7871d048bad653b6","67108864":"021c6826c23a181d14962f43121943569a54f9d5af556eb839aee42d3f62debee6","134217728":"030e1bc651b6496922978d6cd3ed923cbf12b4332c496f841f506f5abf9d186d35","268435456":"03e3219e50cf389a75794f82ab4f880f5ffe9ca227b992c3e93cb4bb659d8e3353","536870912":"03879ad42536c410511ac6956b9da2d0da59ce7fbb6068bd9b25dd7cccddcc8096","1073741824":"03c4d3755a17904c0cfa7d7a21cc5b4e85fca8ac85369fcb12a6e2177525117dee","2147483648":"02e7a5d5cd3ea24f05f741dddad3dc8c5e24db60eb9bf9ad888b1c5dfbd792665e","4294967296":"03c783d24d8c9e51207eb3d6199bf48d6eb81a4b34103b422724be15501ff921bd","8589934592":"03200234495725455f4c4e6b6cb7b7936eb7cd1d1c9bb73d2ce032bae7d728b3ca","17179869184":"02eafa50ac67de2c206d1a67245b72ec20fac081c2a550294cc0a711246ed65a41","34359738368":"024c153c2a56de05860006aff9dc35ec9cafd7ac68708442a3a326c858b0c1a146","68719476736":"035a890c2d5c8bf259b98ac67d0d813b87778bcb0c0ea1ee9717ac804b0be3f563","137438953472":"025184ca832f08b105fdb471e2caf14025a1daa6f44ce90b4c7703878ccb6b26e8","274877906944":"039d19a41abdd49949c60672430018c63f27c5a28991f9fbb760499daccc63146c","549755813888":"03a138ac626dd3e6753459903aa128a13c052ed0058f2ead707c203bd4a7565237","1099511627776":"0298c8ef2eab728613103481167102efaf2d4b7a303cb94b9393da37a034a95c53","2199023255552":"02d88f8fc93cd2edf303fdebfecb70e59b5373cb8f746a1d075a9c86bc9382ac07","4398046511104":"02afd89ee23eee7d5fe6687fee898f64e9b01913ec71b5c596762b215e040c701f","8796093022208":"02196b461f3c804259e597c50e514920427aab4beaef0c666185fb2ff4399813db","17592186044416":"037b33746a6fd7a71d4cf17c85d13a64b98620614c0028d4995163f1b8484ee337","35184372088832":"036cce0a1878bbc63b3108c379ef4e6529fbf20ed675d80d91ca3ccc55fde4bdbd","70368744177664":"039c81dccb319ba70597cdf9db33b459164a1515c27366c8f667b01d988874e554","140737488355328":"036b2dd85a3c44c4458f0b246ce19a1524a191f1716834cfb452c6e1f946172c19","281474976710656":"022c84722c31a2b3d8cfd9b6a9e6199515fd97d6a9c390fc3d82f123bfc501ad04","562949953421312":"0355e2be85ee599b8fa7e6e68a9954573d032e89aa9e65c2e1231991664c200bf3","1125899906842624":"024b10818cd27f3eec6c9daf82b9dfa53928ab0711b711070bd39892ac10dee765","2251799813685248":"02a6d726432bb18c3145eba4fc0b587bf64f3be8617c0070dda33944474b3f8740","4503599627370496":"0248304be3cbaf31ec320bc636bb936c5984caf773df950fc44c6237ec09c557a1","9007199254740992":"03a3c0e9da7ece7d7b132c53662c0389bd87db801dff5ac9edd9f46699cb1dc065","18014398509481984":"03b6c4c874e2392072e17fbfd181afbd40d6766a8ca4cf932264ba98d98de1328c","36028797018963968":"0370dca4416ec6e30ff02f8e9db7804348b42e3f5c22099dfc896fa1b2ccbe7a69","72057594037927936":"0226250140aedb79de91cb4cc7350884bde229063f34ee0849081bb391a37c273e","144115188075855872":"02baef3a94d241aee9d6057c7a7ee7424f8a0bcb910daf6c49ddcabf70ffbc77d8","288230376151711744":"030f95a12369f1867ce0dbf2a6322c27d70c61b743064d76cfc81dd43f1a052ae6","576460752303423488":"021bc89118ab6eb1fbebe0fa6cc76da8236a7991163475a73a22d8efd016a45800","1152921504606846976":"03b0c1e658d7ca12830a0b590ea5a4d6db51084ae80b6d8abf27ad2d762209acd1","2305843009213693952":"0266926ce658a0bdae934071f22e09dbb6ecaff2a4dc4b1f8e23626570d993b48e","4611686018427387904":"03ac17f10f9bb745ebd8ee9cdca1b6981f5a356147d431196c21c6d4869402bde0","9223372036854775808":"037ab5b88c8ce34c4a3970be5c6f75b8a7a5493a12ef56a1c9ba9ff5f90de46fcc"}}]}"#;
+
+    #[test]
+    fn test_legacy_id() {
+        let keysets: Vec<KeySet> = serde_json::from_str::<serde_json::Value>(KEYS_RESPONSE)
+            .unwrap()["keysets"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|keyset| serde_json::from_value(keyset.clone()).unwrap())
+            .collect();
+
+        // Legacy and current ids of the same keys
+        for (legacy, current) in [(&keysets[0], &keysets[2]), (&keysets[3], &keysets[1])] {
+            assert!(legacy.id.is_legacy());
+            assert!(!current.id.is_legacy());
+            assert_eq!(legacy.keys, current.keys);
+            assert_eq!(Id::legacy_from_keys(&current.keys), legacy.id);
+            assert_eq!(Id::from(&current.keys), current.id);
+        }
+
+        let id = Id::from_str("I2yN+iRYfkzT").unwrap();
+        assert!(id.is_legacy());
+        assert_eq!(id.to_string(), "I2yN+iRYfkzT");
+        assert_eq!(
+            serde_json::from_str::<Id>(&serde_json::to_string(&id).unwrap()).unwrap(),
+            id
+        );
+        assert_ne!(id, Id::from_str("000f01df73ea149a").unwrap());
+    }
+
+    #[test]
+    fn test_verify_id() {
+        let keysets: Vec<KeySet> = serde_json::from_str::<serde_json::Value>(KEYS_RESPONSE)
+            .unwrap()["keysets"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|keyset| serde_json::from_value(keyset.clone()).unwrap())
+            .collect();
+
+        // Current and legacy ids both match their derivation
+        for keyset in &keysets {
+            keyset.verify_id().unwrap();
+        }
+
+        let claimed = keysets[2].id;
+        let derived = keysets[1].id;
+        let wrong = KeySet {
+            id: claimed,
+            ..keysets[1].clone()
+        };
+        assert!(matches!(
+            wrong.verify_id(),
+            Err(Error::KeysetIdMismatch { claimed: c, derived: d }) if c == claimed && d == derived
+        ));
+
+        let wrong_legacy = KeySet {
+            id: keysets[0].id,
+            ..keysets[1].clone()
+        };
+        assert!(matches!(
+            wrong_legacy.verify_id(),
+            Err(Error::KeysetIdMismatch { derived, .. }) if derived == keysets[3].id
+        ));
+    }
+
     #[test]
     fn test_de_keysresponse() {
-        let keys = r#"{"keysets":[{"id":"I2yN+iRYfkzT","unit":"sat","keys":{"1":"03ba786a2c0745f8c30e490288acd7a72dd53d65afd292ddefa326a4a3fa14c566","2":"03361cd8bd1329fea797a6add1cf1990ffcf2270ceb9fc81eeee0e8e9c1bd0cdf5","4":"036e378bcf78738ddf68859293c69778035740e41138ab183c94f8fee7572214c7","8":"03909d73beaf28edfb283dbeb8da321afd40651e8902fcf5454ecc7d69788626c0","16":"028a36f0e6638ea7466665fe174d958212723019ec08f9ce6898d897f88e68aa5d","32":"03a97a40e146adee2687ac60c2ba2586a90f970de92a9d0e6cae5a4b9965f54612","64":"03ce86f0c197aab181ddba0cfc5c5576e11dfd5164d9f3d4a3fc3ffbbf2e069664","128":"0284f2c06d938a6f78794814c687560a0aabab19fe5e6f30ede38e113b132a3cb9","256":"03b99f475b68e5b4c0ba809cdecaae64eade2d9787aa123206f91cd61f76c01459","512":"03d4db82ea19a44d35274de51f78af0a710925fe7d9e03620b84e3e9976e3ac2eb","1024":"031fbd4ba801870871d46cf62228a1b748905ebc07d3b210daf48de229e683f2dc","2048":"0276cedb9a3b160db6a158ad4e468d2437f021293204b3cd4bf6247970d8aff54b","4096":"02fc6b89b403ee9eb8a7ed457cd3973638080d6e04ca8af7307c965c166b555ea2","8192":"0320265583e916d3a305f0d2687fcf2cd4e3cd03a16ea8261fda309c3ec5721e21","16384":"036e41de58fdff3cb1d8d713f48c63bc61fa3b3e1631495a444d178363c0d2ed50","32768":"0365438f613f19696264300b069d1dad93f0c60a37536b72a8ab7c7366a5ee6c04","65536":"02408426cfb6fc86341bac79624ba8708a4376b2d92debdf4134813f866eb57a8d","131072":"031063e9f11c94dc778c473e968966eac0e70b7145213fbaff5f7a007e71c65f41","262144":"02f2a3e808f9cd168ec71b7f328258d0c1dda250659c1aced14c7f5cf05aab4328","524288":"038ac10de9f1ff9395903bb73077e94dbf91e9ef98fd77d9a2debc5f74c575bc86","1048576":"0203eaee4db749b0fc7c49870d082024b2c31d889f9bc3b32473d4f1dfa3625788","2097152":"033cdb9d36e1e82ae652b7b6a08e0204569ec7ff9ebf85d80a02786dc7fe00b04c","4194304":"02c8b73f4e3a470ae05e5f2fe39984d41e9f6ae7be9f3b09c9ac31292e403ac512","8388608":"025bbe0cfce8a1f4fbd7f3a0d4a09cb6badd73ef61829dc827aa8a98c270bc25b0","16777216":"037eec3d1651a30a90182d9287a5c51386fe35d4a96839cf7969c6e2a03db1fc21","33554432":"03280576b81a04e6abd7197f305506476f5751356b7643988495ca5c3e14e5c262","67108864":"03268bfb05be1dbb33ab6e7e00e438373ca2c9b9abc018fdb452d0e1a0935e10d3","134217728":"02573b68784ceba9617bbcc7c9487836d296aa7c628c3199173a841e7a19798020","268435456":"0234076b6e70f7fbf755d2227ecc8d8169d662518ee3a1401f729e2a12ccb2b276","536870912":"03015bd88961e2a466a2163bd4248d1d2b42c7c58a157e594785e7eb34d880efc9","1073741824":"02c9b076d08f9020ebee49ac8ba2610b404d4e553a4f800150ceb539e9421aaeee","2147483648":"034d592f4c366afddc919a509600af81b489a03caf4f7517c2b3f4f2b558f9a41a","4294967296":"037c09ecb66da082981e4cbdb1ac65c0eb631fc75d85bed13efb2c6364148879b5","8589934592":"02b4ebb0dda3b9ad83b39e2e31024b777cc0ac205a96b9a6cfab3edea2912ed1b3","17179869184":"026cc4dacdced45e63f6e4f62edbc5779ccd802e7fabb82d5123db879b636176e9","34359738368":"02b2cee01b7d8e90180254459b8f09bbea9aad34c3a2fd98c85517ecfc9805af75","68719476736":"037a0c0d564540fc574b8bfa0253cca987b75466e44b295ed59f6f8bd41aace754","137438953472":"021df6585cae9b9ca431318a713fd73dbb76b3ef5667957e8633bca8aaa7214fb6","274877906944":"02b8f53dde126f8c85fa5bb6061c0be5aca90984ce9b902966941caf963648d53a","549755813888":"029cc8af2840d59f1d8761779b2496623c82c64be8e15f9ab577c657c6dd453785","1099511627776":"03e446fdb84fad492ff3a25fc1046fb9a93a5b262ebcd0151caa442ea28959a38a","2199023255552":"02d6b25bd4ab599dd0818c55f75702fde603c93f259222001246569018842d3258","4398046511104":"03397b522bb4e156ec3952d3f048e5a986c20a00718e5e52cd5718466bf494156a","8796093022208":"02d1fb9e78262b5d7d74028073075b80bb5ab281edcfc3191061962c1346340f1e","17592186044416":"030d3f2ad7a4ca115712ff7f140434f802b19a4c9b2dd1c76f3e8e80c05c6a9310","35184372088832":"03e325b691f292e1dfb151c3fb7cad440b225795583c32e24e10635a80e4221c06","70368744177664":"03bee8f64d88de3dee21d61f89efa32933da51152ddbd67466bef815e9f93f8fd1","140737488355328":"0327244c9019a4892e1f04ba3bf95fe43b327479e2d57c25979446cc508cd379ed","281474976710656":"02fb58522cd662f2f8b042f8161caae6e45de98283f74d4e99f19b0ea85e08a56d","562949953421312":"02adde4b466a9d7e59386b6a701a39717c53f30c4810613c1b55e6b6da43b7bc9a","1125899906842624":"038eeda11f78ce05c774f30e393cda075192b890d68590813ff46362548528dca9","2251799813685248":"02ec13e0058b196db80f7079d329333b330dc30c000dbdd7397cbbc5a37a664c4f","4503599627370496":"02d2d162db63675bd04f7d56df04508840f41e2ad87312a3c93041b494efe80a73","9007199254740992":"0356969d6aef2bb40121dbd07c68b6102339f4ea8e674a9008bb69506795998f49","18014398509481984":"02f4e667567ebb9f4e6e180a4113bb071c48855f657766bb5e9c776a880335d1d6","36028797018963968":"0385b4fe35e41703d7a657d957c67bb536629de57b7e6ee6fe2130728ef0fc90b0","72057594037927936":"02b2bc1968a6fddbcc78fb9903940524824b5f5bed329c6ad48a19b56068c144fd","144115188075855872":"02e0dbb24f1d288a693e8a49bc14264d1276be16972131520cf9e055ae92fba19a","288230376151711744":"03efe75c106f931a525dc2d653ebedddc413a2c7d8cb9da410893ae7d2fa7d19cc","576460752303423488":"02c7ec2bd9508a7fc03f73c7565dc600b30fd86f3d305f8f139c45c404a52d958a","1152921504606846976":"035a6679c6b25e68ff4e29d1c7ef87f21e0a8fc574f6a08c1aa45ff352c1d59f06","2305843009213693952":"033cdc225962c052d485f7cfbf55a5b2367d200fe1fe4373a347deb4cc99e9a099","4611686018427387904":"024a4b806cf413d14b294719090a9da36ba75209c7657135ad09bc65328fba9e6f","9223372036854775808":"0377a6fe114e291a8d8e991627c38001c8305b23b9e98b1c7b1893f5cd0dda6cad"}},{"id":"00759e3f8b06b36f","unit":"sat","keys":{"1":"038a935c51c76c780ff9731cfbe9ab477f38346775809fa4c514340feabbec4b3a","2":"038288b12ebf2db3645e5d58835bd100398b6b19dfef338c698b55c05d0d41fb0a","4":"02fc8201cf4ea29abac0495d1304064f0e698762b8c0db145c1737b38a9d61c7e2","8":"02274243e03ca19f969acc7072812405b38adc672d1d753e65c63746b3f31cc6eb","16":"025f07cb2493351e7d5202f05eaf3934d5c9d17e73385e9de5bfab802f7d8caf92","32":"03afce0a897c858d7c88c1454d492eac43011e3396dda5b778ba1fcab381c748b1","64":"037b2178f42507f0c95e09d9b435a127df4b3e23ccd20af8075817d3abe90947ad","128":"02ebce8457b48407d4d248dba5a31b3eabf08a6285d09d08e40681c4adaf77bd40","256":"03c89713d27d6f8e328597b43dd87623efdcb251a484932f9e095ebfb6dbf4bdf2","512":"02df10f3ebba69916d03ab1754488770498f2e5466224d6df6d12811a13e46776c","1024":"02f5d9cba0502c21c6b39938a09dcb0390f124a2fd65e45dfeccd153cc1864273d","2048":"039de1dad91761b194e7674fb6ba212241aaf7f49dcb578a8fe093196ad1b20d1c","4096":"03cc694ba22e455f1c22b2cee4a40ecdd4f3bb4da0745411adb456158372d3efbb","8192":"029d66c24450fc315e046010df6870d61daa90c5c486c5ec4d7d3b99c5c2bce923","16384":"0387d063821010c7bd5cf79441870182f70cd432d13d3fc255e7b6ffd82c9d3c5a","32768":"021a94c6c03f7de8feb25b8a8b8d1f1c6f56af4bc533eb97c9e8b89c76b616ff11","65536":"038989c6ed91a7c577953115b465ee400a270a64e95eda8f7ee9d6bf30b8fe4908","131072":"03c3d3cd2523f004ee479a170b0ec5c74c060edb8356fc1b0a9ed8087cf6345172","262144":"02e54a7546f1a9194f30baa593a13d4e2949eb866593445d89675d7d394ef6320b","524288":"034e91037b3f1d3258d1e871dede80e98ef83e307c2e5ff589f38bd046f97546f8","1048576":"03306d42752a1adcfa394af2a690961ac9b80b1ac0f5fdc0890f66f8dc7d25ac6e","2097152":"03ec114332fe798c3e36675566c4748fda7d881000a01864ec48486512d7901e76","4194304":"02095e3e443d98ca3dfabcebc2f9154f3656b889783f7edb8290cfb01f497e63cf","8388608":"03c90f31525a4f9ab6562ec3edbf2bafc6662256ea6ce82ab19a45d2aee80b2f15","16777216":"03c0ae897a45724465c713c1379671ac5ff0a81c32e5f2dd27ea7e5530c7af484c","33554432":"034bcf793b70ba511e9c84cd07fc0c73c061e912bc02df4cac7871d048bad653b6","67108864":"021c6826c23a181d14962f43121943569a54f9d5af556eb839aee42d3f62debee6","134217728":"030e1bc651b6496922978d6cd3ed923cbf12b4332c496f841f506f5abf9d186d35","268435456":"03e3219e50cf389a75794f82ab4f880f5ffe9ca227b992c3e93cb4bb659d8e3353","536870912":"03879ad42536c410511ac6956b9da2d0da59ce7fbb6068bd9b25dd7cccddcc8096","1073741824":"03c4d3755a17904c0cfa7d7a21cc5b4e85fca8ac85369fcb12a6e2177525117dee","2147483648":"02e7a5d5cd3ea24f05f741dddad3dc8c5e24db60eb9bf9ad888b1c5dfbd792665e","4294967296":"03c783d24d8c9e51207eb3d6199bf48d6eb81a4b34103b422724be15501ff921bd","8589934592":"03200234495725455f4c4e6b6cb7b7936eb7cd1d1c9bb73d2ce032bae7d728b3ca","17179869184":"02eafa50ac67de2c206d1a67245b72ec20fac081c2a550294cc0a711246ed65a41","34359738368":"024c153c2a56de05860006aff9dc35ec9cafd7ac68708442a3a326c858b0c1a146","68719476736":"035a890c2d5c8bf259b98ac67d0d813b87778bcb0c0ea1ee9717ac804b0be3f563","137438953472":"025184ca832f08b105fdb471e2caf14025a1daa6f44ce90b4c7703878ccb6b26e8","274877906944":"039d19a41abdd49949c60672430018c63f27c5a28991f9fbb760499daccc63146c","549755813888":"03a138ac626dd3e6753459903aa128a13c052ed0058f2ead707c203bd4a7565237","1099511627776":"0298c8ef2eab728613103481167102efaf2d4b7a303cb94b9393da37a034a95c53","2199023255552":"02d88f8fc93cd2edf303fdebfecb70e59b5373cb8f746a1d075a9c86bc9382ac07","4398046511104":"02afd89ee23eee7d5fe6687fee898f64e9b01913ec71b5c596762b215e040c701f","8796093022208":"02196b461f3c804259e597c50e514920427aab4beaef0c666185fb2ff4399813db","17592186044416":"037b33746a6fd7a71d4cf17c85d13a64b98620614c0028d4995163f1b8484ee337","35184372088832":"036cce0a1878bbc63b3108c379ef4e6529fbf20ed675d80d91ca3ccc55fde4bdbd","70368744177664":"039c81dccb319ba70597cdf9db33b459164a1515c27366c8f667b01d988874e554","140737488355328":"036b2dd85a3c44c4458f0b246ce19a1524a191f1716834cfb452c6e1f946172c19","281474976710656":"022c84722c31a2b3d8cfd9b6a9e6199515fd97d6a9c390fc3d82f123bfc501ad04","562949953421312":"0355e2be85ee599b8fa7e6e68a9954573d032e89aa9e65c2e1231991664c200bf3","1125899906842624":"024b10818cd27f3eec6c9daf82b9dfa53928ab0711b711070bd39892ac10dee765","2251799813685248":"02a6d726432bb18c3145eba4fc0b587bf64f3be8617c0070dda33944474b3f8740","4503599627370496":"0248304be3cbaf31ec320bc636bb936c5984caf773df950fc44c6237ec09c557a1","9007199254740992":"03a3c0e9da7ece7d7b132c53662c0389bd87db801dff5ac9edd9f46699cb1dc065","18014398509481984":"03b6c4c874e2392072e17fbfd181afbd40d6766a8ca4cf932264ba98d98de1328c","36028797018963968":"0370dca4416ec6e30ff02f8e9db7804348b42e3f5c22099dfc896fa1b2ccbe7a69","72057594037927936":"0226250140aedb79de91cb4cc7350884bde229063f34ee0849081bb391a37c273e","144115188075855872":"02baef3a94d241aee9d6057c7a7ee7424f8a0bcb910daf6c49ddcabf70ffbc77d8","288230376151711744":"030f95a12369f1867ce0dbf2a6322c27d70c61b743064d76cfc81dd43f1a052ae6","576460752303423488":"021bc89118ab6eb1fbebe0fa6cc76da8236a7991163475a73a22d8efd016a45800","1152921504606846976":"03b0c1e658d7ca12830a0b590ea5a4d6db51084ae80b6d8abf27ad2d762209acd1","2305843009213693952":"0266926ce658a0bdae934071f22e09dbb6ecaff2a4dc4b1f8e23626570d993b48e","4611686018427387904":"03ac17f10f9bb745ebd8ee9cdca1b6981f5a356147d431196c21c6d4869402bde0","9223372036854775808":"037ab5b88c8ce34c4a3970be5c6f75b8a7a5493a12ef56a1c9ba9ff5f90de46fcc"}},{"id":"000f01df73ea149a","unit":"sat","keys":{"1":"03ba786a2c0745f8c30e490288acd7a72dd53d65afd292ddefa326a4a3fa14c566","2":"03361cd8bd1329fea797a6add1cf1990ffcf2270ceb9fc81eeee0e8e9c1bd0cdf5","4":"036e378bcf78738ddf68859293c69778035740e41138ab183c94f8fee7572214c7","8":"03909d73beaf28edfb283dbeb8da321afd40651e8902fcf5454ecc7d69788626c0","16":"028a36f0e6638ea7466665fe174d958212723019ec08f9ce6898d897f88e68aa5d","32":"03a97a40e146adee2687ac60c2ba2586a90f970de92a9d0e6cae5a4b9965f54612","64":"03ce86f0c197aab181ddba0cfc5c5576e11dfd5164d9f3d4a3fc3ffbbf2e069664","128":"0284f2c06d938a6f78794814c687560a0aabab19fe5e6f30ede38e113b132a3cb9","256":"03b99f475b68e5b4c0ba809cdecaae64eade2d9787aa123206f91cd61f76c01459","512":"03d4db82ea19a44d35274de51f78af0a710925fe7d9e03620b84e3e9976e3ac2eb","1024":"031fbd4ba801870871d46cf62228a1b748905ebc07d3b210daf48de229e683f2dc","2048":"0276cedb9a3b160db6a158ad4e468d2437f021293204b3cd4bf6247970d8aff54b","4096":"02fc6b89b403ee9eb8a7ed457cd3973638080d6e04ca8af7307c965c166b555ea2","8192":"0320265583e916d3a305f0d2687fcf2cd4e3cd03a16ea8261fda309c3ec5721e21","16384":"036e41de58fdff3cb1d8d713f48c63bc61fa3b3e1631495a444d178363c0d2ed50","32768":"0365438f613f19696264300b069d1dad93f0c60a37536b72a8ab7c7366a5ee6c04","65536":"02408426cfb6fc86341bac79624ba8708a4376b2d92debdf4134813f866eb57a8d","131072":"031063e9f11c94dc778c473e968966eac0e70b7145213fbaff5f7a007e71c65f41","262144":"02f2a3e808f9cd168ec71b7f328258d0c1dda250659c1aced14c7f5cf05aab4328","524288":"038ac10de9f1ff9395903bb73077e94dbf91e9ef98fd77d9a2debc5f74c575bc86","1048576":"0203eaee4db749b0fc7c49870d082024b2c31d889f9bc3b32473d4f1dfa3625788","2097152":"033cdb9d36e1e82ae652b7b6a08e0204569ec7ff9ebf85d80a02786dc7fe00b04c","4194304":"02c8b73f4e3a470ae05e5f2fe39984d41e9f6ae7be9f3b09c9ac31292e403ac512","8388608":"025bbe0cfce8a1f4fbd7f3a0d4a09cb6badd73ef61829dc827aa8a98c270bc25b0","16777216":"037eec3d1651a30a90182d9287a5c51386fe35d4a96839cf7969c6e2a03db1fc21","33554432":"03280576b81a04e6abd7197f305506476f5751356b7643988495ca5c3e14e5c262","67108864":"03268bfb05be1dbb33ab6e7e00e438373ca2c9b9abc018fdb452d0e1a0935e10d3","134217728":"02573b68784ceba9617bbcc7c9487836d296aa7c628c3199173a841e7a19798020","268435456":"0234076b6e70f7fbf755d2227ecc8d8169d662518ee3a1401f729e2a12ccb2b276","536870912":"03015bd88961e2a466a2163bd4248d1d2b42c7c58a157e594785e7eb34d880efc9","1073741824":"02c9b076d08f9020ebee49ac8ba2610b404d4e553a4f800150ceb539e9421aaeee","2147483648":"034d592f4c366afddc919a509600af81b489a03caf4f7517c2b3f4f2b558f9a41a","4294967296":"037c09ecb66da082981e4cbdb1ac65c0eb631fc75d85bed13efb2c6364148879b5","8589934592":"02b4ebb0dda3b9ad83b39e2e31024b777cc0ac205a96b9a6cfab3edea2912ed1b3","17179869184":"026cc4dacdced45e63f6e4f62edbc5779ccd802e7fabb82d5123db879b636176e9","34359738368":"02b2cee01b7d8e90180254459b8f09bbea9aad34c3a2fd98c85517ecfc9805af75","68719476736":"037a0c0d564540fc574b8bfa0253cca987b75466e44b295ed59f6f8bd41aace754","137438953472":"021df6585cae9b9ca431318a713fd73dbb76b3ef5667957e8633bca8aaa7214fb6","274877906944":"02b8f53dde126f8c85fa5bb6061c0be5aca90984ce9b902966941caf963648d53a","549755813888":"029cc8af2840d59f1d8761779b2496623c82c64be8e15f9ab577c657c6dd453785","1099511627776":"03e446fdb84fad492ff3a25fc1046fb9a93a5b262ebcd0151caa442ea28959a38a","2199023255552":"02d6b25bd4ab599dd0818c55f75702fde603c93f259222001246569018842d3258","4398046511104":"03397b522bb4e156ec3952d3f048e5a986c20a00718e5e52cd5718466bf494156a","8796093022208":"02d1fb9e78262b5d7d74028073075b80bb5ab281edcfc3191061962c1346340f1e","17592186044416":"030d3f2ad7a4ca115712ff7f140434f802b19a4c9b2dd1c76f3e8e80c05c6a9310","35184372088832":"03e325b691f292e1dfb151c3fb7cad440b225795583c32e24e10635a80e4221c06","70368744177664":"03bee8f64d88de3dee21d61f89efa32933da51152ddbd67466bef815e9f93f8fd1","140737488355328":"0327244c9019a4892e1f04ba3bf95fe43b327479e2d57c25979446cc508cd379ed","281474976710656":"02fb58522cd662f2f8b042f8161caae6e45de98283f74d4e99f19b0ea85e08a56d","562949953421312":"02adde4b466a9d7e59386b6a701a39717c53f30c4810613c1b55e6b6da43b7bc9a","1125899906842624":"038eeda11f78ce05c774f30e393cda075192b890d68590813ff46362548528dca9","2251799813685248":"02ec13e0058b196db80f7079d329333b330dc30c000dbdd7397cbbc5a37a664c4f","4503599627370496":"02d2d162db63675bd04f7d56df04508840f41e2ad87312a3c93041b494efe80a73","9007199254740992":"0356969d6aef2bb40121dbd07c68b6102339f4ea8e674a9008bb69506795998f49","18014398509481984":"02f4e667567ebb9f4e6e180a4113bb071c48855f657766bb5e9c776a880335d1d6","36028797018963968":"0385b4fe35e41703d7a657d957c67bb536629de57b7e6ee6fe2130728ef0fc90b0","72057594037927936":"02b2bc1968a6fddbcc78fb9903940524824b5f5bed329c6ad48a19b56068c144fd","144115188075855872":"02e0dbb24f1d288a693e8a49bc14264d1276be16972131520cf9e055ae92fba19a","288230376151711744":"03efe75c106f931a525dc2d653ebedddc413a2c7d8cb9da410893ae7d2fa7d19cc","576460752303423488":"02c7ec2bd9508a7fc03f73c7565dc600b30fd86f3d305f8f139c45c404a52d958a","1152921504606846976":"035a6679c6b25e68ff4e29d1c7ef87f21e0a8fc574f6a08c1aa45ff352c1d59f06","2305843009213693952":"033cdc225962c052d485f7cfbf55a5b2367d200fe1fe4373a347deb4cc99e9a099","4611686018427387904":"024a4b806cf413d14b294719090a9da36ba75209c7657135ad09bc65328fba9e6f","9223372036854775808":"0377a6fe114e291a8d8e991627c38001c8305b23b9e98b1c7b1893f5cd0dda6cad"}},{"id":"yjzQhxghPdrr","unit":"sat","keys":{"1":"038a935c51c76c780ff9731cfbe9ab477f38346775809fa4c514340feabbec4b3a","2":"038288b12ebf2db3645e5d58835bd100398b6b19dfef338c698b55c05d0d41fb0a","4":"02fc8201cf4ea29abac0495d1304064f0e698762b8c0db145c1737b38a9d61c7e2","8":"02274243e03ca19f969acc7072812405b38adc672d1d753e65c63746b3f31cc6eb","16":"025f07cb2493351e7d5202f05eaf3934d5c9d17e73385e9de5bfab802f7d8caf92","32":"03afce0a897c858d7c88c1454d492eac43011e3396dda5b778ba1fcab381c748b1","64":"037b2178f42507f0c95e09d9b435a127df4b3e23ccd20af8075817d3abe90947ad","128":"02ebce8457b48407d4d248dba5a31b3eabf08a6285d09d08e40681c4adaf77bd40","256":"03c89713d27d6f8e328597b43dd87623efdcb251a484932f9e095ebfb6dbf4bdf2","512":"02df10f3ebba69916d03ab1754488770498f2e5466224d6df6d12811a13e46776c","1024":"02f5d9cba0502c21c6b39938a09dcb0390f124a2fd65e45dfeccd153cc1864273d","2048":"039de1dad91761b194e7674fb6ba212241aaf7f49dcb578a8fe093196ad1b20d1c","4096":"03cc694ba22e455f1c22b2cee4a40ecdd4f3bb4da0745411adb456158372d3efbb","8192":"029d66c24450fc315e046010df6870d61daa90c5c486c5ec4d7d3b99c5c2bce923","16384":"0387d063821010c7bd5cf79441870182f70cd432d13d3fc255e7b6ffd82c9d3c5a","32768":"021a94c6c03f7de8feb25b8a8b8d1f1c6f56af4bc533eb97c9e8b89c76b616ff11","65536":"038989c6ed91a7c577953115b465ee400a270a64e95eda8f7ee9d6bf30b8fe4908","131072":"03c3d3cd2523f004ee479a170b0ec5c74c060edb8356fc1b0a9ed8087cf6345172","262144":"02e54a7546f1a9194f30baa593a13d4e2949eb866593445d89675d7d394ef6320b","524288":"034e91037b3f1d3258d1e871dede80e98ef83e307c2e5ff589f38bd046f97546f8","1048576":"03306d42752a1adcfa394af2a690961ac9b80b1ac0f5fdc0890f66f8dc7d25ac6e","2097152":"03ec114332fe798c3e36675566c4748fda7d881000a01864ec48486512d7901e76","4194304":"02095e3e443d98ca3dfabcebc2f9154f3656b889783f7edb8290cfb01f497e63cf","8388608":"03c90f31525a4f9ab6562ec3edbf2bafc6662256ea6ce82ab19a45d2aee80b2f15","16777216":"03c0ae897a45724465c713c1379671ac5ff0a81c32e5f2dd27ea7e5530c7af484c","33554432":"034bcf793b70ba511e9c84cd07fc0c73c061e912bc02df4cac7871d048bad653b6","67108864":"021c6826c23a181d14962f43121943569a54f9d5af556eb839aee42d3f62debee6","134217728":"030e1bc651b6496922978d6cd3ed923cbf12b4332c496f841f506f5abf9d186d35","268435456":"03e3219e50cf389a75794f82ab4f880f5ffe9ca227b992c3e93cb4bb659d8e3353","536870912":"03879ad42536c410511ac6956b9da2d0da59ce7fbb6068bd9b25dd7cccddcc8096","1073741824":"03c4d3755a17904c0cfa7d7a21cc5b4e85fca8ac85369fcb12a6e2177525117dee","2147483648":"02e7a5d5cd3ea24f05f741dddad3dc8c5e24db60eb9bf9ad888b1c5dfbd792665e","4294967296":"03c783d24d8c9e51207eb3d6199bf48d6eb81a4b34103b422724be15501ff921bd","8589934592":"03200234495725455f4c4e6b6cb7b7936eb7cd1d1c9bb73d2ce032bae7d728b3ca","17179869184":"02eafa50ac67de2c206d1a67245b72ec20fac081c2a550294cc0a711246ed65a41","34359738368":"024c153c2a56de05860006aff9dc35ec9cafd7ac68708442a3a326c858b0c1a146","68719476736":"035a890c2d5c8bf259b98ac67d0d813b87778bcb0c0ea1ee9717ac804b0be3f563","137438953472":"025184ca832f08b105fdb471e2caf14025a1daa6f44ce90b4c7703878ccb6b26e8","274877906944":"039d19a41abdd49949c60672430018c63f27c5a28991f9fbb760499daccc63146c","549755813888":"03a138ac626dd3e6753459903aa128a13c052ed0058f2ead707c203bd4a7565237","1099511627776":"0298c8ef2eab728613103481167102efaf2d4b7a303cb94b9393da37a034a95c53","2199023255552":"02d88f8fc93cd2edf303fdebfecb70e59b5373cb8f746a1d075a9c86bc9382ac07","4398046511104":"02afd89ee23eee7d5fe6687fee898f64e9b01913ec71b5c596762b215e040c701f","8796093022208":"02196b461f3c804259e597c50e514920427aab4beaef0c666185fb2ff4399813db","17592186044416":"037b33746a6fd7a71d4cf17c85d13a64b98620614c0028d4995163f1b8484ee337","35184372088832":"036cce0a1878bbc63b3108c379ef4e6529fbf20ed675d80d91ca3ccc55fde4bdbd","70368744177664":"039c81dccb319ba70597cdf9db33b459164a1515c27366c8f667b01d988874e554","140737488355328":"036b2dd85a3c44c4458f0b246ce19a1524a191f1716834cfb452c6e1f946172c19","281474976710656":"022c84722c31a2b3d8cfd9b6a9e6199515fd97d6a9c390fc3d82f123bfc501ad04","562949953421312":"0355e2be85ee599b8fa7e6e68a9954573d032e89aa9e65c2e1231991664c200bf3","1125899906842624":"024b10818cd27f3eec6c9daf82b9dfa53928ab0711b711070bd39892ac10dee765","2251799813685248":"02a6d726432bb18c3145eba4fc0b587bf64f3be8617c0070dda33944474b3f8740","4503599627370496":"0248304be3cbaf31ec320bc636bb936c5984caf773df950fc44c6237ec09c557a1","9007199254740992":"03a3c0e9da7ece7d7b132c53662c0389bd87db801dff5ac9edd9f46699cb1dc065","18014398509481984":"03b6c4c874e2392072e17fbfd181afbd40d6766a8ca4cf932264ba98d98de1328c","36028797018963968":"0370dca4416ec6e30ff02f8e9db7804348b42e3f5c22099dfc896fa1b2ccbe7a69","72057594037927936":"0226250140aedb79de91cb4cc7350884bde229063f34ee0849081bb391a37c273e","144115188075855872":"02baef3a94d241aee9d6057c7a7ee7424f8a0bcb910daf6c49ddcabf70ffbc77d8","288230376151711744":"030f95a12369f1867ce0dbf2a6322c27d70c61b743064d76cfc81dd43f1a052ae6","576460752303423488":"021bc89118ab6eb1fbebe0fa6cc76da8236a7991163475a73a22d8efd016a45800","1152921504606846976":"03b0c1e658d7ca12830a0b590ea5a4d6db51084ae80b6d8abf27ad2d762209acd1","2305843009213693952":"0266926ce658a0bdae934071f22e09dbb6ecaff2a4dc4b1f8e23626570d993b48e","4611686018427387904":"03ac17f10f9bb745ebd8ee9cdca1b6981f5a356147d431196c21c6d4869402bde0","9223372036854775808":"037ab5b88c8ce34c4a3970be5c6f75b8a7a5493a12ef56a1c9ba9ff5f90de46fcc"}}]}"#;
+        let keys = KEYS_RESPONSE;
 
         let keys_response: KeysResponse = serde_json::from_str(keys).unwrap();
 
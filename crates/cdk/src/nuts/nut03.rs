@@ -4,7 +4,11 @@
 
 use serde::{Deserialize, Serialize};
 
-use super::nut00::{BlindSignature, BlindedMessage, PreMintSecrets, Proofs};
+use super::nut00::{
+    BlindSignature, BlindedMessage, Error, LegacyFields, PreMintSecrets, Proofs, ProofsMethods,
+    Request, Vintage,
+};
+use super::nut02::Id;
 use crate::Amount;
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
@@ -15,36 +19,219 @@ pub struct PreSwap {
 
 /// Split Request [NUT-06]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct SwapRequest {
     /// Proofs that are to be spent in `Split`
     pub inputs: Proofs,
     /// Blinded Messages for Mint to sign
     pub outputs: Vec<BlindedMessage>,
+    /// Amount to split off (deprecated)
+    ///
+    /// Sent by older wallets expecting a `fst`/`snd` split. The legacy split
+    /// endpoint is not implemented, so requests carrying it are processed
+    /// with swap semantics and answered in [SwapResponseForm::Split].
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount: Option<Amount>,
 }
 
+impl Request for SwapRequest {}
+
 impl SwapRequest {
     pub fn new(inputs: Proofs, outputs: Vec<BlindedMessage>) -> Self {
-        Self { inputs, outputs }
+        Self {
+            inputs,
+            outputs,
+            amount: None,
+        }
     }
 
     /// Total value of proofs in `SplitRequest`
-    pub fn input_amount(&self) -> Amount {
-        self.inputs.iter().map(|proof| proof.amount).sum()
+    pub fn input_amount(&self) -> Result<Amount, Error> {
+        self.inputs.total_amount()
+    }
+
+    /// Check that the last outputs add up to the split `amount`, so the
+    /// response can be given in [SwapResponseForm::Split]
+    pub fn check_split(&self) -> Result<(), Error> {
+        match self.amount {
+            Some(amount) => split_point(self.outputs.iter().map(|o| o.amount), amount).map(|_| ()),
+            None => Ok(()),
+        }
     }
 
     /// Total value of outputs in `SplitRequest`
-    pub fn output_amount(&self) -> Amount {
-        self.outputs.iter().map(|proof| proof.amount).sum()
+    pub fn output_amount(&self) -> Result<Amount, Error> {
+        self.outputs.iter().try_fold(Amount::ZERO, |total, output| {
+            total
+                .checked_add(output.amount)
+                .ok_or(Error::AmountOverflow)
+        })
+    }
+}
+
+/// Index from which `amounts` add up to `amount`
+fn split_point(
+    amounts: impl DoubleEndedIterator<Item = Amount> + ExactSizeIterator,
+    amount: Amount,
+) -> Result<usize, Error> {
+    let mut at = amounts.len();
+    let mut snd = Amount::ZERO;
+    for output in amounts.rev() {
+        if snd >= amount {
+            break;
+        }
+        at -= 1;
+        snd = snd.checked_add(output).ok_or(Error::AmountOverflow)?;
+    }
+
+    match snd == amount {
+        true => Ok(at),
+        false => Err(Error::SplitAmount(amount)),
+    }
+}
+
+/// Builds a [SwapRequest] along with the blinding data of its outputs
+///
+/// [`build`](Self::build) rejects requests whose outputs and
+/// [`input_fee`](Self::input_fee) do not add up to the value of the inputs,
+/// so the mint never sees an unbalanced swap.
+#[derive(Debug, Default, Clone)]
+pub struct SwapRequestBuilder {
+    inputs: Proofs,
+    outputs: PreMintSecrets,
+    input_fee: Amount,
+}
+
+impl SwapRequestBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add proofs to be spent
+    pub fn inputs(mut self, proofs: Proofs) -> Self {
+        self.inputs.extend(proofs);
+        self
+    }
+
+    /// Add prepared outputs
+    pub fn outputs(mut self, outputs: PreMintSecrets) -> Self {
+        self.outputs.combine(outputs);
+        self
+    }
+
+    /// Add outputs with random secrets totalling `amount`
+    pub fn outputs_for(self, amount: Amount, keyset_id: Id) -> Result<Self, Error> {
+        let outputs = PreMintSecrets::random(keyset_id, amount)?;
+        Ok(self.outputs(outputs))
+    }
+
+    /// Sort outputs by amount to avoid fingerprinting
+    pub fn sort_outputs(mut self) -> Self {
+        self.outputs.sort_secrets();
+        self
+    }
+
+    /// Fee the mint keeps from the inputs [NUT-02]
+    pub fn input_fee(mut self, fee: Amount) -> Self {
+        self.input_fee = fee;
+        self
+    }
+
+    pub fn build(self) -> Result<PreSwap, Error> {
+        let swap_request = SwapRequest::new(self.inputs, self.outputs.blinded_messages());
+
+        let inputs = swap_request.input_amount()?;
+        let outputs = swap_request
+            .output_amount()?
+            .checked_add(self.input_fee)
+            .ok_or(Error::AmountOverflow)?;
+        if inputs != outputs {
+            return Err(Error::Unbalanced { inputs, outputs });
+        }
+
+        Ok(PreSwap {
+            pre_mint_secrets: self.outputs,
+            swap_request,
+        })
     }
 }
 
 /// Split Response [NUT-06]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "SwapResponseBody")]
+#[non_exhaustive]
 pub struct SwapResponse {
     /// Promises
     pub signatures: Vec<BlindSignature>,
 }
 
+/// Swap response as sent by mints of any version
+///
+/// Split responses predating `promises` returned the signatures of the
+/// kept and sent outputs as `fst` and `snd`.
+#[derive(Deserialize)]
+struct SwapResponseBody {
+    #[serde(alias = "promises")]
+    signatures: Option<Vec<BlindSignature>>,
+    fst: Option<Vec<BlindSignature>>,
+    snd: Option<Vec<BlindSignature>>,
+}
+
+impl TryFrom<SwapResponseBody> for SwapResponse {
+    type Error = &'static str;
+
+    fn try_from(body: SwapResponseBody) -> Result<Self, Self::Error> {
+        match body {
+            SwapResponseBody {
+                signatures: Some(signatures),
+                ..
+            } => Ok(Self { signatures }),
+            SwapResponseBody {
+                signatures: None,
+                fst: None,
+                snd: None,
+            } => Err("missing field `signatures`"),
+            SwapResponseBody { fst, snd, .. } => Ok(Self {
+                signatures: fst.into_iter().chain(snd).flatten().collect(),
+            }),
+        }
+    }
+}
+
+impl LegacyFields for SwapResponse {
+    const RENAMED: &'static [(&'static str, &'static str)] = &[("signatures", "promises")];
+}
+
+/// Wire form of a [SwapResponse]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapResponseForm {
+    /// A single list, named `signatures` or `promises` by the [Vintage]
+    Flat(Vintage),
+    /// `fst` and `snd`, the signatures of the outputs for the split amount
+    /// last
+    Split(Amount),
+}
+
+impl Default for SwapResponseForm {
+    fn default() -> Self {
+        Self::Flat(Vintage::default())
+    }
+}
+
+impl SwapResponseForm {
+    /// Form read by the wallet sending `request`
+    ///
+    /// Requests with a split `amount` are answered with `fst` and `snd`,
+    /// others with the field names of `vintage`.
+    pub fn of_request(request: &SwapRequest, vintage: Vintage) -> Self {
+        match request.amount {
+            Some(amount) => Self::Split(amount),
+            None => Self::Flat(vintage),
+        }
+    }
+}
+
 impl SwapResponse {
     pub fn new(promises: Vec<BlindSignature>) -> SwapResponse {
         SwapResponse {
@@ -52,6 +239,21 @@ impl SwapResponse {
         }
     }
 
+    /// JSON of `self` in `form`
+    ///
+    /// Fails with [Error::SplitAmount] if no signatures at the end add up to
+    /// the split amount.
+    pub fn to_json_form(&self, form: SwapResponseForm) -> Result<serde_json::Value, Error> {
+        let amount = match form {
+            SwapResponseForm::Flat(vintage) => return Ok(self.to_json_vintage(vintage)?),
+            SwapResponseForm::Split(amount) => amount,
+        };
+
+        let at = split_point(self.signatures.iter().map(|s| s.amount), amount)?;
+        let (fst, snd) = self.signatures.split_at(at);
+        Ok(serde_json::json!({ "fst": fst, "snd": snd }))
+    }
+
     pub fn promises_amount(&self) -> Amount {
         self.signatures
             .iter()
@@ -59,3 +261,243 @@ impl SwapResponse {
             .sum()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nuts::nut00::MAX_REQUEST_LEN;
+    use crate::nuts::{Strictness, Vintage};
+
+    #[test]
+    fn test_swap_request_without_amount() {
+        let request = r#"{"inputs":[{"amount":2,"id":"009a1f293253e41e","secret":"407915bc212be61a77e3e6d2aeb4c727980bda51cd06a6afc29e2861768a7837","C":"02bc9097997d81afb2cc7346b5e4345a9346bd2a506eb7958598a72f0cf85163ea"}],"outputs":[{"amount":2,"id":"009a1f293253e41e","B_":"02634a2c2b34bec9e8a4aba4361f6bf202d7fa2365379b0840afe249a7a9d71239"}]}"#;
+        let request: SwapRequest = serde_json::from_str(request).unwrap();
+
+        assert!(request.amount.is_none());
+        assert_eq!(request.input_amount().unwrap(), Amount::from(2));
+        assert_eq!(request.output_amount().unwrap(), Amount::from(2));
+
+        let json = serde_json::to_value(&request).unwrap();
+        assert!(json.get("amount").is_none());
+    }
+
+    #[test]
+    fn test_swap_request_strictness() {
+        let request = r#"{"inputs":[{"amount":2,"id":"009a1f293253e41e","secret":"407915bc212be61a77e3e6d2aeb4c727980bda51cd06a6afc29e2861768a7837","C":"02bc9097997d81afb2cc7346b5e4345a9346bd2a506eb7958598a72f0cf85163ea","witness":null}],"outputs":[{"amount":2,"id":"009a1f293253e41e","B_":"02634a2c2b34bec9e8a4aba4361f6bf202d7fa2365379b0840afe249a7a9d71239"}],"foo":"bar"}"#;
+
+        let permissive = SwapRequest::parse_request(request, Strictness::Permissive).unwrap();
+        assert_eq!(permissive.input_amount().unwrap(), Amount::from(2));
+
+        let strict = SwapRequest::parse_request(request, Strictness::Strict);
+        assert!(matches!(strict, Err(Error::UnknownField(field)) if field == "foo"));
+
+        let nested = request
+            .replace(r#","foo":"bar""#, "")
+            .replace(r#""B_""#, r#""foo":1,"B_""#);
+        let strict = SwapRequest::parse_request(&nested, Strictness::Strict);
+        assert!(matches!(strict, Err(Error::UnknownField(field)) if field == "outputs.0.foo"));
+
+        let known = request.replace(r#","foo":"bar""#, "");
+        assert_eq!(
+            SwapRequest::parse_request(&known, Strictness::Strict).unwrap(),
+            permissive
+        );
+    }
+
+    #[test]
+    fn test_swap_request_pathological_inputs() {
+        let request = r#"{"inputs":[{"amount":2,"id":"009a1f293253e41e","secret":"407915bc212be61a77e3e6d2aeb4c727980bda51cd06a6afc29e2861768a7837","C":"02bc9097997d81afb2cc7346b5e4345a9346bd2a506eb7958598a72f0cf85163ea"}],"outputs":[{"amount":2,"id":"009a1f293253e41e","B_":"02634a2c2b34bec9e8a4aba4361f6bf202d7fa2365379b0840afe249a7a9d71239"}]}"#;
+
+        for strictness in [Strictness::Permissive, Strictness::Strict] {
+            assert!(SwapRequest::parse_request(request, strictness).is_ok());
+            for len in 0..request.len() {
+                assert!(SwapRequest::parse_request(&request[..len], strictness).is_err());
+            }
+
+            let oversized = request.replace(
+                r#""secret":""#,
+                &format!(r#""secret":"{}"#, "a".repeat(MAX_REQUEST_LEN)),
+            );
+            assert!(matches!(
+                SwapRequest::parse_request(&oversized, strictness),
+                Err(Error::InputTooLarge {
+                    max: MAX_REQUEST_LEN,
+                    ..
+                })
+            ));
+
+            let nested = request.replace(
+                r#""outputs":["#,
+                &format!(r#""outputs":{}"#, "[".repeat(100_000)),
+            );
+            assert!(matches!(
+                SwapRequest::parse_request(&nested, strictness),
+                Err(Error::SerdeJsonError(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_swap_response_vintages() {
+        let bodies = [
+            // Nutshell before v0.13
+            r#"{"fst":[{"id":"00ffd48b8f5ecf80","amount":8,"C_":"02e9701b804dc05a5294b5a580b428237a27c7ee1690a0177868016799b1761c81"}],"snd":[{"id":"00ffd48b8f5ecf80","amount":2,"C_":"031246ee046519b15648f1b8d8ffcb8e537409c84724e148c8d6800b2e62deb795"}]}"#,
+            // Nutshell v0.14
+            r#"{"promises":[{"id":"00ffd48b8f5ecf80","amount":8,"C_":"02e9701b804dc05a5294b5a580b428237a27c7ee1690a0177868016799b1761c81"},{"id":"00ffd48b8f5ecf80","amount":2,"C_":"031246ee046519b15648f1b8d8ffcb8e537409c84724e148c8d6800b2e62deb795"}]}"#,
+            // Nutshell v0.15 and cashu-ts
+            r#"{"signatures":[{"id":"00ffd48b8f5ecf80","amount":8,"C_":"02e9701b804dc05a5294b5a580b428237a27c7ee1690a0177868016799b1761c81"},{"id":"00ffd48b8f5ecf80","amount":2,"C_":"031246ee046519b15648f1b8d8ffcb8e537409c84724e148c8d6800b2e62deb795"}]}"#,
+        ];
+
+        for body in bodies {
+            let response: SwapResponse = serde_json::from_str(body).unwrap();
+            assert_eq!(response.promises_amount(), Amount::from(10));
+            assert_eq!(response.signatures[0].amount, Amount::from(8));
+        }
+
+        assert!(serde_json::from_str::<SwapResponse>("{}").is_err());
+
+        let response: SwapResponse = serde_json::from_str(bodies[2]).unwrap();
+        let legacy = response.to_json_vintage(Vintage::Legacy).unwrap();
+        assert!(legacy.get("promises").is_some());
+        assert_eq!(
+            serde_json::from_value::<SwapResponse>(legacy).unwrap(),
+            response
+        );
+    }
+
+    #[test]
+    fn test_swap_response_forms() {
+        let response: SwapResponse = serde_json::from_str(r#"{"signatures":[{"id":"00ffd48b8f5ecf80","amount":8,"C_":"02e9701b804dc05a5294b5a580b428237a27c7ee1690a0177868016799b1761c81"},{"id":"00ffd48b8f5ecf80","amount":2,"C_":"031246ee046519b15648f1b8d8ffcb8e537409c84724e148c8d6800b2e62deb795"}]}"#).unwrap();
+
+        let forms = [
+            (
+                SwapResponseForm::Flat(Vintage::Current),
+                r#"{"signatures":[{"amount":8,"id":"00ffd48b8f5ecf80","C_":"02e9701b804dc05a5294b5a580b428237a27c7ee1690a0177868016799b1761c81"},{"amount":2,"id":"00ffd48b8f5ecf80","C_":"031246ee046519b15648f1b8d8ffcb8e537409c84724e148c8d6800b2e62deb795"}]}"#,
+            ),
+            (
+                SwapResponseForm::Flat(Vintage::Legacy),
+                r#"{"promises":[{"amount":8,"id":"00ffd48b8f5ecf80","C_":"02e9701b804dc05a5294b5a580b428237a27c7ee1690a0177868016799b1761c81"},{"amount":2,"id":"00ffd48b8f5ecf80","C_":"031246ee046519b15648f1b8d8ffcb8e537409c84724e148c8d6800b2e62deb795"}]}"#,
+            ),
+            (
+                SwapResponseForm::Split(Amount::from(2)),
+                r#"{"fst":[{"amount":8,"id":"00ffd48b8f5ecf80","C_":"02e9701b804dc05a5294b5a580b428237a27c7ee1690a0177868016799b1761c81"}],"snd":[{"amount":2,"id":"00ffd48b8f5ecf80","C_":"031246ee046519b15648f1b8d8ffcb8e537409c84724e148c8d6800b2e62deb795"}]}"#,
+            ),
+        ];
+        for (form, expected) in forms {
+            let json = response.to_json_form(form).unwrap();
+            assert_eq!(
+                json,
+                serde_json::from_str::<serde_json::Value>(expected).unwrap(),
+                "{form:?}"
+            );
+            assert_eq!(
+                serde_json::from_value::<SwapResponse>(json).unwrap(),
+                response
+            );
+        }
+
+        let json = response
+            .to_json_form(SwapResponseForm::Split(Amount::from(10)))
+            .unwrap();
+        assert_eq!(json["fst"], serde_json::json!([]));
+        assert!(matches!(
+            response.to_json_form(SwapResponseForm::Split(Amount::from(8))),
+            Err(Error::SplitAmount(amount)) if amount == Amount::from(8)
+        ));
+        assert!(matches!(
+            response.to_json_form(SwapResponseForm::Split(Amount::from(11))),
+            Err(Error::SplitAmount(_))
+        ));
+    }
+
+    #[test]
+    fn test_swap_response_form_of_request() {
+        let request = r#"{"inputs":[{"amount":2,"id":"009a1f293253e41e","secret":"407915bc212be61a77e3e6d2aeb4c727980bda51cd06a6afc29e2861768a7837","C":"02bc9097997d81afb2cc7346b5e4345a9346bd2a506eb7958598a72f0cf85163ea"}],"outputs":[{"amount":1,"id":"009a1f293253e41e","B_":"02634a2c2b34bec9e8a4aba4361f6bf202d7fa2365379b0840afe249a7a9d71239"},{"amount":1,"id":"009a1f293253e41e","B_":"029e8e5050b890a7d6c0968db16bc1d5d5fa040ea1de284f6ec69d61299f671059"}]}"#;
+        let mut request: SwapRequest = serde_json::from_str(request).unwrap();
+
+        assert!(request.check_split().is_ok());
+        assert_eq!(
+            SwapResponseForm::of_request(&request, Vintage::Legacy),
+            SwapResponseForm::Flat(Vintage::Legacy)
+        );
+
+        request.amount = Some(Amount::from(1));
+        assert!(request.check_split().is_ok());
+        assert_eq!(
+            SwapResponseForm::of_request(&request, Vintage::Current),
+            SwapResponseForm::Split(Amount::from(1))
+        );
+
+        request.amount = Some(Amount::from(3));
+        assert!(matches!(request.check_split(), Err(Error::SplitAmount(_))));
+    }
+
+    #[test]
+    fn test_swap_request_builder_balances() {
+        let request = r#"{"inputs":[{"amount":2,"id":"009a1f293253e41e","secret":"407915bc212be61a77e3e6d2aeb4c727980bda51cd06a6afc29e2861768a7837","C":"02bc9097997d81afb2cc7346b5e4345a9346bd2a506eb7958598a72f0cf85163ea"}],"outputs":[]}"#;
+        let request: SwapRequest = serde_json::from_str(request).unwrap();
+        let keyset_id = request.inputs[0].keyset_id;
+
+        let pre_swap = SwapRequestBuilder::new()
+            .inputs(request.inputs.clone())
+            .outputs_for(Amount::from(1), keyset_id)
+            .unwrap()
+            .outputs_for(Amount::from(1), keyset_id)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(
+            pre_swap.swap_request.output_amount().unwrap(),
+            Amount::from(2)
+        );
+        assert_eq!(
+            pre_swap.swap_request.outputs,
+            pre_swap.pre_mint_secrets.blinded_messages()
+        );
+
+        let unbalanced = SwapRequestBuilder::new()
+            .inputs(request.inputs.clone())
+            .outputs_for(Amount::from(3), keyset_id)
+            .unwrap()
+            .build();
+        assert!(matches!(
+            unbalanced,
+            Err(Error::Unbalanced { inputs, outputs })
+                if inputs == Amount::from(2) && outputs == Amount::from(3)
+        ));
+
+        // The input fee is part of what the inputs pay for
+        let with_fee = SwapRequestBuilder::new()
+            .inputs(request.inputs.clone())
+            .outputs_for(Amount::from(1), keyset_id)
+            .unwrap()
+            .input_fee(Amount::from(1))
+            .build();
+        assert!(with_fee.is_ok());
+        let without_fee = SwapRequestBuilder::new()
+            .inputs(request.inputs)
+            .outputs_for(Amount::from(1), keyset_id)
+            .unwrap()
+            .build();
+        assert!(matches!(
+            without_fee,
+            Err(Error::Unbalanced { inputs, outputs })
+                if inputs == Amount::from(2) && outputs == Amount::from(1)
+        ));
+    }
+
+    #[test]
+    fn test_swap_request_with_deprecated_amount() {
+        let request = r#"{"amount":1,"inputs":[{"amount":2,"id":"009a1f293253e41e","secret":"407915bc212be61a77e3e6d2aeb4c727980bda51cd06a6afc29e2861768a7837","C":"02bc9097997d81afb2cc7346b5e4345a9346bd2a506eb7958598a72f0cf85163ea"}],"outputs":[{"amount":1,"id":"009a1f293253e41e","B_":"02634a2c2b34bec9e8a4aba4361f6bf202d7fa2365379b0840afe249a7a9d71239"},{"amount":1,"id":"009a1f293253e41e","B_":"029e8e5050b890a7d6c0968db16bc1d5d5fa040ea1de284f6ec69d61299f671059"}]}"#;
+        let request: SwapRequest = serde_json::from_str(request).unwrap();
+
+        assert_eq!(request.amount, Some(Amount::from(1)));
+        assert_eq!(
+            request.input_amount().unwrap(),
+            request.output_amount().unwrap()
+        );
+
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["amount"], 1);
+    }
+}
@@ -4,21 +4,62 @@
 
 use serde::{Deserialize, Serialize};
 
-use super::nut00::{BlindSignature, BlindedMessage, CurrencyUnit, PaymentMethod};
-use crate::types::MintQuote;
+use super::nut00::{
+    BlindSignature, BlindedMessage, CurrencyUnit, Error, LegacyFields, PaymentMethod,
+    PreMintSecrets, Request,
+};
+use super::nut02::Id;
+use crate::types::{InvoiceDescription, MintQuote};
 use crate::Amount;
 
 /// Mint quote request [NUT-04]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct MintQuoteBolt11Request {
     /// Amount
     pub amount: Amount,
     /// Unit wallet would like to pay with
     pub unit: CurrencyUnit,
+    /// Description for the invoice, shown to the payer
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// SHA-256 of a description for the invoice to commit to instead
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::serde_utils::option_hash_hex"
+    )]
+    pub description_hash: Option<[u8; 32]>,
+}
+
+impl Request for MintQuoteBolt11Request {}
+
+impl MintQuoteBolt11Request {
+    pub fn new(amount: Amount, unit: CurrencyUnit) -> Self {
+        Self {
+            amount,
+            unit,
+            description: None,
+            description_hash: None,
+        }
+    }
+
+    /// What the invoice should commit to, a request cannot carry both a
+    /// description and a description hash
+    pub fn invoice_description(&self) -> Result<Option<InvoiceDescription>, Error> {
+        match (&self.description, self.description_hash) {
+            (Some(_), Some(_)) => Err(Error::DescriptionAndHash),
+            (Some(description), None) => Ok(Some(InvoiceDescription::Text(description.clone()))),
+            (None, Some(hash)) => Ok(Some(InvoiceDescription::Hash(hash))),
+            (None, None) => Ok(None),
+        }
+    }
 }
 
 /// Mint quote response [NUT-04]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct MintQuoteBolt11Response {
     /// Quote Id
     pub quote: String,
@@ -30,6 +71,17 @@ pub struct MintQuoteBolt11Response {
     pub expiry: u64,
 }
 
+impl MintQuoteBolt11Response {
+    pub fn new(quote: String, request: String, paid: bool, expiry: u64) -> Self {
+        Self {
+            quote,
+            request,
+            paid,
+            expiry,
+        }
+    }
+}
+
 impl From<MintQuote> for MintQuoteBolt11Response {
     fn from(mint_quote: MintQuote) -> MintQuoteBolt11Response {
         MintQuoteBolt11Response {
@@ -43,6 +95,7 @@ impl From<MintQuote> for MintQuoteBolt11Response {
 
 /// Mint request [NUT-04]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct MintBolt11Request {
     /// Quote id
     pub quote: String,
@@ -50,38 +103,171 @@ pub struct MintBolt11Request {
     pub outputs: Vec<BlindedMessage>,
 }
 
+impl Request for MintBolt11Request {}
+
 impl MintBolt11Request {
-    pub fn total_amount(&self) -> Amount {
-        self.outputs
-            .iter()
-            .map(|BlindedMessage { amount, .. }| *amount)
-            .sum()
+    pub fn new(quote: String, outputs: Vec<BlindedMessage>) -> Self {
+        Self { quote, outputs }
+    }
+
+    pub fn total_amount(&self) -> Result<Amount, Error> {
+        self.outputs.iter().try_fold(Amount::ZERO, |total, output| {
+            total
+                .checked_add(output.amount)
+                .ok_or(Error::AmountOverflow)
+        })
+    }
+}
+
+/// [MintBolt11Request] with the blinding data of its outputs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PreMintRequest {
+    pub pre_mint_secrets: PreMintSecrets,
+    pub mint_request: MintBolt11Request,
+}
+
+/// Builds a [MintBolt11Request] for a paid quote
+///
+/// [`build`](Self::build) rejects outputs that do not add up to the quote
+/// amount.
+#[derive(Debug, Clone)]
+pub struct MintRequestBuilder {
+    quote: String,
+    amount: Amount,
+    outputs: PreMintSecrets,
+}
+
+impl MintRequestBuilder {
+    pub fn new(quote: &str, amount: Amount) -> Self {
+        Self {
+            quote: quote.to_string(),
+            amount,
+            outputs: PreMintSecrets::default(),
+        }
+    }
+
+    /// Add prepared outputs
+    pub fn outputs(mut self, outputs: PreMintSecrets) -> Self {
+        self.outputs.combine(outputs);
+        self
+    }
+
+    /// Add outputs with random secrets for the quote amount
+    pub fn outputs_for(self, keyset_id: Id) -> Result<Self, Error> {
+        let outputs = PreMintSecrets::random(keyset_id, self.amount)?;
+        Ok(self.outputs(outputs))
+    }
+
+    pub fn build(self) -> Result<PreMintRequest, Error> {
+        let mint_request = MintBolt11Request {
+            quote: self.quote,
+            outputs: self.outputs.blinded_messages(),
+        };
+
+        let outputs = mint_request.total_amount()?;
+        if outputs != self.amount {
+            return Err(Error::Unbalanced {
+                inputs: self.amount,
+                outputs,
+            });
+        }
+
+        Ok(PreMintRequest {
+            pre_mint_secrets: self.outputs,
+            mint_request,
+        })
     }
 }
 
 /// Mint response [NUT-04]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct MintBolt11Response {
     /// Blinded Signatures
+    #[serde(alias = "promises")]
     pub signatures: Vec<BlindSignature>,
 }
 
+impl MintBolt11Response {
+    pub fn new(signatures: Vec<BlindSignature>) -> Self {
+        Self { signatures }
+    }
+}
+
+impl LegacyFields for MintBolt11Response {
+    const RENAMED: &'static [(&'static str, &'static str)] = &[("signatures", "promises")];
+}
+
 /// Mint Method Settings
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MintMethodSettings {
     /// Payment Method e.g. bolt11
-    method: PaymentMethod,
+    pub method: PaymentMethod,
     /// Currency Unit e.g. sat
-    unit: CurrencyUnit,
+    pub unit: CurrencyUnit,
     /// Min Amount
-    min_amount: Amount,
+    pub min_amount: Amount,
     /// Max Amount
-    max_amount: Amount,
+    pub max_amount: Amount,
+    /// Whether quotes accept an invoice description
+    #[serde(default)]
+    pub description: bool,
+    /// Whether quotes accept a description hash instead
+    #[serde(default)]
+    pub description_hash: bool,
 }
 
 /// Mint Settings
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Settings {
-    methods: Vec<MintMethodSettings>,
-    disabled: bool,
+    pub methods: Vec<MintMethodSettings>,
+    pub disabled: bool,
+}
+
+impl Settings {
+    /// Settings for a `method` and `unit` pair
+    pub fn get_settings(
+        &self,
+        method: &PaymentMethod,
+        unit: &CurrencyUnit,
+    ) -> Option<&MintMethodSettings> {
+        self.methods
+            .iter()
+            .find(|settings| settings.method.eq(method) && settings.unit.eq(unit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nuts::Vintage;
+
+    #[test]
+    fn test_mint_response_vintages() {
+        let bodies = [
+            // Nutshell v0.14
+            r#"{"promises":[{"id":"00ffd48b8f5ecf80","amount":8,"C_":"02e9701b804dc05a5294b5a580b428237a27c7ee1690a0177868016799b1761c81"}]}"#,
+            // Nutshell v0.15
+            r#"{"signatures":[{"id":"00ffd48b8f5ecf80","amount":8,"C_":"02e9701b804dc05a5294b5a580b428237a27c7ee1690a0177868016799b1761c81"}]}"#,
+            // cashu-ts
+            r#"{"signatures":[{"id":"00ffd48b8f5ecf80","amount":8,"C_":"02e9701b804dc05a5294b5a580b428237a27c7ee1690a0177868016799b1761c81","dleq":null}]}"#,
+        ];
+
+        for body in bodies {
+            let response: MintBolt11Response = serde_json::from_str(body).unwrap();
+            assert_eq!(response.signatures.len(), 1);
+            assert_eq!(response.signatures[0].amount, 8.into());
+        }
+
+        let response: MintBolt11Response = serde_json::from_str(bodies[1]).unwrap();
+        let legacy = response.to_json_vintage(Vintage::Legacy).unwrap();
+        assert!(legacy.get("signatures").is_none());
+        assert_eq!(
+            serde_json::from_value::<MintBolt11Response>(legacy).unwrap(),
+            response
+        );
+
+        let current = response.to_json_vintage(Vintage::Current).unwrap();
+        assert_eq!(current, serde_json::to_value(&response).unwrap());
+    }
 }
@@ -4,21 +4,142 @@
 
 use serde::{Deserialize, Serialize};
 
-use super::nut00::{BlindSignature, BlindedMessage, CurrencyUnit, PaymentMethod, Proofs};
+use super::nut00::{
+    BlindSignature, BlindedMessage, CurrencyUnit, LegacyFields, PaymentMethod, PreMintSecrets,
+    Proofs, ProofsMethods, Request,
+};
+use super::nut02::Id;
+use super::nut15::MeltOptions;
+use crate::error::Error;
 use crate::types::MeltQuote;
 use crate::{Amount, Bolt11Invoice};
 
 /// Melt quote request [NUT-05]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct MeltQuoteBolt11Request {
     /// Bolt11 invoice to be paid
+    #[serde(alias = "pr")]
     pub request: Bolt11Invoice,
     /// Unit wallet would like to pay with
     pub unit: CurrencyUnit,
+    /// Amount in `unit` to pay an amountless invoice
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount: Option<Amount>,
+    /// Pay only part of the invoice [NUT-15]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<MeltOptions>,
+}
+
+impl MeltQuoteBolt11Request {
+    pub fn new(request: Bolt11Invoice, unit: CurrencyUnit) -> Self {
+        Self {
+            request,
+            unit,
+            amount: None,
+            options: None,
+        }
+    }
+
+    /// Request paying `amount` in `unit`, needed for amountless invoices
+    pub fn with_amount(mut self, amount: Amount) -> Self {
+        self.amount = Some(amount);
+        self
+    }
+
+    /// Request paying `amount_msat` of the invoice, the other mints of the
+    /// multi-path payment pay the rest [NUT-15]
+    pub fn with_mpp(mut self, amount_msat: u64) -> Self {
+        self.options = Some(MeltOptions::mpp(amount_msat));
+        self
+    }
+
+    /// Msat to send, the invoice amount or `amount` for an amountless invoice
+    ///
+    /// `amount` is in msat for [CurrencyUnit::Msat] and in sats otherwise.
+    /// Errors with [Error::InvoiceAmountUndefined] if neither is set and with
+    /// [Error::InvoiceAmountMismatch] if `amount` is not the invoice amount.
+    ///
+    /// For a partial payment it is the msat of the mpp option, which must be
+    /// more than nothing and at most the amount of the invoice.
+    pub fn amount_msat(&self) -> Result<u64, Error> {
+        if let Some(options) = self.options {
+            let invoice_msat = self
+                .request
+                .amount_milli_satoshis()
+                .ok_or(Error::InvoiceAmountUndefined)?;
+            let partial_msat = u64::from(options.mpp.amount);
+
+            if self.amount.is_some() || partial_msat == 0 || partial_msat > invoice_msat {
+                return Err(Error::InvoiceAmountMismatch);
+            }
+
+            return Ok(partial_msat);
+        }
+
+        match (self.request.amount_milli_satoshis(), self.amount) {
+            (Some(invoice_msat), None) => Ok(invoice_msat),
+            (Some(invoice_msat), Some(amount)) => {
+                if self.unit_amount(invoice_msat) == amount {
+                    Ok(invoice_msat)
+                } else {
+                    Err(Error::InvoiceAmountMismatch)
+                }
+            }
+            (None, Some(amount)) => Ok(match self.unit {
+                CurrencyUnit::Msat => u64::from(amount),
+                _ => amount.checked_to_msat().ok_or(Error::Amount)?,
+            }),
+            (None, None) => Err(Error::InvoiceAmountUndefined),
+        }
+    }
+
+    /// Amount of the melt in `unit`, sats are rounded up to the next sat
+    ///
+    /// Same errors as [MeltQuoteBolt11Request::amount_msat].
+    pub fn invoice_amount(&self) -> Result<Amount, Error> {
+        Ok(self.unit_amount(self.amount_msat()?))
+    }
+
+    fn unit_amount(&self, msat: u64) -> Amount {
+        match self.unit {
+            CurrencyUnit::Msat => Amount::from(msat),
+            // Charged by the mint, a fraction of a sat costs a whole one
+            _ => Amount::from_msat_ceil(msat),
+        }
+    }
+}
+
+impl From<CheckFeesRequest> for MeltQuoteBolt11Request {
+    /// `/checkfees` only supported sats
+    fn from(request: CheckFeesRequest) -> Self {
+        Self::new(request.pr, CurrencyUnit::Sat)
+    }
+}
+
+impl LegacyFields for MeltQuoteBolt11Request {
+    const RENAMED: &'static [(&'static str, &'static str)] = &[("request", "pr")];
+}
+
+impl Request for MeltQuoteBolt11Request {
+    const ALIASES: &'static [(&'static str, &'static str)] = Self::RENAMED;
+}
+
+/// State of a melt quote [NUT-05]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MeltQuoteState {
+    /// Not paid yet, the quote can be melted
+    Unpaid,
+    /// Payment in flight, the inputs are reserved until it settles
+    Pending,
+    /// Invoice paid
+    Paid,
 }
 
 /// Melt quote response [NUT-05]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct MeltQuoteBolt11Response {
     /// Quote Id
     pub quote: String,
@@ -28,42 +149,191 @@ pub struct MeltQuoteBolt11Response {
     pub fee_reserve: u64,
     /// Whether the the request haas be paid
     pub paid: bool,
+    /// State of the quote, not sent by mints that only report
+    /// [MeltQuoteBolt11Response::paid]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<MeltQuoteState>,
     /// Unix timestamp until the quote is valid
     pub expiry: u64,
+    /// Preimage of the invoice, once paid over Lightning
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payment_preimage: Option<String>,
+}
+
+impl MeltQuoteBolt11Response {
+    pub fn new(quote: String, amount: u64, fee_reserve: u64, paid: bool, expiry: u64) -> Self {
+        Self {
+            quote,
+            amount,
+            fee_reserve,
+            paid,
+            state: None,
+            expiry,
+            payment_preimage: None,
+        }
+    }
+
+    /// [MeltQuoteBolt11Response::state], or the state `paid` implies for
+    /// mints that do not send it
+    pub fn state(&self) -> MeltQuoteState {
+        match (self.state, self.paid) {
+            (Some(state), _) => state,
+            (None, true) => MeltQuoteState::Paid,
+            (None, false) => MeltQuoteState::Unpaid,
+        }
+    }
 }
 
 impl From<MeltQuote> for MeltQuoteBolt11Response {
     fn from(melt_quote: MeltQuote) -> MeltQuoteBolt11Response {
         MeltQuoteBolt11Response {
+            state: Some(melt_quote.state()),
             quote: melt_quote.id,
             amount: u64::from(melt_quote.amount),
             fee_reserve: u64::from(melt_quote.fee_reserve),
             paid: melt_quote.paid,
             expiry: melt_quote.expiry,
+            payment_preimage: melt_quote.payment_preimage,
         }
     }
 }
 
 /// Melt Bolt11 Request [NUT-05]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct MeltBolt11Request {
     /// Quote ID
     pub quote: String,
     /// Proofs
+    #[serde(alias = "proofs")]
     pub inputs: Proofs,
     /// Blinded Message that can be used to return change [NUT-08]
     /// Amount field of BlindedMessages `SHOULD` be set to zero
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub outputs: Option<Vec<BlindedMessage>>,
 }
 
 impl MeltBolt11Request {
-    pub fn proofs_amount(&self) -> Amount {
-        self.inputs.iter().map(|proof| proof.amount).sum()
+    pub fn new(quote: String, inputs: Proofs, outputs: Option<Vec<BlindedMessage>>) -> Self {
+        Self {
+            quote,
+            inputs,
+            outputs,
+        }
+    }
+
+    pub fn proofs_amount(&self) -> Result<Amount, super::nut00::Error> {
+        self.inputs.total_amount()
+    }
+}
+
+impl LegacyFields for MeltBolt11Request {
+    const RENAMED: &'static [(&'static str, &'static str)] = &[("inputs", "proofs")];
+}
+
+impl Request for MeltBolt11Request {
+    const ALIASES: &'static [(&'static str, &'static str)] = Self::RENAMED;
+}
+
+/// [MeltBolt11Request] with the blinding data of its change outputs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PreMelt {
+    pub pre_mint_secrets: PreMintSecrets,
+    pub melt_request: MeltBolt11Request,
+}
+
+/// Builds a [MeltBolt11Request] for a melt quote
+///
+/// [`build`](Self::build) rejects inputs that do not cover the quote amount
+/// plus its fee reserve and [`input_fee`](Self::input_fee).
+#[derive(Debug, Clone)]
+pub struct MeltRequestBuilder {
+    quote: String,
+    amount: Amount,
+    fee_reserve: Amount,
+    input_fee: Amount,
+    inputs: Proofs,
+    outputs: PreMintSecrets,
+}
+
+impl MeltRequestBuilder {
+    pub fn new(quote: &str, amount: Amount, fee_reserve: Amount) -> Self {
+        Self {
+            quote: quote.to_string(),
+            amount,
+            fee_reserve,
+            input_fee: Amount::ZERO,
+            inputs: Proofs::new(),
+            outputs: PreMintSecrets::default(),
+        }
+    }
+
+    /// Fee the mint keeps from the inputs [NUT-02]
+    pub fn input_fee(mut self, fee: Amount) -> Self {
+        self.input_fee = fee;
+        self
+    }
+
+    /// Add proofs to be melted
+    pub fn inputs(mut self, proofs: Proofs) -> Self {
+        self.inputs.extend(proofs);
+        self
+    }
+
+    /// Most change the mint can return for the current inputs [NUT-08]
+    ///
+    /// The whole fee reserve is returned when routing is free, along with
+    /// anything the inputs overpay, so outputs are sized for that rather
+    /// than for the fee reserve alone.
+    pub fn max_change(&self) -> Result<Amount, super::nut00::Error> {
+        Ok(self
+            .inputs
+            .total_amount()?
+            .checked_sub(self.amount.saturating_add(self.input_fee))
+            .unwrap_or(Amount::ZERO))
+    }
+
+    /// Add prepared blank outputs for change
+    pub fn change_outputs(mut self, outputs: PreMintSecrets) -> Self {
+        self.outputs.combine(outputs);
+        self
+    }
+
+    /// Add as many blank outputs as [`max_change`](Self::max_change) needs
+    pub fn blank_outputs(self, keyset_id: Id) -> Result<Self, super::nut00::Error> {
+        let outputs = PreMintSecrets::blank(keyset_id, self.max_change()?)?;
+        Ok(self.change_outputs(outputs))
+    }
+
+    pub fn build(self) -> Result<PreMelt, super::nut00::Error> {
+        let inputs = self.inputs.total_amount()?;
+        let outputs = self
+            .amount
+            .checked_add(self.fee_reserve)
+            .and_then(|total| total.checked_add(self.input_fee))
+            .ok_or(super::nut00::Error::AmountOverflow)?;
+        if inputs < outputs {
+            return Err(super::nut00::Error::Unbalanced { inputs, outputs });
+        }
+
+        let change = (!self.outputs.is_empty()).then(|| self.outputs.blinded_messages());
+
+        Ok(PreMelt {
+            melt_request: MeltBolt11Request {
+                quote: self.quote,
+                inputs: self.inputs,
+                outputs: change,
+            },
+            pre_mint_secrets: self.outputs,
+        })
     }
 }
 
 /// Melt Response [NUT-05]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct MeltBolt11Response {
     /// Indicate if payment was successful
     pub paid: bool,
@@ -71,23 +341,332 @@ pub struct MeltBolt11Response {
     pub payment_preimage: Option<String>,
     /// Change
     pub change: Option<Vec<BlindSignature>>,
+    /// Lightning fee charged to the inputs, see [MeltSettlement::fee_paid]
+    ///
+    /// The inputs cover the quote amount, this fee, the input fees and the
+    /// change. Not sent by mints that predate it.
+    ///
+    /// [MeltSettlement::fee_paid]: crate::types::MeltSettlement::fee_paid
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee_paid: Option<Amount>,
+}
+
+impl MeltBolt11Response {
+    pub fn new(
+        paid: bool,
+        payment_preimage: Option<String>,
+        change: Option<Vec<BlindSignature>>,
+    ) -> Self {
+        Self {
+            paid,
+            payment_preimage,
+            change,
+            fee_paid: None,
+        }
+    }
+
+    /// Melt that was not paid: no preimage, no change, and the inputs are
+    /// still spendable
+    pub fn unpaid() -> Self {
+        Self::new(false, None, None)
+    }
+}
+
+/// Check fees request (legacy `/checkfees`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct CheckFeesRequest {
+    /// Bolt11 invoice to be paid
+    pub pr: Bolt11Invoice,
+}
+
+impl Request for CheckFeesRequest {}
+
+impl CheckFeesRequest {
+    pub fn new(pr: Bolt11Invoice) -> Self {
+        Self { pr }
+    }
+}
+
+/// Check fees response (legacy `/checkfees`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct CheckFeesResponse {
+    /// Maximum lightning fee the mint will reserve
+    pub fee: Amount,
+}
+
+impl CheckFeesResponse {
+    pub fn new(fee: Amount) -> Self {
+        Self { fee }
+    }
 }
 
 /// Melt Method Settings
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MeltMethodSettings {
     /// Payment Method e.g. bolt11
-    method: PaymentMethod,
+    pub method: PaymentMethod,
     /// Currency Unit e.g. sat
-    unit: CurrencyUnit,
+    pub unit: CurrencyUnit,
     /// Min Amount
-    min_amount: Amount,
+    pub min_amount: Amount,
     /// Max Amount
-    max_amount: Amount,
+    pub max_amount: Amount,
 }
 
 /// Melt Settings
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Settings {
-    methods: Vec<MeltMethodSettings>,
+    pub methods: Vec<MeltMethodSettings>,
+}
+
+impl Settings {
+    /// Settings for a `method` and `unit` pair
+    pub fn get_settings(
+        &self,
+        method: &PaymentMethod,
+        unit: &CurrencyUnit,
+    ) -> Option<&MeltMethodSettings> {
+        self.methods
+            .iter()
+            .find(|settings| settings.method.eq(method) && settings.unit.eq(unit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nuts::Vintage;
+
+    fn melt_quote_request(invoice: &str) -> String {
+        format!(r#"{{"request":"{invoice}","unit":"sat"}}"#)
+    }
+
+    #[test]
+    fn test_melt_quote_request_invoice_amount() {
+        // Mainnet 2500u
+        let mainnet = "lnbc2500u1pj48ugqdq8w3jhxaqpp5qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqsp59g4z52329g4z52329g4z52329g4z52329g4z52329g4z52329g4q9qrsgqcqzysd6exwz2yjprkglq820pffp8wcu97sxuzcgx7raensa83dnc4kpmhkuqevwf6hmv6a3mc9aegn89jv7a8e9wggdxtfsjwrur7n6sx4rsq7rvjx3";
+        let request: MeltQuoteBolt11Request =
+            serde_json::from_str(&melt_quote_request(mainnet)).unwrap();
+        assert_eq!(request.invoice_amount().unwrap(), Amount::from(250_000));
+
+        // Testnet 100u
+        let testnet = "lntb100u1pj48ugqdq8w3jhxaqpp5qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqsp59g4z52329g4z52329g4z52329g4z52329g4z52329g4z52329g4q9qrsgqcqzysdf4sws69s6xxh260csmvjjr05ldennv7m8ezt4yvjx9ufdu0f0vngwtup7en07xqvs7nkvajnnuf0n2esddz34wk529jdx4tkgxzhqgpk4nrm4";
+        let request: MeltQuoteBolt11Request =
+            serde_json::from_str(&melt_quote_request(testnet)).unwrap();
+        assert_eq!(request.invoice_amount().unwrap(), Amount::from(10_000));
+    }
+
+    #[test]
+    fn test_melt_request_vintages() {
+        let invoice = "lnbc2500u1pj48ugqdq8w3jhxaqpp5qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqsp59g4z52329g4z52329g4z52329g4z52329g4z52329g4z52329g4q9qrsgqcqzysd6exwz2yjprkglq820pffp8wcu97sxuzcgx7raensa83dnc4kpmhkuqevwf6hmv6a3mc9aegn89jv7a8e9wggdxtfsjwrur7n6sx4rsq7rvjx3";
+
+        // Nutshell v0.14 and older cashu-ts send `pr`
+        let legacy: MeltQuoteBolt11Request =
+            serde_json::from_str(&format!(r#"{{"pr":"{invoice}","unit":"sat"}}"#)).unwrap();
+        let current: MeltQuoteBolt11Request =
+            serde_json::from_str(&melt_quote_request(invoice)).unwrap();
+        assert_eq!(legacy, current);
+
+        let json = current.to_json_vintage(Vintage::Legacy).unwrap();
+        assert_eq!(json["pr"], invoice);
+        assert!(json.get("request").is_none());
+
+        let bodies = [
+            r#"{"quote":"abc","proofs":[{"amount":2,"id":"009a1f293253e41e","secret":"407915bc212be61a77e3e6d2aeb4c727980bda51cd06a6afc29e2861768a7837","C":"02bc9097997d81afb2cc7346b5e4345a9346bd2a506eb7958598a72f0cf85163ea"}],"outputs":null}"#,
+            r#"{"quote":"abc","inputs":[{"amount":2,"id":"009a1f293253e41e","secret":"407915bc212be61a77e3e6d2aeb4c727980bda51cd06a6afc29e2861768a7837","C":"02bc9097997d81afb2cc7346b5e4345a9346bd2a506eb7958598a72f0cf85163ea"}],"outputs":null}"#,
+        ];
+        let requests: Vec<MeltBolt11Request> = bodies
+            .iter()
+            .map(|body| serde_json::from_str(body).unwrap())
+            .collect();
+        assert_eq!(requests[0], requests[1]);
+        assert_eq!(requests[0].proofs_amount().unwrap(), Amount::from(2));
+
+        let json = requests[1].to_json_vintage(Vintage::Legacy).unwrap();
+        assert!(json.get("proofs").is_some());
+        assert_eq!(
+            serde_json::from_value::<MeltBolt11Request>(json).unwrap(),
+            requests[1]
+        );
+    }
+
+    #[test]
+    fn test_melt_quote_request_amountless_invoice() {
+        let amountless = "lnbc1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdpl2pkx2ctnv5sxxmmwwd5kgetjypeh2ursdae8g6twvus8g6rfwvs8qun0dfjkxaq9qrsgq357wnc5r2ueh7ck6q93dj32dlqnls087fxdwk8qakdyafkq3yap9us6v52vjjsrvywa6rt52cm9r9zqt8r2t7mlcwspyetp5h2tztugp9lfyql";
+        let request: MeltQuoteBolt11Request =
+            serde_json::from_str(&melt_quote_request(amountless)).unwrap();
+
+        assert!(matches!(
+            request.invoice_amount(),
+            Err(Error::InvoiceAmountUndefined)
+        ));
+
+        let with_amount: MeltQuoteBolt11Request = serde_json::from_str(&format!(
+            r#"{{"request":"{amountless}","unit":"sat","amount":21}}"#
+        ))
+        .unwrap();
+        assert_eq!(with_amount, request.clone().with_amount(Amount::from(21)));
+        assert_eq!(with_amount.invoice_amount().unwrap(), Amount::from(21));
+        assert_eq!(with_amount.amount_msat().unwrap(), 21_000);
+
+        let msat = MeltQuoteBolt11Request::new(request.request.clone(), CurrencyUnit::Msat)
+            .with_amount(Amount::from(21_500));
+        assert_eq!(msat.amount_msat().unwrap(), 21_500);
+        assert_eq!(msat.invoice_amount().unwrap(), Amount::from(21_500));
+    }
+
+    #[test]
+    fn test_melt_quote_request_amount_must_match_invoice() {
+        // Mainnet 2500u
+        let invoice = "lnbc2500u1pj48ugqdq8w3jhxaqpp5qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqsp59g4z52329g4z52329g4z52329g4z52329g4z52329g4z52329g4q9qrsgqcqzysd6exwz2yjprkglq820pffp8wcu97sxuzcgx7raensa83dnc4kpmhkuqevwf6hmv6a3mc9aegn89jv7a8e9wggdxtfsjwrur7n6sx4rsq7rvjx3";
+        let request: MeltQuoteBolt11Request =
+            serde_json::from_str(&melt_quote_request(invoice)).unwrap();
+
+        let conflicting = request.clone().with_amount(Amount::from(1_000));
+        assert!(matches!(
+            conflicting.amount_msat(),
+            Err(Error::InvoiceAmountMismatch)
+        ));
+        assert!(matches!(
+            conflicting.invoice_amount(),
+            Err(Error::InvoiceAmountMismatch)
+        ));
+
+        let matching = request.clone().with_amount(Amount::from(250_000));
+        assert_eq!(matching.amount_msat().unwrap(), 250_000_000);
+
+        // No amount is not serialized
+        let json = serde_json::to_value(&request).unwrap();
+        assert!(json.get("amount").is_none());
+    }
+
+    #[test]
+    fn test_melt_quote_request_partial_amount() {
+        // Mainnet 2500u
+        let invoice = "lnbc2500u1pj48ugqdq8w3jhxaqpp5qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqsp59g4z52329g4z52329g4z52329g4z52329g4z52329g4z52329g4q9qrsgqcqzysd6exwz2yjprkglq820pffp8wcu97sxuzcgx7raensa83dnc4kpmhkuqevwf6hmv6a3mc9aegn89jv7a8e9wggdxtfsjwrur7n6sx4rsq7rvjx3";
+        let request: MeltQuoteBolt11Request = serde_json::from_str(&format!(
+            r#"{{"request":"{invoice}","unit":"sat","options":{{"mpp":{{"amount":100000500}}}}}}"#
+        ))
+        .unwrap();
+
+        assert_eq!(request.amount_msat().unwrap(), 100_000_500);
+        assert_eq!(request.invoice_amount().unwrap(), Amount::from(100_001));
+
+        let request: MeltQuoteBolt11Request =
+            serde_json::from_str(&melt_quote_request(invoice)).unwrap();
+        for partial_msat in [0, 250_000_001] {
+            assert!(matches!(
+                request.clone().with_mpp(partial_msat).amount_msat(),
+                Err(Error::InvoiceAmountMismatch)
+            ));
+        }
+        assert!(matches!(
+            request
+                .clone()
+                .with_mpp(1_000)
+                .with_amount(Amount::from(1))
+                .amount_msat(),
+            Err(Error::InvoiceAmountMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_melt_quote_request_corrupted_invoice() {
+        // Last character of the checksum changed
+        let corrupted = "lnbc2500u1pj48ugqdq8w3jhxaqpp5qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqsp59g4z52329g4z52329g4z52329g4z52329g4z52329g4z52329g4q9qrsgqcqzysd6exwz2yjprkglq820pffp8wcu97sxuzcgx7raensa83dnc4kpmhkuqevwf6hmv6a3mc9aegn89jv7a8e9wggdxtfsjwrur7n6sx4rsq7rvjx2";
+        assert!(
+            serde_json::from_str::<MeltQuoteBolt11Request>(&melt_quote_request(corrupted)).is_err()
+        );
+
+        assert!(
+            serde_json::from_str::<MeltQuoteBolt11Request>(&melt_quote_request("not an invoice"))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_check_fees_serde() {
+        let invoice = "lnbc2500u1pj48ugqdq8w3jhxaqpp5qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqsp59g4z52329g4z52329g4z52329g4z52329g4z52329g4z52329g4q9qrsgqcqzysd6exwz2yjprkglq820pffp8wcu97sxuzcgx7raensa83dnc4kpmhkuqevwf6hmv6a3mc9aegn89jv7a8e9wggdxtfsjwrur7n6sx4rsq7rvjx3";
+        let request: CheckFeesRequest =
+            serde_json::from_str(&format!(r#"{{"pr":"{invoice}"}}"#)).unwrap();
+        assert_eq!(request.pr.to_string(), invoice);
+
+        let response: CheckFeesResponse = serde_json::from_str(r#"{"fee":2}"#).unwrap();
+        assert_eq!(response.fee, Amount::from(2));
+        assert_eq!(serde_json::to_string(&response).unwrap(), r#"{"fee":2}"#);
+    }
+
+    #[test]
+    fn test_melt_request_builder_sizes_change_outputs() {
+        let inputs: Proofs = serde_json::from_str(r#"[{"amount":8,"id":"009a1f293253e41e","secret":"407915bc212be61a77e3e6d2aeb4c727980bda51cd06a6afc29e2861768a7837","C":"02bc9097997d81afb2cc7346b5e4345a9346bd2a506eb7958598a72f0cf85163ea"},{"amount":8,"id":"009a1f293253e41e","secret":"fe15109314e61d7756b0f8ee0f23a624acaa3f4e042f61433c728c7057b931be","C":"029e8e5050b890a7d6c0968db16bc1d5d5fa040ea1de284f6ec69d61299f671059"}]"#).unwrap();
+        let keyset_id = inputs[0].keyset_id;
+
+        // 16 sat of inputs for a 4 sat payment can return up to 12 sat
+        let builder = MeltRequestBuilder::new("quote", Amount::from(4), Amount::from(2))
+            .inputs(inputs.clone());
+        assert_eq!(builder.max_change().unwrap(), Amount::from(12));
+
+        let pre_melt = builder.blank_outputs(keyset_id).unwrap().build().unwrap();
+        let outputs = pre_melt.melt_request.outputs.unwrap();
+        assert_eq!(outputs.len(), 4);
+        assert!(outputs.iter().all(|output| output.amount == Amount::ZERO));
+        assert_eq!(pre_melt.pre_mint_secrets.len(), outputs.len());
+
+        let short = MeltRequestBuilder::new("quote", Amount::from(15), Amount::from(2))
+            .inputs(inputs.clone())
+            .build();
+        assert!(matches!(
+            short,
+            Err(super::super::nut00::Error::Unbalanced { inputs, outputs })
+                if inputs == Amount::from(16) && outputs == Amount::from(17)
+        ));
+
+        // Input fees are not returned as change and have to be covered
+        let builder = MeltRequestBuilder::new("quote", Amount::from(12), Amount::from(2))
+            .input_fee(Amount::from(2))
+            .inputs(inputs.clone());
+        assert_eq!(builder.max_change().unwrap(), Amount::from(2));
+        assert!(builder.build().is_ok());
+        let short = MeltRequestBuilder::new("quote", Amount::from(12), Amount::from(2))
+            .input_fee(Amount::from(3))
+            .inputs(inputs)
+            .build();
+        assert!(matches!(
+            short,
+            Err(super::super::nut00::Error::Unbalanced { inputs, outputs })
+                if inputs == Amount::from(16) && outputs == Amount::from(17)
+        ));
+    }
+
+    #[test]
+    fn test_melt_response_ignores_unknown_fields() {
+        let response =
+            r#"{"paid":true,"payment_preimage":null,"change":[],"future_field":{"a":1}}"#;
+        let response: MeltBolt11Response = serde_json::from_str(response).unwrap();
+
+        assert_eq!(response, MeltBolt11Response::new(true, None, Some(vec![])));
+    }
+
+    #[test]
+    fn test_melt_response_fee_paid() {
+        let mut response = MeltBolt11Response::new(true, None, None);
+        assert_eq!(
+            serde_json::to_string(&response).unwrap(),
+            r#"{"paid":true,"payment_preimage":null,"change":null}"#
+        );
+
+        response.fee_paid = Some(Amount::from(3));
+        let json = serde_json::to_string(&response).unwrap();
+        assert_eq!(
+            json,
+            r#"{"paid":true,"payment_preimage":null,"change":null,"fee_paid":3}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<MeltBolt11Response>(&json).unwrap(),
+            response
+        );
+    }
 }
@@ -2,10 +2,14 @@
 //!
 //! <https://github.com/cashubtc/nuts/blob/main/06.md>
 
+use std::fmt;
+
+use bitcoin::secp256k1::schnorr::Signature;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use super::nut01::PublicKey;
-use super::{nut04, nut05, nut07, nut08};
+use super::nut11::VerifyingKey;
+use super::{nut04, nut05, nut07, nut08, nut15};
 
 /// Mint Version
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -41,6 +45,100 @@ impl<'de> Deserialize<'de> for MintVersion {
     }
 }
 
+/// Way to reach a mint operator
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ContactMethod {
+    Email,
+    Nostr,
+    Twitter,
+    /// Any other method
+    Custom(String),
+}
+
+impl<S> From<S> for ContactMethod
+where
+    S: AsRef<str>,
+{
+    fn from(method: S) -> Self {
+        match method.as_ref() {
+            "email" => Self::Email,
+            "nostr" => Self::Nostr,
+            "twitter" => Self::Twitter,
+            o => Self::Custom(o.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ContactMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContactMethod::Email => write!(f, "email"),
+            ContactMethod::Nostr => write!(f, "nostr"),
+            ContactMethod::Twitter => write!(f, "twitter"),
+            ContactMethod::Custom(method) => write!(f, "{}", method),
+        }
+    }
+}
+
+impl Serialize for ContactMethod {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for ContactMethod {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let method = String::deserialize(deserializer)?;
+        Ok(Self::from(method))
+    }
+}
+
+/// Contact of the mint operator
+///
+/// Serialized as `{"method": "nostr", "info": "npub..."}`. The legacy
+/// `["nostr", "npub..."]` shape is accepted as well.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct ContactInfo {
+    pub method: ContactMethod,
+    pub info: String,
+}
+
+impl ContactInfo {
+    pub fn new<M>(method: M, info: String) -> Self
+    where
+        M: Into<ContactMethod>,
+    {
+        Self {
+            method: method.into(),
+            info,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ContactInfo {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Shape {
+            Object { method: ContactMethod, info: String },
+            Pair(ContactMethod, String),
+        }
+
+        Ok(match Shape::deserialize(deserializer)? {
+            Shape::Object { method, info } | Shape::Pair(method, info) => Self { method, info },
+        })
+    }
+}
+
 /// Mint Info [NIP-09]
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MintInfo {
@@ -61,12 +159,61 @@ pub struct MintInfo {
     pub description_long: Option<String>,
     /// contact methods to reach the mint operator
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub contact: Option<Vec<Vec<String>>>,
+    pub contact: Option<Vec<ContactInfo>>,
     /// shows which NUTs the mint supports
     pub nuts: Nuts,
     /// message of the day that the wallet must display to the user
     #[serde(skip_serializing_if = "Option::is_none")]
     pub motd: Option<String>,
+    /// url of the mint icon
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon_url: Option<String>,
+    /// urls the mint is reachable at
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub urls: Option<Vec<String>>,
+}
+
+impl MintInfo {
+    pub fn with_name(mut self, name: String) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    pub fn with_description(mut self, description: String) -> Self {
+        self.description = Some(description);
+        self
+    }
+
+    /// Add a contact of the mint operator
+    pub fn with_contact(mut self, contact: ContactInfo) -> Self {
+        self.contact.get_or_insert_with(Vec::new).push(contact);
+        self
+    }
+
+    pub fn with_motd(mut self, motd: String) -> Self {
+        self.motd = Some(motd);
+        self
+    }
+
+    pub fn with_icon_url(mut self, icon_url: String) -> Self {
+        self.icon_url = Some(icon_url);
+        self
+    }
+
+    pub fn with_urls(mut self, urls: Vec<String>) -> Self {
+        self.urls = Some(urls);
+        self
+    }
+
+    /// Whether `signature` over `msg` is by the pubkey of the mint, `false`
+    /// if it has none
+    pub fn verify_signature(&self, msg: &[u8], signature: &Signature) -> bool {
+        self.pubkey.is_some_and(|pubkey| {
+            VerifyingKey::from_public_key(pubkey)
+                .verify(msg, signature)
+                .is_ok()
+        })
+    }
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -94,12 +241,17 @@ pub struct Nuts {
     #[serde(default)]
     #[serde(rename = "12")]
     pub nut12: nut07::Settings,
+    #[serde(default)]
+    #[serde(rename = "15")]
+    pub nut15: nut15::Settings,
 }
 
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use crate::nuts::{CurrencyUnit, PaymentMethod};
+    use crate::Amount;
 
     #[test]
     fn test_ser_mint_info() {
@@ -159,6 +311,150 @@ mod tests {
     "12": {"supported": true}
   }
 }"#;
-        let _info: MintInfo = serde_json::from_str(mint_info_str).unwrap();
+        let info: MintInfo = serde_json::from_str(mint_info_str).unwrap();
+        assert_eq!(
+            info.contact.unwrap(),
+            vec![
+                ContactInfo::new(ContactMethod::Email, "contact@me.com".to_string()),
+                ContactInfo::new(ContactMethod::Twitter, "@me".to_string()),
+                ContactInfo::new(ContactMethod::Nostr, "npub...".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mint_info_profile_round_trip() {
+        // Shape of a current Nutshell /v1/info
+        let mint_info_str = r#"{
+  "name": "Cashu mint",
+  "pubkey": "0283bf290884eed3a7ca2663fc0260de2e2064d6b355ea13f98dec004b7a7ead99",
+  "version": "Nutshell/0.16.0",
+  "description": "The short mint description",
+  "contact": [
+    {"method": "email", "info": "contact@me.com"},
+    {"method": "nostr", "info": "npub1..."},
+    {"method": "signal", "info": "@me.01"}
+  ],
+  "motd": "Message to display to users.",
+  "icon_url": "https://mint.example.com/icon.png",
+  "urls": ["https://mint.example.com", "http://mint.onion"],
+  "time": 1725304480,
+  "nuts": {
+    "4": {
+      "methods": [{"method": "bolt11", "unit": "sat", "min_amount": 0, "max_amount": 10000, "description": true}],
+      "disabled": false
+    },
+    "5": {
+      "methods": [{"method": "bolt11", "unit": "sat", "min_amount": 0, "max_amount": 10000}],
+      "disabled": false
+    },
+    "7": {"supported": true},
+    "8": {"supported": true},
+    "9": {"supported": true},
+    "10": {"supported": true},
+    "11": {"supported": true},
+    "12": {"supported": true}
+  }
+}"#;
+        let info: MintInfo = serde_json::from_str(mint_info_str).unwrap();
+
+        assert_eq!(
+            info.icon_url.as_deref(),
+            Some("https://mint.example.com/icon.png")
+        );
+        assert_eq!(info.urls.as_ref().unwrap().len(), 2);
+        assert_eq!(info.motd.as_deref(), Some("Message to display to users."));
+        let contact = info.contact.as_ref().unwrap();
+        assert_eq!(contact[1].method, ContactMethod::Nostr);
+        assert_eq!(
+            contact[2].method,
+            ContactMethod::Custom("signal".to_string())
+        );
+
+        let json = serde_json::to_value(&info).unwrap();
+        assert_eq!(json["contact"][2]["method"], "signal");
+        assert_eq!(json["contact"][2]["info"], "@me.01");
+        let round_trip: MintInfo = serde_json::from_value(json).unwrap();
+        assert_eq!(round_trip, info);
+
+        // Minimal info has none of the profile fields
+        let json = serde_json::to_value(MintInfo::default()).unwrap();
+        for field in ["contact", "motd", "icon_url", "urls"] {
+            assert!(json.get(field).is_none());
+        }
+    }
+
+    #[test]
+    fn test_mint_info_builder() {
+        let info = MintInfo::default()
+            .with_name("Bob's mint".to_string())
+            .with_contact(ContactInfo::new("nostr", "npub1...".to_string()))
+            .with_contact(ContactInfo::new("email", "bob@example.com".to_string()))
+            .with_motd("Maintenance at noon".to_string())
+            .with_icon_url("https://example.com/icon.png".to_string())
+            .with_urls(vec!["https://example.com".to_string()]);
+
+        assert_eq!(info.name.as_deref(), Some("Bob's mint"));
+        assert_eq!(
+            info.contact.as_ref().unwrap()[1],
+            ContactInfo::new(ContactMethod::Email, "bob@example.com".to_string())
+        );
+
+        // Legacy pairs and objects can be mixed
+        let contact: Vec<ContactInfo> = serde_json::from_str(
+            r#"[["nostr", "npub1..."], {"method": "email", "info": "bob@example.com"}]"#,
+        )
+        .unwrap();
+        assert_eq!(&contact, info.contact.as_ref().unwrap());
+        assert!(serde_json::from_str::<ContactInfo>(r#"["nostr"]"#).is_err());
+    }
+
+    #[test]
+    fn test_method_settings_round_trip() {
+        let nuts = r#"{
+  "4": {
+    "methods": [
+      {"method": "bolt11", "unit": "sat", "min_amount": 0, "max_amount": 10000},
+      {"method": "bolt12", "unit": "sat", "min_amount": 1, "max_amount": 5000},
+      {"method": "onchain", "unit": "usd", "min_amount": 100, "max_amount": 1000}
+    ],
+    "disabled": false
+  },
+  "5": {
+    "methods": [
+      {"method": "bolt11", "unit": "sat", "min_amount": 0, "max_amount": 10000}
+    ]
+  }
+}"#;
+        let nuts: Nuts = serde_json::from_str(nuts).unwrap();
+
+        let bolt12 = nuts
+            .nut04
+            .get_settings(&PaymentMethod::Bolt12, &CurrencyUnit::Sat)
+            .unwrap();
+        assert_eq!(bolt12.max_amount, Amount::from(5000));
+
+        let custom = nuts
+            .nut04
+            .get_settings(&PaymentMethod::from("onchain"), &CurrencyUnit::Usd)
+            .unwrap();
+        assert_eq!(custom.method, PaymentMethod::Custom("onchain".to_string()));
+
+        assert!(nuts
+            .nut04
+            .get_settings(&PaymentMethod::Bolt11, &CurrencyUnit::Usd)
+            .is_none());
+        assert!(nuts
+            .nut05
+            .get_settings(&PaymentMethod::Bolt12, &CurrencyUnit::Sat)
+            .is_none());
+
+        let round_trip: Nuts =
+            serde_json::from_str(&serde_json::to_string(&nuts).unwrap()).unwrap();
+        assert_eq!(round_trip, nuts);
+
+        let json = serde_json::to_value(&nuts).unwrap();
+        assert_eq!(json["4"]["methods"][1]["method"], "bolt12");
+        assert_eq!(json["4"]["methods"][2]["method"], "onchain");
     }
 }
@@ -4,6 +4,7 @@
 
 use serde::{Deserialize, Serialize};
 
+use super::nut00::Request;
 use super::nut01::PublicKey;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -16,14 +17,24 @@ pub enum State {
 
 /// Check spendabale request [NUT-07]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct CheckStateRequest {
     /// Y's of the proofs to check
     #[serde(rename = "Ys")]
     pub ys: Vec<PublicKey>,
 }
 
+impl Request for CheckStateRequest {}
+
+impl CheckStateRequest {
+    pub fn new(ys: Vec<PublicKey>) -> Self {
+        Self { ys }
+    }
+}
+
 /// Proof state [NUT-07]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct ProofState {
     /// Y of proof
     #[serde(rename = "Y")]
@@ -34,14 +45,62 @@ pub struct ProofState {
     pub witness: Option<String>,
 }
 
+impl ProofState {
+    pub fn new(y: PublicKey, state: State, witness: Option<String>) -> Self {
+        Self { y, state, witness }
+    }
+}
+
 /// Check Spendable Response [NUT-07]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct CheckStateResponse {
     pub states: Vec<ProofState>,
 }
 
+impl CheckStateResponse {
+    pub fn new(states: Vec<ProofState>) -> Self {
+        Self { states }
+    }
+}
+
 /// Spendable Settings
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Settings {
     supported: bool,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_check_state_serde() {
+        let y = PublicKey::from_str(
+            "02194603ffa36356f4a56b7df9371fc3192472351453ec7398b8da8117e7c3e104",
+        )
+        .unwrap();
+
+        let request = CheckStateRequest::new(vec![y]);
+        let json = serde_json::to_string(&request).unwrap();
+        assert_eq!(
+            json,
+            r#"{"Ys":["02194603ffa36356f4a56b7df9371fc3192472351453ec7398b8da8117e7c3e104"]}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<CheckStateRequest>(&json).unwrap(),
+            request
+        );
+
+        let response: CheckStateResponse = serde_json::from_str(
+            r#"{"states":[{"Y":"02194603ffa36356f4a56b7df9371fc3192472351453ec7398b8da8117e7c3e104","state":"PENDING","witness":null}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            response.states,
+            vec![ProofState::new(y, State::Pending, None)]
+        );
+    }
+}
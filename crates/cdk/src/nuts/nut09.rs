@@ -4,26 +4,45 @@
 
 use serde::{Deserialize, Serialize};
 
-use super::nut00::{BlindSignature, BlindedMessage};
+use super::nut00::{BlindSignature, BlindedMessage, Request};
 
 /// Restore Request [NUT-09]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct RestoreRequest {
     /// Outputs
     pub outputs: Vec<BlindedMessage>,
 }
 
+impl Request for RestoreRequest {}
+
+impl RestoreRequest {
+    pub fn new(outputs: Vec<BlindedMessage>) -> Self {
+        Self { outputs }
+    }
+}
+
 /// Restore Response [NUT-09]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct RestoreResponse {
     /// Outputs
     pub outputs: Vec<BlindedMessage>,
     /// Signatures
     // TODO: remove rename just for temp compatanlite with nutshell
-    #[serde(rename = "promises")]
+    #[serde(rename = "promises", alias = "signatures")]
     pub signatures: Vec<BlindSignature>,
 }
 
+impl RestoreResponse {
+    pub fn new(outputs: Vec<BlindedMessage>, signatures: Vec<BlindSignature>) -> Self {
+        Self {
+            outputs,
+            signatures,
+        }
+    }
+}
+
 mod test {
 
     #[test]
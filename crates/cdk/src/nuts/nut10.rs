@@ -14,6 +14,8 @@ pub enum Kind {
     /// NUT-11 P2PK
     #[default]
     P2PK,
+    /// NUT-14 HTLC
+    HTLC,
 }
 
 #[derive(Debug, Default, Clone, Deserialize, PartialEq, Eq, Serialize)]
@@ -23,6 +25,7 @@ pub struct SecretData {
     /// Expresses the spending condition specific to each kind
     pub data: String,
     /// Additional data committed to and can be used for feature extensions
+    #[serde(default)]
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub tags: Vec<Vec<String>>,
 }
@@ -102,4 +105,23 @@ mod tests {
 
         assert_eq!(serde_json::to_string(&secret).unwrap(), secret_str);
     }
+
+    #[test]
+    fn test_secret_without_tags() {
+        let secret_str = r#"["P2PK",{"nonce":"5d11913ee0f92fefdc82a6764fd2457a","data":"026562efcfadc8e86d44da6a8adf80633d974302e62c850774db1fb36ff4cc7198"}]"#;
+        let secret: Secret = serde_json::from_str(secret_str).unwrap();
+
+        assert_eq!(secret.kind, Kind::P2PK);
+        assert!(secret.secret_data.tags.is_empty());
+        assert_eq!(serde_json::to_string(&secret).unwrap(), secret_str);
+    }
+
+    #[test]
+    fn test_htlc_secret() {
+        let secret_str = r#"["HTLC",{"nonce":"da62796403af76c80cd6ce9153ed3746","data":"023192200a0cfd3867e48eb63b03ff599c7e46c8f4e41146b2d281173ca6c50c54"}]"#;
+        let secret: Secret = serde_json::from_str(secret_str).unwrap();
+
+        assert_eq!(secret.kind, Kind::HTLC);
+        assert_eq!(serde_json::to_string(&secret).unwrap(), secret_str);
+    }
 }
@@ -10,18 +10,16 @@ use std::str::FromStr;
 use bitcoin::hashes::sha256::Hash as Sha256Hash;
 use bitcoin::hashes::Hash;
 use bitcoin::secp256k1::schnorr::Signature;
-use bitcoin::secp256k1::{
-    KeyPair, Message, Parity, PublicKey as NormalizedPublicKey, XOnlyPublicKey,
-};
+use bitcoin::secp256k1::{KeyPair, Message, XOnlyPublicKey};
 use serde::de::Error as DeserializerError;
 use serde::ser::SerializeSeq;
-use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use thiserror::Error;
 
 use super::nut01::PublicKey;
 use super::nut10::{Secret, SecretData};
 use super::{Proof, SecretKey};
-use crate::nuts::nut00::BlindedMessage;
+use crate::nuts::nut00::{BlindedMessage, Witness};
 use crate::util::{hex, unix_time};
 use crate::SECP256K1;
 
@@ -47,6 +45,9 @@ pub enum Error {
     P2PKPubkeyRequired,
     #[error("Kind not found")]
     KindNotFound,
+    /// Tag without the value its kind requires
+    #[error("Tag `{0}` without a value")]
+    TagValueNotFound(TagKind),
     /// Parse Url Error
     #[error(transparent)]
     UrlParseError(#[from] url::ParseError),
@@ -70,39 +71,27 @@ pub enum Error {
     Secret(#[from] crate::secret::Error),
 }
 
+/// P2PK Witness
 #[derive(Default, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub struct Signatures {
-    #[serde(default)]
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+pub struct P2PKWitness {
     pub signatures: Vec<String>,
 }
 
-impl Signatures {
+impl P2PKWitness {
     #[inline]
     pub fn is_empty(&self) -> bool {
         self.signatures.is_empty()
     }
 }
 
-/// Serialize [Signatures] as stringified JSON
-pub fn witness_serialize<S>(x: &Option<Signatures>, s: S) -> Result<S::Ok, S::Error>
-where
-    S: Serializer,
-{
-    s.serialize_str(&serde_json::to_string(&x).map_err(ser::Error::custom)?)
-}
-
-/// Serialize [Signatures] from stringified JSON
-pub fn witness_deserialize<'de, D>(deserializer: D) -> Result<Option<Signatures>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let s: String = String::deserialize(deserializer)?;
-    serde_json::from_str(&s).map_err(de::Error::custom)
-}
-
 impl Proof {
     pub fn verify_p2pk(&self) -> Result<(), Error> {
+        self.verify_p2pk_at(unix_time())
+    }
+
+    /// [Proof::verify_p2pk] with the refund path open if the locktime is
+    /// before `now`
+    pub fn verify_p2pk_at(&self, now: u64) -> Result<(), Error> {
         if !self.secret.is_p2pk() {
             return Err(Error::IncorrectSecretKind);
         }
@@ -113,8 +102,8 @@ impl Proof {
 
         let mut valid_sigs = 0;
 
-        if let Some(witness) = &self.witness {
-            for signature in witness.signatures.iter() {
+        if let Some(signatures) = self.witness.as_ref().and_then(|w| w.signatures()) {
+            for signature in signatures.iter() {
                 let mut pubkeys = spending_conditions.pubkeys.clone();
 
                 pubkeys.push(VerifyingKey::from_str(&secret.secret_data.data)?);
@@ -143,9 +132,9 @@ impl Proof {
             spending_conditions.refund_keys,
         ) {
             // If lock time has passed check if refund witness signature is valid
-            if locktime.lt(&unix_time()) {
-                if let Some(signatures) = &self.witness {
-                    for s in &signatures.signatures {
+            if locktime.lt(&now) {
+                if let Some(signatures) = self.witness.as_ref().and_then(|w| w.signatures()) {
+                    for s in signatures {
                         for v in &refund_keys {
                             let sig =
                                 Signature::from_str(s).map_err(|_| Error::InvalidSignature)?;
@@ -167,11 +156,14 @@ impl Proof {
         let msg: Vec<u8> = self.secret.to_bytes();
         let signature: Signature = secret_key.sign(&msg)?;
 
-        self.witness
-            .as_mut()
-            .unwrap_or(&mut Signatures::default())
-            .signatures
-            .push(signature.to_string());
+        match self.witness.as_mut() {
+            Some(witness) => witness.add_signatures(vec![signature.to_string()]),
+            None => {
+                self.witness = Some(Witness::P2PKWitness(P2PKWitness {
+                    signatures: vec![signature.to_string()],
+                }))
+            }
+        }
 
         Ok(())
     }
@@ -182,11 +174,14 @@ impl BlindedMessage {
         let msg: [u8; 33] = self.blinded_secret.to_bytes();
         let signature: Signature = secret_key.sign(&msg)?;
 
-        self.witness
-            .as_mut()
-            .unwrap_or(&mut Signatures::default())
-            .signatures
-            .push(signature.to_string());
+        match self.witness.as_mut() {
+            Some(witness) => witness.add_signatures(vec![signature.to_string()]),
+            None => {
+                self.witness = Some(Witness::P2PKWitness(P2PKWitness {
+                    signatures: vec![signature.to_string()],
+                }))
+            }
+        }
 
         Ok(())
     }
@@ -197,8 +192,8 @@ impl BlindedMessage {
         required_sigs: u64,
     ) -> Result<(), Error> {
         let mut valid_sigs = 0;
-        if let Some(witness) = &self.witness {
-            for signature in &witness.signatures {
+        if let Some(signatures) = self.witness.as_ref().and_then(|w| w.signatures()) {
+            for signature in signatures {
                 for v in pubkeys {
                     let msg = &self.blinded_secret.to_bytes();
                     let sig = Signature::from_str(signature)?;
@@ -319,14 +314,17 @@ impl TryFrom<P2PKConditions> for crate::secret::Secret {
 impl TryFrom<Secret> for P2PKConditions {
     type Error = Error;
     fn try_from(secret: Secret) -> Result<P2PKConditions, Self::Error> {
-        let tags: HashMap<TagKind, Tag> = secret
-            .clone()
-            .secret_data
-            .tags
-            .into_iter()
-            .map(|t| Tag::try_from(t).unwrap())
-            .map(|t| (t.kind(), t))
-            .collect();
+        let mut tags: HashMap<TagKind, Tag> = HashMap::new();
+        for tag in secret.secret_data.tags.clone() {
+            match Tag::try_from(tag) {
+                Ok(tag) => {
+                    tags.insert(tag.kind(), tag);
+                }
+                // Tags of other spending conditions do not concern P2PK
+                Err(Error::UnknownTag) => (),
+                Err(err) => return Err(err),
+            }
+        }
 
         let mut pubkeys: Vec<VerifyingKey> = vec![];
 
@@ -500,10 +498,16 @@ where
             None => return Err(Error::KindNotFound),
         };
 
+        let value = || {
+            tag.get(1)
+                .map(|value| value.as_ref())
+                .ok_or_else(|| Error::TagValueNotFound(tag_kind.clone()))
+        };
+
         match tag_kind {
-            TagKind::SigFlag => Ok(Tag::SigFlag(SigFlag::from(tag[1].as_ref()))),
-            TagKind::NSigs => Ok(Tag::NSigs(tag[1].as_ref().parse()?)),
-            TagKind::Locktime => Ok(Tag::LockTime(tag[1].as_ref().parse()?)),
+            TagKind::SigFlag => Ok(Tag::SigFlag(SigFlag::from(value()?))),
+            TagKind::NSigs => Ok(Tag::NSigs(value()?.parse()?)),
+            TagKind::Locktime => Ok(Tag::LockTime(value()?.parse()?)),
             TagKind::Refund => {
                 let pubkeys = tag
                     .iter()
@@ -589,12 +593,11 @@ impl VerifyingKey {
     }
 
     pub fn from_public_key(public_key: PublicKey) -> Self {
-        let (pk, ..) = public_key.x_only_public_key();
-        Self(pk)
+        Self(public_key.into())
     }
 
     pub fn to_normalized_public_key(self) -> PublicKey {
-        NormalizedPublicKey::from_x_only_public_key(self.0, Parity::Even).into()
+        self.0.into()
     }
 
     pub fn verify(&self, msg: &[u8], sig: &Signature) -> Result<(), Error> {
@@ -705,7 +708,7 @@ mod tests {
     use std::str::FromStr;
 
     use super::*;
-    use crate::nuts::Id;
+    use crate::nuts::{Id, Kind};
     use crate::Amount;
 
     #[test]
@@ -743,6 +746,35 @@ mod tests {
         assert_eq!(secret_der, secret);
     }
 
+    #[test]
+    fn test_malformed_tags() {
+        let pubkey = "033281c37677ea273eb7183b783067f5244933ef78d8c3f15b1a77cb246099c26e";
+        let conditions = |tags: Vec<Vec<&str>>| {
+            let tags = tags
+                .into_iter()
+                .map(|tag| tag.into_iter().map(str::to_string).collect())
+                .collect();
+            P2PKConditions::try_from(Secret::new(Kind::P2PK, pubkey, tags))
+        };
+
+        for kind in [TagKind::SigFlag, TagKind::NSigs, TagKind::Locktime] {
+            let tag = kind.to_string();
+            assert!(matches!(
+                conditions(vec![vec![tag.as_str()]]),
+                Err(Error::TagValueNotFound(k)) if k == kind
+            ));
+        }
+        assert!(matches!(
+            conditions(vec![vec!["n_sigs", "many"]]),
+            Err(Error::ParseInt(_))
+        ));
+        assert!(matches!(conditions(vec![vec![]]), Err(Error::KindNotFound)));
+
+        // Tags of other conditions are skipped
+        let parsed = conditions(vec![vec!["hashlock", "00"], vec!["n_sigs", "1"]]).unwrap();
+        assert_eq!(parsed.num_sigs, Some(1));
+    }
+
     #[test]
     fn sign_proof() {
         let secret_key = SigningKey::from_str(
@@ -781,8 +813,9 @@ mod tests {
                 "02698c4e2b5f9534cd0687d87513c759790cf829aa5739184a3e3735471fbda904",
             )
             .unwrap(),
-            witness: Some(Signatures { signatures: vec![] }),
+            witness: Some(Witness::P2PKWitness(P2PKWitness { signatures: vec![] })),
             dleq: None,
+            y: Default::default(),
         };
 
         proof.sign_p2pk(secret_key).unwrap();
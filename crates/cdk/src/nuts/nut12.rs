@@ -30,12 +30,15 @@ pub enum Error {
     Secp256k1(#[from] secp256k1::Error),
 }
 
+/// DLEQ proof of a [BlindSignature], `{"e", "s"}` as hex strings
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct BlindSignatureDleq {
     pub e: SecretKey,
     pub s: SecretKey,
 }
 
+/// DLEQ proof of a [Proof], the [BlindSignatureDleq] it was unblinded from
+/// with the blinding factor `r` of its output
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ProofDleq {
     pub e: SecretKey,
@@ -151,7 +154,7 @@ impl BlindSignature {
         blinded_signature: PublicKey,
         keyset_id: Id,
         blinded_message: &PublicKey,
-        mint_secretkey: SecretKey,
+        mint_secretkey: &SecretKey,
     ) -> Result<Self, Error> {
         Ok(Self {
             amount,
@@ -160,7 +163,7 @@ impl BlindSignature {
             dleq: Some(calculate_dleq(
                 blinded_signature,
                 blinded_message,
-                &mint_secretkey,
+                mint_secretkey,
             )?),
         })
     }
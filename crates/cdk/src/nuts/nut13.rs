@@ -15,7 +15,7 @@ use crate::dhke::blind_message;
 use crate::error::Error;
 use crate::secret::Secret;
 use crate::util::hex;
-use crate::{Amount, SECP256K1};
+use crate::{Amount, SplitTarget, SECP256K1};
 
 impl Secret {
     pub fn from_seed(mnemonic: &Mnemonic, keyset_id: Id, counter: u64) -> Result<Self, Error> {
@@ -71,11 +71,45 @@ impl PreMintSecrets {
         amount: Amount,
         zero_amount: bool,
     ) -> Result<Self, Error> {
-        let mut pre_mint_secrets = PreMintSecrets::default();
+        Self::from_seed_with_target(
+            keyset_id,
+            counter,
+            mnemonic,
+            amount,
+            &SplitTarget::None,
+            zero_amount,
+        )
+    }
+
+    /// [PreMintSecrets::from_seed] with `amount` split as `target` asks
+    pub fn from_seed_with_target(
+        keyset_id: Id,
+        counter: u64,
+        mnemonic: &Mnemonic,
+        amount: Amount,
+        target: &SplitTarget,
+        zero_amount: bool,
+    ) -> Result<Self, Error> {
+        Self::from_seed_with_amounts(
+            keyset_id,
+            counter,
+            mnemonic,
+            &amount.split_targeted(target),
+            zero_amount,
+        )
+    }
 
-        let mut counter = counter;
+    /// [PreMintSecrets::from_seed] with one output for each of `amounts`
+    pub fn from_seed_with_amounts(
+        keyset_id: Id,
+        counter: u64,
+        mnemonic: &Mnemonic,
+        amounts: &[Amount],
+        zero_amount: bool,
+    ) -> Result<Self, Error> {
+        let mut pre_mint_secrets = PreMintSecrets::default();
 
-        for amount in amount.split() {
+        for (counter, &amount) in (counter..).zip(amounts) {
             let secret = Secret::from_seed(mnemonic, keyset_id, counter)?;
             let blinding_factor = SecretKey::from_seed(mnemonic, keyset_id, counter)?;
 
@@ -93,7 +127,6 @@ impl PreMintSecrets {
             };
 
             pre_mint_secrets.secrets.push(pre_mint);
-            counter += 1;
         }
 
         Ok(pre_mint_secrets)
@@ -0,0 +1,15 @@
+//! NUT-14: Hashed Time Lock Contracts (HTLC)
+//!
+//! <https://github.com/cashubtc/nuts/blob/main/14.md>
+
+use serde::{Deserialize, Serialize};
+
+/// HTLC Witness
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct HTLCWitness {
+    /// Preimage
+    pub preimage: String,
+    /// Signatures
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signatures: Option<Vec<String>>,
+}
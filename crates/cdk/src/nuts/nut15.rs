@@ -0,0 +1,77 @@
+//! NUT-15: Partial multi-path payments
+//!
+//! <https://github.com/cashubtc/nuts/blob/main/15.md>
+
+use serde::{Deserialize, Serialize};
+
+use super::nut00::{CurrencyUnit, PaymentMethod};
+use crate::Amount;
+
+/// Part of an invoice paid by one mint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mpp {
+    /// Msat of the invoice this mint pays
+    pub amount: Amount,
+}
+
+/// Melt quote request options [NUT-15]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MeltOptions {
+    pub mpp: Mpp,
+}
+
+impl MeltOptions {
+    /// Pay `amount_msat` of the invoice
+    pub fn mpp(amount_msat: u64) -> Self {
+        Self {
+            mpp: Mpp {
+                amount: Amount::from(amount_msat),
+            },
+        }
+    }
+}
+
+/// Mpp Method Settings
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MppMethodSettings {
+    /// Payment Method e.g. bolt11
+    pub method: PaymentMethod,
+    /// Currency Unit e.g. sat
+    pub unit: CurrencyUnit,
+}
+
+/// Mpp Settings
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Settings {
+    pub methods: Vec<MppMethodSettings>,
+}
+
+impl Settings {
+    /// Whether partial payments are supported for a `method` and `unit` pair
+    pub fn supports(&self, method: &PaymentMethod, unit: &CurrencyUnit) -> bool {
+        self.methods
+            .iter()
+            .any(|settings| settings.method.eq(method) && settings.unit.eq(unit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mpp_settings() {
+        let settings: Settings =
+            serde_json::from_str(r#"{"methods":[{"method":"bolt11","unit":"sat"}]}"#).unwrap();
+
+        assert!(settings.supports(&PaymentMethod::Bolt11, &CurrencyUnit::Sat));
+        assert!(!settings.supports(&PaymentMethod::Bolt11, &CurrencyUnit::Usd));
+        assert!(!Settings::default().supports(&PaymentMethod::Bolt11, &CurrencyUnit::Sat));
+
+        let options = MeltOptions::mpp(50_000);
+        assert_eq!(
+            serde_json::to_string(&options).unwrap(),
+            r#"{"mpp":{"amount":50000}}"#
+        );
+    }
+}
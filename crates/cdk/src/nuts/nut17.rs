@@ -0,0 +1,203 @@
+//! NUT-17: WebSocket subscriptions
+//!
+//! <https://github.com/cashubtc/nuts/blob/main/17.md>
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::nut04::MintQuoteBolt11Response;
+use super::nut05::MeltQuoteBolt11Response;
+use super::nut07::ProofState;
+
+/// JSON-RPC version of every message
+pub const JSON_RPC_VERSION: &str = "2.0";
+
+/// What a subscription is notified of, its filters are quote ids or the hex
+/// Ys of proofs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriptionKind {
+    Bolt11MintQuote,
+    Bolt11MeltQuote,
+    ProofState,
+}
+
+/// Params of a subscribe request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Params {
+    pub kind: SubscriptionKind,
+    #[serde(rename = "subId")]
+    pub sub_id: String,
+    pub filters: Vec<String>,
+}
+
+/// Params of an unsubscribe request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnsubscribeParams {
+    #[serde(rename = "subId")]
+    pub sub_id: String,
+}
+
+/// Method of a [WsRequest]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum WsMethod {
+    Subscribe(Params),
+    Unsubscribe(UnsubscribeParams),
+}
+
+/// Request sent by a wallet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WsRequest {
+    pub jsonrpc: String,
+    #[serde(flatten)]
+    pub method: WsMethod,
+    pub id: u64,
+}
+
+impl WsRequest {
+    pub fn new(method: WsMethod, id: u64) -> Self {
+        Self {
+            jsonrpc: JSON_RPC_VERSION.to_string(),
+            method,
+            id,
+        }
+    }
+}
+
+/// Result of an accepted [WsRequest]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WsResult {
+    pub status: String,
+    #[serde(rename = "subId")]
+    pub sub_id: String,
+}
+
+/// Error of a refused [WsRequest]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WsErrorBody {
+    pub code: i64,
+    pub message: String,
+}
+
+/// Params of a notification, its payload is typed by the
+/// [SubscriptionKind] of the subscription
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotificationParams {
+    #[serde(rename = "subId")]
+    pub sub_id: String,
+    pub payload: Value,
+}
+
+/// Message sent by a mint
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum WsMessage {
+    Notification {
+        jsonrpc: String,
+        method: String,
+        params: NotificationParams,
+    },
+    Response {
+        jsonrpc: String,
+        result: WsResult,
+        id: u64,
+    },
+    Error {
+        jsonrpc: String,
+        error: WsErrorBody,
+        id: u64,
+    },
+}
+
+/// Current state of a subscribed quote or proof
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Notification {
+    MintQuote(MintQuoteBolt11Response),
+    MeltQuote(MeltQuoteBolt11Response),
+    ProofState(ProofState),
+}
+
+impl Notification {
+    /// `payload` of a notification of a subscription of `kind`
+    pub fn from_payload(kind: SubscriptionKind, payload: Value) -> Result<Self, serde_json::Error> {
+        Ok(match kind {
+            SubscriptionKind::Bolt11MintQuote => Self::MintQuote(serde_json::from_value(payload)?),
+            SubscriptionKind::Bolt11MeltQuote => Self::MeltQuote(serde_json::from_value(payload)?),
+            SubscriptionKind::ProofState => Self::ProofState(serde_json::from_value(payload)?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nuts::State;
+
+    #[test]
+    fn test_ws_request() {
+        let request = WsRequest::new(
+            WsMethod::Subscribe(Params {
+                kind: SubscriptionKind::Bolt11MintQuote,
+                sub_id: "sub".to_string(),
+                filters: vec!["quote".to_string()],
+            }),
+            0,
+        );
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "subscribe",
+                "params": {"kind": "bolt11_mint_quote", "subId": "sub", "filters": ["quote"]},
+                "id": 0
+            })
+        );
+
+        let request = WsRequest::new(
+            WsMethod::Unsubscribe(UnsubscribeParams {
+                sub_id: "sub".to_string(),
+            }),
+            1,
+        );
+        assert_eq!(
+            serde_json::to_string(&request).unwrap(),
+            r#"{"jsonrpc":"2.0","method":"unsubscribe","params":{"subId":"sub"},"id":1}"#
+        );
+    }
+
+    #[test]
+    fn test_ws_message() {
+        let message: WsMessage = serde_json::from_str(
+            r#"{"jsonrpc":"2.0","result":{"status":"OK","subId":"sub"},"id":0}"#,
+        )
+        .unwrap();
+        assert!(matches!(message, WsMessage::Response { id: 0, .. }));
+
+        let message: WsMessage = serde_json::from_str(
+            r#"{"jsonrpc":"2.0","error":{"code":-32601,"message":"Method not found"},"id":1}"#,
+        )
+        .unwrap();
+        assert!(matches!(message, WsMessage::Error { id: 1, error, .. } if error.code == -32601));
+
+        let message: WsMessage = serde_json::from_str(
+            r#"{"jsonrpc":"2.0","method":"subscribe","params":{"subId":"sub","payload":{"Y":"02194603ffa36356f4a56b7df9371fc3192472351453ec7398b8da8117e7c3e104","state":"SPENT","witness":null}}}"#,
+        )
+        .unwrap();
+        let WsMessage::Notification { params, .. } = message else {
+            panic!("Not a notification");
+        };
+        let Notification::ProofState(state) =
+            Notification::from_payload(SubscriptionKind::ProofState, params.payload.clone())
+                .unwrap()
+        else {
+            panic!("Not a proof state");
+        };
+        assert_eq!(state.state, State::Spent);
+        // The payload of one kind is not read as another
+        assert!(
+            Notification::from_payload(SubscriptionKind::Bolt11MintQuote, params.payload).is_err()
+        );
+    }
+}
@@ -0,0 +1,131 @@
+//! NUT-25: Mint Tokens via Bolt12
+//!
+//! <https://github.com/cashubtc/nuts/blob/main/25.md>
+
+use serde::{Deserialize, Serialize};
+
+use super::nut00::{CurrencyUnit, PaymentMethod, Request};
+use crate::types::MintQuote;
+use crate::Amount;
+
+/// Mint quote request [NUT-25]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct MintQuoteBolt12Request {
+    /// Amount of each payment, [None] for the payer to choose
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount: Option<Amount>,
+    /// Unit wallet would like to pay with
+    pub unit: CurrencyUnit,
+    /// Description for the offer, shown to the payer
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl Request for MintQuoteBolt12Request {}
+
+impl MintQuoteBolt12Request {
+    pub fn new(amount: Option<Amount>, unit: CurrencyUnit) -> Self {
+        Self {
+            amount,
+            unit,
+            description: None,
+        }
+    }
+}
+
+/// Mint quote response [NUT-25]
+///
+/// The offer can be paid several times, ecash can be minted up to
+/// `amount_paid - amount_issued`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct MintQuoteBolt12Response {
+    /// Quote Id
+    pub quote: String,
+    /// Offer to pay
+    pub request: String,
+    /// Amount of each payment, [None] if the payer chooses
+    pub amount: Option<Amount>,
+    pub unit: CurrencyUnit,
+    /// Unix timestamp until the quote is valid
+    pub expiry: u64,
+    /// Received over the offer so far
+    pub amount_paid: Amount,
+    /// Issued for the quote so far
+    pub amount_issued: Amount,
+}
+
+impl TryFrom<MintQuote> for MintQuoteBolt12Response {
+    type Error = PaymentMethod;
+
+    /// Fails with the method of a quote that is not a bolt12 quote
+    fn try_from(mint_quote: MintQuote) -> Result<Self, Self::Error> {
+        if mint_quote.method != PaymentMethod::Bolt12 {
+            return Err(mint_quote.method);
+        }
+
+        Ok(Self {
+            quote: mint_quote.id,
+            request: mint_quote.request,
+            amount: Some(mint_quote.amount).filter(|amount| *amount != Amount::ZERO),
+            unit: mint_quote.unit,
+            expiry: mint_quote.expiry,
+            amount_paid: mint_quote.amount_paid,
+            amount_issued: mint_quote.amount_issued,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mint_quote_bolt12_response() {
+        let mut quote = MintQuote::new_bolt12(
+            "lno1".to_string(),
+            CurrencyUnit::Sat,
+            None,
+            100,
+            Some("coffee".to_string()),
+        );
+        quote.amount_paid = Amount::from(8);
+        quote.amount_issued = Amount::from(5);
+
+        let response = MintQuoteBolt12Response::try_from(quote.clone()).unwrap();
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            serde_json::json!({
+                "quote": quote.id,
+                "request": "lno1",
+                "amount": null,
+                "unit": "sat",
+                "expiry": 100,
+                "amount_paid": 8,
+                "amount_issued": 5
+            })
+        );
+        assert_eq!(quote.mintable(), Amount::from(3));
+
+        let bolt11 = MintQuote::new(
+            "lnbc".to_string(),
+            CurrencyUnit::Sat,
+            Amount::from(8),
+            100,
+            None,
+        );
+        assert_eq!(
+            MintQuoteBolt12Response::try_from(bolt11),
+            Err(PaymentMethod::Bolt11)
+        );
+
+        let request: MintQuoteBolt12Request = serde_json::from_str(r#"{"unit":"sat"}"#).unwrap();
+        assert_eq!(
+            request,
+            MintQuoteBolt12Request::new(None, CurrencyUnit::Sat)
+        );
+    }
+}
@@ -0,0 +1,189 @@
+//! Types of the v1 HTTP API
+//!
+//! The NUT modules already follow the quote based v1 API; this module
+//! gathers its request and response bodies under their v1 names.
+//!
+//! <https://github.com/cashubtc/nuts>
+
+pub use super::nut01::KeysResponse;
+pub use super::nut02::KeysetResponse;
+pub use super::nut03::{SwapRequest, SwapResponse};
+pub use super::nut04::{
+    MintBolt11Request, MintBolt11Response, MintQuoteBolt11Request, MintQuoteBolt11Response,
+};
+pub use super::nut05::{
+    MeltBolt11Request, MeltBolt11Response, MeltQuoteBolt11Request, MeltQuoteBolt11Response,
+};
+
+#[cfg(test)]
+mod tests {
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::nuts::CheckFeesRequest;
+
+    const INVOICE: &str = "lnbc2500u1pj48ugqdq8w3jhxaqpp5qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqsp59g4z52329g4z52329g4z52329g4z52329g4z52329g4z52329g4q9qrsgqcqzysd6exwz2yjprkglq820pffp8wcu97sxuzcgx7raensa83dnc4kpmhkuqevwf6hmv6a3mc9aegn89jv7a8e9wggdxtfsjwrur7n6sx4rsq7rvjx3";
+
+    /// Parse the spec `example` and check it serializes back unchanged
+    fn snapshot<T>(example: Value) -> T
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let parsed: T = serde_json::from_value(example.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), example);
+        parsed
+    }
+
+    fn proofs() -> Value {
+        json!([
+            {
+                "amount": 2,
+                "id": "009a1f293253e41e",
+                "secret": "407915bc212be61a77e3e6d2aeb4c727980bda51cd06a6afc29e2861768a7837",
+                "C": "02bc9097997d81afb2cc7346b5e4345a9346bd2a506eb7958598a72f0cf85163ea"
+            },
+            {
+                "amount": 8,
+                "id": "009a1f293253e41e",
+                "secret": "fe15109314e61d7756b0f8ee0f23a624acaa3f4e042f61433c728c7057b931be",
+                "C": "029e8e5050b890a7d6c0968db16bc1d5d5fa040ea1de284f6ec69d61299f671059"
+            }
+        ])
+    }
+
+    fn outputs() -> Value {
+        json!([
+            {
+                "amount": 2,
+                "id": "009a1f293253e41e",
+                "B_": "02634a2c2b34bec9e8a4aba4361f6bf202d7fa2365379b0840afe249a7a9d71239"
+            },
+            {
+                "amount": 8,
+                "id": "009a1f293253e41e",
+                "B_": "03b25a7a5e2d764b4ecad555dbe21c5ad1cdcc504a57ab36948b2d4e9fc2e3a13e"
+            }
+        ])
+    }
+
+    fn signatures() -> Value {
+        json!([
+            {
+                "id": "009a1f293253e41e",
+                "amount": 2,
+                "C_": "0224f1c4c564230ad3d96c5033efdc425582397a5a7691d600202732edc6d4b1ec"
+            },
+            {
+                "id": "009a1f293253e41e",
+                "amount": 8,
+                "C_": "0277d1de806ed177007e5b94a8139343b6382e472c752a74e99949d511f7194f6c"
+            }
+        ])
+    }
+
+    #[test]
+    fn test_keys_serde() {
+        let response: KeysResponse = snapshot(json!({
+            "keysets": [{
+                "id": "009a1f293253e41e",
+                "unit": "sat",
+                "keys": {
+                    "1": "02194603ffa36356f4a56b7df9371fc3192472351453ec7398b8da8117e7c3e104",
+                    "2": "03b0f36d6d47ce14df8a7be9137712c42bcdd960b19dd02f1d4a9703b1f31d7513"
+                }
+            }]
+        }));
+        assert_eq!(response.keysets.len(), 1);
+
+        let response: KeysetResponse = snapshot(json!({
+            "keysets": [
+                {"id": "009a1f293253e41e", "unit": "sat", "active": true, "input_fee_ppk": 100},
+                {"id": "0042ade98b2a370a", "unit": "sat", "active": false, "input_fee_ppk": 100}
+            ]
+        }));
+        assert!(response.keysets[0].active);
+    }
+
+    #[test]
+    fn test_mint_serde() {
+        let request: MintQuoteBolt11Request = snapshot(json!({"amount": 10, "unit": "sat"}));
+        assert!(request.description.is_none());
+
+        snapshot::<MintQuoteBolt11Response>(json!({
+            "quote": "DSGLX9kevM",
+            "request": INVOICE,
+            "paid": false,
+            "expiry": 1701704757
+        }));
+
+        let request: MintBolt11Request = snapshot(json!({
+            "quote": "DSGLX9kevM",
+            "outputs": outputs()
+        }));
+        assert_eq!(request.outputs.len(), 2);
+
+        snapshot::<MintBolt11Response>(json!({"signatures": signatures()}));
+    }
+
+    #[test]
+    fn test_swap_serde() {
+        let request: SwapRequest = snapshot(json!({
+            "inputs": proofs(),
+            "outputs": outputs()
+        }));
+        assert_eq!(
+            request.input_amount().unwrap(),
+            request.output_amount().unwrap()
+        );
+
+        snapshot::<SwapResponse>(json!({"signatures": signatures()}));
+    }
+
+    #[test]
+    fn test_melt_serde() {
+        let request: MeltQuoteBolt11Request = snapshot(json!({
+            "request": INVOICE,
+            "unit": "sat"
+        }));
+        let legacy = CheckFeesRequest::new(request.request.clone());
+        assert_eq!(MeltQuoteBolt11Request::from(legacy), request);
+
+        let legacy: MeltQuoteBolt11Response = snapshot(json!({
+            "quote": "TRmjduhIsPxd",
+            "amount": 10,
+            "fee_reserve": 2,
+            "paid": false,
+            "expiry": 1701704757
+        }));
+        assert_eq!(legacy.state(), crate::nuts::MeltQuoteState::Unpaid);
+        let paid: MeltQuoteBolt11Response = snapshot(json!({
+            "quote": "TRmjduhIsPxd",
+            "amount": 10,
+            "fee_reserve": 2,
+            "paid": true,
+            "state": "PAID",
+            "expiry": 1701704757,
+            "payment_preimage": "c5a1ae1f639e1f4a3872e81500fd028bece7bedc1152f740cba5c3417b748c1b"
+        }));
+        assert_eq!(paid.state(), crate::nuts::MeltQuoteState::Paid);
+
+        let request: MeltBolt11Request = snapshot(json!({
+            "quote": "od4CN5smMMS3K3QVHkbGGNCTxfcAIyIXeq8IrfhP",
+            "inputs": proofs()
+        }));
+        assert!(request.outputs.is_none());
+        snapshot::<MeltBolt11Request>(json!({
+            "quote": "od4CN5smMMS3K3QVHkbGGNCTxfcAIyIXeq8IrfhP",
+            "inputs": proofs(),
+            "outputs": outputs()
+        }));
+
+        snapshot::<MeltBolt11Response>(json!({
+            "paid": true,
+            "payment_preimage": "c5a1ae1f639e1f4a3872e81500fd028bece7bedc1152f740cba5c3417b748c1b",
+            "change": signatures()
+        }));
+    }
+}
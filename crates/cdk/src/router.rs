@@ -0,0 +1,2608 @@
+//! HTTP routes of a mint
+//!
+//! [MintRouter] answers the NUT HTTP API of a [Mint] with plain [http]
+//! types, so it can be mounted in any server framework. With axum, as a
+//! fallback handler:
+//!
+//! ```ignore
+//! let router = Arc::new(MintRouter::new(mint));
+//! let app = axum::Router::new().fallback(move |request: Request| async move {
+//!     let (parts, body) = request.into_parts();
+//!     let body = axum::body::to_bytes(body, usize::MAX).await?.to_vec();
+//!     router.handle(http::Request::from_parts(parts, body)).await
+//! });
+//! ```
+
+use std::str::FromStr;
+use std::sync::Arc;
+
+use http::header::CONTENT_TYPE;
+use http::{HeaderValue, Method, Request, Response, StatusCode};
+use serde::Serialize;
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+use crate::error::{ErrorCode, ErrorResponse};
+use crate::mint::{Error, Mint};
+use crate::nuts::{
+    CheckFeesRequest, CheckStateRequest, Id, LegacyFields, MeltBolt11Request,
+    MeltQuoteBolt11Request, MintBolt11Request, MintQuoteBolt11Request, MintQuoteBolt12Request,
+    PaymentMethod, RestoreRequest, Strictness, SwapRequest, SwapResponseForm, Vintage,
+};
+use crate::util::unix_time;
+use crate::{lightning, Bolt11Invoice};
+
+/// Seconds a quote created through the router can be paid for
+const DEFAULT_QUOTE_TTL: u64 = 3600;
+
+/// Routes NUT HTTP requests to a [Mint]
+///
+/// Mint and melt quote invoices are created and paid by the
+/// [LnBackend](crate::lightning::LnBackend) of the mint. While a melt is
+/// paid the mint lock is released, so a slow payment does not hold up other
+/// requests.
+#[derive(Clone)]
+pub struct MintRouter {
+    mint: Arc<Mutex<Mint>>,
+    quote_ttl: u64,
+    strictness: Strictness,
+    vintage: Vintage,
+}
+
+impl MintRouter {
+    pub fn new(mint: Arc<Mutex<Mint>>) -> Self {
+        Self {
+            mint,
+            quote_ttl: DEFAULT_QUOTE_TTL,
+            strictness: Strictness::default(),
+            vintage: Vintage::default(),
+        }
+    }
+
+    /// Seconds until quotes created through the router expire
+    pub fn set_quote_ttl(&mut self, quote_ttl: u64) {
+        self.quote_ttl = quote_ttl;
+    }
+
+    /// How request bodies with unknown fields are treated
+    pub fn set_strictness(&mut self, strictness: Strictness) {
+        self.strictness = strictness;
+    }
+
+    /// Field names of mint and swap response bodies, for mints that still
+    /// serve wallets predating the v1 API
+    ///
+    /// Swap requests with a split `amount` are always answered with `fst`
+    /// and `snd`.
+    pub fn set_vintage(&mut self, vintage: Vintage) {
+        self.vintage = vintage;
+    }
+
+    /// Answer `request`
+    ///
+    /// Errors are answered with an [ErrorResponse] body and the
+    /// [status](crate::mint::Error::status_code) of the error, unknown routes
+    /// with a 404.
+    pub async fn handle(&self, request: Request<Vec<u8>>) -> Response<Vec<u8>> {
+        let method = request.method().clone();
+        let path = request.uri().path().to_string();
+
+        let response = self.route(&method, &path, request.into_body()).await;
+
+        match response {
+            Ok(Some(response)) => response,
+            Ok(None) => json_response(
+                StatusCode::NOT_FOUND,
+                &ErrorResponse {
+                    code: ErrorCode::Unknown(9999),
+                    error: None,
+                    detail: Some(format!("No route for {method} {path}")),
+                    active_keyset_id: None,
+                },
+            ),
+            Err(err) => {
+                let (status, body): (StatusCode, ErrorResponse) = err.into();
+                if status.is_server_error() {
+                    warn!(%method, %path, %status, "Request failed: {:?}", body.detail);
+                } else {
+                    debug!(%method, %path, %status, "Request refused: {:?}", body.detail);
+                }
+
+                json_response(status, &body)
+            }
+        }
+    }
+
+    async fn route(
+        &self,
+        method: &Method,
+        path: &str,
+        body: Vec<u8>,
+    ) -> Result<Option<Response<Vec<u8>>>, Error> {
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+        let response = match (method, segments.as_slice()) {
+            (&Method::GET, ["v1", "keys"]) => ok(&self.mint.lock().await.pubkeys().await?),
+            (&Method::GET, ["v1", "keys", id]) => {
+                let id = Id::from_str(id).map_err(|_| Error::UnknownKeySet)?;
+                ok(&self.mint.lock().await.keyset_pubkeys(&id).await?)
+            }
+            (&Method::GET, ["v1", "keysets"]) => ok(&self.mint.lock().await.keysets().await?),
+            (&Method::GET, ["v1", "info"]) => ok(&self.mint.lock().await.mint_info().await?),
+            (&Method::POST, ["v1", "mint", "quote", "bolt11"]) => {
+                let request: MintQuoteBolt11Request = self.parse(body)?;
+                ok(&self.mint_quote(request).await?)
+            }
+            (&Method::GET, ["v1", "mint", "quote", "bolt11", quote_id]) => {
+                // The backend is asked without holding the lock
+                let mint = self.mint.lock().await.clone();
+                mint.sync_mint_quote(quote_id).await?;
+                ok(&mint.check_mint_quote(quote_id).await?)
+            }
+            (&Method::POST, ["v1", "mint", "quote", "bolt12"]) => {
+                let request: MintQuoteBolt12Request = self.parse(body)?;
+                ok(&self.mint_quote_bolt12(request).await?)
+            }
+            (&Method::GET, ["v1", "mint", "quote", "bolt12", quote_id]) => {
+                let mint = self.mint.lock().await.clone();
+                mint.sync_mint_quote(quote_id).await?;
+                ok(&mint.check_mint_quote_bolt12(quote_id).await?)
+            }
+            (&Method::POST, ["v1", "mint", "bolt11" | "bolt12"]) => {
+                let request: MintBolt11Request = self.parse(body)?;
+                let mint = self.mint.lock().await.clone();
+                mint.sync_mint_quote(&request.quote).await?;
+                let response = self.mint.lock().await.process_mint_request(request).await?;
+                ok(&response
+                    .to_json_vintage(self.vintage)
+                    .map_err(crate::nuts::nut00::Error::from)?)
+            }
+            (&Method::POST, ["v1", "melt", "quote", "bolt11"]) => {
+                let request: MeltQuoteBolt11Request = self.parse(body)?;
+                let mint = self.mint.lock().await;
+                let quote = mint
+                    .new_melt_quote_bolt11(&request, unix_time() + self.quote_ttl)
+                    .await?;
+                ok(&mint.check_melt_quote(&quote.id).await?)
+            }
+            (&Method::GET, ["v1", "melt", "quote", "bolt11", quote_id]) => {
+                ok(&self.mint.lock().await.check_melt_quote(quote_id).await?)
+            }
+            (&Method::POST, ["v1", "melt", "bolt11"]) => {
+                let request: MeltBolt11Request = self.parse(body)?;
+                ok(&self.melt(request).await?)
+            }
+            (&Method::POST, ["v1", "swap"]) => {
+                let request: SwapRequest = self.parse(body)?;
+                // Checked before the inputs are spent
+                request.check_split()?;
+                let form = SwapResponseForm::of_request(&request, self.vintage);
+                let response = self.mint.lock().await.process_swap_request(request).await?;
+                ok(&response.to_json_form(form)?)
+            }
+            (&Method::POST, ["checkfees"]) => {
+                let request: CheckFeesRequest = self.parse(body)?;
+                ok(&self.mint.lock().await.check_fees(&request)?)
+            }
+            (&Method::POST, ["v1", "checkstate"]) => {
+                let request: CheckStateRequest = self.parse(body)?;
+                ok(&self.mint.lock().await.check_state(&request).await?)
+            }
+            (&Method::POST, ["v1", "restore"]) => {
+                let request: RestoreRequest = self.parse(body)?;
+                ok(&self.mint.lock().await.restore(request).await?)
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(response))
+    }
+
+    fn parse<T>(&self, body: Vec<u8>) -> Result<T, Error>
+    where
+        T: crate::nuts::nut00::Request,
+    {
+        let json = String::from_utf8(body).map_err(crate::error::Error::from)?;
+
+        Ok(T::parse_request(&json, self.strictness)?)
+    }
+
+    async fn mint_quote(
+        &self,
+        request: MintQuoteBolt11Request,
+    ) -> Result<crate::nuts::MintQuoteBolt11Response, Error> {
+        let ln_backend = self.ln_backend().await?;
+        let expiry = unix_time() + self.quote_ttl;
+        let description = request.invoice_description()?;
+
+        let invoice = ln_backend
+            .create_invoice(request.amount, &request.unit, description.clone(), expiry)
+            .await?;
+
+        let mint = self.mint.lock().await;
+        let quote = mint
+            .new_mint_quote(
+                PaymentMethod::Bolt11,
+                invoice.to_string(),
+                request.unit,
+                request.amount,
+                expiry,
+                description,
+            )
+            .await?;
+
+        mint.check_mint_quote(&quote.id).await
+    }
+
+    async fn mint_quote_bolt12(
+        &self,
+        request: MintQuoteBolt12Request,
+    ) -> Result<crate::nuts::MintQuoteBolt12Response, Error> {
+        let ln_backend = self.ln_backend().await?;
+        let expiry = unix_time() + self.quote_ttl;
+
+        let offer = ln_backend
+            .create_offer(request.amount, &request.unit, request.description.clone())
+            .await?;
+
+        let mint = self.mint.lock().await;
+        let quote = mint
+            .new_mint_quote_bolt12(
+                offer,
+                request.unit,
+                request.amount,
+                expiry,
+                request.description,
+            )
+            .await?;
+
+        mint.check_mint_quote_bolt12(&quote.id).await
+    }
+
+    /// Settle `request` internally if it pays an invoice of this mint,
+    /// otherwise pay it over Lightning
+    ///
+    /// A payment that failed releases the inputs, a payment in an unknown
+    /// state leaves the melt pending.
+    async fn melt(
+        &self,
+        request: MeltBolt11Request,
+    ) -> Result<crate::nuts::MeltBolt11Response, Error> {
+        let (quote, ln_backend) = {
+            let mut mint = self.mint.lock().await;
+
+            if let Some(response) = mint.process_internal_melt_request(&request).await? {
+                return Ok(response);
+            }
+
+            let ln_backend = mint.ln_backend();
+            (mint.reserve_melt_request(&request).await?, ln_backend)
+        };
+
+        let payment = match (ln_backend, Bolt11Invoice::from_str(&quote.request)) {
+            (Some(ln_backend), Ok(bolt11)) => {
+                let amount_msat = quote.amount_msat.unwrap_or(quote.amount.to_msat());
+                ln_backend
+                    .pay_invoice(&bolt11, amount_msat, quote.fee_reserve)
+                    .await
+            }
+            (None, _) => Err(lightning::Error::Unsupported("pay_invoice")),
+            (_, Err(err)) => Err(lightning::Error::PaymentFailed(err.to_string())),
+        };
+
+        let mut mint = self.mint.lock().await;
+        match payment {
+            Ok(payment) => {
+                mint.process_melt_request_msat(
+                    &request,
+                    &payment.preimage,
+                    payment.total_spent_msat,
+                )
+                .await
+            }
+            Err(err @ lightning::Error::Backend(_)) => {
+                warn!(quote = %quote.id, "Melt payment state unknown: {err}");
+                Err(err.into())
+            }
+            Err(err) => {
+                mint.abort_melt(&quote.id).await?;
+                Err(err.into())
+            }
+        }
+    }
+
+    async fn ln_backend(&self) -> Result<Arc<dyn lightning::LnBackend>, Error> {
+        self.mint
+            .lock()
+            .await
+            .ln_backend()
+            .ok_or(lightning::Error::Unsupported("create_invoice").into())
+    }
+}
+
+fn ok<T>(body: &T) -> Response<Vec<u8>>
+where
+    T: Serialize,
+{
+    json_response(StatusCode::OK, body)
+}
+
+fn json_response<T>(status: StatusCode, body: &T) -> Response<Vec<u8>>
+where
+    T: Serialize,
+{
+    let (status, body) = match serde_json::to_vec(body) {
+        Ok(body) => (status, body),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, b"{}".to_vec()),
+    };
+
+    let mut response = Response::new(body);
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+    response
+}
+
+#[cfg(all(test, feature = "wallet"))]
+mod tests {
+    use std::collections::{BTreeMap, HashMap, HashSet};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    use async_trait::async_trait;
+    use bip39::Mnemonic;
+    use bitcoin::hashes::Hash as _;
+    use url::Url;
+
+    use super::*;
+    use crate::cdk_database::mint_memory::MintMemoryDatabase;
+    use crate::cdk_database::wallet_memory::WalletMemoryDatabase;
+    use crate::dhke::construct_proofs;
+    use crate::mint::{derivation_path_from_unit, MintKeySetInfo, RequestLimits};
+    use crate::nuts::nut00::MintProofs;
+    use crate::nuts::{
+        CurrencyUnit, MintInfo, MintQuoteBolt11Response, MintQuoteBolt12Response, P2PKConditions,
+        PreMintSecrets, Proofs, ProofsMethods, PublicKey, SecretKey, SigFlag, SigningKey, State,
+        Token,
+    };
+    use crate::test_utils::clock::ManualClock;
+    use crate::test_utils::fake_ln::{Call, FakeLnBackend, PaymentScript};
+    use crate::test_utils::interop::{LightningHook, Suite};
+    use crate::types::{
+        InvoiceDescription, TransactionDirection, TransactionFilter, TransactionKind,
+        TransactionStatus,
+    };
+    use crate::url::UncheckedUrl;
+    use crate::util::Clock;
+    use crate::wallet::{
+        ConsolidateOptions, ConsolidationReport, Error as WalletError, ImportReport,
+        MaintenancePolicy, MaintenanceReport, ReceiveOptions, ReceiveOutcome, TransferStatus,
+        UnclaimedReason, Wallet, WalletBackup, WALLET_BACKUP_VERSION,
+    };
+    use crate::{Amount, HttpClient, Sha256, SplitTarget};
+
+    const MNEMONIC: &str =
+        "half depart obvious quality work element tank gorilla view sugar picture humble";
+
+    /// Router of a mint whose backend pays invoices for a 1 sat fee
+    async fn router() -> (MintRouter, Arc<Mutex<Mint>>, Arc<FakeLnBackend>) {
+        let backend = Arc::new(FakeLnBackend::new());
+        backend.set_default_payment(PaymentScript::Pay { fee_msat: 1000 });
+        let (router, mint) = router_with(MNEMONIC, Arc::clone(&backend), HashSet::new()).await;
+        (router, mint, backend)
+    }
+
+    /// Router of a mint of `mnemonic` with `keysets_info` paying through
+    /// `backend`
+    async fn router_with(
+        mnemonic: &str,
+        backend: Arc<FakeLnBackend>,
+        keysets_info: HashSet<MintKeySetInfo>,
+    ) -> (MintRouter, Arc<Mutex<Mint>>) {
+        let localstore = MintMemoryDatabase::new(
+            MintInfo::default(),
+            HashMap::new(),
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            HashMap::new(),
+        )
+        .unwrap();
+
+        let mut mint = Mint::new(
+            Arc::new(localstore),
+            Mnemonic::from_str(mnemonic).unwrap(),
+            keysets_info,
+            Amount::from(2),
+            0.01,
+        )
+        .await
+        .unwrap();
+        mint.set_ln_backend(backend as Arc<dyn lightning::LnBackend>);
+
+        let mint = Arc::new(Mutex::new(mint));
+        (MintRouter::new(Arc::clone(&mint)), mint)
+    }
+
+    /// Serve `router` on a random port, one request per connection
+    fn serve(router: MintRouter) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        let runtime = tokio::runtime::Handle::current();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut parts = request_line.split_whitespace();
+                let method = parts.next().unwrap().to_string();
+                let uri = parts.next().unwrap().to_string();
+
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(length) = line.to_lowercase().strip_prefix("content-length:") {
+                        content_length = length.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+
+                let request = Request::builder()
+                    .method(method.as_str())
+                    .uri(uri)
+                    .body(body)
+                    .unwrap();
+                let response = runtime.block_on(router.handle(request));
+
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    response.status(),
+                    response.body().len()
+                )
+                .unwrap();
+                stream.write_all(response.body()).unwrap();
+            }
+        });
+
+        url
+    }
+
+    #[tokio::test]
+    async fn test_router_with_http_client() {
+        let (router, _mint, backend) = router().await;
+        let url = serve(router);
+        let client = HttpClient::new();
+
+        client.get_mint_info(url.clone()).await.unwrap();
+        let keysets = client.get_mint_keysets(url.clone()).await.unwrap();
+        let keyset_id = keysets.keysets[0].id;
+        let keys = client.get_mint_keys(url.clone()).await.unwrap();
+        assert_eq!(keys[0].id, keyset_id);
+        let keyset = client
+            .get_mint_keyset(url.clone(), keyset_id)
+            .await
+            .unwrap();
+
+        // Mint
+        let amount = Amount::from(12);
+        let quote = client
+            .post_mint_quote(url.clone(), amount, CurrencyUnit::Sat, None)
+            .await
+            .unwrap();
+        assert!(!quote.paid);
+        let request = Bolt11Invoice::from_str(&quote.request).unwrap();
+        backend.settle_invoice(*request.payment_hash());
+        let status = client
+            .get_mint_quote_status(url.clone(), &quote.quote)
+            .await
+            .unwrap();
+        assert!(status.paid);
+
+        let pre_mint = PreMintSecrets::random(keyset_id, amount).unwrap();
+        let mint_request = MintBolt11Request {
+            quote: quote.quote.clone(),
+            outputs: pre_mint.blinded_messages(),
+        };
+        let signatures = client
+            .post_mint(url.clone(), mint_request.clone())
+            .await
+            .unwrap()
+            .signatures;
+        let proofs =
+            construct_proofs(signatures, pre_mint.rs(), pre_mint.secrets(), &keyset.keys).unwrap();
+
+        assert!(matches!(
+            client.post_mint(url.clone(), mint_request).await,
+            Err(crate::client::Error::UnknownErrorResponse(ErrorResponse {
+                code: ErrorCode::BlindedMessageAlreadySigned,
+                ..
+            }))
+        ));
+
+        // Swap
+        let pre_swap = PreMintSecrets::random(keyset_id, amount).unwrap();
+        let swap_request = SwapRequest::new(proofs.clone(), pre_swap.blinded_messages());
+        let signatures = client
+            .post_swap(url.clone(), swap_request)
+            .await
+            .unwrap()
+            .signatures;
+        let swapped = construct_proofs(
+            signatures.clone(),
+            pre_swap.rs(),
+            pre_swap.secrets(),
+            &keyset.keys,
+        )
+        .unwrap();
+
+        let states = client
+            .post_check_state(url.clone(), proofs.ys().unwrap())
+            .await
+            .unwrap();
+        assert!(states
+            .states
+            .iter()
+            .all(|state| state.state == State::Spent));
+
+        let restored = client
+            .post_restore(
+                url.clone(),
+                RestoreRequest {
+                    outputs: pre_swap.blinded_messages(),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(restored.signatures, signatures);
+
+        // Melt a 10 sat invoice with a 2 sat fee reserve, 1 sat of fee
+        let invoice = backend.invoice(Some(10_000));
+        let preimage = backend.preimage(invoice.payment_hash());
+        let melt_quote = client
+            .post_melt_quote(url.clone(), CurrencyUnit::Sat, invoice, None)
+            .await
+            .unwrap();
+        assert_eq!(melt_quote.amount + melt_quote.fee_reserve, 12);
+
+        let change =
+            PreMintSecrets::blank(keyset_id, Amount::from(melt_quote.fee_reserve)).unwrap();
+        let response = client
+            .post_melt(
+                url.clone(),
+                MeltBolt11Request::new(
+                    melt_quote.quote,
+                    swapped.clone(),
+                    Some(change.blinded_messages()),
+                ),
+            )
+            .await
+            .unwrap();
+        assert!(response.paid);
+        assert_eq!(response.payment_preimage.as_deref(), preimage.as_deref());
+        let change: Amount = response.change.unwrap().iter().map(|s| s.amount).sum();
+        assert_eq!(change, Amount::from(1));
+
+        let states = client
+            .post_check_state(url, swapped.ys().unwrap())
+            .await
+            .unwrap();
+        assert!(states
+            .states
+            .iter()
+            .all(|state| state.state == State::Spent));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_claim_expired_p2pk_after_locktime() {
+        let (router, mint, backend) = router().await;
+        let clock = Arc::new(ManualClock::new(unix_time()));
+        mint.lock()
+            .await
+            .set_clock(Arc::clone(&clock) as Arc<dyn Clock>);
+        let url = serve(router);
+        let mint_url = UncheckedUrl::from(url.as_str().trim_end_matches('/'));
+        let client = HttpClient::new();
+
+        let mut wallet = Wallet::new(
+            client.clone(),
+            Arc::new(WalletMemoryDatabase::default()),
+            None,
+        )
+        .await;
+        wallet.set_clock(Arc::clone(&clock) as Arc<dyn Clock>);
+
+        let quote = wallet
+            .mint_quote(mint_url.clone(), Amount::from(16), CurrencyUnit::Sat, None)
+            .await
+            .unwrap();
+        let invoice = Bolt11Invoice::from_str(&quote.request).unwrap();
+        backend.settle_invoice(*invoice.payment_hash());
+        wallet.mint(mint_url.clone(), &quote.id).await.unwrap();
+
+        // Locked to the receiver, refundable to the sender after an hour
+        let sender = SigningKey::new(SecretKey::generate());
+        let receiver = SigningKey::new(SecretKey::generate());
+        let locktime = clock.now() + 3600;
+        let conditions = P2PKConditions::new(
+            Some(locktime),
+            vec![receiver.verifying_key()],
+            Some(vec![sender.verifying_key()]),
+            None,
+            None,
+        )
+        .unwrap();
+        let locked = wallet
+            .send_p2pk(&mint_url, &CurrencyUnit::Sat, Amount::from(10), conditions)
+            .await
+            .unwrap();
+        let token = Token::new(
+            mint_url.clone(),
+            locked.clone(),
+            None,
+            Some(CurrencyUnit::Sat),
+        )
+        .unwrap();
+
+        // Before the locktime nothing is claimed, and the mint refuses a
+        // refund signature
+        let claim = wallet
+            .claim_expired(&token, vec![sender.clone()])
+            .await
+            .unwrap();
+        assert!(claim.claimed.is_empty());
+        assert_eq!(claim.unclaimed.len(), locked.len());
+        assert!(claim
+            .unclaimed
+            .iter()
+            .all(|unclaimed| unclaimed.reason == UnclaimedReason::NotExpired { locktime }));
+
+        let mut refund = locked.clone();
+        for proof in &mut refund {
+            proof.sign_p2pk(sender.clone()).unwrap();
+        }
+        let keyset_id = client.get_mint_keysets(url.clone()).await.unwrap().keysets[0].id;
+        let outputs = PreMintSecrets::random(keyset_id, Amount::from(10)).unwrap();
+        assert!(client
+            .post_swap(
+                url.clone(),
+                SwapRequest::new(refund, outputs.blinded_messages())
+            )
+            .await
+            .is_err());
+
+        // After it only the refund key claims
+        clock.set(locktime + 1);
+        let claim = wallet.claim_expired(&token, vec![receiver]).await.unwrap();
+        assert!(claim.claimed.is_empty());
+        assert!(claim
+            .unclaimed
+            .iter()
+            .all(|unclaimed| unclaimed.reason == UnclaimedReason::NotOurRefundKey));
+
+        let claim = wallet.claim_expired(&token, vec![sender]).await.unwrap();
+        assert!(claim.unclaimed.is_empty());
+        assert_eq!(claim.claimed.total_amount().unwrap(), Amount::from(10));
+        assert!(claim.claimed.iter().all(|proof| !proof.secret.is_p2pk()));
+        assert_eq!(
+            wallet.mint_balances().await.unwrap()[&mint_url],
+            Amount::from(16)
+        );
+
+        let states = client
+            .post_check_state(url, locked.ys().unwrap())
+            .await
+            .unwrap();
+        assert!(states
+            .states
+            .iter()
+            .all(|state| state.state == State::Spent));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_maintain_consolidates_migrates_and_checks_pending() {
+        let (router, mint, backend) = router().await;
+        let url = serve(router);
+        let mint_url = UncheckedUrl::from(url.as_str().trim_end_matches('/'));
+        let mut wallet = Wallet::new(
+            HttpClient::new(),
+            Arc::new(WalletMemoryDatabase::default()),
+            None,
+        )
+        .await;
+
+        for amount in [1, 1, 1, 1, 8] {
+            let quote = wallet
+                .mint_quote(
+                    mint_url.clone(),
+                    Amount::from(amount),
+                    CurrencyUnit::Sat,
+                    None,
+                )
+                .await
+                .unwrap();
+            let invoice = Bolt11Invoice::from_str(&quote.request).unwrap();
+            backend.settle_invoice(*invoice.payment_hash());
+            wallet.mint(mint_url.clone(), &quote.id).await.unwrap();
+        }
+        let old_keyset = wallet.get_mint_keysets(&mint_url).await.unwrap()[0].id;
+
+        // 1 + 1 + 1 + 1 + 8 becomes 4 + 8
+        let policy = MaintenancePolicy {
+            consolidate_above: Some(3),
+            ..MaintenancePolicy::default()
+        };
+        let report = wallet.maintain(&policy).await.unwrap();
+        assert_eq!(
+            report,
+            MaintenanceReport {
+                consolidated: 5,
+                consolidated_into: 2,
+                ..MaintenanceReport::default()
+            }
+        );
+        let proofs = wallet.get_proofs(mint_url.clone()).await.unwrap().unwrap();
+        assert_eq!(proofs.len(), 2);
+        assert_eq!(proofs.total_amount().unwrap(), Amount::from(12));
+
+        // Nothing left to do
+        assert_eq!(
+            wallet.maintain(&policy).await.unwrap(),
+            MaintenanceReport::default()
+        );
+
+        let sent = wallet
+            .send(&mint_url, &CurrencyUnit::Sat, Amount::from(2), false)
+            .await
+            .unwrap();
+        let pending = wallet
+            .localstore
+            .get_pending_proofs(mint_url.clone())
+            .await
+            .unwrap()
+            .unwrap();
+        let kept = wallet.get_proofs(mint_url.clone()).await.unwrap().unwrap();
+
+        mint.lock()
+            .await
+            .rotate_keyset(
+                CurrencyUnit::Sat,
+                &derivation_path_from_unit(&CurrencyUnit::Sat, 1).unwrap(),
+                32,
+            )
+            .await
+            .unwrap();
+
+        let report = wallet.maintain(&policy).await.unwrap();
+        assert_eq!(
+            report,
+            MaintenanceReport {
+                migrated: kept.len(),
+                pending_spent: pending.len() - sent.len(),
+                still_pending: sent.len(),
+                ..MaintenanceReport::default()
+            }
+        );
+
+        // The unclaimed token is still pending, and no value was lost
+        let pending = wallet
+            .localstore
+            .get_pending_proofs(mint_url.clone())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(pending.ys().unwrap(), sent.ys().unwrap());
+        let proofs = wallet.get_proofs(mint_url.clone()).await.unwrap().unwrap();
+        assert!(proofs.iter().all(|proof| proof.keyset_id != old_keyset));
+        assert_eq!(proofs.total_amount().unwrap(), Amount::from(10));
+        assert_eq!(
+            proofs.total_amount().unwrap() + pending.total_amount().unwrap(),
+            Amount::from(12)
+        );
+
+        let states = HttpClient::new()
+            .post_check_state(url, kept.ys().unwrap())
+            .await
+            .unwrap();
+        assert!(states
+            .states
+            .iter()
+            .all(|state| state.state == State::Spent));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_wallet_transfer_between_mints() {
+        // Both mints pay through the same node, so each settles the other's
+        // invoices
+        let backend = Arc::new(FakeLnBackend::new());
+        backend.set_default_payment(PaymentScript::Pay { fee_msat: 1000 });
+        let (router_a, _) = router_with(MNEMONIC, Arc::clone(&backend), HashSet::new()).await;
+        let (router_b, _) = router_with(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+            Arc::clone(&backend),
+            HashSet::new(),
+        )
+        .await;
+        let mint_a = UncheckedUrl::from(serve(router_a).as_str().trim_end_matches('/'));
+        let mint_b = UncheckedUrl::from(serve(router_b).as_str().trim_end_matches('/'));
+
+        let mut wallet = Wallet::new(
+            HttpClient::new(),
+            Arc::new(WalletMemoryDatabase::default()),
+            None,
+        )
+        .await;
+        let quote = wallet
+            .mint_quote(mint_a.clone(), Amount::from(64), CurrencyUnit::Sat, None)
+            .await
+            .unwrap();
+        let invoice = Bolt11Invoice::from_str(&quote.request).unwrap();
+        backend.settle_invoice(*invoice.payment_hash());
+        wallet.mint(mint_a.clone(), &quote.id).await.unwrap();
+
+        // 1 sat routing fee, the rest of the fee reserve comes back as change
+        let result = wallet
+            .transfer(&mint_a, &mint_b, Amount::from(10))
+            .await
+            .unwrap();
+        assert_eq!(
+            result.status,
+            TransferStatus::Completed {
+                minted: Amount::from(10),
+                fee: Amount::from(1),
+            }
+        );
+        let balances = wallet.mint_balances().await.unwrap();
+        assert_eq!(balances[&mint_a], Amount::from(53));
+        assert_eq!(balances[&mint_b], Amount::from(10));
+        assert!(wallet
+            .localstore
+            .get_mint_quote(&result.mint_quote_id)
+            .await
+            .unwrap()
+            .is_none());
+
+        // A failed payment rolls back, the proofs at A stay spendable
+        backend.set_default_payment(PaymentScript::Fail("No route".to_string()));
+        let result = wallet
+            .transfer(&mint_a, &mint_b, Amount::from(10))
+            .await
+            .unwrap();
+        assert_eq!(result.status, TransferStatus::MeltFailed);
+        let balances = wallet.mint_balances().await.unwrap();
+        assert_eq!(balances[&mint_a], Amount::from(53));
+        assert_eq!(balances[&mint_b], Amount::from(10));
+        assert!(wallet
+            .localstore
+            .get_mint_quote(&result.mint_quote_id)
+            .await
+            .unwrap()
+            .is_none());
+        let melts = wallet
+            .transactions(&TransactionFilter {
+                kind: Some(TransactionKind::Melt),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let failed = melts
+            .iter()
+            .find(|t| t.quote_id.as_deref() == Some(result.melt_quote_id.as_str()))
+            .unwrap();
+        assert_eq!(failed.status, TransactionStatus::Failed);
+        assert_eq!(failed.fee, Amount::ZERO);
+
+        // The released proofs can be spent again
+        backend.set_default_payment(PaymentScript::Pay { fee_msat: 0 });
+        let result = wallet
+            .transfer(&mint_a, &mint_b, Amount::from(53 - 2))
+            .await
+            .unwrap();
+        assert!(matches!(result.status, TransferStatus::Completed { .. }));
+        assert_eq!(
+            wallet.mint_balances().await.unwrap()[&mint_b],
+            Amount::from(61)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_reclaim_reserved_melt_inputs() {
+        let (router, _mint, backend) = router().await;
+        let url = serve(router);
+        let mint_url = UncheckedUrl::from(url.as_str().trim_end_matches('/'));
+        let new_wallet = || {
+            Wallet::new(
+                HttpClient::new(),
+                Arc::new(WalletMemoryDatabase::default()),
+                None,
+            )
+        };
+        let mut alice = new_wallet().await;
+        let mut bob = new_wallet().await;
+
+        let quote = alice
+            .mint_quote(mint_url.clone(), Amount::from(255), CurrencyUnit::Sat, None)
+            .await
+            .unwrap();
+        let invoice = Bolt11Invoice::from_str(&quote.request).unwrap();
+        backend.settle_invoice(*invoice.payment_hash());
+        alice.mint(mint_url.clone(), &quote.id).await.unwrap();
+
+        let reserved_total = |wallet: &Wallet| {
+            let localstore = Arc::clone(&wallet.localstore);
+            let mint_url = mint_url.clone();
+            async move {
+                localstore
+                    .get_reserved_proofs(mint_url)
+                    .await
+                    .unwrap()
+                    .unwrap_or_default()
+                    .total_amount()
+                    .unwrap()
+            }
+        };
+
+        // The payment is refused, the mint releases the inputs
+        let invoice = backend.invoice(Some(10_000));
+        backend.script_payment(
+            *invoice.payment_hash(),
+            PaymentScript::Fail("No route".to_string()),
+        );
+        let quote = alice
+            .melt_quote(
+                mint_url.clone(),
+                CurrencyUnit::Sat,
+                invoice.to_string(),
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(alice.melt(&mint_url, &quote.id).await.is_err());
+        let failed = reserved_total(&alice).await;
+        assert!(failed >= Amount::from(12));
+
+        // The node does not know how the payment went, the inputs stay pending
+        let invoice = backend.invoice(Some(20_000));
+        backend.script_payment(
+            *invoice.payment_hash(),
+            PaymentScript::Unknown("Node timed out".to_string()),
+        );
+        let quote = alice
+            .melt_quote(
+                mint_url.clone(),
+                CurrencyUnit::Sat,
+                invoice.to_string(),
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(alice.melt(&mint_url, &quote.id).await.is_err());
+        let in_flight = reserved_total(&alice).await - failed;
+        assert!(in_flight >= Amount::from(22));
+
+        // Proofs the mint spent without the wallet seeing the response
+        let sent = alice
+            .send(&mint_url, &CurrencyUnit::Sat, Amount::from(8), false)
+            .await
+            .unwrap();
+        let token = alice
+            .proofs_to_token(
+                mint_url.clone(),
+                sent.clone(),
+                None,
+                Some(CurrencyUnit::Sat),
+            )
+            .unwrap();
+        bob.receive(&token, ReceiveOptions::default())
+            .await
+            .unwrap();
+        alice
+            .localstore
+            .remove_pending_proofs(mint_url.clone(), &sent)
+            .await
+            .unwrap();
+        alice
+            .localstore
+            .add_reserved_proofs(mint_url.clone(), sent)
+            .await
+            .unwrap();
+
+        let spendable = alice.mint_balances().await.unwrap()[&mint_url];
+        let report = alice.reclaim_reserved().await.unwrap();
+        assert_eq!(report.reclaimed, failed);
+        assert_eq!(report.spent, Amount::from(8));
+        assert_eq!(report.pending.len(), 1);
+        let pending = &report.pending[&mint_url];
+        assert_eq!(pending.total_amount().unwrap(), in_flight);
+
+        assert_eq!(
+            alice.mint_balances().await.unwrap()[&mint_url],
+            spendable + failed
+        );
+        assert_eq!(reserved_total(&alice).await, in_flight);
+
+        // Nothing changed at the mint, a second pass reports the same
+        let again = alice.reclaim_reserved().await.unwrap();
+        assert_eq!(again.reclaimed, Amount::ZERO);
+        assert_eq!(again.spent, Amount::ZERO);
+        assert_eq!(again.pending, report.pending);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_send_with_random_split() {
+        let (router, _mint, backend) = router().await;
+        let url = serve(router);
+        let mint_url = UncheckedUrl::from(url.as_str().trim_end_matches('/'));
+        let new_wallet = || {
+            Wallet::new(
+                HttpClient::new(),
+                Arc::new(WalletMemoryDatabase::default()),
+                None,
+            )
+        };
+        let mut alice = new_wallet().await;
+        let mut bob = new_wallet().await;
+
+        let quote = alice
+            .mint_quote(mint_url.clone(), Amount::from(255), CurrencyUnit::Sat, None)
+            .await
+            .unwrap();
+        let invoice = Bolt11Invoice::from_str(&quote.request).unwrap();
+        backend.settle_invoice(*invoice.payment_hash());
+        alice.mint(mint_url.clone(), &quote.id).await.unwrap();
+
+        let target = SplitTarget::Random { seed: Some(3) };
+        let sent = alice
+            .send_with_target(
+                &mint_url,
+                &CurrencyUnit::Sat,
+                Amount::from(100),
+                false,
+                &target,
+            )
+            .await
+            .unwrap();
+        let mut amounts: Vec<Amount> = sent.iter().map(|p| p.amount).collect();
+        amounts.sort_unstable_by(|a, b| b.cmp(a));
+        assert_eq!(amounts, Amount::from(100).split_targeted(&target));
+        assert_eq!(
+            alice.mint_balances().await.unwrap()[&mint_url],
+            Amount::from(155)
+        );
+
+        let token = alice
+            .proofs_to_token(mint_url.clone(), sent, None, Some(CurrencyUnit::Sat))
+            .unwrap();
+        bob.receive(&token, ReceiveOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            bob.mint_balances().await.unwrap()[&mint_url],
+            Amount::from(100)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_receive_with_options() {
+        let (router, _mint, backend) = router().await;
+        let url = serve(router);
+        let mint_url = UncheckedUrl::from(url.as_str().trim_end_matches('/'));
+        let new_wallet = || {
+            Wallet::new(
+                HttpClient::new(),
+                Arc::new(WalletMemoryDatabase::default()),
+                None,
+            )
+        };
+        let mut alice = new_wallet().await;
+        let mut bob = new_wallet().await;
+
+        let quote = alice
+            .mint_quote(mint_url.clone(), Amount::from(255), CurrencyUnit::Sat, None)
+            .await
+            .unwrap();
+        let invoice = Bolt11Invoice::from_str(&quote.request).unwrap();
+        backend.settle_invoice(*invoice.payment_hash());
+        alice.mint(mint_url.clone(), &quote.id).await.unwrap();
+        let denominations = |wallet: &Wallet| {
+            let wallet = wallet.clone();
+            let mint_url = mint_url.clone();
+            async move {
+                wallet.inventory().await.unwrap()[&(mint_url, CurrencyUnit::Sat)]
+                    .denominations
+                    .clone()
+            }
+        };
+
+        // Plain token received as 1 sat proofs
+        let sent = alice
+            .send(&mint_url, &CurrencyUnit::Sat, Amount::from(10), false)
+            .await
+            .unwrap();
+        let token = alice
+            .proofs_to_token(mint_url.clone(), sent, None, Some(CurrencyUnit::Sat))
+            .unwrap();
+        let options = ReceiveOptions {
+            split_target: SplitTarget::Value(Amount::from(1)),
+            ..Default::default()
+        };
+        bob.receive(&token, options).await.unwrap();
+        assert_eq!(
+            denominations(&bob).await,
+            BTreeMap::from([(Amount::from(1), 10)])
+        );
+
+        // Locked tokens are unlocked with the key of the options, SIG_ALL
+        // ones have their outputs signed too
+        let bob_key = SigningKey::new(SecretKey::generate());
+        for sig_flag in [SigFlag::SigInputs, SigFlag::SigAll] {
+            let conditions = P2PKConditions::new(
+                None,
+                vec![bob_key.verifying_key()],
+                None,
+                None,
+                Some(sig_flag),
+            )
+            .unwrap();
+            let locked = alice
+                .send_p2pk(&mint_url, &CurrencyUnit::Sat, Amount::from(8), conditions)
+                .await
+                .unwrap();
+            let token = alice
+                .proofs_to_token(mint_url.clone(), locked, None, Some(CurrencyUnit::Sat))
+                .unwrap();
+
+            assert!(bob
+                .receive(&token, ReceiveOptions::default())
+                .await
+                .is_err());
+            let options = ReceiveOptions {
+                split_target: SplitTarget::Value(Amount::from(2)),
+                p2pk_signing_keys: vec![bob_key.clone()],
+            };
+            bob.receive(&token, options).await.unwrap();
+        }
+        assert_eq!(
+            denominations(&bob).await,
+            BTreeMap::from([(Amount::from(1), 10), (Amount::from(2), 8)])
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_receive_token_once() {
+        let (router, _mint, backend) = router().await;
+        let url = serve(router);
+        let mint_url = UncheckedUrl::from(url.as_str().trim_end_matches('/'));
+        let new_wallet = || {
+            Wallet::new(
+                HttpClient::new(),
+                Arc::new(WalletMemoryDatabase::default()),
+                None,
+            )
+        };
+        let mut alice = new_wallet().await;
+        let mut bob = new_wallet().await;
+
+        let quote = alice
+            .mint_quote(mint_url.clone(), Amount::from(100), CurrencyUnit::Sat, None)
+            .await
+            .unwrap();
+        let invoice = Bolt11Invoice::from_str(&quote.request).unwrap();
+        backend.settle_invoice(*invoice.payment_hash());
+        alice.mint(mint_url.clone(), &quote.id).await.unwrap();
+        let balance = |wallet: &Wallet| {
+            let wallet = wallet.clone();
+            let mint_url = mint_url.clone();
+            async move {
+                wallet
+                    .mint_balances()
+                    .await
+                    .unwrap()
+                    .get(&mint_url)
+                    .copied()
+                    .unwrap_or_default()
+            }
+        };
+        let send = |amount: u64| {
+            let mut alice = alice.clone();
+            let mint_url = mint_url.clone();
+            async move {
+                alice
+                    .send(&mint_url, &CurrencyUnit::Sat, Amount::from(amount), false)
+                    .await
+                    .unwrap()
+            }
+        };
+
+        // The same token twice, and the same proofs in another encoding
+        let sent = send(10).await;
+        let token = alice
+            .proofs_to_token(
+                mint_url.clone(),
+                sent.clone(),
+                None,
+                Some(CurrencyUnit::Sat),
+            )
+            .unwrap();
+        assert_eq!(
+            bob.receive(&token, ReceiveOptions::default())
+                .await
+                .unwrap(),
+            ReceiveOutcome::Received {
+                amount: Amount::from(10)
+            }
+        );
+        let timestamp = bob.localstore.get_transactions().await.unwrap()[0].timestamp;
+        let with_memo = alice
+            .proofs_to_token(
+                mint_url.clone(),
+                sent.clone(),
+                Some("again".to_string()),
+                Some(CurrencyUnit::Sat),
+            )
+            .unwrap();
+        for token in [&token, &with_memo] {
+            assert_eq!(
+                bob.receive(token, ReceiveOptions::default()).await.unwrap(),
+                ReceiveOutcome::AlreadyReceived {
+                    amount: Amount::from(10),
+                    timestamp
+                }
+            );
+        }
+        assert_eq!(balance(&bob).await, Amount::from(10));
+
+        // Two receives of one token at once swap it once
+        let token = alice
+            .proofs_to_token(
+                mint_url.clone(),
+                send(20).await,
+                None,
+                Some(CurrencyUnit::Sat),
+            )
+            .unwrap();
+        let mut other_device = bob.clone();
+        let (first, second) = tokio::join!(
+            bob.receive(&token, ReceiveOptions::default()),
+            other_device.receive(&token, ReceiveOptions::default())
+        );
+        let mut outcomes = [first.unwrap(), second.unwrap()];
+        outcomes.sort_by_key(|outcome| matches!(outcome, ReceiveOutcome::AlreadyReceived { .. }));
+        assert_eq!(
+            outcomes[0],
+            ReceiveOutcome::Received {
+                amount: Amount::from(20)
+            }
+        );
+        assert!(matches!(
+            outcomes[1],
+            ReceiveOutcome::AlreadyReceived { amount, .. } if amount == Amount::from(20)
+        ));
+        assert_eq!(balance(&bob).await, Amount::from(30));
+
+        // A token sharing one proof with a received one names that proof and
+        // leaves its other proofs receivable
+        let fresh = send(4).await;
+        let mut overlapping = fresh.clone();
+        overlapping.push(sent[0].clone());
+        let token = alice
+            .proofs_to_token(mint_url.clone(), overlapping, None, Some(CurrencyUnit::Sat))
+            .unwrap();
+        let reused = vec![sent[0].y().unwrap()];
+        assert!(matches!(
+            bob.receive(&token, ReceiveOptions::default()).await,
+            Err(WalletError::ProofsAlreadyReceived(ys)) if ys == reused
+        ));
+        assert_eq!(balance(&bob).await, Amount::from(30));
+
+        let token = alice
+            .proofs_to_token(mint_url.clone(), fresh, None, Some(CurrencyUnit::Sat))
+            .unwrap();
+        assert_eq!(
+            bob.receive(&token, ReceiveOptions::default())
+                .await
+                .unwrap(),
+            ReceiveOutcome::Received {
+                amount: Amount::from(4)
+            }
+        );
+        assert_eq!(balance(&bob).await, Amount::from(34));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_receive_multi_mint_token_partly() {
+        let backend = Arc::new(FakeLnBackend::new());
+        let (router_a, _) = router_with(MNEMONIC, Arc::clone(&backend), HashSet::new()).await;
+        let (router_b, _) = router_with(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+            Arc::clone(&backend),
+            HashSet::new(),
+        )
+        .await;
+        let mint_a = UncheckedUrl::from(serve(router_a).as_str().trim_end_matches('/'));
+        let mint_b = UncheckedUrl::from(serve(router_b).as_str().trim_end_matches('/'));
+        let new_wallet = || {
+            Wallet::new(
+                HttpClient::new(),
+                Arc::new(WalletMemoryDatabase::default()),
+                None,
+            )
+        };
+        let mut alice = new_wallet().await;
+        let mut bob = new_wallet().await;
+        let mut carol = new_wallet().await;
+
+        for mint_url in [&mint_a, &mint_b] {
+            let quote = alice
+                .mint_quote(mint_url.clone(), Amount::from(16), CurrencyUnit::Sat, None)
+                .await
+                .unwrap();
+            let invoice = Bolt11Invoice::from_str(&quote.request).unwrap();
+            backend.settle_invoice(*invoice.payment_hash());
+            alice.mint(mint_url.clone(), &quote.id).await.unwrap();
+        }
+        let sent_a = alice
+            .send(&mint_a, &CurrencyUnit::Sat, Amount::from(10), false)
+            .await
+            .unwrap();
+        let sent_b = alice
+            .send(&mint_b, &CurrencyUnit::Sat, Amount::from(5), false)
+            .await
+            .unwrap();
+        let token_b = alice
+            .proofs_to_token(
+                mint_b.clone(),
+                sent_b.clone(),
+                None,
+                Some(CurrencyUnit::Sat),
+            )
+            .unwrap();
+
+        // Carol spends the part of mint B first
+        carol
+            .receive(&token_b, ReceiveOptions::default())
+            .await
+            .unwrap();
+
+        let token = Token {
+            token: vec![
+                MintProofs::new(mint_a.clone(), sent_a.clone()),
+                MintProofs::new(mint_b.clone(), sent_b),
+            ],
+            memo: None,
+            unit: Some(CurrencyUnit::Sat),
+        }
+        .to_string();
+        assert!(bob
+            .receive(&token, ReceiveOptions::default())
+            .await
+            .is_err());
+        assert_eq!(
+            bob.mint_balances().await.unwrap()[&mint_a],
+            Amount::from(10)
+        );
+
+        // The part of mint A stays received, the part of mint B does not
+        let mut received = sent_a.ys().unwrap();
+        received.sort_unstable();
+        assert!(matches!(
+            bob.receive(&token, ReceiveOptions::default()).await,
+            Err(WalletError::ProofsAlreadyReceived(ys)) if ys == received
+        ));
+        let outcome = bob.receive(&token_b, ReceiveOptions::default()).await;
+        assert!(outcome.is_err());
+        assert!(!matches!(
+            outcome,
+            Err(WalletError::ProofsAlreadyReceived(_))
+        ));
+        assert_eq!(bob.mint_balances().await.unwrap().get(&mint_b), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_send_many() {
+        let (router, _mint, backend) = router().await;
+        let url = serve(router);
+        let mint_url = UncheckedUrl::from(url.as_str().trim_end_matches('/'));
+        let new_wallet = || {
+            Wallet::new(
+                HttpClient::new(),
+                Arc::new(WalletMemoryDatabase::default()),
+                None,
+            )
+        };
+        let mut alice = new_wallet().await;
+        let mut bob = new_wallet().await;
+
+        let quote = alice
+            .mint_quote(mint_url.clone(), Amount::from(255), CurrencyUnit::Sat, None)
+            .await
+            .unwrap();
+        let invoice = Bolt11Invoice::from_str(&quote.request).unwrap();
+        backend.settle_invoice(*invoice.payment_hash());
+        alice.mint(mint_url.clone(), &quote.id).await.unwrap();
+        let token_amount = |token: &Token| -> Amount {
+            token.token[0].proofs.iter().map(|proof| proof.amount).sum()
+        };
+
+        // Amounts of held denominations are sent without a swap
+        let held: HashSet<PublicKey> = alice
+            .get_proofs(mint_url.clone())
+            .await
+            .unwrap()
+            .unwrap()
+            .ys()
+            .unwrap()
+            .into_iter()
+            .collect();
+        let tokens = alice
+            .send_many(
+                &mint_url,
+                &CurrencyUnit::Sat,
+                &[Amount::from(128), Amount::from(3)],
+                false,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            tokens.iter().map(token_amount).collect::<Vec<_>>(),
+            vec![Amount::from(128), Amount::from(3)]
+        );
+        assert!(tokens
+            .iter()
+            .flat_map(|token| token.token[0].proofs.ys().unwrap())
+            .all(|y| held.contains(&y)));
+        let inventory = &alice.inventory().await.unwrap()[&(mint_url.clone(), CurrencyUnit::Sat)];
+        assert_eq!(inventory.spendable, Amount::from(124));
+        assert_eq!(inventory.pending, Amount::from(131));
+
+        // Duplicates get proofs of their own, the held ones go to the first
+        let amounts = [Amount::from(50), Amount::from(50), Amount::from(20)];
+        let tokens = alice
+            .send_many(&mint_url, &CurrencyUnit::Sat, &amounts, true)
+            .await
+            .unwrap();
+        assert_eq!(
+            tokens.iter().map(token_amount).collect::<Vec<_>>(),
+            amounts.to_vec()
+        );
+        let ys: Vec<PublicKey> = tokens
+            .iter()
+            .flat_map(|token| token.token[0].proofs.ys().unwrap())
+            .collect();
+        assert_eq!(ys.len(), ys.iter().collect::<HashSet<_>>().len());
+        assert_eq!(
+            alice.balances().await.unwrap()[&(mint_url.clone(), CurrencyUnit::Sat)],
+            Amount::from(4)
+        );
+
+        for token in tokens {
+            bob.receive(&token.to_string(), ReceiveOptions::default())
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            bob.mint_balances().await.unwrap()[&mint_url],
+            Amount::from(120)
+        );
+
+        assert!(matches!(
+            alice
+                .send_many(&mint_url, &CurrencyUnit::Sat, &[Amount::from(5)], false)
+                .await,
+            Err(WalletError::Shortfall { needed, available })
+                if needed == Amount::from(5) && available == Amount::from(4)
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_consolidate_dust() {
+        let backend = Arc::new(FakeLnBackend::new());
+        let keyset_info = MintKeySetInfo {
+            id: Id::from_str("009a1f293253e41e").unwrap(),
+            unit: CurrencyUnit::Sat,
+            active: true,
+            valid_from: 0,
+            valid_to: None,
+            derivation_path: derivation_path_from_unit(&CurrencyUnit::Sat, 0).unwrap(),
+            max_order: 32,
+            amounts: None,
+            legacy_id: None,
+            input_fee_ppk: 10,
+        };
+        let (router, mint) =
+            router_with(MNEMONIC, Arc::clone(&backend), HashSet::from([keyset_info])).await;
+        mint.lock().await.set_request_limits(RequestLimits {
+            max_inputs: 100,
+            ..RequestLimits::default()
+        });
+        let url = serve(router);
+        let mint_url = UncheckedUrl::from(url.as_str().trim_end_matches('/'));
+        let new_wallet = || {
+            Wallet::new(
+                HttpClient::new(),
+                Arc::new(WalletMemoryDatabase::default()),
+                None,
+            )
+        };
+        let mut minter = new_wallet().await;
+        let mut wallet = new_wallet().await;
+
+        // 301 sat received as 300 one sat proofs, 1 sat paying the fee
+        let quote = minter
+            .mint_quote(mint_url.clone(), Amount::from(301), CurrencyUnit::Sat, None)
+            .await
+            .unwrap();
+        let invoice = Bolt11Invoice::from_str(&quote.request).unwrap();
+        backend.settle_invoice(*invoice.payment_hash());
+        minter.mint(mint_url.clone(), &quote.id).await.unwrap();
+        let minted = minter.get_proofs(mint_url.clone()).await.unwrap().unwrap();
+        let token = minter
+            .proofs_to_token(mint_url.clone(), minted, None, Some(CurrencyUnit::Sat))
+            .unwrap();
+        let options = ReceiveOptions {
+            split_target: SplitTarget::Value(Amount::from(1)),
+            ..Default::default()
+        };
+        wallet.receive(&token, options).await.unwrap();
+        let before = wallet.get_proofs(mint_url.clone()).await.unwrap().unwrap();
+        assert_eq!(before.len(), 300);
+        assert!(before.iter().all(|proof| proof.amount == Amount::from(1)));
+
+        // Swaps of as many proofs as the mint takes pay 1 sat each for 99 sat
+        // in 4 proofs, the budget stops the third
+        let mut options = ConsolidateOptions {
+            max_fee: Amount::from(2),
+            max_inputs: 100,
+        };
+        let report = wallet.consolidate_with_options(&options).await.unwrap();
+        assert_eq!(
+            report,
+            ConsolidationReport {
+                consolidated: 200,
+                consolidated_into: 8,
+                swaps: 2,
+                fees: Amount::from(2),
+                budget_exhausted: true,
+                ..ConsolidationReport::default()
+            }
+        );
+        let after = wallet.get_proofs(mint_url.clone()).await.unwrap().unwrap();
+        assert_eq!(after.len(), 108);
+        assert_eq!(after.total_amount().unwrap(), Amount::from(298));
+
+        // As if the run had been interrupted before removing its inputs
+        let after_ys: HashSet<PublicKey> = after.iter().map(|p| p.y().unwrap()).collect();
+        let spent: Proofs = before
+            .into_iter()
+            .filter(|proof| !after_ys.contains(&proof.y().unwrap()))
+            .collect();
+        assert_eq!(spent.len(), 200);
+        wallet
+            .localstore
+            .add_proofs(mint_url.clone(), spent)
+            .await
+            .unwrap();
+
+        // Run again, the spent proofs are forgotten and the rest
+        // consolidated down to one proof per denomination
+        options.max_fee = Amount::from(100);
+        let report = wallet.consolidate_with_options(&options).await.unwrap();
+        assert_eq!(report.already_spent, 200);
+        assert!(!report.budget_exhausted);
+        assert!(report.consolidated_into < report.consolidated);
+        let proofs = wallet.get_proofs(mint_url.clone()).await.unwrap().unwrap();
+        let balance = proofs.total_amount().unwrap();
+        assert_eq!(balance, Amount::from(298) - report.fees);
+        assert_eq!(balance, Amount::from(300) - Amount::from(2) - report.fees);
+        assert_eq!(proofs.len(), balance.split().len());
+
+        // Nothing left to do
+        assert_eq!(
+            wallet.consolidate(Amount::from(100)).await.unwrap(),
+            ConsolidationReport::default()
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_send_many_short_after_fees() {
+        let backend = Arc::new(FakeLnBackend::new());
+        let keyset_info = MintKeySetInfo {
+            id: Id::from_str("009a1f293253e41e").unwrap(),
+            unit: CurrencyUnit::Sat,
+            active: true,
+            valid_from: 0,
+            valid_to: None,
+            derivation_path: derivation_path_from_unit(&CurrencyUnit::Sat, 0).unwrap(),
+            max_order: 32,
+            amounts: None,
+            legacy_id: None,
+            input_fee_ppk: 400,
+        };
+        let (router, _mint) =
+            router_with(MNEMONIC, Arc::clone(&backend), HashSet::from([keyset_info])).await;
+        let url = serve(router);
+        let mint_url = UncheckedUrl::from(url.as_str().trim_end_matches('/'));
+        let mut alice = Wallet::new(
+            HttpClient::new(),
+            Arc::new(WalletMemoryDatabase::default()),
+            None,
+        )
+        .await;
+
+        let quote = alice
+            .mint_quote(mint_url.clone(), Amount::from(255), CurrencyUnit::Sat, None)
+            .await
+            .unwrap();
+        let invoice = Bolt11Invoice::from_str(&quote.request).unwrap();
+        backend.settle_invoice(*invoice.payment_hash());
+        alice.mint(mint_url.clone(), &quote.id).await.unwrap();
+
+        // The last two amounts need a swap of every proof left, whose fee
+        // the balance does not cover
+        let amounts = [Amount::from(100), Amount::from(100), Amount::from(55)];
+        match alice
+            .send_many(&mint_url, &CurrencyUnit::Sat, &amounts, false)
+            .await
+        {
+            Err(WalletError::Shortfall { needed, available }) => {
+                assert!(needed > Amount::from(255));
+                assert_eq!(available, Amount::from(255));
+            }
+            other => panic!("Expected a shortfall, got {other:?}"),
+        }
+        let inventory = &alice.inventory().await.unwrap()[&(mint_url.clone(), CurrencyUnit::Sat)];
+        assert_eq!(inventory.spendable, Amount::from(255));
+        assert_eq!(inventory.pending_proofs, 0);
+
+        // Leaving room for the fee the tokens are made
+        let amounts = [Amount::from(100), Amount::from(100), Amount::from(50)];
+        let tokens = alice
+            .send_many(&mint_url, &CurrencyUnit::Sat, &amounts, false)
+            .await
+            .unwrap();
+        assert_eq!(tokens.len(), 3);
+        let balance = alice.balances().await.unwrap()[&(mint_url.clone(), CurrencyUnit::Sat)];
+        assert!(balance < Amount::from(5));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_export_import_round_trip() {
+        let (router, _mint, backend) = router().await;
+        let url = serve(router);
+        let mint_url = UncheckedUrl::from(url.as_str().trim_end_matches('/'));
+        let mnemonic = Mnemonic::from_str(
+            "half depart obvious quality work element tank gorilla view sugar picture humble",
+        )
+        .unwrap();
+        let mut alice = Wallet::new(
+            HttpClient::new(),
+            Arc::new(WalletMemoryDatabase::default()),
+            Some(mnemonic),
+        )
+        .await;
+        let mut carol = Wallet::new(
+            HttpClient::new(),
+            Arc::new(WalletMemoryDatabase::default()),
+            None,
+        )
+        .await;
+
+        let quote = alice
+            .mint_quote(mint_url.clone(), Amount::from(255), CurrencyUnit::Sat, None)
+            .await
+            .unwrap();
+        let invoice = Bolt11Invoice::from_str(&quote.request).unwrap();
+        backend.settle_invoice(*invoice.payment_hash());
+        alice.mint(mint_url.clone(), &quote.id).await.unwrap();
+        let sent = alice
+            .send(&mint_url, &CurrencyUnit::Sat, Amount::from(100), true)
+            .await
+            .unwrap();
+
+        let backup = alice.export().await.unwrap();
+        assert_eq!(backup.version, WALLET_BACKUP_VERSION);
+        assert_eq!(backup.mints.len(), 1);
+        assert!(backup.mints[0]
+            .proofs
+            .iter()
+            .chain(&backup.mints[0].pending)
+            .all(|proof| proof.dleq.is_some()));
+        let keyset_id = backup.mints[0].proofs[0].keyset_id;
+        assert_eq!(
+            backup.counters.get(&keyset_id).copied(),
+            alice.keyset_counter(&keyset_id).await.unwrap()
+        );
+
+        let json = serde_json::to_string(&backup).unwrap();
+        let backup: WalletBackup = serde_json::from_str(&json).unwrap();
+        // Inputs of the send swap are pending with alice until she checks them
+        let swapped = backup.mints[0].pending.len() - sent.len();
+
+        let report = carol.import(backup, true).await.unwrap();
+        assert_eq!(
+            report,
+            ImportReport {
+                imported: Amount::from(155),
+                pending: Amount::from(100),
+                duplicates: 0,
+                spent: swapped,
+            }
+        );
+        assert_eq!(
+            carol.balances().await.unwrap(),
+            alice.balances().await.unwrap()
+        );
+        assert_eq!(
+            carol.keyset_counter(&keyset_id).await.unwrap(),
+            alice.keyset_counter(&keyset_id).await.unwrap()
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_import_overlapping_backup() {
+        let (router, _mint, backend) = router().await;
+        let url = serve(router);
+        let mint_url = UncheckedUrl::from(url.as_str().trim_end_matches('/'));
+        let new_wallet = || {
+            Wallet::new(
+                HttpClient::new(),
+                Arc::new(WalletMemoryDatabase::default()),
+                None,
+            )
+        };
+        let mut alice = new_wallet().await;
+        let mut bob = new_wallet().await;
+        let mut carol = new_wallet().await;
+
+        let quote = alice
+            .mint_quote(mint_url.clone(), Amount::from(255), CurrencyUnit::Sat, None)
+            .await
+            .unwrap();
+        let invoice = Bolt11Invoice::from_str(&quote.request).unwrap();
+        backend.settle_invoice(*invoice.payment_hash());
+        alice.mint(mint_url.clone(), &quote.id).await.unwrap();
+
+        let first = alice.export().await.unwrap();
+        let report = carol.import(first.clone(), false).await.unwrap();
+        assert_eq!(report.imported, Amount::from(255));
+
+        // Importing the same backup again adds nothing
+        let report = carol.import(first.clone(), true).await.unwrap();
+        assert_eq!(
+            report,
+            ImportReport {
+                duplicates: first.mints[0].proofs.len(),
+                ..Default::default()
+            }
+        );
+
+        let sent = alice
+            .send(&mint_url, &CurrencyUnit::Sat, Amount::from(100), false)
+            .await
+            .unwrap();
+        let token = alice
+            .proofs_to_token(
+                mint_url.clone(),
+                sent.clone(),
+                None,
+                Some(CurrencyUnit::Sat),
+            )
+            .unwrap();
+        bob.receive(&token, ReceiveOptions::default())
+            .await
+            .unwrap();
+
+        let second = alice.export().await.unwrap();
+        let first_ys: HashSet<PublicKey> =
+            first.mints[0].proofs.ys().unwrap().into_iter().collect();
+        let (held, new): (Proofs, Proofs) = second.mints[0]
+            .proofs
+            .iter()
+            .chain(&second.mints[0].pending)
+            .cloned()
+            .partition(|proof| first_ys.contains(&proof.y().unwrap()));
+        // What alice got back from the send swap, the sent proofs are spent
+        let sent_ys = sent.ys().unwrap();
+        let kept: Amount = new
+            .iter()
+            .filter(|proof| !sent_ys.contains(&proof.y().unwrap()))
+            .map(|proof| proof.amount)
+            .sum();
+        assert!(!held.is_empty() && kept > Amount::ZERO);
+
+        let report = carol.import(second.clone(), true).await.unwrap();
+        assert_eq!(
+            report,
+            ImportReport {
+                imported: kept,
+                pending: Amount::ZERO,
+                duplicates: held.len(),
+                spent: sent.len(),
+            }
+        );
+
+        let mut newer = second.clone();
+        newer.version = WALLET_BACKUP_VERSION + 1;
+        assert!(matches!(
+            carol.import(newer, false).await,
+            Err(WalletError::UnsupportedBackupVersion(_))
+        ));
+
+        let mut doubled = second;
+        let proof = doubled.mints[0].proofs[0].clone();
+        doubled.mints[0].proofs.push(proof);
+        assert!(matches!(
+            carol.import(doubled, false).await,
+            Err(WalletError::InvalidBackup(_))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_fee_estimates_match_mint_charges() {
+        let backend = Arc::new(FakeLnBackend::new());
+        let keyset_info = MintKeySetInfo {
+            id: Id::from_str("009a1f293253e41e").unwrap(),
+            unit: CurrencyUnit::Sat,
+            active: true,
+            valid_from: 0,
+            valid_to: None,
+            derivation_path: derivation_path_from_unit(&CurrencyUnit::Sat, 0).unwrap(),
+            max_order: 32,
+            amounts: None,
+            legacy_id: None,
+            input_fee_ppk: 400,
+        };
+        let (router, _mint) =
+            router_with(MNEMONIC, Arc::clone(&backend), HashSet::from([keyset_info])).await;
+        let url = serve(router);
+        let mint_url = UncheckedUrl::from(url.as_str().trim_end_matches('/'));
+        let new_wallet = || {
+            Wallet::new(
+                HttpClient::new(),
+                Arc::new(WalletMemoryDatabase::default()),
+                None,
+            )
+        };
+        let mut alice = new_wallet().await;
+        let mut bob = new_wallet().await;
+
+        let quote = alice
+            .mint_quote(mint_url.clone(), Amount::from(255), CurrencyUnit::Sat, None)
+            .await
+            .unwrap();
+        let invoice = Bolt11Invoice::from_str(&quote.request).unwrap();
+        backend.settle_invoice(*invoice.payment_hash());
+        alice.mint(mint_url.clone(), &quote.id).await.unwrap();
+        let balance = |wallet: &Wallet| {
+            let wallet = wallet.clone();
+            let mint_url = mint_url.clone();
+            async move { wallet.mint_balances().await.unwrap()[&mint_url] }
+        };
+
+        // Receiving swaps the proofs, the fee is kept from them
+        let sent = alice
+            .send(&mint_url, &CurrencyUnit::Sat, Amount::from(100), false)
+            .await
+            .unwrap();
+        let swap_fee = bob.estimate_swap_fee(&mint_url, &sent).await.unwrap();
+        assert_eq!(
+            swap_fee,
+            Amount::from((sent.len() as u64 * 400 + 999) / 1000)
+        );
+        let token = alice
+            .proofs_to_token(mint_url.clone(), sent, None, Some(CurrencyUnit::Sat))
+            .unwrap();
+        bob.receive(&token, ReceiveOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(balance(&bob).await, Amount::from(100) - swap_fee);
+
+        // The payment uses the whole fee reserve, nothing is estimated short
+        let invoice = backend.invoice(Some(20_000));
+        let estimate = alice
+            .estimate_melt_cost(&mint_url, &CurrencyUnit::Sat, &invoice.to_string())
+            .await
+            .unwrap();
+        assert_eq!(estimate.amount, Amount::from(20));
+        assert!(estimate.input_fees > Amount::ZERO);
+        assert_eq!(
+            HttpClient::new()
+                .post_check_fees(url.clone(), invoice.clone())
+                .await
+                .unwrap()
+                .fee,
+            estimate.fee_reserve
+        );
+        backend.script_payment(
+            *invoice.payment_hash(),
+            PaymentScript::Pay {
+                fee_msat: estimate.fee_reserve.to_msat(),
+            },
+        );
+
+        let before = balance(&alice).await;
+        let quote = alice
+            .melt_quote(
+                mint_url.clone(),
+                CurrencyUnit::Sat,
+                invoice.to_string(),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(quote.fee_reserve, estimate.fee_reserve);
+        alice.melt(&mint_url, &quote.id).await.unwrap();
+        assert_eq!(before - balance(&alice).await, estimate.total);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_wallet_melt_fee_paid() {
+        let (router, _mint, backend) = router().await;
+        let url = serve(router);
+        let mint_url = UncheckedUrl::from(url.as_str().trim_end_matches('/'));
+        let mut alice = Wallet::new(
+            HttpClient::new(),
+            Arc::new(WalletMemoryDatabase::default()),
+            None,
+        )
+        .await;
+
+        let quote = alice
+            .mint_quote(mint_url.clone(), Amount::from(64), CurrencyUnit::Sat, None)
+            .await
+            .unwrap();
+        let invoice = Bolt11Invoice::from_str(&quote.request).unwrap();
+        backend.settle_invoice(*invoice.payment_hash());
+        alice.mint(mint_url.clone(), &quote.id).await.unwrap();
+
+        // 10.5 sat invoice with a fee of 1.25 sat spends 11.75 sat of the 64
+        let invoice = backend.invoice(Some(10_500));
+        backend.script_payment(
+            *invoice.payment_hash(),
+            PaymentScript::Pay { fee_msat: 1_250 },
+        );
+        let melt_quote = alice
+            .melt_quote(
+                mint_url.clone(),
+                CurrencyUnit::Sat,
+                invoice.to_string(),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(melt_quote.amount, Amount::from(11));
+        let melted = alice.melt(&mint_url, &melt_quote.id).await.unwrap();
+
+        assert!(melted.paid);
+        assert_eq!(melted.fee_paid, Some(Amount::from(1)));
+        let change = melted.change.unwrap().total_amount().unwrap();
+        assert_eq!(
+            Amount::from(64),
+            melt_quote.amount + melted.fee_paid.unwrap() + change
+        );
+        assert_eq!(alice.mint_balances().await.unwrap()[&mint_url], change);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_wallet_transaction_history() {
+        let (router, _mint, backend) = router().await;
+        let url = serve(router);
+        let mint_url = UncheckedUrl::from(url.as_str().trim_end_matches('/'));
+        let clock = Arc::new(ManualClock::new(unix_time()));
+        let start = clock.now();
+        let new_wallet = || async {
+            let mut wallet = Wallet::new(
+                HttpClient::new(),
+                Arc::new(WalletMemoryDatabase::default()),
+                None,
+            )
+            .await;
+            wallet.set_clock(Arc::clone(&clock) as Arc<dyn Clock>);
+            wallet
+        };
+        let mut alice = new_wallet().await;
+        let mut bob = new_wallet().await;
+
+        let quote = alice
+            .mint_quote(mint_url.clone(), Amount::from(64), CurrencyUnit::Sat, None)
+            .await
+            .unwrap();
+        let invoice = Bolt11Invoice::from_str(&quote.request).unwrap();
+        backend.settle_invoice(*invoice.payment_hash());
+        alice.mint(mint_url.clone(), &quote.id).await.unwrap();
+
+        clock.advance(10);
+        let sent = alice
+            .send(&mint_url, &CurrencyUnit::Sat, Amount::from(10), false)
+            .await
+            .unwrap();
+        let token = alice
+            .proofs_to_token(
+                mint_url.clone(),
+                sent.clone(),
+                Some("lunch".to_string()),
+                Some(CurrencyUnit::Sat),
+            )
+            .unwrap();
+
+        clock.advance(10);
+        bob.receive(&token, ReceiveOptions::default())
+            .await
+            .unwrap();
+
+        // 10 sat invoice, 1 sat of the 2 sat fee reserve is paid
+        clock.advance(10);
+        let invoice = backend.invoice(Some(10_000));
+        let melt_quote = alice
+            .melt_quote(
+                mint_url.clone(),
+                CurrencyUnit::Sat,
+                invoice.to_string(),
+                None,
+            )
+            .await
+            .unwrap();
+        alice.melt(&mint_url, &melt_quote.id).await.unwrap();
+
+        let history = alice
+            .transactions(&TransactionFilter::default())
+            .await
+            .unwrap();
+        let ledger: Vec<_> = history
+            .iter()
+            .map(|t| (t.kind, t.direction, t.amount, t.fee, t.status, t.timestamp))
+            .collect();
+        assert_eq!(
+            ledger,
+            vec![
+                (
+                    TransactionKind::Mint,
+                    TransactionDirection::Incoming,
+                    Amount::from(64),
+                    Amount::ZERO,
+                    TransactionStatus::Settled,
+                    start
+                ),
+                (
+                    TransactionKind::Send,
+                    TransactionDirection::Outgoing,
+                    Amount::from(10),
+                    Amount::ZERO,
+                    TransactionStatus::Settled,
+                    start + 10
+                ),
+                (
+                    TransactionKind::Melt,
+                    TransactionDirection::Outgoing,
+                    Amount::from(10),
+                    Amount::from(1),
+                    TransactionStatus::Settled,
+                    start + 30
+                ),
+            ]
+        );
+        assert_eq!(history[0].quote_id.as_deref(), Some(quote.id.as_str()));
+        assert_eq!(history[1].ys, sent.ys().unwrap());
+        assert_eq!(history[2].quote_id.as_deref(), Some(melt_quote.id.as_str()));
+        assert!(history
+            .iter()
+            .all(|t| t.mint == mint_url && t.unit == CurrencyUnit::Sat));
+        assert_eq!(
+            alice.mint_balances().await.unwrap()[&mint_url],
+            Amount::from(64 - 10 - 10 - 1)
+        );
+
+        // The receiver refers to the same token, with its memo
+        let received = bob
+            .transactions(&TransactionFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].kind, TransactionKind::Receive);
+        assert_eq!(received[0].direction, TransactionDirection::Incoming);
+        assert_eq!(received[0].amount, Amount::from(10));
+        assert_eq!(received[0].memo.as_deref(), Some("lunch"));
+        assert_eq!(received[0].ys, history[1].ys);
+
+        // Filters
+        let melts = alice
+            .transactions(&TransactionFilter {
+                kind: Some(TransactionKind::Melt),
+                ..TransactionFilter::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(melts, vec![history[2].clone()]);
+        let window = alice
+            .transactions(&TransactionFilter {
+                since: Some(start + 10),
+                until: Some(start + 30),
+                ..TransactionFilter::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(window, vec![history[1].clone()]);
+        let elsewhere = alice
+            .transactions(&TransactionFilter {
+                mint: Some(UncheckedUrl::from("https://other.example.com")),
+                ..TransactionFilter::default()
+            })
+            .await
+            .unwrap();
+        assert!(elsewhere.is_empty());
+    }
+
+    /// Settles and issues invoices on the mint's fake backend
+    struct FakeHook(Arc<FakeLnBackend>);
+
+    #[async_trait]
+    impl LightningHook for FakeHook {
+        async fn pay_invoice(&self, quote: &MintQuoteBolt11Response) -> Result<(), String> {
+            let invoice = Bolt11Invoice::from_str(&quote.request).map_err(|err| err.to_string())?;
+            self.0.settle_invoice(*invoice.payment_hash());
+            Ok(())
+        }
+
+        async fn create_invoice(&self, amount: Amount) -> Result<Bolt11Invoice, String> {
+            Ok(self.0.invoice(Some(amount.to_msat())))
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_conformance_suite() {
+        let (router, _mint, backend) = router().await;
+        let url = serve(router);
+
+        let suite = Suite::new(
+            UncheckedUrl::from(url.as_str().trim_end_matches('/')),
+            Arc::new(FakeHook(backend)),
+        );
+        suite.run().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_mint_quote_description_hash() {
+        let (router, mint, backend) = router().await;
+        let request = |body: String| {
+            Request::builder()
+                .method("POST")
+                .uri("/v1/mint/quote/bolt11")
+                .body(body.into_bytes())
+                .unwrap()
+        };
+        let hash = [0x5a; 32];
+
+        let response = router
+            .handle(request(format!(
+                r#"{{"amount":10,"unit":"sat","description":"pay me","description_hash":"{}"}}"#,
+                crate::util::hex::encode(hash)
+            )))
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(backend.calls().is_empty());
+
+        let response = router
+            .handle(request(format!(
+                r#"{{"amount":10,"unit":"sat","description_hash":"{}"}}"#,
+                crate::util::hex::encode(hash)
+            )))
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let quote: MintQuoteBolt11Response = serde_json::from_slice(response.body()).unwrap();
+
+        assert!(matches!(
+            &backend.calls()[..],
+            [Call::CreateInvoice {
+                description: Some(InvoiceDescription::Hash(sent)),
+                ..
+            }] if *sent == hash
+        ));
+        let invoice = Bolt11Invoice::from_str(&quote.request).unwrap();
+        assert_eq!(
+            invoice.description(),
+            lightning_invoice::Bolt11InvoiceDescription::Hash(&lightning_invoice::Sha256(
+                Sha256::from_byte_array(hash)
+            ))
+        );
+        let stored = mint.lock().await.mint_quotes().await.unwrap();
+        assert_eq!(stored[0].id, quote.quote);
+        assert_eq!(stored[0].description_hash, Some(hash));
+    }
+
+    #[tokio::test]
+    async fn test_mint_quote_bolt12() {
+        let (router, mint, backend) = router().await;
+        let request = |method: &str, uri: &str, body: String| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(body.into_bytes())
+                .unwrap()
+        };
+
+        let response = router
+            .handle(request(
+                "POST",
+                "/v1/mint/quote/bolt12",
+                r#"{"unit":"sat","description":"tips"}"#.to_string(),
+            ))
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let quote: MintQuoteBolt12Response = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(quote.amount, None);
+        assert_eq!(quote.amount_paid, Amount::ZERO);
+        assert!(matches!(
+            &backend.calls()[..],
+            [Call::CreateOffer { amount: None, description: Some(description), .. }]
+                if description == "tips"
+        ));
+
+        let state = |quote_id: String| {
+            let router = router.clone();
+            async move {
+                let response = router
+                    .handle(request(
+                        "GET",
+                        &format!("/v1/mint/quote/bolt12/{quote_id}"),
+                        String::new(),
+                    ))
+                    .await;
+                serde_json::from_slice::<MintQuoteBolt12Response>(response.body()).unwrap()
+            }
+        };
+
+        backend.pay_offer(&quote.request, Amount::from(6));
+        let paid = state(quote.quote.clone()).await;
+        assert_eq!(
+            (paid.amount_paid, paid.amount_issued),
+            (Amount::from(6), Amount::ZERO)
+        );
+
+        let keyset_id = mint.lock().await.keysets().await.unwrap().keysets[0].id;
+        let pre_mint = PreMintSecrets::random(keyset_id, Amount::from(6)).unwrap();
+        let body = serde_json::json!({
+            "quote": quote.quote,
+            "outputs": pre_mint.blinded_messages(),
+        });
+        let response = router
+            .handle(request("POST", "/v1/mint/bolt12", body.to_string()))
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let issued = state(quote.quote.clone()).await;
+        assert_eq!(issued.amount_issued, Amount::from(6));
+
+        // Bolt11 quotes are not bolt12 quotes
+        let response = router
+            .handle(request(
+                "POST",
+                "/v1/mint/quote/bolt11",
+                r#"{"amount":10,"unit":"sat"}"#.to_string(),
+            ))
+            .await;
+        let bolt11: MintQuoteBolt11Response = serde_json::from_slice(response.body()).unwrap();
+        let response = router
+            .handle(request(
+                "GET",
+                &format!("/v1/mint/quote/bolt12/{}", bolt11.quote),
+                String::new(),
+            ))
+            .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_router_status_codes() {
+        let (router, _mint, _backend) = router().await;
+
+        let request = |method: &str, uri: &str, body: &str| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(body.as_bytes().to_vec())
+                .unwrap()
+        };
+
+        let response = router.handle(request("GET", "/v1/keysets", "")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+
+        for (method, uri, body, status) in [
+            ("GET", "/v1/nope", "", StatusCode::NOT_FOUND),
+            ("DELETE", "/v1/keys", "", StatusCode::NOT_FOUND),
+            (
+                "GET",
+                "/v1/keys/00ffffffffffffff",
+                "",
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                "GET",
+                "/v1/mint/quote/bolt11/nope",
+                "",
+                StatusCode::NOT_FOUND,
+            ),
+            ("POST", "/v1/swap", "not json", StatusCode::BAD_REQUEST),
+            (
+                "POST",
+                "/v1/mint/bolt11",
+                r#"{"quote":"nope","outputs":[]}"#,
+                StatusCode::NOT_FOUND,
+            ),
+        ] {
+            let response = router.handle(request(method, uri, body)).await;
+            assert_eq!(response.status(), status, "{method} {uri}");
+            assert!(serde_json::from_slice::<ErrorResponse>(response.body()).is_ok());
+        }
+
+        let mut strict = router.clone();
+        strict.set_strictness(Strictness::Strict);
+        let body = r#"{"inputs":[],"outputs":[],"extra":1}"#;
+        assert_eq!(
+            router
+                .handle(request("POST", "/v1/swap", body))
+                .await
+                .status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            strict
+                .handle(request("POST", "/v1/swap", body))
+                .await
+                .status(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[tokio::test]
+    async fn test_router_swap_response_forms() {
+        let (mut router, mint, _backend) = router().await;
+        let keyset_id = mint.lock().await.keysets().await.unwrap().keysets[0].id;
+        let keys = mint
+            .lock()
+            .await
+            .keyset_pubkeys(&keyset_id)
+            .await
+            .unwrap()
+            .keysets[0]
+            .keys
+            .clone();
+
+        let mint_proofs = |amount: u64| {
+            let mint = Arc::clone(&mint);
+            let keys = keys.clone();
+            async move {
+                let mut mint = mint.lock().await;
+                let amount = Amount::from(amount);
+                let quote = mint
+                    .new_mint_quote(
+                        PaymentMethod::Bolt11,
+                        "lnbc".to_string(),
+                        CurrencyUnit::Sat,
+                        amount,
+                        0,
+                        None,
+                    )
+                    .await
+                    .unwrap();
+                mint.pay_mint_quote(&quote.id).await.unwrap();
+                let pre_mint = PreMintSecrets::random(keyset_id, amount).unwrap();
+                let signatures = mint
+                    .process_mint_request(MintBolt11Request {
+                        quote: quote.id,
+                        outputs: pre_mint.blinded_messages(),
+                    })
+                    .await
+                    .unwrap()
+                    .signatures;
+                construct_proofs(signatures, pre_mint.rs(), pre_mint.secrets(), &keys).unwrap()
+            }
+        };
+        let swap = |router: MintRouter, request: SwapRequest| async move {
+            let response = router
+                .handle(
+                    Request::builder()
+                        .method("POST")
+                        .uri("/v1/swap")
+                        .body(serde_json::to_vec(&request).unwrap())
+                        .unwrap(),
+                )
+                .await;
+            let status = response.status();
+            let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+            (status, body)
+        };
+        let outputs = |amount: u64| {
+            PreMintSecrets::random(keyset_id, Amount::from(amount))
+                .unwrap()
+                .blinded_messages()
+        };
+
+        let request = SwapRequest::new(mint_proofs(8).await, outputs(8));
+        let (status, body) = swap(router.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.get("signatures").is_some());
+
+        router.set_vintage(Vintage::Legacy);
+        let request = SwapRequest::new(mint_proofs(8).await, outputs(8));
+        let (_, body) = swap(router.clone(), request).await;
+        assert!(body.get("promises").is_some());
+        assert!(body.get("signatures").is_none());
+
+        // Old wallets split off `amount` with the last outputs
+        let inputs = mint_proofs(8).await;
+        let mut request = SwapRequest::new(inputs.clone(), [outputs(6), outputs(2)].concat());
+        request.amount = Some(Amount::from(2));
+        let (status, body) = swap(router.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+        let total = |signatures: &serde_json::Value| -> u64 {
+            signatures
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|s| s["amount"].as_u64().unwrap())
+                .sum()
+        };
+        assert_eq!((total(&body["fst"]), total(&body["snd"])), (6, 2));
+
+        // A split the outputs do not end with is refused before spending
+        let inputs = mint_proofs(8).await;
+        let mut request = SwapRequest::new(inputs.clone(), outputs(8));
+        request.amount = Some(Amount::from(3));
+        let (status, _) = swap(router.clone(), request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = swap(router.clone(), SwapRequest::new(inputs, outputs(8))).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[cfg(feature = "nostr")]
+    #[tokio::test]
+    async fn test_nostr_send_and_inbox() {
+        use crate::nostr::{self, Event, NostrRelay};
+        use crate::nuts::Nut10Secret;
+        use crate::test_utils::nostr_relay::MemoryRelay;
+
+        let (router, _mint, backend) = router().await;
+        let url = serve(router);
+        let mint_url = UncheckedUrl::from(url.as_str().trim_end_matches('/'));
+        let new_wallet = || async {
+            let mut wallet = Wallet::new(
+                HttpClient::new(),
+                Arc::new(WalletMemoryDatabase::default()),
+                None,
+            )
+            .await;
+            let keys = SigningKey::new(SecretKey::generate());
+            wallet.set_nostr_keys(keys.clone());
+            (wallet, keys)
+        };
+        let (mut alice, alice_keys) = new_wallet().await;
+        let (mut bob, bob_keys) = new_wallet().await;
+        let bob_npub = nostr::to_npub(&bob_keys.verifying_key());
+
+        let quote = alice
+            .mint_quote(mint_url.clone(), Amount::from(255), CurrencyUnit::Sat, None)
+            .await
+            .unwrap();
+        let invoice = Bolt11Invoice::from_str(&quote.request).unwrap();
+        backend.settle_invoice(*invoice.payment_hash());
+        alice.mint(mint_url.clone(), &quote.id).await.unwrap();
+
+        let up = Arc::new(MemoryRelay::new("wss://up.example"));
+        let down = Arc::new(MemoryRelay::new("wss://down.example"));
+        down.set_offline(true);
+        let relays: Vec<Arc<dyn NostrRelay>> = vec![up.clone(), down.clone()];
+
+        // Sent once plainly and once locked to the key of the npub, the
+        // offline relay is reported but does not fail the send
+        let plain = alice
+            .send_to_npub(
+                &mint_url,
+                &CurrencyUnit::Sat,
+                Amount::from(10),
+                &bob_npub,
+                false,
+                &relays,
+            )
+            .await
+            .unwrap();
+        assert_eq!(plain.published, vec!["wss://up.example".to_string()]);
+        assert_eq!(plain.relay_failures.len(), 1);
+        assert_eq!(plain.relay_failures[0].0, "wss://down.example");
+        assert_ne!(plain.event.content, plain.token);
+
+        let locked = alice
+            .send_to_npub(
+                &mint_url,
+                &CurrencyUnit::Sat,
+                Amount::from(20),
+                &bob_npub,
+                true,
+                &relays,
+            )
+            .await
+            .unwrap();
+        let locked_token = Token::from_str(&locked.token).unwrap();
+        assert!(locked_token.token[0].proofs.iter().all(|proof| {
+            let secret = Nut10Secret::try_from(&proof.secret).unwrap();
+            let conditions = P2PKConditions::try_from(secret).unwrap();
+            conditions.pubkeys == vec![bob_keys.verifying_key()]
+        }));
+
+        // A message without a token is reported, not an error
+        let chat = Event::direct_message(
+            &alice_keys,
+            &bob_keys.verifying_key(),
+            "lunch tomorrow?",
+            unix_time(),
+        )
+        .unwrap();
+        up.publish(&chat).await.unwrap();
+        assert_eq!(up.events().len(), 3);
+
+        down.set_offline(false);
+        let inbox = bob.check_nostr_inbox(&relays, None).await.unwrap();
+        assert!(inbox.failures.is_empty(), "{:?}", inbox.failures);
+        assert!(inbox.relay_failures.is_empty());
+        assert_eq!(inbox.without_token, vec![chat.id.clone()]);
+        assert_eq!(
+            inbox
+                .received
+                .iter()
+                .map(|received| (received.event_id.clone(), received.amount))
+                .collect::<Vec<_>>(),
+            vec![
+                (plain.event.id.clone(), Amount::from(10)),
+                (locked.event.id.clone(), Amount::from(20)),
+            ]
+        );
+        assert!(inbox
+            .received
+            .iter()
+            .all(|received| received.sender == alice_keys.verifying_key().to_string()));
+        assert_eq!(
+            bob.balances().await.unwrap()[&(mint_url.clone(), CurrencyUnit::Sat)],
+            Amount::from(30)
+        );
+
+        // Tokens read before are not received twice
+        let again = bob.check_nostr_inbox(&relays, None).await.unwrap();
+        assert!(again.received.is_empty());
+        assert!(again.failures.is_empty(), "{:?}", again.failures);
+        assert_eq!(
+            again.already_received,
+            vec![plain.event.id.clone(), locked.event.id.clone()]
+        );
+        assert!(bob
+            .check_nostr_inbox(&relays, Some(unix_time() + 60))
+            .await
+            .unwrap()
+            .already_received
+            .is_empty());
+
+        // Without keys there is no inbox, other keys see none of Bob's messages
+        let mut eve = Wallet::default();
+        assert!(matches!(
+            eve.check_nostr_inbox(&relays, None).await,
+            Err(WalletError::NostrKeysRequired)
+        ));
+        eve.set_nostr_keys(alice_keys);
+        assert!(eve
+            .check_nostr_inbox(&relays, None)
+            .await
+            .unwrap()
+            .received
+            .is_empty());
+    }
+}
@@ -29,6 +29,7 @@ impl Default for Secret {
 }
 
 impl Secret {
+    /// Wrap an externally supplied secret value
     #[inline]
     pub fn new<S>(secret: S) -> Self
     where
@@ -37,8 +38,9 @@ impl Secret {
         Self(secret.into())
     }
 
-    /// Create secret value
     /// Generate a new random secret as the recommended 32 byte hex
+    ///
+    /// Random bytes come from the thread local CSPRNG
     pub fn generate() -> Self {
         let mut rng = rand::thread_rng();
 
@@ -91,6 +93,18 @@ impl fmt::Display for Secret {
     }
 }
 
+impl From<String> for Secret {
+    fn from(secret: String) -> Self {
+        Self(secret)
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(secret: &str) -> Self {
+        Self(secret.to_string())
+    }
+}
+
 impl From<Secret> for Vec<u8> {
     fn from(value: Secret) -> Vec<u8> {
         value.to_bytes()
@@ -122,6 +136,7 @@ impl TryFrom<&Secret> for crate::nuts::nut10::Secret {
 #[cfg(test)]
 mod tests {
     use std::assert_eq;
+    use std::collections::HashSet;
     use std::str::FromStr;
 
     use super::*;
@@ -138,4 +153,33 @@ mod tests {
 
         assert_eq!(secret_n, secret)
     }
+
+    #[test]
+    fn test_generated_secret_is_hex() {
+        let secret = Secret::generate();
+        let secret_str = secret.to_string();
+
+        assert_eq!(secret_str.len(), 64);
+        assert!(secret_str.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(secret.as_bytes(), secret_str.as_bytes());
+    }
+
+    #[test]
+    fn test_generated_secrets_are_unique() {
+        let secrets: HashSet<Secret> = (0..10_000).map(|_| Secret::generate()).collect();
+
+        assert_eq!(secrets.len(), 10_000);
+    }
+
+    #[test]
+    fn test_secret_from_string() {
+        let value = "407915bc212be61a77e3e6d2aeb4c727980bda51cd06a6afc29e2861768a7837";
+
+        assert_eq!(Secret::from(value.to_string()), Secret::new(value));
+        assert_eq!(Secret::from(value), Secret::from_str(value).unwrap());
+        assert_eq!(
+            serde_json::to_string(&Secret::new(value)).unwrap(),
+            format!("\"{}\"", value)
+        );
+    }
 }
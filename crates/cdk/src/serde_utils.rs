@@ -0,0 +1,168 @@
+//! Serde helpers
+
+use base64::alphabet;
+use base64::engine::{general_purpose, GeneralPurpose};
+
+/// Standard base64 that decodes with or without padding
+const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    general_purpose::GeneralPurposeConfig::new()
+        .with_decode_padding_mode(base64::engine::DecodePaddingMode::Indifferent),
+);
+
+/// Bytes as a standard base64 string
+///
+/// Serialized with padding, padded and unpadded strings are both accepted.
+pub mod bytes_base64 {
+    use base64::Engine as _;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    use super::BASE64;
+
+    pub fn serialize<S>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&base64::engine::general_purpose::STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let encoded = String::deserialize(deserializer)?;
+        BASE64.decode(encoded).map_err(de::Error::custom)
+    }
+}
+
+/// Optional 32 byte hash as a hex string
+pub mod option_hash_hex {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    use crate::util::hex;
+
+    pub fn serialize<S>(hash: &Option<[u8; 32]>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match hash {
+            Some(hash) => serializer.serialize_some(&hex::encode(hash)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<[u8; 32]>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let Some(encoded) = Option::<String>::deserialize(deserializer)? else {
+            return Ok(None);
+        };
+        let bytes = hex::decode(encoded).map_err(de::Error::custom)?;
+        bytes
+            .try_into()
+            .map(Some)
+            .map_err(|_| de::Error::custom("hash must be 32 bytes"))
+    }
+}
+
+/// Secret that may be in the legacy base64 form of early wallets
+///
+/// Early wallets stored the secret as bytes and sent them base64 encoded.
+/// A string is taken as that form only if it decodes to printable ASCII,
+/// which the secrets of those wallets always were; anything else is kept as
+/// a plain string secret.
+///
+/// Only use this for data known to come from such wallets. A plain secret
+/// that happens to be base64 of printable text would be decoded.
+pub mod legacy_secret {
+    use base64::Engine as _;
+    use serde::{Deserialize, Deserializer};
+
+    use super::BASE64;
+    use crate::secret::Secret;
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Secret, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let secret = String::deserialize(deserializer)?;
+        Ok(Secret::new(decode(&secret).unwrap_or(secret)))
+    }
+
+    /// Secret encoded in `secret`, if it is in the legacy form
+    pub(crate) fn decode(secret: &str) -> Option<String> {
+        let bytes = BASE64.decode(secret).ok()?;
+
+        if bytes.is_empty() || !bytes.iter().all(|b| b.is_ascii_graphic()) {
+            return None;
+        }
+
+        String::from_utf8(bytes).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::secret::Secret;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Bytes {
+        #[serde(with = "bytes_base64")]
+        bytes: Vec<u8>,
+    }
+
+    #[test]
+    fn test_bytes_base64() {
+        let bytes = Bytes {
+            bytes: vec![0xff, 0x00, 0x7e, 0x10],
+        };
+
+        let json = serde_json::to_string(&bytes).unwrap();
+        assert_eq!(json, r#"{"bytes":"/wB+EA=="}"#);
+        assert_eq!(serde_json::from_str::<Bytes>(&json).unwrap(), bytes);
+
+        // Unpadded
+        let unpadded: Bytes = serde_json::from_str(r#"{"bytes":"/wB+EA"}"#).unwrap();
+        assert_eq!(unpadded, bytes);
+
+        // Url safe alphabet is not standard base64
+        assert!(serde_json::from_str::<Bytes>(r#"{"bytes":"_wB-EA"}"#).is_err());
+    }
+
+    #[test]
+    fn test_legacy_secret() {
+        #[derive(Deserialize)]
+        struct Legacy {
+            #[serde(deserialize_with = "legacy_secret::deserialize")]
+            secret: Secret,
+        }
+
+        let secret = |json: &str| serde_json::from_str::<Legacy>(json).unwrap().secret;
+
+        // base64 of `gfk7rBNw0Q2E-_pIZngyPA`
+        assert_eq!(
+            secret(r#"{"secret":"Z2ZrN3JCTncwUTJFLV9wSVpuZ3lQQQ=="}"#),
+            Secret::new("gfk7rBNw0Q2E-_pIZngyPA")
+        );
+        assert_eq!(
+            secret(r#"{"secret":"Z2ZrN3JCTncwUTJFLV9wSVpuZ3lQQQ"}"#),
+            Secret::new("gfk7rBNw0Q2E-_pIZngyPA")
+        );
+
+        // Current secrets are kept as they are
+        let hex = "407915bc212be61a77e3e6d2aeb4c727980bda51cd06a6afc29e2861768a7837";
+        assert_eq!(
+            secret(&format!(r#"{{"secret":"{hex}"}}"#)),
+            Secret::new(hex)
+        );
+        let nut10 = r#"["P2PK",{"nonce":"5d11913ee0f92fefdc82a6764fd2457a","data":"026562efcfadc8e86d44da6a8adf80633d974302e62c850774db1fb36ff4cc7198"}]"#;
+        assert_eq!(
+            secret(&serde_json::json!({ "secret": nut10 }).to_string()),
+            Secret::new(nut10)
+        );
+    }
+}
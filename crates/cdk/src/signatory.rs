@@ -0,0 +1,385 @@
+//! Signatory holding the keyset secret keys of a mint
+//!
+//! The [Mint](crate::mint::Mint) checks requests, spending conditions and
+//! its database itself and leaves the curve math on the keyset keys to a
+//! [Signatory], so the keys can live in another process or on an HSM.
+//! Keysets are still generated and rotated by the mint from its mnemonic, a
+//! remote signatory has to hold the same keysets.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use thiserror::Error;
+use tokio::sync::RwLock;
+
+use crate::cdk_database::{self, MintDatabase};
+use crate::dhke::{hash_to_curve, sign_message, verify_y};
+use crate::nuts::{BlindSignature, BlindedMessage, Id, KeySet, MintKeySet, Proof, PublicKey};
+use crate::secret::Secret;
+use crate::Amount;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// Signatory does not hold the keyset
+    #[error("Unknown keyset `{0}`")]
+    UnknownKeyset(Id),
+    /// Keyset of the signatory has no key for the amount
+    #[error("Keyset `{keyset_id}` has no key for amount `{amount}`")]
+    AmountKey { keyset_id: Id, amount: Amount },
+    /// Signatory failed or could not be reached, nothing was signed
+    #[error(transparent)]
+    Backend(Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// Blind signs and verifies with the keyset keys of a mint
+///
+/// Keyset ids passed in are always current ids, the mint resolves legacy ids
+/// before calling. Calls may be slow, the mint holds no lock across them.
+#[async_trait]
+pub trait Signatory: Send + Sync {
+    /// Signature with DLEQ proof on `blinded_secret` by the key of `amount`
+    /// in keyset `keyset_id`
+    async fn blind_sign(
+        &self,
+        keyset_id: Id,
+        amount: Amount,
+        blinded_secret: PublicKey,
+    ) -> Result<BlindSignature, Error>;
+
+    /// Whether `c` is the signature of the key of `amount` in keyset
+    /// `keyset_id` on `secret`
+    async fn verify_proof(
+        &self,
+        keyset_id: Id,
+        amount: Amount,
+        c: PublicKey,
+        secret: &Secret,
+    ) -> Result<bool, Error>;
+
+    /// Public keys of every keyset held
+    async fn keysets(&self) -> Result<Vec<KeySet>, Error>;
+
+    /// [Signatory::blind_sign] of each of `blinded_messages` in order, a
+    /// remote signatory should sign them in a single round trip
+    async fn blind_sign_many(
+        &self,
+        blinded_messages: &[BlindedMessage],
+    ) -> Result<Vec<BlindSignature>, Error> {
+        let mut signatures = Vec::with_capacity(blinded_messages.len());
+
+        for blinded_message in blinded_messages {
+            signatures.push(
+                self.blind_sign(
+                    blinded_message.keyset_id,
+                    blinded_message.amount,
+                    blinded_message.blinded_secret,
+                )
+                .await?,
+            );
+        }
+
+        Ok(signatures)
+    }
+
+    /// [Signatory::verify_proof] of each of `proofs` in order, a remote
+    /// signatory should verify them in a single round trip
+    async fn verify_proofs(&self, proofs: &[Proof]) -> Result<Vec<bool>, Error> {
+        let mut verified = Vec::with_capacity(proofs.len());
+
+        for proof in proofs {
+            verified.push(
+                self.verify_proof(proof.keyset_id, proof.amount, proof.c, &proof.secret)
+                    .await?,
+            );
+        }
+
+        Ok(verified)
+    }
+}
+
+/// [Signatory] using the keysets in the database of the mint, the default
+/// of [Mint::new](crate::mint::Mint::new)
+///
+/// With the `parallel` feature batches are signed and verified on the rayon
+/// pool.
+pub struct LocalSignatory {
+    localstore: Arc<dyn MintDatabase<Err = cdk_database::Error> + Send + Sync>,
+    keysets: RwLock<HashMap<Id, Arc<MintKeySet>>>,
+}
+
+impl LocalSignatory {
+    pub fn new(localstore: Arc<dyn MintDatabase<Err = cdk_database::Error> + Send + Sync>) -> Self {
+        Self {
+            localstore,
+            keysets: RwLock::default(),
+        }
+    }
+
+    async fn keyset(&self, id: Id) -> Result<Arc<MintKeySet>, Error> {
+        if let Some(keyset) = self.keysets.read().await.get(&id) {
+            return Ok(Arc::clone(keyset));
+        }
+
+        let keyset = Arc::new(
+            self.localstore
+                .get_keyset(&id)
+                .await
+                .map_err(|e| Error::Backend(Box::new(e)))?
+                .ok_or(Error::UnknownKeyset(id))?,
+        );
+        self.keysets.write().await.insert(id, Arc::clone(&keyset));
+
+        Ok(keyset)
+    }
+
+    /// Keysets of `ids`, each looked up once
+    async fn keysets_of(
+        &self,
+        ids: impl Iterator<Item = Id>,
+    ) -> Result<HashMap<Id, Arc<MintKeySet>>, Error> {
+        let mut keysets = HashMap::new();
+
+        for id in ids {
+            if let Entry::Vacant(entry) = keysets.entry(id) {
+                entry.insert(self.keyset(id).await?);
+            }
+        }
+
+        Ok(keysets)
+    }
+}
+
+/// Secret key of `amount` in `keyset`
+fn amount_key(keyset: &MintKeySet, amount: Amount) -> Result<&crate::nuts::SecretKey, Error> {
+    keyset
+        .keys
+        .get(&amount)
+        .map(|keypair| &keypair.secret_key)
+        .ok_or(Error::AmountKey {
+            keyset_id: keyset.id,
+            amount,
+        })
+}
+
+#[async_trait]
+impl Signatory for LocalSignatory {
+    async fn blind_sign(
+        &self,
+        keyset_id: Id,
+        amount: Amount,
+        blinded_secret: PublicKey,
+    ) -> Result<BlindSignature, Error> {
+        let keyset = self.keyset(keyset_id).await?;
+        let blinded_message = BlindedMessage::new(amount, keyset_id, blinded_secret);
+
+        sign_blinded_message(&blinded_message, amount_key(&keyset, amount)?)
+    }
+
+    async fn verify_proof(
+        &self,
+        keyset_id: Id,
+        amount: Amount,
+        c: PublicKey,
+        secret: &Secret,
+    ) -> Result<bool, Error> {
+        let keyset = self.keyset(keyset_id).await?;
+        let y = hash_to_curve(secret.as_bytes()).map_err(|e| Error::Backend(Box::new(e)))?;
+
+        Ok(verify_y(amount_key(&keyset, amount)?, c, y).is_ok())
+    }
+
+    async fn keysets(&self) -> Result<Vec<KeySet>, Error> {
+        Ok(self
+            .localstore
+            .get_keysets()
+            .await
+            .map_err(|e| Error::Backend(Box::new(e)))?
+            .iter()
+            .map(KeySet::from)
+            .collect())
+    }
+
+    async fn blind_sign_many(
+        &self,
+        blinded_messages: &[BlindedMessage],
+    ) -> Result<Vec<BlindSignature>, Error> {
+        let keysets = self
+            .keysets_of(blinded_messages.iter().map(|message| message.keyset_id))
+            .await?;
+        let keys = blinded_messages
+            .iter()
+            .map(|message| amount_key(&keysets[&message.keyset_id], message.amount))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        sign_blinded_messages(blinded_messages, &keys)
+    }
+
+    async fn verify_proofs(&self, proofs: &[Proof]) -> Result<Vec<bool>, Error> {
+        let keysets = self
+            .keysets_of(proofs.iter().map(|proof| proof.keyset_id))
+            .await?;
+        let keys = proofs
+            .iter()
+            .map(|proof| amount_key(&keysets[&proof.keyset_id], proof.amount))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(verify_signatures(proofs, &keys))
+    }
+}
+
+/// Blind signature with DLEQ proof for a single message
+fn sign_blinded_message(
+    blinded_message: &BlindedMessage,
+    secret_key: &crate::nuts::SecretKey,
+) -> Result<BlindSignature, Error> {
+    let c = sign_message(secret_key, &blinded_message.blinded_secret)
+        .map_err(|e| Error::Backend(Box::new(e)))?;
+
+    BlindSignature::new(
+        blinded_message.amount,
+        c,
+        blinded_message.keyset_id,
+        &blinded_message.blinded_secret,
+        secret_key,
+    )
+    .map_err(|e| Error::Backend(Box::new(e)))
+}
+
+#[cfg(not(feature = "parallel"))]
+fn sign_blinded_messages(
+    blinded_messages: &[BlindedMessage],
+    keys: &[&crate::nuts::SecretKey],
+) -> Result<Vec<BlindSignature>, Error> {
+    blinded_messages
+        .iter()
+        .zip(keys)
+        .map(|(blinded_message, secret_key)| sign_blinded_message(blinded_message, secret_key))
+        .collect()
+}
+
+#[cfg(feature = "parallel")]
+fn sign_blinded_messages(
+    blinded_messages: &[BlindedMessage],
+    keys: &[&crate::nuts::SecretKey],
+) -> Result<Vec<BlindSignature>, Error> {
+    use rayon::prelude::*;
+
+    blinded_messages
+        .par_iter()
+        .zip(keys)
+        .map(|(blinded_message, secret_key)| sign_blinded_message(blinded_message, secret_key))
+        .collect()
+}
+
+/// Whether the signature of each proof is by its key, a proof whose `Y`
+/// cannot be computed is not verified
+fn verify_signature(proof: &Proof, secret_key: &crate::nuts::SecretKey) -> bool {
+    // `Y` is cached on the proof since the request was checked for
+    // duplicates
+    proof
+        .y()
+        .is_ok_and(|y| verify_y(secret_key, proof.c, y).is_ok())
+}
+
+#[cfg(not(feature = "parallel"))]
+pub(crate) fn verify_signatures(proofs: &[Proof], keys: &[&crate::nuts::SecretKey]) -> Vec<bool> {
+    proofs
+        .iter()
+        .zip(keys)
+        .map(|(proof, secret_key)| verify_signature(proof, secret_key))
+        .collect()
+}
+
+#[cfg(feature = "parallel")]
+pub(crate) fn verify_signatures(proofs: &[Proof], keys: &[&crate::nuts::SecretKey]) -> Vec<bool> {
+    use rayon::prelude::*;
+
+    proofs
+        .par_iter()
+        .zip(keys)
+        .map(|(proof, secret_key)| verify_signature(proof, secret_key))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::bip32::DerivationPath;
+
+    use super::*;
+    use crate::cdk_database::mint_memory::MintMemoryDatabase;
+    use crate::cdk_database::MintDatabase;
+    use crate::dhke::{blind_message, unblind_message};
+    use crate::nuts::{CurrencyUnit, MintInfo};
+
+    async fn signatory() -> (LocalSignatory, MintKeySet) {
+        let keyset =
+            MintKeySet::generate(&[7; 32], CurrencyUnit::Sat, &DerivationPath::default(), 4)
+                .unwrap();
+        let localstore = MintMemoryDatabase::new(
+            MintInfo::default(),
+            HashMap::new(),
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            HashMap::new(),
+        )
+        .unwrap();
+        localstore.add_keyset(keyset.clone()).await.unwrap();
+
+        (LocalSignatory::new(Arc::new(localstore)), keyset)
+    }
+
+    #[tokio::test]
+    async fn test_local_signatory() {
+        let (signatory, keyset) = signatory().await;
+        let amount = Amount::from(4);
+
+        let secret = Secret::generate();
+        let (blinded_secret, r) = blind_message(secret.as_bytes(), None).unwrap();
+        let signature = signatory
+            .blind_sign(keyset.id, amount, blinded_secret)
+            .await
+            .unwrap();
+        assert_eq!(signature.keyset_id, keyset.id);
+        assert_eq!(signature.amount, amount);
+
+        let public_key = keyset.keys.get(&amount).unwrap().public_key;
+        let c = unblind_message(&signature.c, &r, &public_key).unwrap();
+        assert!(signatory
+            .verify_proof(keyset.id, amount, c, &secret)
+            .await
+            .unwrap());
+        // Signed by the key of another amount
+        assert!(!signatory
+            .verify_proof(keyset.id, Amount::from(2), c, &secret)
+            .await
+            .unwrap());
+        assert!(!signatory
+            .verify_proof(keyset.id, amount, c, &Secret::generate())
+            .await
+            .unwrap());
+
+        assert!(matches!(
+            signatory
+                .blind_sign(keyset.id, Amount::from(16), blinded_secret)
+                .await,
+            Err(Error::AmountKey { keyset_id, amount }) if keyset_id == keyset.id && amount == Amount::from(16)
+        ));
+        let unknown = Id::from_str("00ffd48b8f5ecf80").unwrap();
+        assert!(matches!(
+            signatory.blind_sign(unknown, amount, blinded_secret).await,
+            Err(Error::UnknownKeyset(id)) if id == unknown
+        ));
+
+        assert_eq!(
+            signatory.keysets().await.unwrap(),
+            vec![KeySet::from(&keyset)]
+        );
+    }
+}
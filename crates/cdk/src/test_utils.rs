@@ -0,0 +1,267 @@
+//! Proptest strategies for protocol types
+//!
+//! Values are structurally valid but adversarial: extreme amounts, empty
+//! vectors and unusual secrets. Enabled with the `test-utils` feature.
+
+pub mod clock;
+#[cfg(feature = "mint")]
+pub mod fake_ln;
+#[cfg(feature = "wallet")]
+pub mod interop;
+#[cfg(feature = "nostr")]
+pub mod nostr_relay;
+#[cfg(feature = "mint")]
+pub mod slow_signatory;
+#[cfg(feature = "ws")]
+pub mod ws_mint;
+
+use proptest::collection::vec;
+use proptest::option;
+use proptest::prelude::*;
+
+use crate::nuts::nut00::MintProofs;
+use crate::nuts::nut04::MintBolt11Request;
+use crate::nuts::{
+    BlindedMessage, CurrencyUnit, HTLCWitness, Id, P2PKWitness, Proof, Proofs, PublicKey,
+    SecretKey, SwapRequest, Token, Witness,
+};
+use crate::secret::Secret;
+use crate::url::UncheckedUrl;
+use crate::Amount;
+
+/// Maximum number of items in generated vectors
+const MAX_LEN: usize = 8;
+
+/// [Amount] biased towards zero, powers of two and [u64::MAX]
+pub fn amount() -> impl Strategy<Value = Amount> {
+    prop_oneof![
+        Just(Amount::ZERO),
+        Just(Amount::from(u64::MAX)),
+        (0..64u32).prop_map(|exp| Amount::from(1 << exp)),
+        any::<u64>().prop_map(Amount::from),
+    ]
+}
+
+/// Version `00` keyset [Id]
+pub fn keyset_id() -> impl Strategy<Value = Id> {
+    "00[0-9a-f]{14}".prop_map(|id| id.parse().expect("Valid keyset id"))
+}
+
+/// [PublicKey] of a random secret key
+pub fn public_key() -> impl Strategy<Value = PublicKey> {
+    any::<[u8; 32]>().prop_filter_map("Invalid secret key", |bytes| {
+        SecretKey::from_slice(&bytes)
+            .ok()
+            .map(|secret_key| secret_key.public_key())
+    })
+}
+
+/// [Secret] that is random hex, any string or NUT-10 shaped
+pub fn secret() -> impl Strategy<Value = Secret> {
+    prop_oneof![
+        "[0-9a-f]{64}".prop_map(Secret::new),
+        any::<String>().prop_map(Secret::new),
+        ("[0-9a-f]{64}", public_key()).prop_map(|(nonce, data)| {
+            Secret::new(format!(
+                r#"["P2PK",{{"nonce":"{nonce}","data":"{data}","tags":[["sigflag","SIG_INPUTS"]]}}]"#
+            ))
+        }),
+    ]
+}
+
+/// P2PK or HTLC [Witness]
+pub fn witness() -> impl Strategy<Value = Witness> {
+    prop_oneof![
+        vec("[0-9a-f]{128}", 0..3).prop_map(|signatures| P2PKWitness { signatures }.into()),
+        ("[0-9a-f]{64}", option::of(vec("[0-9a-f]{128}", 0..3))).prop_map(
+            |(preimage, signatures)| HTLCWitness {
+                preimage,
+                signatures,
+            }
+            .into()
+        ),
+    ]
+}
+
+/// Known or custom [CurrencyUnit]
+pub fn currency_unit() -> impl Strategy<Value = CurrencyUnit> {
+    prop_oneof![
+        Just(CurrencyUnit::Sat),
+        Just(CurrencyUnit::Msat),
+        Just(CurrencyUnit::Usd),
+        Just(CurrencyUnit::Eur),
+        any::<String>().prop_map(CurrencyUnit::from),
+    ]
+}
+
+/// Mint url
+pub fn mint_url() -> impl Strategy<Value = UncheckedUrl> {
+    "https://[a-z]{1,12}\\.[a-z]{2,5}(:[0-9]{1,5})?(/[a-z]{1,8}){0,2}".prop_map(UncheckedUrl::new)
+}
+
+/// [Proof] without DLEQ
+pub fn proof() -> impl Strategy<Value = Proof> {
+    (
+        amount(),
+        keyset_id(),
+        secret(),
+        public_key(),
+        option::of(witness()),
+    )
+        .prop_map(|(amount, keyset_id, secret, c, witness)| Proof {
+            amount,
+            keyset_id,
+            secret,
+            c,
+            witness,
+            dleq: None,
+            y: Default::default(),
+        })
+}
+
+/// Possibly empty [Proofs]
+pub fn proofs() -> impl Strategy<Value = Proofs> {
+    vec(proof(), 0..MAX_LEN)
+}
+
+/// [BlindedMessage]
+pub fn blinded_message() -> impl Strategy<Value = BlindedMessage> {
+    (amount(), keyset_id(), public_key(), option::of(witness())).prop_map(
+        |(amount, keyset_id, blinded_secret, witness)| BlindedMessage {
+            amount,
+            keyset_id,
+            blinded_secret,
+            witness,
+        },
+    )
+}
+
+/// [MintBolt11Request]
+pub fn mint_request() -> impl Strategy<Value = MintBolt11Request> {
+    (any::<String>(), vec(blinded_message(), 0..MAX_LEN))
+        .prop_map(|(quote, outputs)| MintBolt11Request { quote, outputs })
+}
+
+/// [SwapRequest] with or without the deprecated amount
+pub fn swap_request() -> impl Strategy<Value = SwapRequest> {
+    (
+        proofs(),
+        vec(blinded_message(), 0..MAX_LEN),
+        option::of(amount()),
+    )
+        .prop_map(|(inputs, outputs, amount)| SwapRequest {
+            inputs,
+            outputs,
+            amount,
+        })
+}
+
+/// [Token] passing [Token::validate], with at least one proof per mint and
+/// amounts that cannot overflow
+pub fn token() -> impl Strategy<Value = Token> {
+    let token_proof = (proof(), 1..=u64::MAX >> 8).prop_map(|(proof, amount)| Proof {
+        amount: Amount::from(amount),
+        ..proof
+    });
+
+    (
+        vec((mint_url(), vec(token_proof, 1..MAX_LEN)), 1..3),
+        option::of(any::<String>()),
+        option::of(currency_unit()),
+    )
+        .prop_map(|(token, memo, unit)| Token {
+            token: token
+                .into_iter()
+                .map(|(mint, proofs)| MintProofs::new(mint, proofs))
+                .collect(),
+            memo,
+            unit,
+        })
+}
+
+macro_rules! impl_arbitrary {
+    ($($ty:ty => $strategy:ident),* $(,)?) => {
+        $(
+            impl Arbitrary for $ty {
+                type Parameters = ();
+                type Strategy = BoxedStrategy<Self>;
+
+                fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+                    $strategy().boxed()
+                }
+            }
+        )*
+    };
+}
+
+impl_arbitrary!(
+    Amount => amount,
+    Id => keyset_id,
+    PublicKey => public_key,
+    Secret => secret,
+    Witness => witness,
+    CurrencyUnit => currency_unit,
+    Proof => proof,
+    BlindedMessage => blinded_message,
+    MintBolt11Request => mint_request,
+    SwapRequest => swap_request,
+    Token => token,
+);
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+
+    use super::*;
+    use crate::nuts::TokenV4;
+
+    fn round_trip<T>(value: &T) -> T
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        serde_json::from_str(&serde_json::to_string(value).unwrap()).unwrap()
+    }
+
+    proptest! {
+        #[test]
+        fn prop_amount_round_trip(amount in any::<Amount>()) {
+            prop_assert_eq!(round_trip(&amount), amount);
+        }
+
+        #[test]
+        fn prop_proof_round_trip(proof in any::<Proof>()) {
+            prop_assert_eq!(round_trip(&proof), proof);
+        }
+
+        #[test]
+        fn prop_blinded_message_round_trip(blinded_message in any::<BlindedMessage>()) {
+            prop_assert_eq!(round_trip(&blinded_message), blinded_message);
+        }
+
+        #[test]
+        fn prop_mint_request_round_trip(request in any::<MintBolt11Request>()) {
+            prop_assert_eq!(round_trip(&request), request);
+        }
+
+        #[test]
+        fn prop_swap_request_round_trip(request in any::<SwapRequest>()) {
+            prop_assert_eq!(round_trip(&request), request);
+        }
+
+        #[test]
+        fn prop_token_round_trip(token in any::<Token>()) {
+            prop_assert_eq!(round_trip(&token), token.clone());
+            prop_assert_eq!(Token::from_str(&token.to_string()).unwrap(), token);
+        }
+
+        #[test]
+        fn prop_token_v4_round_trip(token in any::<Token>()) {
+            if let Ok(token) = TokenV4::try_from(token) {
+                prop_assert_eq!(TokenV4::from_str(&token.to_string()).unwrap(), token);
+            }
+        }
+    }
+}
@@ -0,0 +1,591 @@
+//! Scriptable Lightning backend
+//!
+//! [FakeLnBackend] issues invoices whose payment hashes and preimages
+//! follow from a counter, so tests can build invoices without bech32
+//! fixtures. What happens to an invoice or a payment is scripted per payment
+//! hash, and every call is recorded. Paying an invoice the backend issued
+//! settles it, so mints sharing a backend can pay each other. Offers
+//! receive only what [FakeLnBackend::pay_offer] pays them.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::{Secp256k1, SecretKey};
+use lightning_invoice::{Currency, InvoiceBuilder, PaymentSecret};
+use tokio::sync::Notify;
+
+use crate::lightning::{Error, LnBackend, Payment, PaymentStatus};
+use crate::nuts::CurrencyUnit;
+use crate::types::{InvoiceDescription, InvoiceStatus};
+use crate::util::unix_time;
+use crate::{Amount, Bolt11Invoice, Sha256};
+
+/// What [LnBackend::invoice_status] reports for an invoice
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvoiceScript {
+    /// Unpaid until [FakeLnBackend::settle_invoice], the default
+    Unpaid,
+    /// Paid from the `n`th poll on, immediately for 0
+    SettleAfter(u32),
+    Expired,
+}
+
+/// What [LnBackend::pay_invoice] does for a payment hash
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaymentScript {
+    /// Pay with `fee_msat` of routing fees, or fail if that is more than
+    /// the maximum fee
+    Pay { fee_msat: u64 },
+    /// Fail with [Error::PaymentFailed]
+    Fail(String),
+    /// Return a [Error::Backend], the payment state is unknown and reported
+    /// pending by [LnBackend::payment_status]
+    Unknown(String),
+    /// Wait until [FakeLnBackend::trigger] replaces the script
+    Hang,
+    /// Pay, but with a preimage that does not match the payment hash
+    WrongPreimage,
+}
+
+impl Default for PaymentScript {
+    fn default() -> Self {
+        Self::Pay { fee_msat: 0 }
+    }
+}
+
+/// Call made to a [FakeLnBackend]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Call {
+    EstimateFee {
+        payment_hash: Sha256,
+        amount_msat: u64,
+    },
+    CreateInvoice {
+        amount: Amount,
+        unit: CurrencyUnit,
+        description: Option<InvoiceDescription>,
+        expiry: u64,
+    },
+    PayInvoice {
+        payment_hash: Sha256,
+        amount_msat: u64,
+        max_fee: Amount,
+    },
+    InvoiceStatus(Sha256),
+    PaymentStatus(Sha256),
+    CreateOffer {
+        amount: Option<Amount>,
+        unit: CurrencyUnit,
+        description: Option<String>,
+    },
+    OfferPaymentReceived(String),
+}
+
+#[derive(Debug, Default)]
+struct State {
+    counter: u64,
+    preimages: HashMap<Sha256, [u8; 32]>,
+    invoices: HashMap<Sha256, InvoiceScript>,
+    payments: HashMap<Sha256, PaymentScript>,
+    /// Outcome of payments made, or set by [FakeLnBackend::set_payment_status]
+    payment_status: HashMap<Sha256, PaymentStatus>,
+    /// Total received by each offer
+    offers: HashMap<String, Amount>,
+    default_payment: PaymentScript,
+    fee_estimate: Option<Amount>,
+    offline: bool,
+    calls: Vec<Call>,
+}
+
+/// [LnBackend] whose invoices and payments are scripted by the test
+#[derive(Debug, Default)]
+pub struct FakeLnBackend {
+    state: Mutex<State>,
+    triggered: Notify,
+}
+
+impl FakeLnBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Invoice for `amount_msat`, `None` for an amountless one
+    ///
+    /// The `n`th invoice of a backend always has the same payment hash and
+    /// preimage, only its timestamp differs between runs.
+    pub fn invoice(&self, amount_msat: Option<u64>) -> Bolt11Invoice {
+        self.build_invoice(
+            amount_msat,
+            InvoiceDescription::Text("fake".to_string()),
+            Duration::from_secs(3600),
+        )
+    }
+
+    fn build_invoice(
+        &self,
+        amount_msat: Option<u64>,
+        description: InvoiceDescription,
+        expiry: Duration,
+    ) -> Bolt11Invoice {
+        let mut state = self.state();
+        state.counter += 1;
+
+        let preimage = sha256::Hash::hash(&state.counter.to_be_bytes()).to_byte_array();
+        let payment_hash = sha256::Hash::hash(&preimage);
+        state.preimages.insert(payment_hash, preimage);
+
+        let private_key = SecretKey::from_slice(&[42; 32]).expect("Valid key");
+        let builder = InvoiceBuilder::new(Currency::Regtest);
+        let builder = match description {
+            InvoiceDescription::Text(description) => builder.description(description),
+            InvoiceDescription::Hash(hash) => {
+                builder.description_hash(sha256::Hash::from_byte_array(hash))
+            }
+        };
+        let builder = builder
+            .payment_hash(payment_hash)
+            .payment_secret(PaymentSecret([42; 32]))
+            .current_timestamp()
+            .expiry_time(expiry)
+            .min_final_cltv_expiry_delta(144);
+
+        let builder = match amount_msat {
+            Some(amount_msat) => builder.amount_milli_satoshis(amount_msat),
+            None => builder,
+        };
+
+        builder
+            .build_signed(|hash| Secp256k1::new().sign_ecdsa_recoverable(hash, &private_key))
+            .expect("Invoice fields are complete")
+    }
+
+    /// Hex preimage of an invoice issued by this backend
+    pub fn preimage(&self, payment_hash: &Sha256) -> Option<String> {
+        self.state().preimages.get(payment_hash).map(hex)
+    }
+
+    pub fn script_invoice(&self, payment_hash: Sha256, script: InvoiceScript) {
+        self.state().invoices.insert(payment_hash, script);
+    }
+
+    /// Mark an invoice paid
+    pub fn settle_invoice(&self, payment_hash: Sha256) {
+        self.script_invoice(payment_hash, InvoiceScript::SettleAfter(0));
+    }
+
+    pub fn script_payment(&self, payment_hash: Sha256, script: PaymentScript) {
+        self.state().payments.insert(payment_hash, script);
+    }
+
+    /// Script of payment hashes without their own, [PaymentScript::Pay]
+    /// without fee unless set
+    pub fn set_default_payment(&self, script: PaymentScript) {
+        self.state().default_payment = script;
+    }
+
+    /// Replace the script of `payment_hash` and wake payments hanging on it
+    pub fn trigger(&self, payment_hash: Sha256, script: PaymentScript) {
+        self.script_payment(payment_hash, script);
+        self.triggered.notify_waiters();
+    }
+
+    /// State [LnBackend::payment_status] reports for `payment_hash`, as
+    /// the node would after a restart
+    pub fn set_payment_status(&self, payment_hash: Sha256, status: PaymentStatus) {
+        self.state().payment_status.insert(payment_hash, status);
+    }
+
+    /// Pay `amount` to an offer, which need not have been created by this
+    /// backend
+    pub fn pay_offer(&self, offer: &str, amount: Amount) {
+        let mut state = self.state();
+        let received = state.offers.entry(offer.to_string()).or_default();
+        *received = received.saturating_add(amount);
+    }
+
+    /// Answer to [LnBackend::estimate_fee]
+    pub fn set_fee_estimate(&self, fee_estimate: Option<Amount>) {
+        self.state().fee_estimate = fee_estimate;
+    }
+
+    /// While offline every call fails with [Error::Backend]
+    pub fn set_offline(&self, offline: bool) {
+        self.state().offline = offline;
+    }
+
+    /// Calls made so far, in order
+    pub fn calls(&self) -> Vec<Call> {
+        self.state().calls.clone()
+    }
+
+    fn record(&self, call: Call) -> Result<(), Error> {
+        let mut state = self.state();
+        state.calls.push(call);
+
+        match state.offline {
+            true => Err(Error::Backend("Fake node is offline".into())),
+            false => Ok(()),
+        }
+    }
+}
+
+fn hex(bytes: &[u8; 32]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[async_trait]
+impl LnBackend for FakeLnBackend {
+    fn supports_description_hash(&self) -> bool {
+        true
+    }
+
+    async fn estimate_fee(&self, bolt11: &Bolt11Invoice, amount_msat: u64) -> Option<Amount> {
+        self.record(Call::EstimateFee {
+            payment_hash: *bolt11.payment_hash(),
+            amount_msat,
+        })
+        .ok()?;
+
+        self.state().fee_estimate
+    }
+
+    async fn create_invoice(
+        &self,
+        amount: Amount,
+        unit: &CurrencyUnit,
+        description: Option<InvoiceDescription>,
+        expiry: u64,
+    ) -> Result<Bolt11Invoice, Error> {
+        self.record(Call::CreateInvoice {
+            amount,
+            unit: unit.clone(),
+            description: description.clone(),
+            expiry,
+        })?;
+
+        let amount_msat = match unit {
+            CurrencyUnit::Sat => amount
+                .checked_to_msat()
+                .ok_or(Error::Unsupported("create_invoice over u64::MAX msat"))?,
+            CurrencyUnit::Msat => amount.into(),
+            _ => return Err(Error::Unsupported("create_invoice in this unit")),
+        };
+
+        Ok(self.build_invoice(
+            Some(amount_msat),
+            description.unwrap_or(InvoiceDescription::Text(String::new())),
+            Duration::from_secs(expiry.saturating_sub(unix_time())),
+        ))
+    }
+
+    async fn pay_invoice(
+        &self,
+        bolt11: &Bolt11Invoice,
+        amount_msat: u64,
+        max_fee: Amount,
+    ) -> Result<Payment, Error> {
+        let payment_hash = *bolt11.payment_hash();
+        self.record(Call::PayInvoice {
+            payment_hash,
+            amount_msat,
+            max_fee,
+        })?;
+
+        let script = loop {
+            // Created before the script is read, so no trigger is missed
+            let triggered = self.triggered.notified();
+
+            let script = {
+                let mut state = self.state();
+                let script = match state.payments.get(&payment_hash) {
+                    Some(script) => script.clone(),
+                    None => state.default_payment.clone(),
+                };
+                if script == PaymentScript::Hang {
+                    state
+                        .payment_status
+                        .insert(payment_hash, PaymentStatus::Pending);
+                }
+                script
+            };
+
+            if script != PaymentScript::Hang {
+                break script;
+            }
+            triggered.await;
+        };
+
+        let preimage = self
+            .preimage(&payment_hash)
+            .unwrap_or_else(|| hex(payment_hash.as_byte_array()));
+
+        let (status, result) = match script {
+            PaymentScript::Pay { fee_msat } if fee_msat > max_fee.to_msat() => (
+                PaymentStatus::Failed,
+                Err(Error::PaymentFailed("Fee exceeds the maximum".to_string())),
+            ),
+            PaymentScript::Pay { fee_msat } => {
+                let payment = Payment {
+                    preimage,
+                    total_spent_msat: amount_msat + fee_msat,
+                };
+                (PaymentStatus::Paid(payment.clone()), Ok(payment))
+            }
+            PaymentScript::WrongPreimage => {
+                let payment = Payment {
+                    preimage: hex(&[0; 32]),
+                    total_spent_msat: amount_msat,
+                };
+                (PaymentStatus::Paid(payment.clone()), Ok(payment))
+            }
+            PaymentScript::Fail(reason) => {
+                (PaymentStatus::Failed, Err(Error::PaymentFailed(reason)))
+            }
+            PaymentScript::Unknown(reason) => {
+                (PaymentStatus::Pending, Err(Error::Backend(reason.into())))
+            }
+            PaymentScript::Hang => unreachable!("Waited for a trigger"),
+        };
+
+        let mut state = self.state();
+        if matches!(status, PaymentStatus::Paid(_)) && state.preimages.contains_key(&payment_hash) {
+            state
+                .invoices
+                .insert(payment_hash, InvoiceScript::SettleAfter(0));
+        }
+        state.payment_status.insert(payment_hash, status);
+        result
+    }
+
+    async fn invoice_status(&self, payment_hash: &Sha256) -> Result<InvoiceStatus, Error> {
+        self.record(Call::InvoiceStatus(*payment_hash))?;
+
+        let mut state = self.state();
+        let script = state
+            .invoices
+            .entry(*payment_hash)
+            .or_insert(InvoiceScript::Unpaid);
+
+        Ok(match script {
+            InvoiceScript::Unpaid => InvoiceStatus::Unpaid,
+            InvoiceScript::Expired => InvoiceStatus::Expired,
+            InvoiceScript::SettleAfter(0) => InvoiceStatus::Paid,
+            InvoiceScript::SettleAfter(polls) => {
+                *polls -= 1;
+                InvoiceStatus::Unpaid
+            }
+        })
+    }
+
+    async fn create_offer(
+        &self,
+        amount: Option<Amount>,
+        unit: &CurrencyUnit,
+        description: Option<String>,
+    ) -> Result<String, Error> {
+        self.record(Call::CreateOffer {
+            amount,
+            unit: unit.clone(),
+            description,
+        })?;
+
+        // Numbered apart from invoices, which keep their payment hashes
+        let mut state = self.state();
+        let offer = format!("lno1fake{}", state.offers.len() + 1);
+        state.offers.insert(offer.clone(), Amount::ZERO);
+
+        Ok(offer)
+    }
+
+    async fn offer_payment_received(
+        &self,
+        offer: &str,
+        _unit: &CurrencyUnit,
+    ) -> Result<Amount, Error> {
+        self.record(Call::OfferPaymentReceived(offer.to_string()))?;
+
+        Ok(self.state().offers.get(offer).copied().unwrap_or_default())
+    }
+
+    async fn payment_status(&self, payment_hash: &Sha256) -> Result<PaymentStatus, Error> {
+        self.record(Call::PaymentStatus(*payment_hash))?;
+
+        Ok(self
+            .state()
+            .payment_status
+            .get(payment_hash)
+            .cloned()
+            .unwrap_or(PaymentStatus::Failed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn test_deterministic_invoices() {
+        let first = FakeLnBackend::new();
+        let second = FakeLnBackend::new();
+
+        let a = first.invoice(Some(1000));
+        let b = second.invoice(None);
+        assert_eq!(a.payment_hash(), b.payment_hash());
+        assert_eq!(a.amount_milli_satoshis(), Some(1000));
+        assert_eq!(b.amount_milli_satoshis(), None);
+        assert_ne!(first.invoice(Some(1000)).payment_hash(), a.payment_hash());
+
+        let preimage = first.preimage(a.payment_hash()).unwrap();
+        let preimage: Vec<u8> = (0..64)
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&preimage[i..i + 2], 16).unwrap())
+            .collect();
+        assert_eq!(&sha256::Hash::hash(&preimage), a.payment_hash());
+    }
+
+    #[tokio::test]
+    async fn test_invoice_scripts() {
+        let backend = FakeLnBackend::new();
+        let invoice = backend
+            .create_invoice(
+                Amount::from(10),
+                &CurrencyUnit::Sat,
+                Some(InvoiceDescription::Text("memo".to_string())),
+                unix_time() + 60,
+            )
+            .await
+            .unwrap();
+        let hash = *invoice.payment_hash();
+
+        assert_eq!(
+            backend.invoice_status(&hash).await.unwrap(),
+            InvoiceStatus::Unpaid
+        );
+        backend.script_invoice(hash, InvoiceScript::SettleAfter(2));
+        for status in [
+            InvoiceStatus::Unpaid,
+            InvoiceStatus::Unpaid,
+            InvoiceStatus::Paid,
+            InvoiceStatus::Paid,
+        ] {
+            assert_eq!(backend.invoice_status(&hash).await.unwrap(), status);
+        }
+
+        backend.set_offline(true);
+        assert!(matches!(
+            backend.invoice_status(&hash).await,
+            Err(Error::Backend(_))
+        ));
+
+        assert!(matches!(
+            &backend.calls()[0],
+            Call::CreateInvoice { amount, description: Some(InvoiceDescription::Text(description)), .. }
+                if *amount == Amount::from(10) && description == "memo"
+        ));
+        assert_eq!(backend.calls().len(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_payment_scripts() {
+        let backend = Arc::new(FakeLnBackend::new());
+        let invoice = |backend: &FakeLnBackend| backend.invoice(Some(1000));
+
+        let paid = invoice(&backend);
+        backend.script_payment(*paid.payment_hash(), PaymentScript::Pay { fee_msat: 500 });
+        let payment = backend
+            .pay_invoice(&paid, 1000, Amount::from(1))
+            .await
+            .unwrap();
+        assert_eq!(payment.total_spent_msat, 1500);
+        assert_eq!(
+            Some(payment.preimage.clone()),
+            backend.preimage(paid.payment_hash())
+        );
+        assert_eq!(
+            backend.payment_status(paid.payment_hash()).await.unwrap(),
+            PaymentStatus::Paid(payment)
+        );
+
+        let expensive = invoice(&backend);
+        backend.script_payment(
+            *expensive.payment_hash(),
+            PaymentScript::Pay { fee_msat: 1001 },
+        );
+        assert!(matches!(
+            backend.pay_invoice(&expensive, 1000, Amount::from(1)).await,
+            Err(Error::PaymentFailed(_))
+        ));
+
+        let failed = invoice(&backend);
+        backend.script_payment(
+            *failed.payment_hash(),
+            PaymentScript::Fail("no route".into()),
+        );
+        assert!(matches!(
+            backend.pay_invoice(&failed, 1000, Amount::ZERO).await,
+            Err(Error::PaymentFailed(reason)) if reason == "no route"
+        ));
+        assert_eq!(
+            backend.payment_status(failed.payment_hash()).await.unwrap(),
+            PaymentStatus::Failed
+        );
+
+        let unknown = invoice(&backend);
+        backend.script_payment(
+            *unknown.payment_hash(),
+            PaymentScript::Unknown("timeout".into()),
+        );
+        assert!(matches!(
+            backend.pay_invoice(&unknown, 1000, Amount::ZERO).await,
+            Err(Error::Backend(_))
+        ));
+        assert_eq!(
+            backend
+                .payment_status(unknown.payment_hash())
+                .await
+                .unwrap(),
+            PaymentStatus::Pending
+        );
+
+        let wrong = invoice(&backend);
+        backend.script_payment(*wrong.payment_hash(), PaymentScript::WrongPreimage);
+        let payment = backend
+            .pay_invoice(&wrong, 1000, Amount::ZERO)
+            .await
+            .unwrap();
+        assert_ne!(
+            Some(payment.preimage),
+            backend.preimage(wrong.payment_hash())
+        );
+
+        // Hang until triggered
+        let hanging = invoice(&backend);
+        let hash = *hanging.payment_hash();
+        backend.script_payment(hash, PaymentScript::Hang);
+        let payer = {
+            let backend = Arc::clone(&backend);
+            tokio::spawn(async move { backend.pay_invoice(&hanging, 1000, Amount::ZERO).await })
+        };
+        while backend.payment_status(&hash).await.unwrap() != PaymentStatus::Pending {
+            tokio::task::yield_now().await;
+        }
+        assert!(!payer.is_finished());
+        backend.trigger(hash, PaymentScript::default());
+        assert_eq!(payer.await.unwrap().unwrap().total_spent_msat, 1000);
+
+        assert!(backend.calls().contains(&Call::PayInvoice {
+            payment_hash: hash,
+            amount_msat: 1000,
+            max_fee: Amount::ZERO,
+        }));
+    }
+}
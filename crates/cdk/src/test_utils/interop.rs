@@ -0,0 +1,432 @@
+//! Conformance suite for a running mint
+//!
+//! Runs the wallet flows against `mint_url` with [Wallet] and [HttpClient],
+//! so every response has to parse into the crate's types. How invoices get
+//! paid depends on the deployment and is left to a [LightningHook].
+//!
+//! ```ignore
+//! let suite = Suite::from_env().expect("MINT_URL is set");
+//! suite.run().await?;
+//! ```
+
+use std::process::Command;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use bip39::Mnemonic;
+use thiserror::Error;
+use url::Url;
+
+use crate::cdk_database::wallet_memory::WalletMemoryDatabase;
+use crate::nuts::{CurrencyUnit, KeySet, MintQuoteBolt11Response, State, Token};
+use crate::url::UncheckedUrl;
+use crate::wallet::{ReceiveOptions, Wallet};
+use crate::{Amount, Bolt11Invoice, HttpClient};
+
+/// Step of [Suite::run]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Keys,
+    MintQuote,
+    PayInvoice,
+    Mint,
+    Swap,
+    CheckState,
+    Melt,
+    Restore,
+}
+
+#[derive(Debug, Error)]
+#[error("{step:?}: {message}")]
+pub struct Error {
+    pub step: Step,
+    pub message: String,
+}
+
+impl Error {
+    pub fn new(step: Step, message: impl ToString) -> Self {
+        Self {
+            step,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Fail `step` unless `expected == actual`
+pub fn assert_step_eq<T: PartialEq + std::fmt::Debug>(
+    step: Step,
+    what: &str,
+    expected: T,
+    actual: T,
+) -> Result<(), Error> {
+    if expected != actual {
+        return Err(Error::new(
+            step,
+            format!("{}: expected {:?}, got {:?}", what, expected, actual),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Check every keyset id is the one derived from its keys, and that the
+/// active keysets of `/v1/keysets` are the ones served by `/v1/keys`
+pub fn assert_keysets_valid(keysets: &[KeySet], active: &[KeySet]) -> Result<(), Error> {
+    for keyset in keysets.iter().chain(active) {
+        keyset
+            .verify_id()
+            .map_err(|err| Error::new(Step::Keys, err))?;
+    }
+
+    if active.is_empty() {
+        return Err(Error::new(Step::Keys, "Mint has no active keyset"));
+    }
+
+    for keyset in active {
+        if !keysets
+            .iter()
+            .any(|k| k.id == keyset.id && k.keys == keyset.keys)
+        {
+            return Err(Error::new(
+                Step::Keys,
+                format!("Keys of keyset {} not served by /v1/keys/{{id}}", keyset.id),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Lightning side of a deployment under test
+#[async_trait]
+pub trait LightningHook: Send + Sync {
+    /// Pay the invoice of a mint quote
+    async fn pay_invoice(&self, quote: &MintQuoteBolt11Response) -> Result<(), String>;
+
+    /// Invoice of `amount` sats for the mint to pay when melting
+    async fn create_invoice(&self, amount: Amount) -> Result<Bolt11Invoice, String>;
+}
+
+/// Hook running shell commands, for a regtest node next to the mint
+///
+/// `pay` is run with the invoice as `$1`, `invoice` with the amount in sats
+/// as `$1` and prints the invoice. Without `pay` the mint is expected to
+/// settle quotes by itself, as the fake wallets of reference mints do, and
+/// without `invoice` the melt invoice is signed with a throwaway key, which
+/// only such fake wallets accept.
+#[derive(Debug, Clone, Default)]
+pub struct CommandHook {
+    pub pay: Option<String>,
+    pub invoice: Option<String>,
+}
+
+impl CommandHook {
+    async fn run(command: String, arg: String) -> Result<String, String> {
+        let output = tokio::task::spawn_blocking(move || {
+            Command::new("sh")
+                .arg("-c")
+                .arg(&command)
+                .arg("sh")
+                .arg(arg)
+                .output()
+        })
+        .await
+        .map_err(|err| err.to_string())?
+        .map_err(|err| err.to_string())?;
+
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).into_owned());
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+}
+
+#[async_trait]
+impl LightningHook for CommandHook {
+    async fn pay_invoice(&self, quote: &MintQuoteBolt11Response) -> Result<(), String> {
+        match &self.pay {
+            Some(pay) => Self::run(pay.clone(), quote.request.clone())
+                .await
+                .map(|_| ()),
+            None => Ok(()),
+        }
+    }
+
+    async fn create_invoice(&self, amount: Amount) -> Result<Bolt11Invoice, String> {
+        match &self.invoice {
+            Some(invoice) => {
+                let invoice = Self::run(invoice.clone(), u64::from(amount).to_string()).await?;
+                Bolt11Invoice::from_str(&invoice).map_err(|err| err.to_string())
+            }
+            None => Ok(throwaway_invoice(amount.to_msat())),
+        }
+    }
+}
+
+/// Regtest invoice signed with a random key
+pub fn throwaway_invoice(amount_msat: u64) -> Bolt11Invoice {
+    use bitcoin::hashes::{sha256, Hash};
+    use bitcoin::secp256k1::rand::random;
+    use bitcoin::secp256k1::{Secp256k1, SecretKey};
+    use lightning_invoice::{Currency, InvoiceBuilder, PaymentSecret};
+
+    let private_key = SecretKey::new(&mut bitcoin::secp256k1::rand::thread_rng());
+
+    InvoiceBuilder::new(Currency::Regtest)
+        .description("cdk interop".to_string())
+        .payment_hash(sha256::Hash::from_byte_array(random()))
+        .payment_secret(PaymentSecret(random()))
+        .current_timestamp()
+        .min_final_cltv_expiry_delta(144)
+        .amount_milli_satoshis(amount_msat)
+        .build_signed(|hash| Secp256k1::new().sign_ecdsa_recoverable(hash, &private_key))
+        .expect("Invoice fields are complete")
+}
+
+/// Wallet flows against one mint
+pub struct Suite {
+    pub mint_url: UncheckedUrl,
+    pub hook: Arc<dyn LightningHook>,
+    /// Amount minted, must leave room for the melt and its fee reserve
+    pub amount: Amount,
+    /// Amount of the invoice melted
+    pub melt_amount: Amount,
+    /// How long to wait for a mint quote to be paid
+    pub quote_timeout: Duration,
+    pub client: HttpClient,
+}
+
+impl Suite {
+    pub fn new(mint_url: UncheckedUrl, hook: Arc<dyn LightningHook>) -> Self {
+        Self {
+            mint_url,
+            hook,
+            amount: Amount::from(64),
+            melt_amount: Amount::from(4),
+            quote_timeout: Duration::from_secs(30),
+            client: HttpClient::new(),
+        }
+    }
+
+    /// Suite for `MINT_URL` with a [CommandHook] of `INTEROP_PAY_CMD` and
+    /// `INTEROP_INVOICE_CMD`, `None` if `MINT_URL` is not set
+    pub fn from_env() -> Option<Self> {
+        let mint_url = UncheckedUrl::from_str(&std::env::var("MINT_URL").ok()?).ok()?;
+        let hook = CommandHook {
+            pay: std::env::var("INTEROP_PAY_CMD").ok(),
+            invoice: std::env::var("INTEROP_INVOICE_CMD").ok(),
+        };
+
+        Some(Self::new(mint_url, Arc::new(hook)))
+    }
+
+    async fn wallet(&self, mnemonic: &Mnemonic) -> Wallet {
+        Wallet::new(
+            self.client.clone(),
+            Arc::new(WalletMemoryDatabase::default()),
+            Some(mnemonic.clone()),
+        )
+        .await
+    }
+
+    /// Run every [Step] in order, stopping at the first failure
+    pub async fn run(&self) -> Result<(), Error> {
+        let mint_url = self.mint_url.clone();
+        let url: Url = (&mint_url)
+            .try_into()
+            .map_err(|err: crate::url::Error| Error::new(Step::Keys, err))?;
+        let unit = CurrencyUnit::Sat;
+
+        let mnemonic = Mnemonic::from_entropy(&bitcoin::secp256k1::rand::random::<[u8; 16]>())
+            .map_err(|err| Error::new(Step::Restore, err))?;
+        let mut wallet = self.wallet(&mnemonic).await;
+
+        // Keys
+        let keysets = self
+            .client
+            .get_mint_keysets(url.clone())
+            .await
+            .map_err(|err| Error::new(Step::Keys, err))?;
+        let mut served = Vec::new();
+        for info in keysets.keysets.iter().filter(|k| k.unit == unit) {
+            served.push(
+                self.client
+                    .get_mint_keyset(url.clone(), info.id)
+                    .await
+                    .map_err(|err| Error::new(Step::Keys, err))?,
+            );
+        }
+        let active: Vec<KeySet> = wallet
+            .get_active_mint_keys(&mint_url)
+            .await
+            .map_err(|err| Error::new(Step::Keys, err))?
+            .into_iter()
+            .filter(|k| k.unit == unit)
+            .collect();
+        assert_keysets_valid(&served, &active)?;
+
+        // Mint quote, paid by the hook
+        let quote = wallet
+            .mint_quote(mint_url.clone(), self.amount, unit.clone(), None)
+            .await
+            .map_err(|err| Error::new(Step::MintQuote, err))?;
+        let quote_response = self
+            .client
+            .get_mint_quote_status(url, &quote.id)
+            .await
+            .map_err(|err| Error::new(Step::MintQuote, err))?;
+        assert_step_eq(
+            Step::MintQuote,
+            "quote request",
+            quote.request.as_str(),
+            quote_response.request.as_str(),
+        )?;
+
+        self.hook
+            .pay_invoice(&quote_response)
+            .await
+            .map_err(|err| Error::new(Step::PayInvoice, err))?;
+        let deadline = Instant::now() + self.quote_timeout;
+        while !wallet
+            .mint_quote_paid(mint_url.clone(), &quote.id)
+            .await
+            .map_err(|err| Error::new(Step::PayInvoice, err))?
+        {
+            if Instant::now() > deadline {
+                return Err(Error::new(Step::PayInvoice, "Quote not paid in time"));
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+
+        // Mint
+        let minted = wallet
+            .mint(mint_url.clone(), &quote.id)
+            .await
+            .map_err(|err| Error::new(Step::Mint, err))?;
+        assert_step_eq(Step::Mint, "minted", self.amount, minted)?;
+
+        // Swap, by sending half to ourselves
+        let sent = wallet
+            .send(
+                &mint_url,
+                &unit,
+                Amount::from(u64::from(self.amount) / 2),
+                true,
+            )
+            .await
+            .map_err(|err| Error::new(Step::Swap, err))?;
+        let token = wallet
+            .proofs_to_token(mint_url.clone(), sent.clone(), None, Some(unit.clone()))
+            .map_err(|err| Error::new(Step::Swap, err))?;
+
+        // The token carries DLEQ proofs a receiver can check offline, and is
+        // still spendable without them
+        let mut token = Token::from_str(&token).map_err(|err| Error::new(Step::Swap, err))?;
+        for proof in token.token.iter().flat_map(|t| t.proofs.iter()) {
+            let key = active
+                .iter()
+                .find(|keyset| keyset.id == proof.keyset_id)
+                .and_then(|keyset| keyset.keys.amount_key(proof.amount))
+                .ok_or_else(|| Error::new(Step::Swap, "Proof of an inactive keyset"))?;
+            proof
+                .verify_dleq(key)
+                .map_err(|err| Error::new(Step::Swap, err))?;
+        }
+        token.strip_dleq();
+        wallet
+            .receive(&token.to_string(), ReceiveOptions::default())
+            .await
+            .map_err(|err| Error::new(Step::Swap, err))?;
+
+        // Check state, the sent proofs were spent by receiving them
+        let states = wallet
+            .check_proofs_spent(mint_url.clone(), sent.clone())
+            .await
+            .map_err(|err| Error::new(Step::CheckState, err))?;
+        assert_step_eq(Step::CheckState, "states", sent.len(), states.len())?;
+        if let Some(state) = states.iter().find(|s| s.state != State::Spent) {
+            return Err(Error::new(
+                Step::CheckState,
+                format!("Received proof {} is {:?}", state.y, state.state),
+            ));
+        }
+
+        // Melt
+        let invoice = self
+            .hook
+            .create_invoice(self.melt_amount)
+            .await
+            .map_err(|err| Error::new(Step::Melt, err))?;
+        let melt_quote = wallet
+            .melt_quote(mint_url.clone(), unit.clone(), invoice.to_string(), None)
+            .await
+            .map_err(|err| Error::new(Step::Melt, err))?;
+        assert_step_eq(
+            Step::Melt,
+            "melt amount",
+            self.melt_amount,
+            melt_quote.amount,
+        )?;
+        let melted = wallet
+            .melt(&mint_url, &melt_quote.id)
+            .await
+            .map_err(|err| Error::new(Step::Melt, err))?;
+        assert_step_eq(Step::Melt, "paid", true, melted.paid)?;
+
+        // Restore what is left into a fresh wallet from the same seed
+        let balance = wallet
+            .mint_balances()
+            .await
+            .map_err(|err| Error::new(Step::Restore, err))?
+            .remove(&mint_url)
+            .unwrap_or_default();
+        let restored = self
+            .wallet(&mnemonic)
+            .await
+            .restore(mint_url.clone())
+            .await
+            .map_err(|err| Error::new(Step::Restore, err))?;
+        assert_step_eq(Step::Restore, "restored", balance, restored)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs against the mint at `MINT_URL`, skipped when it is not set
+    #[tokio::test]
+    async fn test_interop_with_mint_url() {
+        let Some(suite) = Suite::from_env() else {
+            return;
+        };
+
+        suite.run().await.unwrap();
+    }
+
+    #[test]
+    fn test_assert_keysets_valid() {
+        let keyset: KeySet = serde_json::from_str(
+            r#"{"id":"00a1b5b5f1b56df1","unit":"sat","keys":{"1":"0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"}}"#,
+        )
+        .unwrap();
+
+        let keysets = vec![keyset.clone()];
+        let error = assert_keysets_valid(&keysets, &keysets).unwrap_err();
+        assert_eq!(error.step, Step::Keys);
+
+        let derived = KeySet {
+            id: crate::nuts::Id::from(&keyset.keys),
+            ..keyset
+        };
+        let keysets = vec![derived];
+        assert_keysets_valid(&keysets, &keysets).unwrap();
+        assert!(assert_keysets_valid(&[], &keysets).is_err());
+        assert!(assert_keysets_valid(&[], &[]).is_err());
+    }
+}
@@ -0,0 +1,64 @@
+//! [NostrRelay] kept in memory
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+
+use crate::nostr::{Error, Event, Filter, NostrRelay};
+
+/// [NostrRelay] storing every event it is sent, can be taken offline
+#[derive(Debug, Default)]
+pub struct MemoryRelay {
+    url: String,
+    events: Mutex<Vec<Event>>,
+    offline: AtomicBool,
+}
+
+impl MemoryRelay {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Every published event, oldest first
+    pub fn events(&self) -> Vec<Event> {
+        self.events.lock().expect("lock").clone()
+    }
+
+    /// Fail every request while `offline`
+    pub fn set_offline(&self, offline: bool) {
+        self.offline.store(offline, Ordering::SeqCst);
+    }
+
+    fn check_online(&self) -> Result<(), Error> {
+        match self.offline.load(Ordering::SeqCst) {
+            true => Err(Error::Relay(format!("{} is offline", self.url))),
+            false => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl NostrRelay for MemoryRelay {
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    async fn publish(&self, event: &Event) -> Result<(), Error> {
+        self.check_online()?;
+        self.events.lock().expect("lock").push(event.clone());
+        Ok(())
+    }
+
+    async fn fetch(&self, filter: &Filter) -> Result<Vec<Event>, Error> {
+        self.check_online()?;
+        Ok(self
+            .events()
+            .into_iter()
+            .filter(|event| filter.matches(event))
+            .collect())
+    }
+}
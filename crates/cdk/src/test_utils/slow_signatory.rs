@@ -0,0 +1,94 @@
+//! [Signatory] answering after a delay, like one across the network
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::nuts::{BlindSignature, BlindedMessage, Id, KeySet, Proof, PublicKey};
+use crate::secret::Secret;
+use crate::signatory::{Error, Signatory};
+use crate::Amount;
+
+/// [Signatory] sleeping before each call to `inner`, can be made to fail
+pub struct SlowSignatory<S> {
+    inner: S,
+    delay: Duration,
+    calls: AtomicUsize,
+    failing: AtomicBool,
+}
+
+impl<S: Signatory> SlowSignatory<S> {
+    pub fn new(inner: S, delay: Duration) -> Self {
+        Self {
+            inner,
+            delay,
+            calls: AtomicUsize::new(0),
+            failing: AtomicBool::new(false),
+        }
+    }
+
+    /// Calls made, a batch counts once
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+
+    /// Fail every call with [Error::Backend] while `failing`
+    pub fn set_failing(&self, failing: bool) {
+        self.failing.store(failing, Ordering::SeqCst);
+    }
+
+    async fn call(&self) -> Result<(), Error> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(self.delay).await;
+
+        match self.failing.load(Ordering::SeqCst) {
+            true => Err(Error::Backend("Signatory unreachable".into())),
+            false => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl<S: Signatory> Signatory for SlowSignatory<S> {
+    async fn blind_sign(
+        &self,
+        keyset_id: Id,
+        amount: Amount,
+        blinded_secret: PublicKey,
+    ) -> Result<BlindSignature, Error> {
+        self.call().await?;
+        self.inner
+            .blind_sign(keyset_id, amount, blinded_secret)
+            .await
+    }
+
+    async fn verify_proof(
+        &self,
+        keyset_id: Id,
+        amount: Amount,
+        c: PublicKey,
+        secret: &Secret,
+    ) -> Result<bool, Error> {
+        self.call().await?;
+        self.inner.verify_proof(keyset_id, amount, c, secret).await
+    }
+
+    async fn keysets(&self) -> Result<Vec<KeySet>, Error> {
+        self.call().await?;
+        self.inner.keysets().await
+    }
+
+    async fn blind_sign_many(
+        &self,
+        blinded_messages: &[BlindedMessage],
+    ) -> Result<Vec<BlindSignature>, Error> {
+        self.call().await?;
+        self.inner.blind_sign_many(blinded_messages).await
+    }
+
+    async fn verify_proofs(&self, proofs: &[Proof]) -> Result<Vec<bool>, Error> {
+        self.call().await?;
+        self.inner.verify_proofs(proofs).await
+    }
+}
@@ -0,0 +1,284 @@
+//! Mint serving [NUT-17] subscriptions and mint quote states on localhost
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc};
+use url::Url;
+
+use crate::client::ws::{
+    accept_key, read_frame, write_frame, Frame, OPCODE_CLOSE, OPCODE_PING, OPCODE_PONG, OPCODE_TEXT,
+};
+use crate::nuts::nut17::{WsMethod, WsRequest};
+use crate::nuts::{MintQuoteBolt11Response, PublicKey, State, SubscriptionKind};
+
+#[derive(Debug, Clone)]
+enum Command {
+    /// Payload for the subscriptions of `kind` with `filter`
+    Notify {
+        kind: SubscriptionKind,
+        filter: String,
+        payload: Value,
+    },
+    Disconnect,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    requests: Vec<WsRequest>,
+    /// Filters of each open subscription, by sub id
+    subscriptions: HashMap<String, (SubscriptionKind, Vec<String>)>,
+    paid: bool,
+    polls: usize,
+}
+
+/// Mint on a random local port accepting WebSockets at `v1/ws` and
+/// answering `GET v1/mint/quote/bolt11/{quote}`
+///
+/// Subscriptions without filters are refused.
+pub struct WsMint {
+    url: Url,
+    state: Arc<Mutex<Shared>>,
+    commands: broadcast::Sender<Command>,
+}
+
+impl WsMint {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let url =
+            Url::parse(&format!("http://{}", listener.local_addr().expect("addr"))).expect("url");
+        let state = Arc::new(Mutex::new(Shared::default()));
+        let (commands, _) = broadcast::channel(64);
+
+        let mint = Self {
+            url,
+            state: Arc::clone(&state),
+            commands: commands.clone(),
+        };
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, Arc::clone(&state), commands.subscribe()));
+            }
+        });
+
+        mint
+    }
+
+    pub fn url(&self) -> Url {
+        self.url.clone()
+    }
+
+    /// Every request received over a WebSocket, oldest first
+    pub fn requests(&self) -> Vec<WsRequest> {
+        self.state.lock().expect("lock").requests.clone()
+    }
+
+    /// Subscriptions not unsubscribed from
+    pub fn subscriptions(&self) -> usize {
+        self.state.lock().expect("lock").subscriptions.len()
+    }
+
+    /// Mint quote states fetched over HTTP
+    pub fn polls(&self) -> usize {
+        self.state.lock().expect("lock").polls
+    }
+
+    /// Whether quotes fetched over HTTP are paid
+    pub fn set_paid(&self, paid: bool) {
+        self.state.lock().expect("lock").paid = paid;
+    }
+
+    pub fn notify_mint_quote(&self, quote: &str, paid: bool) {
+        self.send(Command::Notify {
+            kind: SubscriptionKind::Bolt11MintQuote,
+            filter: quote.to_string(),
+            payload: serde_json::to_value(mint_quote(quote, paid)).expect("json"),
+        });
+    }
+
+    pub fn notify_proof_state(&self, y: PublicKey, state: State) {
+        self.send(Command::Notify {
+            kind: SubscriptionKind::ProofState,
+            filter: y.to_hex(),
+            payload: json!({"Y": y, "state": state, "witness": null}),
+        });
+    }
+
+    /// Drop every open connection
+    pub fn disconnect(&self) {
+        self.send(Command::Disconnect);
+    }
+
+    fn send(&self, command: Command) {
+        // No connection open is not an error
+        self.commands.send(command).ok();
+    }
+}
+
+fn mint_quote(quote: &str, paid: bool) -> MintQuoteBolt11Response {
+    MintQuoteBolt11Response {
+        quote: quote.to_string(),
+        request: "lnbc".to_string(),
+        paid,
+        expiry: 0,
+    }
+}
+
+async fn serve(
+    stream: TcpStream,
+    state: Arc<Mutex<Shared>>,
+    mut commands: broadcast::Receiver<Command>,
+) {
+    let mut stream = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    if stream.read_line(&mut request_line).await.is_err() {
+        return;
+    }
+    let mut key = None;
+    loop {
+        let mut line = String::new();
+        match stream.read_line(&mut line).await {
+            Ok(0) | Err(_) => return,
+            Ok(_) if line.trim().is_empty() => break,
+            Ok(_) => {
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("sec-websocket-key") {
+                        key = Some(value.trim().to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    let Some(key) = key else {
+        // Mint quote state
+        let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+        let quote = path.rsplit('/').next().unwrap_or_default();
+        let body = {
+            let mut state = state.lock().expect("lock");
+            state.polls += 1;
+            serde_json::to_string(&mint_quote(quote, state.paid)).expect("json")
+        };
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        stream.write_all(response.as_bytes()).await.ok();
+        return;
+    };
+
+    let response = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(&key)
+    );
+    if stream.write_all(response.as_bytes()).await.is_err() {
+        return;
+    }
+
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let (frames, mut incoming) = mpsc::channel::<Frame>(16);
+    let reading = tokio::spawn(async move {
+        while let Ok(frame) = read_frame(&mut reader).await {
+            if frames.send(frame).await.is_err() {
+                break;
+            }
+        }
+    });
+    // Subscriptions of this connection
+    let mut subscriptions: HashMap<String, (SubscriptionKind, Vec<String>)> = HashMap::new();
+
+    loop {
+        let reply = tokio::select! {
+            frame = incoming.recv() => match frame {
+                Some(frame) if frame.opcode == OPCODE_TEXT => {
+                    let Ok(request) = serde_json::from_slice::<WsRequest>(&frame.payload) else {
+                        continue;
+                    };
+                    state.lock().expect("lock").requests.push(request.clone());
+                    vec![handle(request, &state, &mut subscriptions)]
+                }
+                Some(frame) if frame.opcode == OPCODE_PING => {
+                    write_frame(&mut writer, OPCODE_PONG, &frame.payload, false).await.ok();
+                    continue;
+                }
+                Some(frame) if frame.opcode != OPCODE_CLOSE => continue,
+                _ => break,
+            },
+            command = commands.recv() => match command {
+                Ok(Command::Notify { kind, filter, payload }) => subscriptions
+                    .iter()
+                    .filter(|(_, (sub_kind, filters))| *sub_kind == kind && filters.contains(&filter))
+                    .map(|(sub_id, _)| json!({
+                        "jsonrpc": "2.0",
+                        "method": "subscribe",
+                        "params": {"subId": sub_id, "payload": payload},
+                    }))
+                    .collect(),
+                Ok(Command::Disconnect) | Err(_) => break,
+            },
+        };
+
+        for message in reply {
+            if write_frame(
+                &mut writer,
+                OPCODE_TEXT,
+                message.to_string().as_bytes(),
+                false,
+            )
+            .await
+            .is_err()
+            {
+                break;
+            }
+        }
+    }
+
+    reading.abort();
+}
+
+/// Response to `request`, recording the subscriptions it opens or closes
+fn handle(
+    request: WsRequest,
+    state: &Mutex<Shared>,
+    subscriptions: &mut HashMap<String, (SubscriptionKind, Vec<String>)>,
+) -> Value {
+    let sub_id = match request.method {
+        WsMethod::Subscribe(params) if params.filters.is_empty() => {
+            return json!({
+                "jsonrpc": "2.0",
+                "error": {"code": -32602, "message": "Invalid params"},
+                "id": request.id,
+            });
+        }
+        WsMethod::Subscribe(params) => {
+            let subscription = (params.kind, params.filters);
+            subscriptions.insert(params.sub_id.clone(), subscription.clone());
+            state
+                .lock()
+                .expect("lock")
+                .subscriptions
+                .insert(params.sub_id.clone(), subscription);
+            params.sub_id
+        }
+        WsMethod::Unsubscribe(params) => {
+            subscriptions.remove(&params.sub_id);
+            state
+                .lock()
+                .expect("lock")
+                .subscriptions
+                .remove(&params.sub_id);
+            params.sub_id
+        }
+    };
+
+    json!({
+        "jsonrpc": "2.0",
+        "result": {"status": "OK", "subId": sub_id},
+        "id": request.id,
+    })
+}
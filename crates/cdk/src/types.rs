@@ -1,10 +1,14 @@
 //! Types
 
+use std::str::FromStr;
+
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::nuts::{CurrencyUnit, Proofs};
-use crate::Amount;
+use crate::nuts::{BlindedMessage, CurrencyUnit, MeltQuoteState, PaymentMethod, Proofs, PublicKey};
+use crate::secret::Secret;
+use crate::url::UncheckedUrl;
+use crate::{Amount, Bolt11Invoice, Sha256, UnitAmount};
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ProofsStatus {
@@ -18,6 +22,8 @@ pub struct Melted {
     pub paid: bool,
     pub preimage: Option<String>,
     pub change: Option<Proofs>,
+    /// Lightning fee the mint reports charging, [None] for older mints
+    pub fee_paid: Option<Amount>,
 }
 
 /// Possible states of an invoice
@@ -29,20 +35,67 @@ pub enum InvoiceStatus {
     InFlight,
 }
 
+/// What the invoice of a mint quote commits to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvoiceDescription {
+    /// Description shown to the payer
+    Text(String),
+    /// SHA-256 of a description the payer learns elsewhere, as with LNURL-pay
+    Hash([u8; 32]),
+}
+
 /// Mint Quote Info
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct MintQuote {
     pub id: String,
+    /// Amount to pay, zero for a bolt12 quote the payer chooses the amount
+    /// of
     pub amount: Amount,
     pub unit: CurrencyUnit,
     pub request: String,
     pub paid: bool,
     pub expiry: u64,
+    /// Description the invoice was created with
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Description hash the invoice was created with instead
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::serde_utils::option_hash_hex"
+    )]
+    pub description_hash: Option<[u8; 32]>,
+    /// Whether ecash has been issued for the quote
+    #[serde(default)]
+    pub issued: bool,
+    /// Method [MintQuote::request] is paid with
+    ///
+    /// A bolt12 quote is an offer that can be paid any number of times, it
+    /// is paid once anything is received and never marked issued.
+    #[serde(default)]
+    pub method: PaymentMethod,
+    /// Received over the offer of a bolt12 quote so far
+    #[serde(default)]
+    pub amount_paid: Amount,
+    /// Issued for a bolt12 quote so far
+    #[serde(default)]
+    pub amount_issued: Amount,
 }
 
 impl MintQuote {
-    pub fn new(request: String, unit: CurrencyUnit, amount: Amount, expiry: u64) -> Self {
+    pub fn new(
+        request: String,
+        unit: CurrencyUnit,
+        amount: Amount,
+        expiry: u64,
+        description: Option<InvoiceDescription>,
+    ) -> Self {
         let id = Uuid::new_v4();
+        let (description, description_hash) = match description {
+            Some(InvoiceDescription::Text(description)) => (Some(description), None),
+            Some(InvoiceDescription::Hash(hash)) => (None, Some(hash)),
+            None => (None, None),
+        };
 
         Self {
             id: id.to_string(),
@@ -51,8 +104,56 @@ impl MintQuote {
             request,
             paid: false,
             expiry,
+            description,
+            description_hash,
+            issued: false,
+            method: PaymentMethod::Bolt11,
+            amount_paid: Amount::ZERO,
+            amount_issued: Amount::ZERO,
+        }
+    }
+
+    /// Quote paid to the BOLT12 `offer`, of `amount` or of any amount the
+    /// payer chooses for [None]
+    pub fn new_bolt12(
+        offer: String,
+        unit: CurrencyUnit,
+        amount: Option<Amount>,
+        expiry: u64,
+        description: Option<String>,
+    ) -> Self {
+        Self {
+            method: PaymentMethod::Bolt12,
+            ..Self::new(
+                offer,
+                unit,
+                amount.unwrap_or(Amount::ZERO),
+                expiry,
+                description.map(InvoiceDescription::Text),
+            )
+        }
+    }
+
+    /// Amount ecash can be issued for, received but not yet issued
+    pub fn mintable(&self) -> Amount {
+        match self.method {
+            PaymentMethod::Bolt12 => self.amount_paid.saturating_sub(self.amount_issued),
+            _ if self.paid && !self.issued => self.amount,
+            _ => Amount::ZERO,
         }
     }
+
+    /// [MintQuote::amount] in [MintQuote::unit]
+    pub fn unit_amount(&self) -> UnitAmount {
+        UnitAmount::new(self.amount, self.unit.clone())
+    }
+
+    /// Payment hash of [MintQuote::request], if it is a bolt11 invoice
+    pub fn payment_hash(&self) -> Option<Sha256> {
+        Bolt11Invoice::from_str(&self.request)
+            .ok()
+            .map(|invoice| *invoice.payment_hash())
+    }
 }
 
 /// Melt Quote Info
@@ -65,6 +166,40 @@ pub struct MeltQuote {
     pub fee_reserve: Amount,
     pub paid: bool,
     pub expiry: u64,
+    /// Whether the payment is in flight
+    #[serde(default)]
+    pub pending: bool,
+    /// Secrets of the inputs held pending while the payment is in flight
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reserved_inputs: Vec<Secret>,
+    /// Change outputs of the melt the inputs are reserved for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reserved_outputs: Option<Vec<BlindedMessage>>,
+    /// Msat to send over Lightning, the invoice amount or the amount
+    /// requested for an amountless invoice
+    ///
+    /// For sat quotes `amount` is this rounded up to the next sat. [None]
+    /// for quotes created without it and quotes stored before it was
+    /// recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount_msat: Option<u64>,
+    /// What the fee reserve was computed by
+    #[serde(default)]
+    pub fee_estimator: FeeEstimator,
+    /// Preimage of the invoice once paid, [None] for internal melts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payment_preimage: Option<String>,
+}
+
+/// Source of the fee reserve of a [MeltQuote]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeEstimator {
+    /// Static fee reserve policy of the mint
+    #[default]
+    Policy,
+    /// Estimate of the Lightning backend, plus a safety margin
+    Backend,
 }
 
 impl MeltQuote {
@@ -85,6 +220,260 @@ impl MeltQuote {
             fee_reserve,
             paid: false,
             expiry,
+            pending: false,
+            reserved_inputs: Vec::new(),
+            reserved_outputs: None,
+            amount_msat: None,
+            fee_estimator: FeeEstimator::Policy,
+            payment_preimage: None,
+        }
+    }
+
+    /// Same as [MeltQuote::new] for an invoice of `amount_msat`
+    pub fn new_msat(
+        request: String,
+        unit: CurrencyUnit,
+        amount_msat: u64,
+        fee_reserve: Amount,
+        expiry: u64,
+    ) -> Self {
+        Self {
+            amount_msat: Some(amount_msat),
+            // The wallet pays whole sats, rounded up to cover the invoice
+            ..Self::new(
+                request,
+                unit,
+                Amount::from_msat_ceil(amount_msat),
+                fee_reserve,
+                expiry,
+            )
+        }
+    }
+
+    /// Whether the quote is paid, in flight or can still be melted
+    pub fn state(&self) -> MeltQuoteState {
+        match (self.paid, self.pending) {
+            (true, _) => MeltQuoteState::Paid,
+            (false, true) => MeltQuoteState::Pending,
+            (false, false) => MeltQuoteState::Unpaid,
+        }
+    }
+
+    /// [MeltQuote::amount] in [MeltQuote::unit]
+    pub fn unit_amount(&self) -> UnitAmount {
+        UnitAmount::new(self.amount, self.unit.clone())
+    }
+
+    /// [MeltQuote::fee_reserve] in [MeltQuote::unit]
+    pub fn unit_fee_reserve(&self) -> UnitAmount {
+        UnitAmount::new(self.fee_reserve, self.unit.clone())
+    }
+}
+
+/// Split of the inputs of a settled sat melt, see [MeltSettlement::new]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MeltSettlement {
+    /// Msat sent over Lightning, the partial amount of an MPP melt
+    pub amount_msat: u64,
+    /// Lightning fee in msat
+    pub fee_msat: u64,
+    /// Sats of the inputs spent, `amount_msat + fee_msat` rounded up
+    pub charged: Amount,
+    /// Sats of the inputs returned as change
+    pub change: Amount,
+    /// Msat of `charged` not spent over Lightning, kept by the mint
+    pub remainder_msat: u64,
+}
+
+impl MeltSettlement {
+    /// Split `proofs` sats for a payment of `amount_msat` plus `fee_msat`
+    ///
+    /// `change = proofs - ceil((amount_msat + fee_msat) / 1000)`, the
+    /// fraction of a sat rounded up is [MeltSettlement::remainder_msat].
+    /// [None] if the proofs do not cover the payment.
+    pub fn new(proofs: Amount, amount_msat: u64, fee_msat: u64) -> Option<Self> {
+        let spent_msat = amount_msat.checked_add(fee_msat)?;
+        let charged = Amount::from_msat_ceil(spent_msat);
+
+        Some(Self {
+            amount_msat,
+            fee_msat,
+            charged,
+            change: proofs.checked_sub(charged)?,
+            // `charged.to_msat() - spent_msat`, without overflowing when
+            // `charged` is the sat above `u64::MAX` msat
+            remainder_msat: (Amount::MSAT_PER_SAT - spent_msat % Amount::MSAT_PER_SAT)
+                % Amount::MSAT_PER_SAT,
+        })
+    }
+
+    /// Sats of [MeltSettlement::charged] over the amount rounded up, the
+    /// Lightning fee with the sub-sat remainder it rounds up to
+    pub fn fee_paid(&self) -> Amount {
+        self.charged
+            .saturating_sub(Amount::from_msat_ceil(self.amount_msat))
+    }
+}
+
+/// Entry of the wallet history
+///
+/// Tokens are referred to by the `Y`s of their proofs, never by their
+/// secrets, and melts do not keep the payment preimage.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transaction {
+    pub id: String,
+    pub direction: TransactionDirection,
+    /// Value received or sent, without fees
+    pub amount: Amount,
+    /// Fees paid to the mint or for the Lightning payment
+    pub fee: Amount,
+    pub unit: CurrencyUnit,
+    pub mint: UncheckedUrl,
+    /// Unix time the operation started
+    pub timestamp: u64,
+    pub kind: TransactionKind,
+    pub memo: Option<String>,
+    pub status: TransactionStatus,
+    /// Mint or melt quote of the operation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_id: Option<String>,
+    /// `Y`s of the proofs sent or received
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ys: Vec<PublicKey>,
+}
+
+impl Transaction {
+    /// Settled transaction of `kind`, in the direction value moves for it
+    pub fn new(
+        kind: TransactionKind,
+        mint: UncheckedUrl,
+        amount: Amount,
+        unit: CurrencyUnit,
+        timestamp: u64,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            direction: kind.direction(),
+            amount,
+            fee: Amount::ZERO,
+            unit,
+            mint,
+            timestamp,
+            kind,
+            memo: None,
+            status: TransactionStatus::Settled,
+            quote_id: None,
+            ys: Vec::new(),
+        }
+    }
+}
+
+/// Operation recorded by a [Transaction]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionKind {
+    Mint,
+    Send,
+    Receive,
+    Melt,
+    /// Swap of the wallet's own proofs
+    Swap,
+}
+
+impl TransactionKind {
+    /// Which way value moves for operations of this kind
+    pub fn direction(&self) -> TransactionDirection {
+        match self {
+            Self::Mint | Self::Receive => TransactionDirection::Incoming,
+            Self::Send | Self::Melt => TransactionDirection::Outgoing,
+            Self::Swap => TransactionDirection::Internal,
         }
     }
 }
+
+/// Which way value moved in a [Transaction]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionDirection {
+    /// Value came into the wallet
+    Incoming,
+    /// Value and fees left the wallet
+    Outgoing,
+    /// Value stayed in the wallet, only fees left it
+    Internal,
+}
+
+/// State of a [Transaction]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionStatus {
+    /// Payment is in flight
+    Pending,
+    Settled,
+    /// Payment failed, no value moved
+    Failed,
+}
+
+/// Which transactions `Wallet::transactions` returns
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TransactionFilter {
+    pub mint: Option<UncheckedUrl>,
+    pub kind: Option<TransactionKind>,
+    /// Earliest timestamp, inclusive
+    pub since: Option<u64>,
+    /// Latest timestamp, exclusive
+    pub until: Option<u64>,
+}
+
+impl TransactionFilter {
+    pub fn matches(&self, transaction: &Transaction) -> bool {
+        self.mint
+            .as_ref()
+            .map_or(true, |mint| mint == &transaction.mint)
+            && self.kind.map_or(true, |kind| kind == transaction.kind)
+            && self
+                .since
+                .map_or(true, |since| transaction.timestamp >= since)
+            && self
+                .until
+                .map_or(true, |until| transaction.timestamp < until)
+    }
+}
+
+/// Token received by a wallet, known by the fingerprint of its proofs
+///
+/// The fingerprint is the SHA-256 of the sorted `Y`s, so the same proofs are
+/// recognized whatever token they are encoded in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReceivedToken {
+    pub fingerprint: Sha256,
+    /// `Y`s of the proofs, sorted
+    pub ys: Vec<PublicKey>,
+    /// Value of the proofs, before fees
+    pub amount: Amount,
+    /// Unix time the token was received
+    pub timestamp: u64,
+}
+
+impl ReceivedToken {
+    pub fn new(mut ys: Vec<PublicKey>, amount: Amount, timestamp: u64) -> Self {
+        ys.sort_unstable();
+        ys.dedup();
+
+        Self {
+            fingerprint: Self::fingerprint(&ys),
+            ys,
+            amount,
+            timestamp,
+        }
+    }
+
+    /// Fingerprint of the proofs with the sorted `ys`
+    pub fn fingerprint(ys: &[PublicKey]) -> Sha256 {
+        use bitcoin::hashes::Hash;
+
+        let bytes: Vec<u8> = ys.iter().flat_map(|y| y.to_bytes()).collect();
+
+        Sha256::hash(&bytes)
+    }
+}
@@ -105,4 +105,56 @@ mod tests {
         // assert_eq!(relay,
         // serde_json::to_string(&unchecked_relay_url).unwrap())
     }
+
+    #[test]
+    fn test_unchecked_url_is_not_normalized() {
+        let urls = [
+            "https://mint.example",
+            "https://mint.example/",
+            "https://MINT.example",
+            "https://mint.example:443",
+            "https://mint.example/cashu/api/v1",
+            "https://mint.example/cashu/api/v1/",
+        ];
+
+        for url in urls {
+            let unchecked = UncheckedUrl::from_str(url).unwrap();
+            assert_eq!(unchecked.to_string(), url);
+
+            let json = serde_json::to_string(&unchecked).unwrap();
+            assert_eq!(json, format!("\"{url}\""));
+            assert_eq!(
+                serde_json::from_str::<UncheckedUrl>(&json).unwrap(),
+                unchecked
+            );
+        }
+
+        for (i, a) in urls.iter().enumerate() {
+            for b in &urls[i + 1..] {
+                assert_ne!(UncheckedUrl::from(*a), UncheckedUrl::from(*b));
+            }
+        }
+
+        // Parsing normalizes, which is why the string itself is kept
+        let parsed: Url = UncheckedUrl::from("https://MINT.example:443")
+            .try_into()
+            .unwrap();
+        assert_eq!(parsed.as_str(), "https://mint.example/");
+    }
+
+    #[test]
+    fn test_unchecked_url_join() {
+        let base = UncheckedUrl::from("https://mint.example/cashu/");
+        assert_eq!(
+            base.join("v1/keys").unwrap().as_str(),
+            "https://mint.example/cashu/v1/keys"
+        );
+
+        // Without the trailing slash the last segment is replaced
+        let base = UncheckedUrl::from("https://mint.example/cashu");
+        assert_eq!(
+            base.join("v1/keys").unwrap().as_str(),
+            "https://mint.example/v1/keys"
+        );
+    }
 }
@@ -0,0 +1,411 @@
+//! Minimal CBOR
+//!
+//! The subset of [RFC 8949](https://www.rfc-editor.org/rfc/rfc8949) used by
+//! `cashuB` tokens: unsigned integers, byte and text strings, arrays, maps,
+//! booleans and null, all of definite length. Everything else is refused
+//! when decoding.
+
+use thiserror::Error;
+
+/// Max nesting of arrays and maps accepted by [decode]
+pub const MAX_DEPTH: usize = 16;
+
+/// Max data items accepted by [decode]
+///
+/// Enough for a token of several thousand proofs, while keeping what a
+/// short input can make the decoder allocate in check.
+pub const MAX_ITEMS: usize = 1 << 16;
+
+const UNSIGNED: u8 = 0;
+const BYTES: u8 = 2;
+const TEXT: u8 = 3;
+const ARRAY: u8 = 4;
+const MAP: u8 = 5;
+const SIMPLE: u8 = 7;
+
+const FALSE: u8 = 20;
+const TRUE: u8 = 21;
+const NULL: u8 = 22;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum Error {
+    #[error("CBOR: Unexpected end of input")]
+    UnexpectedEnd,
+    #[error("CBOR: Unsupported item with initial byte `{0:#04x}`")]
+    Unsupported(u8),
+    #[error("CBOR: Text is not UTF-8")]
+    InvalidUtf8,
+    #[error("CBOR: Nested deeper than {0}")]
+    TooDeep(usize),
+    #[error("CBOR: More than {0} items")]
+    TooManyItems(usize),
+    #[error("CBOR: Trailing bytes after the item")]
+    TrailingBytes,
+}
+
+/// Decoded CBOR data item
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Unsigned(u64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Value>),
+    /// Entries in the order they are written
+    Map(Vec<(Value, Value)>),
+    Bool(bool),
+    Null,
+}
+
+impl Value {
+    /// Value of the text key `key` of a map
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Self::Map(entries) => entries
+                .iter()
+                .find(|(k, _)| matches!(k, Self::Text(k) if k == key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Self::Unsigned(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Self::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Self::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Self::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// Map with text keys
+    pub fn map<K: Into<String>>(entries: impl IntoIterator<Item = (K, Value)>) -> Self {
+        Self::Map(
+            entries
+                .into_iter()
+                .map(|(k, v)| (Self::Text(k.into()), v))
+                .collect(),
+        )
+    }
+}
+
+/// Encode `value`, every length and integer in its shortest form
+pub fn encode(value: &Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_value(&mut out, value);
+    out
+}
+
+fn write_head(out: &mut Vec<u8>, major: u8, n: u64) {
+    let major = major << 5;
+    if n < 24 {
+        out.push(major | n as u8);
+    } else if n <= u8::MAX as u64 {
+        out.push(major | 24);
+        out.push(n as u8);
+    } else if n <= u16::MAX as u64 {
+        out.push(major | 25);
+        out.extend_from_slice(&(n as u16).to_be_bytes());
+    } else if n <= u32::MAX as u64 {
+        out.push(major | 26);
+        out.extend_from_slice(&(n as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&n.to_be_bytes());
+    }
+}
+
+fn write_value(out: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Unsigned(n) => write_head(out, UNSIGNED, *n),
+        Value::Bytes(bytes) => {
+            write_head(out, BYTES, bytes.len() as u64);
+            out.extend_from_slice(bytes);
+        }
+        Value::Text(text) => {
+            write_head(out, TEXT, text.len() as u64);
+            out.extend_from_slice(text.as_bytes());
+        }
+        Value::Array(values) => {
+            write_head(out, ARRAY, values.len() as u64);
+            values.iter().for_each(|v| write_value(out, v));
+        }
+        Value::Map(entries) => {
+            write_head(out, MAP, entries.len() as u64);
+            for (k, v) in entries {
+                write_value(out, k);
+                write_value(out, v);
+            }
+        }
+        Value::Bool(false) => write_head(out, SIMPLE, FALSE as u64),
+        Value::Bool(true) => write_head(out, SIMPLE, TRUE as u64),
+        Value::Null => write_head(out, SIMPLE, NULL as u64),
+    }
+}
+
+/// Bounds on what [decode_with] accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Max nesting of arrays and maps
+    pub max_depth: usize,
+    /// Max data items, counting every element and every map key and value
+    pub max_items: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_depth: MAX_DEPTH,
+            max_items: MAX_ITEMS,
+        }
+    }
+}
+
+/// Decode `bytes`, which must hold exactly one item, within the default
+/// [Limits]
+pub fn decode(bytes: &[u8]) -> Result<Value, Error> {
+    decode_with(bytes, Limits::default())
+}
+
+/// Decode `bytes`, which must hold exactly one item, within `limits`
+pub fn decode_with(bytes: &[u8], limits: Limits) -> Result<Value, Error> {
+    let mut decoder = Decoder {
+        bytes,
+        pos: 0,
+        limits,
+        items: 0,
+    };
+    let value = decoder.value(0)?;
+
+    if decoder.pos != bytes.len() {
+        return Err(Error::TrailingBytes);
+    }
+
+    Ok(value)
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    limits: Limits,
+    /// Items decoded so far
+    items: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let end = self.pos.checked_add(len).ok_or(Error::UnexpectedEnd)?;
+        let taken = self.bytes.get(self.pos..end).ok_or(Error::UnexpectedEnd)?;
+        self.pos = end;
+        Ok(taken)
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.pos
+    }
+
+    /// Major type and argument of the next item
+    fn head(&mut self) -> Result<(u8, u8, u64), Error> {
+        let initial = self.take(1)?[0];
+        let info = initial & 0x1f;
+
+        let n = match info {
+            0..=23 => info as u64,
+            24 => self.take(1)?[0] as u64,
+            25 => u16::from_be_bytes(self.take(2)?.try_into().expect("Two bytes")) as u64,
+            26 => u32::from_be_bytes(self.take(4)?.try_into().expect("Four bytes")) as u64,
+            27 => u64::from_be_bytes(self.take(8)?.try_into().expect("Eight bytes")),
+            // Reserved or indefinite length
+            _ => return Err(Error::Unsupported(initial)),
+        };
+
+        Ok((initial >> 5, initial, n))
+    }
+
+    /// Length of a string or container, refused if the input cannot hold
+    /// that many bytes or items
+    fn len(&self, n: u64) -> Result<usize, Error> {
+        usize::try_from(n)
+            .ok()
+            .filter(|len| *len <= self.remaining())
+            .ok_or(Error::UnexpectedEnd)
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, Error> {
+        self.items += 1;
+        if self.items > self.limits.max_items {
+            return Err(Error::TooManyItems(self.limits.max_items));
+        }
+
+        let (major, initial, n) = self.head()?;
+
+        Ok(match major {
+            UNSIGNED => Value::Unsigned(n),
+            BYTES => {
+                let len = self.len(n)?;
+                Value::Bytes(self.take(len)?.to_vec())
+            }
+            TEXT => {
+                let len = self.len(n)?;
+                let text = std::str::from_utf8(self.take(len)?).map_err(|_| Error::InvalidUtf8)?;
+                Value::Text(text.to_string())
+            }
+            ARRAY | MAP => {
+                if depth >= self.limits.max_depth {
+                    return Err(Error::TooDeep(self.limits.max_depth));
+                }
+                // Every item takes at least a byte
+                let len = self.len(n)?;
+                // Capacity is only reserved for items that may still come
+                let capacity = len.min(self.limits.max_items - self.items);
+
+                if major == ARRAY {
+                    let mut values = Vec::with_capacity(capacity);
+                    for _ in 0..len {
+                        values.push(self.value(depth + 1)?);
+                    }
+                    Value::Array(values)
+                } else {
+                    let mut entries = Vec::with_capacity(capacity.min(self.remaining() / 2));
+                    for _ in 0..len {
+                        let key = self.value(depth + 1)?;
+                        entries.push((key, self.value(depth + 1)?));
+                    }
+                    Value::Map(entries)
+                }
+            }
+            SIMPLE if initial & 0x1f < 24 => match n as u8 {
+                FALSE => Value::Bool(false),
+                TRUE => Value::Bool(true),
+                NULL => Value::Null,
+                _ => return Err(Error::Unsupported(initial)),
+            },
+            // Negative integers, tags, floats
+            _ => return Err(Error::Unsupported(initial)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::hex;
+
+    #[test]
+    fn test_rfc_8949_examples() {
+        for (value, encoded) in [
+            (Value::Unsigned(0), "00"),
+            (Value::Unsigned(23), "17"),
+            (Value::Unsigned(24), "1818"),
+            (Value::Unsigned(1000), "1903e8"),
+            (Value::Unsigned(1_000_000), "1a000f4240"),
+            (Value::Unsigned(u64::MAX), "1bffffffffffffffff"),
+            (Value::Bytes(vec![1, 2, 3, 4]), "4401020304"),
+            (Value::Text("IETF".to_string()), "6449455446"),
+            (Value::Text("\u{00fc}".to_string()), "62c3bc"),
+            (
+                Value::Array(vec![
+                    Value::Unsigned(1),
+                    Value::Array(vec![Value::Unsigned(2), Value::Unsigned(3)]),
+                ]),
+                "8201820203",
+            ),
+            (
+                Value::map([("a", Value::Unsigned(1)), ("b", Value::Null)]),
+                "a26161016162f6",
+            ),
+            (Value::Bool(false), "f4"),
+            (Value::Bool(true), "f5"),
+        ] {
+            let encoded = hex::decode(encoded).unwrap();
+            assert_eq!(encode(&value), encoded);
+            assert_eq!(decode(&encoded).unwrap(), value);
+        }
+
+        let long = Value::Array(vec![Value::Unsigned(1); 25]);
+        assert_eq!(&encode(&long)[..2], &[0x98, 25]);
+        assert_eq!(decode(&encode(&long)).unwrap(), long);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        for (encoded, error) in [
+            ("", Error::UnexpectedEnd),
+            ("19e8", Error::UnexpectedEnd),
+            ("4401", Error::UnexpectedEnd),
+            // Claims more items than there are bytes
+            ("9bffffffffffffffff", Error::UnexpectedEnd),
+            ("bbffffffffffffffff", Error::UnexpectedEnd),
+            ("62c328", Error::InvalidUtf8),
+            ("0000", Error::TrailingBytes),
+            // Negative integer, tag, float, indefinite length
+            ("20", Error::Unsupported(0x20)),
+            ("c074", Error::Unsupported(0xc0)),
+            ("f93c00", Error::Unsupported(0xf9)),
+            ("9fff", Error::Unsupported(0x9f)),
+            ("f7", Error::Unsupported(0xf7)),
+        ] {
+            assert_eq!(
+                decode(&hex::decode(encoded).unwrap()),
+                Err(error),
+                "{encoded}"
+            );
+        }
+
+        let mut deep = vec![0x81; MAX_DEPTH];
+        deep.push(0x00);
+        assert!(decode(&deep).is_ok());
+        let mut too_deep = vec![0x81; MAX_DEPTH + 1];
+        too_deep.push(0x00);
+        assert_eq!(decode(&too_deep), Err(Error::TooDeep(MAX_DEPTH)));
+    }
+
+    #[test]
+    fn test_decode_limits() {
+        let limits = Limits {
+            max_depth: 2,
+            max_items: 4,
+        };
+
+        // Array of three items
+        let array = hex::decode("83010203").unwrap();
+        assert!(decode_with(&array, limits).is_ok());
+        let array = hex::decode("8401020304").unwrap();
+        assert_eq!(decode_with(&array, limits), Err(Error::TooManyItems(4)));
+        // Map entries count twice
+        let map = hex::decode("a201020304").unwrap();
+        assert_eq!(decode_with(&map, limits), Err(Error::TooManyItems(4)));
+
+        let nested = hex::decode("818100").unwrap();
+        assert!(decode_with(&nested, limits).is_ok());
+        let nested = hex::decode("81818100").unwrap();
+        assert_eq!(decode_with(&nested, limits), Err(Error::TooDeep(2)));
+
+        // An array of empty arrays, each small in the input but not in memory
+        let mut wide = vec![0x9a];
+        wide.extend_from_slice(&(MAX_ITEMS as u32).to_be_bytes());
+        wide.extend(std::iter::repeat(0x80).take(MAX_ITEMS));
+        assert_eq!(decode(&wide), Err(Error::TooManyItems(MAX_ITEMS)));
+        wide.truncate(wide.len() - 1);
+        wide[1..5].copy_from_slice(&(MAX_ITEMS as u32 - 1).to_be_bytes());
+        assert!(decode(&wide).is_ok());
+    }
+}
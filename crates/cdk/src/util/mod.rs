@@ -11,6 +11,7 @@ use bitcoin::secp256k1::{All, Secp256k1};
 use instant::SystemTime;
 use once_cell::sync::Lazy;
 
+pub mod cbor;
 pub mod hex;
 
 #[cfg(target_arch = "wasm32")]
@@ -39,3 +40,18 @@ pub fn unix_time() -> u64 {
         .unwrap_or_default()
         .as_secs()
 }
+
+/// Source of the unix time locktimes are checked against
+pub trait Clock: Send + Sync {
+    fn now(&self) -> u64;
+}
+
+/// [Clock] of [unix_time]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        unix_time()
+    }
+}
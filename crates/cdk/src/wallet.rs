@@ -1,29 +1,47 @@
 //! Cashu Wallet
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::num::ParseIntError;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use bip39::Mnemonic;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tracing::{debug, warn};
+use url::Url;
 
 use crate::cdk_database::wallet_memory::WalletMemoryDatabase;
 use crate::cdk_database::{self, WalletDatabase};
-use crate::client::HttpClient;
-use crate::dhke::{construct_proofs, hash_to_curve, unblind_message};
+#[cfg(feature = "ws")]
+use crate::client::ws::Subscription;
+use crate::client::{BatchedCheckState, HttpClient};
+use crate::dhke::{construct_proofs, unblind_message};
+use crate::error::{ErrorCode, ErrorResponse};
+#[cfg(feature = "nostr")]
+use crate::nostr::{self, Event, Filter, NostrRelay};
+use crate::nuts::nut00::MintProofs;
 use crate::nuts::{
-    nut12, BlindSignature, CurrencyUnit, Id, KeySet, KeySetInfo, Keys, MintInfo, P2PKConditions,
-    PreMintSecrets, PreSwap, Proof, ProofState, Proofs, PublicKey, RestoreRequest, SigFlag,
-    SigningKey, State, SwapRequest, Token,
+    nut12, BlindSignature, CurrencyUnit, Id, KeySet, KeySetInfo, Keys, MeltQuoteBolt11Request,
+    MeltRequestBuilder, MintInfo, MintRequestBuilder, P2PKConditions, PaymentMethod, PreMelt,
+    PreMint, PreMintRequest, PreMintSecrets, PreSwap, Proof, ProofState, Proofs, ProofsMethods,
+    PublicKey, RestoreRequest, RestoreResponse, SigFlag, SigningKey, State, SwapRequestBuilder,
+    Token,
+};
+#[cfg(feature = "ws")]
+use crate::nuts::{MintQuoteBolt11Response, Notification, SubscriptionKind};
+use crate::secret::Secret;
+use crate::types::{
+    FeeEstimator, MeltQuote, Melted, MintQuote, ReceivedToken, Transaction, TransactionFilter,
+    TransactionKind, TransactionStatus,
 };
-use crate::types::{MeltQuote, Melted, MintQuote};
 use crate::url::UncheckedUrl;
-use crate::util::unix_time;
-use crate::{Amount, Bolt11Invoice};
+use crate::util::{unix_time, Clock, SystemClock};
+use crate::{Amount, Bolt11Invoice, SplitTarget};
 
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum Error {
     /// Insufficient Funds
     #[error("Insufficient Funds")]
@@ -44,8 +62,18 @@ pub enum Error {
     InvalidSpendConditions(String),
     #[error("Unknown Key")]
     UnknownKey,
+    /// Some proofs of a received token, by `Y`, were received before in
+    /// another token
+    #[error("Proofs already received: `{0:?}`")]
+    ProofsAlreadyReceived(Vec<PublicKey>),
+    /// Received proof is for an amount its keyset has no key for
+    #[error("Keyset `{keyset_id}` has no key for amount `{amount}`")]
+    InvalidProofAmount { keyset_id: Id, amount: Amount },
     #[error(transparent)]
     ParseInt(#[from] ParseIntError),
+    /// Bolt11 Invoice Error
+    #[error(transparent)]
+    Invoice(#[from] lightning_invoice::ParseOrSemanticError),
     /// Cashu Url Error
     #[error(transparent)]
     CashuUrl(#[from] crate::url::Error),
@@ -61,6 +89,45 @@ pub enum Error {
     /// Database Error
     #[error(transparent)]
     Database(#[from] crate::cdk_database::Error),
+    /// Restore needs a [SecretSource::Deterministic] wallet
+    #[error("Wallet has no seed to restore from")]
+    MnemonicRequired,
+    /// Melt change signs an output that was not sent, or is not for the
+    /// keys of its amount
+    #[error("Melt change does not match the outputs")]
+    InvalidMeltChange,
+    /// Restore response has outputs that were not requested
+    #[error("Restore response does not match the request")]
+    InvalidRestoreResponse,
+    /// Derived secret was already blinded by this wallet
+    #[error("Secret for keyset `{keyset_id}` counter `{counter}` was already used")]
+    SecretReused { keyset_id: Id, counter: u64 },
+    /// Mint cannot pay part of an invoice [NUT-15]
+    #[error("Mint `{0}` does not support multi-path payments")]
+    MppUnsupported(UncheckedUrl),
+    /// Parts of a multi-path payment do not add up to the invoice amount
+    #[error("Multi-path payment parts do not add up to the invoice amount")]
+    MppAllocationMismatch,
+    /// [OfflinePolicy::Require] send of an amount no spendable proofs add
+    /// up to
+    #[error("No spendable proofs add up to exactly `{0}`")]
+    NoExactMatch(Amount),
+    /// Spendable proofs do not cover what is sent and its fees
+    #[error("Need `{needed}` but only `{available}` is spendable")]
+    Shortfall { needed: Amount, available: Amount },
+    /// Backup was written by a newer version of the wallet
+    #[error("Unsupported wallet backup version `{0}`")]
+    UnsupportedBackupVersion(u32),
+    /// Backup is not one [Wallet::export] could have written
+    #[error("Invalid wallet backup: `{0}`")]
+    InvalidBackup(String),
+    #[cfg(feature = "nostr")]
+    #[error(transparent)]
+    Nostr(#[from] crate::nostr::Error),
+    /// [Wallet::set_nostr_keys] was not called
+    #[cfg(feature = "nostr")]
+    #[error("Wallet has no Nostr keys")]
+    NostrKeysRequired,
     #[error("`{0}`")]
     Custom(String),
 }
@@ -71,11 +138,44 @@ impl From<Error> for cdk_database::Error {
     }
 }
 
+/// Cashu Wallet
+///
+/// Mint, swap and melt bodies are constructed with [MintRequestBuilder],
+/// [SwapRequestBuilder] and [MeltRequestBuilder], so every request sent is
+/// balanced before it reaches the mint.
 #[derive(Clone)]
 pub struct Wallet {
     pub client: HttpClient,
     pub localstore: Arc<dyn WalletDatabase<Err = cdk_database::Error> + Send + Sync>,
-    mnemonic: Option<Mnemonic>,
+    secret_source: SecretSource,
+    /// Blinded secrets handed out by [Wallet::pre_mint_secrets]
+    blinded_secrets: Arc<Mutex<HashSet<PublicKey>>>,
+    clock: Arc<dyn Clock>,
+    #[cfg(feature = "nostr")]
+    nostr_keys: Option<SigningKey>,
+}
+
+/// Where the secrets of a [Wallet] come from
+///
+/// Every output the wallet blinds is created by [Wallet::pre_mint_secrets]
+/// or [Wallet::blank_pre_mint_secrets] from this source.
+#[derive(Clone)]
+pub enum SecretSource {
+    /// Random secrets and blinding factors
+    Random,
+    /// Secrets and blinding factors derived from `mnemonic` [NUT-13]
+    ///
+    /// The last counter used for each keyset is kept in the wallet database.
+    Deterministic { mnemonic: Mnemonic },
+}
+
+impl From<Option<Mnemonic>> for SecretSource {
+    fn from(mnemonic: Option<Mnemonic>) -> Self {
+        match mnemonic {
+            Some(mnemonic) => Self::Deterministic { mnemonic },
+            None => Self::Random,
+        }
+    }
 }
 
 impl Default for Wallet {
@@ -83,7 +183,11 @@ impl Default for Wallet {
         Self {
             localstore: Arc::new(WalletMemoryDatabase::default()),
             client: HttpClient::default(),
-            mnemonic: None,
+            secret_source: SecretSource::Random,
+            blinded_secrets: Arc::new(Mutex::new(HashSet::new())),
+            clock: Arc::new(SystemClock),
+            #[cfg(feature = "nostr")]
+            nostr_keys: None,
         }
     }
 }
@@ -95,15 +199,183 @@ impl Wallet {
         mnemonic: Option<Mnemonic>,
     ) -> Self {
         Self {
-            mnemonic,
+            secret_source: mnemonic.into(),
             client,
             localstore,
+            blinded_secrets: Arc::new(Mutex::new(HashSet::new())),
+            clock: Arc::new(SystemClock),
+            #[cfg(feature = "nostr")]
+            nostr_keys: None,
         }
     }
 
+    /// Time P2PK locktimes are checked at by [Wallet::claim_expired],
+    /// [SystemClock] by default
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Keys [Wallet::send_to_npub] signs messages with and
+    /// [Wallet::check_nostr_inbox] reads messages to
+    #[cfg(feature = "nostr")]
+    pub fn set_nostr_keys(&mut self, keys: SigningKey) {
+        self.nostr_keys = Some(keys);
+    }
+
     /// Back up seed
     pub fn mnemonic(&self) -> Option<Mnemonic> {
-        self.mnemonic.clone()
+        match &self.secret_source {
+            SecretSource::Deterministic { mnemonic } => Some(mnemonic.clone()),
+            SecretSource::Random => None,
+        }
+    }
+
+    pub fn secret_source(&self) -> &SecretSource {
+        &self.secret_source
+    }
+
+    /// Last counter used for keyset `keyset_id`, [None] if no secret has been
+    /// derived for it
+    pub async fn keyset_counter(&self, keyset_id: &Id) -> Result<Option<u64>, Error> {
+        Ok(self.localstore.get_keyset_counter(keyset_id).await?)
+    }
+
+    /// Set the last counter used for keyset `keyset_id`, e.g. after
+    /// restoring from seed on another device
+    ///
+    /// Secrets are derived from the next counter on. Setting a counter below
+    /// one already used makes [Wallet::pre_mint_secrets] fail with
+    /// [Error::SecretReused] for secrets the wallet still knows about.
+    pub async fn set_keyset_counter(&self, keyset_id: &Id, counter: u64) -> Result<(), Error> {
+        Ok(self
+            .localstore
+            .set_keyset_counter(keyset_id, counter)
+            .await?)
+    }
+
+    /// Outputs for `amount` in keyset `keyset_id` of `mint_url`
+    ///
+    /// Deterministic secrets advance the keyset counter before they are
+    /// returned, so a failed request never hands out the same secret twice.
+    pub async fn pre_mint_secrets(
+        &self,
+        mint_url: &UncheckedUrl,
+        keyset_id: Id,
+        amount: Amount,
+    ) -> Result<PreMintSecrets, Error> {
+        self.pre_mint_secrets_with_target(mint_url, keyset_id, amount, &SplitTarget::None)
+            .await
+    }
+
+    /// [Wallet::pre_mint_secrets] with `amount` split as `target` asks
+    pub async fn pre_mint_secrets_with_target(
+        &self,
+        mint_url: &UncheckedUrl,
+        keyset_id: Id,
+        amount: Amount,
+        target: &SplitTarget,
+    ) -> Result<PreMintSecrets, Error> {
+        self.pre_mint_secrets_with_amounts(mint_url, keyset_id, &amount.split_targeted(target))
+            .await
+    }
+
+    /// [Wallet::pre_mint_secrets] with one output for each of `amounts`
+    pub async fn pre_mint_secrets_with_amounts(
+        &self,
+        mint_url: &UncheckedUrl,
+        keyset_id: Id,
+        amounts: &[Amount],
+    ) -> Result<PreMintSecrets, Error> {
+        let pre_mint_secrets = match &self.secret_source {
+            SecretSource::Random => PreMintSecrets::random_with_amounts(keyset_id, amounts)?,
+            SecretSource::Deterministic { mnemonic } => {
+                let counter = self.next_keyset_counter(&keyset_id).await?;
+                PreMintSecrets::from_seed_with_amounts(
+                    keyset_id, counter, mnemonic, amounts, false,
+                )?
+            }
+        };
+
+        self.claim_pre_mint_secrets(mint_url, keyset_id, pre_mint_secrets)
+            .await
+    }
+
+    /// Blank outputs for NUT-08 change of up to `fee_reserve`
+    pub async fn blank_pre_mint_secrets(
+        &self,
+        mint_url: &UncheckedUrl,
+        keyset_id: Id,
+        fee_reserve: Amount,
+    ) -> Result<PreMintSecrets, Error> {
+        let pre_mint_secrets = match &self.secret_source {
+            SecretSource::Random => PreMintSecrets::blank(keyset_id, fee_reserve)?,
+            SecretSource::Deterministic { mnemonic } => {
+                let counter = self.next_keyset_counter(&keyset_id).await?;
+                let count = PreMintSecrets::blank_count(fee_reserve);
+                PreMintSecrets::restore_batch(keyset_id, mnemonic, counter, counter + count - 1)?
+            }
+        };
+
+        self.claim_pre_mint_secrets(mint_url, keyset_id, pre_mint_secrets)
+            .await
+    }
+
+    async fn next_keyset_counter(&self, keyset_id: &Id) -> Result<u64, Error> {
+        Ok(self
+            .keyset_counter(keyset_id)
+            .await?
+            .map_or(0, |counter| counter + 1))
+    }
+
+    /// Refuse secrets the wallet has already blinded or holds proofs for,
+    /// then record them and advance the keyset counter
+    async fn claim_pre_mint_secrets(
+        &self,
+        mint_url: &UncheckedUrl,
+        keyset_id: Id,
+        pre_mint_secrets: PreMintSecrets,
+    ) -> Result<PreMintSecrets, Error> {
+        let counter = match self.secret_source {
+            SecretSource::Random => None,
+            SecretSource::Deterministic { .. } => Some(self.next_keyset_counter(&keyset_id).await?),
+        };
+
+        let mut known_secrets = HashSet::new();
+        for proofs in [
+            self.localstore.get_proofs(mint_url.clone()).await?,
+            self.localstore.get_pending_proofs(mint_url.clone()).await?,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            known_secrets.extend(proofs.into_iter().map(|proof| proof.secret));
+        }
+
+        {
+            let mut blinded_secrets = self.blinded_secrets.lock().expect("lock poisoned");
+            for (i, pre_mint) in pre_mint_secrets.iter().enumerate() {
+                if known_secrets.contains(&pre_mint.secret)
+                    || blinded_secrets.contains(&pre_mint.blinded_message.blinded_secret)
+                {
+                    return Err(Error::SecretReused {
+                        keyset_id,
+                        counter: counter.unwrap_or_default() + i as u64,
+                    });
+                }
+            }
+            blinded_secrets.extend(
+                pre_mint_secrets
+                    .iter()
+                    .map(|pre_mint| pre_mint.blinded_message.blinded_secret),
+            );
+        }
+
+        if let (Some(counter), false) = (counter, pre_mint_secrets.is_empty()) {
+            self.set_keyset_counter(&keyset_id, counter + pre_mint_secrets.len() as u64 - 1)
+                .await?;
+        }
+
+        Ok(pre_mint_secrets)
     }
 
     pub async fn mint_balances(&self) -> Result<HashMap<UncheckedUrl, Amount>, Error> {
@@ -124,6 +396,63 @@ impl Wallet {
         Ok(balances)
     }
 
+    /// Spendable balance of each mint in each unit
+    ///
+    /// Read from the proof store without asking the mints. Pending proofs
+    /// are not spendable and are counted in [Wallet::inventory] instead.
+    pub async fn balances(&self) -> Result<HashMap<(UncheckedUrl, CurrencyUnit), Amount>, Error> {
+        Ok(self
+            .inventory()
+            .await?
+            .into_iter()
+            .map(|(key, inventory)| (key, inventory.spendable))
+            .collect())
+    }
+
+    /// Proofs held at each mint in each unit
+    ///
+    /// Read from the proof store without asking the mints. The unit of a
+    /// proof is that of its keyset, or the default unit if the wallet has
+    /// not stored the keyset.
+    pub async fn inventory(
+        &self,
+    ) -> Result<HashMap<(UncheckedUrl, CurrencyUnit), ProofInventory>, Error> {
+        let mut inventory: HashMap<(UncheckedUrl, CurrencyUnit), ProofInventory> = HashMap::new();
+
+        for (mint_url, _) in self.localstore.get_mints().await? {
+            let units: HashMap<Id, CurrencyUnit> = self
+                .localstore
+                .get_mint_keysets(mint_url.clone())
+                .await?
+                .unwrap_or_default()
+                .into_iter()
+                .map(|keyset| (keyset.id, keyset.unit))
+                .collect();
+            let key = |proof: &Proof| {
+                let unit = units.get(&proof.keyset_id).cloned().unwrap_or_default();
+                (mint_url.clone(), unit)
+            };
+
+            let proofs = self.localstore.get_proofs(mint_url.clone()).await?;
+            for proof in proofs.unwrap_or_default() {
+                inventory
+                    .entry(key(&proof))
+                    .or_default()
+                    .add_spendable(&proof)?;
+            }
+
+            let pending = self.localstore.get_pending_proofs(mint_url.clone()).await?;
+            for proof in pending.unwrap_or_default() {
+                inventory
+                    .entry(key(&proof))
+                    .or_default()
+                    .add_pending(&proof)?;
+            }
+        }
+
+        Ok(inventory)
+    }
+
     pub async fn get_proofs(&self, mint_url: UncheckedUrl) -> Result<Option<Proofs>, Error> {
         Ok(self.localstore.get_proofs(mint_url).await?)
     }
@@ -202,38 +531,56 @@ impl Wallet {
     }
 
     /// Check if a proof is spent
+    ///
+    /// Fails if any batch of the check does, see
+    /// [Wallet::check_proofs_states].
     pub async fn check_proofs_spent(
         &self,
         mint_url: UncheckedUrl,
         proofs: Proofs,
     ) -> Result<Vec<ProofState>, Error> {
-        let spendable = self
-            .client
-            .post_check_state(
-                mint_url.try_into()?,
-                proofs
-                    .into_iter()
-                    // Find Y for the secret
-                    .flat_map(|p| hash_to_curve(p.secret.as_bytes()))
-                    .collect::<Vec<PublicKey>>(),
-            )
-            .await?;
-
-        // Separate proofs in spent and unspent based on mint response
+        Ok(self
+            .check_proofs_states(mint_url, &proofs)
+            .await?
+            .into_result()?)
+    }
 
-        Ok(spendable.states)
+    /// State of each of `proofs`, checked in batches per the
+    /// [CheckStateBatching](crate::client::CheckStateBatching) of the client
+    ///
+    /// States are in the order of `proofs`. Batches the mint did not answer
+    /// are reported in [BatchedCheckState::failures] next to the states of
+    /// the others.
+    pub async fn check_proofs_states(
+        &self,
+        mint_url: UncheckedUrl,
+        proofs: &Proofs,
+    ) -> Result<BatchedCheckState, Error> {
+        Ok(self
+            .client
+            .check_proofs_states(mint_url.try_into()?, proofs.ys()?)
+            .await)
     }
 
     /// Mint Quote
+    ///
+    /// `description` is put on the invoice if the mint advertises support
+    /// for it in its NUT-04 settings.
     pub async fn mint_quote(
         &mut self,
         mint_url: UncheckedUrl,
         amount: Amount,
         unit: CurrencyUnit,
+        description: Option<String>,
     ) -> Result<MintQuote, Error> {
         let quote_res = self
             .client
-            .post_mint_quote(mint_url.try_into()?, amount, unit.clone())
+            .post_mint_quote(
+                mint_url.try_into()?,
+                amount,
+                unit.clone(),
+                description.clone(),
+            )
             .await?;
 
         let quote = MintQuote {
@@ -243,6 +590,12 @@ impl Wallet {
             request: quote_res.request,
             paid: quote_res.paid,
             expiry: quote_res.expiry,
+            description,
+            description_hash: None,
+            issued: false,
+            method: PaymentMethod::Bolt11,
+            amount_paid: Amount::ZERO,
+            amount_issued: Amount::ZERO,
         };
 
         self.localstore.add_mint_quote(quote.clone()).await?;
@@ -304,8 +657,45 @@ impl Wallet {
         Ok(keys)
     }
 
+    /// Whether the invoice of mint quote `quote_id` has been paid, so
+    /// [Wallet::mint] can be called
+    pub async fn mint_quote_paid(
+        &self,
+        mint_url: UncheckedUrl,
+        quote_id: &str,
+    ) -> Result<bool, Error> {
+        let quote = self
+            .client
+            .get_mint_quote_status(mint_url.try_into()?, quote_id)
+            .await?;
+
+        Ok(quote.paid)
+    }
+
+    /// Updates of mint quote `quote_id` once its invoice is paid, pushed by
+    /// the mint over a WebSocket [NUT-17]
+    #[cfg(feature = "ws")]
+    pub async fn mint_quote_paid_stream(
+        &self,
+        mint_url: UncheckedUrl,
+        quote_id: &str,
+    ) -> Result<MintQuotePaidStream, Error> {
+        let subscription = self
+            .client
+            .subscribe(
+                mint_url.try_into()?,
+                SubscriptionKind::Bolt11MintQuote,
+                vec![quote_id.to_string()],
+            )
+            .await?;
+
+        Ok(MintQuotePaidStream { subscription })
+    }
+
     /// Mint
     pub async fn mint(&mut self, mint_url: UncheckedUrl, quote_id: &str) -> Result<Amount, Error> {
+        let timestamp = self.clock.now();
+
         // Check that mint is in store of mints
         if self.localstore.get_mint(mint_url.clone()).await?.is_none() {
             self.add_mint(mint_url.clone()).await?;
@@ -325,49 +715,20 @@ impl Wallet {
 
         let active_keyset_id = self.active_mint_keyset(&mint_url, &quote_info.unit).await?;
 
-        let mut counter: Option<u64> = None;
-
-        let premint_secrets;
-
-        #[cfg(not(feature = "nut13"))]
-        {
-            premint_secrets = PreMintSecrets::random(active_keyset_id, quote_info.amount)?;
-        }
+        let premint_secrets = self
+            .pre_mint_secrets(&mint_url, active_keyset_id, quote_info.amount)
+            .await?;
 
-        #[cfg(feature = "nut13")]
-        {
-            premint_secrets = match &self.mnemonic {
-                Some(mnemonic) => {
-                    let count = self
-                        .localstore
-                        .get_keyset_counter(&active_keyset_id)
-                        .await?;
+        let PreMintRequest {
+            pre_mint_secrets: premint_secrets,
+            mint_request,
+        } = MintRequestBuilder::new(quote_id, quote_info.amount)
+            .outputs(premint_secrets)
+            .build()?;
 
-                    let count = if let Some(count) = count {
-                        count + 1
-                    } else {
-                        0
-                    };
-
-                    counter = Some(count);
-                    PreMintSecrets::from_seed(
-                        active_keyset_id,
-                        count,
-                        mnemonic,
-                        quote_info.amount,
-                        false,
-                    )?
-                }
-                None => PreMintSecrets::random(active_keyset_id, quote_info.amount)?,
-            };
-        }
         let mint_res = self
             .client
-            .post_mint(
-                mint_url.clone().try_into()?,
-                quote_id,
-                premint_secrets.clone(),
-            )
+            .post_mint(mint_url.clone().try_into()?, mint_request)
             .await?;
 
         let keys = self.get_keyset_keys(&mint_url, active_keyset_id).await?;
@@ -396,28 +757,58 @@ impl Wallet {
         // Remove filled quote from store
         self.localstore.remove_mint_quote(&quote_info.id).await?;
 
-        // Update counter for keyset
-        #[cfg(feature = "nut13")]
-        if counter.is_some() {
-            self.localstore
-                .increment_keyset_counter(&active_keyset_id, proofs.len() as u64)
-                .await?;
-        }
+        let transaction = Transaction {
+            memo: quote_info.description,
+            quote_id: Some(quote_info.id),
+            ys: proofs.ys()?,
+            ..Transaction::new(
+                TransactionKind::Mint,
+                mint_url.clone(),
+                minted_amount,
+                quote_info.unit,
+                timestamp,
+            )
+        };
 
         // Add new proofs to store
         self.localstore.add_proofs(mint_url, proofs).await?;
+        self.localstore.add_transaction(transaction).await?;
 
         Ok(minted_amount)
     }
 
-    /// Receive
-    pub async fn receive(&mut self, encoded_token: &str) -> Result<(), Error> {
+    /// Receive `encoded_token`, swapping its proofs for fresh ones split as
+    /// `options` asks
+    ///
+    /// P2PK locked proofs are signed with the keys of `options` their lock
+    /// names [NUT-11], and the outputs too if the lock is [SigFlag::SigAll].
+    ///
+    /// A token whose proofs were received before, in this or any other
+    /// encoding, is not swapped again and is reported as
+    /// [ReceiveOutcome::AlreadyReceived], also while another receive of it
+    /// is still swapping. A token sharing only some of its proofs with
+    /// received ones fails with [Error::ProofsAlreadyReceived].
+    ///
+    /// The proofs of each mint of the token are swapped and stored in turn.
+    /// If the swap at one mint fails, the parts of the mints stored before
+    /// it stay received and the rest can be received again.
+    pub async fn receive(
+        &mut self,
+        encoded_token: &str,
+        options: ReceiveOptions,
+    ) -> Result<ReceiveOutcome, Error> {
+        let timestamp = self.clock.now();
         let token_data = Token::from_str(encoded_token)?;
 
-        let unit = token_data.unit.unwrap_or_default();
+        let value = token_data.value()?;
+        let signing_keys: HashMap<String, SigningKey> = options
+            .p2pk_signing_keys
+            .into_iter()
+            .map(|key| (key.verifying_key().to_string(), key))
+            .collect();
 
-        // Verify the signature DLEQ is valid
-        // Verify that all proofs in the token have a valid DLEQ proof if one is supplied
+        // Verify that all proofs in the token are for amounts of their keyset
+        // and have a valid DLEQ proof if one is supplied, before any swap
         {
             for mint_proof in &token_data.token {
                 let mint_url = &mint_proof.mint;
@@ -425,7 +816,7 @@ impl Wallet {
 
                 for proof in proofs {
                     let keys = self.get_keyset_keys(mint_url, proof.keyset_id).await?;
-                    let key = keys.amount_key(proof.amount).ok_or(Error::UnknownKey)?;
+                    let key = proof_key(&keys, proof)?;
                     match proof.verify_dleq(key) {
                         Ok(_) | Err(nut12::Error::MissingDleqProof) => continue,
                         Err(_) => return Err(Error::CouldNotVerifyDleq),
@@ -434,33 +825,138 @@ impl Wallet {
             }
         }
 
-        let mut proofs: HashMap<UncheckedUrl, Proofs> = HashMap::new();
+        let mut ys = Vec::new();
+        for token in &token_data.token {
+            ys.extend(token.proofs.ys()?);
+        }
+        let received = ReceivedToken::new(ys, value, timestamp);
+        let fingerprint = received.fingerprint;
+
+        // Claimed before the swap, so a concurrent receive of the same proofs
+        // finds them received
+        let overlapping = self.localstore.add_received_token(received.clone()).await?;
+        if let Some(original) = overlapping
+            .iter()
+            .find(|original| original.fingerprint == fingerprint)
+        {
+            return Ok(ReceiveOutcome::AlreadyReceived {
+                amount: original.amount,
+                timestamp: original.timestamp,
+            });
+        }
+        if !overlapping.is_empty() {
+            let ys = received
+                .ys
+                .into_iter()
+                .filter(|y| overlapping.iter().any(|original| original.ys.contains(y)))
+                .collect();
+            return Err(Error::ProofsAlreadyReceived(ys));
+        }
+
+        let mut stored = Vec::new();
+        match self
+            .swap_received(
+                token_data,
+                signing_keys,
+                &options.split_target,
+                timestamp,
+                &mut stored,
+            )
+            .await
+        {
+            Ok(amount) => Ok(ReceiveOutcome::Received { amount }),
+            Err(err) => {
+                // Only the parts of the mints that were stored stay received,
+                // the rest of the token can be received again
+                self.localstore.remove_received_token(&fingerprint).await?;
+                if !stored.is_empty() {
+                    let mut ys = Vec::new();
+                    let mut amount = Amount::ZERO;
+                    for part in &stored {
+                        ys.extend(part.proofs.ys()?);
+                        amount += part.proofs.total_amount()?;
+                    }
+                    self.localstore
+                        .add_received_token(ReceivedToken::new(ys, amount, timestamp))
+                        .await?;
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Swap the proofs of the verified `token_data` and store the new ones,
+    /// returning their value
+    ///
+    /// Each mint is swapped and its new proofs stored before the next, and
+    /// its part of the token is pushed to `stored`, so the parts stored
+    /// before a failing one are known to the caller.
+    async fn swap_received(
+        &mut self,
+        token_data: Token,
+        signing_keys: HashMap<String, SigningKey>,
+        split_target: &SplitTarget,
+        timestamp: u64,
+        stored: &mut Vec<MintProofs>,
+    ) -> Result<Amount, Error> {
+        let unit = token_data.unit();
+        let mut parts: Vec<MintProofs> = Vec::new();
         for token in token_data.token {
             if token.proofs.is_empty() {
                 continue;
             }
+            match parts.iter_mut().find(|part| part.mint == token.mint) {
+                Some(part) => part.proofs.extend(token.proofs),
+                None => parts.push(token),
+            }
+        }
 
-            let active_keyset_id = self.active_mint_keyset(&token.mint, &unit).await?;
+        let mut amount = Amount::ZERO;
+        for part in parts {
+            let active_keyset_id = self.active_mint_keyset(&part.mint, &unit).await?;
 
             // TODO: if none fetch keyset for mint
 
             let keys = if let Some(keys) = self.localstore.get_keys(&active_keyset_id).await? {
                 keys
             } else {
-                self.get_keyset_keys(&token.mint, active_keyset_id).await?;
+                self.get_keyset_keys(&part.mint, active_keyset_id).await?;
                 self.localstore.get_keys(&active_keyset_id).await?.unwrap()
             };
 
-            // Sum amount of all proofs
-            let amount: Amount = token.proofs.iter().map(|p| p.amount).sum();
+            let mut token_proofs = part.proofs.clone();
+            let mut sig_all_key = None;
+            for proof in &mut token_proofs {
+                let Some(conditions) = crate::nuts::nut10::Secret::try_from(proof.secret.clone())
+                    .ok()
+                    .and_then(|secret| P2PKConditions::try_from(secret).ok())
+                else {
+                    continue;
+                };
+
+                for pubkey in &conditions.pubkeys {
+                    if let Some(key) = signing_keys.get(&pubkey.to_string()) {
+                        proof.sign_p2pk(key.clone())?;
+                        if conditions.sig_flag == SigFlag::SigAll {
+                            sig_all_key = Some(key.clone());
+                        }
+                    }
+                }
+            }
 
-            let pre_swap = self
-                .create_swap(&token.mint, &unit, Some(amount), token.proofs)
+            let mut pre_swap = self
+                .create_swap(&part.mint, &unit, None, token_proofs, split_target)
                 .await?;
 
+            if let Some(key) = sig_all_key {
+                for blinded_message in &mut pre_swap.swap_request.outputs {
+                    blinded_message.sign_p2pk(key.clone())?;
+                }
+            }
+
             let swap_response = self
                 .client
-                .post_swap(token.mint.clone().try_into()?, pre_swap.swap_request)
+                .post_swap(part.mint.clone().try_into()?, pre_swap.swap_request)
                 .await?;
 
             // Proof to keep
@@ -471,100 +967,68 @@ impl Wallet {
                 &keys,
             )?;
 
-            #[cfg(feature = "nut13")]
-            if self.mnemonic.is_some() {
-                self.localstore
-                    .increment_keyset_counter(&active_keyset_id, p.len() as u64)
-                    .await?;
-            }
-
-            let mint_proofs = proofs.entry(token.mint).or_default();
-
-            mint_proofs.extend(p);
-        }
+            let mint_amount = p.total_amount()?;
+            let transaction = Transaction {
+                memo: token_data.memo.clone(),
+                ys: part.proofs.ys()?,
+                ..Transaction::new(
+                    TransactionKind::Receive,
+                    part.mint.clone(),
+                    mint_amount,
+                    unit.clone(),
+                    timestamp,
+                )
+            };
 
-        for (mint, p) in proofs {
-            self.add_mint(mint.clone()).await?;
-            self.localstore.add_proofs(mint, p).await?;
+            self.add_mint(part.mint.clone()).await?;
+            self.localstore.add_proofs(part.mint.clone(), p).await?;
+            self.localstore.add_transaction(transaction).await?;
+            amount += mint_amount;
+            stored.push(part);
         }
 
-        Ok(())
+        Ok(amount)
     }
 
     /// Create Swap Payload
+    ///
+    /// The outputs of the desired amount are split as `target` asks.
     async fn create_swap(
         &mut self,
         mint_url: &UncheckedUrl,
         unit: &CurrencyUnit,
         amount: Option<Amount>,
         proofs: Proofs,
+        target: &SplitTarget,
     ) -> Result<PreSwap, Error> {
         let active_keyset_id = self.active_mint_keyset(mint_url, unit).await?;
 
         // Desired amount is either amount passwed or value of all proof
-        let proofs_total = proofs.iter().map(|p| p.amount).sum();
-
-        let desired_amount = amount.unwrap_or(proofs_total);
-
-        let mut counter = None;
-
-        let mut desired_messages;
-
-        #[cfg(not(feature = "nut13"))]
-        {
-            desired_messages = PreMintSecrets::random(active_keyset_id, desired_amount)?;
-        }
-
-        #[cfg(feature = "nut13")]
-        {
-            desired_messages = if let Some(mnemonic) = &self.mnemonic {
-                let count = self
-                    .localstore
-                    .get_keyset_counter(&active_keyset_id)
-                    .await?;
-
-                let count = if let Some(count) = count {
-                    count + 1
-                } else {
-                    0
-                };
-
-                let premint_secrets = PreMintSecrets::from_seed(
-                    active_keyset_id,
-                    count,
-                    mnemonic,
-                    desired_amount,
-                    false,
-                )?;
-
-                counter = Some(count + premint_secrets.len() as u64);
-
-                premint_secrets
-            } else {
-                PreMintSecrets::random(active_keyset_id, desired_amount)?
-            };
-        }
+        // less the input fee
+        let proofs_total: Amount = proofs.iter().map(|p| p.amount).sum();
+        let fee = self.estimate_swap_fee(mint_url, &proofs).await?;
+        let split = Amount::split_with_fee(proofs_total, fee, amount, target)
+            .map_err(|_| Error::InsufficientFunds)?;
+
+        let desired_messages = self
+            .pre_mint_secrets_with_amounts(mint_url, active_keyset_id, &split.send)
+            .await?;
 
-        if let Some(amt) = amount {
-            let change_amount = proofs_total - amt;
+        let mut builder = SwapRequestBuilder::new()
+            .inputs(proofs)
+            .outputs(desired_messages)
+            .input_fee(fee);
 
-            let change_messages = if let (Some(count), Some(mnemonic)) = (counter, &self.mnemonic) {
-                PreMintSecrets::from_seed(active_keyset_id, count, mnemonic, change_amount, false)?
-            } else {
-                PreMintSecrets::random(active_keyset_id, change_amount)?
-            };
-            // Combine the BlindedMessages totoalling the desired amount with change
-            desired_messages.combine(change_messages);
-            // Sort the premint secrets to avoid finger printing
-            desired_messages.sort_secrets();
+        if amount.is_some() {
+            let change_messages = self
+                .pre_mint_secrets_with_amounts(mint_url, active_keyset_id, &split.keep)
+                .await?;
+            // Sort the change in with the outputs totalling the desired amount
+            // to avoid finger printing
+            builder = builder.outputs(change_messages).sort_outputs();
         };
 
-        let swap_request = SwapRequest::new(proofs, desired_messages.blinded_messages());
-
-        Ok(PreSwap {
-            pre_mint_secrets: desired_messages,
-            swap_request,
-        })
+        Ok(builder.build()?)
     }
 
     pub async fn process_swap_response(
@@ -574,8 +1038,6 @@ impl Wallet {
     ) -> Result<Proofs, Error> {
         let mut proofs = vec![];
 
-        let mut proof_count: HashMap<Id, u64> = HashMap::new();
-
         for (promise, premint) in promises.iter().zip(blinded_messages) {
             // Verify the signature DLEQ is valid
             {
@@ -602,9 +1064,6 @@ impl Wallet {
 
             let unblinded_sig = unblind_message(&promise.c, &premint.r, &a).unwrap();
 
-            let count = proof_count.get(&promise.keyset_id).unwrap_or(&0);
-            proof_count.insert(promise.keyset_id, count + 1);
-
             let proof = Proof::new(
                 promise.amount,
                 promise.keyset_id,
@@ -615,73 +1074,126 @@ impl Wallet {
             proofs.push(proof);
         }
 
-        #[cfg(feature = "nut13")]
-        if self.mnemonic.is_some() {
-            for (keyset_id, count) in proof_count {
-                self.localstore
-                    .increment_keyset_counter(&keyset_id, count)
-                    .await?;
-            }
-        }
-
         Ok(proofs)
     }
 
     /// Send
+    ///
+    /// The returned proofs carry the DLEQ proofs of the mint signatures if
+    /// `include_dleq` is set and the mint sent them, so the receiver can
+    /// verify them offline. Without them the token is shorter, for QR codes.
     pub async fn send(
         &mut self,
         mint_url: &UncheckedUrl,
         unit: &CurrencyUnit,
         amount: Amount,
+        include_dleq: bool,
     ) -> Result<Proofs, Error> {
-        let proofs = self.select_proofs(mint_url.clone(), unit, amount).await?;
+        self.send_with_target(mint_url, unit, amount, include_dleq, &SplitTarget::None)
+            .await
+    }
 
-        let pre_swap = self
-            .create_swap(mint_url, unit, Some(amount), proofs.clone())
+    /// [Wallet::send] with the sent proofs and the change split as `target`
+    /// asks
+    pub async fn send_with_target(
+        &mut self,
+        mint_url: &UncheckedUrl,
+        unit: &CurrencyUnit,
+        amount: Amount,
+        include_dleq: bool,
+        target: &SplitTarget,
+    ) -> Result<Proofs, Error> {
+        let options = SendOptions {
+            split_target: *target,
+            include_dleq,
+            offline: OfflinePolicy::Never,
+        };
+
+        self.send_with_options(mint_url, unit, amount, &options)
+            .await
+    }
+
+    /// [Wallet::send] as `options` ask
+    ///
+    /// Unless [OfflinePolicy::Never], spendable proofs adding up to exactly
+    /// `amount` are sent as they are, without contacting the mint. P2PK
+    /// locked proofs are never sent as part of `amount`.
+    pub async fn send_with_options(
+        &mut self,
+        mint_url: &UncheckedUrl,
+        unit: &CurrencyUnit,
+        amount: Amount,
+        options: &SendOptions,
+    ) -> Result<Proofs, Error> {
+        let timestamp = self.clock.now();
+
+        if options.offline != OfflinePolicy::Never {
+            match self.take_exact_held(mint_url, unit, amount).await? {
+                Some(mut send_proofs) => {
+                    self.localstore
+                        .remove_proofs(mint_url.clone(), &send_proofs)
+                        .await?;
+                    self.localstore
+                        .add_pending_proofs(mint_url.clone(), send_proofs.clone())
+                        .await?;
+                    self.record_send(mint_url, unit, &send_proofs, timestamp)
+                        .await?;
+
+                    if !options.include_dleq {
+                        for proof in &mut send_proofs {
+                            proof.dleq = None;
+                        }
+                    }
+
+                    return Ok(send_proofs);
+                }
+                None if options.offline == OfflinePolicy::Require => {
+                    return Err(Error::NoExactMatch(amount));
+                }
+                None => (),
+            }
+        }
+
+        let include_dleq = options.include_dleq;
+        let target = &options.split_target;
+        let (proofs, fee) = self.select_proofs_with_fee(mint_url, unit, amount).await?;
+        let proofs_total: Amount = proofs.iter().map(|p| p.amount).sum();
+        let split = Amount::split_with_fee(proofs_total, fee, Some(amount), target)
+            .map_err(|_| Error::InsufficientFunds)?;
+
+        let active_keyset_id = self.active_mint_keyset(mint_url, unit).await?;
+        let send_messages = self
+            .pre_mint_secrets_with_amounts(mint_url, active_keyset_id, &split.send)
+            .await?;
+        let change_messages = self
+            .pre_mint_secrets_with_amounts(mint_url, active_keyset_id, &split.keep)
             .await?;
+        let send_secrets: HashSet<Secret> = send_messages.secrets().into_iter().collect();
+
+        // Sorted so the mint cannot tell the sent outputs from the change
+        let pre_swap = SwapRequestBuilder::new()
+            .inputs(proofs.clone())
+            .outputs(send_messages)
+            .outputs(change_messages)
+            .input_fee(fee)
+            .sort_outputs()
+            .build()?;
 
         let swap_response = self
             .client
             .post_swap(mint_url.clone().try_into()?, pre_swap.swap_request)
             .await?;
 
-        let mut keep_proofs = Proofs::new();
-        let mut send_proofs = Proofs::new();
-
-        let mut post_swap_proofs = construct_proofs(
+        let post_swap_proofs = construct_proofs(
             swap_response.signatures,
             pre_swap.pre_mint_secrets.rs(),
             pre_swap.pre_mint_secrets.secrets(),
             &self.active_keys(mint_url, unit).await?.unwrap(),
         )?;
 
-        let active_keyset = self.active_mint_keyset(mint_url, unit).await?;
-
-        #[cfg(feature = "nut13")]
-        if self.mnemonic.is_some() {
-            self.localstore
-                .increment_keyset_counter(&active_keyset, post_swap_proofs.len() as u64)
-                .await?;
-        }
-
-        post_swap_proofs.reverse();
-
-        for proof in post_swap_proofs {
-            if (proof.amount + send_proofs.iter().map(|p| p.amount).sum()).gt(&amount) {
-                keep_proofs.push(proof);
-            } else {
-                send_proofs.push(proof);
-            }
-        }
-
-        let send_amount: Amount = send_proofs.iter().map(|p| p.amount).sum();
-
-        if send_amount.ne(&amount) {
-            warn!(
-                "Send amount proofs is {:?} expected {:?}",
-                send_amount, amount
-            );
-        }
+        let (mut send_proofs, keep_proofs): (Proofs, Proofs) = post_swap_proofs
+            .into_iter()
+            .partition(|proof| send_secrets.contains(&proof.secret));
 
         self.localstore
             .remove_proofs(mint_url.clone(), &proofs)
@@ -697,34 +1209,231 @@ impl Wallet {
         self.localstore
             .add_proofs(mint_url.clone(), keep_proofs)
             .await?;
+        self.record_send(mint_url, unit, &send_proofs, timestamp)
+            .await?;
+
+        if !include_dleq {
+            for proof in &mut send_proofs {
+                proof.dleq = None;
+            }
+        }
 
         Ok(send_proofs)
     }
 
+    /// Tokens of exactly `amounts` in `unit`, in the order of `amounts`
+    ///
+    /// Amounts the spendable proofs already add up to are sent without a
+    /// swap, the others are all made by a single swap. Its input fee is paid
+    /// from the remainder, which stays in the wallet. No proof is in two
+    /// tokens. Fails with [Error::Shortfall] before anything is spent if the
+    /// proofs do not cover the amounts and the fee.
+    pub async fn send_many(
+        &mut self,
+        mint_url: &UncheckedUrl,
+        unit: &CurrencyUnit,
+        amounts: &[Amount],
+        include_dleq: bool,
+    ) -> Result<Vec<Token>, Error> {
+        if amounts.contains(&Amount::ZERO) {
+            return Err(crate::nuts::nut00::Error::ProofsRequired.into());
+        }
+        let needed = amounts
+            .iter()
+            .try_fold(Amount::ZERO, |total, amount| total.checked_add(*amount))
+            .ok_or(crate::nuts::nut00::Error::AmountOverflow)?;
+
+        let timestamp = self.clock.now();
+        let keysets: HashMap<Id, KeySetInfo> = self
+            .localstore
+            .get_mint_keysets(mint_url.clone())
+            .await?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|keyset| (keyset.id, keyset))
+            .collect();
+        let mut pool: Proofs = self
+            .proofs_by_unit(mint_url, &keysets)
+            .await?
+            .into_iter()
+            .find(|(proofs_unit, _)| proofs_unit == unit)
+            .map(|(_, proofs)| proofs)
+            .unwrap_or_default();
+        pool.retain(|proof| !is_locked(proof));
+        let available: Amount = pool.iter().map(|proof| proof.amount).sum();
+        if needed > available {
+            return Err(Error::Shortfall { needed, available });
+        }
+
+        pool.sort_unstable_by_key(|proof| std::cmp::Reverse(proof.amount));
+        let mut tokens: Vec<Proofs> = amounts
+            .iter()
+            .map(|amount| take_exact(&mut pool, *amount).unwrap_or_default())
+            .collect();
+        let sent_as_held: Proofs = tokens.iter().flatten().cloned().collect();
+
+        let swapped: Vec<(usize, Amount)> = amounts
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| tokens[*index].is_empty())
+            .map(|(index, amount)| (index, *amount))
+            .collect();
+        let swap_amount: Amount = swapped.iter().map(|(_, amount)| *amount).sum();
+
+        let mut inputs = Proofs::new();
+        if swap_amount > Amount::ZERO {
+            let mut fee = Amount::ZERO;
+            let fee = loop {
+                let target = swap_amount
+                    .checked_add(fee)
+                    .ok_or(crate::nuts::nut00::Error::AmountOverflow)?;
+                inputs.clear();
+                let mut total = Amount::ZERO;
+                for proof in &pool {
+                    if total >= target {
+                        break;
+                    }
+                    total += proof.amount;
+                    inputs.push(proof.clone());
+                }
+                if total < target {
+                    return Err(Error::Shortfall {
+                        needed: needed + fee,
+                        available,
+                    });
+                }
+
+                let inputs_fee = self.estimate_swap_fee(mint_url, &inputs).await?;
+                if inputs_fee <= fee {
+                    break inputs_fee;
+                }
+                fee = inputs_fee;
+            };
+            let inputs_total: Amount = inputs.iter().map(|proof| proof.amount).sum();
+
+            let active_keyset_id = self.active_mint_keyset(mint_url, unit).await?;
+            let mut builder = SwapRequestBuilder::new().inputs(inputs.clone());
+            let mut token_of_secret: HashMap<Secret, usize> = HashMap::new();
+            for (index, amount) in &swapped {
+                let messages = self
+                    .pre_mint_secrets(mint_url, active_keyset_id, *amount)
+                    .await?;
+                for secret in messages.secrets() {
+                    token_of_secret.insert(secret, *index);
+                }
+                builder = builder.outputs(messages);
+            }
+            let change_messages = self
+                .pre_mint_secrets(mint_url, active_keyset_id, inputs_total - swap_amount - fee)
+                .await?;
+
+            // Sorted so the mint cannot tell the tokens from the change
+            let pre_swap = builder
+                .outputs(change_messages)
+                .input_fee(fee)
+                .sort_outputs()
+                .build()?;
+
+            let swap_response = self
+                .client
+                .post_swap(mint_url.clone().try_into()?, pre_swap.swap_request)
+                .await?;
+            let post_swap_proofs = construct_proofs(
+                swap_response.signatures,
+                pre_swap.pre_mint_secrets.rs(),
+                pre_swap.pre_mint_secrets.secrets(),
+                &self.active_keys(mint_url, unit).await?.unwrap(),
+            )?;
+
+            let mut change = Proofs::new();
+            for proof in post_swap_proofs {
+                match token_of_secret.get(&proof.secret) {
+                    Some(index) => tokens[*index].push(proof),
+                    None => change.push(proof),
+                }
+            }
+
+            self.localstore
+                .remove_proofs(mint_url.clone(), &inputs)
+                .await?;
+            self.localstore
+                .add_pending_proofs(mint_url.clone(), inputs)
+                .await?;
+            self.localstore.add_proofs(mint_url.clone(), change).await?;
+        }
+
+        self.localstore
+            .remove_proofs(mint_url.clone(), &sent_as_held)
+            .await?;
+        self.localstore
+            .add_pending_proofs(mint_url.clone(), tokens.iter().flatten().cloned().collect())
+            .await?;
+
+        let mut sent = Vec::with_capacity(tokens.len());
+        for mut proofs in tokens {
+            self.record_send(mint_url, unit, &proofs, timestamp).await?;
+            if !include_dleq {
+                for proof in &mut proofs {
+                    proof.dleq = None;
+                }
+            }
+            sent.push(Token::new(
+                mint_url.clone(),
+                proofs,
+                None,
+                Some(unit.clone()),
+            )?);
+        }
+
+        Ok(sent)
+    }
+
     /// Melt Quote
+    ///
+    /// `amount` in `unit` is required for an amountless invoice and must be
+    /// the invoice amount otherwise.
     pub async fn melt_quote(
         &mut self,
         mint_url: UncheckedUrl,
         unit: CurrencyUnit,
         request: String,
+        amount: Option<Amount>,
+    ) -> Result<MeltQuote, Error> {
+        let invoice = Bolt11Invoice::from_str(&request)?;
+
+        let mut quote_request = MeltQuoteBolt11Request::new(invoice, unit);
+        quote_request.amount = amount;
+
+        self.melt_quote_request(mint_url, quote_request).await
+    }
+
+    async fn melt_quote_request(
+        &mut self,
+        mint_url: UncheckedUrl,
+        quote_request: MeltQuoteBolt11Request,
     ) -> Result<MeltQuote, Error> {
+        let amount_msat = quote_request.amount_msat()?;
+
         let quote_res = self
             .client
-            .post_melt_quote(
-                mint_url.clone().try_into()?,
-                unit.clone(),
-                Bolt11Invoice::from_str(&request.clone()).unwrap(),
-            )
+            .post_melt_quote_request(mint_url.try_into()?, &quote_request)
             .await?;
 
         let quote = MeltQuote {
             id: quote_res.quote,
             amount: quote_res.amount.into(),
-            request,
-            unit,
+            request: quote_request.request.to_string(),
+            unit: quote_request.unit,
             fee_reserve: quote_res.fee_reserve.into(),
             paid: quote_res.paid,
             expiry: quote_res.expiry,
+            pending: false,
+            reserved_inputs: Vec::new(),
+            reserved_outputs: None,
+            amount_msat: Some(amount_msat),
+            // Only known to the mint
+            fee_estimator: FeeEstimator::default(),
+            payment_preimage: None,
         };
 
         self.localstore.add_melt_quote(quote.clone()).await?;
@@ -758,7 +1467,7 @@ impl Wallet {
         let mut active_proofs: Proofs = Vec::new();
         let mut inactive_proofs: Proofs = Vec::new();
 
-        for proof in mint_proofs {
+        for proof in mint_proofs.into_iter().filter(|proof| !is_locked(proof)) {
             if active.contains(&proof.keyset_id) {
                 active_proofs.push(proof);
             } else if inactive.contains(&proof.keyset_id) {
@@ -788,6 +1497,140 @@ impl Wallet {
         Ok(selected_proofs)
     }
 
+    /// Select proofs covering `amount` and the input fee of the proofs
+    /// themselves, returned along with them
+    ///
+    /// Selection is repeated for the fee of the proofs picked so far until
+    /// no further proof is needed.
+    async fn select_proofs_with_fee(
+        &self,
+        mint_url: &UncheckedUrl,
+        unit: &CurrencyUnit,
+        amount: Amount,
+    ) -> Result<(Proofs, Amount), Error> {
+        let mut fee = Amount::ZERO;
+
+        loop {
+            let target = amount.checked_add(fee).ok_or(Error::InsufficientFunds)?;
+            let proofs = self.select_proofs(mint_url.clone(), unit, target).await?;
+            let proofs_fee = self.estimate_swap_fee(mint_url, &proofs).await?;
+            if proofs_fee <= fee {
+                return Ok((proofs, proofs_fee));
+            }
+            fee = proofs_fee;
+        }
+    }
+
+    /// Unlocked spendable proofs of `unit` adding up to exactly `amount`,
+    /// found with the cached keysets only
+    async fn take_exact_held(
+        &self,
+        mint_url: &UncheckedUrl,
+        unit: &CurrencyUnit,
+        amount: Amount,
+    ) -> Result<Option<Proofs>, Error> {
+        let keysets: HashMap<Id, KeySetInfo> = self
+            .localstore
+            .get_mint_keysets(mint_url.clone())
+            .await?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|keyset| (keyset.id, keyset))
+            .collect();
+        let mut pool: Proofs = self
+            .proofs_by_unit(mint_url, &keysets)
+            .await?
+            .into_iter()
+            .find(|(proofs_unit, _)| proofs_unit == unit)
+            .map(|(_, proofs)| proofs)
+            .unwrap_or_default();
+        pool.retain(|proof| !is_locked(proof));
+        pool.sort_unstable_by_key(|proof| std::cmp::Reverse(proof.amount));
+
+        Ok(take_exact(&mut pool, amount).filter(|proofs| !proofs.is_empty()))
+    }
+
+    /// Input fee `mint_url` charges to spend `proofs` [NUT-02]
+    ///
+    /// Computed from the cached keysets of the mint, which are only fetched
+    /// again when a proof is of a keyset not cached yet. Nothing is reserved
+    /// or spent.
+    pub async fn estimate_swap_fee(
+        &self,
+        mint_url: &UncheckedUrl,
+        proofs: &Proofs,
+    ) -> Result<Amount, Error> {
+        let mut keysets: HashMap<Id, KeySetInfo> = self
+            .localstore
+            .get_mint_keysets(mint_url.clone())
+            .await?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|keyset| (keyset.id, keyset))
+            .collect();
+
+        if proofs
+            .iter()
+            .any(|proof| !keysets.contains_key(&proof.keyset_id))
+        {
+            keysets = self
+                .get_mint_keysets(mint_url)
+                .await?
+                .into_iter()
+                .map(|keyset| (keyset.id, keyset))
+                .collect();
+        }
+
+        Ok(input_fee(proofs, &keysets))
+    }
+
+    /// What melting `bolt11` at `mint_url` would cost, without reserving or
+    /// spending anything
+    ///
+    /// The fee reserve comes from a melt quote that is not stored, or the
+    /// legacy `/checkfees` endpoint if the mint does not answer the quote.
+    /// Input fees are those of the proofs [Wallet::melt] would select now.
+    pub async fn estimate_melt_cost(
+        &self,
+        mint_url: &UncheckedUrl,
+        unit: &CurrencyUnit,
+        bolt11: &str,
+    ) -> Result<MeltCostEstimate, Error> {
+        let invoice = Bolt11Invoice::from_str(bolt11)?;
+        let url: Url = mint_url.try_into()?;
+
+        let (amount, fee_reserve) = match self
+            .client
+            .post_melt_quote(url.clone(), unit.clone(), invoice.clone(), None)
+            .await
+        {
+            Ok(quote) => (Amount::from(quote.amount), Amount::from(quote.fee_reserve)),
+            Err(err) => {
+                let amount_msat = invoice
+                    .amount_milli_satoshis()
+                    .ok_or(crate::error::Error::InvoiceAmountUndefined)?;
+                match self.client.post_check_fees(url, invoice).await {
+                    // Whole sats are paid for the invoice, rounded up
+                    Ok(fees) if unit == &CurrencyUnit::Sat => {
+                        (Amount::from_msat_ceil(amount_msat), fees.fee)
+                    }
+                    _ => return Err(err.into()),
+                }
+            }
+        };
+
+        let (_, input_fees) = self
+            .select_proofs_with_fee(mint_url, unit, amount + fee_reserve)
+            .await?;
+
+        Ok(MeltCostEstimate {
+            amount,
+            fee_reserve,
+            input_fees,
+            total: amount + fee_reserve + input_fees,
+        })
+    }
+
     /// Melt
     pub async fn melt(&mut self, mint_url: &UncheckedUrl, quote_id: &str) -> Result<Melted, Error> {
         let quote_info = self.localstore.get_melt_quote(quote_id).await?;
@@ -802,68 +1645,93 @@ impl Wallet {
             return Err(Error::QuoteUnknown);
         };
 
-        let proofs = self
-            .select_proofs(mint_url.clone(), &quote_info.unit, quote_info.amount)
+        let (proofs, input_fee) = self
+            .select_proofs_with_fee(
+                mint_url,
+                &quote_info.unit,
+                quote_info.amount + quote_info.fee_reserve,
+            )
             .await?;
 
-        let proofs_amount = proofs.iter().map(|p| p.amount).sum();
-
-        let mut counter: Option<u64> = None;
+        let melt_builder =
+            MeltRequestBuilder::new(quote_id, quote_info.amount, quote_info.fee_reserve)
+                .input_fee(input_fee)
+                .inputs(proofs.clone());
 
         let active_keyset_id = self.active_mint_keyset(mint_url, &quote_info.unit).await?;
 
-        let premint_secrets;
-
-        #[cfg(not(feature = "nut13"))]
-        {
-            premint_secrets = PreMintSecrets::blank(active_keyset_id, proofs_amount)?;
-        }
-
-        #[cfg(feature = "nut13")]
-        {
-            premint_secrets = match &self.mnemonic {
-                Some(mnemonic) => {
-                    let count = self
-                        .localstore
-                        .get_keyset_counter(&active_keyset_id)
-                        .await?;
+        let change_outputs = self
+            .blank_pre_mint_secrets(mint_url, active_keyset_id, melt_builder.max_change()?)
+            .await?;
+        let builder = melt_builder.change_outputs(change_outputs);
+
+        let PreMelt {
+            pre_mint_secrets: premint_secrets,
+            melt_request,
+        } = builder.build()?;
+
+        // Recorded before the payment, so one that never returns stays pending
+        let mut transaction = Transaction {
+            fee: quote_info.fee_reserve,
+            status: TransactionStatus::Pending,
+            quote_id: Some(quote_info.id.clone()),
+            ..Transaction::new(
+                TransactionKind::Melt,
+                mint_url.clone(),
+                quote_info.amount,
+                quote_info.unit.clone(),
+                self.clock.now(),
+            )
+        };
+        self.localstore.add_transaction(transaction.clone()).await?;
 
-                    let count = if let Some(count) = count {
-                        count + 1
-                    } else {
-                        0
-                    };
-
-                    counter = Some(count);
-                    PreMintSecrets::from_seed(
-                        active_keyset_id,
-                        count,
-                        mnemonic,
-                        proofs_amount,
-                        true,
-                    )?
-                }
-                None => PreMintSecrets::blank(active_keyset_id, proofs_amount)?,
-            };
-        }
+        // Reserved until the mint answers, see [Wallet::reclaim_reserved]
+        self.localstore
+            .add_reserved_proofs(mint_url.clone(), proofs.clone())
+            .await?;
+        self.localstore
+            .remove_proofs(mint_url.clone(), &proofs)
+            .await?;
 
         let melt_response = self
             .client
-            .post_melt(
-                mint_url.clone().try_into()?,
-                quote_id.to_string(),
-                proofs.clone(),
-                Some(premint_secrets.blinded_messages()),
-            )
+            .post_melt(mint_url.clone().try_into()?, melt_request)
             .await?;
 
+        // Unpaid melts leave the inputs spendable, keep them and the quote
+        if !melt_response.paid {
+            warn!(quote = %quote_info.id, "Melt was not paid");
+            self.localstore
+                .add_proofs(mint_url.clone(), proofs.clone())
+                .await?;
+            self.localstore
+                .remove_reserved_proofs(mint_url.clone(), &proofs)
+                .await?;
+            transaction.fee = Amount::ZERO;
+            transaction.status = TransactionStatus::Failed;
+            self.localstore.add_transaction(transaction).await?;
+            return Ok(Melted {
+                paid: false,
+                preimage: None,
+                change: None,
+                fee_paid: None,
+            });
+        }
+
         let change_proofs = match melt_response.change {
-            Some(change) => Some(construct_proofs(
-                change,
-                premint_secrets.rs(),
-                premint_secrets.secrets(),
-                &self.active_keys(mint_url, &quote_info.unit).await?.unwrap(),
-            )?),
+            Some(change) => {
+                let keys = self.active_keys(mint_url, &quote_info.unit).await?.unwrap();
+                let (outputs, change): (Vec<PreMint>, Vec<BlindSignature>) =
+                    match_melt_change(&premint_secrets, change, &keys)?
+                        .into_iter()
+                        .unzip();
+                Some(construct_proofs(
+                    change,
+                    outputs.iter().map(|output| output.r.clone()).collect(),
+                    outputs.into_iter().map(|output| output.secret).collect(),
+                    &keys,
+                )?)
+            }
             None => None,
         };
 
@@ -871,21 +1739,21 @@ impl Wallet {
             paid: true,
             preimage: melt_response.payment_preimage,
             change: change_proofs.clone(),
+            fee_paid: melt_response.fee_paid,
         };
 
-        if let Some(change_proofs) = change_proofs {
-            debug!(
-                "Change amount returned from melt: {}",
-                change_proofs.iter().map(|p| p.amount).sum::<Amount>()
-            );
+        let change_amount = match &change_proofs {
+            Some(change_proofs) => change_proofs.total_amount()?,
+            None => Amount::ZERO,
+        };
+        transaction.fee = proofs
+            .total_amount()?
+            .saturating_sub(quote_info.amount)
+            .saturating_sub(change_amount);
+        transaction.status = TransactionStatus::Settled;
 
-            // Update counter for keyset
-            #[cfg(feature = "nut13")]
-            if counter.is_some() {
-                self.localstore
-                    .increment_keyset_counter(&active_keyset_id, change_proofs.len() as u64)
-                    .await?;
-            }
+        if let Some(change_proofs) = change_proofs {
+            debug!("Change amount returned from melt: {}", change_amount);
 
             self.localstore
                 .add_proofs(mint_url.clone(), change_proofs)
@@ -895,321 +1763,1205 @@ impl Wallet {
         self.localstore.remove_melt_quote(&quote_info.id).await?;
 
         self.localstore
-            .remove_proofs(mint_url.clone(), &proofs)
+            .remove_reserved_proofs(mint_url.clone(), &proofs)
             .await?;
+        self.localstore.add_transaction(transaction).await?;
 
         Ok(melted)
     }
 
-    /// Create P2PK locked proofs
-    /// Uses a swap to swap proofs for locked p2pk conditions
-    pub async fn send_p2pk(
-        &mut self,
-        mint_url: &UncheckedUrl,
-        unit: &CurrencyUnit,
-        amount: Amount,
-        conditions: P2PKConditions,
-    ) -> Result<Proofs, Error> {
-        let input_proofs = self.select_proofs(mint_url.clone(), unit, amount).await?;
-        let active_keyset_id = self.active_mint_keyset(mint_url, unit).await?;
+    /// Return the inputs of failed or abandoned melts to the spendable proofs
+    ///
+    /// Every reserved proof is checked with its mint first, the payment may
+    /// have gone through without the wallet seeing the response. Unspent
+    /// proofs are spendable again and spent ones are dropped. Pending ones,
+    /// of a payment still in flight, stay reserved.
+    pub async fn reclaim_reserved(&self) -> Result<ReclaimReport, Error> {
+        let mut report = ReclaimReport::default();
+
+        for mint_url in self.localstore.get_mints().await?.into_keys() {
+            self.reclaim_reserved_at(&mint_url, &mut report).await?;
+        }
 
-        let input_amount: Amount = input_proofs.iter().map(|p| p.amount).sum();
-        let change_amount = input_amount - amount;
+        Ok(report)
+    }
 
-        let send_premint_secrets =
-            PreMintSecrets::with_p2pk_conditions(active_keyset_id, amount, conditions)?;
+    async fn reclaim_reserved_at(
+        &self,
+        mint_url: &UncheckedUrl,
+        report: &mut ReclaimReport,
+    ) -> Result<(), Error> {
+        let reserved = match self
+            .localstore
+            .get_reserved_proofs(mint_url.clone())
+            .await?
+        {
+            Some(reserved) if !reserved.is_empty() => reserved,
+            _ => return Ok(()),
+        };
 
-        let change_premint_secrets = PreMintSecrets::random(active_keyset_id, change_amount)?;
-        let mut pre_mint_secrets = send_premint_secrets;
-        pre_mint_secrets.combine(change_premint_secrets);
+        let states: HashMap<PublicKey, State> = self
+            .check_proofs_spent(mint_url.clone(), reserved.clone())
+            .await?
+            .into_iter()
+            .map(|state| (state.y, state.state))
+            .collect();
 
-        let swap_request =
-            SwapRequest::new(input_proofs.clone(), pre_mint_secrets.blinded_messages());
+        let (mut unspent, mut spent, mut pending) = (Proofs::new(), Proofs::new(), Proofs::new());
+        for proof in reserved {
+            // A proof the mint did not report on is left reserved
+            match states.get(&proof.y()?) {
+                Some(State::Unspent) => unspent.push(proof),
+                Some(State::Spent) => spent.push(proof),
+                _ => pending.push(proof),
+            }
+        }
 
-        let pre_swap = PreSwap {
-            pre_mint_secrets,
-            swap_request,
-        };
+        if !unspent.is_empty() {
+            report.reclaimed = report
+                .reclaimed
+                .checked_add(unspent.total_amount()?)
+                .ok_or(crate::nuts::nut00::Error::AmountOverflow)?;
+            self.localstore
+                .add_proofs(mint_url.clone(), unspent.clone())
+                .await?;
+            self.localstore
+                .remove_reserved_proofs(mint_url.clone(), &unspent)
+                .await?;
+        }
 
-        let swap_response = self
-            .client
-            .post_swap(mint_url.clone().try_into()?, pre_swap.swap_request)
-            .await?;
+        if !spent.is_empty() {
+            report.spent = report
+                .spent
+                .checked_add(spent.total_amount()?)
+                .ok_or(crate::nuts::nut00::Error::AmountOverflow)?;
+            self.localstore
+                .remove_reserved_proofs(mint_url.clone(), &spent)
+                .await?;
+        }
 
-        let post_swap_proofs = construct_proofs(
-            swap_response.signatures,
-            pre_swap.pre_mint_secrets.rs(),
-            pre_swap.pre_mint_secrets.secrets(),
-            &self.active_keys(mint_url, unit).await?.unwrap(),
-        )?;
+        if !pending.is_empty() {
+            report.pending.insert(mint_url.clone(), pending);
+        }
 
-        let mut send_proofs = vec![];
-        let mut change_proofs = vec![];
+        Ok(())
+    }
 
-        for proof in post_swap_proofs {
-            let conditions: Result<crate::nuts::nut10::Secret, _> = (&proof.secret).try_into();
-            if conditions.is_ok() {
-                send_proofs.push(proof);
-            } else {
-                change_proofs.push(proof);
+    /// Pay `bolt11` in parts melted at several mints [NUT-15]
+    ///
+    /// `allocation` is the msat each mint pays, together they have to be the
+    /// invoice amount. Every mint must support partial payments in sats and
+    /// quotes its part before any melt is sent. The melts are then sent at
+    /// once, the payee only settles when all parts have arrived.
+    ///
+    /// One mint may pay its part while another fails, so every part is
+    /// reported. Parts that are not [MppShardStatus::Paid] keep their quote
+    /// in the wallet and can be melted again with [Wallet::melt].
+    pub async fn melt_mpp(
+        &mut self,
+        bolt11: &str,
+        allocation: HashMap<UncheckedUrl, u64>,
+    ) -> Result<MppMelted, Error> {
+        let invoice = Bolt11Invoice::from_str(bolt11)?;
+        check_mpp_allocation(&invoice, &allocation)?;
+
+        for mint_url in allocation.keys() {
+            let mint_info = self
+                .client
+                .get_mint_info(mint_url.clone().try_into()?)
+                .await?;
+            if !mint_info
+                .nuts
+                .nut15
+                .supports(&PaymentMethod::Bolt11, &CurrencyUnit::Sat)
+            {
+                return Err(Error::MppUnsupported(mint_url.clone()));
             }
         }
 
-        self.localstore
-            .remove_proofs(mint_url.clone(), &input_proofs)
-            .await?;
+        let mut shards = Vec::with_capacity(allocation.len());
+        for (mint_url, amount_msat) in allocation {
+            let quote_request = MeltQuoteBolt11Request::new(invoice.clone(), CurrencyUnit::Sat)
+                .with_mpp(amount_msat);
+            let quote = self
+                .melt_quote_request(mint_url.clone(), quote_request)
+                .await?;
 
-        self.localstore
-            .add_pending_proofs(mint_url.clone(), input_proofs)
-            .await?;
-        self.localstore
-            .add_pending_proofs(mint_url.clone(), send_proofs.clone())
-            .await?;
-        self.localstore
-            .add_proofs(mint_url.clone(), change_proofs.clone())
-            .await?;
+            shards.push(MppShard::new(mint_url, amount_msat, quote.id));
+        }
 
-        Ok(send_proofs)
+        let wallet = self.clone();
+        Ok(melt_shards(shards, move |shard| {
+            let mut wallet = wallet.clone();
+            let mint_url = shard.mint_url.clone();
+            let quote_id = shard.quote_id.clone();
+            async move { wallet.melt(&mint_url, &quote_id).await }
+        })
+        .await)
     }
 
-    /// Receive p2pk
-    pub async fn receive_p2pk(
+    /// Move `amount` sats from `from_mint` to `to_mint` over Lightning
+    ///
+    /// Requests a mint quote at `to_mint`, melts proofs at `from_mint` to pay
+    /// its invoice plus the fee reserve of `from_mint`, then mints at
+    /// `to_mint` once the invoice is reported paid, polling for up to
+    /// [TRANSFER_POLL_ATTEMPTS] times [TRANSFER_POLL_INTERVAL].
+    ///
+    /// Errors before the melt is sent leave the proofs untouched. A melt that
+    /// is not paid, or fails while the mint reports its inputs unspent,
+    /// leaves the proofs at `from_mint` spendable, see
+    /// [TransferStatus::MeltFailed]. Other melt errors are returned, the
+    /// outcome has to be checked with the mint. If the invoice was paid but nothing
+    /// could be minted yet the mint quote is kept, see
+    /// [TransferStatus::Outstanding].
+    pub async fn transfer(
         &mut self,
-        encoded_token: &str,
-        signing_keys: Vec<SigningKey>,
-    ) -> Result<(), Error> {
-        let signing_key = signing_keys[0].clone();
-        let pubkey_secret_key: HashMap<String, SigningKey> = signing_keys
+        from_mint: &UncheckedUrl,
+        to_mint: &UncheckedUrl,
+        amount: Amount,
+    ) -> Result<TransferResult, Error> {
+        let mint_quote = self
+            .mint_quote(to_mint.clone(), amount, CurrencyUnit::Sat, None)
+            .await?;
+
+        let melt_quote = match self.transfer_melt_quote(from_mint, &mint_quote).await {
+            Ok(melt_quote) => melt_quote,
+            Err(err) => {
+                self.localstore.remove_mint_quote(&mint_quote.id).await?;
+                return Err(err);
+            }
+        };
+
+        let mut result = TransferResult {
+            from_mint: from_mint.clone(),
+            to_mint: to_mint.clone(),
+            mint_quote_id: mint_quote.id.clone(),
+            melt_quote_id: melt_quote.id.clone(),
+            status: TransferStatus::MeltFailed,
+        };
+
+        let melted = match self.melt(from_mint, &melt_quote.id).await {
+            Ok(melted) => melted,
+            Err(err) => {
+                // A rejected payment, the mint released the inputs
+                let mut reclaim = ReclaimReport::default();
+                let rolled_back = self
+                    .reclaim_reserved_at(from_mint, &mut reclaim)
+                    .await
+                    .is_ok()
+                    && reclaim.spent == Amount::ZERO
+                    && reclaim.pending.is_empty();
+                if !rolled_back {
+                    return Err(err);
+                }
+
+                warn!(quote = %melt_quote.id, "Transfer melt failed: {}", err);
+                if let Some(mut transaction) = self.melt_transaction(&melt_quote.id).await? {
+                    transaction.fee = Amount::ZERO;
+                    transaction.status = TransactionStatus::Failed;
+                    self.localstore.add_transaction(transaction).await?;
+                }
+                Melted::default()
+            }
+        };
+
+        if !melted.paid {
+            self.localstore.remove_mint_quote(&mint_quote.id).await?;
+            return Ok(result);
+        }
+
+        let fee = self
+            .melt_transaction(&melt_quote.id)
+            .await?
+            .map_or(Amount::ZERO, |transaction| transaction.fee);
+
+        result.status = TransferStatus::Outstanding { fee };
+
+        let mut paid = false;
+        for attempt in 0..TRANSFER_POLL_ATTEMPTS {
+            if attempt > 0 {
+                tokio::time::sleep(TRANSFER_POLL_INTERVAL).await;
+            }
+
+            match self.mint_quote_paid(to_mint.clone(), &mint_quote.id).await {
+                Ok(true) => {
+                    paid = true;
+                    break;
+                }
+                Ok(false) => (),
+                Err(err) => {
+                    warn!(quote = %mint_quote.id, "Could not check transfer quote: {}", err);
+                    return Ok(result);
+                }
+            }
+        }
+
+        if !paid {
+            warn!(quote = %mint_quote.id, "Transfer quote not paid in time");
+            return Ok(result);
+        }
+
+        match self.mint(to_mint.clone(), &mint_quote.id).await {
+            Ok(minted) => result.status = TransferStatus::Completed { minted, fee },
+            Err(err) => warn!(quote = %mint_quote.id, "Transfer not minted: {}", err),
+        }
+
+        Ok(result)
+    }
+
+    /// Transaction recorded by [Wallet::melt] for `quote_id`
+    async fn melt_transaction(&self, quote_id: &str) -> Result<Option<Transaction>, Error> {
+        Ok(self
+            .localstore
+            .get_transactions()
+            .await?
             .into_iter()
-            .map(|s| (s.public_key().to_string(), s))
-            .collect();
+            .find(|transaction| {
+                transaction.kind == TransactionKind::Melt
+                    && transaction.quote_id.as_deref() == Some(quote_id)
+            }))
+    }
 
-        let token_data = Token::from_str(encoded_token)?;
+    /// Melt quote at `from_mint` for the invoice of `mint_quote`
+    async fn transfer_melt_quote(
+        &mut self,
+        from_mint: &UncheckedUrl,
+        mint_quote: &MintQuote,
+    ) -> Result<MeltQuote, Error> {
+        if mint_quote.expiry != 0 && mint_quote.expiry <= self.clock.now() {
+            return Err(Error::QuoteExpired);
+        }
 
-        let unit = token_data.unit.unwrap_or_default();
+        self.melt_quote(
+            from_mint.clone(),
+            CurrencyUnit::Sat,
+            mint_quote.request.clone(),
+            None,
+        )
+        .await
+    }
 
-        let mut received_proofs: HashMap<UncheckedUrl, Proofs> = HashMap::new();
-        for token in token_data.token {
-            if token.proofs.is_empty() {
-                continue;
+    /// Create P2PK locked proofs
+    /// Uses a swap to swap proofs for locked p2pk conditions
+    pub async fn send_p2pk(
+        &mut self,
+        mint_url: &UncheckedUrl,
+        unit: &CurrencyUnit,
+        amount: Amount,
+        conditions: P2PKConditions,
+    ) -> Result<Proofs, Error> {
+        let timestamp = self.clock.now();
+        let (input_proofs, fee) = self.select_proofs_with_fee(mint_url, unit, amount).await?;
+        let active_keyset_id = self.active_mint_keyset(mint_url, unit).await?;
+
+        let input_amount: Amount = input_proofs.iter().map(|p| p.amount).sum();
+        let change_amount = input_amount - amount - fee;
+
+        let send_premint_secrets =
+            PreMintSecrets::with_p2pk_conditions(active_keyset_id, amount, conditions)?;
+
+        let change_premint_secrets = self
+            .pre_mint_secrets(mint_url, active_keyset_id, change_amount)
+            .await?;
+
+        let pre_swap = SwapRequestBuilder::new()
+            .inputs(input_proofs.clone())
+            .outputs(send_premint_secrets)
+            .outputs(change_premint_secrets)
+            .input_fee(fee)
+            .build()?;
+
+        let swap_response = self
+            .client
+            .post_swap(mint_url.clone().try_into()?, pre_swap.swap_request)
+            .await?;
+
+        let post_swap_proofs = construct_proofs(
+            swap_response.signatures,
+            pre_swap.pre_mint_secrets.rs(),
+            pre_swap.pre_mint_secrets.secrets(),
+            &self.active_keys(mint_url, unit).await?.unwrap(),
+        )?;
+
+        let mut send_proofs = vec![];
+        let mut change_proofs = vec![];
+
+        for proof in post_swap_proofs {
+            let conditions: Result<crate::nuts::nut10::Secret, _> = (&proof.secret).try_into();
+            if conditions.is_ok() {
+                send_proofs.push(proof);
+            } else {
+                change_proofs.push(proof);
             }
+        }
 
-            let active_keyset_id = self.active_mint_keyset(&token.mint, &unit).await?;
+        self.localstore
+            .remove_proofs(mint_url.clone(), &input_proofs)
+            .await?;
 
-            // TODO: if none fetch keyset for mint
+        self.localstore
+            .add_pending_proofs(mint_url.clone(), input_proofs)
+            .await?;
+        self.localstore
+            .add_pending_proofs(mint_url.clone(), send_proofs.clone())
+            .await?;
+        self.localstore
+            .add_proofs(mint_url.clone(), change_proofs.clone())
+            .await?;
+        self.record_send(mint_url, unit, &send_proofs, timestamp)
+            .await?;
 
-            let keys = self.localstore.get_keys(&active_keyset_id).await?;
+        Ok(send_proofs)
+    }
 
-            // Sum amount of all proofs
-            let amount: Amount = token.proofs.iter().map(|p| p.amount).sum();
+    /// [Wallet::receive] signing P2PK locked proofs with `signing_keys`
+    pub async fn receive_p2pk(
+        &mut self,
+        encoded_token: &str,
+        signing_keys: Vec<SigningKey>,
+    ) -> Result<ReceiveOutcome, Error> {
+        self.receive(
+            encoded_token,
+            ReceiveOptions {
+                p2pk_signing_keys: signing_keys,
+                ..Default::default()
+            },
+        )
+        .await
+    }
 
-            let mut proofs = token.proofs;
+    /// Reclaim the P2PK locked proofs of `token` whose locktime has passed
+    ///
+    /// Proofs with a refund key among `signing_keys` are signed with it and
+    /// swapped for unconditional proofs, which are added to the wallet in
+    /// place of any pending copy [Wallet::send_p2pk] kept. Every other proof
+    /// is returned with why it was not claimed. HTLC locks cannot be
+    /// refunded, as the mint does not verify them, and are reported as
+    /// [UnclaimedReason::Unsupported].
+    ///
+    /// The outputs of a swap can only be signed with one key, so the claim
+    /// is refused before any swap if the SIG_ALL locks of one mint have
+    /// different refund keys.
+    pub async fn claim_expired(
+        &mut self,
+        token: &Token,
+        signing_keys: Vec<SigningKey>,
+    ) -> Result<ExpiredClaim, Error> {
+        use crate::nuts::nut10;
 
-            let mut sig_flag = None;
+        let now = self.clock.now();
+        let unit = token.unit();
+        let refund_secret_key: HashMap<String, SigningKey> = signing_keys
+            .into_iter()
+            .map(|s| (s.verifying_key().to_string(), s))
+            .collect();
 
-            for proof in &mut proofs {
-                // Verify that proof DLEQ is valid
+        let mut claim = ExpiredClaim::default();
+        let mut refunds = Vec::with_capacity(token.token.len());
+        for mint_proofs in &token.token {
+            let mut locked = Proofs::new();
+            let mut refundable = Proofs::new();
+            let mut sig_all_key: Option<SigningKey> = None;
+
+            for proof in &mint_proofs.proofs {
+                let secret = nut10::Secret::try_from(&proof.secret).ok();
+                if secret
+                    .as_ref()
+                    .is_some_and(|secret| secret.kind == nut10::Kind::HTLC)
                 {
-                    let keys = self.localstore.get_keys(&proof.keyset_id).await?.unwrap();
-                    let key = keys.amount_key(proof.amount).unwrap();
-                    proof.verify_dleq(key).unwrap();
+                    claim.unclaim(proof, UnclaimedReason::Unsupported);
+                    continue;
                 }
+                let conditions = secret.and_then(|secret| P2PKConditions::try_from(secret).ok());
+                let Some(conditions) = conditions else {
+                    claim.unclaim(proof, UnclaimedReason::NotLocked);
+                    continue;
+                };
 
-                if let Ok(secret) =
-                    <crate::secret::Secret as TryInto<crate::nuts::nut10::Secret>>::try_into(
-                        proof.secret.clone(),
-                    )
-                {
-                    let conditions: Result<P2PKConditions, _> = secret.try_into();
-                    if let Ok(conditions) = conditions {
-                        let pubkeys = conditions.pubkeys;
-
-                        for pubkey in pubkeys {
-                            if let Some(signing) = pubkey_secret_key.get(&pubkey.to_string()) {
-                                proof.sign_p2pk(signing.clone())?;
-                            }
-                        }
+                let (Some(locktime), Some(refund_keys)) =
+                    (conditions.locktime, conditions.refund_keys)
+                else {
+                    claim.unclaim(proof, UnclaimedReason::NotRefundable);
+                    continue;
+                };
 
-                        sig_flag = Some(conditions.sig_flag);
+                // The mint opens the refund path once the locktime is past
+                if locktime >= now {
+                    claim.unclaim(proof, UnclaimedReason::NotExpired { locktime });
+                    continue;
+                }
+
+                let Some(signing_key) = refund_keys
+                    .iter()
+                    .find_map(|key| refund_secret_key.get(&key.to_string()))
+                else {
+                    claim.unclaim(proof, UnclaimedReason::NotOurRefundKey);
+                    continue;
+                };
+
+                if conditions.sig_flag.eq(&SigFlag::SigAll) {
+                    match &sig_all_key {
+                        Some(key) if key.verifying_key() != signing_key.verifying_key() => {
+                            return Err(Error::InvalidSpendConditions(
+                                "SIG_ALL locks with different refund keys".to_string(),
+                            ));
+                        }
+                        Some(_) => (),
+                        None => sig_all_key = Some(signing_key.clone()),
                     }
                 }
+
+                let mut refund = proof.clone();
+                refund.witness = None;
+                refund.sign_p2pk(signing_key.clone())?;
+
+                locked.push(proof.clone());
+                refundable.push(refund);
+            }
+
+            if !refundable.is_empty() {
+                refunds.push((&mint_proofs.mint, locked, refundable, sig_all_key));
             }
+        }
 
+        for (mint_url, locked, refundable, sig_all_key) in refunds {
             let mut pre_swap = self
-                .create_swap(&token.mint, &unit, Some(amount), proofs)
+                .create_swap(mint_url, &unit, None, refundable, &SplitTarget::None)
                 .await?;
-
-            if let Some(sigflag) = sig_flag {
-                if sigflag.eq(&SigFlag::SigAll) {
-                    for blinded_message in &mut pre_swap.swap_request.outputs {
-                        blinded_message.sign_p2pk(signing_key.clone()).unwrap();
-                    }
+            if let Some(signing_key) = sig_all_key {
+                for blinded_message in &mut pre_swap.swap_request.outputs {
+                    blinded_message.sign_p2pk(signing_key.clone())?;
                 }
             }
 
             let swap_response = self
                 .client
-                .post_swap(token.mint.clone().try_into()?, pre_swap.swap_request)
+                .post_swap(mint_url.clone().try_into()?, pre_swap.swap_request)
                 .await?;
-
-            // Proof to keep
-            let p = construct_proofs(
+            let proofs = construct_proofs(
                 swap_response.signatures,
                 pre_swap.pre_mint_secrets.rs(),
                 pre_swap.pre_mint_secrets.secrets(),
-                &keys.unwrap(),
+                &self
+                    .active_keys(mint_url, &unit)
+                    .await?
+                    .ok_or(Error::UnknownKey)?,
             )?;
-            let mint_proofs = received_proofs.entry(token.mint).or_default();
 
-            #[cfg(feature = "nut13")]
-            if self.mnemonic.is_some() {
-                self.localstore
-                    .increment_keyset_counter(&active_keyset_id, p.len() as u64)
-                    .await?;
-            }
+            let transaction = Transaction {
+                ys: locked.ys()?,
+                ..Transaction::new(
+                    TransactionKind::Receive,
+                    mint_url.clone(),
+                    proofs.total_amount()?,
+                    unit.clone(),
+                    now,
+                )
+            };
 
-            mint_proofs.extend(p);
-        }
+            self.localstore
+                .remove_pending_proofs(mint_url.clone(), &locked)
+                .await?;
+            self.localstore
+                .add_proofs(mint_url.clone(), proofs.clone())
+                .await?;
+            self.localstore.add_transaction(transaction).await?;
 
-        for (mint, proofs) in received_proofs {
-            self.localstore.add_proofs(mint, proofs).await?;
+            debug!(
+                mint = %mint_url,
+                proofs = locked.len(),
+                "Claimed expired P2PK proofs"
+            );
+            claim.claimed.extend(proofs);
         }
 
-        Ok(())
-    }
-
-    pub fn proofs_to_token(
-        &self,
-        mint_url: UncheckedUrl,
-        proofs: Proofs,
-        memo: Option<String>,
-        unit: Option<CurrencyUnit>,
-    ) -> Result<String, Error> {
-        Ok(Token::new(mint_url, proofs, memo, unit)?.to_string())
+        Ok(claim)
     }
 
-    #[cfg(feature = "nut13")]
-    pub async fn restore(&mut self, mint_url: UncheckedUrl) -> Result<Amount, Error> {
-        // Check that mint is in store of mints
-        if self.localstore.get_mint(mint_url.clone()).await?.is_none() {
-            self.add_mint(mint_url.clone()).await?;
-        }
-
-        let keysets = self.get_mint_keysets(&mint_url).await?;
+    /// Run the maintenance `policy` allows over the proofs of every mint
+    ///
+    /// Pending proofs the mint reports spent are forgotten, proofs of
+    /// inactive keysets are swapped into the active keyset of their unit,
+    /// and the proofs of a unit are swapped for as few as possible once
+    /// there are more than [MaintenancePolicy::consolidate_above]. Each swap
+    /// is atomic at the mint and its outputs are stored before its inputs
+    /// are removed, so an interrupted pass loses nothing and can be run
+    /// again. A swap whose input fees would take the pass over
+    /// [MaintenancePolicy::max_fee] is skipped.
+    pub async fn maintain(
+        &mut self,
+        policy: &MaintenancePolicy,
+    ) -> Result<MaintenanceReport, Error> {
+        let mut report = MaintenanceReport::default();
 
-        let mut restored_value = Amount::ZERO;
+        for (mint_url, _) in self.localstore.get_mints().await? {
+            if policy.check_pending {
+                self.check_pending(&mint_url, &mut report).await?;
+            }
 
-        for keyset in keysets {
-            let keys = self.get_keyset_keys(&mint_url, keyset.id).await?;
-            let mut empty_batch = 0;
-            let mut start_counter = 0;
-
-            while empty_batch.lt(&3) {
-                let premint_secrets = PreMintSecrets::restore_batch(
-                    keyset.id,
-                    &self.mnemonic.clone().unwrap(),
-                    start_counter,
-                    start_counter + 100,
-                )?;
-
-                debug!(
-                    "Attempting to restore counter {}-{} for mint {} keyset {}",
-                    start_counter,
-                    start_counter + 100,
-                    mint_url,
-                    keyset.id
-                );
-
-                let restore_request = RestoreRequest {
-                    outputs: premint_secrets.blinded_messages(),
-                };
+            if !policy.migrate_inactive && policy.consolidate_above.is_none() {
+                continue;
+            }
 
-                let response = self
-                    .client
-                    .post_restore(mint_url.clone().try_into()?, restore_request)
-                    .await
-                    .unwrap();
+            let keysets: HashMap<Id, KeySetInfo> = self
+                .get_mint_keysets(&mint_url)
+                .await?
+                .into_iter()
+                .map(|keyset| (keyset.id, keyset))
+                .collect();
+
+            if policy.migrate_inactive {
+                for (unit, proofs) in self.proofs_by_unit(&mint_url, &keysets).await? {
+                    let inactive: Proofs = proofs
+                        .into_iter()
+                        .filter(|proof| {
+                            let keyset = &keysets[&proof.keyset_id];
+                            !keyset.active && !keyset.frozen
+                        })
+                        .collect();
+                    if inactive.is_empty() {
+                        continue;
+                    }
 
-                if response.signatures.is_empty() {
-                    empty_batch += 1;
-                    start_counter += 100;
-                    continue;
+                    let count = inactive.len();
+                    if self
+                        .maintenance_swap(&mint_url, &unit, inactive, &keysets, policy, &mut report)
+                        .await?
+                        .is_some()
+                    {
+                        report.migrated += count;
+                    }
                 }
+            }
 
-                let premint_secrets: Vec<_> = premint_secrets
-                    .secrets
-                    .iter()
-                    .filter(|p| response.outputs.contains(&p.blinded_message))
-                    .collect();
-
-                let premint_secrets: Vec<_> = premint_secrets
-                    .iter()
-                    .filter(|p| response.outputs.contains(&p.blinded_message))
-                    .collect();
+            if let Some(threshold) = policy.consolidate_above {
+                for (unit, proofs) in self.proofs_by_unit(&mint_url, &keysets).await? {
+                    let proofs: Proofs = proofs
+                        .into_iter()
+                        .filter(|proof| !keysets[&proof.keyset_id].frozen)
+                        .collect();
+                    let total: Amount = proofs.iter().map(|p| p.amount).sum();
+                    if proofs.len() <= threshold || total.split().len() >= proofs.len() {
+                        continue;
+                    }
 
-                // the response outputs and premint secrets should be the same after filtering
-                // blinded messages the mint did not have signatures for
-                assert_eq!(response.outputs.len(), premint_secrets.len());
+                    let count = proofs.len();
+                    if let Some(outputs) = self
+                        .maintenance_swap(&mint_url, &unit, proofs, &keysets, policy, &mut report)
+                        .await?
+                    {
+                        report.consolidated += count;
+                        report.consolidated_into += outputs;
+                    }
+                }
+            }
+        }
 
-                let proofs = construct_proofs(
-                    response.signatures,
-                    premint_secrets.iter().map(|p| p.r.clone()).collect(),
-                    premint_secrets.iter().map(|p| p.secret.clone()).collect(),
-                    &keys,
-                )?;
+        Ok(report)
+    }
 
-                debug!("Restored {} proofs", proofs.len());
+    /// Forget the pending proofs of `mint_url` the mint reports spent
+    async fn check_pending(
+        &self,
+        mint_url: &UncheckedUrl,
+        report: &mut MaintenanceReport,
+    ) -> Result<(), Error> {
+        let pending = match self.localstore.get_pending_proofs(mint_url.clone()).await? {
+            Some(pending) if !pending.is_empty() => pending,
+            _ => return Ok(()),
+        };
 
-                #[cfg(feature = "nut13")]
-                self.localstore
-                    .increment_keyset_counter(&keyset.id, proofs.len() as u64)
-                    .await?;
+        let states = self
+            .check_proofs_spent(mint_url.clone(), pending.clone())
+            .await?;
+        let spent: HashSet<PublicKey> = states
+            .into_iter()
+            .filter(|state| state.state == State::Spent)
+            .map(|state| state.y)
+            .collect();
 
-                let states = self
-                    .check_proofs_spent(mint_url.clone(), proofs.clone())
-                    .await?;
+        let (spent, still_pending): (Proofs, Proofs) = pending
+            .into_iter()
+            .partition(|proof| proof.y().is_ok_and(|y| spent.contains(&y)));
 
-                let unspent_proofs: Vec<Proof> = proofs
-                    .iter()
-                    .zip(states)
-                    .filter(|(_, state)| !state.state.eq(&State::Spent))
-                    .map(|(p, _)| p)
-                    .cloned()
-                    .collect();
+        self.localstore
+            .remove_pending_proofs(mint_url.clone(), &spent)
+            .await?;
+        report.pending_spent += spent.len();
+        report.still_pending += still_pending.len();
 
-                restored_value += unspent_proofs.iter().map(|p| p.amount).sum();
+        Ok(())
+    }
 
-                self.localstore
-                    .add_proofs(mint_url.clone(), unspent_proofs)
-                    .await?;
+    /// Stored proofs of `mint_url` by the unit of their keyset
+    ///
+    /// Proofs of keysets not in `keysets` are left out.
+    async fn proofs_by_unit(
+        &self,
+        mint_url: &UncheckedUrl,
+        keysets: &HashMap<Id, KeySetInfo>,
+    ) -> Result<Vec<(CurrencyUnit, Proofs)>, Error> {
+        let mut by_unit: Vec<(CurrencyUnit, Proofs)> = Vec::new();
 
-                empty_batch = 0;
-                start_counter += 100;
+        for proof in self
+            .localstore
+            .get_proofs(mint_url.clone())
+            .await?
+            .unwrap_or_default()
+        {
+            let Some(keyset) = keysets.get(&proof.keyset_id) else {
+                continue;
+            };
+            match by_unit.iter_mut().find(|(unit, _)| unit == &keyset.unit) {
+                Some((_, proofs)) => proofs.push(proof),
+                None => by_unit.push((keyset.unit.clone(), vec![proof])),
             }
         }
-        Ok(restored_value)
-    }
 
-    /// Verify all proofs in token have meet the required spend
-    /// Can be used to allow a wallet to accept payments offline while reducing
-    /// the risk of claiming back to the limits let by the spending_conditions
-    pub fn verify_token_p2pk(
-        &self,
-        token: &Token,
-        spending_conditions: P2PKConditions,
-    ) -> Result<(), Error> {
-        use crate::nuts::nut10;
+        Ok(by_unit)
+    }
 
-        if spending_conditions.refund_keys.is_some() && spending_conditions.locktime.is_none() {
-            warn!(
-                "Invalid spending conditions set: Locktime must be set if refund keys are allowed"
-            );
-            return Err(Error::InvalidSpendConditions(
-                "Must set locktime".to_string(),
-            ));
+    /// Swap `proofs` for proofs of the active keyset of `unit`, unless
+    /// their fee would exceed what is left of the policy's budget
+    ///
+    /// Returns how many proofs they were swapped for.
+    async fn maintenance_swap(
+        &mut self,
+        mint_url: &UncheckedUrl,
+        unit: &CurrencyUnit,
+        proofs: Proofs,
+        keysets: &HashMap<Id, KeySetInfo>,
+        policy: &MaintenancePolicy,
+        report: &mut MaintenanceReport,
+    ) -> Result<Option<usize>, Error> {
+        let fee = input_fee(&proofs, keysets);
+        if report.fees + fee > policy.max_fee {
+            report.skipped += 1;
+            return Ok(None);
         }
 
-        for mint_proof in &token.token {
+        let count = self.swap_to_active(mint_url, unit, proofs, fee).await?;
+        report.fees += fee;
+        Ok(Some(count))
+    }
+
+    /// Swap `proofs`, whose input fee is `fee`, for proofs of the active
+    /// keyset of `unit`
+    ///
+    /// Returns how many proofs they were swapped for.
+    async fn swap_to_active(
+        &mut self,
+        mint_url: &UncheckedUrl,
+        unit: &CurrencyUnit,
+        proofs: Proofs,
+        fee: Amount,
+    ) -> Result<usize, Error> {
+        let pre_swap = self
+            .create_swap(mint_url, unit, None, proofs.clone(), &SplitTarget::None)
+            .await?;
+        let swap_response = self
+            .client
+            .post_swap(mint_url.clone().try_into()?, pre_swap.swap_request)
+            .await?;
+        let swapped = construct_proofs(
+            swap_response.signatures,
+            pre_swap.pre_mint_secrets.rs(),
+            pre_swap.pre_mint_secrets.secrets(),
+            &self
+                .active_keys(mint_url, unit)
+                .await?
+                .ok_or(Error::UnknownKey)?,
+        )?;
+
+        // Outputs first, so an interruption leaves spent proofs rather
+        // than losing the new ones
+        let count = swapped.len();
+        let transaction = Transaction {
+            fee,
+            ..Transaction::new(
+                TransactionKind::Swap,
+                mint_url.clone(),
+                swapped.total_amount()?,
+                unit.clone(),
+                self.clock.now(),
+            )
+        };
+        self.localstore
+            .add_proofs(mint_url.clone(), swapped)
+            .await?;
+        self.localstore
+            .remove_proofs(mint_url.clone(), &proofs)
+            .await?;
+        self.localstore.add_transaction(transaction).await?;
+
+        Ok(count)
+    }
+
+    /// [Wallet::consolidate_with_options] spending at most `max_fee` on input
+    /// fees
+    pub async fn consolidate(&mut self, max_fee: Amount) -> Result<ConsolidationReport, Error> {
+        self.consolidate_with_options(&ConsolidateOptions {
+            max_fee,
+            ..ConsolidateOptions::default()
+        })
+        .await
+    }
+
+    /// Swap the dust proofs of every mint for as few proofs as possible
+    ///
+    /// The proofs of a unit are fewest when no two are worth the same, so
+    /// dust are the proofs worth no more than the largest denomination held
+    /// twice. They are swapped smallest first, at most
+    /// [ConsolidateOptions::max_inputs] at a time, until none are left or the
+    /// next swap would take the fees paid over [ConsolidateOptions::max_fee].
+    ///
+    /// Each swap is stored on its own, outputs before inputs, so an
+    /// interrupted run is finished by running it again: stored proofs the
+    /// mint already spent are forgotten instead of failing the swap.
+    pub async fn consolidate_with_options(
+        &mut self,
+        options: &ConsolidateOptions,
+    ) -> Result<ConsolidationReport, Error> {
+        let mut report = ConsolidationReport::default();
+
+        for (mint_url, _) in self.localstore.get_mints().await? {
+            let keysets: HashMap<Id, KeySetInfo> = self
+                .get_mint_keysets(&mint_url)
+                .await?
+                .into_iter()
+                .map(|keyset| (keyset.id, keyset))
+                .collect();
+
+            for (unit, _) in self.proofs_by_unit(&mint_url, &keysets).await? {
+                self.consolidate_unit(&mint_url, &unit, &keysets, options, &mut report)
+                    .await?;
+                if report.budget_exhausted {
+                    return Ok(report);
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Consolidate the dust of `unit` at `mint_url` until there is none or
+    /// the budget of `options` is used up
+    async fn consolidate_unit(
+        &mut self,
+        mint_url: &UncheckedUrl,
+        unit: &CurrencyUnit,
+        keysets: &HashMap<Id, KeySetInfo>,
+        options: &ConsolidateOptions,
+        report: &mut ConsolidationReport,
+    ) -> Result<(), Error> {
+        loop {
+            let dust = self.dust(mint_url, unit, keysets).await?;
+            let mut progress = false;
+
+            for batch in dust.chunks(options.max_inputs.max(1)) {
+                let batch = batch.to_vec();
+                let fee = input_fee(&batch, keysets);
+                let total = batch.total_amount()?;
+                // Not worth its fee if it does not leave fewer proofs
+                if total <= fee || (total - fee).split().len() >= batch.len() {
+                    continue;
+                }
+                if report.fees + fee > options.max_fee {
+                    report.budget_exhausted = true;
+                    return Ok(());
+                }
+
+                let count = batch.len();
+                match self
+                    .swap_to_active(mint_url, unit, batch.clone(), fee)
+                    .await
+                {
+                    Ok(outputs) => {
+                        report.consolidated += count;
+                        report.consolidated_into += outputs;
+                        report.swaps += 1;
+                        report.fees += fee;
+                    }
+                    // Left by a run interrupted after its swap
+                    Err(err) if is_token_spent(&err) => {
+                        let spent = self.forget_spent(mint_url, batch).await?;
+                        if spent == 0 {
+                            return Err(err);
+                        }
+                        report.already_spent += spent;
+                    }
+                    Err(err) => return Err(err),
+                }
+                progress = true;
+            }
+
+            if !progress {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Proofs of `unit` at `mint_url` worth no more than the largest amount
+    /// two of them are worth, smallest first
+    ///
+    /// Proofs of frozen keysets are left out, the mint does not take them.
+    async fn dust(
+        &self,
+        mint_url: &UncheckedUrl,
+        unit: &CurrencyUnit,
+        keysets: &HashMap<Id, KeySetInfo>,
+    ) -> Result<Proofs, Error> {
+        let proofs: Proofs = self
+            .proofs_by_unit(mint_url, keysets)
+            .await?
+            .into_iter()
+            .find(|(proofs_unit, _)| proofs_unit == unit)
+            .map(|(_, proofs)| proofs)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|proof| !keysets[&proof.keyset_id].frozen)
+            .collect();
+
+        let mut counts: HashMap<Amount, usize> = HashMap::new();
+        for proof in &proofs {
+            *counts.entry(proof.amount).or_default() += 1;
+        }
+        let Some(largest) = counts
+            .into_iter()
+            .filter(|(_, count)| *count > 1)
+            .map(|(amount, _)| amount)
+            .max()
+        else {
+            return Ok(Proofs::new());
+        };
+
+        let mut dust: Proofs = proofs
+            .into_iter()
+            .filter(|proof| proof.amount <= largest)
+            .collect();
+        dust.sort_by_key(|proof| proof.amount);
+
+        Ok(dust)
+    }
+
+    /// Remove the proofs of `proofs` the mint reports spent from the store,
+    /// returns how many there were
+    async fn forget_spent(&self, mint_url: &UncheckedUrl, proofs: Proofs) -> Result<usize, Error> {
+        let states = self
+            .check_proofs_spent(mint_url.clone(), proofs.clone())
+            .await?;
+        let spent: HashSet<PublicKey> = states
+            .into_iter()
+            .filter(|state| state.state == State::Spent)
+            .map(|state| state.y)
+            .collect();
+
+        let spent: Proofs = proofs
+            .into_iter()
+            .filter(|proof| proof.y().is_ok_and(|y| spent.contains(&y)))
+            .collect();
+        self.localstore
+            .remove_proofs(mint_url.clone(), &spent)
+            .await?;
+
+        Ok(spent.len())
+    }
+
+    /// History of the wallet matching `filter`, oldest first
+    pub async fn transactions(
+        &self,
+        filter: &TransactionFilter,
+    ) -> Result<Vec<Transaction>, Error> {
+        let mut transactions: Vec<Transaction> = self
+            .localstore
+            .get_transactions()
+            .await?
+            .into_iter()
+            .filter(|transaction| filter.matches(transaction))
+            .collect();
+        transactions.sort_by_key(|transaction| transaction.timestamp);
+
+        Ok(transactions)
+    }
+
+    async fn record_send(
+        &self,
+        mint_url: &UncheckedUrl,
+        unit: &CurrencyUnit,
+        send_proofs: &Proofs,
+        timestamp: u64,
+    ) -> Result<(), Error> {
+        let transaction = Transaction {
+            ys: send_proofs.ys()?,
+            ..Transaction::new(
+                TransactionKind::Send,
+                mint_url.clone(),
+                send_proofs.total_amount()?,
+                unit.clone(),
+                timestamp,
+            )
+        };
+
+        Ok(self.localstore.add_transaction(transaction).await?)
+    }
+
+    /// Send `amount` to `recipient_npub` in a direct message published to
+    /// `relays` [NIP-44]
+    ///
+    /// With `p2pk_lock` the proofs are locked to the key of the npub
+    /// [NUT-11], so only the recipient can claim them if the message leaks.
+    /// The token is returned even if no relay accepted the message, its
+    /// proofs stay pending until it is received or reclaimed.
+    #[cfg(feature = "nostr")]
+    pub async fn send_to_npub(
+        &mut self,
+        mint_url: &UncheckedUrl,
+        unit: &CurrencyUnit,
+        amount: Amount,
+        recipient_npub: &str,
+        p2pk_lock: bool,
+        relays: &[Arc<dyn NostrRelay>],
+    ) -> Result<NostrSend, Error> {
+        let keys = self.nostr_keys.clone().ok_or(Error::NostrKeysRequired)?;
+        let recipient = nostr::parse_npub(recipient_npub)?;
+        if relays.is_empty() {
+            return Err(nostr::Error::NoRelays.into());
+        }
+
+        let proofs = match p2pk_lock {
+            true => {
+                let conditions =
+                    P2PKConditions::new(None, vec![recipient.clone()], None, None, None)?;
+                self.send_p2pk(mint_url, unit, amount, conditions).await?
+            }
+            false => self.send(mint_url, unit, amount, true).await?,
+        };
+        let token = self.proofs_to_token(mint_url.clone(), proofs, None, Some(unit.clone()))?;
+        let event = Event::direct_message(&keys, &recipient, &token, self.clock.now())?;
+
+        let mut published = Vec::new();
+        let mut relay_failures = Vec::new();
+        for relay in relays {
+            match relay.publish(&event).await {
+                Ok(()) => published.push(relay.url().to_string()),
+                Err(err) => {
+                    warn!("Could not publish to {}: {}", relay.url(), err);
+                    relay_failures.push((relay.url().to_string(), err));
+                }
+            }
+        }
+
+        Ok(NostrSend {
+            token,
+            event,
+            published,
+            relay_failures,
+        })
+    }
+
+    /// Receive the tokens in the direct messages to the wallet's Nostr keys
+    /// created at or after `since`
+    ///
+    /// Messages are fetched from every relay in `relays` and read once.
+    /// Tokens received before are reported in [NostrInbox::already_received]
+    /// and tokens that cannot be received in [NostrInbox::failures].
+    #[cfg(feature = "nostr")]
+    pub async fn check_nostr_inbox(
+        &mut self,
+        relays: &[Arc<dyn NostrRelay>],
+        since: Option<u64>,
+    ) -> Result<NostrInbox, Error> {
+        let keys = self.nostr_keys.clone().ok_or(Error::NostrKeysRequired)?;
+        if relays.is_empty() {
+            return Err(nostr::Error::NoRelays.into());
+        }
+
+        let filter = Filter::direct_messages(&keys.verifying_key(), since);
+        let mut inbox = NostrInbox::default();
+        let mut seen = HashSet::new();
+        let mut events = Vec::new();
+        for relay in relays {
+            match relay.fetch(&filter).await {
+                Ok(fetched) => events.extend(
+                    fetched
+                        .into_iter()
+                        .filter(|event| seen.insert(event.id.clone())),
+                ),
+                Err(err) => {
+                    warn!("Could not fetch from {}: {}", relay.url(), err);
+                    inbox.relay_failures.push((relay.url().to_string(), err));
+                }
+            }
+        }
+        events.sort_by_key(|event| event.created_at);
+
+        for event in events {
+            // Relays are not trusted to filter or to pass events on unchanged
+            let message = match event.verify().and_then(|_| event.decrypt(&keys)) {
+                Ok(message) => message,
+                Err(err) => {
+                    inbox.failures.push((event.id, err.into()));
+                    continue;
+                }
+            };
+
+            let tokens = nostr::find_tokens(&message);
+            if tokens.is_empty() {
+                inbox.without_token.push(event.id);
+                continue;
+            }
+
+            for token in tokens {
+                let amount = match Token::from_str(token).and_then(|token| token.value()) {
+                    Ok(amount) => amount,
+                    Err(err) => {
+                        inbox.failures.push((event.id.clone(), err.into()));
+                        continue;
+                    }
+                };
+                let options = ReceiveOptions {
+                    p2pk_signing_keys: vec![keys.clone()],
+                    ..Default::default()
+                };
+                match self.receive(token, options).await {
+                    Ok(ReceiveOutcome::Received { .. }) => inbox.received.push(NostrReceived {
+                        event_id: event.id.clone(),
+                        sender: event.pubkey.clone(),
+                        token: token.to_string(),
+                        amount,
+                    }),
+                    Ok(ReceiveOutcome::AlreadyReceived { .. }) => {
+                        inbox.already_received.push(event.id.clone())
+                    }
+                    Err(err) => inbox.failures.push((event.id.clone(), err)),
+                }
+            }
+        }
+
+        Ok(inbox)
+    }
+
+    pub fn proofs_to_token(
+        &self,
+        mint_url: UncheckedUrl,
+        proofs: Proofs,
+        memo: Option<String>,
+        unit: Option<CurrencyUnit>,
+    ) -> Result<String, Error> {
+        Ok(Token::new(mint_url, proofs, memo, unit)?.to_string())
+    }
+
+    #[cfg(feature = "nut13")]
+    pub async fn restore(&mut self, mint_url: UncheckedUrl) -> Result<Amount, Error> {
+        self.restore_with_options(mint_url, RestoreOptions::default())
+            .await
+    }
+
+    /// Restore the unspent proofs of every keyset of `mint_url` from seed
+    ///
+    /// Proofs the wallet already holds are skipped, and keyset counters are
+    /// moved past the last output the mint signed but never moved back.
+    #[cfg(feature = "nut13")]
+    pub async fn restore_with_options(
+        &mut self,
+        mint_url: UncheckedUrl,
+        options: RestoreOptions,
+    ) -> Result<Amount, Error> {
+        let mnemonic = self.mnemonic().ok_or(Error::MnemonicRequired)?;
+
+        // Check that mint is in store of mints
+        if self.localstore.get_mint(mint_url.clone()).await?.is_none() {
+            self.add_mint(mint_url.clone()).await?;
+        }
+
+        let keysets = self.get_mint_keysets(&mint_url).await?;
+        let url: Url = mint_url.clone().try_into()?;
+
+        let mut restored_value = Amount::ZERO;
+
+        for keyset in keysets {
+            let keys = self.get_keyset_keys(&mint_url, keyset.id).await?;
+
+            let client = &self.client;
+            let (proofs, last_counter) =
+                restore_keyset(keyset.id, &mnemonic, &keys, options, |request| {
+                    let url = url.clone();
+                    async move { Ok(client.post_restore(url, request).await?) }
+                })
+                .await?;
+
+            let Some(last_counter) = last_counter else {
+                continue;
+            };
+
+            if self
+                .keyset_counter(&keyset.id)
+                .await?
+                .map_or(true, |counter| counter < last_counter)
+            {
+                self.set_keyset_counter(&keyset.id, last_counter).await?;
+            }
+
+            let known_secrets: HashSet<Secret> = self
+                .localstore
+                .get_proofs(mint_url.clone())
+                .await?
+                .unwrap_or_default()
+                .into_iter()
+                .map(|proof| proof.secret)
+                .collect();
+            let proofs: Proofs = proofs
+                .into_iter()
+                .filter(|proof| !known_secrets.contains(&proof.secret))
+                .collect();
+
+            if proofs.is_empty() {
+                continue;
+            }
+
+            debug!("Restored {} proofs", proofs.len());
+
+            let states = self
+                .check_proofs_spent(mint_url.clone(), proofs.clone())
+                .await?;
+
+            let unspent_proofs: Vec<Proof> = proofs
+                .iter()
+                .zip(states)
+                .filter(|(_, state)| !state.state.eq(&State::Spent))
+                .map(|(p, _)| p)
+                .cloned()
+                .collect();
+
+            restored_value += unspent_proofs.iter().map(|p| p.amount).sum();
+
+            self.localstore
+                .add_proofs(mint_url.clone(), unspent_proofs)
+                .await?;
+        }
+        Ok(restored_value)
+    }
+
+    /// Verify all proofs in token have meet the required spend
+    /// Can be used to allow a wallet to accept payments offline while reducing
+    /// the risk of claiming back to the limits let by the spending_conditions
+    pub fn verify_token_p2pk(
+        &self,
+        token: &Token,
+        spending_conditions: P2PKConditions,
+    ) -> Result<(), Error> {
+        use crate::nuts::nut10;
+
+        if spending_conditions.refund_keys.is_some() && spending_conditions.locktime.is_none() {
+            warn!(
+                "Invalid spending conditions set: Locktime must be set if refund keys are allowed"
+            );
+            return Err(Error::InvalidSpendConditions(
+                "Must set locktime".to_string(),
+            ));
+        }
+
+        for mint_proof in &token.token {
             for proof in &mint_proof.proofs {
                 let secret: nut10::Secret = (&proof.secret).try_into().unwrap();
 
@@ -1287,33 +3039,2305 @@ impl Wallet {
         Ok(())
     }
 
-    /// Verify all proofs in token have a valid DLEQ proof
-    pub async fn verify_token_dleq(&self, token: &Token) -> Result<(), Error> {
-        let mut keys_cache: HashMap<Id, Keys> = HashMap::new();
+    /// Proofs and keyset counters of every mint, to move the wallet to
+    /// another device with [Wallet::import]
+    ///
+    /// Read from the store without asking the mints. Pending and reserved
+    /// proofs are exported as pending. The backup holds the secrets of the
+    /// proofs, anyone who reads it can spend them.
+    pub async fn export(&self) -> Result<WalletBackup, Error> {
+        let mut mints: Vec<MintBackup> = Vec::new();
+        let mut counters = HashMap::new();
+
+        let mut mint_urls: Vec<UncheckedUrl> =
+            self.localstore.get_mints().await?.into_keys().collect();
+        mint_urls.sort();
+
+        for mint_url in mint_urls {
+            let keysets = self
+                .localstore
+                .get_mint_keysets(mint_url.clone())
+                .await?
+                .unwrap_or_default();
+            for keyset in &keysets {
+                if let Some(counter) = self.localstore.get_keyset_counter(&keyset.id).await? {
+                    counters.insert(keyset.id, counter);
+                }
+            }
+            let units: HashMap<Id, CurrencyUnit> = keysets
+                .into_iter()
+                .map(|keyset| (keyset.id, keyset.unit))
+                .collect();
+
+            let unit = |proof: &Proof| units.get(&proof.keyset_id).cloned().unwrap_or_default();
+            let mut entries: Vec<MintBackup> = Vec::new();
+
+            let proofs = self.localstore.get_proofs(mint_url.clone()).await?;
+            for proof in proofs.unwrap_or_default() {
+                mint_backup(&mut entries, &mint_url, unit(&proof))
+                    .proofs
+                    .push(proof);
+            }
+            let pending = self.localstore.get_pending_proofs(mint_url.clone()).await?;
+            let reserved = self
+                .localstore
+                .get_reserved_proofs(mint_url.clone())
+                .await?;
+            for proof in pending
+                .unwrap_or_default()
+                .into_iter()
+                .chain(reserved.unwrap_or_default())
+            {
+                mint_backup(&mut entries, &mint_url, unit(&proof))
+                    .pending
+                    .push(proof);
+            }
 
-        for mint_proof in &token.token {
-            for proof in &mint_proof.proofs {
-                let mint_pubkey = match keys_cache.get(&proof.keyset_id) {
-                    Some(keys) => keys.amount_key(proof.amount),
-                    None => {
-                        let keys = self.localstore.get_keys(&proof.keyset_id).await?.unwrap();
+            entries.sort_by_key(|entry| entry.unit.to_string());
+            mints.extend(entries);
+        }
 
-                        let key = keys.amount_key(proof.amount);
-                        keys_cache.insert(proof.keyset_id, keys);
+        Ok(WalletBackup {
+            version: WALLET_BACKUP_VERSION,
+            mints,
+            counters,
+        })
+    }
 
-                        key
-                    }
-                }
-                .ok_or(Error::UnknownKey)?;
+    /// Add the proofs and keyset counters of `backup`, from [Wallet::export]
+    ///
+    /// The whole backup is checked before anything is stored: its version,
+    /// the mint urls and that no proof appears twice. Proofs the wallet
+    /// already holds, spendable or not, are skipped by `Y`. Keyset counters
+    /// are only ever moved forward so no secret is derived twice.
+    ///
+    /// With `verify` each mint is asked for its keysets and the state of the
+    /// new proofs, proofs of keysets the mint does not have in the unit of
+    /// their entry fail the import and spent proofs are dropped. Without it
+    /// the proofs are trusted as they are.
+    pub async fn import(
+        &mut self,
+        backup: WalletBackup,
+        verify: bool,
+    ) -> Result<ImportReport, Error> {
+        if backup.version > WALLET_BACKUP_VERSION {
+            return Err(Error::UnsupportedBackupVersion(backup.version));
+        }
 
-                proof
-                    .verify_dleq(mint_pubkey)
-                    .map_err(|_| Error::CouldNotVerifyDleq)?;
+        let mut ys = HashSet::new();
+        for entry in &backup.mints {
+            Url::try_from(&entry.mint_url)?;
+            for proof in entry.proofs.iter().chain(&entry.pending) {
+                if proof.amount == Amount::ZERO {
+                    return Err(Error::InvalidBackup(format!(
+                        "zero amount proof at `{}`",
+                        entry.mint_url
+                    )));
+                }
+                if !ys.insert(proof.y()?) {
+                    return Err(Error::InvalidBackup(format!(
+                        "proof listed twice at `{}`",
+                        entry.mint_url
+                    )));
+                }
             }
         }
 
+        let known_mints = self.localstore.get_mints().await?;
+        let mut units: HashMap<UncheckedUrl, HashMap<Id, CurrencyUnit>> = HashMap::new();
+        for entry in &backup.mints {
+            if !verify || units.contains_key(&entry.mint_url) {
+                continue;
+            }
+            let keysets = self
+                .get_mint_keysets(&entry.mint_url)
+                .await?
+                .into_iter()
+                .map(|keyset| (keyset.id, keyset.unit))
+                .collect();
+            units.insert(entry.mint_url.clone(), keysets);
+        }
+        for entry in &backup.mints {
+            let Some(keysets) = units.get(&entry.mint_url) else {
+                continue;
+            };
+            for proof in entry.proofs.iter().chain(&entry.pending) {
+                if keysets.get(&proof.keyset_id) != Some(&entry.unit) {
+                    return Err(Error::InvalidBackup(format!(
+                        "keyset `{}` is not a `{}` keyset of `{}`",
+                        proof.keyset_id, entry.unit, entry.mint_url
+                    )));
+                }
+            }
+        }
+
+        let mut report = ImportReport::default();
+
+        for entry in backup.mints {
+            if !known_mints.contains_key(&entry.mint_url) {
+                self.add_mint(entry.mint_url.clone()).await?;
+            }
+
+            let mut held = HashSet::new();
+            for proofs in [
+                self.localstore.get_proofs(entry.mint_url.clone()).await?,
+                self.localstore
+                    .get_pending_proofs(entry.mint_url.clone())
+                    .await?,
+                self.localstore
+                    .get_reserved_proofs(entry.mint_url.clone())
+                    .await?,
+            ] {
+                held.extend(proofs.unwrap_or_default().ys()?);
+            }
+
+            let is_new = |proof: &Proof| proof.y().is_ok_and(|y| !held.contains(&y));
+            let (mut proofs, duplicates): (Proofs, Proofs) =
+                entry.proofs.into_iter().partition(is_new);
+            let (mut pending, pending_duplicates): (Proofs, Proofs) =
+                entry.pending.into_iter().partition(is_new);
+            report.duplicates += duplicates.len() + pending_duplicates.len();
+
+            if verify && !(proofs.is_empty() && pending.is_empty()) {
+                let states: HashMap<PublicKey, State> = self
+                    .check_proofs_spent(
+                        entry.mint_url.clone(),
+                        proofs.iter().chain(&pending).cloned().collect(),
+                    )
+                    .await?
+                    .into_iter()
+                    .map(|state| (state.y, state.state))
+                    .collect();
+                let state = |proof: &Proof| {
+                    proof
+                        .y()
+                        .ok()
+                        .and_then(|y| states.get(&y).copied())
+                        .unwrap_or(State::Unspent)
+                };
+
+                let before = proofs.len() + pending.len();
+                let (unspent, in_flight): (Proofs, Proofs) = proofs
+                    .into_iter()
+                    .filter(|proof| state(proof) != State::Spent)
+                    .partition(|proof| state(proof) == State::Unspent);
+                pending.retain(|proof| state(proof) != State::Spent);
+                pending.extend(in_flight);
+                proofs = unspent;
+                report.spent += before - proofs.len() - pending.len();
+            }
+
+            report.imported = report
+                .imported
+                .checked_add(proofs.iter().map(|proof| proof.amount).sum())
+                .ok_or(crate::nuts::nut00::Error::AmountOverflow)?;
+            report.pending = report
+                .pending
+                .checked_add(pending.iter().map(|proof| proof.amount).sum())
+                .ok_or(crate::nuts::nut00::Error::AmountOverflow)?;
+
+            if !proofs.is_empty() {
+                self.localstore
+                    .add_proofs(entry.mint_url.clone(), proofs)
+                    .await?;
+            }
+            if !pending.is_empty() {
+                self.localstore
+                    .add_pending_proofs(entry.mint_url, pending)
+                    .await?;
+            }
+        }
+
+        for (keyset_id, counter) in backup.counters {
+            let current = self.localstore.get_keyset_counter(&keyset_id).await?;
+            if current.map_or(true, |current| current < counter) {
+                self.localstore
+                    .set_keyset_counter(&keyset_id, counter)
+                    .await?;
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Verify all proofs in token are for amounts of their keyset and have a
+    /// valid DLEQ proof, with the cached keys only
+    pub async fn verify_token_dleq(&self, token: &Token) -> Result<(), Error> {
+        let mut keys_cache: HashMap<Id, Keys> = HashMap::new();
+        token.value()?;
+
+        for mint_proof in &token.token {
+            for proof in &mint_proof.proofs {
+                let mint_pubkey = match keys_cache.get(&proof.keyset_id) {
+                    Some(keys) => proof_key(keys, proof)?,
+                    None => {
+                        let keys = self
+                            .localstore
+                            .get_keys(&proof.keyset_id)
+                            .await?
+                            .ok_or(Error::UnknownKey)?;
+
+                        let key = proof_key(&keys, proof)?;
+                        keys_cache.insert(proof.keyset_id, keys);
+
+                        key
+                    }
+                };
+
+                proof
+                    .verify_dleq(mint_pubkey)
+                    .map_err(|_| Error::CouldNotVerifyDleq)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Result of [Wallet::claim_expired]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExpiredClaim {
+    /// Unconditional proofs the refundable proofs were swapped for
+    pub claimed: Proofs,
+    /// Proofs of the token left as they were
+    pub unclaimed: Vec<UnclaimedProof>,
+}
+
+impl ExpiredClaim {
+    fn unclaim(&mut self, proof: &Proof, reason: UnclaimedReason) {
+        self.unclaimed.push(UnclaimedProof {
+            proof: proof.clone(),
+            reason,
+        });
+    }
+}
+
+/// Proof not claimed by [Wallet::claim_expired]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnclaimedProof {
+    pub proof: Proof,
+    pub reason: UnclaimedReason,
+}
+
+/// Why [Wallet::claim_expired] did not claim a proof
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnclaimedReason {
+    /// Secret is not a P2PK lock
+    NotLocked,
+    /// Lock of a kind that cannot be refunded, such as an HTLC
+    Unsupported,
+    /// Lock has no locktime or no refund keys
+    NotRefundable,
+    /// Refund path opens after `locktime`
+    NotExpired { locktime: u64 },
+    /// No refund key of the lock is among the signing keys
+    NotOurRefundKey,
+}
+
+/// Proofs of one unit held at one mint, from [Wallet::inventory]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProofInventory {
+    /// Value of the proofs the wallet can spend
+    pub spendable: Amount,
+    /// Value of the proofs reserved by a send or melt in flight
+    pub pending: Amount,
+    /// Number of spendable proofs of each amount
+    pub denominations: BTreeMap<Amount, usize>,
+    /// Number of spendable proofs of each keyset
+    pub keysets: HashMap<Id, usize>,
+    /// Number of pending proofs
+    pub pending_proofs: usize,
+}
+
+impl ProofInventory {
+    /// Number of spendable proofs
+    pub fn proof_count(&self) -> usize {
+        self.denominations.values().sum()
+    }
+
+    fn add_spendable(&mut self, proof: &Proof) -> Result<(), Error> {
+        self.spendable = self
+            .spendable
+            .checked_add(proof.amount)
+            .ok_or(crate::nuts::nut00::Error::AmountOverflow)?;
+        *self.denominations.entry(proof.amount).or_default() += 1;
+        *self.keysets.entry(proof.keyset_id).or_default() += 1;
+
         Ok(())
     }
+
+    fn add_pending(&mut self, proof: &Proof) -> Result<(), Error> {
+        self.pending = self
+            .pending
+            .checked_add(proof.amount)
+            .ok_or(crate::nuts::nut00::Error::AmountOverflow)?;
+        self.pending_proofs += 1;
+
+        Ok(())
+    }
+}
+
+/// What [Wallet::maintain] may do
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaintenancePolicy {
+    /// Swap proofs of inactive keysets into the active keyset of their unit
+    pub migrate_inactive: bool,
+    /// Consolidate the proofs of a unit at a mint once there are more
+    pub consolidate_above: Option<usize>,
+    /// Ask the mints about pending proofs and forget the spent ones
+    pub check_pending: bool,
+    /// Most input fees the pass may spend [NUT-02]
+    pub max_fee: Amount,
+}
+
+impl Default for MaintenancePolicy {
+    fn default() -> Self {
+        Self {
+            migrate_inactive: true,
+            consolidate_above: Some(64),
+            check_pending: true,
+            max_fee: Amount::ZERO,
+        }
+    }
+}
+
+/// How [Wallet::receive] swaps the proofs of a token
+#[derive(Clone, Default)]
+pub struct ReceiveOptions {
+    /// Denominations of the fresh proofs
+    pub split_target: SplitTarget,
+    /// Keys to sign P2PK locked proofs with [NUT-11]
+    pub p2pk_signing_keys: Vec<SigningKey>,
+}
+
+/// Result of [Wallet::receive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReceiveOutcome {
+    /// Proofs were swapped for `amount` of new ones, after fees
+    Received { amount: Amount },
+    /// Proofs were received before, at unix time `timestamp`, as a token of
+    /// `amount` before fees. Nothing was swapped.
+    AlreadyReceived { amount: Amount, timestamp: u64 },
+}
+
+/// How [Wallet::send_with_options] makes the proofs it sends
+#[derive(Clone, Default)]
+pub struct SendOptions {
+    /// Denominations of the sent proofs and of the change of the swap
+    pub split_target: SplitTarget,
+    /// Keep the DLEQ proofs of the sent proofs [NUT-12]
+    pub include_dleq: bool,
+    pub offline: OfflinePolicy,
+}
+
+/// Whether [Wallet::send_with_options] may send held proofs without a swap
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OfflinePolicy {
+    /// Always swap for fresh proofs, as [Wallet::send] does
+    #[default]
+    Never,
+    /// Send held proofs adding up to the amount, swap only if none do
+    Prefer,
+    /// Send held proofs adding up to the amount, or fail with
+    /// [Error::NoExactMatch] without contacting the mint
+    Require,
+}
+
+/// Result of [Wallet::send_to_npub]
+#[cfg(feature = "nostr")]
+#[derive(Debug)]
+pub struct NostrSend {
+    pub token: String,
+    /// Direct message carrying [NostrSend::token]
+    pub event: Event,
+    /// Urls of the relays that accepted [NostrSend::event]
+    pub published: Vec<String>,
+    pub relay_failures: Vec<(String, nostr::Error)>,
+}
+
+/// Token received by [Wallet::check_nostr_inbox]
+#[cfg(feature = "nostr")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NostrReceived {
+    pub event_id: String,
+    /// Hex key of the author of the message
+    pub sender: String,
+    pub token: String,
+    pub amount: Amount,
+}
+
+/// Result of [Wallet::check_nostr_inbox]
+#[cfg(feature = "nostr")]
+#[derive(Debug, Default)]
+pub struct NostrInbox {
+    pub received: Vec<NostrReceived>,
+    /// Ids of the messages with a token received before
+    pub already_received: Vec<String>,
+    /// Ids of the messages without a token
+    pub without_token: Vec<String>,
+    /// Messages or tokens that could not be read or received, by event id
+    pub failures: Vec<(String, Error)>,
+    pub relay_failures: Vec<(String, nostr::Error)>,
+}
+
+/// Paid states of a mint quote, see [Wallet::mint_quote_paid_stream]
+#[cfg(feature = "ws")]
+#[derive(Debug)]
+pub struct MintQuotePaidStream {
+    subscription: Subscription,
+}
+
+#[cfg(feature = "ws")]
+impl MintQuotePaidStream {
+    /// Next state of the quote that is paid, [None] once the subscription
+    /// ended
+    pub async fn next(&mut self) -> Option<MintQuoteBolt11Response> {
+        while let Some(notification) = self.subscription.next().await {
+            if let Notification::MintQuote(quote) = notification {
+                if quote.paid {
+                    return Some(quote);
+                }
+            }
+        }
+
+        None
+    }
+
+    pub async fn unsubscribe(self) -> Result<(), Error> {
+        Ok(self.subscription.unsubscribe().await?)
+    }
+}
+
+/// Cost of a melt, see [Wallet::estimate_melt_cost]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeltCostEstimate {
+    /// Amount of the invoice
+    pub amount: Amount,
+    /// Lightning fee reserve, what the payment does not use is returned
+    pub fee_reserve: Amount,
+    /// Input fees of the proofs spent
+    pub input_fees: Amount,
+    /// Value of the proofs spent
+    pub total: Amount,
+}
+
+/// Version of the [WalletBackup] format written by [Wallet::export]
+pub const WALLET_BACKUP_VERSION: u32 = 1;
+
+/// Proofs and keyset counters of a [Wallet], see [Wallet::export]
+///
+/// Plain JSON, the proofs keep their keyset ids and DLEQ proofs [NUT-12].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletBackup {
+    /// [WALLET_BACKUP_VERSION] of the wallet that wrote the backup
+    pub version: u32,
+    pub mints: Vec<MintBackup>,
+    /// Last counter used for each keyset [NUT-13]
+    pub counters: HashMap<Id, u64>,
+}
+
+/// Proofs of one unit held at one mint, in a [WalletBackup]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MintBackup {
+    pub mint_url: UncheckedUrl,
+    pub unit: CurrencyUnit,
+    /// Spendable proofs
+    pub proofs: Proofs,
+    /// Proofs of sends and melts in flight, imported as pending
+    #[serde(default)]
+    pub pending: Proofs,
+}
+
+/// Entry of `unit` in `entries`, added if there is none
+fn mint_backup<'a>(
+    entries: &'a mut Vec<MintBackup>,
+    mint_url: &UncheckedUrl,
+    unit: CurrencyUnit,
+) -> &'a mut MintBackup {
+    let index = match entries.iter().position(|entry| entry.unit == unit) {
+        Some(index) => index,
+        None => {
+            entries.push(MintBackup {
+                mint_url: mint_url.clone(),
+                unit,
+                proofs: Vec::new(),
+                pending: Vec::new(),
+            });
+            entries.len() - 1
+        }
+    };
+
+    &mut entries[index]
+}
+
+/// What [Wallet::import] added
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    /// Value of the proofs added as spendable
+    pub imported: Amount,
+    /// Value of the proofs added as pending
+    pub pending: Amount,
+    /// Proofs skipped as the wallet already held them
+    pub duplicates: usize,
+    /// Proofs dropped as the mint reported them spent
+    pub spent: usize,
+}
+
+/// What a [Wallet::maintain] pass did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MaintenanceReport {
+    /// Proofs of inactive keysets swapped into active ones
+    pub migrated: usize,
+    /// Proofs swapped away by consolidation
+    pub consolidated: usize,
+    /// Proofs consolidation swapped them for
+    pub consolidated_into: usize,
+    /// Pending proofs the mint reported spent, now forgotten
+    pub pending_spent: usize,
+    /// Pending proofs the mint has not seen spent, still pending
+    pub still_pending: usize,
+    /// Input fees of the swaps made
+    pub fees: Amount,
+    /// Swaps not made as they would have gone over the fee budget
+    pub skipped: usize,
+}
+
+/// Limits of a [Wallet::consolidate_with_options] run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConsolidateOptions {
+    /// Most input fees the run may spend [NUT-02]
+    pub max_fee: Amount,
+    /// Most proofs spent in one swap
+    ///
+    /// Mints do not advertise their limits, a swap of more inputs than the
+    /// mint accepts fails the run.
+    pub max_inputs: usize,
+}
+
+impl Default for ConsolidateOptions {
+    fn default() -> Self {
+        Self {
+            max_fee: Amount::ZERO,
+            // What the mints of this crate accept unless configured otherwise
+            max_inputs: 1_000,
+        }
+    }
+}
+
+/// What a [Wallet::consolidate] run did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConsolidationReport {
+    /// Proofs swapped away
+    pub consolidated: usize,
+    /// Proofs they were swapped for
+    pub consolidated_into: usize,
+    pub swaps: usize,
+    /// Input fees of the swaps
+    pub fees: Amount,
+    /// Stored proofs the mint had already spent, left by an interrupted run
+    /// and forgotten
+    pub already_spent: usize,
+    /// Stopped as the next swap would have gone over the fee budget
+    pub budget_exhausted: bool,
+}
+
+/// Whether the mint rejected a request as its inputs were spent
+fn is_token_spent(err: &Error) -> bool {
+    matches!(
+        err,
+        Error::Client(crate::client::Error::UnknownErrorResponse(ErrorResponse {
+            code: ErrorCode::TokenAlreadySpent,
+            ..
+        }))
+    )
+}
+
+/// Take proofs adding up to exactly `amount` out of `pool`, sorted largest
+/// first, or leave it as it is if none do
+///
+/// Denominations are powers of two, so if any proofs of `pool` add up to
+/// `amount` the largest that fit do.
+fn take_exact(pool: &mut Proofs, amount: Amount) -> Option<Proofs> {
+    let mut remaining = amount;
+    let mut picked = Vec::new();
+    for (index, proof) in pool.iter().enumerate() {
+        if remaining == Amount::ZERO {
+            break;
+        }
+        if proof.amount <= remaining {
+            remaining -= proof.amount;
+            picked.push(index);
+        }
+    }
+    if remaining != Amount::ZERO {
+        return None;
+    }
+
+    let mut taken: Proofs = picked
+        .into_iter()
+        .rev()
+        .map(|index| pool.remove(index))
+        .collect();
+    taken.reverse();
+
+    Some(taken)
+}
+
+/// Whether `proof` has spending conditions [NUT-10], so it cannot be spent
+/// or sent as plain ecash
+fn is_locked(proof: &Proof) -> bool {
+    crate::nuts::nut10::Secret::try_from(&proof.secret).is_ok()
+}
+
+/// Key `proof` is signed with, a sender can claim an amount the keyset of
+/// the proof has no key for
+fn proof_key(keys: &Keys, proof: &Proof) -> Result<PublicKey, Error> {
+    keys.amount_key(proof.amount)
+        .ok_or(Error::InvalidProofAmount {
+            keyset_id: proof.keyset_id,
+            amount: proof.amount,
+        })
+}
+
+/// Fee the mint charges to spend `proofs` [NUT-02]
+///
+/// Proofs of keysets not in `keysets` count as free.
+fn input_fee(proofs: &Proofs, keysets: &HashMap<Id, KeySetInfo>) -> Amount {
+    let fee_ppk: u64 = proofs
+        .iter()
+        .filter_map(|proof| keysets.get(&proof.keyset_id))
+        .map(|keyset| keyset.input_fee_ppk)
+        .sum();
+
+    Amount::from((fee_ppk + 999) / 1000)
+}
+
+/// Part of a payment melted by [Wallet::melt_mpp]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MppShard {
+    pub mint_url: UncheckedUrl,
+    /// Msat of the invoice paid by this mint
+    pub amount_msat: u64,
+    /// Melt quote of this part at `mint_url`
+    pub quote_id: String,
+    pub status: MppShardStatus,
+}
+
+impl MppShard {
+    fn new(mint_url: UncheckedUrl, amount_msat: u64, quote_id: String) -> Self {
+        Self {
+            mint_url,
+            amount_msat,
+            quote_id,
+            status: MppShardStatus::Unpaid,
+        }
+    }
+}
+
+/// Outcome of the melt of a [MppShard]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MppShardStatus {
+    /// Mint paid its part
+    Paid(Melted),
+    /// Mint did not pay its part, the proofs are still spendable
+    Unpaid,
+    /// Melt failed in the wallet, the state of the quote has to be checked
+    /// with the mint
+    Failed(String),
+}
+
+/// Result of [Wallet::melt_mpp]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MppMelted {
+    pub shards: Vec<MppShard>,
+}
+
+impl MppMelted {
+    /// Whether every mint paid its part
+    pub fn is_paid(&self) -> bool {
+        self.shards
+            .iter()
+            .all(|shard| matches!(shard.status, MppShardStatus::Paid(_)))
+    }
+
+    /// Preimage of the invoice, from any mint that paid its part
+    pub fn preimage(&self) -> Option<&str> {
+        self.shards.iter().find_map(|shard| match &shard.status {
+            MppShardStatus::Paid(melted) => melted.preimage.as_deref(),
+            _ => None,
+        })
+    }
+
+    /// Parts that still have to be paid
+    pub fn unpaid(&self) -> impl Iterator<Item = &MppShard> {
+        self.shards
+            .iter()
+            .filter(|shard| !matches!(shard.status, MppShardStatus::Paid(_)))
+    }
+}
+
+/// Result of [Wallet::reclaim_reserved]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReclaimReport {
+    /// Value returned to the spendable proofs
+    pub reclaimed: Amount,
+    /// Value the mints reported spent, dropped from the store
+    pub spent: Amount,
+    /// Proofs still pending at their mint, left reserved
+    pub pending: HashMap<UncheckedUrl, Proofs>,
+}
+
+/// Delay between checks of the mint quote of a [Wallet::transfer]
+pub const TRANSFER_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Checks of the mint quote of a [Wallet::transfer] before giving up
+pub const TRANSFER_POLL_ATTEMPTS: u32 = 30;
+
+/// Result of [Wallet::transfer]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferResult {
+    pub from_mint: UncheckedUrl,
+    pub to_mint: UncheckedUrl,
+    /// Mint quote at `to_mint` whose invoice the melt pays
+    pub mint_quote_id: String,
+    /// Melt quote at `from_mint`
+    pub melt_quote_id: String,
+    pub status: TransferStatus,
+}
+
+/// Stage a [Wallet::transfer] ended at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransferStatus {
+    /// `minted` sats were issued at `to_mint`, `fee` was paid at `from_mint`
+    Completed { minted: Amount, fee: Amount },
+    /// Melt was not paid, the proofs at `from_mint` are still spendable
+    MeltFailed,
+    /// Melt was paid with `fee` but nothing was minted yet, the mint quote is
+    /// kept and can be claimed with [Wallet::mint]
+    Outstanding { fee: Amount },
+}
+
+/// Check that `allocation` pays all of `invoice` and nothing more
+fn check_mpp_allocation(
+    invoice: &Bolt11Invoice,
+    allocation: &HashMap<UncheckedUrl, u64>,
+) -> Result<(), Error> {
+    let invoice_msat = invoice
+        .amount_milli_satoshis()
+        .ok_or(crate::error::Error::InvoiceAmountUndefined)?;
+
+    let mut total_msat: u64 = 0;
+    for amount_msat in allocation.values() {
+        if *amount_msat == 0 {
+            return Err(Error::MppAllocationMismatch);
+        }
+        total_msat = total_msat
+            .checked_add(*amount_msat)
+            .ok_or(Error::MppAllocationMismatch)?;
+    }
+
+    if total_msat != invoice_msat {
+        return Err(Error::MppAllocationMismatch);
+    }
+
+    Ok(())
+}
+
+/// Melt every shard at the same time with `melt` and record the outcomes
+///
+/// Shards are reported in the order given, whatever order the melts finish
+/// in.
+async fn melt_shards<F, Fut>(mut shards: Vec<MppShard>, melt: F) -> MppMelted
+where
+    F: Fn(&MppShard) -> Fut,
+    Fut: std::future::Future<Output = Result<Melted, Error>> + Send + 'static,
+{
+    let handles: Vec<_> = shards
+        .iter()
+        .map(|shard| tokio::spawn(melt(shard)))
+        .collect();
+
+    for (shard, handle) in shards.iter_mut().zip(handles) {
+        shard.status = match handle.await {
+            Ok(Ok(melted)) if melted.paid => MppShardStatus::Paid(melted),
+            Ok(Ok(_)) => MppShardStatus::Unpaid,
+            Ok(Err(err)) => MppShardStatus::Failed(err.to_string()),
+            Err(err) => MppShardStatus::Failed(err.to_string()),
+        };
+
+        match &shard.status {
+            MppShardStatus::Paid(_) => {
+                debug!(mint = %shard.mint_url, msat = shard.amount_msat, "Mpp part paid")
+            }
+            _ => warn!(mint = %shard.mint_url, msat = shard.amount_msat, "Mpp part not paid"),
+        }
+    }
+
+    MppMelted { shards }
+}
+
+/// Windows of deterministic outputs sent by [Wallet::restore_with_options]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestoreOptions {
+    /// Counter of the first output sent
+    pub start_counter: u64,
+    /// Outputs sent per restore request
+    pub batch_size: u64,
+    /// Consecutive windows without a signature before restore stops
+    pub gap_limit: u32,
+}
+
+impl Default for RestoreOptions {
+    fn default() -> Self {
+        Self {
+            start_counter: 0,
+            batch_size: 100,
+            gap_limit: 3,
+        }
+    }
+}
+
+/// Proofs the mint signed for outputs of `keyset_id` derived from
+/// `mnemonic`, and the last counter it signed
+///
+/// Only windows the mint answered count towards the gap limit, an error
+/// from `post_restore` is returned so a flaky connection never ends the
+/// scan early.
+#[cfg(feature = "nut13")]
+async fn restore_keyset<F, Fut>(
+    keyset_id: Id,
+    mnemonic: &Mnemonic,
+    keys: &Keys,
+    options: RestoreOptions,
+    mut post_restore: F,
+) -> Result<(Proofs, Option<u64>), Error>
+where
+    F: FnMut(RestoreRequest) -> Fut,
+    Fut: std::future::Future<Output = Result<RestoreResponse, Error>>,
+{
+    let batch_size = options.batch_size.max(1);
+    let mut start_counter = options.start_counter;
+    let mut empty_windows = 0;
+    let mut proofs = Proofs::new();
+    let mut last_counter = None;
+
+    while empty_windows < options.gap_limit {
+        let end_counter = start_counter + batch_size - 1;
+        let premint_secrets =
+            PreMintSecrets::restore_batch(keyset_id, mnemonic, start_counter, end_counter)?;
+
+        debug!(
+            "Attempting to restore counter {}-{} for keyset {}",
+            start_counter, end_counter, keyset_id
+        );
+
+        let response = post_restore(RestoreRequest {
+            outputs: premint_secrets.blinded_messages(),
+        })
+        .await?;
+        let restored = match_restore_response(&premint_secrets, start_counter, response)?;
+
+        if let Some((counter, _, _)) = restored.last() {
+            empty_windows = 0;
+            last_counter = Some(*counter);
+
+            let mut rs = Vec::with_capacity(restored.len());
+            let mut secrets = Vec::with_capacity(restored.len());
+            let mut signatures = Vec::with_capacity(restored.len());
+            for (_, premint, signature) in restored {
+                rs.push(premint.r);
+                secrets.push(premint.secret);
+                signatures.push(signature);
+            }
+
+            proofs.extend(construct_proofs(signatures, rs, secrets, keys)?);
+        } else {
+            empty_windows += 1;
+        }
+
+        start_counter = end_counter + 1;
+    }
+
+    Ok((proofs, last_counter))
+}
+
+/// Pair each signature of `response` with the output and counter it was
+/// requested for, ordered by counter
+///
+/// The mint may answer with any subset of the outputs in any order, but
+/// never with an output that was not requested.
+#[cfg(feature = "nut13")]
+fn match_restore_response(
+    premint_secrets: &PreMintSecrets,
+    start_counter: u64,
+    response: RestoreResponse,
+) -> Result<Vec<(u64, PreMint, BlindSignature)>, Error> {
+    if response.outputs.len() != response.signatures.len() {
+        return Err(Error::InvalidRestoreResponse);
+    }
+
+    let requested: HashMap<PublicKey, (u64, &PreMint)> = premint_secrets
+        .iter()
+        .enumerate()
+        .map(|(i, premint)| {
+            (
+                premint.blinded_message.blinded_secret,
+                (start_counter + i as u64, premint),
+            )
+        })
+        .collect();
+
+    let mut restored = response
+        .outputs
+        .iter()
+        .zip(response.signatures)
+        .map(
+            |(output, signature)| match requested.get(&output.blinded_secret) {
+                Some((counter, premint))
+                    if signature.keyset_id == premint.blinded_message.keyset_id =>
+                {
+                    Ok((*counter, (*premint).clone(), signature))
+                }
+                _ => Err(Error::InvalidRestoreResponse),
+            },
+        )
+        .collect::<Result<Vec<_>, _>>()?;
+
+    restored.sort_by_key(|(counter, _, _)| *counter);
+    restored.dedup_by_key(|(counter, _, _)| *counter);
+
+    Ok(restored)
+}
+
+/// Pair each change signature of a melt with the blank output it signs
+///
+/// The mint signs as many of the outputs as the change needs [NUT-08]. A
+/// signature with a DLEQ proof is paired with the output the proof verifies
+/// for, so the change may come in any order, one without with the output at
+/// its position. A signature of no output left is refused.
+fn match_melt_change(
+    blank_outputs: &PreMintSecrets,
+    change: Vec<BlindSignature>,
+    keys: &Keys,
+) -> Result<Vec<(PreMint, BlindSignature)>, Error> {
+    let mut unused: Vec<Option<&PreMint>> = blank_outputs.iter().map(Some).collect();
+    let mut matched = Vec::with_capacity(change.len());
+
+    for (position, signature) in change.into_iter().enumerate() {
+        let index = match &signature.dleq {
+            Some(_) => {
+                let mint_key = keys
+                    .amount_key(signature.amount)
+                    .ok_or(Error::InvalidMeltChange)?;
+                unused.iter().position(|output| {
+                    output.is_some_and(|output| {
+                        signature
+                            .verify_dleq(mint_key, output.blinded_message.blinded_secret)
+                            .is_ok()
+                    })
+                })
+            }
+            None => unused
+                .get(position)
+                .and_then(|output| output.map(|_| position)),
+        };
+        let output = index
+            .and_then(|index| unused[index].take())
+            .filter(|output| output.blinded_message.keyset_id == signature.keyset_id)
+            .ok_or(Error::InvalidMeltChange)?;
+
+        matched.push((output.clone(), signature));
+    }
+
+    Ok(matched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MNEMONIC: &str =
+        "half depart obvious quality work element tank gorilla view sugar picture humble";
+
+    fn deterministic_wallet() -> Wallet {
+        Wallet {
+            secret_source: Some(Mnemonic::from_str(MNEMONIC).unwrap()).into(),
+            ..Wallet::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_deterministic_secrets_advance_counter() {
+        let wallet = deterministic_wallet();
+        let mint_url = UncheckedUrl::from_str("https://mint.example.com").unwrap();
+        let keyset_id = Id::from_str("009a1f293253e41e").unwrap();
+
+        assert_eq!(wallet.keyset_counter(&keyset_id).await.unwrap(), None);
+
+        // 10 = 8 + 2
+        let first = wallet
+            .pre_mint_secrets(&mint_url, keyset_id, Amount::from(10))
+            .await
+            .unwrap();
+        assert_eq!(first.len(), 2);
+        assert_eq!(wallet.keyset_counter(&keyset_id).await.unwrap(), Some(1));
+
+        let second = wallet
+            .blank_pre_mint_secrets(&mint_url, keyset_id, Amount::from(4))
+            .await
+            .unwrap();
+        assert_eq!(second.len(), 2);
+        assert_eq!(wallet.keyset_counter(&keyset_id).await.unwrap(), Some(3));
+
+        let mnemonic = wallet.mnemonic().unwrap();
+        let expected = PreMintSecrets::restore_batch(keyset_id, &mnemonic, 0, 3).unwrap();
+        assert_eq!(
+            first
+                .secrets()
+                .into_iter()
+                .chain(second.secrets())
+                .collect::<Vec<_>>(),
+            expected.secrets()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rewound_counter_refuses_used_secrets() {
+        let wallet = deterministic_wallet();
+        let mint_url = UncheckedUrl::from_str("https://mint.example.com").unwrap();
+        let keyset_id = Id::from_str("009a1f293253e41e").unwrap();
+
+        wallet
+            .pre_mint_secrets(&mint_url, keyset_id, Amount::from(3))
+            .await
+            .unwrap();
+
+        wallet.set_keyset_counter(&keyset_id, 0).await.unwrap();
+        assert_eq!(wallet.keyset_counter(&keyset_id).await.unwrap(), Some(0));
+
+        assert!(matches!(
+            wallet
+                .pre_mint_secrets(&mint_url, keyset_id, Amount::from(1))
+                .await,
+            Err(Error::SecretReused { counter: 1, .. })
+        ));
+        // Counter is left where it was set
+        assert_eq!(wallet.keyset_counter(&keyset_id).await.unwrap(), Some(0));
+
+        wallet.set_keyset_counter(&keyset_id, 1).await.unwrap();
+        wallet
+            .pre_mint_secrets(&mint_url, keyset_id, Amount::from(1))
+            .await
+            .unwrap();
+        assert_eq!(wallet.keyset_counter(&keyset_id).await.unwrap(), Some(2));
+    }
+
+    /// Keys of a test mint and its signatures for the outputs at `counters`
+    fn signed_outputs(
+        mnemonic: &Mnemonic,
+        counters: &[u64],
+    ) -> (Id, Keys, HashMap<PublicKey, BlindSignature>) {
+        use bitcoin::bip32::DerivationPath;
+
+        use crate::dhke::sign_message;
+        use crate::nuts::MintKeySet;
+
+        let keyset = MintKeySet::generate(
+            b"restore",
+            CurrencyUnit::Sat,
+            &DerivationPath::from_str("m/0'/0'/0'").unwrap(),
+            8,
+        )
+        .unwrap();
+
+        let mut signatures = HashMap::new();
+        for (i, counter) in counters.iter().enumerate() {
+            let amount = Amount::from(1 << i);
+            let output = PreMintSecrets::restore_batch(keyset.id, mnemonic, *counter, *counter)
+                .unwrap()
+                .blinded_messages()
+                .remove(0);
+            let secret_key = &keyset.keys.get(&amount).unwrap().secret_key;
+            let c = sign_message(secret_key, &output.blinded_secret).unwrap();
+            signatures.insert(
+                output.blinded_secret,
+                BlindSignature::new(amount, c, keyset.id, &output.blinded_secret, secret_key)
+                    .unwrap(),
+            );
+        }
+
+        (keyset.id, Keys::from(&keyset.keys), signatures)
+    }
+
+    /// Answer a restore request with the signed outputs in reverse order
+    fn restore_response(
+        signatures: &HashMap<PublicKey, BlindSignature>,
+        request: RestoreRequest,
+    ) -> RestoreResponse {
+        let (outputs, signatures) = request
+            .outputs
+            .into_iter()
+            .rev()
+            .filter_map(|output| {
+                let signature = signatures.get(&output.blinded_secret)?.clone();
+                Some((output, signature))
+            })
+            .unzip();
+
+        RestoreResponse::new(outputs, signatures)
+    }
+
+    #[tokio::test]
+    async fn test_restore_keyset_windows() {
+        let mnemonic = Mnemonic::from_str(MNEMONIC).unwrap();
+        let (keyset_id, keys, signatures) = signed_outputs(&mnemonic, &[2, 3, 15, 41]);
+        let options = RestoreOptions {
+            start_counter: 0,
+            batch_size: 10,
+            gap_limit: 2,
+        };
+
+        let mut requests = 0;
+        let (proofs, last_counter) =
+            restore_keyset(keyset_id, &mnemonic, &keys, options, |request| {
+                requests += 1;
+                let response = restore_response(&signatures, request);
+                async move { Ok(response) }
+            })
+            .await
+            .unwrap();
+
+        // 20..30 and 30..40 are empty, 41 is past the gap limit
+        assert_eq!(requests, 4);
+        assert_eq!(last_counter, Some(15));
+        let expected: Vec<Secret> = [2, 3, 15]
+            .iter()
+            .map(|counter| Secret::from_seed(&mnemonic, keyset_id, *counter).unwrap())
+            .collect();
+        assert_eq!(
+            proofs.iter().map(|p| p.secret.clone()).collect::<Vec<_>>(),
+            expected
+        );
+        assert_eq!(
+            proofs.iter().map(|p| p.amount).sum::<Amount>(),
+            Amount::from(7)
+        );
+
+        // Partially restored before, starting mid-range
+        let options = RestoreOptions {
+            start_counter: 3,
+            gap_limit: 3,
+            ..options
+        };
+        let (proofs, last_counter) =
+            restore_keyset(keyset_id, &mnemonic, &keys, options, |request| {
+                let response = restore_response(&signatures, request);
+                async move { Ok(response) }
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(last_counter, Some(41));
+        assert_eq!(
+            proofs.iter().map(|p| p.amount).collect::<Vec<_>>(),
+            [2, 4, 8].map(Amount::from).to_vec()
+        );
+        assert_eq!(
+            proofs[0].secret,
+            Secret::from_seed(&mnemonic, keyset_id, 3).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_restore_keyset_transport_error_is_not_a_gap() {
+        let mnemonic = Mnemonic::from_str(MNEMONIC).unwrap();
+        let (keyset_id, keys, signatures) = signed_outputs(&mnemonic, &[2, 25]);
+        let options = RestoreOptions {
+            start_counter: 0,
+            batch_size: 10,
+            gap_limit: 2,
+        };
+
+        let mut requests = 0;
+        let result = restore_keyset(keyset_id, &mnemonic, &keys, options, |request| {
+            requests += 1;
+            let response = match requests {
+                2 => Err(Error::Custom("connection reset".to_string())),
+                _ => Ok(restore_response(&signatures, request)),
+            };
+            async move { response }
+        })
+        .await;
+
+        assert!(matches!(result, Err(Error::Custom(_))));
+        assert_eq!(requests, 2);
+    }
+
+    #[test]
+    fn test_match_restore_response_rejects_unrequested_outputs() {
+        let mnemonic = Mnemonic::from_str(MNEMONIC).unwrap();
+        let (keyset_id, _, signatures) = signed_outputs(&mnemonic, &[12]);
+
+        let premint_secrets = PreMintSecrets::restore_batch(keyset_id, &mnemonic, 0, 9).unwrap();
+        let unrequested = PreMintSecrets::restore_batch(keyset_id, &mnemonic, 10, 19).unwrap();
+
+        let response = restore_response(
+            &signatures,
+            RestoreRequest {
+                outputs: unrequested.blinded_messages(),
+            },
+        );
+        assert_eq!(response.signatures.len(), 1);
+        assert!(matches!(
+            match_restore_response(&premint_secrets, 0, response),
+            Err(Error::InvalidRestoreResponse)
+        ));
+
+        // Counters follow the request, not the start of the scan
+        let response = restore_response(
+            &signatures,
+            RestoreRequest {
+                outputs: unrequested.blinded_messages(),
+            },
+        );
+        let restored = match_restore_response(&unrequested, 10, response).unwrap();
+        assert_eq!(restored.len(), 1);
+        assert_eq!(restored[0].0, 12);
+    }
+
+    #[test]
+    fn test_match_melt_change() {
+        use bitcoin::bip32::DerivationPath;
+
+        use crate::dhke::{sign_message, verify_message};
+        use crate::nuts::MintKeySet;
+
+        let keyset = MintKeySet::generate(
+            b"change",
+            CurrencyUnit::Sat,
+            &DerivationPath::from_str("m/0'/0'/0'").unwrap(),
+            8,
+        )
+        .unwrap();
+        let keys = Keys::from(&keyset.keys);
+        // A fee reserve of 10 sat takes 4 blank outputs
+        let blank = PreMintSecrets::blank(keyset.id, Amount::from(10)).unwrap();
+        let outputs: Vec<&PreMint> = blank.iter().collect();
+        let sign = |blinded_secret: &PublicKey, amount: u64| {
+            let amount = Amount::from(amount);
+            let secret_key = &keyset.keys.get(&amount).unwrap().secret_key;
+            let c = sign_message(secret_key, blinded_secret).unwrap();
+            BlindSignature::new(amount, c, keyset.id, blinded_secret, secret_key).unwrap()
+        };
+        // Amounts of the change, each unblinded into a valid proof of the
+        // output it was paired with
+        let unblind = |change: Vec<BlindSignature>| -> Result<Vec<(usize, u64)>, Error> {
+            let matched = match_melt_change(&blank, change, &keys)?;
+            let mut amounts = Vec::new();
+            for (output, signature) in matched {
+                let proof = construct_proofs(
+                    vec![signature],
+                    vec![output.r.clone()],
+                    vec![output.secret.clone()],
+                    &keys,
+                )?
+                .remove(0);
+                let secret_key = &keyset.keys.get(&proof.amount).unwrap().secret_key;
+                verify_message(secret_key, proof.c, proof.secret.as_bytes()).unwrap();
+                let index = outputs
+                    .iter()
+                    .position(|o| o.secret == output.secret)
+                    .unwrap();
+                amounts.push((index, u64::from(proof.amount)));
+            }
+            Ok(amounts)
+        };
+        let blinded = |index: usize| outputs[index].blinded_message.blinded_secret;
+
+        // Change of 6 sat for two of the four outputs, the others unused
+        let change = vec![sign(&blinded(0), 4), sign(&blinded(1), 2)];
+        assert_eq!(unblind(change).unwrap(), vec![(0, 4), (1, 2)]);
+
+        // Reordered, paired by the outputs the DLEQ proofs are for
+        let change = vec![sign(&blinded(3), 1), sign(&blinded(0), 4)];
+        assert_eq!(unblind(change).unwrap(), vec![(3, 1), (0, 4)]);
+
+        // Without DLEQ proofs the change is paired by position
+        let change: Vec<BlindSignature> = [sign(&blinded(0), 4), sign(&blinded(1), 2)]
+            .into_iter()
+            .map(|signature| BlindSignature {
+                dleq: None,
+                ..signature
+            })
+            .collect();
+        assert_eq!(unblind(change).unwrap(), vec![(0, 4), (1, 2)]);
+
+        // A signature of an output that was never sent
+        let unknown = crate::nuts::SecretKey::generate().public_key();
+        let change = vec![sign(&blinded(0), 4), sign(&unknown, 2)];
+        assert!(matches!(unblind(change), Err(Error::InvalidMeltChange)));
+
+        // The same output signed twice
+        let change = vec![sign(&blinded(2), 4), sign(&blinded(2), 2)];
+        assert!(matches!(unblind(change), Err(Error::InvalidMeltChange)));
+
+        // More change than outputs
+        let change = (0..5)
+            .map(|i| BlindSignature {
+                dleq: None,
+                ..sign(&blinded(i % 4), 1)
+            })
+            .collect();
+        assert!(matches!(unblind(change), Err(Error::InvalidMeltChange)));
+
+        // Signed by another keyset
+        let mut other = sign(&blinded(0), 4);
+        other.keyset_id = Id::from_str("00ad268c4d1f5826").unwrap();
+        assert!(matches!(
+            unblind(vec![other]),
+            Err(Error::InvalidMeltChange)
+        ));
+    }
+
+    #[test]
+    fn test_input_fee_rounds_up() {
+        let charged = Id::from_str("009a1f293253e41e").unwrap();
+        let free = Id::from_str("0042ade98b2a370a").unwrap();
+        let keysets: HashMap<Id, KeySetInfo> = [(charged, 400), (free, 0)]
+            .into_iter()
+            .map(|(id, input_fee_ppk)| {
+                let keyset = KeySetInfo {
+                    input_fee_ppk,
+                    ..KeySetInfo::from(KeySet {
+                        id,
+                        unit: CurrencyUnit::Sat,
+                        keys: Keys::new(Default::default()),
+                    })
+                };
+                (id, keyset)
+            })
+            .collect();
+        let proofs = |ids: &[Id]| -> Proofs {
+            ids.iter()
+                .map(|id| {
+                    Proof::new(
+                        Amount::from(1),
+                        *id,
+                        Secret::generate(),
+                        crate::nuts::SecretKey::generate().public_key(),
+                    )
+                })
+                .collect()
+        };
+
+        assert_eq!(input_fee(&proofs(&[]), &keysets), Amount::ZERO);
+        assert_eq!(input_fee(&proofs(&[free, free]), &keysets), Amount::ZERO);
+        assert_eq!(input_fee(&proofs(&[charged]), &keysets), Amount::from(1));
+        assert_eq!(
+            input_fee(&proofs(&[charged, charged, free]), &keysets),
+            Amount::from(1)
+        );
+        assert_eq!(
+            input_fee(&proofs(&[charged, charged, charged]), &keysets),
+            Amount::from(2)
+        );
+    }
+
+    #[test]
+    fn test_swap_split_balances_with_input_fees() {
+        let keyset_id = Id::from_str("009a1f293253e41e").unwrap();
+
+        // (input amounts, input_fee_ppk, send, fee)
+        let cases: [(&[u64], u64, Option<u64>, u64); 8] = [
+            (&[8, 4, 2, 1], 0, Some(10), 0),
+            (&[8, 4, 2, 1], 250, Some(10), 1),
+            (&[8, 4, 2, 1], 251, Some(10), 2),
+            (&[8, 4, 2, 1], 333, None, 2),
+            (&[8, 4, 2, 1], 1000, Some(11), 4),
+            (&[64], 1, Some(63), 1),
+            (&[32, 32, 32], 334, Some(50), 2),
+            (&[32, 32, 32], 1000, None, 3),
+        ];
+
+        for (amounts, input_fee_ppk, send, fee) in cases {
+            let keysets = HashMap::from([(
+                keyset_id,
+                KeySetInfo {
+                    input_fee_ppk,
+                    ..KeySetInfo::from(KeySet {
+                        id: keyset_id,
+                        unit: CurrencyUnit::Sat,
+                        keys: Keys::new(Default::default()),
+                    })
+                },
+            )]);
+            let proofs: Proofs = amounts
+                .iter()
+                .map(|amount| {
+                    Proof::new(
+                        Amount::from(*amount),
+                        keyset_id,
+                        Secret::generate(),
+                        crate::nuts::SecretKey::generate().public_key(),
+                    )
+                })
+                .collect();
+            let fee_paid = input_fee(&proofs, &keysets);
+            assert_eq!(
+                fee_paid,
+                Amount::from(fee),
+                "{amounts:?} at {input_fee_ppk} ppk"
+            );
+
+            let split = Amount::split_with_fee(
+                proofs.total_amount().unwrap(),
+                fee_paid,
+                send.map(Amount::from),
+                &SplitTarget::None,
+            )
+            .unwrap();
+            if let Some(send) = send {
+                assert_eq!(split.send_total(), Amount::from(send));
+            }
+
+            // The mint would accept the outputs as balanced
+            let outputs = [split.send, split.keep].concat();
+            SwapRequestBuilder::new()
+                .inputs(proofs)
+                .outputs(PreMintSecrets::random_with_amounts(keyset_id, &outputs).unwrap())
+                .input_fee(fee_paid)
+                .build()
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_take_exact() {
+        let id = Id::from_str("009a1f293253e41e").unwrap();
+        let mut pool: Proofs = [64, 16, 8, 8, 2, 1]
+            .into_iter()
+            .map(|amount| {
+                Proof::new(
+                    Amount::from(amount),
+                    id,
+                    Secret::generate(),
+                    crate::nuts::SecretKey::generate().public_key(),
+                )
+            })
+            .collect();
+        let amounts = |proofs: &Proofs| -> Vec<u64> {
+            proofs.iter().map(|proof| u64::from(proof.amount)).collect()
+        };
+
+        let taken = take_exact(&mut pool, Amount::from(26)).unwrap();
+        assert_eq!(amounts(&taken), vec![16, 8, 2]);
+        assert_eq!(amounts(&pool), vec![64, 8, 1]);
+
+        // Nothing adds up to 4, the pool is left as it is
+        assert_eq!(take_exact(&mut pool, Amount::from(4)), None);
+        assert_eq!(amounts(&pool), vec![64, 8, 1]);
+
+        let taken = take_exact(&mut pool, Amount::from(73)).unwrap();
+        assert_eq!(amounts(&taken), vec![64, 8, 1]);
+        assert!(pool.is_empty());
+    }
+
+    /// Url of a mint counting the requests that reach it, all refused
+    fn counting_mint() -> (UncheckedUrl, Arc<std::sync::atomic::AtomicUsize>) {
+        use std::io::Write;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = UncheckedUrl::new(format!("http://{}", listener.local_addr().unwrap()));
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&hits);
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                let _ = stream
+                    .write_all(b"HTTP/1.1 500 X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+            }
+        });
+
+        (url, hits)
+    }
+
+    /// Wallet of `mint_url` holding 8, 4, 4 and 1 sat and 16 sat locked to a
+    /// key
+    async fn offline_wallet(mint_url: &UncheckedUrl) -> Wallet {
+        let mut wallet = Wallet::default();
+        wallet
+            .client
+            .set_retry_policy(crate::client::RetryPolicy::none());
+        let keyset_id = Id::from_str("009a1f293253e41e").unwrap();
+        let proof = |amount: u64, secret: Secret| {
+            Proof::new(
+                Amount::from(amount),
+                keyset_id,
+                secret,
+                crate::nuts::SecretKey::generate().public_key(),
+            )
+        };
+
+        wallet
+            .localstore
+            .add_mint(mint_url.clone(), None)
+            .await
+            .unwrap();
+        wallet
+            .localstore
+            .add_mint_keysets(
+                mint_url.clone(),
+                vec![KeySetInfo {
+                    active: true,
+                    ..KeySetInfo::from(KeySet {
+                        id: keyset_id,
+                        unit: CurrencyUnit::Sat,
+                        keys: Keys::new(Default::default()),
+                    })
+                }],
+            )
+            .await
+            .unwrap();
+
+        let key = SigningKey::new(crate::nuts::SecretKey::generate());
+        let conditions =
+            P2PKConditions::new(None, vec![key.verifying_key()], None, None, None).unwrap();
+        let mut proofs: Proofs = [8, 4, 4, 1]
+            .into_iter()
+            .map(|amount| proof(amount, Secret::generate()))
+            .collect();
+        proofs.push(proof(16, Secret::try_from(conditions).unwrap()));
+        wallet
+            .localstore
+            .add_proofs(mint_url.clone(), proofs)
+            .await
+            .unwrap();
+
+        wallet
+    }
+
+    #[tokio::test]
+    async fn test_receive_proof_of_amount_without_key() {
+        use std::sync::atomic::Ordering;
+
+        let (mint_url, hits) = counting_mint();
+        let mut wallet = offline_wallet(&mint_url).await;
+        let keyset = crate::nuts::MintKeySet::generate(
+            b"secret",
+            CurrencyUnit::Sat,
+            &bitcoin::bip32::DerivationPath::from_str("m/0'/0'/0'").unwrap(),
+            8,
+        )
+        .unwrap();
+        wallet
+            .localstore
+            .add_keys(Keys::from(&keyset.keys))
+            .await
+            .unwrap();
+        let balance = wallet.mint_balances().await.unwrap();
+        let proof = |amount: u64| {
+            Proof::new(
+                Amount::from(amount),
+                keyset.id,
+                Secret::generate(),
+                crate::nuts::SecretKey::generate().public_key(),
+            )
+        };
+
+        // No key for 3 among the powers of two
+        let token = Token::new(
+            mint_url.clone(),
+            vec![proof(3), proof(2)],
+            None,
+            Some(CurrencyUnit::Sat),
+        )
+        .unwrap();
+        let invalid_amount = |result| {
+            matches!(
+                result,
+                Err(Error::InvalidProofAmount { keyset_id, amount })
+                    if keyset_id == keyset.id && amount == Amount::from(3)
+            )
+        };
+        assert!(invalid_amount(wallet.verify_token_dleq(&token).await));
+        assert!(invalid_amount(
+            wallet
+                .receive(&token.to_string(), ReceiveOptions::default())
+                .await
+                .map(|_| ())
+        ));
+
+        // Claimed amounts that cannot add up
+        let token = Token::new(
+            mint_url.clone(),
+            vec![proof(1 << 63), proof(1 << 63)],
+            None,
+            None,
+        )
+        .unwrap();
+        assert!(matches!(
+            wallet
+                .receive(&token.to_string(), ReceiveOptions::default())
+                .await,
+            Err(Error::NUT00(crate::nuts::nut00::Error::AmountOverflow))
+        ));
+
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+        assert_eq!(wallet.mint_balances().await.unwrap(), balance);
+    }
+
+    #[tokio::test]
+    async fn test_offline_send_of_held_proofs() {
+        use std::sync::atomic::Ordering;
+
+        let (mint_url, hits) = counting_mint();
+        let mut wallet = offline_wallet(&mint_url).await;
+        let balance = |wallet: &Wallet| {
+            let wallet = wallet.clone();
+            let mint_url = mint_url.clone();
+            async move { wallet.mint_balances().await.unwrap()[&mint_url] }
+        };
+        let options = |offline| SendOptions {
+            offline,
+            ..SendOptions::default()
+        };
+
+        let sent = wallet
+            .send_with_options(
+                &mint_url,
+                &CurrencyUnit::Sat,
+                Amount::from(9),
+                &options(OfflinePolicy::Require),
+            )
+            .await
+            .unwrap();
+        let amounts: Vec<Amount> = sent.iter().map(|proof| proof.amount).collect();
+        assert_eq!(amounts, vec![Amount::from(8), Amount::from(1)]);
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+
+        let sent = wallet
+            .send_with_options(
+                &mint_url,
+                &CurrencyUnit::Sat,
+                Amount::from(4),
+                &options(OfflinePolicy::Prefer),
+            )
+            .await
+            .unwrap();
+        assert_eq!(sent.total_amount().unwrap(), Amount::from(4));
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+
+        // The locked proof is not sent as plain ecash
+        assert_eq!(balance(&wallet).await, Amount::from(20));
+        assert!(matches!(
+            wallet
+                .send_with_options(
+                    &mint_url,
+                    &CurrencyUnit::Sat,
+                    Amount::from(16),
+                    &options(OfflinePolicy::Require),
+                )
+                .await,
+            Err(Error::NoExactMatch(amount)) if amount == Amount::from(16)
+        ));
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+
+        let transactions = wallet
+            .transactions(&TransactionFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(transactions.len(), 2);
+        assert!(transactions
+            .iter()
+            .all(|transaction| transaction.kind == TransactionKind::Send));
+    }
+
+    #[tokio::test]
+    async fn test_offline_send_without_exact_proofs() {
+        use std::sync::atomic::Ordering;
+
+        let (mint_url, hits) = counting_mint();
+        let mut wallet = offline_wallet(&mint_url).await;
+        let options = |offline| SendOptions {
+            offline,
+            ..SendOptions::default()
+        };
+
+        // No proofs of 8, 4, 4 and 1 sat add up to 10
+        assert!(matches!(
+            wallet
+                .send_with_options(
+                    &mint_url,
+                    &CurrencyUnit::Sat,
+                    Amount::from(10),
+                    &options(OfflinePolicy::Require),
+                )
+                .await,
+            Err(Error::NoExactMatch(amount)) if amount == Amount::from(10)
+        ));
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+
+        // Preferred offline but swapped online, which the mint refuses
+        assert!(wallet
+            .send_with_options(
+                &mint_url,
+                &CurrencyUnit::Sat,
+                Amount::from(10),
+                &options(OfflinePolicy::Prefer),
+            )
+            .await
+            .is_err());
+        assert!(hits.load(Ordering::SeqCst) > 0);
+
+        // Nothing was spent
+        assert_eq!(
+            wallet.mint_balances().await.unwrap()[&mint_url],
+            Amount::from(33)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_claim_expired_htlc_and_mixed_sig_all() {
+        use std::sync::atomic::Ordering;
+
+        use crate::nuts::nut10;
+        use crate::test_utils::clock::ManualClock;
+
+        let (mint_url, hits) = counting_mint();
+        let mut wallet = offline_wallet(&mint_url).await;
+        let keyset_id = Id::from_str("009a1f293253e41e").unwrap();
+        let proof = |amount: u64, secret: Secret| {
+            Proof::new(
+                Amount::from(amount),
+                keyset_id,
+                secret,
+                crate::nuts::SecretKey::generate().public_key(),
+            )
+        };
+
+        let locktime = unix_time() + 60;
+        let clock = Arc::new(ManualClock::new(locktime + 1));
+        wallet.set_clock(Arc::clone(&clock) as Arc<dyn Clock>);
+        let first = SigningKey::new(crate::nuts::SecretKey::generate());
+        let second = SigningKey::new(crate::nuts::SecretKey::generate());
+        let receiver = SigningKey::new(crate::nuts::SecretKey::generate());
+        let sig_all = |refund: &SigningKey| {
+            let conditions = P2PKConditions::new(
+                Some(locktime),
+                vec![receiver.verifying_key()],
+                Some(vec![refund.verifying_key()]),
+                None,
+                Some(SigFlag::SigAll),
+            )
+            .unwrap();
+            Secret::try_from(conditions).unwrap()
+        };
+        let token = |proofs: Proofs| {
+            Token::new(mint_url.clone(), proofs, None, Some(CurrencyUnit::Sat)).unwrap()
+        };
+
+        // An HTLC is not refunded, even though nothing else needs a swap
+        let htlc: Secret = nut10::Secret::new(
+            nut10::Kind::HTLC,
+            receiver.verifying_key().to_string(),
+            vec![],
+        )
+        .try_into()
+        .unwrap();
+        let claim = wallet
+            .claim_expired(&token(vec![proof(4, htlc)]), vec![first.clone()])
+            .await
+            .unwrap();
+        assert!(claim.claimed.is_empty());
+        assert_eq!(claim.unclaimed.len(), 1);
+        assert_eq!(claim.unclaimed[0].reason, UnclaimedReason::Unsupported);
+
+        // Outputs cannot be signed with both refund keys
+        assert!(matches!(
+            wallet
+                .claim_expired(
+                    &token(vec![proof(4, sig_all(&first)), proof(2, sig_all(&second))]),
+                    vec![first.clone(), second.clone()],
+                )
+                .await,
+            Err(Error::InvalidSpendConditions(_))
+        ));
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+
+        // One key for every SIG_ALL lock goes to the mint
+        assert!(wallet
+            .claim_expired(
+                &token(vec![proof(4, sig_all(&first)), proof(2, sig_all(&first))]),
+                vec![first],
+            )
+            .await
+            .is_err());
+        assert!(hits.load(Ordering::SeqCst) > 0);
+    }
+
+    #[tokio::test]
+    async fn test_balances_and_inventory_by_mint_and_unit() {
+        let wallet = Wallet::default();
+        let mint_a = UncheckedUrl::from("https://a.example.com");
+        let mint_b = UncheckedUrl::from("https://b.example.com");
+        let sat_a = Id::from_str("009a1f293253e41e").unwrap();
+        let usd_a = Id::from_str("0042ade98b2a370a").unwrap();
+        let sat_b = Id::from_str("00ad268c4d1f5826").unwrap();
+        let proof = |amount: u64, keyset_id: Id| {
+            Proof::new(
+                Amount::from(amount),
+                keyset_id,
+                Secret::generate(),
+                crate::nuts::SecretKey::generate().public_key(),
+            )
+        };
+
+        for (mint_url, keysets) in [
+            (
+                &mint_a,
+                vec![(sat_a, CurrencyUnit::Sat), (usd_a, CurrencyUnit::Usd)],
+            ),
+            (&mint_b, vec![(sat_b, CurrencyUnit::Sat)]),
+        ] {
+            wallet
+                .localstore
+                .add_mint(mint_url.clone(), None)
+                .await
+                .unwrap();
+            let keysets = keysets
+                .into_iter()
+                .map(|(id, unit)| KeySetInfo {
+                    active: true,
+                    ..KeySetInfo::from(KeySet {
+                        id,
+                        unit,
+                        keys: Keys::new(Default::default()),
+                    })
+                })
+                .collect();
+            wallet
+                .localstore
+                .add_mint_keysets(mint_url.clone(), keysets)
+                .await
+                .unwrap();
+        }
+
+        let dust: Proofs = (0..5).map(|_| proof(1, sat_a)).collect();
+        wallet
+            .localstore
+            .add_proofs(mint_a.clone(), dust)
+            .await
+            .unwrap();
+        wallet
+            .localstore
+            .add_proofs(mint_a.clone(), vec![proof(8, sat_a), proof(4, usd_a)])
+            .await
+            .unwrap();
+        wallet
+            .localstore
+            .add_proofs(mint_b.clone(), vec![proof(2, sat_b)])
+            .await
+            .unwrap();
+
+        // A send in flight at mint B has reserved its inputs
+        wallet
+            .localstore
+            .add_pending_proofs(mint_b.clone(), vec![proof(16, sat_b), proof(4, sat_b)])
+            .await
+            .unwrap();
+
+        let balances = wallet.balances().await.unwrap();
+        assert_eq!(balances.len(), 3);
+        assert_eq!(
+            balances[&(mint_a.clone(), CurrencyUnit::Sat)],
+            Amount::from(13)
+        );
+        assert_eq!(
+            balances[&(mint_a.clone(), CurrencyUnit::Usd)],
+            Amount::from(4)
+        );
+        assert_eq!(
+            balances[&(mint_b.clone(), CurrencyUnit::Sat)],
+            Amount::from(2)
+        );
+
+        let inventory = wallet.inventory().await.unwrap();
+        let sats_a = &inventory[&(mint_a.clone(), CurrencyUnit::Sat)];
+        assert_eq!(sats_a.proof_count(), 6);
+        assert_eq!(
+            sats_a.denominations,
+            BTreeMap::from([(Amount::from(1), 5), (Amount::from(8), 1)])
+        );
+        assert_eq!(sats_a.keysets, HashMap::from([(sat_a, 6)]));
+        assert_eq!(sats_a.pending, Amount::ZERO);
+
+        let sats_b = &inventory[&(mint_b.clone(), CurrencyUnit::Sat)];
+        assert_eq!(sats_b.spendable, Amount::from(2));
+        assert_eq!(sats_b.proof_count(), 1);
+        assert_eq!(sats_b.pending, Amount::from(20));
+        assert_eq!(sats_b.pending_proofs, 2);
+    }
+
+    #[tokio::test]
+    async fn test_random_secrets_leave_counter() {
+        let wallet = Wallet::default();
+        let mint_url = UncheckedUrl::from_str("https://mint.example.com").unwrap();
+        let keyset_id = Id::from_str("009a1f293253e41e").unwrap();
+
+        let pre_mint_secrets = wallet
+            .pre_mint_secrets(&mint_url, keyset_id, Amount::from(7))
+            .await
+            .unwrap();
+        assert_eq!(pre_mint_secrets.len(), 3);
+        assert_eq!(wallet.keyset_counter(&keyset_id).await.unwrap(), None);
+    }
+
+    #[cfg(feature = "mint")]
+    mod mpp {
+        use std::time::Duration;
+
+        use tokio::sync::{Barrier, Mutex as AsyncMutex};
+
+        use super::*;
+        use crate::cdk_database::mint_memory::MintMemoryDatabase;
+        use crate::mint::Mint;
+        use crate::nuts::nut15::MppMethodSettings;
+        use crate::nuts::{MeltBolt11Request, Nuts};
+
+        // Mainnet 2500u
+        const INVOICE: &str = "lnbc2500u1pj48ugqdq8w3jhxaqpp5qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqsp59g4z52329g4z52329g4z52329g4z52329g4z52329g4z52329g4q9qrsgqcqzysd6exwz2yjprkglq820pffp8wcu97sxuzcgx7raensa83dnc4kpmhkuqevwf6hmv6a3mc9aegn89jv7a8e9wggdxtfsjwrur7n6sx4rsq7rvjx3";
+
+        /// Lightning node of the payee, it settles once every part arrived
+        struct FakeLightning {
+            parts: Barrier,
+            timeout: Duration,
+        }
+
+        impl FakeLightning {
+            fn new(parts: usize, timeout: Duration) -> Arc<Self> {
+                Arc::new(Self {
+                    parts: Barrier::new(parts),
+                    timeout,
+                })
+            }
+
+            async fn pay_part(&self) -> Result<String, Error> {
+                tokio::time::timeout(self.timeout, self.parts.wait())
+                    .await
+                    .map_err(|_| Error::Custom("Payment timed out".to_string()))?;
+
+                Ok("0".repeat(64))
+            }
+        }
+
+        async fn create_mint(mpp: bool) -> Arc<AsyncMutex<Mint>> {
+            let mut nuts = Nuts::default();
+            if mpp {
+                nuts.nut15.methods.push(MppMethodSettings {
+                    method: PaymentMethod::Bolt11,
+                    unit: CurrencyUnit::Sat,
+                });
+            }
+            let mint_info = MintInfo {
+                nuts,
+                ..MintInfo::default()
+            };
+            let localstore = MintMemoryDatabase::new(
+                mint_info,
+                HashMap::new(),
+                vec![],
+                vec![],
+                vec![],
+                vec![],
+                vec![],
+                HashMap::new(),
+            )
+            .unwrap();
+
+            let mint = Mint::new(
+                Arc::new(localstore),
+                Mnemonic::from_str(MNEMONIC).unwrap(),
+                HashSet::new(),
+                Amount::from(1),
+                0.01,
+            )
+            .await
+            .unwrap();
+
+            Arc::new(AsyncMutex::new(mint))
+        }
+
+        /// Melt request for `quote` with inputs freshly signed by `mint`
+        async fn melt_request(mint: &Mint, quote: &MeltQuote) -> MeltBolt11Request {
+            let keyset = mint
+                .pubkeys()
+                .await
+                .unwrap()
+                .keysets
+                .into_iter()
+                .find(|keyset| keyset.unit == CurrencyUnit::Sat)
+                .unwrap();
+            let pre_mint =
+                PreMintSecrets::random(keyset.id, quote.amount + quote.fee_reserve).unwrap();
+
+            let mut signatures = Vec::new();
+            for blinded_message in pre_mint.blinded_messages() {
+                signatures.push(mint.blind_sign(&blinded_message).await.unwrap());
+            }
+            let inputs =
+                construct_proofs(signatures, pre_mint.rs(), pre_mint.secrets(), &keyset.keys)
+                    .unwrap();
+
+            MeltBolt11Request::new(quote.id.clone(), inputs, None)
+        }
+
+        /// What the mint does for a melt, paying its part over `lightning`
+        async fn melt_at_mint(
+            mint: Arc<AsyncMutex<Mint>>,
+            request: MeltBolt11Request,
+            lightning: Arc<FakeLightning>,
+        ) -> Result<Melted, Error> {
+            let mut mint = mint.lock().await;
+            let quote = mint
+                .reserve_melt_request(&request)
+                .await
+                .map_err(|err| Error::Custom(err.to_string()))?;
+
+            match lightning.pay_part().await {
+                Ok(preimage) => {
+                    let response = mint
+                        .process_melt_request(&request, &preimage, quote.amount)
+                        .await
+                        .map_err(|err| Error::Custom(err.to_string()))?;
+
+                    Ok(Melted {
+                        paid: response.paid,
+                        preimage: response.payment_preimage,
+                        change: None,
+                        fee_paid: response.fee_paid,
+                    })
+                }
+                Err(_) => {
+                    mint.abort_melt(&quote.id)
+                        .await
+                        .map_err(|err| Error::Custom(err.to_string()))?;
+
+                    Ok(Melted::default())
+                }
+            }
+        }
+
+        /// Quote every part at its mint and melt the `sent` parts at once,
+        /// losing the response of the `lost` mint
+        async fn melt_parts(
+            mints: &HashMap<UncheckedUrl, Arc<AsyncMutex<Mint>>>,
+            allocation: &HashMap<UncheckedUrl, u64>,
+            sent: &[&UncheckedUrl],
+            lost: Option<&UncheckedUrl>,
+            lightning: Arc<FakeLightning>,
+        ) -> MppMelted {
+            let invoice = Bolt11Invoice::from_str(INVOICE).unwrap();
+            check_mpp_allocation(&invoice, allocation).unwrap();
+
+            let mut shards = Vec::new();
+            let mut requests = HashMap::new();
+            for mint_url in sent {
+                let amount_msat = allocation[*mint_url];
+                let mint = mints[*mint_url].lock().await;
+                let quote = mint
+                    .new_melt_quote_bolt11(
+                        &MeltQuoteBolt11Request::new(invoice.clone(), CurrencyUnit::Sat)
+                            .with_mpp(amount_msat),
+                        unix_time() + 60,
+                    )
+                    .await
+                    .unwrap();
+                assert_eq!(quote.amount_msat, Some(amount_msat));
+
+                requests.insert((*mint_url).clone(), melt_request(&mint, &quote).await);
+                shards.push(MppShard::new(
+                    (*mint_url).clone(),
+                    amount_msat,
+                    quote.id.clone(),
+                ));
+            }
+
+            let mints = mints.clone();
+            let lost = lost.cloned();
+            melt_shards(shards, move |shard| {
+                let mint = Arc::clone(&mints[&shard.mint_url]);
+                let request = requests[&shard.mint_url].clone();
+                let lightning = Arc::clone(&lightning);
+                let lose_response = lost.as_ref() == Some(&shard.mint_url);
+
+                async move {
+                    let melted = melt_at_mint(mint, request, lightning).await?;
+                    if lose_response {
+                        return Err(Error::Custom("Connection reset".to_string()));
+                    }
+
+                    Ok(melted)
+                }
+            })
+            .await
+        }
+
+        fn shard<'a>(melted: &'a MppMelted, mint_url: &UncheckedUrl) -> &'a MppShard {
+            melted
+                .shards
+                .iter()
+                .find(|shard| shard.mint_url.eq(mint_url))
+                .unwrap()
+        }
+
+        #[test]
+        fn test_mpp_allocation_must_pay_invoice() {
+            let invoice = Bolt11Invoice::from_str(INVOICE).unwrap();
+            let mint_a = UncheckedUrl::from_str("https://a.example.com").unwrap();
+            let mint_b = UncheckedUrl::from_str("https://b.example.com").unwrap();
+
+            let allocation =
+                HashMap::from([(mint_a.clone(), 100_000_000), (mint_b.clone(), 150_000_000)]);
+            assert!(check_mpp_allocation(&invoice, &allocation).is_ok());
+
+            for allocation in [
+                HashMap::from([(mint_a.clone(), 100_000_000)]),
+                HashMap::from([(mint_a.clone(), 100_000_000), (mint_b.clone(), 150_000_001)]),
+                HashMap::from([(mint_a.clone(), 250_000_000), (mint_b.clone(), 0)]),
+            ] {
+                assert!(matches!(
+                    check_mpp_allocation(&invoice, &allocation),
+                    Err(Error::MppAllocationMismatch)
+                ));
+            }
+        }
+
+        #[tokio::test]
+        async fn test_mint_without_mpp_refuses_partial_quote() {
+            let mint = create_mint(false).await;
+            let mint = mint.lock().await;
+            let invoice = Bolt11Invoice::from_str(INVOICE).unwrap();
+
+            assert!(!mint
+                .mint_info()
+                .await
+                .unwrap()
+                .nuts
+                .nut15
+                .supports(&PaymentMethod::Bolt11, &CurrencyUnit::Sat));
+
+            let request =
+                MeltQuoteBolt11Request::new(invoice, CurrencyUnit::Sat).with_mpp(100_000_000);
+            assert!(matches!(
+                mint.new_melt_quote_bolt11(&request, unix_time() + 60).await,
+                Err(crate::mint::Error::Cashu(
+                    crate::error::Error::MppUnsupported
+                ))
+            ));
+        }
+
+        #[tokio::test(flavor = "multi_thread")]
+        async fn test_melt_shards_reports_each_part() {
+            let mint_a = UncheckedUrl::from_str("https://a.example.com").unwrap();
+            let mint_b = UncheckedUrl::from_str("https://b.example.com").unwrap();
+            let mints = HashMap::from([
+                (mint_a.clone(), create_mint(true).await),
+                (mint_b.clone(), create_mint(true).await),
+            ]);
+            let allocation =
+                HashMap::from([(mint_a.clone(), 100_000_000), (mint_b.clone(), 150_000_000)]);
+            let timeout = Duration::from_secs(5);
+
+            // Both parts arrive, the payee settles and both mints paid
+            let melted = melt_parts(
+                &mints,
+                &allocation,
+                &[&mint_a, &mint_b],
+                None,
+                FakeLightning::new(2, timeout),
+            )
+            .await;
+            assert!(melted.is_paid());
+            assert_eq!(melted.preimage(), Some("0".repeat(64).as_str()));
+            assert_eq!(melted.unpaid().count(), 0);
+
+            // Mint b paid its part but its response never reached the wallet
+            let melted = melt_parts(
+                &mints,
+                &allocation,
+                &[&mint_a, &mint_b],
+                Some(&mint_b),
+                FakeLightning::new(2, timeout),
+            )
+            .await;
+            assert!(!melted.is_paid());
+            assert!(matches!(
+                shard(&melted, &mint_a).status,
+                MppShardStatus::Paid(_)
+            ));
+            let failed = shard(&melted, &mint_b);
+            assert!(matches!(failed.status, MppShardStatus::Failed(_)));
+            let unpaid: Vec<&MppShard> = melted.unpaid().collect();
+            assert_eq!(unpaid, vec![failed]);
+            assert_eq!(failed.amount_msat, 150_000_000);
+
+            // The mint knows the part was paid
+            let quote = mints[&mint_b]
+                .lock()
+                .await
+                .check_melt_quote(&failed.quote_id)
+                .await
+                .unwrap();
+            assert!(quote.paid);
+
+            // Only the part of mint a is sent, the payee never settles
+            let melted = melt_parts(
+                &mints,
+                &allocation,
+                &[&mint_a],
+                None,
+                FakeLightning::new(2, Duration::from_millis(100)),
+            )
+            .await;
+            let unpaid = shard(&melted, &mint_a);
+            assert_eq!(unpaid.status, MppShardStatus::Unpaid);
+            let quote = mints[&mint_a]
+                .lock()
+                .await
+                .check_melt_quote(&unpaid.quote_id)
+                .await
+                .unwrap();
+            assert!(!quote.paid);
+        }
+    }
+
+    #[cfg(feature = "ws")]
+    #[tokio::test]
+    async fn test_mint_quote_paid_stream() {
+        let mint = crate::test_utils::ws_mint::WsMint::start().await;
+        let mint_url = UncheckedUrl::from(mint.url().to_string());
+        let wallet = Wallet::default();
+
+        let mut stream = wallet
+            .mint_quote_paid_stream(mint_url, "quote")
+            .await
+            .unwrap();
+        // Only the paid state is waited for
+        mint.notify_mint_quote("quote", false);
+        mint.notify_mint_quote("quote", true);
+        let quote = tokio::time::timeout(Duration::from_secs(5), stream.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(quote.quote, "quote");
+        assert!(quote.paid);
+
+        stream.unsubscribe().await.unwrap();
+        assert_eq!(mint.subscriptions(), 0);
+    }
 }
 
 /*
@@ -0,0 +1,345 @@
+//! JavaScript interface through wasm-bindgen
+//!
+//! Token handling, blinding and DLEQ verification for browser wallets, so
+//! the crypto runs in this crate rather than in a JS reimplementation.
+//!
+//! Points and keys cross the interface as hex strings, messages to blind as
+//! `Uint8Array`, amounts as `bigint` and protocol messages as plain objects
+//! of their JSON form. Errors are thrown as `Error`s whose `code` is one of
+//! `InvalidToken`, `InvalidArgument`, `Dleq` and `Crypto`.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::nuts::{BlindSignature, Keys, Proof, PublicKey, SecretKey, Token};
+use crate::secret::Secret;
+use crate::{dhke, Amount};
+
+const INVALID_TOKEN: &str = "InvalidToken";
+const INVALID_ARGUMENT: &str = "InvalidArgument";
+const DLEQ: &str = "Dleq";
+const CRYPTO: &str = "Crypto";
+
+/// `Error` with the `code` of its kind
+fn js_error(code: &str, err: impl fmt::Display) -> JsValue {
+    let error = js_sys::Error::new(&err.to_string());
+    // Setting a property of a fresh object cannot fail
+    let _ = js_sys::Reflect::set(&error, &JsValue::from_str("code"), &JsValue::from_str(code));
+    error.into()
+}
+
+fn invalid_argument(err: impl fmt::Display) -> JsValue {
+    js_error(INVALID_ARGUMENT, err)
+}
+
+fn crypto(err: impl fmt::Display) -> JsValue {
+    js_error(CRYPTO, err)
+}
+
+/// Value of the plain object of its JSON form
+fn from_js<T: DeserializeOwned>(value: JsValue) -> Result<T, JsValue> {
+    let json: serde_json::Value =
+        serde_wasm_bindgen::from_value(value).map_err(invalid_argument)?;
+    serde_json::from_value(json).map_err(invalid_argument)
+}
+
+/// Plain object of the JSON form of `value`
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsValue> {
+    let json = serde_json::to_value(value).map_err(invalid_argument)?;
+    json.serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(invalid_argument)
+}
+
+fn public_key(hex: &str) -> Result<PublicKey, JsValue> {
+    PublicKey::from_hex(hex).map_err(invalid_argument)
+}
+
+fn secret_key(hex: &str) -> Result<SecretKey, JsValue> {
+    SecretKey::from_hex(hex).map_err(invalid_argument)
+}
+
+/// Token of any encoding the crate reads
+#[wasm_bindgen(js_name = decodeToken)]
+pub fn decode_token(token: &str) -> Result<JsValue, JsValue> {
+    let token = Token::from_str(token).map_err(|err| js_error(INVALID_TOKEN, err))?;
+    to_js(&token)
+}
+
+#[wasm_bindgen(js_name = encodeToken)]
+pub fn encode_token(token: JsValue) -> Result<String, JsValue> {
+    let token: Token = from_js(token)?;
+    token
+        .validate()
+        .map_err(|err| js_error(INVALID_TOKEN, err))?;
+    Ok(token.to_string())
+}
+
+/// Random secret for an output
+#[wasm_bindgen(js_name = generateSecret)]
+pub fn generate_secret() -> String {
+    Secret::generate().to_string()
+}
+
+/// Random blinding factor `r`
+#[wasm_bindgen(js_name = generateBlindingFactor)]
+pub fn generate_blinding_factor() -> String {
+    SecretKey::generate().to_secret_hex()
+}
+
+/// Powers of two `amount` is split into, largest first
+#[wasm_bindgen(js_name = splitAmount)]
+pub fn split_amount(amount: u64) -> Vec<u64> {
+    Amount::from(amount)
+        .split()
+        .into_iter()
+        .map(u64::from)
+        .collect()
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Blinded {
+    /// `B_`
+    blinded_message: PublicKey,
+    /// `r`
+    blinding_factor: String,
+}
+
+/// Blind `secret` with `blindingFactor`, or a random one
+///
+/// Returns `{ blindedMessage, blindingFactor }`.
+#[wasm_bindgen(js_name = blindMessage)]
+pub fn blind_message(
+    secret: &[u8],
+    #[wasm_bindgen(js_name = blindingFactor)] blinding_factor: Option<String>,
+) -> Result<JsValue, JsValue> {
+    let blinding_factor = blinding_factor.as_deref().map(secret_key).transpose()?;
+    let (blinded_message, r) = dhke::blind_message(secret, blinding_factor).map_err(crypto)?;
+    to_js(&Blinded {
+        blinded_message,
+        blinding_factor: r.to_secret_hex(),
+    })
+}
+
+/// `C` of the blind signature `C_` of the mint key `mintPubkey`
+#[wasm_bindgen(js_name = unblindSignature)]
+pub fn unblind_signature(
+    #[wasm_bindgen(js_name = blindedSignature)] blinded_signature: &str,
+    #[wasm_bindgen(js_name = blindingFactor)] blinding_factor: &str,
+    #[wasm_bindgen(js_name = mintPubkey)] mint_pubkey: &str,
+) -> Result<String, JsValue> {
+    let c = dhke::unblind_message(
+        &public_key(blinded_signature)?,
+        &secret_key(blinding_factor)?,
+        &public_key(mint_pubkey)?,
+    )
+    .map_err(crypto)?;
+    Ok(c.to_hex())
+}
+
+/// Proofs of the blind signatures of outputs with `blindingFactors` and
+/// `secrets`, in their order, by the keys of a keyset
+///
+/// The DLEQ proof of each signature is kept for [verify_proof_dleq].
+#[wasm_bindgen(js_name = constructProofs)]
+pub fn construct_proofs(
+    signatures: JsValue,
+    #[wasm_bindgen(js_name = blindingFactors)] blinding_factors: Vec<String>,
+    secrets: Vec<String>,
+    keys: JsValue,
+) -> Result<JsValue, JsValue> {
+    let signatures: Vec<BlindSignature> = from_js(signatures)?;
+    let keys: Keys = from_js(keys)?;
+    let rs = blinding_factors
+        .iter()
+        .map(|r| secret_key(r))
+        .collect::<Result<Vec<_>, _>>()?;
+    let secrets = secrets
+        .iter()
+        .map(|secret| Secret::from_str(secret).map_err(invalid_argument))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let proofs = dhke::construct_proofs(signatures, rs, secrets, &keys).map_err(crypto)?;
+    to_js(&proofs)
+}
+
+/// Throws unless the DLEQ proof of `proof` verifies against `mintPubkey`
+#[wasm_bindgen(js_name = verifyProofDleq)]
+pub fn verify_proof_dleq(
+    proof: JsValue,
+    #[wasm_bindgen(js_name = mintPubkey)] mint_pubkey: &str,
+) -> Result<(), JsValue> {
+    let proof: Proof = from_js(proof)?;
+    proof
+        .verify_dleq(public_key(mint_pubkey)?)
+        .map_err(|err| js_error(DLEQ, err))
+}
+
+/// Throws unless the DLEQ proof of the blind signature of `blindedMessage`
+/// verifies against `mintPubkey`
+#[wasm_bindgen(js_name = verifyBlindSignatureDleq)]
+pub fn verify_blind_signature_dleq(
+    signature: JsValue,
+    #[wasm_bindgen(js_name = mintPubkey)] mint_pubkey: &str,
+    #[wasm_bindgen(js_name = blindedMessage)] blinded_message: &str,
+) -> Result<(), JsValue> {
+    let signature: BlindSignature = from_js(signature)?;
+    signature
+        .verify_dleq(public_key(mint_pubkey)?, public_key(blinded_message)?)
+        .map_err(|err| js_error(DLEQ, err))
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use wasm_bindgen_test::*;
+
+    use super::*;
+    use crate::nuts::Id;
+    use crate::util::hex;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    /// Mint key of the NUT-00 test vectors
+    const MINT_KEY: &str = "0000000000000000000000000000000000000000000000000000000000000001";
+    const KEYSET_ID: &str = "00882760bfa2eb41";
+
+    fn code(err: JsValue) -> String {
+        js_sys::Reflect::get(&err, &JsValue::from_str("code"))
+            .unwrap()
+            .as_string()
+            .unwrap()
+    }
+
+    fn blinded(value: JsValue) -> Blinded {
+        serde_wasm_bindgen::from_value(value).unwrap()
+    }
+
+    #[wasm_bindgen_test]
+    fn test_blind_message_vector() {
+        let message =
+            hex::decode("d341ee4871f1f889041e63cf0d3823c713eea6aff01e80f1719f08f9e5be98f6")
+                .unwrap();
+        let r = "99fce58439fc37412ab3468b73db0569322588f62fb3a49182d67e23d877824a";
+
+        let output = blinded(blind_message(&message, Some(r.to_string())).unwrap());
+        assert_eq!(
+            output.blinded_message.to_hex(),
+            "033b1a9737a40cc3fd9b6af4b723632b76a67a36782596304612a6c2bfb5197e6d"
+        );
+        assert_eq!(output.blinding_factor, r);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_blind_sign_unblind_verify() {
+        let mint_key = SecretKey::from_hex(MINT_KEY).unwrap();
+        let mint_pubkey = mint_key.public_key().to_hex();
+        let keyset_id = Id::from_str(KEYSET_ID).unwrap();
+
+        let amounts = split_amount(5);
+        assert_eq!(amounts, vec![4, 1]);
+        let secrets: Vec<String> = amounts.iter().map(|_| generate_secret()).collect();
+        let outputs: Vec<Blinded> = secrets
+            .iter()
+            .map(|secret| blinded(blind_message(secret.as_bytes(), None).unwrap()))
+            .collect();
+
+        // Signed by the mint with DLEQ proofs
+        let signatures: Vec<BlindSignature> = amounts
+            .iter()
+            .zip(&outputs)
+            .map(|(amount, output)| {
+                let c = dhke::sign_message(&mint_key, &output.blinded_message).unwrap();
+                BlindSignature::new(
+                    Amount::from(*amount),
+                    c,
+                    keyset_id,
+                    &output.blinded_message,
+                    &mint_key,
+                )
+                .unwrap()
+            })
+            .collect();
+        for (signature, output) in signatures.iter().zip(&outputs) {
+            verify_blind_signature_dleq(
+                to_js(signature).unwrap(),
+                &mint_pubkey,
+                &output.blinded_message.to_hex(),
+            )
+            .unwrap();
+        }
+
+        // Unblinded one at a time and all at once
+        let c = unblind_signature(
+            &signatures[0].c.to_hex(),
+            &outputs[0].blinding_factor,
+            &mint_pubkey,
+        )
+        .unwrap();
+        dhke::verify_message(
+            &mint_key,
+            PublicKey::from_hex(&c).unwrap(),
+            secrets[0].as_bytes(),
+        )
+        .unwrap();
+
+        let keys: serde_json::Value = amounts
+            .iter()
+            .map(|amount| (amount.to_string(), serde_json::json!(mint_pubkey)))
+            .collect::<serde_json::Map<_, _>>()
+            .into();
+        let proofs = construct_proofs(
+            to_js(&signatures).unwrap(),
+            outputs.iter().map(|o| o.blinding_factor.clone()).collect(),
+            secrets.clone(),
+            to_js(&keys).unwrap(),
+        )
+        .unwrap();
+        let proofs: Vec<Proof> = from_js(proofs).unwrap();
+        assert_eq!(proofs[0].c.to_hex(), c);
+        for proof in &proofs {
+            dhke::verify_message(&mint_key, proof.c, proof.secret.as_bytes()).unwrap();
+            verify_proof_dleq(to_js(proof).unwrap(), &mint_pubkey).unwrap();
+        }
+
+        // Another mint key does not verify
+        let other = SecretKey::generate().public_key().to_hex();
+        let err = verify_proof_dleq(to_js(&proofs[0]).unwrap(), &other).unwrap_err();
+        assert_eq!(code(err), DLEQ);
+
+        // Through a token and back
+        let token = Token::new(
+            "https://mint.example.com".into(),
+            proofs.clone(),
+            None,
+            None,
+        )
+        .unwrap();
+        let decoded: Token = from_js(decode_token(&token.to_string()).unwrap()).unwrap();
+        assert_eq!(decoded, token);
+        assert_eq!(
+            encode_token(to_js(&decoded).unwrap()).unwrap(),
+            token.to_string()
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn test_error_codes() {
+        assert_eq!(code(decode_token("cashuAnope").unwrap_err()), INVALID_TOKEN);
+        assert_eq!(
+            code(blind_message(b"secret", Some("zz".to_string())).unwrap_err()),
+            INVALID_ARGUMENT
+        );
+        assert_eq!(
+            code(unblind_signature("02", MINT_KEY, MINT_KEY).unwrap_err()),
+            INVALID_ARGUMENT
+        );
+        assert_eq!(
+            code(encode_token(JsValue::from_str("token")).unwrap_err()),
+            INVALID_ARGUMENT
+        );
+    }
+}
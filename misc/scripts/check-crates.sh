@@ -28,6 +28,10 @@ buildargs=(
     "-p cdk --no-default-features --features wallet"
     "-p cdk --no-default-features --features mint"
     "-p cdk --no-default-features --features nut13"
+    "-p cdk --features invariant-checks"
+    "-p cdk --features router"
+    "-p cdk --no-default-features --features uniffi"
+    "-p cdk --no-default-features --features wasm"
 )
 
 for arg in "${buildargs[@]}"; do